@@ -40,7 +40,7 @@ aws ecr describe-repositories --repository-names "${ENVIRONMENT}-video-streaming
 
 # Build and push backend
 echo "Building backend container..."
-docker build --platform linux/amd64 -t rust-backend:latest ./rust-backend/
+docker build --platform linux/amd64 -f ./rust-backend/Dockerfile -t rust-backend:latest .
 docker tag rust-backend:latest $ECR_REGISTRY/${ENVIRONMENT}-video-streaming-backend:latest
 echo "Pushing backend container..."
 docker push $ECR_REGISTRY/${ENVIRONMENT}-video-streaming-backend:latest
@@ -54,7 +54,7 @@ docker push $ECR_REGISTRY/${ENVIRONMENT}-video-streaming-frontend:latest
 
 # Build and push scraper
 echo "Building scraper container..."
-docker build --platform linux/amd64 -t youtube-scraper:latest ./youtube-scraper/
+docker build --platform linux/amd64 -f ./youtube-scraper/Dockerfile -t youtube-scraper:latest .
 docker tag youtube-scraper:latest $ECR_REGISTRY/${ENVIRONMENT}-video-streaming-scraper:latest
 echo "Pushing scraper container..."
 docker push $ECR_REGISTRY/${ENVIRONMENT}-video-streaming-scraper:latest
@@ -7,6 +7,7 @@ import Login from './components/Login';
 import Register from './components/Register';
 import Home from './components/Home';
 import VideoPlayer from './components/VideoPlayer';
+import EmbedPlayer from './components/EmbedPlayer';
 import UserList from './components/UserList';
 import TagVideos from './components/TagVideos';
 import Categories from './components/Categories';
@@ -28,6 +29,10 @@ function App() {
               path="/video/:id" 
               element={<VideoPlayer />} 
             />
+            <Route 
+              path="/embed/:id" 
+              element={<EmbedPlayer />} 
+            />
             <Route 
               path="/users" 
               element={<UserList />} 
@@ -1,7 +1,7 @@
 import React, { useState, useEffect } from 'react';
 import { useNavigate, useParams } from 'react-router-dom';
 import Navbar from './Navbar';
-import { buildApiUrl, API_CONFIG } from '../config';
+import { buildApiUrl, resolveApiUrl, API_CONFIG } from '../config';
 import { useSearchFocus } from '../contexts/SearchFocusContext';
 
 interface Category {
@@ -179,7 +179,7 @@ const Categories: React.FC = () => {
             {videos.map((video) => (
               <div key={video.id} className="video-card-themed rounded-lg shadow-md overflow-hidden">
                 <img 
-                  src={video.thumbnail_url ? buildApiUrl(API_CONFIG.ENDPOINTS.THUMBNAILS, video.thumbnail_url.split('/').pop() || '') : ''}
+                  src={video.thumbnail_url ? resolveApiUrl(video.thumbnail_url) : ''}
                   alt={video.title} 
                   className="w-full h-48 object-cover" 
                 />
@@ -1,4 +1,4 @@
-import React, { useState, useEffect } from 'react';
+import React, { useState, useEffect, useRef } from 'react';
 import EmojiPicker from './EmojiPicker';
 import {
   Box,
@@ -29,14 +29,26 @@ interface CommentSectionProps {
   currentTime: number;
 }
 
+// Jumps in playback time larger than this are treated as seeks
+const SEEK_THRESHOLD_SECONDS = 5;
+
+// Add comments that aren't already known; the replay and live comments can overlap
+const mergeComments = (existing: Comment[], incoming: Comment[]): Comment[] => {
+  const known = new Set(existing.map(comment => comment.id));
+  return [...existing, ...incoming.filter(comment => !known.has(comment.id))];
+};
+
 const CommentSection: React.FC<CommentSectionProps> = ({ videoId, currentTime }) => {
   const [comments, setComments] = useState<Comment[]>([]);
   const [visibleComments, setVisibleComments] = useState<Comment[]>([]);
   const [newComment, setNewComment] = useState('');
   const [showEmojiPicker, setShowEmojiPicker] = useState(false);
+  const websocketRef = useRef<WebSocket | null>(null);
+  const currentTimeRef = useRef(currentTime);
+  currentTimeRef.current = currentTime;
 
   useEffect(() => {
-    // Fetch comments from backend
+    // Only needed for the Server-Sent Events fallback; WebSocket clients get a replay on connect
     const fetchComments = async () => {
       try {
         const response = await fetch(buildApiUrl(API_CONFIG.ENDPOINTS.COMMENTS, videoId.toString()), {
@@ -54,19 +66,28 @@ const CommentSection: React.FC<CommentSectionProps> = ({ videoId, currentTime })
       }
     };
 
-    fetchComments();
-
-    // Setup WebSocket for real-time comments
-    const websocket = new WebSocket(buildWebSocketUrl(API_CONFIG.ENDPOINTS.WS_COMMENTS, videoId.toString()));
+    // Setup WebSocket for real-time comments, starting with those around the playback position
+    let eventSource: EventSource | null = null;
+    let websocketOpened = false;
+    const position = Math.floor(currentTimeRef.current);
+    const websocket = new WebSocket(`${buildWebSocketUrl(API_CONFIG.ENDPOINTS.WS_COMMENTS, videoId.toString())}?position=${position}`);
+    websocketRef.current = websocket;
+    setComments([]);
+    setVisibleComments([]);
     websocket.onopen = () => {
+      websocketOpened = true;
       console.log('WebSocket connected');
     };
     websocket.onmessage = (event) => {
       try {
         if (event.data) {
           const message = JSON.parse(event.data);
-          if (message.type === 'newComment') {
-            setComments(prev => [...prev, message.comment]);
+          if (message.type === 'replay') {
+            setComments(prev => mergeComments(prev, message.comments));
+          } else if (message.type === 'newComment') {
+            setComments(prev => mergeComments(prev, [message.comment]));
+          } else if (message.id !== undefined) {
+            setComments(prev => mergeComments(prev, [message]));
           }
         }
       } catch (error) {
@@ -75,16 +96,42 @@ const CommentSection: React.FC<CommentSectionProps> = ({ videoId, currentTime })
     };
     websocket.onerror = (error) => {
       console.error('WebSocket error:', error);
+      // Some proxies block WebSockets outright; fall back to Server-Sent Events
+      if (!websocketOpened && !eventSource) {
+        fetchComments();
+        eventSource = new EventSource(buildApiUrl(API_CONFIG.ENDPOINTS.SSE_COMMENTS, videoId.toString()));
+        eventSource.addEventListener('comment', (event) => {
+          try {
+            const comment = JSON.parse((event as MessageEvent).data);
+            setComments(prev => mergeComments(prev, [comment]));
+          } catch (error) {
+            console.error('Error parsing comment event:', error);
+          }
+        });
+      }
     };
     websocket.onclose = () => {
       console.log('WebSocket closed');
     };
 
     return () => {
+      websocketRef.current = null;
       websocket.close();
+      eventSource?.close();
     };
   }, [videoId]);
 
+  // After a seek, ask for the comments around the new position
+  const lastTimeRef = useRef(currentTime);
+  useEffect(() => {
+    const jumped = Math.abs(currentTime - lastTimeRef.current) > SEEK_THRESHOLD_SECONDS;
+    lastTimeRef.current = currentTime;
+    const websocket = websocketRef.current;
+    if (jumped && websocket?.readyState === WebSocket.OPEN) {
+      websocket.send(JSON.stringify({ type: 'seek', position: Math.floor(currentTime) }));
+    }
+  }, [currentTime]);
+
   useEffect(() => {
     // Reset visible comments if currentTime changes significantly (e.g., on seek)
     setVisibleComments(prev => {
@@ -126,7 +173,7 @@ const CommentSection: React.FC<CommentSectionProps> = ({ videoId, currentTime })
 
         if (response.ok) {
           const newCommentData = await response.json();
-          setComments([...comments, newCommentData]);
+          setComments(prev => mergeComments(prev, [newCommentData]));
           setNewComment('');
         } else if (response.status === 401) {
           alert('You must be logged in to post a comment. Please log in and try again.');
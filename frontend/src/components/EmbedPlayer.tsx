@@ -0,0 +1,75 @@
+import React, { useEffect, useState } from 'react';
+import { useParams } from 'react-router-dom';
+import { Box, Link, Typography } from '@mui/material';
+import { API_CONFIG, buildApiUrl, resolveApiUrl } from '../config';
+
+interface EmbeddedVideo {
+  id: number;
+  title: string;
+  thumbnail_url?: string;
+  allow_embed: boolean;
+}
+
+// Bare player for the iframes handed out by /api/oembed
+const EmbedPlayer: React.FC = () => {
+  const { id } = useParams<{ id: string }>();
+  const [video, setVideo] = useState<EmbeddedVideo | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    const fetchVideo = async () => {
+      try {
+        const response = await fetch(buildApiUrl(API_CONFIG.ENDPOINTS.VIDEO_BY_ID, id!));
+        if (!response.ok) {
+          setError('This video is unavailable');
+          return;
+        }
+        setVideo(await response.json());
+      } catch (err) {
+        console.error('Error fetching video:', err);
+        setError('This video is unavailable');
+      }
+    };
+    fetchVideo();
+  }, [id]);
+
+  const watchUrl = `${window.location.origin}/video/${id}`;
+  const framedElsewhere = window.self !== window.top;
+
+  if (error || (video && !video.allow_embed && framedElsewhere)) {
+    return (
+      <Box sx={{ height: '100vh', display: 'flex', alignItems: 'center', justifyContent: 'center', backgroundColor: '#000' }}>
+        <Typography color="white">
+          {error || 'Embedding is disabled for this video.'}{' '}
+          <Link href={watchUrl} target="_blank" rel="noopener">Watch it on the site</Link>
+        </Typography>
+      </Box>
+    );
+  }
+
+  return (
+    <Box sx={{ height: '100vh', position: 'relative', backgroundColor: '#000' }}>
+      {video && (
+        <>
+          <video
+            src={buildApiUrl(API_CONFIG.ENDPOINTS.VIDEO_STREAM, id!, 'stream')}
+            poster={video.thumbnail_url ? resolveApiUrl(video.thumbnail_url) : undefined}
+            controls
+            style={{ width: '100%', height: '100%', display: 'block' }}
+          />
+          <Link
+            href={watchUrl}
+            target="_blank"
+            rel="noopener"
+            underline="hover"
+            sx={{ position: 'absolute', top: 8, left: 12, color: 'white', textShadow: '0 0 4px #000' }}
+          >
+            {video.title}
+          </Link>
+        </>
+      )}
+    </Box>
+  );
+};
+
+export default EmbedPlayer;
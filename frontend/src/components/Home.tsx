@@ -21,7 +21,7 @@ import {
 } from '@mui/icons-material';
 import Navbar from './Navbar';
 import CategorySidebar from './CategorySidebar';
-import { buildApiUrl, API_CONFIG } from '../config';
+import { buildApiUrl, resolveApiUrl, API_CONFIG } from '../config';
 import { useSearchFocus } from '../contexts/SearchFocusContext';
 
 const Home: React.FC = () => {
@@ -265,7 +265,7 @@ const Home: React.FC = () => {
                       justifyContent: 'center',
                     }}>
                       <img
-                        src={video.thumbnail_url ? buildApiUrl(API_CONFIG.ENDPOINTS.THUMBNAILS, video.thumbnail_url.split('/').pop()) : ''}
+                        src={video.thumbnail_url ? resolveApiUrl(video.thumbnail_url) : ''}
                         alt={video.title}
                         style={{
                           width: '100%',
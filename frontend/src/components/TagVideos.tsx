@@ -1,7 +1,7 @@
 import React, { useState, useEffect } from 'react';
 import { useNavigate, useParams } from 'react-router-dom';
 import Navbar from './Navbar';
-import { buildApiUrl, API_CONFIG } from '../config';
+import { buildApiUrl, resolveApiUrl, API_CONFIG } from '../config';
 
 interface Video {
   id: string;
@@ -41,7 +41,7 @@ const TagVideos = () => {
           {videos.map((video: Video) => (
             <div key={video.id} className="bg-white rounded-lg shadow-md overflow-hidden">
               <img 
-                src={video.thumbnail_url ? buildApiUrl(API_CONFIG.ENDPOINTS.THUMBNAILS, video.thumbnail_url.split('/').pop() || '') : ''}
+                src={video.thumbnail_url ? resolveApiUrl(video.thumbnail_url) : ''}
                 alt={video.title} 
                 className="w-full h-48 object-cover" 
               />
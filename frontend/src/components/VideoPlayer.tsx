@@ -1,5 +1,5 @@
 import React, { useState, useRef, useEffect } from 'react';
-import { useParams, useNavigate } from 'react-router-dom';
+import { useParams, useNavigate, useSearchParams } from 'react-router-dom';
 import {
   Container,
   Box,
@@ -32,19 +32,30 @@ import {
   PlaylistPlay as PlaylistPlayIcon,
   Shuffle as ShuffleIcon,
   Repeat as RepeatIcon,
+  Mic as MicIcon,
+  MicOff as MicOffIcon,
 } from '@mui/icons-material';
 import CommentSection from './CommentSection';
+import { ReactionOverlay, ReactionBar, FloatingReaction, REACTION_DURATION_MS } from './WatchPartyReactions';
+import { useWatchPartyVoice } from '../hooks/useWatchPartyVoice';
 import Navbar from './Navbar';
 import { buildApiUrl, buildWebSocketUrl, API_CONFIG } from '../config';
 
 const VideoPlayer: React.FC = () => {
   const { id } = useParams<{ id: string }>();
   const navigate = useNavigate();
+  const [searchParams] = useSearchParams();
+  const inviteToken = searchParams.get('invite');
   const [video, setVideo] = useState<any>(null);
   const [videoUrl, setVideoUrl] = useState<string>('');
   const [isWatchParty, setIsWatchParty] = useState(false);
   const [ws, setWs] = useState<WebSocket | null>(null);
+  const [reactions, setReactions] = useState<FloatingReaction[]>([]);
   const [currentUserId, setCurrentUserId] = useState<number | null>(null);
+  const { voiceEnabled, voicePeers, toggleVoice, handleSignal } = useWatchPartyVoice(ws, currentUserId);
+  // The socket's message handler is set up once per connection, so it calls the latest handler through a ref
+  const handleSignalRef = useRef(handleSignal);
+  handleSignalRef.current = handleSignal;
   const [isFullWidth, setIsFullWidth] = useState(window.innerWidth < 1024); // Default to full width on mobile
   
   // Video player state
@@ -158,6 +169,23 @@ const VideoPlayer: React.FC = () => {
     }
   };
 
+  const showReaction = (emoji: string) => {
+    const reaction = { id: `${Date.now()}_${Math.random()}`, emoji, left: 10 + Math.random() * 80 };
+    setReactions(prev => [...prev, reaction]);
+    setTimeout(() => setReactions(prev => prev.filter(r => r.id !== reaction.id)), REACTION_DURATION_MS);
+  };
+
+  const sendReaction = (emoji: string) => {
+    if (isWatchParty && ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify({
+        type: 'reaction',
+        emoji,
+        time: videoRef.current?.currentTime
+      }));
+      showReaction(emoji);
+    }
+  };
+
   const handlePause = () => {
     if (videoRef.current) {
       videoRef.current.pause();
@@ -391,14 +419,18 @@ const VideoPlayer: React.FC = () => {
             alert('Failed to join watch party. Please ensure you are logged in.');
             return;
           }
-          const response = await fetch(buildApiUrl(API_CONFIG.ENDPOINTS.WATCHPARTY_JOIN, id!, 'join'), {
+          const joinUrl = buildApiUrl(API_CONFIG.ENDPOINTS.WATCHPARTY_JOIN, id!, 'join');
+          const response = await fetch(inviteToken ? `${joinUrl}?invite=${encodeURIComponent(inviteToken)}` : joinUrl, {
             method: 'POST',
             headers: {
               'Content-Type': 'application/json',
               'Authorization': `Bearer ${token}`
             },
           });
-          if (!response.ok) {
+          if (response.status === 403) {
+            setIsWatchParty(false);
+            alert('This watch party is invite-only. Ask the host for an invite link.');
+          } else if (!response.ok) {
             console.error('Failed to join watch party:', await response.text());
             setIsWatchParty(false);
             alert('Failed to join watch party. Please ensure you are logged in.');
@@ -412,7 +444,13 @@ const VideoPlayer: React.FC = () => {
       joinWatchParty();
 
       const token = localStorage.getItem('token');
-      const websocket = new WebSocket(buildWebSocketUrl(API_CONFIG.ENDPOINTS.WS_WATCHPARTY, id!));
+      // Invite-only rooms are checked before the socket upgrade, so credentials go in the query string
+      const wsParams = new URLSearchParams();
+      if (token) wsParams.set('token', token);
+      if (inviteToken) wsParams.set('invite', inviteToken);
+      const wsQuery = wsParams.toString();
+      const wsUrl = buildWebSocketUrl(API_CONFIG.ENDPOINTS.WS_WATCHPARTY, id!);
+      const websocket = new WebSocket(wsQuery ? `${wsUrl}?${wsQuery}` : wsUrl);
       
       websocket.onopen = () => {
         console.log('Watch Party WebSocket connected');
@@ -428,6 +466,21 @@ const VideoPlayer: React.FC = () => {
         try {
           if (event.data) {
             const message = JSON.parse(event.data);
+            if (message.type_field === 'watchPartyReaction') {
+              // Our own reactions are shown as soon as they are sent
+              if (message.user_id !== currentUserId) {
+                showReaction(message.emoji);
+              }
+              return;
+            }
+            if (message.type_field === 'watchPartySignal') {
+              handleSignalRef.current(message);
+              return;
+            }
+            if (message.type_field === 'watchPartyError') {
+              console.warn('Watch party:', message.error);
+              return;
+            }
             if (message.type_field === 'watchPartyControl') {
               if (message.source_id && currentUserId) {
                 const sourceIdParts = message.source_id.split('_');
@@ -461,7 +514,7 @@ const VideoPlayer: React.FC = () => {
       setWs(websocket);
       return () => websocket.close();
     }
-  }, [isWatchParty, id, currentUserId]);
+  }, [isWatchParty, id, currentUserId, inviteToken]);
 
   return (
     <Box sx={{ minHeight: '100vh', backgroundColor: 'background.default' }}>
@@ -510,6 +563,8 @@ const VideoPlayer: React.FC = () => {
                   onClick={() => isPlaying ? handlePause() : handlePlay()}
                 />
 
+                {isWatchParty && <ReactionOverlay reactions={reactions} />}
+
                 {/* Hidden Preview Video */}
                 <video
                   ref={previewVideoRef}
@@ -730,6 +785,17 @@ const VideoPlayer: React.FC = () => {
                 </Box>
               </Box>
 
+              {isWatchParty && (
+                <Stack direction="row" alignItems="center" justifyContent="center" spacing={1}>
+                  <ReactionBar onReact={sendReaction} />
+                  <Tooltip title={voiceEnabled ? `Leave voice chat (${voicePeers.length} connected)` : 'Join voice chat'}>
+                    <IconButton onClick={toggleVoice} color={voiceEnabled ? 'success' : 'default'} sx={{ mt: 1 }}>
+                      {voiceEnabled ? <MicIcon /> : <MicOffIcon />}
+                    </IconButton>
+                  </Tooltip>
+                </Stack>
+              )}
+
               {/* Video Info */}
               <Box sx={{ p: { xs: 2, sm: 3 } }}>
                 <Typography 
@@ -0,0 +1,77 @@
+import React from 'react';
+import { Box, Paper, IconButton, keyframes } from '@mui/material';
+
+export interface FloatingReaction {
+  id: string;
+  emoji: string;
+  // Horizontal position as a percentage of the player width
+  left: number;
+}
+
+// How long a reaction floats before it is removed
+export const REACTION_DURATION_MS = 3000;
+
+export const QUICK_REACTIONS = ['👍', '😂', '😮', '😢', '🔥', '🎉'];
+
+const floatUp = keyframes`
+  0% {
+    opacity: 0;
+    transform: translateY(0) scale(0.6);
+  }
+  15% {
+    opacity: 1;
+    transform: translateY(-20%) scale(1.1);
+  }
+  100% {
+    opacity: 0;
+    transform: translateY(-300%) scale(1);
+  }
+`;
+
+// Floating emoji drawn over the video; clicks pass through to the player
+export const ReactionOverlay: React.FC<{ reactions: FloatingReaction[] }> = ({ reactions }) => (
+  <Box
+    sx={{
+      position: 'absolute',
+      inset: 0,
+      overflow: 'hidden',
+      pointerEvents: 'none',
+      zIndex: 2,
+    }}
+  >
+    {reactions.map((reaction) => (
+      <Box
+        key={reaction.id}
+        sx={{
+          position: 'absolute',
+          bottom: '15%',
+          left: `${reaction.left}%`,
+          fontSize: '2rem',
+          animation: `${floatUp} ${REACTION_DURATION_MS}ms ease-out forwards`,
+        }}
+      >
+        {reaction.emoji}
+      </Box>
+    ))}
+  </Box>
+);
+
+export const ReactionBar: React.FC<{ onReact: (emoji: string) => void }> = ({ onReact }) => (
+  <Paper
+    elevation={0}
+    sx={{
+      display: 'flex',
+      gap: 0.5,
+      p: 0.5,
+      mt: 1,
+      justifyContent: 'center',
+      backgroundColor: 'background.paper',
+    }}
+  >
+    {QUICK_REACTIONS.map((emoji) => (
+      <IconButton key={emoji} size="small" onClick={() => onReact(emoji)} sx={{ fontSize: '1.4rem' }}>
+        {emoji}
+      </IconButton>
+    ))}
+  </Paper>
+);
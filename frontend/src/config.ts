@@ -59,8 +59,13 @@ export const API_CONFIG = {
     WS_WATCHPARTY: '/api/ws/watchparty',
     WS_COMMENTS: '/api/ws/comments',
     
+    // Server-Sent Events fallbacks for clients whose proxies break WebSockets
+    SSE_COMMENTS: '/api/sse/comments',
+    SSE_NOTIFICATIONS: '/api/sse/notifications',
+    
     // Watchparty endpoints
-    WATCHPARTY_JOIN: '/api/watchparty'
+    WATCHPARTY_JOIN: '/api/watchparty',
+    WATCHPARTY_RTC_CONFIG: '/api/watchparty/rtc-config'
   }
 };
 
@@ -73,6 +78,11 @@ export const buildApiUrl = (endpoint: string, ...params: string[]): string => {
   return url;
 };
 
+// URLs the API hands out (e.g. signed thumbnail URLs) are relative to the API unless absolute
+export const resolveApiUrl = (url: string): string => {
+  return /^https?:\/\//.test(url) ? url : `${API_CONFIG.API_BASE_URL}${url}`;
+};
+
 export const buildWebSocketUrl = (endpoint: string, ...params: string[]): string => {
   let url = `${API_CONFIG.WS_BASE_URL}${endpoint}`;
   if (params.length > 0) {
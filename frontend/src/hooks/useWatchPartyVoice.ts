@@ -0,0 +1,130 @@
+import { useState, useRef, useCallback, useEffect } from 'react';
+import { buildApiUrl, API_CONFIG } from '../config';
+
+// Voice chat for a watch party: a full mesh of WebRTC audio connections, signaled over the
+// watch party WebSocket. When someone joins voice they announce it, and everyone already
+// in voice sends them an offer.
+export const useWatchPartyVoice = (ws: WebSocket | null, currentUserId: number | null) => {
+  const [voiceEnabled, setVoiceEnabled] = useState(false);
+  const [voicePeers, setVoicePeers] = useState<number[]>([]);
+  const localStreamRef = useRef<MediaStream | null>(null);
+  const iceServersRef = useRef<RTCIceServer[]>([]);
+  const peersRef = useRef<Map<number, RTCPeerConnection>>(new Map());
+  const audioRef = useRef<Map<number, HTMLAudioElement>>(new Map());
+
+  const sendSignal = useCallback((signal: string, to?: number, payload?: unknown) => {
+    if (ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify({ type: 'signal', signal, to, payload }));
+    }
+  }, [ws]);
+
+  const closePeer = useCallback((peerId: number) => {
+    peersRef.current.get(peerId)?.close();
+    peersRef.current.delete(peerId);
+    const audio = audioRef.current.get(peerId);
+    if (audio) {
+      audio.srcObject = null;
+      audioRef.current.delete(peerId);
+    }
+    setVoicePeers(Array.from(peersRef.current.keys()));
+  }, []);
+
+  const createPeer = useCallback((peerId: number) => {
+    closePeer(peerId);
+    const pc = new RTCPeerConnection({ iceServers: iceServersRef.current });
+    localStreamRef.current?.getTracks().forEach(track => pc.addTrack(track, localStreamRef.current!));
+
+    pc.onicecandidate = (event) => {
+      if (event.candidate) {
+        sendSignal('ice', peerId, event.candidate.toJSON());
+      }
+    };
+    pc.ontrack = (event) => {
+      const audio = audioRef.current.get(peerId) ?? new Audio();
+      audio.autoplay = true;
+      audio.srcObject = event.streams[0];
+      audioRef.current.set(peerId, audio);
+    };
+    pc.onconnectionstatechange = () => {
+      if (pc.connectionState === 'failed' || pc.connectionState === 'closed') {
+        closePeer(peerId);
+      }
+    };
+
+    peersRef.current.set(peerId, pc);
+    setVoicePeers(Array.from(peersRef.current.keys()));
+    return pc;
+  }, [closePeer, sendSignal]);
+
+  // Called for every watchPartySignal message received on the watch party socket
+  const handleSignal = useCallback(async (message: any) => {
+    const peerId: number = message.user_id;
+    if (!localStreamRef.current || peerId === currentUserId) {
+      return;
+    }
+
+    try {
+      if (message.action === 'join') {
+        const pc = createPeer(peerId);
+        const offer = await pc.createOffer();
+        await pc.setLocalDescription(offer);
+        sendSignal('offer', peerId, offer);
+      } else if (message.action === 'offer') {
+        const pc = createPeer(peerId);
+        await pc.setRemoteDescription(message.payload);
+        const answer = await pc.createAnswer();
+        await pc.setLocalDescription(answer);
+        sendSignal('answer', peerId, answer);
+      } else if (message.action === 'answer') {
+        await peersRef.current.get(peerId)?.setRemoteDescription(message.payload);
+      } else if (message.action === 'ice') {
+        await peersRef.current.get(peerId)?.addIceCandidate(message.payload);
+      } else if (message.action === 'leave') {
+        closePeer(peerId);
+      }
+    } catch (error) {
+      console.error('Voice chat signaling error:', error);
+    }
+  }, [currentUserId, createPeer, closePeer, sendSignal]);
+
+  const stopVoice = useCallback(() => {
+    if (localStreamRef.current) {
+      sendSignal('leave');
+    }
+    Array.from(peersRef.current.keys()).forEach(closePeer);
+    localStreamRef.current?.getTracks().forEach(track => track.stop());
+    localStreamRef.current = null;
+    setVoiceEnabled(false);
+  }, [closePeer, sendSignal]);
+
+  const startVoice = useCallback(async () => {
+    try {
+      const token = localStorage.getItem('token');
+      const response = await fetch(buildApiUrl(API_CONFIG.ENDPOINTS.WATCHPARTY_RTC_CONFIG), {
+        headers: { 'Authorization': `Bearer ${token}` },
+      });
+      if (response.ok) {
+        const config = await response.json();
+        iceServersRef.current = config.iceServers;
+      }
+
+      localStreamRef.current = await navigator.mediaDevices.getUserMedia({ audio: true });
+      setVoiceEnabled(true);
+      sendSignal('join');
+    } catch (error) {
+      console.error('Failed to start voice chat:', error);
+      alert('Could not start voice chat. Please allow microphone access.');
+    }
+  }, [sendSignal]);
+
+  const toggleVoice = () => (voiceEnabled ? stopVoice() : startVoice());
+
+  // Leave voice when the socket goes away (watch party ended or page closed)
+  useEffect(() => {
+    if (!ws) {
+      stopVoice();
+    }
+  }, [ws, stopVoice]);
+
+  return { voiceEnabled, voicePeers, toggleVoice, handleSignal };
+};
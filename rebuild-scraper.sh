@@ -36,15 +36,12 @@ aws ecr get-login-password --region us-west-2 | docker login --username AWS --pa
 
 # Build the scraper image
 echo -e "${YELLOW}Building scraper image for AMD64 platform...${NC}"
-cd youtube-scraper
-docker build --platform linux/amd64 -t "${ECR_REGISTRY}/prod-video-streaming-scraper:latest" .
+docker build --platform linux/amd64 -f youtube-scraper/Dockerfile -t "${ECR_REGISTRY}/prod-video-streaming-scraper:latest" .
 
 # Push the image
 echo -e "${YELLOW}Pushing scraper image to ECR...${NC}"
 docker push "${ECR_REGISTRY}/prod-video-streaming-scraper:latest"
 
-cd ..
-
 echo -e "${GREEN}✅ Scraper image rebuilt and pushed successfully!${NC}"
 echo ""
 echo -e "${BLUE}🎬 You can now use the scraper with cookies:${NC}"
@@ -0,0 +1,8 @@
+-- Drop notifications table
+DROP TABLE IF EXISTS notifications;
+
+-- Drop saved searches table
+DROP TABLE IF EXISTS saved_searches;
+
+-- Drop search history table
+DROP TABLE IF EXISTS search_history;
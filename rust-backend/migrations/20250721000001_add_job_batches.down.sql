@@ -0,0 +1,5 @@
+-- Drop index
+DROP INDEX IF EXISTS jobs_batch_id_idx;
+
+-- Remove batch_id column from jobs table
+ALTER TABLE jobs DROP COLUMN IF EXISTS batch_id;
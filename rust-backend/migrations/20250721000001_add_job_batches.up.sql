@@ -0,0 +1,5 @@
+-- Group scrape jobs submitted together through the batch endpoint
+ALTER TABLE jobs ADD COLUMN batch_id TEXT;
+
+-- Create index on batch_id for aggregate progress lookups
+CREATE INDEX IF NOT EXISTS jobs_batch_id_idx ON jobs (batch_id);
@@ -0,0 +1,5 @@
+-- Drop schedule video tracking table
+DROP TABLE IF EXISTS scrape_schedule_videos;
+
+-- Drop scrape schedules table
+DROP TABLE IF EXISTS scrape_schedules;
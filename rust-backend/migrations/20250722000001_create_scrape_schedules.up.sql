@@ -0,0 +1,26 @@
+-- Create scrape schedules table (recurring channel/playlist scrapes run by the scraper)
+CREATE TABLE IF NOT EXISTS scrape_schedules (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    source_url TEXT NOT NULL,
+    interval_minutes INTEGER NOT NULL CHECK (interval_minutes > 0),
+    tags TEXT[] DEFAULT ARRAY[]::TEXT[],
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    next_run_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create index on next_run_at for finding due schedules
+CREATE INDEX IF NOT EXISTS scrape_schedules_next_run_at_idx ON scrape_schedules (next_run_at) WHERE enabled;
+
+-- Track which YouTube video IDs each schedule has already enqueued
+CREATE TABLE IF NOT EXISTS scrape_schedule_videos (
+    schedule_id INTEGER NOT NULL REFERENCES scrape_schedules(id) ON DELETE CASCADE,
+    youtube_id TEXT NOT NULL,
+    job_id TEXT,
+    first_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (schedule_id, youtube_id)
+);
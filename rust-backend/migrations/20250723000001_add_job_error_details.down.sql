@@ -0,0 +1,2 @@
+-- Remove error_details column from jobs table
+ALTER TABLE jobs DROP COLUMN IF EXISTS error_details;
@@ -0,0 +1,2 @@
+-- Structured scrape failure (kind, exit code, stderr, ...) alongside the plain error message
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS error_details JSONB;
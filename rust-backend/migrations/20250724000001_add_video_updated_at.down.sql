@@ -0,0 +1,7 @@
+-- Drop trigger, function and index
+DROP TRIGGER IF EXISTS videos_set_updated_at ON videos;
+DROP FUNCTION IF EXISTS set_updated_at();
+DROP INDEX IF EXISTS videos_updated_at_idx;
+
+-- Remove updated_at column from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS updated_at;
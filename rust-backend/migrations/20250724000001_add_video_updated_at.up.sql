@@ -0,0 +1,19 @@
+-- Track when each video row last changed so the video list can be served with a collection ETag
+ALTER TABLE videos ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+-- Generic trigger function to bump updated_at on every UPDATE.
+-- clock_timestamp() rather than NOW() so long transactions don't write a time earlier than rows already committed.
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = clock_timestamp();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER videos_set_updated_at
+    BEFORE UPDATE ON videos
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+-- Create index on updated_at for fast MAX(updated_at) lookups
+CREATE INDEX IF NOT EXISTS videos_updated_at_idx ON videos (updated_at);
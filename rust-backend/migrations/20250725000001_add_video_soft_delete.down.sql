@@ -0,0 +1,5 @@
+-- Drop index
+DROP INDEX IF EXISTS videos_deleted_at_idx;
+
+-- Remove deleted_at column from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS deleted_at;
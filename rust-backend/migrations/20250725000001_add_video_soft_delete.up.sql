@@ -0,0 +1,5 @@
+-- Soft delete: videos with deleted_at set are in the uploader's trash and hidden everywhere else
+ALTER TABLE videos ADD COLUMN deleted_at TIMESTAMP;
+
+-- Create index on deleted_at for trash listings and the purge job
+CREATE INDEX IF NOT EXISTS videos_deleted_at_idx ON videos (deleted_at) WHERE deleted_at IS NOT NULL;
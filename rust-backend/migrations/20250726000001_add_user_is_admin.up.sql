@@ -0,0 +1,2 @@
+-- Admins can manage any video, not just their own uploads
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Drop video renditions table
+DROP TABLE IF EXISTS video_renditions;
@@ -0,0 +1,10 @@
+-- Create video renditions table (transcoded copies of a video at fixed heights)
+CREATE TABLE IF NOT EXISTS video_renditions (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    name VARCHAR(32) NOT NULL,
+    height INTEGER NOT NULL,
+    s3_key TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (video_id, name)
+);
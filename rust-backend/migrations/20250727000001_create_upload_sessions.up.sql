@@ -0,0 +1,22 @@
+-- Create upload sessions table (resumable uploads backed by S3 multipart uploads)
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    s3_key TEXT NOT NULL,
+    s3_upload_id TEXT NOT NULL,
+    upload_length BIGINT NOT NULL CHECK (upload_length > 0),
+    upload_offset BIGINT NOT NULL DEFAULT 0,
+    parts JSONB NOT NULL DEFAULT '[]'::jsonb,
+    -- Bytes received since the last S3 part; parts other than the last must be at least 5 MB
+    pending_data BYTEA NOT NULL DEFAULT ''::bytea,
+    title VARCHAR(255) NOT NULL,
+    description TEXT,
+    tags TEXT[] DEFAULT ARRAY[]::TEXT[],
+    category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+    video_id INTEGER REFERENCES videos(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
+
+-- Create index on expires_at for the cleanup job
+CREATE INDEX IF NOT EXISTS upload_sessions_expires_at_idx ON upload_sessions (expires_at);
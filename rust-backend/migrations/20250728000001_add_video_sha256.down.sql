@@ -0,0 +1,5 @@
+-- Drop index
+DROP INDEX IF EXISTS videos_sha256_idx;
+
+-- Remove sha256 column from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS sha256;
@@ -0,0 +1,5 @@
+-- SHA-256 of the stored video file (hex), computed at upload/scrape time
+ALTER TABLE videos ADD COLUMN sha256 TEXT;
+
+-- Create index on sha256 for duplicate detection
+CREATE INDEX IF NOT EXISTS videos_sha256_idx ON videos (sha256) WHERE sha256 IS NOT NULL AND deleted_at IS NULL;
@@ -0,0 +1,5 @@
+-- Drop watch party invites table
+DROP TABLE IF EXISTS watch_party_invites;
+
+-- Drop watch parties table
+DROP TABLE IF EXISTS watch_parties;
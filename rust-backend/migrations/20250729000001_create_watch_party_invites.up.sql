@@ -0,0 +1,21 @@
+-- Create watch parties table (one room per video; the first user to invite becomes the host)
+CREATE TABLE IF NOT EXISTS watch_parties (
+    video_id INTEGER PRIMARY KEY REFERENCES videos(id) ON DELETE CASCADE,
+    host_user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invite_only BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create watch party invites table. Invites with no invited_user_id are shareable links.
+CREATE TABLE IF NOT EXISTS watch_party_invites (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES watch_parties(video_id) ON DELETE CASCADE,
+    token TEXT NOT NULL UNIQUE,
+    invited_user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create index on video_id and invited_user_id for join checks
+CREATE INDEX IF NOT EXISTS watch_party_invites_video_user_idx ON watch_party_invites (video_id, invited_user_id);
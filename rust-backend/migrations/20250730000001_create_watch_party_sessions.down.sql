@@ -0,0 +1,5 @@
+-- Drop watch party participants table
+DROP TABLE IF EXISTS watch_party_participants;
+
+-- Drop watch party sessions table
+DROP TABLE IF EXISTS watch_party_sessions;
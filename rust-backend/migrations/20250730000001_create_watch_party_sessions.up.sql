@@ -0,0 +1,26 @@
+-- Create watch party sessions table. A session runs from the first participant connecting to a
+-- video's watch party until the last one leaves.
+CREATE TABLE IF NOT EXISTS watch_party_sessions (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    host_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    started_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    ended_at TIMESTAMP,
+    peak_participants INTEGER NOT NULL DEFAULT 0
+);
+
+-- Create unique index so each video has at most one session in progress
+CREATE UNIQUE INDEX IF NOT EXISTS watch_party_sessions_open_video_idx ON watch_party_sessions (video_id) WHERE ended_at IS NULL;
+
+-- Create watch party participants table. connections counts the user's open sockets (e.g. several tabs).
+CREATE TABLE IF NOT EXISTS watch_party_participants (
+    session_id INTEGER NOT NULL REFERENCES watch_party_sessions(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    connections INTEGER NOT NULL DEFAULT 0,
+    joined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    left_at TIMESTAMP,
+    PRIMARY KEY (session_id, user_id)
+);
+
+-- Create index on user_id for watch party history
+CREATE INDEX IF NOT EXISTS watch_party_participants_user_idx ON watch_party_participants (user_id);
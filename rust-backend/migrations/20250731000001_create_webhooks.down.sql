@@ -0,0 +1,5 @@
+-- Drop webhook deliveries table
+DROP TABLE IF EXISTS webhook_deliveries;
+
+-- Drop webhooks table
+DROP TABLE IF EXISTS webhooks;
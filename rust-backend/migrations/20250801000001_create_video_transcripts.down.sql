@@ -0,0 +1,2 @@
+-- Drop video transcripts table
+DROP TABLE IF EXISTS video_transcripts;
@@ -0,0 +1,15 @@
+-- Create video transcripts table
+CREATE TABLE IF NOT EXISTS video_transcripts (
+    video_id INTEGER PRIMARY KEY REFERENCES videos(id) ON DELETE CASCADE,
+    backend TEXT NOT NULL,
+    language TEXT,
+    segments JSONB NOT NULL,
+    vtt TEXT NOT NULL,
+    content TEXT NOT NULL,
+    search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create full text index on transcripts for search
+CREATE INDEX IF NOT EXISTS video_transcripts_search_idx ON video_transcripts USING GIN (search_vector);
@@ -0,0 +1,2 @@
+-- Drop video chapters table
+DROP TABLE IF EXISTS video_chapters;
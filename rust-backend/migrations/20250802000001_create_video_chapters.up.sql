@@ -0,0 +1,15 @@
+-- Create video chapters table. Manual chapters are added by the owner; auto chapters are
+-- proposed by scene detection and only shown to viewers once the owner accepts them.
+CREATE TABLE IF NOT EXISTS video_chapters (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    start_time DOUBLE PRECISION NOT NULL,
+    source TEXT NOT NULL DEFAULT 'manual',
+    accepted BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create index on video_id and start_time for listing chapters in order
+CREATE INDEX IF NOT EXISTS video_chapters_video_idx ON video_chapters (video_id, start_time);
@@ -0,0 +1,2 @@
+-- Drop thumbnail candidates table
+DROP TABLE IF EXISTS thumbnail_candidates;
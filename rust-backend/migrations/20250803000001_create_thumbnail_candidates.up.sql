@@ -0,0 +1,12 @@
+-- Create thumbnail candidates table. The video's thumbnail_url points at the selected candidate.
+CREATE TABLE IF NOT EXISTS thumbnail_candidates (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    s3_key TEXT NOT NULL,
+    time_offset DOUBLE PRECISION NOT NULL,
+    score DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create index on video_id for listing a video's candidates
+CREATE INDEX IF NOT EXISTS thumbnail_candidates_video_idx ON thumbnail_candidates (video_id);
@@ -0,0 +1,8 @@
+-- Drop index
+DROP INDEX IF EXISTS videos_moderation_status_idx;
+
+-- Remove moderation columns from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS moderated_by;
+ALTER TABLE videos DROP COLUMN IF EXISTS moderated_at;
+ALTER TABLE videos DROP COLUMN IF EXISTS moderation_reason;
+ALTER TABLE videos DROP COLUMN IF EXISTS moderation_status;
@@ -0,0 +1,9 @@
+-- Moderation: uploads start out pending when a classifier is configured and are hidden from public
+-- listings until approved. Existing videos are treated as approved.
+ALTER TABLE videos ADD COLUMN moderation_status TEXT NOT NULL DEFAULT 'approved';
+ALTER TABLE videos ADD COLUMN moderation_reason TEXT;
+ALTER TABLE videos ADD COLUMN moderated_at TIMESTAMP;
+ALTER TABLE videos ADD COLUMN moderated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Create index on moderation_status for the admin moderation queue
+CREATE INDEX IF NOT EXISTS videos_moderation_status_idx ON videos (moderation_status) WHERE moderation_status <> 'approved';
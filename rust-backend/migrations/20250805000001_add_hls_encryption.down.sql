@@ -0,0 +1,8 @@
+-- Drop HLS segments table
+DROP TABLE IF EXISTS video_hls_segments;
+
+-- Drop HLS keys table
+DROP TABLE IF EXISTS video_hls_keys;
+
+-- Remove hls_encrypted column from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS hls_encrypted;
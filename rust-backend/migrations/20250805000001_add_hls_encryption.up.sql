@@ -0,0 +1,22 @@
+-- Encrypted HLS: videos with hls_encrypted set are only served as AES-128 HLS, with keys
+-- handed out by an authenticated endpoint
+ALTER TABLE videos ADD COLUMN hls_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Create HLS keys table. Keys rotate every few segments, so a video has several.
+CREATE TABLE IF NOT EXISTS video_hls_keys (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    key_index INTEGER NOT NULL,
+    key_bytes BYTEA NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (video_id, key_index)
+);
+
+-- Create HLS segments table
+CREATE TABLE IF NOT EXISTS video_hls_segments (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    segment_index INTEGER NOT NULL,
+    duration DOUBLE PRECISION NOT NULL,
+    s3_key TEXT NOT NULL,
+    key_index INTEGER NOT NULL,
+    PRIMARY KEY (video_id, segment_index)
+);
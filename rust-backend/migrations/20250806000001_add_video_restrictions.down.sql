@@ -0,0 +1,4 @@
+-- Remove playback restriction columns from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS allow_embed;
+ALTER TABLE videos DROP COLUMN IF EXISTS blocked_countries;
+ALTER TABLE videos DROP COLUMN IF EXISTS allowed_countries;
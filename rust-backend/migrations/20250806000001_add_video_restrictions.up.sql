@@ -0,0 +1,5 @@
+-- Playback restrictions set by the uploader: ISO 3166-1 alpha-2 country lists (an empty
+-- allow list means everywhere) and whether other sites may embed the video
+ALTER TABLE videos ADD COLUMN allowed_countries TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE videos ADD COLUMN blocked_countries TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE videos ADD COLUMN allow_embed BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,2 @@
+-- Remove allow_download column from videos table
+ALTER TABLE videos DROP COLUMN IF EXISTS allow_download;
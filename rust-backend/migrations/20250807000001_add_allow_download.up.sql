@@ -0,0 +1,2 @@
+-- Downloads are off unless the uploader allows them
+ALTER TABLE videos ADD COLUMN allow_download BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Drop sitemap documents table
+DROP TABLE IF EXISTS sitemap_documents;
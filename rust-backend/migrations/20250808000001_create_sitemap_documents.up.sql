@@ -0,0 +1,8 @@
+-- Create sitemap documents table: generated SEO documents stored in S3, with the catalogue
+-- watermark (latest videos.updated_at) each was built from
+CREATE TABLE IF NOT EXISTS sitemap_documents (
+    name TEXT PRIMARY KEY,
+    s3_key TEXT NOT NULL,
+    last_modified TIMESTAMP NOT NULL,
+    generated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
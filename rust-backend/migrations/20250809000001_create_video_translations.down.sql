@@ -0,0 +1,2 @@
+-- Drop video translations table
+DROP TABLE IF EXISTS video_translations;
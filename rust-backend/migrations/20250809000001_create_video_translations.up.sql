@@ -0,0 +1,11 @@
+-- Create video translations table: titles and descriptions in other languages, keyed by
+-- lower-cased BCP 47 language tag
+CREATE TABLE IF NOT EXISTS video_translations (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    language TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (video_id, language)
+);
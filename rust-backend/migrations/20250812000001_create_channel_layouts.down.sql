@@ -0,0 +1,5 @@
+-- Drop channel layout tables
+DROP TABLE IF EXISTS channel_section_videos;
+DROP TABLE IF EXISTS channel_sections;
+DROP TABLE IF EXISTS channel_video_positions;
+DROP TABLE IF EXISTS channel_settings;
@@ -0,0 +1,2 @@
+-- Drop video views table
+DROP TABLE IF EXISTS video_views;
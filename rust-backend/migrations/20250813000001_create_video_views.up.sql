@@ -0,0 +1,11 @@
+-- Create video views table recording each counted view, for analytics exports
+CREATE TABLE IF NOT EXISTS video_views (
+    id BIGSERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    country TEXT,
+    viewed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create index for exporting views by date
+CREATE INDEX IF NOT EXISTS video_views_viewed_at_idx ON video_views (viewed_at);
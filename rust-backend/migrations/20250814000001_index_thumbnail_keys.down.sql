@@ -0,0 +1,3 @@
+-- Drop thumbnail key lookup indexes
+DROP INDEX IF EXISTS thumbnail_candidates_s3_key_idx;
+DROP INDEX IF EXISTS videos_thumbnail_url_idx;
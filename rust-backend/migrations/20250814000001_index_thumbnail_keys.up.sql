@@ -0,0 +1,3 @@
+-- get_thumbnail only serves keys a video refers to, looked up by key on every request
+CREATE INDEX IF NOT EXISTS videos_thumbnail_url_idx ON videos (thumbnail_url);
+CREATE INDEX IF NOT EXISTS thumbnail_candidates_s3_key_idx ON thumbnail_candidates (s3_key);
@@ -0,0 +1,2 @@
+-- Drop the settings merge function
+DROP FUNCTION IF EXISTS jsonb_merge_patch(JSONB, JSONB);
@@ -0,0 +1,26 @@
+-- Settings updates are applied as a JSON merge patch (RFC 7386) inside a single UPDATE, so
+-- concurrent updates of different keys don't overwrite each other. Objects merge recursively,
+-- null removes a key and anything else replaces it.
+CREATE OR REPLACE FUNCTION jsonb_merge_patch(target JSONB, patch JSONB) RETURNS JSONB AS $$
+DECLARE
+    merged JSONB;
+    entry RECORD;
+BEGIN
+    IF patch IS NULL OR jsonb_typeof(patch) <> 'object' THEN
+        RETURN patch;
+    END IF;
+    IF target IS NULL OR jsonb_typeof(target) <> 'object' THEN
+        merged := '{}'::jsonb;
+    ELSE
+        merged := target;
+    END IF;
+    FOR entry IN SELECT key, value FROM jsonb_each(patch) LOOP
+        IF jsonb_typeof(entry.value) = 'null' THEN
+            merged := merged - entry.key;
+        ELSE
+            merged := jsonb_set(merged, ARRAY[entry.key], jsonb_merge_patch(merged -> entry.key, entry.value));
+        END IF;
+    END LOOP;
+    RETURN merged;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
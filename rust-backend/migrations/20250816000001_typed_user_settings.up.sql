@@ -0,0 +1,19 @@
+-- Settings now follow the typed UserSettings model. Existing rows keep their theme, limited to
+-- the fields a theme has, and lose anything else; unset settings read as their defaults.
+UPDATE users SET settings = CASE
+    WHEN jsonb_typeof(settings -> 'theme') = 'object' AND jsonb_typeof(settings -> 'theme' -> 'name') = 'string' THEN
+        jsonb_build_object('theme', (
+            SELECT jsonb_object_agg(key, value)
+            FROM jsonb_each(settings -> 'theme')
+            WHERE (key = 'name' AND jsonb_typeof(value) = 'string')
+               OR (key IN ('isCustom', 'isDark') AND jsonb_typeof(value) = 'boolean')
+               OR (key IN ('primary', 'secondary', 'accent', 'background', 'surface', 'text', 'textSecondary', 'plyrColor')
+                   AND jsonb_typeof(value) = 'string')
+        ))
+    ELSE '{}'::jsonb
+END;
+
+ALTER TABLE users ALTER COLUMN settings SET NOT NULL;
+
+-- Updates replace whole top-level settings now, so the merge patch function is no longer used
+DROP FUNCTION IF EXISTS jsonb_merge_patch(JSONB, JSONB);
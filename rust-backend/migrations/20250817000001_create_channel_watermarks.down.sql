@@ -0,0 +1,2 @@
+-- Drop channel watermarks
+DROP TABLE IF EXISTS channel_watermarks;
@@ -0,0 +1,6 @@
+-- Each uploader's watermark image, burned into their renditions when enabled in their settings
+CREATE TABLE IF NOT EXISTS channel_watermarks (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    s3_key TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
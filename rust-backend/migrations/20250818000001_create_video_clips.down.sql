@@ -0,0 +1,2 @@
+-- Drop video clips
+DROP TABLE IF EXISTS video_clips;
@@ -0,0 +1,13 @@
+-- Animated GIF/WebP clips of short video segments. A segment is rendered once per format and
+-- shared by everyone who asks for it.
+CREATE TABLE IF NOT EXISTS video_clips (
+    id SERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    start_ms INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    format TEXT NOT NULL,
+    s3_key TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (video_id, start_ms, duration_ms, format)
+);
@@ -0,0 +1,3 @@
+-- Drop compilation tables
+DROP TABLE IF EXISTS compilation_segments;
+DROP TABLE IF EXISTS compilations;
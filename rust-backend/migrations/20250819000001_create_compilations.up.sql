@@ -0,0 +1,28 @@
+-- Compilations: new videos cut together from segments of a user's existing videos, rendered by
+-- a background worker that reports its progress here
+CREATE TABLE IF NOT EXISTS compilations (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, processing, completed or failed
+    progress INTEGER NOT NULL DEFAULT 0,    -- percent
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    video_id INTEGER REFERENCES videos(id) ON DELETE SET NULL,
+    lease_expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS compilations_user_id_idx ON compilations (user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS compilations_unfinished_idx ON compilations (id) WHERE status IN ('pending', 'processing');
+
+CREATE TABLE IF NOT EXISTS compilation_segments (
+    compilation_id INTEGER NOT NULL REFERENCES compilations(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    source_video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    start_ms INTEGER NOT NULL,
+    end_ms INTEGER NOT NULL,
+    PRIMARY KEY (compilation_id, position)
+);
@@ -0,0 +1,2 @@
+-- Drop video encoding profiles
+DROP TABLE IF EXISTS video_encoding_profiles;
@@ -0,0 +1,11 @@
+-- The rendition ladder chosen for each video from an analysis of its source, so later transcode
+-- jobs know which renditions it is meant to have
+CREATE TABLE IF NOT EXISTS video_encoding_profiles (
+    video_id INTEGER PRIMARY KEY REFERENCES videos(id) ON DELETE CASCADE,
+    complexity TEXT NOT NULL,
+    bitrate_kbps INTEGER,
+    motion DOUBLE PRECISION,
+    heights INTEGER[] NOT NULL,
+    crf INTEGER NOT NULL,
+    analyzed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
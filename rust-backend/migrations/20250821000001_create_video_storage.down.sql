@@ -0,0 +1,3 @@
+-- Drop video storage tiers
+DROP INDEX IF EXISTS video_views_video_id_viewed_at_idx;
+DROP TABLE IF EXISTS video_storage;
@@ -0,0 +1,14 @@
+-- Videos whose original file was moved out of the STANDARD storage class. Videos without a row
+-- are in STANDARD.
+CREATE TABLE IF NOT EXISTS video_storage (
+    video_id INTEGER PRIMARY KEY REFERENCES videos(id) ON DELETE CASCADE,
+    storage_class TEXT NOT NULL,
+    -- Set while the file is being brought back to STANDARD
+    restore_requested_at TIMESTAMP,
+    transitioned_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS video_storage_restore_requested_at_idx ON video_storage (restore_requested_at) WHERE restore_requested_at IS NOT NULL;
+
+-- Finding videos without recent views
+CREATE INDEX IF NOT EXISTS video_views_video_id_viewed_at_idx ON video_views (video_id, viewed_at);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS videos_processing_status_idx;
+DROP TABLE IF EXISTS video_processing_jobs;
+ALTER TABLE videos DROP COLUMN IF EXISTS processing_status;
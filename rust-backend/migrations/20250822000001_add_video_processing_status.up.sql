@@ -0,0 +1,17 @@
+-- Processing status: uploaded (waiting for its jobs), processing, ready or failed. Existing videos
+-- are treated as ready; new ones start out uploaded.
+ALTER TABLE videos ADD COLUMN processing_status TEXT NOT NULL DEFAULT 'ready'
+    CHECK (processing_status IN ('uploaded', 'processing', 'ready', 'failed'));
+ALTER TABLE videos ALTER COLUMN processing_status SET DEFAULT 'uploaded';
+
+-- The latest run of each processing job of a video, which the status is derived from
+CREATE TABLE IF NOT EXISTS video_processing_jobs (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    state TEXT NOT NULL CHECK (state IN ('queued', 'running', 'done', 'failed')),
+    error TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (video_id, kind)
+);
+
+CREATE INDEX IF NOT EXISTS videos_processing_status_idx ON videos (processing_status) WHERE processing_status <> 'ready';
@@ -0,0 +1,17 @@
+-- Every time a worker picked up a media job, for the admin job status API. A job that fails and
+-- is retried gets one row per attempt.
+CREATE TABLE IF NOT EXISTS media_job_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    outcome TEXT NOT NULL DEFAULT 'running' CHECK (outcome IN ('running', 'succeeded', 'failed')),
+    error TEXT,
+    will_retry BOOLEAN NOT NULL DEFAULT FALSE,
+    started_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS media_job_attempts_video_idx ON media_job_attempts (video_id, kind, started_at);
+CREATE INDEX IF NOT EXISTS media_job_attempts_running_idx ON media_job_attempts (started_at) WHERE outcome = 'running';
+CREATE INDEX IF NOT EXISTS media_job_attempts_failed_idx ON media_job_attempts (finished_at) WHERE outcome = 'failed';
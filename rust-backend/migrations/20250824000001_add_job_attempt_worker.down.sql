@@ -0,0 +1 @@
+ALTER TABLE media_job_attempts DROP COLUMN IF EXISTS worker;
@@ -0,0 +1,2 @@
+-- Which worker ran each attempt, now that dedicated worker processes can share the queue
+ALTER TABLE media_job_attempts ADD COLUMN IF NOT EXISTS worker TEXT;
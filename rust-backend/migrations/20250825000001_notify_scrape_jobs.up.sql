@@ -0,0 +1,13 @@
+-- Wake scraper workers as soon as a job is queued, or queued again, instead of on their next poll
+CREATE OR REPLACE FUNCTION notify_scrape_job_queued() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('scrape_jobs', NEW.job_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS jobs_notify_queued ON jobs;
+CREATE TRIGGER jobs_notify_queued
+    AFTER INSERT OR UPDATE OF status ON jobs
+    FOR EACH ROW WHEN (NEW.status = 'queued')
+    EXECUTE FUNCTION notify_scrape_job_queued();
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS scraper_workers;
+DROP INDEX IF EXISTS jobs_processing_heartbeat_idx;
+ALTER TABLE jobs DROP COLUMN IF EXISTS heartbeat_at;
+ALTER TABLE jobs DROP COLUMN IF EXISTS worker_id;
@@ -0,0 +1,13 @@
+-- Which scraper replica is running each job, and when it last showed it was still alive. Jobs
+-- whose worker stops heartbeating are queued again.
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS worker_id TEXT;
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMP WITH TIME ZONE;
+CREATE INDEX IF NOT EXISTS jobs_processing_heartbeat_idx ON jobs (heartbeat_at) WHERE status = 'processing';
+
+-- Every running scraper replica, for GET /api/workers
+CREATE TABLE IF NOT EXISTS scraper_workers (
+    worker_id TEXT PRIMARY KEY,
+    hostname TEXT NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    heartbeat_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS jobs_active_user_idx;
@@ -0,0 +1,3 @@
+-- The scraper counts each user's queued and running jobs whenever they submit more
+CREATE INDEX IF NOT EXISTS jobs_active_user_idx ON jobs (((request->>'user_id')::INT))
+    WHERE status IN ('queued', 'processing');
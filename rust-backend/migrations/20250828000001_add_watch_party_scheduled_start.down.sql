@@ -0,0 +1,6 @@
+-- Drop pending start index
+DROP INDEX IF EXISTS watch_parties_pending_start_idx;
+
+-- Drop scheduling columns from watch parties
+ALTER TABLE watch_parties DROP COLUMN IF EXISTS started_at;
+ALTER TABLE watch_parties DROP COLUMN IF EXISTS scheduled_start;
@@ -0,0 +1,7 @@
+-- Parties can be scheduled: playback stays locked until scheduled_start, and started_at records
+-- that the server issued the initial play so only one replica does
+ALTER TABLE watch_parties ADD COLUMN IF NOT EXISTS scheduled_start TIMESTAMP;
+ALTER TABLE watch_parties ADD COLUMN IF NOT EXISTS started_at TIMESTAMP;
+
+-- Create index for the scheduler's lookup of parties waiting to start
+CREATE INDEX IF NOT EXISTS watch_parties_pending_start_idx ON watch_parties (scheduled_start) WHERE started_at IS NULL;
@@ -0,0 +1,2 @@
+-- Drop watch party polls table
+DROP TABLE IF EXISTS watch_party_polls;
@@ -0,0 +1,17 @@
+-- Create watch party polls table. Open polls are tallied in Redis; the ones the host chose to
+-- keep are saved here with the session they ran in when they close.
+CREATE TABLE IF NOT EXISTS watch_party_polls (
+    id SERIAL PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES watch_party_sessions(id) ON DELETE CASCADE,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    question TEXT NOT NULL,
+    options JSONB NOT NULL,
+    counts JSONB NOT NULL,
+    total_votes INTEGER NOT NULL DEFAULT 0,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    closed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create index on session_id for session history
+CREATE INDEX IF NOT EXISTS watch_party_polls_session_idx ON watch_party_polls (session_id);
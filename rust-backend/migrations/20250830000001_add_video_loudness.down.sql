@@ -0,0 +1,4 @@
+-- Drop loudness columns from videos
+ALTER TABLE videos DROP COLUMN IF EXISTS true_peak_dbtp;
+ALTER TABLE videos DROP COLUMN IF EXISTS loudness_range_lu;
+ALTER TABLE videos DROP COLUMN IF EXISTS loudness_lufs;
@@ -0,0 +1,4 @@
+-- EBU R128 loudness of each video's audio, measured by the analyze_loudness job
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS loudness_lufs DOUBLE PRECISION;
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS loudness_range_lu DOUBLE PRECISION;
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS true_peak_dbtp DOUBLE PRECISION;
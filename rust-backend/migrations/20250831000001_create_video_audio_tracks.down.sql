@@ -0,0 +1,3 @@
+-- Drop HLS audio segments and audio tracks tables
+DROP TABLE IF EXISTS video_hls_audio_segments;
+DROP TABLE IF EXISTS video_audio_tracks;
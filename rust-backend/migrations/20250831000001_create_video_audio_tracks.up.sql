@@ -0,0 +1,25 @@
+-- Create audio tracks table: the audio streams of each video's original upload, e.g. the
+-- original language plus dubs, in the order they appear in the file
+CREATE TABLE IF NOT EXISTS video_audio_tracks (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    track_index INTEGER NOT NULL,
+    language TEXT,
+    label TEXT NOT NULL,
+    codec TEXT,
+    channels INTEGER,
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (video_id, track_index)
+);
+
+-- Create HLS audio segments table. Videos with more than one audio track are packaged as a
+-- video-only playlist plus an audio playlist per track, encrypted with the video's HLS keys.
+CREATE TABLE IF NOT EXISTS video_hls_audio_segments (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    track_index INTEGER NOT NULL,
+    segment_index INTEGER NOT NULL,
+    duration DOUBLE PRECISION NOT NULL,
+    s3_key TEXT NOT NULL,
+    key_index INTEGER NOT NULL,
+    PRIMARY KEY (video_id, track_index, segment_index)
+);
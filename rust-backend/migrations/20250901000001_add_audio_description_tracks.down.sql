@@ -0,0 +1,5 @@
+-- Drop audio description columns from video_audio_tracks
+DELETE FROM video_audio_tracks WHERE s3_key IS NOT NULL;
+ALTER TABLE video_audio_tracks DROP COLUMN IF EXISTS content_type;
+ALTER TABLE video_audio_tracks DROP COLUMN IF EXISTS s3_key;
+ALTER TABLE video_audio_tracks DROP COLUMN IF EXISTS audio_description;
@@ -0,0 +1,5 @@
+-- Audio description: tracks narrating what's on screen for blind and low-vision viewers. They
+-- are either flagged in the upload itself or uploaded on their own, with s3_key set.
+ALTER TABLE video_audio_tracks ADD COLUMN IF NOT EXISTS audio_description BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE video_audio_tracks ADD COLUMN IF NOT EXISTS s3_key TEXT;
+ALTER TABLE video_audio_tracks ADD COLUMN IF NOT EXISTS content_type TEXT;
@@ -0,0 +1,2 @@
+-- Drop spherical video table
+DROP TABLE IF EXISTS video_spherical;
@@ -0,0 +1,9 @@
+-- Create spherical video table: 360° and VR projection of videos that have one, detected from the
+-- upload's metadata or set by the uploader, whose setting detection then leaves alone
+CREATE TABLE IF NOT EXISTS video_spherical (
+    video_id INTEGER PRIMARY KEY REFERENCES videos(id) ON DELETE CASCADE,
+    projection TEXT NOT NULL,
+    stereo_mode TEXT NOT NULL DEFAULT 'mono',
+    source TEXT NOT NULL DEFAULT 'detected',
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
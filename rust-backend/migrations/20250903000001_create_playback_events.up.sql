@@ -0,0 +1,20 @@
+-- Create playback events table: quality-of-experience beacons sent by players, aggregated per
+-- rendition to tune the encoding ladder and purged after TELEMETRY_RETENTION_DAYS
+CREATE TABLE IF NOT EXISTS playback_events (
+    id BIGSERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    session_id TEXT,
+    rendition TEXT,
+    startup_ms INTEGER,
+    rebuffer_count INTEGER,
+    rebuffer_ms INTEGER,
+    bitrate_kbps INTEGER,
+    watched_secs DOUBLE PRECISION,
+    dropped_frames INTEGER,
+    country TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_playback_events_created_at ON playback_events(created_at);
+CREATE INDEX IF NOT EXISTS idx_playback_events_video_id ON playback_events(video_id, created_at);
@@ -0,0 +1,8 @@
+-- Drop listing indexes
+DROP INDEX IF EXISTS comments_video_id_created_at_idx;
+DROP INDEX IF EXISTS comments_video_id_video_time_idx;
+DROP INDEX IF EXISTS videos_tags_idx;
+CREATE INDEX IF NOT EXISTS videos_category_id_idx ON videos (category_id);
+DROP INDEX IF EXISTS videos_category_id_upload_date_idx;
+DROP INDEX IF EXISTS videos_uploaded_by_upload_date_idx;
+DROP INDEX IF EXISTS videos_public_upload_date_idx;
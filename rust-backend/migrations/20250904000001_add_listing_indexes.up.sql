@@ -0,0 +1,23 @@
+-- Indexes for the listings that were scanning whole tables. users.email and users.username are
+-- already unique, and so indexed, since the initial tables.
+
+-- Public listings (home page, search, feeds, sitemaps) only ever show live, approved videos,
+-- newest first
+CREATE INDEX IF NOT EXISTS videos_public_upload_date_idx ON videos (upload_date DESC)
+    WHERE deleted_at IS NULL AND moderation_status = 'approved';
+
+-- A user's own videos and their channel, newest first
+CREATE INDEX IF NOT EXISTS videos_uploaded_by_upload_date_idx ON videos (uploaded_by, upload_date DESC)
+    WHERE deleted_at IS NULL;
+
+-- Category pages and feeds, newest first; replaces the plain category_id index
+CREATE INDEX IF NOT EXISTS videos_category_id_upload_date_idx ON videos (category_id, upload_date DESC)
+    WHERE deleted_at IS NULL;
+DROP INDEX IF EXISTS videos_category_id_idx;
+
+-- Tag listings and advanced search match tags with @> and &&, which GIN indexes serve
+CREATE INDEX IF NOT EXISTS videos_tags_idx ON videos USING GIN (tags);
+
+-- Comments are listed by position in the video, and replayed newest first
+CREATE INDEX IF NOT EXISTS comments_video_id_video_time_idx ON comments (video_id, video_time);
+CREATE INDEX IF NOT EXISTS comments_video_id_created_at_idx ON comments (video_id, created_at DESC, id DESC);
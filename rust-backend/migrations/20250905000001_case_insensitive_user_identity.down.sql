@@ -0,0 +1,3 @@
+-- Drop case-insensitive user uniqueness
+DROP INDEX IF EXISTS users_username_lower_unique_idx;
+DROP INDEX IF EXISTS users_email_lower_unique_idx;
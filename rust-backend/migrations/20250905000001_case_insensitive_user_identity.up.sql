@@ -0,0 +1,18 @@
+-- Usernames and emails are unique regardless of case, and logins match emails that way. Accounts
+-- that already differ only by case have to be merged or renamed first; the migration names them.
+DO $$
+DECLARE
+    clashes TEXT;
+BEGIN
+    SELECT string_agg(clash, ', ') INTO clashes FROM (
+        SELECT 'email ' || LOWER(email) AS clash FROM users GROUP BY LOWER(email) HAVING COUNT(*) > 1
+        UNION ALL
+        SELECT 'username ' || LOWER(username) FROM users GROUP BY LOWER(username) HAVING COUNT(*) > 1
+    ) duplicates;
+    IF clashes IS NOT NULL THEN
+        RAISE EXCEPTION 'Users differing only by case must be resolved first: %', clashes;
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_unique_idx ON users (LOWER(email));
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_lower_unique_idx ON users (LOWER(username));
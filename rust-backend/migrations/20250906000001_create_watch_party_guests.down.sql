@@ -0,0 +1,5 @@
+-- Drop watch party guest participants table
+DROP TABLE IF EXISTS watch_party_guest_participants;
+
+-- Drop watch party guests table
+DROP TABLE IF EXISTS watch_party_guests;
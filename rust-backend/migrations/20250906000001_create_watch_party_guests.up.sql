@@ -0,0 +1,22 @@
+-- Create watch party guests table. Guests join parties under a display name without an account;
+-- claimed_by is set once a guest links their identity to a registered account.
+CREATE TABLE IF NOT EXISTS watch_party_guests (
+    id SERIAL PRIMARY KEY,
+    display_name VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    claimed_by INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    claimed_at TIMESTAMP
+);
+
+-- Create watch party guest participants table, the guests' counterpart of watch_party_participants
+CREATE TABLE IF NOT EXISTS watch_party_guest_participants (
+    session_id INTEGER NOT NULL REFERENCES watch_party_sessions(id) ON DELETE CASCADE,
+    guest_id INTEGER NOT NULL REFERENCES watch_party_guests(id) ON DELETE CASCADE,
+    connections INTEGER NOT NULL DEFAULT 0,
+    joined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    left_at TIMESTAMP,
+    PRIMARY KEY (session_id, guest_id)
+);
+
+-- Create index on guest_id for merging a guest's history into an account
+CREATE INDEX IF NOT EXISTS watch_party_guest_participants_guest_idx ON watch_party_guest_participants (guest_id);
@@ -0,0 +1,2 @@
+-- Drop premiere time from videos
+ALTER TABLE videos DROP COLUMN IF EXISTS premiere_at;
@@ -0,0 +1,2 @@
+-- When a video premieres; only its uploader can play it before then
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS premiere_at TIMESTAMP;
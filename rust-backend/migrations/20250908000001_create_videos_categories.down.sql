@@ -0,0 +1,14 @@
+-- Restore single categories, keeping each video's and upload's primary one
+ALTER TABLE upload_sessions ADD COLUMN IF NOT EXISTS category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL;
+UPDATE upload_sessions SET category_id = category_ids[1] WHERE cardinality(category_ids) > 0;
+ALTER TABLE upload_sessions DROP COLUMN IF EXISTS category_ids;
+
+ALTER TABLE videos ADD COLUMN IF NOT EXISTS category_id INTEGER REFERENCES categories(id);
+UPDATE videos v SET category_id = (
+    SELECT vc.category_id FROM videos_categories vc WHERE vc.video_id = v.id ORDER BY vc.position LIMIT 1
+);
+CREATE INDEX IF NOT EXISTS videos_category_id_upload_date_idx ON videos (category_id, upload_date DESC)
+    WHERE deleted_at IS NULL;
+
+-- Drop videos_categories table
+DROP TABLE IF EXISTS videos_categories;
@@ -0,0 +1,24 @@
+-- Create videos_categories table, replacing videos.category_id so a video can be cross-posted to
+-- several categories. position is the order the uploader gave them in; the first is the primary one.
+CREATE TABLE IF NOT EXISTS videos_categories (
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (video_id, category_id)
+);
+
+-- Create index on category_id for category pages and feeds
+CREATE INDEX IF NOT EXISTS videos_categories_category_idx ON videos_categories (category_id, video_id);
+
+-- Carry over each video's single category
+INSERT INTO videos_categories (video_id, category_id, position)
+SELECT id, category_id, 0 FROM videos WHERE category_id IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+DROP INDEX IF EXISTS videos_category_id_upload_date_idx;
+ALTER TABLE videos DROP COLUMN IF EXISTS category_id;
+
+-- Uploads in progress keep the categories to assign once they complete
+ALTER TABLE upload_sessions ADD COLUMN IF NOT EXISTS category_ids INTEGER[] NOT NULL DEFAULT ARRAY[]::INTEGER[];
+UPDATE upload_sessions SET category_ids = ARRAY[category_id] WHERE category_id IS NOT NULL;
+ALTER TABLE upload_sessions DROP COLUMN IF EXISTS category_id;
@@ -0,0 +1,9 @@
+-- Drop the change log triggers
+DROP TRIGGER IF EXISTS videos_categories_log_change ON videos_categories;
+DROP TRIGGER IF EXISTS videos_log_update ON videos;
+DROP TRIGGER IF EXISTS videos_log_insert_delete ON videos;
+DROP FUNCTION IF EXISTS log_video_category_change();
+DROP FUNCTION IF EXISTS log_video_change();
+
+-- Drop video_changes table
+DROP TABLE IF EXISTS video_changes;
@@ -0,0 +1,54 @@
+-- Create video_changes table, a log of videos being created, updated and deleted that
+-- downstream systems sync from (GET /api/changes). txid is the writing transaction, so readers
+-- can hold back changes until every transaction that could still add an earlier one has ended.
+CREATE TABLE IF NOT EXISTS video_changes (
+    id BIGSERIAL PRIMARY KEY,
+    video_id INTEGER NOT NULL, -- no foreign key, deletions are logged too
+    change VARCHAR(16) NOT NULL CHECK (change IN ('created', 'updated', 'deleted')),
+    txid BIGINT NOT NULL DEFAULT pg_current_xact_id()::TEXT::BIGINT,
+    changed_at TIMESTAMP NOT NULL DEFAULT clock_timestamp()
+);
+
+-- Create index on the feed's order
+CREATE INDEX IF NOT EXISTS video_changes_txid_id_idx ON video_changes (txid, id);
+
+CREATE OR REPLACE FUNCTION log_video_change() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO video_changes (video_id, change) VALUES (NEW.id, 'created');
+    ELSIF TG_OP = 'DELETE' THEN
+        INSERT INTO video_changes (video_id, change) VALUES (OLD.id, 'deleted');
+    ELSIF NEW.deleted_at IS NOT NULL AND OLD.deleted_at IS NULL THEN
+        INSERT INTO video_changes (video_id, change) VALUES (NEW.id, 'deleted');
+    ELSE
+        INSERT INTO video_changes (video_id, change) VALUES (NEW.id, 'updated');
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER videos_log_insert_delete
+    AFTER INSERT OR DELETE ON videos
+    FOR EACH ROW
+    EXECUTE FUNCTION log_video_change();
+
+-- View counts and the updated_at they bump change all the time and aren't worth syncing
+CREATE TRIGGER videos_log_update
+    AFTER UPDATE ON videos
+    FOR EACH ROW
+    WHEN ((to_jsonb(OLD) - 'view_count' - 'updated_at') IS DISTINCT FROM (to_jsonb(NEW) - 'view_count' - 'updated_at'))
+    EXECUTE FUNCTION log_video_change();
+
+-- A video's categories are part of it too
+CREATE OR REPLACE FUNCTION log_video_category_change() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO video_changes (video_id, change)
+    VALUES (CASE WHEN TG_OP = 'DELETE' THEN OLD.video_id ELSE NEW.video_id END, 'updated');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER videos_categories_log_change
+    AFTER INSERT OR UPDATE OR DELETE ON videos_categories
+    FOR EACH ROW
+    EXECUTE FUNCTION log_video_category_change();
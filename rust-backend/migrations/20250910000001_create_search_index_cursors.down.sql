@@ -0,0 +1,2 @@
+-- Drop search_index_cursors table
+DROP TABLE IF EXISTS search_index_cursors;
@@ -0,0 +1,7 @@
+-- Create search_index_cursors table, how far each external search backend has synced the
+-- video change feed. cursor is NULL until the first sync, which starts from the beginning.
+CREATE TABLE IF NOT EXISTS search_index_cursors (
+    backend VARCHAR(32) PRIMARY KEY,
+    cursor TEXT,
+    synced_at TIMESTAMP
+);
@@ -0,0 +1,3 @@
+-- Drop recommendation tables
+DROP TABLE IF EXISTS user_recommendations;
+DROP TABLE IF EXISTS recommendation_exports;
@@ -0,0 +1,23 @@
+-- Create recommendation_exports table, the interaction matrices written to S3 for training
+-- recommendation models
+CREATE TABLE IF NOT EXISTS recommendation_exports (
+    id SERIAL PRIMARY KEY,
+    s3_key TEXT NOT NULL,
+    row_count BIGINT NOT NULL,
+    window_start TIMESTAMP NOT NULL, -- interactions since then are included
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Create user_recommendations table, precomputed lists imported from a model, served by the
+-- home feed in rank order
+CREATE TABLE IF NOT EXISTS user_recommendations (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    video_id INTEGER NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    rank INTEGER NOT NULL,
+    model VARCHAR(100),
+    imported_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, video_id)
+);
+
+-- Create index for reading a user's list in order
+CREATE INDEX IF NOT EXISTS user_recommendations_user_rank_idx ON user_recommendations (user_id, rank);
@@ -0,0 +1,23 @@
+-- Create experiments table: A/B tests ranking code can branch on. Users are split between the
+-- variants in proportion to their weights (variant_weights[i] goes with variants[i]).
+CREATE TABLE IF NOT EXISTS experiments (
+    id SERIAL PRIMARY KEY,
+    key VARCHAR(64) NOT NULL UNIQUE,
+    description TEXT,
+    variants TEXT[] NOT NULL,
+    variant_weights INTEGER[] NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK (cardinality(variants) = cardinality(variant_weights))
+);
+
+-- Create experiment_exposures table, one row per user that saw an experiment's variant
+CREATE TABLE IF NOT EXISTS experiment_exposures (
+    experiment_id INTEGER NOT NULL REFERENCES experiments(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    variant TEXT NOT NULL,
+    exposures INTEGER NOT NULL DEFAULT 1,
+    first_exposed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_exposed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (experiment_id, user_id)
+);
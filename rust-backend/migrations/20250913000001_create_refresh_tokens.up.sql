@@ -0,0 +1,12 @@
+-- Create refresh_tokens table: one-time tokens exchanged for a session's next access token.
+-- Only a hash of each token is kept. Exchanging a token uses it up; presenting a used one again
+-- revokes its session, as it must have been copied.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    token_hash TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES user_sessions(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMP
+);
+
+-- Create index on session_id for finding a session's tokens
+CREATE INDEX IF NOT EXISTS refresh_tokens_session_id_idx ON refresh_tokens (session_id);
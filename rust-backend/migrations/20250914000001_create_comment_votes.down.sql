@@ -0,0 +1,10 @@
+-- Drop the vote counting trigger
+DROP TRIGGER IF EXISTS comment_votes_count ON comment_votes;
+DROP FUNCTION IF EXISTS count_comment_vote();
+
+DROP INDEX IF EXISTS comments_video_id_score_idx;
+ALTER TABLE comments DROP COLUMN IF EXISTS downvotes;
+ALTER TABLE comments DROP COLUMN IF EXISTS upvotes;
+
+-- Drop comment_votes table
+DROP TABLE IF EXISTS comment_votes;
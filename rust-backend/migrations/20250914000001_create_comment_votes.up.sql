@@ -0,0 +1,36 @@
+-- Create comment_votes table: one up (1) or down (-1) vote per user and comment
+CREATE TABLE IF NOT EXISTS comment_votes (
+    comment_id INTEGER NOT NULL REFERENCES comments(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    value SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, user_id)
+);
+
+-- Vote counts are kept on the comment, so top comments can be listed from an index
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS upvotes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS downvotes INTEGER NOT NULL DEFAULT 0;
+
+CREATE OR REPLACE FUNCTION count_comment_vote() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE comments
+        SET upvotes = upvotes - (OLD.value = 1)::INTEGER, downvotes = downvotes - (OLD.value = -1)::INTEGER
+        WHERE id = OLD.comment_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE comments
+        SET upvotes = upvotes + (NEW.value = 1)::INTEGER, downvotes = downvotes + (NEW.value = -1)::INTEGER
+        WHERE id = NEW.comment_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER comment_votes_count
+    AFTER INSERT OR UPDATE OR DELETE ON comment_votes
+    FOR EACH ROW
+    EXECUTE FUNCTION count_comment_vote();
+
+-- Top comments: highest score first, newest first among equals
+CREATE INDEX IF NOT EXISTS comments_video_id_score_idx ON comments (video_id, (upvotes - downvotes) DESC, created_at DESC, id DESC);
@@ -0,0 +1,2 @@
+-- Drop s3_object_events table
+DROP TABLE IF EXISTS s3_object_events;
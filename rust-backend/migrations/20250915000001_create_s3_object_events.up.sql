@@ -0,0 +1,15 @@
+-- Create s3_object_events table: objects S3 reported as created, waiting to become videos.
+-- Events are delivered at least once, so each object is recorded once per bucket and key.
+CREATE TABLE IF NOT EXISTS s3_object_events (
+    bucket TEXT NOT NULL,
+    s3_key TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    event_time TIMESTAMP,
+    received_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    processed_at TIMESTAMP,
+    video_id INTEGER REFERENCES videos(id) ON DELETE SET NULL,
+    PRIMARY KEY (bucket, s3_key)
+);
+
+-- Create index for finding the events still to be processed
+CREATE INDEX IF NOT EXISTS s3_object_events_pending_idx ON s3_object_events (received_at) WHERE processed_at IS NULL;
@@ -0,0 +1,9 @@
+-- Create feature_flags table: switches admins flip at runtime. Flags without a row have their
+-- default, see feature_flags.rs.
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    message TEXT, -- shown to users while a subsystem is off or maintenance is on
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- Drop push_subscriptions table and the pending push flag
+DROP INDEX IF EXISTS notifications_push_pending_idx;
+ALTER TABLE notifications DROP COLUMN IF EXISTS push_pending;
+DROP TABLE IF EXISTS push_subscriptions;
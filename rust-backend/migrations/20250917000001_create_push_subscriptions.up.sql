@@ -0,0 +1,18 @@
+-- Create push_subscriptions table: browsers registered for Web Push, one row per push service
+-- endpoint. p256dh and auth are the browser's keys the payload is encrypted for.
+CREATE TABLE IF NOT EXISTS push_subscriptions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint TEXT NOT NULL UNIQUE,
+    p256dh TEXT NOT NULL,
+    auth TEXT NOT NULL,
+    user_agent TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS push_subscriptions_user_id_idx ON push_subscriptions (user_id);
+
+-- Notifications for users with a push subscription wait here until the dispatcher sends them
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS push_pending BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS notifications_push_pending_idx ON notifications (id) WHERE push_pending;
@@ -0,0 +1,2 @@
+-- Nothing to undo: the previous hosts aren't kept
+SELECT 1;
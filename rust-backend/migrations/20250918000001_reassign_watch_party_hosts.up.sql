@@ -0,0 +1,9 @@
+-- Rooms used to be hosted by whoever opened them first. Hand the ones held by someone other than
+-- the video's uploader or an admin back to the uploader.
+UPDATE watch_parties wp
+SET host_user_id = v.uploaded_by
+FROM videos v
+WHERE v.id = wp.video_id
+  AND v.uploaded_by IS NOT NULL
+  AND wp.host_user_id <> v.uploaded_by
+  AND NOT EXISTS (SELECT 1 FROM users u WHERE u.id = wp.host_user_id AND u.is_admin);
@@ -0,0 +1,153 @@
+use std::env;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, LINK};
+use actix_web::http::Uri;
+use actix_web::middleware::Next;
+use chrono::{NaiveDate, NaiveTime};
+use log::warn;
+
+use crate::field_case;
+
+// Versioned API paths. The routes of handlers::configure_routes_v1 are served under /api/v1,
+// with camelCase JSON responses, and at the unversioned /api paths older clients use, with the
+// responses they always had. Breaking changes go into a new version; the old one keeps working
+// until its sunset.
+//
+// Endpoints on their way out are listed in DEPRECATED_ROUTES, and the unversioned paths as a
+// whole are deprecated by setting LEGACY_API_DEPRECATED (and LEGACY_API_SUNSET) to a date.
+// Their responses then carry Deprecation (RFC 9745), Sunset (RFC 8594) and a Link to the
+// endpoint replacing them.
+
+pub const V1_PREFIX: &str = "/api/v1";
+const UNVERSIONED_PREFIX: &str = "/api";
+
+// Which version of the API a request was made to, kept in the request's extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    Unversioned,
+    V1,
+}
+
+pub struct DeprecatedRoute {
+    pub method: &'static str,
+    pub route: &'static str, // the route's pattern, e.g. "/api/videos/{id}"
+    pub since: &'static str, // YYYY-MM-DD
+    pub sunset: Option<&'static str>,
+    pub successor: Option<&'static str>,
+}
+
+pub const DEPRECATED_ROUTES: &[DeprecatedRoute] = &[];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    pub since: NaiveDate,
+    pub sunset: Option<NaiveDate>,
+    pub successor: Option<String>,
+}
+
+// The unversioned path a /api/v1 path is routed to, or None when it isn't one
+pub fn unversioned_path(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(V1_PREFIX)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None; // e.g. /api/v10
+    }
+    Some(format!("{}{}", UNVERSIONED_PREFIX, rest))
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok()
+}
+
+fn date_from_env(name: &str) -> Option<NaiveDate> {
+    let value = env::var(name).ok()?;
+    let date = parse_date(&value);
+    if date.is_none() {
+        warn!("Ignoring {}: '{}' isn't a date like 2026-01-31", name, value);
+    }
+    date
+}
+
+// The deprecation of an unversioned path, pointing at its /api/v1 successor
+pub fn legacy_deprecation(path: &str, since: Option<NaiveDate>, sunset: Option<NaiveDate>) -> Option<Deprecation> {
+    let since = since?;
+    let rest = path.strip_prefix(UNVERSIONED_PREFIX).filter(|rest| rest.starts_with('/'))?;
+    Some(Deprecation { since, sunset, successor: Some(format!("{}{}", V1_PREFIX, rest)) })
+}
+
+pub fn route_deprecation(method: &str, route: &str, routes: &[DeprecatedRoute]) -> Option<Deprecation> {
+    let deprecated = routes.iter().find(|r| r.method.eq_ignore_ascii_case(method) && r.route == route)?;
+    Some(Deprecation {
+        since: parse_date(deprecated.since)?,
+        sunset: deprecated.sunset.and_then(parse_date),
+        successor: deprecated.successor.map(str::to_string),
+    })
+}
+
+pub fn deprecation_headers(deprecation: &Deprecation) -> Vec<(HeaderName, String)> {
+    let midnight = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_utc();
+    let mut headers = vec![(HeaderName::from_static("deprecation"), format!("@{}", midnight(deprecation.since).timestamp()))];
+    if let Some(sunset) = deprecation.sunset {
+        headers.push((HeaderName::from_static("sunset"), midnight(sunset).format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+    }
+    if let Some(successor) = &deprecation.successor {
+        headers.push((LINK, format!("<{}>; rel=\"successor-version\"", successor)));
+    }
+    headers
+}
+
+// Route the request to the same path as an unversioned one, like NormalizePath does
+fn rewrite_path(req: &mut ServiceRequest, path: &str) {
+    let path_and_query = match req.query_string() {
+        "" => path.to_string(),
+        query => format!("{}?{}", path, query),
+    };
+    let mut parts = req.head().uri.clone().into_parts();
+    let Ok(path_and_query) = path_and_query.parse() else {
+        return;
+    };
+    parts.path_and_query = Some(path_and_query);
+    if let Ok(uri) = Uri::from_parts(parts) {
+        req.match_info_mut().get_mut().update(&uri);
+        req.head_mut().uri = uri;
+    }
+}
+
+// Middleware serving /api/v1 from the unversioned routes and adding deprecation headers. Has to
+// wrap every middleware that looks at the request path.
+pub async fn route_version(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let original_path = req.path().to_string();
+    let version = match unversioned_path(&original_path) {
+        Some(path) => {
+            rewrite_path(&mut req, &path);
+            ApiVersion::V1
+        }
+        None => ApiVersion::Unversioned,
+    };
+    req.extensions_mut().insert(version);
+
+    let mut response = next.call(req).await?.map_into_boxed_body();
+
+    let route = response.request().match_pattern();
+    let deprecation = route
+        .and_then(|route| route_deprecation(response.request().method().as_str(), &route, DEPRECATED_ROUTES))
+        .or_else(|| match version {
+            ApiVersion::Unversioned => legacy_deprecation(&original_path, date_from_env("LEGACY_API_DEPRECATED"), date_from_env("LEGACY_API_SUNSET")),
+            ApiVersion::V1 => None,
+        });
+    if let Some(deprecation) = deprecation {
+        for (name, value) in deprecation_headers(&deprecation) {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                response.headers_mut().append(name, value);
+            }
+        }
+    }
+
+    match version {
+        ApiVersion::V1 => field_case::camelize_response(response).await,
+        ApiVersion::Unversioned => Ok(response),
+    }
+}
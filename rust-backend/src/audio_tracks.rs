@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::error::ApiError;
+use crate::models::VideoAudioTrack;
+
+// The audio streams of each video's upload, e.g. the original language plus dubs, probed when
+// renditions are transcoded or HLS is packaged. Renditions keep every stream, so track_index is
+// also the stream's position in them. In HLS a video with more than one track is served as a
+// master playlist with an alternate audio rendition per track.
+//
+// Owners can also upload tracks of their own, usually audio description. These standalone tracks
+// are numbered from STANDALONE_TRACK_BASE so they never clash with the upload's streams, and are
+// kept when the upload is probed again.
+
+pub const STANDALONE_TRACK_BASE: i32 = 100;
+pub const MAX_STANDALONE_BYTES: usize = 200 * 1024 * 1024;
+const MAX_LABEL_CHARS: usize = 100;
+
+pub(crate) const TRACK_COLUMNS: &str =
+    "video_id, track_index, language, label, codec, channels, is_default, audio_description,
+     s3_key IS NOT NULL AS standalone, s3_key, content_type";
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeAudioStream>,
+}
+
+#[derive(Deserialize)]
+struct ProbeAudioStream {
+    codec_name: Option<String>,
+    channels: Option<i32>,
+    #[serde(default)]
+    disposition: HashMap<String, i32>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+// A language tag fit for an HLS LANGUAGE attribute, lower-cased. "und" (undetermined) and
+// anything that isn't a plain tag count as untagged.
+pub fn normalize_language(language: &str) -> Option<String> {
+    let language = language.trim().to_lowercase();
+    let valid = !language.is_empty()
+        && language.len() <= 35
+        && language.split('-').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    (valid && language != "und").then_some(language)
+}
+
+// Tracks from ffprobe's JSON listing of a file's audio streams. Labels come from the stream's
+// title tag, falling back to its language and then its position. The first stream flagged
+// default is the default track, or the first stream if none is; streams flagged
+// visual_impaired are audio description.
+pub fn parse_probe_output(video_id: i32, json: &str) -> Result<Vec<VideoAudioTrack>, serde_json::Error> {
+    let probe: ProbeOutput = serde_json::from_str(json)?;
+    let default_index = probe.streams
+        .iter()
+        .position(|s| s.disposition.get("default").is_some_and(|d| *d == 1))
+        .unwrap_or(0);
+
+    Ok(probe.streams
+        .into_iter()
+        .enumerate()
+        .map(|(index, stream)| {
+            let language = stream.tags.get("language").and_then(|l| normalize_language(l));
+            let audio_description = stream.disposition.get("visual_impaired").is_some_and(|d| *d == 1);
+            let label = stream.tags.get("title")
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .or_else(|| language.clone())
+                .unwrap_or_else(|| format!("Track {}", index + 1));
+            VideoAudioTrack {
+                video_id,
+                track_index: index as i32,
+                language,
+                label,
+                codec: stream.codec_name,
+                channels: stream.channels.filter(|c| *c > 0),
+                is_default: index == default_index,
+                audio_description,
+                standalone: false,
+                s3_key: None,
+                content_type: None,
+            }
+        })
+        .collect())
+}
+
+// A standalone track's label: the one given, else "Audio description" or the language
+pub fn standalone_label(label: Option<&str>, language: Option<&str>, audio_description: bool) -> Result<String, String> {
+    match label.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(label) if label.chars().count() > MAX_LABEL_CHARS => {
+            Err(format!("Track labels must be at most {} characters", MAX_LABEL_CHARS))
+        }
+        Some(label) => Ok(label.to_string()),
+        None if audio_description => Ok("Audio description".to_string()),
+        None => language.map(str::to_string).ok_or_else(|| "A track needs a label or a language".to_string()),
+    }
+}
+
+// Standalone tracks are audio files; video containers such as MP4 with only audio are fine too
+pub fn is_audio_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    essence.starts_with("audio/") || essence == "video/mp4" || essence == "video/webm"
+}
+
+// A new key per upload, so packaging already running keeps the file it started with
+pub fn standalone_s3_key(video_id: i32) -> String {
+    format!("audio-tracks/{}/{}", video_id, uuid::Uuid::new_v4())
+}
+
+// The request body, refused once it passes MAX_STANDALONE_BYTES
+pub async fn read_upload(mut payload: actix_web::web::Payload) -> Result<Bytes, ApiError> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|_| ApiError::BadRequest("Upload interrupted".to_string()))?;
+        if body.len() + chunk.len() > MAX_STANDALONE_BYTES {
+            return Err(ApiError::BadRequest(format!(
+                "Audio tracks must be at most {} MiB", MAX_STANDALONE_BYTES / (1024 * 1024)
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    if body.is_empty() {
+        return Err(ApiError::BadRequest("The request body should be the audio file".to_string()));
+    }
+    Ok(body.freeze())
+}
+
+pub async fn list(db_pool: &PgPool, video_id: i32) -> Result<Vec<VideoAudioTrack>, sqlx::Error> {
+    sqlx::query_as::<_, VideoAudioTrack>(&format!(
+        "SELECT {} FROM video_audio_tracks WHERE video_id = $1 ORDER BY track_index ASC", TRACK_COLUMNS
+    ))
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn get(db_pool: &PgPool, video_id: i32, track_index: i32) -> Result<Option<VideoAudioTrack>, sqlx::Error> {
+    sqlx::query_as::<_, VideoAudioTrack>(&format!(
+        "SELECT {} FROM video_audio_tracks WHERE video_id = $1 AND track_index = $2", TRACK_COLUMNS
+    ))
+    .bind(video_id)
+    .bind(track_index)
+    .fetch_optional(db_pool)
+    .await
+}
+
+// Replace the tracks probed from the video's upload, leaving standalone ones
+pub async fn replace(db_pool: &PgPool, video_id: i32, tracks: &[VideoAudioTrack]) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("DELETE FROM video_audio_tracks WHERE video_id = $1 AND s3_key IS NULL")
+        .bind(video_id)
+        .execute(&mut tx)
+        .await?;
+    for track in tracks {
+        sqlx::query(
+            "INSERT INTO video_audio_tracks (video_id, track_index, language, label, codec, channels, is_default, audio_description)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(video_id)
+        .bind(track.track_index)
+        .bind(&track.language)
+        .bind(&track.label)
+        .bind(&track.codec)
+        .bind(track.channels)
+        .bind(track.is_default)
+        .bind(track.audio_description)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// Add a standalone track after any the video already has
+pub async fn add_standalone(
+    db_pool: &PgPool,
+    video_id: i32,
+    label: &str,
+    language: Option<&str>,
+    audio_description: bool,
+    s3_key: &str,
+    content_type: &str,
+) -> Result<VideoAudioTrack, sqlx::Error> {
+    sqlx::query_as::<_, VideoAudioTrack>(&format!(
+        "INSERT INTO video_audio_tracks (video_id, track_index, language, label, audio_description, s3_key, content_type)
+         SELECT $1, GREATEST($2, COALESCE(MAX(track_index) + 1, 0)), $3, $4, $5, $6, $7
+         FROM video_audio_tracks WHERE video_id = $1
+         RETURNING {}", TRACK_COLUMNS
+    ))
+    .bind(video_id)
+    .bind(STANDALONE_TRACK_BASE)
+    .bind(language)
+    .bind(label)
+    .bind(audio_description)
+    .bind(s3_key)
+    .bind(content_type)
+    .fetch_one(db_pool)
+    .await
+}
+
+// Remove a standalone track, returning its file's key. Tracks of the upload itself can't be removed.
+pub async fn remove_standalone(db_pool: &PgPool, video_id: i32, track_index: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "DELETE FROM video_audio_tracks WHERE video_id = $1 AND track_index = $2 AND s3_key IS NOT NULL RETURNING s3_key"
+    )
+    .bind(video_id)
+    .bind(track_index)
+    .fetch_optional(db_pool)
+    .await
+}
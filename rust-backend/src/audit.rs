@@ -0,0 +1,26 @@
+use log::info;
+use sqlx::PgPool;
+
+pub const EVENT_LOGIN_LOCKOUT: &str = "login_lockout";
+pub const EVENT_FEATURE_FLAG_CHANGED: &str = "feature_flag_changed";
+
+// Append an entry to the audit log
+pub async fn record(
+    db_pool: &PgPool,
+    event: &str,
+    user_id: Option<i32>,
+    ip_address: Option<&str>,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO audit_log (event, user_id, ip_address, details, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(event)
+        .bind(user_id)
+        .bind(ip_address)
+        .bind(&details)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(db_pool)
+        .await?;
+
+    info!("Audit: {} (user {:?}, ip {:?}) {}", event, user_id, ip_address, details);
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use std::env;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::HttpRequest;
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::error::ApiError;
+use crate::handlers::decode_claims;
+
+pub const SESSION_COOKIE: &str = "session";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+// Routes that start a session rather than act on one, so there is nothing to forge yet, refresh,
+// which only renews the cookies of the browser sending it, and playback beacons, which
+// navigator.sendBeacon can't attach headers to and which change nothing but telemetry
+const CSRF_EXEMPT_PATHS: [&str; 4] = ["/api/auth/login", "/api/auth/register", "/api/auth/refresh", "/api/telemetry/playback"];
+
+// AUTH_MODE=cookie hands browsers the token in an httpOnly cookie instead of the response body.
+// Bearer tokens are accepted in either mode.
+pub fn cookie_mode() -> bool {
+    env::var("AUTH_MODE").is_ok_and(|mode| mode.eq_ignore_ascii_case("cookie"))
+}
+
+// AUTH_COOKIE_SAMESITE: strict, lax (default) or none
+pub fn same_site() -> SameSite {
+    match env::var("AUTH_COOKIE_SAMESITE").map(|v| v.to_ascii_lowercase()).as_deref() {
+        Ok("strict") => SameSite::Strict,
+        Ok("none") => SameSite::None,
+        _ => SameSite::Lax,
+    }
+}
+
+// Browsers drop SameSite=None cookies that aren't Secure, so AUTH_COOKIE_SECURE=false only
+// applies to the other modes (e.g. plain http during development)
+pub fn secure() -> bool {
+    same_site() == SameSite::None || env::var("AUTH_COOKIE_SECURE").map_or(true, |v| v != "false")
+}
+
+fn cookie_base(name: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path("/")
+        .http_only(true)
+        .secure(secure())
+        .same_site(same_site())
+        .finish()
+}
+
+fn expiring_cookie(name: &'static str, value: &str, expires_at: NaiveDateTime) -> Cookie<'static> {
+    let mut cookie = cookie_base(name, value.to_string());
+    let remaining = (expires_at - chrono::Utc::now().naive_utc()).num_seconds().max(0);
+    cookie.set_max_age(time::Duration::seconds(remaining));
+    cookie
+}
+
+// The session token as a cookie that expires with it
+pub fn session_cookie(token: &str, expires_at: NaiveDateTime) -> Cookie<'static> {
+    expiring_cookie(SESSION_COOKIE, token, expires_at)
+}
+
+// The refresh token as a cookie that expires with its session
+pub fn refresh_cookie(token: &str, expires_at: NaiveDateTime) -> Cookie<'static> {
+    expiring_cookie(REFRESH_COOKIE, token, expires_at)
+}
+
+pub fn removal_cookie() -> Cookie<'static> {
+    let mut cookie = cookie_base(SESSION_COOKIE, String::new());
+    cookie.make_removal();
+    cookie
+}
+
+pub fn refresh_removal_cookie() -> Cookie<'static> {
+    let mut cookie = cookie_base(REFRESH_COOKIE, String::new());
+    cookie.make_removal();
+    cookie
+}
+
+// The refresh token from its cookie, only honoured in cookie mode
+pub fn refresh_cookie_token(http_req: &HttpRequest) -> Option<String> {
+    if !cookie_mode() {
+        return None;
+    }
+    http_req.cookie(REFRESH_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+// The token from the session cookie, only honoured in cookie mode
+pub fn session_cookie_token(http_req: &HttpRequest) -> Option<String> {
+    if !cookie_mode() {
+        return None;
+    }
+    http_req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+// CSRF_SECRET, else JWT_SECRET. Without a default, since a known key would let any page forge
+// tokens; the backend refuses to start in cookie mode without one.
+pub fn csrf_secret() -> Result<String, String> {
+    env::var("CSRF_SECRET")
+        .or_else(|_| env::var("JWT_SECRET"))
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| "CSRF_SECRET (or JWT_SECRET) has to be set for AUTH_MODE=cookie".to_string())
+}
+
+fn csrf_mac(session_id: &str) -> Option<Hmac<Sha256>> {
+    let secret = csrf_secret().ok()?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("csrf.{}", session_id).as_bytes());
+    Some(mac)
+}
+
+// Hex HMAC of the session id. Tied to the session, so nothing needs storing and a token
+// stops working when its session is revoked. Empty without a secret, and then never valid.
+pub fn csrf_token(session_id: &str) -> String {
+    csrf_mac(session_id).map(|mac| hex::encode(mac.finalize().into_bytes())).unwrap_or_default()
+}
+
+pub fn verify_csrf_token(session_id: &str, token: &str) -> bool {
+    let Some(mac) = csrf_mac(session_id) else {
+        return false;
+    };
+    hex::decode(token).is_ok_and(|bytes| mac.verify_slice(&bytes).is_ok())
+}
+
+fn has_valid_bearer(http_req: &HttpRequest) -> bool {
+    http_req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .and_then(decode_claims)
+        .is_some()
+}
+
+// Whether a request has to carry a CSRF token: state-changing requests authenticated by the
+// session cookie. Browsers never attach an Authorization header on their own, so requests
+// with a valid bearer token can't be forged cross-site.
+pub fn requires_csrf(http_req: &HttpRequest) -> bool {
+    let safe_method = matches!(*http_req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    !safe_method
+        && !CSRF_EXEMPT_PATHS.contains(&http_req.path())
+        && session_cookie_token(http_req).is_some()
+        && !has_valid_bearer(http_req)
+}
+
+// Middleware rejecting cookie-authenticated state changes without a matching X-CSRF-Token
+pub async fn check_csrf(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if requires_csrf(req.request()) {
+        let session_id = session_cookie_token(req.request())
+            .and_then(|token| decode_claims(&token))
+            .and_then(|claims| claims.sid);
+        let header = req.headers().get(CSRF_HEADER).and_then(|h| h.to_str().ok());
+        let valid = match (session_id, header) {
+            (Some(session_id), Some(token)) => verify_csrf_token(&session_id, token),
+            _ => false,
+        };
+        if !valid {
+            return Ok(req.error_response(ApiError::Forbidden("Missing or invalid CSRF token".to_string())));
+        }
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
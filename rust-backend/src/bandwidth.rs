@@ -0,0 +1,142 @@
+use std::env;
+use actix_web::HttpRequest;
+use chrono::{NaiveDate, NaiveDateTime};
+use log::{error, warn};
+use redis::AsyncCommands;
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::sessions;
+
+// Optional daily allowances of video bytes served to each signed-in user
+// (BANDWIDTH_DAILY_CAP_BYTES) and each anonymous IP (BANDWIDTH_DAILY_CAP_BYTES_ANONYMOUS), to keep
+// a public deployment's egress bill in check. Usage is counted in Redis under
+// bandwidth:{date}:{subject} and resets at midnight UTC. Without Redis nothing is counted.
+//
+// A response that starts under the cap is served whole, so usage can run past it by one
+// response. Clients over the cap get a 429 until the reset; with
+// BANDWIDTH_CAP_RESPONSE=payment_required signed-in users get a 402 instead, for deployments
+// selling larger allowances.
+
+// Counters outlive their day so a request just before midnight still finds its own
+const COUNTER_TTL_SECS: usize = 2 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    User,
+    Ip,
+}
+
+fn cap_from_env(name: &str) -> Option<u64> {
+    env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).filter(|cap| *cap > 0)
+}
+
+// The daily cap for a scope, None when it isn't capped
+pub fn daily_cap(scope: Scope) -> Option<u64> {
+    match scope {
+        Scope::User => cap_from_env("BANDWIDTH_DAILY_CAP_BYTES"),
+        Scope::Ip => cap_from_env("BANDWIDTH_DAILY_CAP_BYTES_ANONYMOUS"),
+    }
+}
+
+fn payment_required() -> bool {
+    env::var("BANDWIDTH_CAP_RESPONSE").is_ok_and(|v| v.eq_ignore_ascii_case("payment_required"))
+}
+
+// Whom a request's bytes count against: the signed-in user, else the client's IP
+pub fn subject(user_id: Option<i32>, ip: Option<&str>) -> Option<(Scope, String)> {
+    match (user_id, ip) {
+        (Some(user_id), _) => Some((Scope::User, format!("user:{}", user_id))),
+        (None, Some(ip)) => Some((Scope::Ip, format!("ip:{}", ip))),
+        (None, None) => None,
+    }
+}
+
+// The subject of a request. The IP is the peer's, or a trusted proxy's client, so rotating
+// X-Forwarded-For doesn't get a fresh allowance.
+pub fn request_subject(http_req: &HttpRequest, user_id: Option<i32>) -> Option<(Scope, String)> {
+    subject(user_id, sessions::client_ip(http_req).as_deref())
+}
+
+pub fn counter_key(date: NaiveDate, subject: &str) -> String {
+    format!("bandwidth:{}:{}", date.format("%Y-%m-%d"), subject)
+}
+
+// Seconds from `now` until the counters reset at the next midnight UTC
+pub fn secs_until_reset(now: NaiveDateTime) -> u64 {
+    let midnight = (now.date() + chrono::Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or(now);
+    (midnight - now).num_seconds().max(1) as u64
+}
+
+pub fn cap_exceeded(scope: Scope, cap: u64, retry_after_secs: u64, payment_required: bool) -> ApiError {
+    let message = format!("Daily streaming allowance of {} MB used up, it resets at midnight UTC", cap / 1_000_000);
+    if scope == Scope::User && payment_required {
+        ApiError::PaymentRequired(message)
+    } else {
+        ApiError::TooManyRequests { message, retry_after_secs }
+    }
+}
+
+// Counts the bytes of one response against its client's allowance
+pub struct Meter {
+    counter: Option<(redis::Client, String)>,
+}
+
+impl Meter {
+    pub async fn record(&self, bytes: u64) {
+        let Some((redis_client, key)) = &self.counter else {
+            return;
+        };
+        if bytes == 0 {
+            return;
+        }
+        let result: redis::RedisResult<()> = circuit_breaker::redis(redis_client, |mut conn| async move {
+            redis::pipe()
+                .incr(key, bytes)
+                .ignore()
+                .expire(key, COUNTER_TTL_SECS)
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        })
+        .await;
+        if let Err(e) = result {
+            error!("Failed to count {} bytes against {}: {:?}", bytes, key, e);
+        }
+    }
+}
+
+// Refuse the request if its client has used up today's allowance, else return the meter to
+// record the response's size with
+pub async fn check(redis_client: Option<&redis::Client>, http_req: &HttpRequest, user_id: Option<i32>) -> Result<Meter, ApiError> {
+    let unmetered = Meter { counter: None };
+    let Some(redis_client) = redis_client else {
+        return Ok(unmetered);
+    };
+    let Some((scope, subject)) = request_subject(http_req, user_id) else {
+        return Ok(unmetered);
+    };
+    let Some(cap) = daily_cap(scope) else {
+        return Ok(unmetered);
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let key = counter_key(now.date(), &subject);
+    let used = circuit_breaker::redis(redis_client, |mut conn| {
+        let key = &key;
+        async move { conn.get::<_, Option<u64>>(key).await }
+    })
+    .await;
+    match used {
+        Ok(used) if used.unwrap_or(0) >= cap => {
+            warn!("{} is over its daily streaming allowance", subject);
+            Err(cap_exceeded(scope, cap, secs_until_reset(now), payment_required()))
+        }
+        Ok(_) => Ok(Meter { counter: Some((redis_client.clone(), key)) }),
+        // Streaming isn't refused just because usage can't be counted
+        Err(e) => {
+            error!("Bandwidth accounting unavailable in Redis: {:?}", e);
+            Ok(unmetered)
+        }
+    }
+}
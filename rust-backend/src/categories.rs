@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::env;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::error::ApiError;
+use crate::models::Video;
+
+// Videos can be cross-posted to several categories (videos_categories), up to
+// MAX_VIDEO_CATEGORIES. The first one given is the video's primary category, which responses
+// still carry as category_id for clients from before cross-posting.
+
+const DEFAULT_MAX_PER_VIDEO: usize = 3;
+
+pub fn max_per_video() -> usize {
+    env::var("MAX_VIDEO_CATEGORIES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_PER_VIDEO)
+}
+
+// The categories asked for in the order given, without repeats, or why there are too many
+pub fn normalize(category_ids: &[i32], max: usize) -> Result<Vec<i32>, String> {
+    let mut unique: Vec<i32> = Vec::with_capacity(category_ids.len());
+    for id in category_ids {
+        if !unique.contains(id) {
+            unique.push(*id);
+        }
+    }
+    if unique.len() > max {
+        return Err(format!("A video can be in at most {} categories", max));
+    }
+    Ok(unique)
+}
+
+// Normalize the categories a client asked for, failing unless they all exist
+pub async fn validate(db_pool: &PgPool, category_ids: &[i32]) -> Result<Vec<i32>, ApiError> {
+    let category_ids = normalize(category_ids, max_per_video()).map_err(ApiError::BadRequest)?;
+    if category_ids.is_empty() {
+        return Ok(category_ids);
+    }
+    let found: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE id = ANY($1)")
+        .bind(&category_ids)
+        .fetch_one(db_pool)
+        .await?;
+    if found != category_ids.len() as i64 {
+        return Err(ApiError::BadRequest("Unknown category".to_string()));
+    }
+    Ok(category_ids)
+}
+
+// Replace the video's categories. Categories deleted since they were picked are skipped.
+pub async fn set_for_video(tx: &mut Transaction<'_, Postgres>, video_id: i32, category_ids: &[i32]) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM videos_categories WHERE video_id = $1 AND NOT (category_id = ANY($2))")
+        .bind(video_id)
+        .bind(category_ids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO videos_categories (video_id, category_id, position)
+         SELECT $1, c.category_id, (c.position - 1)::SMALLINT
+         FROM unnest($2::INT[]) WITH ORDINALITY AS c(category_id, position)
+         JOIN categories ON categories.id = c.category_id
+         ON CONFLICT (video_id, category_id) DO UPDATE SET position = EXCLUDED.position"
+    )
+    .bind(video_id)
+    .bind(category_ids)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// Load the categories of each video, primary first
+pub async fn attach(db_pool: &PgPool, videos: &mut [Video]) -> Result<(), sqlx::Error> {
+    let ids: Vec<i32> = videos.iter().map(|v| v.id).collect();
+    let rows = sqlx::query_as::<_, (i32, Vec<i32>)>(
+        "SELECT video_id, array_agg(category_id ORDER BY position, category_id)
+         FROM videos_categories WHERE video_id = ANY($1) GROUP BY video_id"
+    )
+    .bind(&ids)
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut by_video: HashMap<i32, Vec<i32>> = rows.into_iter().collect();
+    for video in videos.iter_mut() {
+        video.category_ids = Some(by_video.remove(&video.id).unwrap_or_default());
+    }
+    Ok(())
+}
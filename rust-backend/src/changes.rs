@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::categories;
+use crate::feeds;
+use crate::models::Video;
+
+// An NDJSON feed of videos being created, updated and deleted (GET /api/changes?since=cursor),
+// for recommenders and search indexers to sync from without re-reading the catalogue. Triggers
+// log every change in video_changes; each line carries the video as it is now, and the cursor
+// to resume after it.
+//
+// Only public videos are synced: a change to a video that is deleted, unapproved or encrypted by
+// the time it's read is reported as a deletion, so consumers drop whatever they had. Changes are
+// ordered by the transaction that made them, and held back until every transaction that could
+// still commit an earlier one has ended, so a consumer never skips past one.
+
+pub const DEFAULT_LIMIT: i64 = 1000;
+pub const MAX_LIMIT: i64 = 10_000;
+
+// Position in the feed: the transaction and change log id of the last change read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub txid: i64,
+    pub id: i64,
+}
+
+impl Cursor {
+    // Cursors are handed out as "{txid}-{id}"; none means the start of the log
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+            return Ok(Cursor::default());
+        };
+        let invalid = || format!("Invalid cursor '{}'", value);
+        let (txid, id) = value.split_once('-').ok_or_else(invalid)?;
+        let txid = txid.parse::<i64>().map_err(|_| invalid())?;
+        let id = id.parse::<i64>().map_err(|_| invalid())?;
+        Ok(Cursor { txid, id })
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.txid, self.id)
+    }
+}
+
+pub fn clamp_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub cursor: String,
+    pub video_id: i32,
+    pub change: &'static str, // created, updated or deleted
+    pub changed_at: NaiveDateTime,
+    pub video: Option<Video>, // the video now, for anything but deletions
+}
+
+// How a logged change is reported, given whether the video is public now
+pub fn reported_change(logged: &str, public: bool) -> &'static str {
+    match (logged, public) {
+        (_, false) | ("deleted", _) => "deleted",
+        ("created", true) => "created",
+        _ => "updated",
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct LoggedChange {
+    id: i64,
+    video_id: i32,
+    change: String,
+    txid: i64,
+    changed_at: NaiveDateTime,
+}
+
+// Up to `limit` changes after `since`, and the cursor to ask for the next ones with
+pub async fn since(db_pool: &PgPool, since: Cursor, limit: i64) -> Result<(Vec<Change>, Cursor), sqlx::Error> {
+    let logged = sqlx::query_as::<_, LoggedChange>(
+        "SELECT id, video_id, change, txid, changed_at FROM video_changes
+         WHERE (txid, id) > ($1, $2) AND txid < pg_snapshot_xmin(pg_current_snapshot())::TEXT::BIGINT
+         ORDER BY txid, id
+         LIMIT $3"
+    )
+    .bind(since.txid)
+    .bind(since.id)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await?;
+
+    let video_ids: Vec<i32> = logged.iter().map(|c| c.video_id).collect();
+    let mut videos = sqlx::query_as::<_, Video>(&format!("{} AND id = ANY($1)", feeds::PUBLIC_VIDEOS_SQL))
+        .bind(&video_ids)
+        .fetch_all(db_pool)
+        .await?;
+    categories::attach(db_pool, &mut videos).await?;
+    let public: HashMap<i32, Video> = videos.into_iter().map(|v| (v.id, v)).collect();
+
+    let next = logged.last().map_or(since, |c| Cursor { txid: c.txid, id: c.id });
+    let changes = logged
+        .into_iter()
+        .map(|c| {
+            let video = public.get(&c.video_id).cloned();
+            let change = reported_change(&c.change, video.is_some());
+            Change {
+                cursor: Cursor { txid: c.txid, id: c.id }.to_string(),
+                video_id: c.video_id,
+                change,
+                changed_at: c.changed_at,
+                video: video.filter(|_| change != "deleted"),
+            }
+        })
+        .collect();
+    Ok((changes, next))
+}
+
+// The cursor of the latest change readable now, to follow the feed from without replaying it
+pub async fn head(db_pool: &PgPool) -> Result<Cursor, sqlx::Error> {
+    let latest = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT txid, id FROM video_changes
+         WHERE txid < pg_snapshot_xmin(pg_current_snapshot())::TEXT::BIGINT
+         ORDER BY txid DESC, id DESC
+         LIMIT 1"
+    )
+    .fetch_optional(db_pool)
+    .await?;
+    Ok(latest.map_or(Cursor::default(), |(txid, id)| Cursor { txid, id }))
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::models::{ChannelLayout, ChannelLayoutSection, Video};
+
+pub const MAX_SECTIONS: usize = 20;
+pub const MAX_SECTION_TITLE_CHARS: usize = 100;
+
+#[derive(Debug, Serialize)]
+pub struct ChannelOwner {
+    pub id: i32,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelSection {
+    pub title: String,
+    pub videos: Vec<Video>,
+}
+
+// What the channel videos endpoint returns
+#[derive(Debug, Serialize)]
+pub struct ChannelPage {
+    pub user: ChannelOwner,
+    pub featured: Option<Video>,
+    pub sections: Vec<ChannelSection>,
+    pub videos: Vec<Video>,
+}
+
+fn dedup_ids(ids: &[i32]) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    ids.iter().copied().filter(|id| seen.insert(*id)).collect()
+}
+
+// Check a requested layout against the videos the user owns, trimming titles and dropping
+// repeated ids
+pub fn validate_layout(layout: &ChannelLayout, owned: &HashSet<i32>) -> Result<ChannelLayout, String> {
+    let check = |id: &i32| if owned.contains(id) { Ok(()) } else { Err(format!("Video {} is not one of your videos", id)) };
+
+    if let Some(id) = &layout.featured_video_id {
+        check(id)?;
+    }
+    layout.video_order.iter().try_for_each(check)?;
+
+    if layout.sections.len() > MAX_SECTIONS {
+        return Err(format!("A channel can have at most {} sections", MAX_SECTIONS));
+    }
+    let mut sections = Vec::with_capacity(layout.sections.len());
+    for section in &layout.sections {
+        let title = section.title.trim();
+        if title.is_empty() || title.chars().count() > MAX_SECTION_TITLE_CHARS {
+            return Err(format!("Section titles must be 1 to {} characters", MAX_SECTION_TITLE_CHARS));
+        }
+        section.video_ids.iter().try_for_each(check)?;
+        sections.push(ChannelLayoutSection { title: title.to_string(), video_ids: dedup_ids(&section.video_ids) });
+    }
+
+    Ok(ChannelLayout {
+        featured_video_id: layout.featured_video_id,
+        video_order: dedup_ids(&layout.video_order),
+        sections,
+    })
+}
+
+// Lay out the channel's videos (newest first) for the page. Videos that have since been
+// deleted or hidden are skipped, as are sections left empty by that.
+pub fn arrange(videos: Vec<Video>, layout: &ChannelLayout) -> (Option<Video>, Vec<ChannelSection>, Vec<Video>) {
+    let by_id: HashMap<i32, &Video> = videos.iter().map(|v| (v.id, v)).collect();
+    let pick = |ids: &[i32]| -> Vec<Video> { ids.iter().filter_map(|id| by_id.get(id).map(|v| (*v).clone())).collect() };
+
+    let featured = layout.featured_video_id.and_then(|id| by_id.get(&id).map(|v| (*v).clone()));
+    let sections = layout
+        .sections
+        .iter()
+        .map(|section| ChannelSection { title: section.title.clone(), videos: pick(&section.video_ids) })
+        .filter(|section| !section.videos.is_empty())
+        .collect();
+
+    let mut ordered = pick(&layout.video_order);
+    let positioned: HashSet<i32> = layout.video_order.iter().copied().collect();
+    ordered.extend(videos.iter().filter(|v| !positioned.contains(&v.id)).cloned());
+
+    (featured, sections, ordered)
+}
+
+pub async fn owned_video_ids(db_pool: &PgPool, user_id: i32) -> Result<HashSet<i32>, sqlx::Error> {
+    let ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM videos WHERE uploaded_by = $1 AND deleted_at IS NULL")
+        .bind(user_id)
+        .fetch_all(db_pool)
+        .await?;
+    Ok(ids.into_iter().collect())
+}
+
+pub async fn load_layout(db_pool: &PgPool, user_id: i32) -> Result<ChannelLayout, sqlx::Error> {
+    let featured_video_id = sqlx::query_scalar::<_, Option<i32>>("SELECT featured_video_id FROM channel_settings WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await?
+        .flatten();
+
+    let video_order: Vec<i32> = sqlx::query_scalar(
+        "SELECT video_id FROM channel_video_positions WHERE user_id = $1 ORDER BY position ASC"
+    )
+    .bind(user_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    let rows = sqlx::query_as::<_, (i32, String, Option<i32>)>(
+        "SELECT s.id, s.title, sv.video_id
+         FROM channel_sections s LEFT JOIN channel_section_videos sv ON sv.section_id = s.id
+         WHERE s.user_id = $1
+         ORDER BY s.position ASC, sv.position ASC"
+    )
+    .bind(user_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut sections: Vec<(i32, ChannelLayoutSection)> = Vec::new();
+    for (section_id, title, video_id) in rows {
+        if sections.last().is_none_or(|(id, _)| *id != section_id) {
+            sections.push((section_id, ChannelLayoutSection { title, video_ids: Vec::new() }));
+        }
+        if let (Some(video_id), Some((_, section))) = (video_id, sections.last_mut()) {
+            section.video_ids.push(video_id);
+        }
+    }
+
+    Ok(ChannelLayout {
+        featured_video_id,
+        video_order,
+        sections: sections.into_iter().map(|(_, section)| section).collect(),
+    })
+}
+
+// Replace the user's layout with a validated one
+pub async fn save_layout(db_pool: &PgPool, user_id: i32, layout: &ChannelLayout) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let now = chrono::Utc::now().naive_utc();
+
+    sqlx::query(
+        "INSERT INTO channel_settings (user_id, featured_video_id, updated_at) VALUES ($1, $2, $3)
+         ON CONFLICT (user_id) DO UPDATE SET featured_video_id = EXCLUDED.featured_video_id, updated_at = EXCLUDED.updated_at"
+    )
+    .bind(user_id)
+    .bind(layout.featured_video_id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query("DELETE FROM channel_video_positions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+    for (position, video_id) in layout.video_order.iter().enumerate() {
+        sqlx::query("INSERT INTO channel_video_positions (user_id, video_id, position) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(video_id)
+            .bind(position as i32)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM channel_sections WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+    for (position, section) in layout.sections.iter().enumerate() {
+        let section_id: i32 = sqlx::query_scalar(
+            "INSERT INTO channel_sections (user_id, title, position, created_at) VALUES ($1, $2, $3, $4) RETURNING id"
+        )
+        .bind(user_id)
+        .bind(&section.title)
+        .bind(position as i32)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?;
+        for (video_position, video_id) in section.video_ids.iter().enumerate() {
+            sqlx::query("INSERT INTO channel_section_videos (section_id, video_id, position) VALUES ($1, $2, $3)")
+                .bind(section_id)
+                .bind(video_id)
+                .bind(video_position as i32)
+                .execute(&mut tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await
+}
@@ -0,0 +1,193 @@
+use std::env;
+use std::path::Path;
+use std::process::Stdio;
+use log::info;
+use sqlx::PgPool;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use crate::media;
+use crate::models::VideoChapter;
+
+type ChapterResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Frames are sampled at this size; enough to compare colour distributions, cheap to decode
+const FRAME_WIDTH: usize = 64;
+const FRAME_HEIGHT: usize = 36;
+const FRAME_BYTES: usize = FRAME_WIDTH * FRAME_HEIGHT * 3;
+
+// Bins per RGB channel in each frame's histogram
+const HISTOGRAM_BINS: usize = 16;
+
+pub type Histogram = [f32; HISTOGRAM_BINS * 3];
+
+// Tunables, overridable with SCENE_SAMPLE_FPS, SCENE_CUT_THRESHOLD and MIN_CHAPTER_SECS
+const DEFAULT_SAMPLE_FPS: f64 = 1.0;
+const DEFAULT_CUT_THRESHOLD: f32 = 0.4;
+const DEFAULT_MIN_CHAPTER_SECS: f64 = 30.0;
+
+fn env_or<T: std::str::FromStr + PartialOrd + Default>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<T>().ok())
+        .filter(|v| *v > T::default())
+        .unwrap_or(default)
+}
+
+// Normalized per-channel colour histogram of an RGB24 frame
+pub fn histogram(frame: &[u8]) -> Histogram {
+    let mut hist = [0f32; HISTOGRAM_BINS * 3];
+    let pixels = (frame.len() / 3).max(1) as f32;
+    for pixel in frame.chunks_exact(3) {
+        for (channel, value) in pixel.iter().enumerate() {
+            hist[channel * HISTOGRAM_BINS + (*value as usize * HISTOGRAM_BINS / 256)] += 1.0;
+        }
+    }
+    for bin in hist.iter_mut() {
+        *bin /= pixels;
+    }
+    hist
+}
+
+// 0.0 for identical colour distributions, 1.0 for completely disjoint ones
+pub fn histogram_distance(a: &Histogram, b: &Histogram) -> f32 {
+    let total: f32 = a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum();
+    total / 6.0 // each channel's histogram sums to 1, so each contributes at most 2
+}
+
+// Timestamps (seconds) of frames that differ from the previous sample by more than `threshold`
+pub fn detect_cuts(histograms: &[Histogram], sample_fps: f64, threshold: f32) -> Vec<f64> {
+    histograms
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| histogram_distance(&pair[0], &pair[1]) > threshold)
+        .map(|(i, _)| (i + 1) as f64 / sample_fps)
+        .collect()
+}
+
+// Chapter start times from scene cuts: always one at 0, then cuts at least `min_length`
+// after the previous chapter and before the end of the video
+pub fn propose_chapter_starts(cuts: &[f64], duration: Option<f64>, min_length: f64) -> Vec<f64> {
+    let mut starts = vec![0.0];
+    for &cut in cuts {
+        let last = *starts.last().unwrap();
+        let too_close_to_end = duration.is_some_and(|d| d - cut < min_length);
+        if cut - last >= min_length && !too_close_to_end {
+            starts.push(cut);
+        }
+    }
+    starts
+}
+
+// Decode sampled, downscaled frames with ffmpeg and histogram them as they arrive,
+// so long videos don't have to be held in memory
+async fn sample_histograms(input: &Path, sample_fps: f64) -> ChapterResult<Vec<Histogram>> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(input)
+        .args(["-an", "-vf", &format!("fps={},scale={}:{}", sample_fps, FRAME_WIDTH, FRAME_HEIGHT)])
+        .args(["-pix_fmt", "rgb24", "-f", "rawvideo", "pipe:1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdout = child.stdout.take().ok_or("ffmpeg stdout unavailable")?;
+    let mut stderr = child.stderr.take().ok_or("ffmpeg stderr unavailable")?;
+    let timeout = media::command_timeout();
+    let decoded = tokio::time::timeout(timeout, async {
+        let read_frames = async {
+            let mut histograms = Vec::new();
+            let mut frame = vec![0u8; FRAME_BYTES];
+            loop {
+                match stdout.read_exact(&mut frame).await {
+                    Ok(_) => histograms.push(histogram(&frame)),
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(histograms)
+        };
+        // Read alongside the frames, so ffmpeg never stalls on a full stderr pipe
+        let mut errors = Vec::new();
+        let (histograms, _) = tokio::try_join!(read_frames, stderr.read_to_end(&mut errors))?;
+        child.wait().await.map(|status| (histograms, status, errors))
+    })
+    .await;
+    // Dropping the child on a timeout or read error kills ffmpeg
+    let (histograms, status, errors) = decoded.map_err(|_| format!("ffmpeg took longer than {}s", timeout.as_secs()))??;
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&errors);
+        return Err(Box::new(std::io::Error::other(format!("ffmpeg failed: {}", stderr.trim()))));
+    }
+    Ok(histograms)
+}
+
+// Run scene detection on a local video file and return proposed chapter start times
+pub async fn detect_chapter_starts(input: &Path, duration: Option<f64>) -> ChapterResult<Vec<f64>> {
+    let sample_fps = env_or("SCENE_SAMPLE_FPS", DEFAULT_SAMPLE_FPS);
+    let threshold = env_or("SCENE_CUT_THRESHOLD", DEFAULT_CUT_THRESHOLD);
+    let min_length = env_or("MIN_CHAPTER_SECS", DEFAULT_MIN_CHAPTER_SECS);
+
+    let histograms = sample_histograms(input, sample_fps).await?;
+    let cuts = detect_cuts(&histograms, sample_fps, threshold);
+    info!("Detected {} scene cuts in {} sampled frames of {}", cuts.len(), histograms.len(), input.display());
+    Ok(propose_chapter_starts(&cuts, duration, min_length))
+}
+
+pub async fn has_auto_chapters(db_pool: &PgPool, video_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM video_chapters WHERE video_id = $1 AND source = 'auto')")
+        .bind(video_id)
+        .fetch_one(db_pool)
+        .await
+}
+
+// Replace the video's pending proposals with new ones. Manual and accepted chapters are kept,
+// and proposals too close to one of them are left out.
+pub async fn store_proposals(db_pool: &PgPool, video_id: i32, starts: &[f64]) -> Result<usize, sqlx::Error> {
+    let min_length = env_or("MIN_CHAPTER_SECS", DEFAULT_MIN_CHAPTER_SECS);
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query("DELETE FROM video_chapters WHERE video_id = $1 AND source = 'auto' AND NOT accepted")
+        .bind(video_id)
+        .execute(&mut tx)
+        .await?;
+
+    let kept: Vec<f64> = sqlx::query_scalar("SELECT start_time FROM video_chapters WHERE video_id = $1")
+        .bind(video_id)
+        .fetch_all(&mut tx)
+        .await?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut stored = 0;
+    for start in starts {
+        if kept.iter().any(|k| (k - start).abs() < min_length) {
+            continue;
+        }
+        stored += 1;
+        sqlx::query(
+            "INSERT INTO video_chapters (video_id, title, start_time, source, accepted, created_at, updated_at)
+             VALUES ($1, $2, $3, 'auto', FALSE, $4, $4)"
+        )
+        .bind(video_id)
+        .bind(format!("Chapter {}", stored))
+        .bind(start)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    info!("Stored {} proposed chapters for video ID {}", stored, video_id);
+    Ok(stored)
+}
+
+pub async fn list_chapters(db_pool: &PgPool, video_id: i32, include_proposed: bool) -> Result<Vec<VideoChapter>, sqlx::Error> {
+    sqlx::query_as::<_, VideoChapter>(
+        "SELECT * FROM video_chapters WHERE video_id = $1 AND (accepted OR $2) ORDER BY start_time ASC, id ASC"
+    )
+    .bind(video_id)
+    .bind(include_proposed)
+    .fetch_all(db_pool)
+    .await
+}
@@ -0,0 +1,226 @@
+use std::env;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use aws_sdk_s3::error::SdkError;
+use log::{info, warn};
+
+use crate::error::ApiError;
+
+// Guards calls to S3 and Redis so a dependency that is down costs requests a quick 503 instead of
+// a hang. Each call is bounded by a timeout; after enough consecutive outages the breaker opens
+// and calls fail immediately until, after a pause, a single probe call is let through to see
+// whether the dependency is back.
+
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerConfig {
+    pub timeout: Duration,
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).filter(|v| *v > 0).unwrap_or(default)
+}
+
+// Shared by both breakers: CIRCUIT_FAILURE_THRESHOLD (default 5) and CIRCUIT_OPEN_SECS (default 30)
+fn config(timeout_var: &str, default_timeout_ms: u64) -> BreakerConfig {
+    BreakerConfig {
+        timeout: Duration::from_millis(env_u64(timeout_var, default_timeout_ms)),
+        failure_threshold: env_u64("CIRCUIT_FAILURE_THRESHOLD", 5) as u32,
+        open_for: Duration::from_secs(env_u64("CIRCUIT_OPEN_SECS", 30)),
+    }
+}
+
+// S3_TIMEOUT_MS bounds getting a response from S3, not reading the body that follows
+pub fn s3_config() -> BreakerConfig {
+    config("S3_TIMEOUT_MS", 10_000)
+}
+
+// REDIS_TIMEOUT_MS bounds connecting to Redis together with the commands run through `redis`
+pub fn redis_config() -> BreakerConfig {
+    config("REDIS_TIMEOUT_MS", 2_000)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen, // a probe call is in flight
+}
+
+#[derive(Debug)]
+struct Inner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    inner: Mutex<Inner>,
+}
+
+pub static S3: CircuitBreaker = CircuitBreaker::new("S3");
+pub static REDIS: CircuitBreaker = CircuitBreaker::new("Redis");
+
+impl CircuitBreaker {
+    pub const fn new(name: &'static str) -> Self {
+        CircuitBreaker {
+            name,
+            inner: Mutex::new(Inner { consecutive_failures: 0, opened_at: None, probe_started: None }),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(_) if inner.probe_started.is_some() => BreakerState::HalfOpen,
+            Some(_) => BreakerState::Open,
+        }
+    }
+
+    // Whether a call may go ahead. Once an open breaker has waited `open_for`, one caller is
+    // let through as the probe and everyone else keeps failing fast until it reports back. A
+    // probe that never reports (its request was dropped) is replaced after the timeout.
+    pub fn allow(&self, config: &BreakerConfig, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(opened_at) = inner.opened_at else {
+            return true;
+        };
+        let may_probe = match inner.probe_started {
+            None => now.duration_since(opened_at) >= config.open_for,
+            Some(started) => now.duration_since(started) >= config.timeout,
+        };
+        if may_probe {
+            inner.probe_started = Some(now);
+        }
+        may_probe
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.opened_at.is_some() {
+            info!("{} is reachable again, closing its circuit breaker", self.name);
+        }
+        *inner = Inner { consecutive_failures: 0, opened_at: None, probe_started: None };
+    }
+
+    pub fn record_failure(&self, config: &BreakerConfig, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        // A failed probe re-opens the breaker for another full pause
+        if inner.probe_started.is_some() || (inner.opened_at.is_none() && inner.consecutive_failures >= config.failure_threshold) {
+            if inner.opened_at.is_none() {
+                warn!("{} failed {} times in a row, opening its circuit breaker", self.name, inner.consecutive_failures);
+            }
+            inner.opened_at = Some(now);
+            inner.probe_started = None;
+        }
+    }
+
+    // Run `call` under the breaker and the timeout. `is_outage` tells errors that mean the
+    // dependency is unhealthy from ones it answered with, such as a missing key.
+    pub async fn call<T, E, F>(&self, config: &BreakerConfig, call: F, is_outage: impl FnOnce(&E) -> bool) -> Result<T, CallError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        if !self.allow(config, Instant::now()) {
+            return Err(CallError::Open(self.name));
+        }
+        match tokio::time::timeout(config.timeout, call).await {
+            Ok(Ok(value)) => {
+                self.record_success();
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                if is_outage(&e) {
+                    self.record_failure(config, Instant::now());
+                } else {
+                    self.record_success();
+                }
+                Err(CallError::Failed(e))
+            }
+            Err(_) => {
+                self.record_failure(config, Instant::now());
+                Err(CallError::TimedOut(self.name))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CallError<E> {
+    Open(&'static str),
+    TimedOut(&'static str),
+    Failed(E),
+}
+
+impl<E> CallError<E> {
+    // 503 when the dependency was skipped or too slow, otherwise `f` of its error
+    pub fn into_api_error(self, f: impl FnOnce(E) -> ApiError) -> ApiError {
+        match self {
+            CallError::Open(name) => ApiError::ServiceUnavailable(format!("{} is unavailable, try again later", name)),
+            CallError::TimedOut(name) => ApiError::ServiceUnavailable(format!("{} did not respond in time, try again later", name)),
+            CallError::Failed(e) => f(e),
+        }
+    }
+}
+
+impl<E: Debug> std::fmt::Display for CallError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::Open(name) => write!(f, "{} circuit breaker is open", name),
+            CallError::TimedOut(name) => write!(f, "{} timed out", name),
+            CallError::Failed(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl<E: Debug> std::error::Error for CallError<E> {}
+
+// An S3 request. Errors S3 itself answered with (missing keys, denied access...) don't count
+// against the breaker; failing to reach it or get a response does.
+pub async fn s3<T, E, R>(call: impl Future<Output = Result<T, SdkError<E, R>>>) -> Result<T, CallError<SdkError<E, R>>> {
+    S3.call(&s3_config(), call, |e| !matches!(e, SdkError::ServiceError(_))).await
+}
+
+fn redis_error(e: CallError<redis::RedisError>, timed_out: &'static str) -> redis::RedisError {
+    match e {
+        CallError::Failed(e) => e,
+        CallError::Open(_) => redis::RedisError::from((redis::ErrorKind::IoError, "Redis circuit breaker is open")),
+        CallError::TimedOut(_) => redis::RedisError::from((redis::ErrorKind::IoError, timed_out)),
+    }
+}
+
+// Replies Redis couldn't serve right now, as opposed to ones about the command itself
+fn is_redis_outage(e: &redis::RedisError) -> bool {
+    matches!(e.kind(), redis::ErrorKind::IoError | redis::ErrorKind::BusyLoadingError | redis::ErrorKind::TryAgain)
+}
+
+// A Redis connection and the commands `commands` runs on it, as one call under the breaker and
+// the timeout, so a Redis that accepts connections but stops answering also fails fast. Breaker
+// errors come back as I/O errors so callers' existing Redis error handling and fallbacks apply.
+pub async fn redis<T, F, Fut>(redis_client: &redis::Client, commands: F) -> redis::RedisResult<T>
+where
+    F: FnOnce(redis::aio::Connection) -> Fut,
+    Fut: Future<Output = redis::RedisResult<T>>,
+{
+    let call = async {
+        let conn = redis_client.get_async_connection().await?;
+        commands(conn).await
+    };
+    REDIS.call(&redis_config(), call, is_redis_outage).await.map_err(|e| redis_error(e, "Redis did not answer in time"))
+}
+
+// Just a Redis connection under the breaker, for connections held past a quick exchange:
+// blocking reads, subscriptions and locks kept across other work.
+pub async fn redis_connection(redis_client: &redis::Client) -> redis::RedisResult<redis::aio::Connection> {
+    REDIS
+        .call(&redis_config(), redis_client.get_async_connection(), |_| true)
+        .await
+        .map_err(|e| redis_error(e, "Timed out connecting to Redis"))
+}
@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+use std::env;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use bytes::Bytes;
+use futures::Stream;
+use log::warn;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+// Messages buffered per WebSocket client when WS_CLIENT_QUEUE_SIZE is not set
+const DEFAULT_QUEUE_CAPACITY: usize = 100;
+
+// Bytes written to a client's socket but not yet sent before its queue stops being drained
+pub const MAX_UNSENT_BYTES: usize = 256 * 1024;
+
+// What to do when a client's queue is full because it isn't reading fast enough
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // Discard the oldest queued message to make room (watch party sync only needs the latest state)
+    DropOldest,
+    // Close the client's queue; the connection is then closed so the client can reconnect and resync
+    Disconnect,
+}
+
+impl OverflowPolicy {
+    // WS_OVERFLOW_POLICY is "drop_oldest" (default) or "disconnect"
+    pub fn from_env() -> Self {
+        match env::var("WS_OVERFLOW_POLICY").as_deref() {
+            Ok("disconnect") => OverflowPolicy::Disconnect,
+            _ => OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+static DROPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+static DISCONNECTED_CLIENTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize)]
+pub struct QueueMetrics {
+    pub dropped_messages: u64,
+    pub disconnected_clients: u64,
+}
+
+// Process-wide counts of messages dropped and clients disconnected by the overflow policy
+pub fn metrics() -> QueueMetrics {
+    QueueMetrics {
+        dropped_messages: DROPPED_MESSAGES.load(Ordering::Relaxed),
+        disconnected_clients: DISCONNECTED_CLIENTS.load(Ordering::Relaxed),
+    }
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<String>>,
+    notify: Notify,
+    capacity: usize,
+    policy: OverflowPolicy,
+    closed: AtomicBool,
+    overflowed: AtomicBool,
+    dropped: AtomicU64,
+}
+
+// Sending half of a client's queue. Sends never wait, so one slow client can't hold up a broadcast.
+#[derive(Clone)]
+pub struct ClientSender(Arc<Shared>);
+
+// Receiving half, drained by the task that writes to the client's socket
+pub struct ClientReceiver(Arc<Shared>);
+
+pub fn client_queue() -> (ClientSender, ClientReceiver) {
+    let capacity = env::var("WS_CLIENT_QUEUE_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(DEFAULT_QUEUE_CAPACITY);
+    client_queue_with(capacity, OverflowPolicy::from_env())
+}
+
+pub fn client_queue_with(capacity: usize, policy: OverflowPolicy) -> (ClientSender, ClientReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        notify: Notify::new(),
+        capacity,
+        policy,
+        closed: AtomicBool::new(false),
+        overflowed: AtomicBool::new(false),
+        dropped: AtomicU64::new(0),
+    });
+    (ClientSender(shared.clone()), ClientReceiver(shared))
+}
+
+impl ClientSender {
+    // Queue a message for the client, applying the overflow policy if the queue is full.
+    // Returns false if the message wasn't queued because the client is gone or was disconnected.
+    pub fn send(&self, msg: String) -> bool {
+        let shared = &self.0;
+        if shared.closed.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let mut queue = shared.queue.lock().unwrap();
+        if queue.len() >= shared.capacity {
+            match shared.policy {
+                OverflowPolicy::DropOldest => {
+                    queue.pop_front();
+                    DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+                    // Log once per client rather than once per message
+                    if shared.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                        warn!("WebSocket client is falling behind, dropping its oldest queued messages");
+                    }
+                }
+                OverflowPolicy::Disconnect => {
+                    DROPPED_MESSAGES.fetch_add(queue.len() as u64 + 1, Ordering::Relaxed);
+                    DISCONNECTED_CLIENTS.fetch_add(1, Ordering::Relaxed);
+                    queue.clear();
+                    shared.overflowed.store(true, Ordering::Release);
+                    shared.closed.store(true, Ordering::Release);
+                    drop(queue);
+                    shared.notify.notify_one();
+                    warn!("WebSocket client queue overflowed, disconnecting client");
+                    return false;
+                }
+            }
+        }
+        queue.push_back(msg);
+        drop(queue);
+        shared.notify.notify_one();
+        crate::ws_stats::record_sent();
+        true
+    }
+
+    pub fn same_channel(&self, other: &ClientSender) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Acquire)
+    }
+}
+
+impl ClientReceiver {
+    // Next queued message, or None once the queue is closed and drained
+    pub async fn recv(&mut self) -> Option<String> {
+        loop {
+            if let Some(msg) = self.0.queue.lock().unwrap().pop_front() {
+                return Some(msg);
+            }
+            if self.0.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.0.notify.notified().await;
+        }
+    }
+
+    // Whether the queue was closed because the client fell too far behind
+    pub fn overflowed(&self) -> bool {
+        self.0.overflowed.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for ClientReceiver {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+    }
+}
+
+// What a client's actor has written but its connection hasn't sent yet. Written messages pile
+// up in the actor's context without limit, and the HTTP layer only takes more of the response
+// while the socket keeps up, so this is what shows a client that stopped reading.
+#[derive(Clone, Default)]
+pub struct Backlog(Arc<BacklogShared>);
+
+#[derive(Default)]
+struct BacklogShared {
+    unsent: AtomicUsize,
+    notify: Notify,
+    closed: AtomicBool,
+}
+
+impl Backlog {
+    pub fn written(&self, bytes: usize) {
+        self.0.unsent.fetch_add(bytes, Ordering::AcqRel);
+    }
+
+    // Frame headers make sent bytes a little more than written ones, hence saturating
+    fn sent(&self, bytes: usize) {
+        let _ = self.0.unsent.fetch_update(Ordering::AcqRel, Ordering::Acquire, |unsent| Some(unsent.saturating_sub(bytes)));
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn unsent(&self) -> usize {
+        self.0.unsent.load(Ordering::Acquire)
+    }
+
+    // Wait until fewer than `limit` bytes are unsent. False once the connection is gone.
+    pub async fn wait_below(&self, limit: usize) -> bool {
+        loop {
+            let notified = self.0.notify.notified();
+            if self.0.closed.load(Ordering::Acquire) {
+                return false;
+            }
+            if self.unsent() < limit {
+                return true;
+            }
+            notified.await;
+        }
+    }
+
+    // The connection's outgoing stream, counting what is taken from it as sent
+    pub fn track<S, E>(&self, stream: S) -> TrackedStream<S>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        TrackedStream { inner: Box::pin(stream), backlog: self.clone() }
+    }
+}
+
+pub struct TrackedStream<S> {
+    inner: Pin<Box<S>>,
+    backlog: Backlog,
+}
+
+impl<S, E> Stream for TrackedStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let next = self.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &next {
+            self.backlog.sent(chunk.len());
+        }
+        next
+    }
+}
+
+impl<S> Drop for TrackedStream<S> {
+    fn drop(&mut self) {
+        self.backlog.0.closed.store(true, Ordering::Release);
+        self.backlog.0.notify.notify_waiters();
+    }
+}
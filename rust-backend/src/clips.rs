@@ -0,0 +1,135 @@
+use sqlx::PgPool;
+
+use crate::models::VideoClip;
+
+// Short animated clips of a video for sharing a moment, rendered on request from the smallest
+// rendition and stored in S3. The same segment in the same format is only rendered once.
+
+pub const DEFAULT_CLIP_SECS: f64 = 3.0;
+pub const MIN_CLIP_SECS: f64 = 0.5;
+pub const MAX_CLIP_SECS: f64 = 10.0;
+
+// Windows are snapped to this step so nearby requests share one rendered clip instead of each
+// millisecond offset rendering its own
+pub const CLIP_STEP_MS: i64 = 500;
+
+// Clips are scaled to this width (never up) at this frame rate, which keeps a 10 second GIF to
+// a few megabytes
+pub const CLIP_WIDTH: i32 = 480;
+pub const CLIP_FPS: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipFormat {
+    Gif,
+    Webp,
+}
+
+impl ClipFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("gif") => Ok(ClipFormat::Gif),
+            Some("webp") => Ok(ClipFormat::Webp),
+            Some(other) => Err(format!("Unknown clip format '{}'; use gif or webp", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ClipFormat::Gif => "image/gif",
+            ClipFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ClipFormat::Gif => "gif",
+            ClipFormat::Webp => "webp",
+        }
+    }
+}
+
+// The requested segment as (start, duration) in milliseconds, with the start snapped down and the
+// duration to the nearest CLIP_STEP_MS. Clips running past the end of a video of known length are
+// cut short there.
+pub fn clip_window(start: f64, duration: Option<f64>, video_duration: Option<i32>) -> Result<(i32, i32), String> {
+    let duration = duration.unwrap_or(DEFAULT_CLIP_SECS);
+    if !start.is_finite() || start < 0.0 {
+        return Err("start must be a non-negative number of seconds".to_string());
+    }
+    if !(MIN_CLIP_SECS..=MAX_CLIP_SECS).contains(&duration) {
+        return Err(format!("duration must be between {} and {} seconds", MIN_CLIP_SECS, MAX_CLIP_SECS));
+    }
+    let start_ms = (start * 1000.0).round() as i64 / CLIP_STEP_MS * CLIP_STEP_MS;
+    let steps = ((duration * 1000.0) / CLIP_STEP_MS as f64).round() as i64;
+    let mut duration_ms = steps.max(1) * CLIP_STEP_MS;
+    if let Some(video_duration) = video_duration {
+        let video_ms = i64::from(video_duration) * 1000;
+        if start_ms >= video_ms {
+            return Err("start is past the end of the video".to_string());
+        }
+        duration_ms = duration_ms.min(video_ms - start_ms);
+    }
+    let start_ms = i32::try_from(start_ms).map_err(|_| "start is past the end of the video".to_string())?;
+    Ok((start_ms, duration_ms as i32))
+}
+
+// ffmpeg filter for the clip's frames. GIFs get a palette made for the clip, which looks far
+// better than ffmpeg's default one.
+pub fn clip_filter(format: ClipFormat) -> String {
+    let frames = format!("fps={},scale='min({},iw)':-2:flags=lanczos", CLIP_FPS, CLIP_WIDTH);
+    match format {
+        ClipFormat::Gif => format!("{},split[frames][palette_in];[palette_in]palettegen[palette];[frames][palette]paletteuse", frames),
+        ClipFormat::Webp => frames,
+    }
+}
+
+pub fn clip_s3_key(video_id: i32, start_ms: i32, duration_ms: i32, format: ClipFormat) -> String {
+    format!("clips/{}/{}-{}.{}", video_id, start_ms, duration_ms, format.extension())
+}
+
+pub async fn find_clip(db_pool: &PgPool, video_id: i32, start_ms: i32, duration_ms: i32, format: ClipFormat) -> Result<Option<VideoClip>, sqlx::Error> {
+    sqlx::query_as::<_, VideoClip>("SELECT * FROM video_clips WHERE video_id = $1 AND start_ms = $2 AND duration_ms = $3 AND format = $4")
+        .bind(video_id)
+        .bind(start_ms)
+        .bind(duration_ms)
+        .bind(format.extension())
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Record a rendered clip. Two requests for the same segment may both render it; they write the
+// same key, so whichever is recorded first is kept.
+pub async fn insert_clip(
+    db_pool: &PgPool,
+    video_id: i32,
+    created_by: i32,
+    start_ms: i32,
+    duration_ms: i32,
+    format: ClipFormat,
+    s3_key: &str,
+) -> Result<VideoClip, sqlx::Error> {
+    sqlx::query_as::<_, VideoClip>(
+        "INSERT INTO video_clips (video_id, created_by, start_ms, duration_ms, format, s3_key)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (video_id, start_ms, duration_ms, format) DO UPDATE SET s3_key = video_clips.s3_key
+         RETURNING *"
+    )
+    .bind(video_id)
+    .bind(created_by)
+    .bind(start_ms)
+    .bind(duration_ms)
+    .bind(format.extension())
+    .bind(s3_key)
+    .fetch_one(db_pool)
+    .await
+}
+
+// The S3 key to render clips of the video from: its smallest rendition, or the original if it
+// has none yet
+pub async fn clip_source(db_pool: &PgPool, video_id: i32, original: &str) -> Result<String, sqlx::Error> {
+    let rendition: Option<String> = sqlx::query_scalar("SELECT s3_key FROM video_renditions WHERE video_id = $1 ORDER BY height ASC LIMIT 1")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await?;
+    Ok(rendition.unwrap_or_else(|| original.to_string()))
+}
@@ -0,0 +1,121 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::transcription::format_vtt_timestamp;
+
+// A video's comment track as a file: SubRip or WebVTT captions that show each comment for a few
+// seconds from its video time, like the overlay does, or JSON that keeps every field needed to
+// post the comments again.
+
+// How long each comment is shown in the caption formats
+pub const COMMENT_DISPLAY_SECS: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentExportFormat {
+    Srt,
+    Vtt,
+    Json,
+}
+
+impl CommentExportFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("json") => Ok(CommentExportFormat::Json),
+            Some("srt") => Ok(CommentExportFormat::Srt),
+            Some("vtt") => Ok(CommentExportFormat::Vtt),
+            Some(other) => Err(format!("Unknown comment export format '{}'; use srt, vtt or json", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            CommentExportFormat::Srt => "application/x-subrip; charset=utf-8",
+            CommentExportFormat::Vtt => "text/vtt; charset=utf-8",
+            CommentExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            CommentExportFormat::Srt => "srt",
+            CommentExportFormat::Vtt => "vtt",
+            CommentExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ExportedComment {
+    pub id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub content: String,
+    pub video_time: i32,
+    pub created_at: NaiveDateTime,
+}
+
+// The video's comments in video time order, earlier posts first among equals
+pub async fn comment_track(db_pool: &PgPool, video_id: i32) -> Result<Vec<ExportedComment>, sqlx::Error> {
+    sqlx::query_as::<_, ExportedComment>(
+        "SELECT c.id, c.user_id, u.username, c.content, c.video_time, c.created_at
+         FROM comments c JOIN users u ON u.id = c.user_id
+         WHERE c.video_id = $1
+         ORDER BY c.video_time ASC, c.created_at ASC, c.id ASC"
+    )
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+fn cue_times(comment: &ExportedComment) -> (f64, f64) {
+    let start = comment.video_time.max(0) as f64;
+    (start, start + COMMENT_DISPLAY_SECS)
+}
+
+// Blank lines would end a cue early in both formats
+fn cue_text(comment: &ExportedComment) -> String {
+    let text = comment.content.trim().replace("\r\n", "\n");
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    format!("{}: {}", comment.username, lines.join("\n"))
+}
+
+// HH:MM:SS,mmm as SubRip timings require
+pub fn format_srt_timestamp(seconds: f64) -> String {
+    format_vtt_timestamp(seconds).replacen('.', ",", 1)
+}
+
+pub fn to_srt(comments: &[ExportedComment]) -> String {
+    let mut srt = String::new();
+    for (index, comment) in comments.iter().enumerate() {
+        let (start, end) = cue_times(comment);
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_srt_timestamp(start),
+            format_srt_timestamp(end),
+            cue_text(comment).replace("-->", "->")
+        ));
+    }
+    srt
+}
+
+pub fn to_vtt(comments: &[ExportedComment]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for comment in comments {
+        let (start, end) = cue_times(comment);
+        // Cue text is markup in WebVTT, so what users typed has to be escaped
+        let text = cue_text(comment)
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        vtt.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            comment.id,
+            format_vtt_timestamp(start),
+            format_vtt_timestamp(end),
+            text
+        ));
+    }
+    vtt
+}
@@ -0,0 +1,58 @@
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::models::Comment;
+
+// Comments sent to a comment WebSocket when it connects, so the overlay is populated without a
+// separate REST call. By default the most recent ones; with a playback position, the ones
+// nearest it in the video.
+
+pub const DEFAULT_REPLAY_LIMIT: i64 = 50;
+pub const MAX_REPLAY_LIMIT: i64 = 200;
+
+// How far either side of the playback position comments are replayed from
+pub const REPLAY_WINDOW_SECS: i32 = 60;
+
+pub fn replay_limit(requested: Option<i64>) -> i64 {
+    requested.unwrap_or(DEFAULT_REPLAY_LIMIT).clamp(0, MAX_REPLAY_LIMIT)
+}
+
+// Up to `limit` comments, in video time order
+pub async fn replay_comments(db_pool: &PgPool, video_id: i32, position: Option<i32>, limit: i64) -> Result<Vec<Comment>, sqlx::Error> {
+    match position {
+        Some(position) => {
+            sqlx::query_as::<_, Comment>(
+                "SELECT * FROM (
+                     SELECT * FROM comments
+                     WHERE video_id = $1 AND video_time BETWEEN $2 - $3 AND $2 + $3
+                     ORDER BY ABS(video_time - $2) ASC, id DESC
+                     LIMIT $4
+                 ) nearest
+                 ORDER BY video_time ASC, id ASC"
+            )
+            .bind(video_id)
+            .bind(position)
+            .bind(REPLAY_WINDOW_SECS)
+            .bind(limit)
+            .fetch_all(db_pool)
+            .await
+        }
+        None => {
+            sqlx::query_as::<_, Comment>(
+                "SELECT * FROM (
+                     SELECT * FROM comments WHERE video_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2
+                 ) recent
+                 ORDER BY video_time ASC, id ASC"
+            )
+            .bind(video_id)
+            .bind(limit)
+            .fetch_all(db_pool)
+            .await
+        }
+    }
+}
+
+// Live comments are sent one by one as they are posted; the replay arrives as a single batch
+pub fn replay_message(position: Option<i32>, comments: &[Comment]) -> String {
+    json!({ "type": "replay", "position": position, "comments": comments }).to_string()
+}
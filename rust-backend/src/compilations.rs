@@ -0,0 +1,340 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+
+use crate::error::ApiError;
+use crate::job_queue::JobQueue;
+use crate::media::{self, WorkDir};
+use crate::models::{Compilation, CompilationRequest, CompilationSegment, CompilationSegmentRequest, Video};
+use crate::moderation;
+use crate::services::{download_object_parallel, ParallelDownloadConfig};
+use crate::storage::{ObjectStore, Storage};
+use crate::webhooks;
+
+// Users can cut segments of their videos together into a new video. The request is stored and
+// rendered by a background worker: each segment is cut and re-encoded to a common format, then
+// the segments are joined without re-encoding and added to the catalogue as the user's video.
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const MAX_SEGMENTS: usize = 20;
+pub const MIN_SEGMENT_SECS: f64 = 0.5;
+pub const MAX_TOTAL_SECS: f64 = 30.0 * 60.0;
+pub const MAX_TITLE_CHARS: usize = 200;
+
+// Renders that fail this many times are given up on
+pub const MAX_ATTEMPTS: i32 = 3;
+
+// A worker that dies mid-render leaves the compilation to be picked up again after this long
+const LEASE_SECS: i64 = 30 * 60;
+
+type CompileResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// The requested segments as (video id, start ms, end ms), in order
+pub fn validate_segments(segments: &[CompilationSegmentRequest]) -> Result<Vec<(i32, i32, i32)>, String> {
+    if segments.is_empty() || segments.len() > MAX_SEGMENTS {
+        return Err(format!("A compilation needs 1 to {} segments", MAX_SEGMENTS));
+    }
+    let mut total = 0.0;
+    let mut validated = Vec::with_capacity(segments.len());
+    for (i, segment) in segments.iter().enumerate() {
+        if !segment.start.is_finite() || !segment.end.is_finite() || segment.start < 0.0 {
+            return Err(format!("Segment {} needs a non-negative start and an end in seconds", i + 1));
+        }
+        if segment.end - segment.start < MIN_SEGMENT_SECS {
+            return Err(format!("Segment {} must be at least {} seconds long", i + 1, MIN_SEGMENT_SECS));
+        }
+        total += segment.end - segment.start;
+        if total > MAX_TOTAL_SECS {
+            return Err(format!("Compilations can be at most {} minutes long", MAX_TOTAL_SECS / 60.0));
+        }
+        validated.push((segment.video_id, (segment.start * 1000.0).round() as i32, (segment.end * 1000.0).round() as i32));
+    }
+    Ok(validated)
+}
+
+// Store a compilation for the worker to render. Every segment has to come from a video the user
+// uploaded, unless they are an admin.
+pub async fn create(db_pool: &PgPool, user_id: i32, is_admin: bool, request: &CompilationRequest) -> Result<Compilation, ApiError> {
+    let title = request.title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_CHARS {
+        return Err(ApiError::BadRequest(format!("Titles must be 1 to {} characters", MAX_TITLE_CHARS)));
+    }
+    let segments = validate_segments(&request.segments).map_err(ApiError::BadRequest)?;
+
+    let mut source_ids: Vec<i32> = segments.iter().map(|(video_id, _, _)| *video_id).collect();
+    source_ids.sort_unstable();
+    source_ids.dedup();
+    let sources: HashMap<i32, Video> = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = ANY($1) AND deleted_at IS NULL")
+        .bind(&source_ids)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(|video| (video.id, video))
+        .collect();
+    for (i, (video_id, _, end_ms)) in segments.iter().enumerate() {
+        let video = sources.get(video_id).ok_or_else(|| ApiError::NotFound(format!("Video {} not found", video_id)))?;
+        if !is_admin && video.uploaded_by != Some(user_id) {
+            return Err(ApiError::Forbidden(format!("Video {} is not one of your videos", video_id)));
+        }
+        if video.duration.is_some_and(|duration| i64::from(*end_ms) > i64::from(duration) * 1000) {
+            return Err(ApiError::BadRequest(format!("Segment {} ends after the end of video {}", i + 1, video_id)));
+        }
+    }
+
+    let mut tx = db_pool.begin().await?;
+    let compilation = sqlx::query_as::<_, Compilation>(
+        "INSERT INTO compilations (user_id, title, description) VALUES ($1, $2, $3) RETURNING *"
+    )
+    .bind(user_id)
+    .bind(title)
+    .bind(&request.description)
+    .fetch_one(&mut tx)
+    .await?;
+    for (position, (video_id, start_ms, end_ms)) in segments.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO compilation_segments (compilation_id, position, source_video_id, start_ms, end_ms) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(compilation.id)
+        .bind(position as i32)
+        .bind(video_id)
+        .bind(start_ms)
+        .bind(end_ms)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    info!("User {} queued compilation {} of {} segments", user_id, compilation.id, segments.len());
+    Ok(compilation)
+}
+
+pub async fn get_compilation(db_pool: &PgPool, compilation_id: i32, user_id: i32) -> Result<Option<Compilation>, sqlx::Error> {
+    sqlx::query_as::<_, Compilation>("SELECT * FROM compilations WHERE id = $1 AND user_id = $2")
+        .bind(compilation_id)
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+pub async fn list_compilations(db_pool: &PgPool, user_id: i32) -> Result<Vec<Compilation>, sqlx::Error> {
+    sqlx::query_as::<_, Compilation>("SELECT * FROM compilations WHERE user_id = $1 ORDER BY created_at DESC, id DESC")
+        .bind(user_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+pub async fn segments(db_pool: &PgPool, compilation_id: i32) -> Result<Vec<CompilationSegment>, sqlx::Error> {
+    sqlx::query_as::<_, CompilationSegment>("SELECT * FROM compilation_segments WHERE compilation_id = $1 ORDER BY position")
+        .bind(compilation_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+// Take the oldest compilation waiting to be rendered, or one whose worker stopped renewing its lease
+pub async fn claim_next(db_pool: &PgPool) -> Result<Option<Compilation>, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    sqlx::query_as::<_, Compilation>(
+        "UPDATE compilations SET status = $1, attempts = attempts + 1, lease_expires_at = $2, updated_at = $3 WHERE id = ( \
+             SELECT id FROM compilations \
+             WHERE status = $4 OR (status = $1 AND lease_expires_at < $3) \
+             ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED \
+         ) RETURNING *"
+    )
+    .bind(STATUS_PROCESSING)
+    .bind(now + chrono::Duration::seconds(LEASE_SECS))
+    .bind(now)
+    .bind(STATUS_PENDING)
+    .fetch_optional(db_pool)
+    .await
+}
+
+pub async fn set_progress(db_pool: &PgPool, compilation_id: i32, progress: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE compilations SET progress = $1, updated_at = NOW() WHERE id = $2")
+        .bind(progress.clamp(0, 100))
+        .bind(compilation_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Record a failed render. It is retried later unless `retry` is false or it has failed too often.
+pub async fn record_failure(db_pool: &PgPool, compilation: &Compilation, message: &str, retry: bool) -> Result<(), sqlx::Error> {
+    let status = if retry && compilation.attempts < MAX_ATTEMPTS { STATUS_PENDING } else { STATUS_FAILED };
+    sqlx::query("UPDATE compilations SET status = $1, last_error = $2, lease_expires_at = NULL, updated_at = NOW() WHERE id = $3")
+        .bind(status)
+        .bind(message)
+        .bind(compilation.id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Progress shown once `done` of `total` segments are cut; joining and uploading are the rest
+pub fn segment_progress(done: usize, total: usize) -> i32 {
+    if total == 0 {
+        return 0;
+    }
+    (done * 90 / total) as i32
+}
+
+async fn file_sha256(path: &std::path::Path) -> std::io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// The sources of a compilation's segments, or None if one was deleted since it was requested
+async fn sources(db_pool: &PgPool, segments: &[CompilationSegment]) -> Result<Option<HashMap<i32, Video>>, sqlx::Error> {
+    let ids: Vec<i32> = segments.iter().map(|s| s.source_video_id).collect();
+    let videos: HashMap<i32, Video> = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = ANY($1) AND deleted_at IS NULL")
+        .bind(&ids)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(|video| (video.id, video))
+        .collect();
+    Ok(ids.iter().all(|id| videos.contains_key(id)).then_some(videos))
+}
+
+async fn render(db_pool: &PgPool, storage: &dyn ObjectStore, bucket: &str, compilation: &Compilation, segments: &[CompilationSegment], sources: &HashMap<i32, Video>) -> CompileResult<Video> {
+    let work_dir = WorkDir::create().await?;
+
+    // Each source is downloaded once, however many segments come from it
+    let mut downloaded: HashMap<i32, (std::path::PathBuf, bool)> = HashMap::new();
+    let mut parts = Vec::with_capacity(segments.len());
+    for (i, segment) in segments.iter().enumerate() {
+        if let Entry::Vacant(entry) = downloaded.entry(segment.source_video_id) {
+            let source = work_dir.file(&format!("source-{}", segment.source_video_id));
+            let video = &sources[&segment.source_video_id];
+            download_object_parallel(storage, bucket, &video.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+            let has_audio = media::probe_has_audio(&source).await?;
+            entry.insert((source, has_audio));
+        }
+        let (source, has_audio) = &downloaded[&segment.source_video_id];
+
+        let part = work_dir.file(&format!("part-{:03}.mp4", i));
+        let start = f64::from(segment.start_ms) / 1000.0;
+        let duration = f64::from(segment.end_ms - segment.start_ms) / 1000.0;
+        media::normalize_segment(source, &part, start, duration, *has_audio).await?;
+        parts.push(part);
+        set_progress(db_pool, compilation.id, segment_progress(i + 1, segments.len())).await?;
+    }
+
+    let list = work_dir.file("parts.txt");
+    let listing: String = parts.iter().map(|part| format!("file '{}'\n", part.display())).collect();
+    tokio::fs::write(&list, listing).await?;
+    let output = work_dir.file("compilation.mp4");
+    media::concat(&list, &output).await?;
+
+    let s3_key = format!("videos/{}.mp4", uuid::Uuid::new_v4());
+    media::upload_file(storage, bucket, &s3_key, &output, "video/mp4").await?;
+    let sha256 = file_sha256(&output).await?;
+    let duration_ms: i32 = segments.iter().map(|s| s.end_ms - s.start_ms).sum();
+
+    let mut tx = db_pool.begin().await?;
+    let video = sqlx::query_as::<_, Video>(
+        "INSERT INTO videos (title, description, s3_key, uploaded_by, upload_date, duration, sha256, moderation_status)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING *"
+    )
+    .bind(&compilation.title)
+    .bind(&compilation.description)
+    .bind(&s3_key)
+    .bind(compilation.user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind((duration_ms + 500) / 1000)
+    .bind(&sha256)
+    .bind(moderation::initial_status())
+    .fetch_one(&mut tx)
+    .await?;
+    sqlx::query(
+        "UPDATE compilations SET status = $1, progress = 100, video_id = $2, last_error = NULL, lease_expires_at = NULL, updated_at = NOW() WHERE id = $3"
+    )
+    .bind(STATUS_COMPLETED)
+    .bind(video.id)
+    .bind(compilation.id)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(video)
+}
+
+// Render one waiting compilation, returning whether to look for another straight away. After a
+// failure the worker waits a poll interval before the retry.
+pub async fn process_next(db_pool: &PgPool, storage: &dyn ObjectStore, job_queue: Option<&JobQueue>) -> Result<bool, sqlx::Error> {
+    let Some(compilation) = claim_next(db_pool).await? else {
+        return Ok(false);
+    };
+    info!("Rendering compilation {} (attempt {})", compilation.id, compilation.attempts);
+
+    let segments = segments(db_pool, compilation.id).await?;
+    let Some(sources) = sources(db_pool, &segments).await? else {
+        warn!("A source video of compilation {} was deleted, giving up", compilation.id);
+        record_failure(db_pool, &compilation, "A source video was deleted", false).await?;
+        return Ok(true);
+    };
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let video = match render(db_pool, storage, &bucket, &compilation, &segments, &sources).await {
+        Ok(video) => video,
+        Err(e) => {
+            error!("Failed to render compilation {}: {:?}", compilation.id, e);
+            record_failure(db_pool, &compilation, &e.to_string(), true).await?;
+            return Ok(false);
+        }
+    };
+    info!("Compilation {} completed as video {}", compilation.id, video.id);
+
+    match job_queue {
+        Some(job_queue) => {
+            if let Err(e) = job_queue.enqueue_processing(&video, &bucket, false).await {
+                error!("Failed to enqueue processing for compilation video {}: {:?}", video.id, e);
+            }
+        }
+        None => warn!("No job queue, compilation video {} will be processed once it is requeued", video.id),
+    }
+    webhooks::video_created(db_pool, &video).await;
+    Ok(true)
+}
+
+// Render compilations as they are requested. Interval is configurable via
+// COMPILATION_POLL_INTERVAL_SECS.
+pub async fn run_compilation_worker(db_pool: PgPool, storage: Storage, job_queue: Option<Arc<JobQueue>>) {
+    let interval_secs = env::var("COMPILATION_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(10);
+
+    info!("Starting compilation worker (interval: {} seconds)", interval_secs);
+
+    loop {
+        match process_next(&db_pool, storage.as_ref(), job_queue.as_deref()).await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => error!("Error rendering compilations: {:?}", e),
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
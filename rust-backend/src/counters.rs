@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use log::{error, info, warn};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::circuit_breaker;
+use crate::job_queue::RELEASE_LOCK_SCRIPT;
+use crate::models::Video;
+
+// Hot videos would otherwise have every view update the same row. Increments go into a Redis
+// hash instead, and a background job moves them into Postgres in one batched update. Responses
+// add the increments still waiting in Redis, so counts stay current between flushes. Without
+// Redis, or while it is unreachable, counts are written to Postgres directly.
+
+// How long one replica may hold a counter's flush before another can take over, far longer than
+// the single update a flush makes
+const FLUSH_LOCK_TTL_MILLIS: u64 = 5 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    Views,
+}
+
+pub const COUNTERS: [Counter; 1] = [Counter::Views];
+
+impl Counter {
+    // The videos column the counter is flushed into
+    pub fn column(self) -> &'static str {
+        match self {
+            Counter::Views => "view_count",
+        }
+    }
+
+    // Redis hash of pending increments by video id
+    pub fn key(self) -> String {
+        format!("counters:{}", self.column())
+    }
+
+    // Where a flush moves the hash while it is written to Postgres. A flush that fails leaves it
+    // there to be retried first by the next one.
+    pub fn flushing_key(self) -> String {
+        format!("{}:flushing", self.key())
+    }
+
+    // Held by the replica flushing the counter, so two never apply the same batch
+    pub fn flush_lock_key(self) -> String {
+        format!("{}:flush_lock", self.key())
+    }
+}
+
+async fn increment_in_redis(redis_client: &redis::Client, counter: Counter, video_id: i32) -> redis::RedisResult<()> {
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.hincr(counter.key(), video_id, 1).await
+    })
+    .await
+}
+
+async fn increment_in_postgres(db_pool: &PgPool, counter: Counter, video_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("UPDATE videos SET {0} = COALESCE({0}, 0) + 1 WHERE id = $1", counter.column()))
+        .bind(video_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Count one more for the video. Returns whether the increment is pending in Redis rather than
+// already written to Postgres.
+pub async fn increment(db_pool: &PgPool, redis_client: Option<&redis::Client>, counter: Counter, video_id: i32) -> Result<bool, sqlx::Error> {
+    if let Some(redis_client) = redis_client {
+        match increment_in_redis(redis_client, counter, video_id).await {
+            Ok(()) => return Ok(true),
+            Err(e) => warn!("Failed to count {} in Redis, writing to Postgres instead: {:?}", counter.column(), e),
+        }
+    }
+    increment_in_postgres(db_pool, counter, video_id).await?;
+    Ok(false)
+}
+
+async fn pending_in_redis(redis_client: &redis::Client, counter: Counter, video_ids: &[i32]) -> redis::RedisResult<HashMap<i32, i64>> {
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        let mut pending = HashMap::new();
+        for key in [counter.key(), counter.flushing_key()] {
+            let counts: Vec<Option<i64>> = redis::cmd("HMGET").arg(&key).arg(video_ids).query_async(&mut conn).await?;
+            for (video_id, count) in video_ids.iter().zip(counts) {
+                *pending.entry(*video_id).or_insert(0) += count.unwrap_or(0);
+            }
+        }
+        Ok(pending)
+    })
+    .await
+}
+
+// Increments of the videos not yet flushed to Postgres. Empty without Redis, or if it fails,
+// in which case responses just show the flushed counts.
+pub async fn pending(redis_client: Option<&redis::Client>, counter: Counter, video_ids: &[i32]) -> HashMap<i32, i64> {
+    let Some(redis_client) = redis_client.filter(|_| !video_ids.is_empty()) else {
+        return HashMap::new();
+    };
+    pending_in_redis(redis_client, counter, video_ids).await.unwrap_or_else(|e| {
+        warn!("Failed to read pending {} from Redis: {:?}", counter.column(), e);
+        HashMap::new()
+    })
+}
+
+pub fn apply_pending(videos: &mut [Video], counter: Counter, pending: &HashMap<i32, i64>) {
+    for video in videos {
+        let Some(delta) = pending.get(&video.id) else {
+            continue;
+        };
+        let value = match counter {
+            Counter::Views => &mut video.view_count,
+        };
+        let total = value.unwrap_or(0) as i64 + delta;
+        *value = Some(total.clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    }
+}
+
+// Bring the videos' counters up to date with what is still pending in Redis
+pub async fn with_pending(redis_client: Option<&redis::Client>, videos: &mut [Video]) {
+    let ids: Vec<i32> = videos.iter().map(|v| v.id).collect();
+    for counter in COUNTERS {
+        let pending = pending(redis_client, counter, &ids).await;
+        apply_pending(videos, counter, &pending);
+    }
+}
+
+// Add the increments to Postgres in a single statement. Videos deleted meanwhile are skipped.
+pub async fn apply_deltas(db_pool: &PgPool, counter: Counter, deltas: &[(i32, i64)]) -> Result<u64, sqlx::Error> {
+    if deltas.is_empty() {
+        return Ok(0);
+    }
+    let (ids, counts): (Vec<i32>, Vec<i64>) = deltas.iter().copied().unzip();
+    let result = sqlx::query(&format!(
+        "UPDATE videos SET {0} = COALESCE({0}, 0) + d.delta
+         FROM unnest($1::int[], $2::bigint[]) AS d(id, delta)
+         WHERE videos.id = d.id",
+        counter.column()
+    ))
+    .bind(&ids)
+    .bind(&counts)
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Move the counter's pending increments into Postgres, returning how many videos were updated.
+// The hash is renamed first so increments arriving during the flush start a new one. Every
+// replica runs the flusher, so a flush only goes ahead while it holds the counter's lock;
+// otherwise another replica could retry the renamed hash while it is being applied. Should the
+// process die between the update and the delete, that batch is counted twice; counts are
+// approximate anyway.
+pub async fn flush(db_pool: &PgPool, redis_client: &redis::Client, counter: Counter) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut conn = circuit_breaker::redis_connection(redis_client).await?;
+    let lock_token = uuid::Uuid::new_v4().to_string();
+    let locked: Option<String> = redis::cmd("SET")
+        .arg(counter.flush_lock_key())
+        .arg(&lock_token)
+        .arg("NX")
+        .arg("PX")
+        .arg(FLUSH_LOCK_TTL_MILLIS)
+        .query_async(&mut conn)
+        .await?;
+    if locked.is_none() {
+        return Ok(0);
+    }
+
+    let flushed = flush_locked(db_pool, &mut conn, counter).await;
+    let released: redis::RedisResult<i32> = redis::Script::new(RELEASE_LOCK_SCRIPT)
+        .key(counter.flush_lock_key())
+        .arg(&lock_token)
+        .invoke_async(&mut conn)
+        .await;
+    if let Err(e) = released {
+        error!("Failed to release the {} flush lock: {:?}", counter.column(), e);
+    }
+    flushed
+}
+
+async fn flush_locked(db_pool: &PgPool, conn: &mut redis::aio::Connection, counter: Counter) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let flushing_key = counter.flushing_key();
+
+    let retrying: bool = conn.exists(&flushing_key).await?;
+    if !retrying {
+        let has_pending: bool = conn.exists(counter.key()).await?;
+        if !has_pending {
+            return Ok(0);
+        }
+        conn.rename::<_, _, ()>(counter.key(), &flushing_key).await?;
+    }
+
+    let pending: HashMap<String, i64> = conn.hgetall(&flushing_key).await?;
+    let deltas: Vec<(i32, i64)> = pending
+        .into_iter()
+        .filter_map(|(video_id, delta)| Some((video_id.parse().ok()?, delta)))
+        .collect();
+    let updated = apply_deltas(db_pool, counter, &deltas).await?;
+    conn.del::<_, ()>(&flushing_key).await?;
+    Ok(updated)
+}
+
+// Periodically flush every counter. Interval is configurable via COUNTER_FLUSH_INTERVAL_SECS.
+pub async fn run_counter_flusher(db_pool: PgPool, redis_client: redis::Client) {
+    let interval_secs = std::env::var("COUNTER_FLUSH_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(10);
+
+    info!("Starting counter flusher (interval: {} seconds)", interval_secs);
+
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        for counter in COUNTERS {
+            match flush(&db_pool, &redis_client, counter).await {
+                Ok(0) => {}
+                Ok(updated) => info!("Flushed {} for {} videos", counter.column(), updated),
+                Err(e) => error!("Error flushing {}: {:?}", counter.column(), e),
+            }
+        }
+    }
+}
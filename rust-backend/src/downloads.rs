@@ -0,0 +1,73 @@
+use std::env;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use futures::stream::{self, Stream};
+use futures::TryStreamExt;
+
+use crate::storage::BodyStream;
+
+// Per connection limit when DOWNLOAD_RATE_LIMIT_BYTES is not set; 0 turns limiting off
+const DEFAULT_RATE_LIMIT_BYTES: u64 = 2 * 1024 * 1024;
+
+pub fn rate_limit() -> u64 {
+    env::var("DOWNLOAD_RATE_LIMIT_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RATE_LIMIT_BYTES)
+}
+
+// Keeps a connection's average throughput at or under `bytes_per_sec`
+pub struct Throttle {
+    bytes_per_sec: u64,
+    started: Instant,
+    sent: u64,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self { bytes_per_sec, started: Instant::now(), sent: 0 }
+    }
+
+    // Record `len` more bytes sent `elapsed` after the start and return how long to wait
+    // before sending anything else
+    pub fn record(&mut self, len: usize, elapsed: Duration) -> Duration {
+        self.sent += len as u64;
+        if self.bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+        let due = Duration::from_secs_f64(self.sent as f64 / self.bytes_per_sec as f64);
+        due.saturating_sub(elapsed)
+    }
+}
+
+// A stored object's body as a response stream, paced to `bytes_per_sec`
+pub fn throttled(body: BodyStream, bytes_per_sec: u64) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    stream::unfold((body, Throttle::new(bytes_per_sec)), |(mut body, mut throttle)| async move {
+        match body.try_next().await {
+            Ok(Some(chunk)) => {
+                let wait = throttle.record(chunk.len(), throttle.started.elapsed());
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+                Some((Ok(chunk), (body, throttle)))
+            }
+            Ok(None) => None,
+            Err(e) => Some((Err(actix_web::error::ErrorInternalServerError(e)), (body, throttle))),
+        }
+    })
+}
+
+// File name offered for a download: the title reduced to characters that are safe in a
+// Content-Disposition header, tagged with the quality
+pub fn attachment_filename(title: &str, quality: Option<&str>, extension: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == ' ' || c == '.' || c == '_');
+    let stem = if cleaned.is_empty() { "video" } else { cleaned };
+    match quality {
+        Some(quality) => format!("{} ({}).{}", stem, quality, extension),
+        None => format!("{}.{}", stem, extension),
+    }
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::feature_flags::{self, Flags};
+use crate::models::{Video, VideoAudioTrack, VideoChapter, VideoSpherical, VideoTranscript};
+use crate::playback_tokens;
+use crate::premieres;
+use crate::signed_urls;
+
+// Everything the /embed/{id} iframe player needs, in one response instead of the video, its
+// chapters, transcript, audio tracks and spherical metadata separately. Media URLs are signed, so
+// the player works where HOTLINK_REQUIRE_SIGNED_URLS is on.
+
+#[derive(Debug, Serialize)]
+pub struct EmbedCaptions {
+    pub language: Option<String>,
+    pub auto_generated: bool, // transcripts are machine made
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbedChapter {
+    pub title: String,
+    pub start_time: f64,
+}
+
+// What the uploader and the site's feature flags let embedded viewers do
+#[derive(Debug, Serialize)]
+pub struct EmbedFeatures {
+    pub download: bool,
+    pub comments: bool,
+    pub watch_party: bool,
+    pub requires_sign_in: bool, // for encrypted HLS keys, or playback tokens where they're required
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbedConfig {
+    pub id: i32,
+    pub title: String,
+    pub duration: Option<i32>,
+    pub watch_url: String,
+    pub poster_url: Option<String>,
+    // One of the two, depending on whether the video is encrypted
+    pub stream_url: Option<String>,
+    pub hls_url: Option<String>,
+    // Set while the premiere is upcoming, which only its uploader gets a config for
+    pub premiere_at: Option<NaiveDateTime>,
+    pub captions: Vec<EmbedCaptions>,
+    pub chapters: Vec<EmbedChapter>,
+    pub comments_url: String, // the comment track as WebVTT, for the overlay
+    pub audio_tracks: Vec<VideoAudioTrack>,
+    pub spherical: Option<VideoSpherical>, // null for flat videos
+    pub loudness_lufs: Option<f64>,
+    pub features: EmbedFeatures,
+}
+
+// The player's config for `video` with its chapters and transcript. Audio tracks and spherical
+// metadata are left empty for the caller to fill in.
+pub fn config(
+    video: &Video,
+    chapters: &[VideoChapter],
+    transcript: Option<&VideoTranscript>,
+    flags: &Flags,
+    base_url: &str,
+    now: NaiveDateTime,
+) -> EmbedConfig {
+    let timestamp = now.and_utc().timestamp();
+    let stream_url = (!video.hls_encrypted).then(|| signed_urls::signed_url(&signed_urls::stream_path(video.id), timestamp));
+    let hls_url = video.hls_encrypted.then(|| signed_urls::signed_url(&signed_urls::hls_playlist_path(video.id), timestamp));
+
+    EmbedConfig {
+        id: video.id,
+        title: video.title.clone(),
+        duration: video.duration,
+        watch_url: format!("{}/video/{}", base_url, video.id),
+        poster_url: signed_urls::thumbnail_url(video, timestamp),
+        stream_url,
+        hls_url,
+        premiere_at: video.premiere_at.filter(|_| premieres::upcoming(video, now)),
+        captions: transcript
+            .map(|transcript| EmbedCaptions {
+                language: transcript.language.clone(),
+                auto_generated: true,
+                url: format!("/api/videos/{}/transcript?format=vtt", video.id),
+            })
+            .into_iter()
+            .collect(),
+        chapters: chapters
+            .iter()
+            .filter(|chapter| chapter.accepted)
+            .map(|chapter| EmbedChapter { title: chapter.title.clone(), start_time: chapter.start_time })
+            .collect(),
+        comments_url: format!("/api/videos/{}/comments/export?format=vtt", video.id),
+        audio_tracks: Vec::new(),
+        spherical: None,
+        loudness_lufs: video.loudness_lufs,
+        features: EmbedFeatures {
+            download: video.allow_download,
+            comments: flags.is_enabled(feature_flags::COMMENTS),
+            watch_party: flags.is_enabled(feature_flags::WATCH_PARTIES),
+            requires_sign_in: video.hls_encrypted || playback_tokens::required(),
+        },
+    }
+}
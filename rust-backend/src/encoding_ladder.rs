@@ -0,0 +1,157 @@
+use std::path::Path;
+use sqlx::PgPool;
+
+use crate::media;
+use crate::models::EncodingProfile;
+use crate::thumbnails;
+
+// Rather than every video getting the configured ladder, the source is analyzed first. Simple
+// content (slides, screen recordings, talking heads) looks fine with fewer renditions at a
+// higher CRF, while busy, high-motion content keeps every rung and gets a lower CRF. Set
+// PER_TITLE_ENCODING=false to transcode every video with the fixed ladder.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Complexity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Complexity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Complexity::Low => "low",
+            Complexity::Medium => "medium",
+            Complexity::High => "high",
+        }
+    }
+
+    // x264 CRF for the renditions; Medium is what every video got before per-title encoding
+    pub fn crf(self) -> i32 {
+        match self {
+            Complexity::Low => 27,
+            Complexity::Medium => 23,
+            Complexity::High => 21,
+        }
+    }
+}
+
+// Pairs of frames compared for motion, spread across the video like thumbnail candidates
+pub const MOTION_SAMPLES: usize = 5;
+
+// Seconds between the two frames of a pair
+pub const MOTION_GAP_SECS: f64 = 0.5;
+
+// Mean share of pixel intensity changing between the frames of a pair
+const LOW_MOTION: f64 = 0.015;
+const HIGH_MOTION: f64 = 0.06;
+
+// Sources encoded this sparsely have little detail to preserve
+const LOW_BITS_PER_PIXEL: f64 = 0.03;
+
+// What ffprobe and the sampled frames tell us about a source
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SourceStats {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub fps: Option<f64>,
+    pub bitrate: Option<u64>, // bits per second
+    pub motion: Option<f64>,  // 0.0 for a still image
+}
+
+impl SourceStats {
+    // Bits spent per pixel per frame
+    pub fn bits_per_pixel(&self) -> Option<f64> {
+        let pixels = f64::from(self.width?) * f64::from(self.height?) * self.fps?;
+        let bitrate = self.bitrate? as f64;
+        (pixels > 0.0).then(|| bitrate / pixels)
+    }
+}
+
+pub fn enabled() -> bool {
+    std::env::var("PER_TITLE_ENCODING").map_or(true, |v| v != "false")
+}
+
+// Mean absolute difference of two equally sized grayscale frames, as a share of full scale.
+// None if either frame is missing, e.g. past the end of the video.
+pub fn frame_difference(a: &[u8], b: &[u8]) -> Option<f64> {
+    let pixels = a.len().min(b.len());
+    if pixels == 0 {
+        return None;
+    }
+    let total: u64 = a.iter().zip(b).map(|(x, y)| u64::from(x.abs_diff(*y))).sum();
+    Some(total as f64 / pixels as f64 / 255.0)
+}
+
+// Average of the sampled frame differences
+pub fn motion(differences: &[f64]) -> Option<f64> {
+    (!differences.is_empty()).then(|| differences.iter().sum::<f64>() / differences.len() as f64)
+}
+
+// Sources we know nothing about are treated as Medium, which is the fixed ladder
+pub fn classify(stats: &SourceStats) -> Complexity {
+    if stats.motion.is_some_and(|m| m >= HIGH_MOTION) {
+        return Complexity::High;
+    }
+    if stats.motion.is_some_and(|m| m < LOW_MOTION) || stats.bits_per_pixel().is_some_and(|bpp| bpp < LOW_BITS_PER_PIXEL) {
+        return Complexity::Low;
+    }
+    Complexity::Medium
+}
+
+// The configured heights this source gets, tallest first, never upscaling. Low complexity sources
+// keep only the top and bottom rungs: the bottom for slow connections, and the top looks good
+// without the bandwidth steps in between.
+pub fn select_ladder(configured: &[i32], source_height: Option<i32>, complexity: Complexity) -> Vec<i32> {
+    let mut heights: Vec<i32> = configured
+        .iter()
+        .copied()
+        .filter(|h| source_height.is_none_or(|source_height| *h <= source_height))
+        .collect();
+    heights.sort_unstable_by(|a, b| b.cmp(a));
+    heights.dedup();
+
+    if complexity == Complexity::Low && heights.len() > 2 {
+        heights = vec![heights[0], heights[heights.len() - 1]];
+    }
+    heights
+}
+
+// Probe the source and compare pairs of frames across it for motion
+pub async fn analyze(source: &Path) -> Result<SourceStats, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stats = media::probe_source(source).await?;
+    let duration = media::probe_duration(source).await?;
+    let mut differences = Vec::with_capacity(MOTION_SAMPLES);
+    for offset in thumbnails::candidate_offsets(duration, MOTION_SAMPLES) {
+        let first = media::extract_gray_frame(source, offset, thumbnails::SCORE_WIDTH, thumbnails::SCORE_HEIGHT).await?;
+        let second = media::extract_gray_frame(source, offset + MOTION_GAP_SECS, thumbnails::SCORE_WIDTH, thumbnails::SCORE_HEIGHT).await?;
+        differences.extend(frame_difference(&first, &second));
+    }
+    stats.motion = motion(&differences);
+    Ok(stats)
+}
+
+pub async fn profile(db_pool: &PgPool, video_id: i32) -> Result<Option<EncodingProfile>, sqlx::Error> {
+    sqlx::query_as::<_, EncodingProfile>("SELECT * FROM video_encoding_profiles WHERE video_id = $1")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+pub async fn save_profile(db_pool: &PgPool, video_id: i32, stats: &SourceStats, complexity: Complexity, heights: &[i32]) -> Result<EncodingProfile, sqlx::Error> {
+    sqlx::query_as::<_, EncodingProfile>(
+        "INSERT INTO video_encoding_profiles (video_id, complexity, bitrate_kbps, motion, heights, crf, analyzed_at)
+         VALUES ($1, $2, $3, $4, $5, $6, NOW())
+         ON CONFLICT (video_id) DO UPDATE SET complexity = EXCLUDED.complexity, bitrate_kbps = EXCLUDED.bitrate_kbps,
+             motion = EXCLUDED.motion, heights = EXCLUDED.heights, crf = EXCLUDED.crf, analyzed_at = EXCLUDED.analyzed_at
+         RETURNING *"
+    )
+    .bind(video_id)
+    .bind(complexity.as_str())
+    .bind(stats.bitrate.map(|b| (b / 1000).min(i32::MAX as u64) as i32))
+    .bind(stats.motion)
+    .bind(heights)
+    .bind(complexity.crf())
+    .fetch_one(db_pool)
+    .await
+}
@@ -0,0 +1,148 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::http::header::RETRY_AFTER;
+use log::error;
+use serde_json::json;
+use thiserror::Error;
+
+// Postgres SQLSTATE codes we map to client errors
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+// Error type returned by HTTP handlers. The JSON body always has a human readable
+// `error` message and a machine-readable `code`.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+
+    #[error("Unauthorized: Invalid or missing token")]
+    Unauthorized,
+
+    #[error("{0}")]
+    PaymentRequired(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    // Sent with a Retry-After header
+    #[error("{message}")]
+    TooManyRequests { message: String, retry_after_secs: u64 },
+
+    #[error("Internal server error")]
+    Internal(String),
+
+    // A dependency such as Redis is down; the request may succeed later
+    #[error("{0}")]
+    ServiceUnavailable(String),
+
+    // The file is being restored from archive storage. Sent with a Retry-After header.
+    #[error("This video is being restored from archive storage, try again later")]
+    RestoreInProgress { retry_after_secs: u64 },
+
+    #[error("{}", database_error_message(.0))]
+    Database(#[from] sqlx::Error),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::PaymentRequired(_) => "payment_required",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::Internal(_) => "internal_error",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+            ApiError::RestoreInProgress { .. } => "restore_in_progress",
+            ApiError::Database(e) => match database_error_code(e) {
+                Some(UNIQUE_VIOLATION) => "conflict",
+                Some(FOREIGN_KEY_VIOLATION) => "invalid_reference",
+                _ if matches!(e, sqlx::Error::RowNotFound) => "not_found",
+                _ => "internal_error",
+            },
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidCredentials | ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ServiceUnavailable(_) | ApiError::RestoreInProgress { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(e) => match database_error_code(e) {
+                Some(UNIQUE_VIOLATION) => StatusCode::CONFLICT,
+                Some(FOREIGN_KEY_VIOLATION) => StatusCode::BAD_REQUEST,
+                _ if matches!(e, sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            match self {
+                ApiError::Internal(details) => error!("Internal error: {}", details),
+                other => error!("Request failed: {:?}", other),
+            }
+        }
+
+        let mut response = HttpResponse::build(status);
+        if let ApiError::TooManyRequests { retry_after_secs, .. } | ApiError::RestoreInProgress { retry_after_secs } = self {
+            response.insert_header((RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        response.json(json!({
+            "error": self.to_string(),
+            "code": self.code()
+        }))
+    }
+}
+
+pub fn is_unique_violation(e: &sqlx::Error) -> bool {
+    database_error_code(e) == Some(UNIQUE_VIOLATION)
+}
+
+// Name of the unique constraint or index a failed insert or update ran into
+pub fn violated_unique_constraint(e: &sqlx::Error) -> Option<&str> {
+    if !is_unique_violation(e) {
+        return None;
+    }
+    e.as_database_error()?.constraint()
+}
+
+fn database_error_code(e: &sqlx::Error) -> Option<&'static str> {
+    let code = e.as_database_error()?.code()?;
+    match code.as_ref() {
+        UNIQUE_VIOLATION => Some(UNIQUE_VIOLATION),
+        FOREIGN_KEY_VIOLATION => Some(FOREIGN_KEY_VIOLATION),
+        _ => None,
+    }
+}
+
+fn database_error_message(e: &sqlx::Error) -> &'static str {
+    match database_error_code(e) {
+        Some(UNIQUE_VIOLATION) => "Resource already exists",
+        Some(FOREIGN_KEY_VIOLATION) => "Referenced resource does not exist",
+        _ if matches!(e, sqlx::Error::RowNotFound) => "Resource not found",
+        _ => "Internal server error",
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use log::error;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::models::{ExperimentRequest, ExperimentUpdateRequest};
+
+// A/B experiments on ranking. Each signed-in user lands in the same variant of an experiment
+// every time, from a hash of the experiment key and user id, so no assignment is stored until
+// the user is exposed: ranking code asks for its experiment's variant when the variant changes
+// what the user is shown, which records the exposure. Anonymous visitors, and everyone while an
+// experiment isn't defined or active, get "control" and aren't counted.
+
+pub const CONTROL: &str = "control";
+
+// Experiments the ranking code knows about, and the variants it understands
+pub const HOME_FEED_RANKING: &str = "home_feed_ranking"; // control (most viewed) or recent
+pub const SEARCH_RANKING: &str = "search_ranking"; // control (newest) or most_viewed
+
+// Users are spread over this many buckets, which are split between variants by weight
+const BUCKETS: u64 = 10_000;
+
+const MAX_KEY_CHARS: usize = 64;
+const MAX_VARIANT_CHARS: usize = 32;
+const MAX_VARIANTS: usize = 10;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Experiment {
+    pub id: i32,
+    pub key: String,
+    pub description: Option<String>,
+    pub variants: Vec<String>,
+    pub variant_weights: Vec<i32>,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+// The user's bucket in an experiment, stable for as long as the key doesn't change
+pub fn bucket(key: &str, user_id: i32) -> u64 {
+    let digest = Sha256::digest(format!("{}:{}", key, user_id).as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix) % BUCKETS
+}
+
+// The variant a bucket falls in, each variant getting a share of the buckets by weight
+pub fn pick<'a>(variants: &'a [String], weights: &[i32], bucket: u64) -> Option<&'a str> {
+    let total: u64 = weights.iter().map(|w| (*w).max(0) as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let point = bucket * total / BUCKETS;
+    let mut cumulative = 0;
+    for (variant, weight) in variants.iter().zip(weights) {
+        cumulative += (*weight).max(0) as u64;
+        if point < cumulative {
+            return Some(variant);
+        }
+    }
+    None
+}
+
+pub fn validate(req: &ExperimentRequest) -> Result<(), String> {
+    let key_valid = !req.key.is_empty()
+        && req.key.chars().count() <= MAX_KEY_CHARS
+        && req.key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !key_valid {
+        return Err(format!("key must be 1 to {} lowercase letters, digits or underscores", MAX_KEY_CHARS));
+    }
+    if req.variants.len() < 2 || req.variants.len() > MAX_VARIANTS {
+        return Err(format!("An experiment needs 2 to {} variants", MAX_VARIANTS));
+    }
+    for (i, variant) in req.variants.iter().enumerate() {
+        if variant.name.trim().is_empty() || variant.name.chars().count() > MAX_VARIANT_CHARS {
+            return Err(format!("Variant names must be 1 to {} characters", MAX_VARIANT_CHARS));
+        }
+        if req.variants[..i].iter().any(|v| v.name == variant.name) {
+            return Err(format!("Variant '{}' is listed twice", variant.name));
+        }
+        if variant.weight < 0 {
+            return Err("Variant weights can't be negative".to_string());
+        }
+    }
+    if req.variants.iter().all(|v| v.weight == 0) {
+        return Err("At least one variant needs a weight above 0".to_string());
+    }
+    Ok(())
+}
+
+pub async fn create(db_pool: &PgPool, req: &ExperimentRequest) -> Result<Experiment, sqlx::Error> {
+    let names: Vec<&str> = req.variants.iter().map(|v| v.name.as_str()).collect();
+    let weights: Vec<i32> = req.variants.iter().map(|v| v.weight).collect();
+    sqlx::query_as::<_, Experiment>(
+        "INSERT INTO experiments (key, description, variants, variant_weights) VALUES ($1, $2, $3, $4) RETURNING *"
+    )
+    .bind(&req.key)
+    .bind(&req.description)
+    .bind(&names)
+    .bind(&weights)
+    .fetch_one(db_pool)
+    .await
+}
+
+pub async fn list(db_pool: &PgPool) -> Result<Vec<Experiment>, sqlx::Error> {
+    sqlx::query_as::<_, Experiment>("SELECT * FROM experiments ORDER BY created_at DESC, id DESC")
+        .fetch_all(db_pool)
+        .await
+}
+
+pub async fn get(db_pool: &PgPool, key: &str) -> Result<Option<Experiment>, sqlx::Error> {
+    sqlx::query_as::<_, Experiment>("SELECT * FROM experiments WHERE key = $1")
+        .bind(key)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Start or stop an experiment, or change its description. Variants can't change once users
+// have been split between them.
+pub async fn update(db_pool: &PgPool, key: &str, req: &ExperimentUpdateRequest) -> Result<Option<Experiment>, sqlx::Error> {
+    sqlx::query_as::<_, Experiment>(
+        "UPDATE experiments SET active = COALESCE($2, active), description = COALESCE($3, description)
+         WHERE key = $1 RETURNING *"
+    )
+    .bind(key)
+    .bind(req.active)
+    .bind(&req.description)
+    .fetch_optional(db_pool)
+    .await
+}
+
+async fn record_exposure(db_pool: &PgPool, experiment_id: i32, user_id: i32, variant: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO experiment_exposures (experiment_id, user_id, variant) VALUES ($1, $2, $3)
+         ON CONFLICT (experiment_id, user_id) DO UPDATE
+         SET exposures = experiment_exposures.exposures + 1, last_exposed_at = NOW()"
+    )
+    .bind(experiment_id)
+    .bind(user_id)
+    .bind(variant)
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
+
+// The variant of experiment `key` to show the user, recording that they were exposed to it.
+// Never fails: without a running experiment, or when the database has trouble, it's control.
+pub async fn variant(db_pool: &PgPool, key: &str, user_id: Option<i32>) -> String {
+    let Some(user_id) = user_id else {
+        return CONTROL.to_string();
+    };
+    let experiment = match get(db_pool, key).await {
+        Ok(Some(experiment)) if experiment.active => experiment,
+        Ok(_) => return CONTROL.to_string(),
+        Err(e) => {
+            error!("Error loading experiment {}: {:?}", key, e);
+            return CONTROL.to_string();
+        }
+    };
+    let Some(variant) = pick(&experiment.variants, &experiment.variant_weights, bucket(key, user_id)) else {
+        return CONTROL.to_string();
+    };
+    if let Err(e) = record_exposure(db_pool, experiment.id, user_id, variant).await {
+        error!("Error recording exposure of user {} to experiment {}: {:?}", user_id, key, e);
+    }
+    variant.to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariantAssignments {
+    pub variant: String,
+    pub users: i64,
+    pub exposures: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExperimentReport {
+    #[serde(flatten)]
+    pub experiment: Experiment,
+    pub assignments: Vec<VariantAssignments>,
+}
+
+// The experiment with how many users were exposed to each of its variants, and how often
+pub async fn report(db_pool: &PgPool, experiment: Experiment) -> Result<ExperimentReport, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT variant, COUNT(*), COALESCE(SUM(exposures), 0) FROM experiment_exposures
+         WHERE experiment_id = $1 GROUP BY variant"
+    )
+    .bind(experiment.id)
+    .fetch_all(db_pool)
+    .await?;
+    let mut counts: HashMap<String, (i64, i64)> = rows.into_iter().map(|(variant, users, exposures)| (variant, (users, exposures))).collect();
+    let assignments = experiment
+        .variants
+        .iter()
+        .map(|variant| {
+            let (users, exposures) = counts.remove(variant).unwrap_or((0, 0));
+            VariantAssignments { variant: variant.clone(), users, exposures }
+        })
+        .collect();
+    Ok(ExperimentReport { experiment, assignments })
+}
@@ -0,0 +1,264 @@
+use bytes::Bytes;
+use chrono::{NaiveDate, NaiveDateTime};
+use futures::TryStreamExt;
+use log::error;
+use sqlx::{PgPool, Row};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+// Rows are sent to the client in chunks of about this size rather than one write per row
+const CHUNK_BYTES: usize = 32 * 1024;
+
+// Chunks buffered ahead of a slow client before the database cursor is paused
+const CHANNEL_CAPACITY: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("csv") => Ok(ExportFormat::Csv),
+            Some("ndjson") | Some("jsonl") => Ok(ExportFormat::Ndjson),
+            Some(other) => Err(format!("Unknown export format '{}'; use csv or ndjson", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    expr: &'static str,
+    list: bool, // a text array, joined with ';' in CSV
+}
+
+const fn field(name: &'static str, expr: &'static str) -> Field {
+    Field { name, expr, list: false }
+}
+
+// An exportable table: its fields in default order and the column the date filters apply to
+#[derive(Debug)]
+pub struct Dataset {
+    pub name: &'static str,
+    from: &'static str,
+    date_column: &'static str,
+    order_by: &'static str,
+    pub fields: &'static [Field],
+}
+
+pub const DATASETS: [Dataset; 3] = [
+    Dataset {
+        name: "videos",
+        from: "videos v LEFT JOIN users u ON u.id = v.uploaded_by",
+        date_column: "v.upload_date",
+        order_by: "v.id",
+        fields: &[
+            field("id", "v.id"),
+            field("title", "v.title"),
+            field("description", "v.description"),
+            field("uploaded_by", "v.uploaded_by"),
+            field("uploader", "u.username"),
+            field("upload_date", "v.upload_date"),
+            field("updated_at", "v.updated_at"),
+            field("view_count", "v.view_count"),
+            field("duration", "v.duration"),
+            Field { name: "category_ids", expr: "ARRAY(SELECT vc.category_id FROM videos_categories vc WHERE vc.video_id = v.id ORDER BY vc.position)", list: true },
+            Field { name: "tags", expr: "v.tags", list: true },
+            field("moderation_status", "v.moderation_status"),
+            field("processing_status", "v.processing_status"),
+            field("allow_download", "v.allow_download"),
+            field("deleted_at", "v.deleted_at"),
+        ],
+    },
+    Dataset {
+        name: "users",
+        from: "users u",
+        date_column: "u.created_at",
+        order_by: "u.id",
+        fields: &[
+            field("id", "u.id"),
+            field("username", "u.username"),
+            field("email", "u.email"),
+            field("created_at", "u.created_at"),
+            field("is_admin", "u.is_admin"),
+            field("video_count", "(SELECT COUNT(*) FROM videos v WHERE v.uploaded_by = u.id AND v.deleted_at IS NULL)"),
+        ],
+    },
+    Dataset {
+        name: "views",
+        from: "video_views vv JOIN videos v ON v.id = vv.video_id LEFT JOIN users u ON u.id = vv.user_id",
+        date_column: "vv.viewed_at",
+        order_by: "vv.id",
+        fields: &[
+            field("id", "vv.id"),
+            field("video_id", "vv.video_id"),
+            field("video_title", "v.title"),
+            field("user_id", "vv.user_id"),
+            field("username", "u.username"),
+            field("country", "vv.country"),
+            field("viewed_at", "vv.viewed_at"),
+        ],
+    },
+];
+
+pub fn dataset(name: &str) -> Option<&'static Dataset> {
+    DATASETS.iter().find(|d| d.name == name)
+}
+
+// The requested comma-separated fields in the order given, or all of them
+pub fn select_fields(dataset: &'static Dataset, requested: Option<&str>) -> Result<Vec<&'static Field>, String> {
+    let Some(requested) = requested.filter(|r| !r.trim().is_empty()) else {
+        return Ok(dataset.fields.iter().collect());
+    };
+    let mut fields: Vec<&'static Field> = Vec::new();
+    for name in requested.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let field = dataset.fields.iter().find(|f| f.name == name).ok_or_else(|| {
+            let known: Vec<&str> = dataset.fields.iter().map(|f| f.name).collect();
+            format!("Unknown {} field '{}'; expected one of {}", dataset.name, name, known.join(", "))
+        })?;
+        if !fields.iter().any(|f| f.name == field.name) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+// `from` and `to` as YYYY-MM-DD, both inclusive, turned into [start, end) timestamps
+pub fn date_range(from: Option<&str>, to: Option<&str>) -> Result<(Option<NaiveDateTime>, Option<NaiveDateTime>), String> {
+    let parse = |name: &str, value: &str| {
+        NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| format!("{} must be a date like 2025-08-01", name))
+    };
+    let from = from.map(|v| parse("from", v)).transpose()?;
+    let to = to.map(|v| parse("to", v)).transpose()?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err("from must not be after to".to_string());
+        }
+    }
+    Ok((
+        from.and_then(|d| d.and_hms_opt(0, 0, 0)),
+        to.and_then(|d| d.succ_opt()).and_then(|d| d.and_hms_opt(0, 0, 0)),
+    ))
+}
+
+// NDJSON rows are built by Postgres so numbers, booleans and nulls keep their JSON types; CSV
+// rows select every field as text
+pub fn build_query(dataset: &Dataset, fields: &[&Field], format: ExportFormat) -> String {
+    let select = match format {
+        ExportFormat::Ndjson => {
+            let pairs: Vec<String> = fields.iter().map(|f| format!("'{}', {}", f.name, f.expr)).collect();
+            format!("json_build_object({})::text", pairs.join(", "))
+        }
+        ExportFormat::Csv => fields
+            .iter()
+            .map(|f| if f.list { format!("array_to_string({}, ';')", f.expr) } else { format!("({})::text", f.expr) })
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    format!(
+        "SELECT {} FROM {} WHERE ($1::timestamp IS NULL OR {date} >= $1) AND ($2::timestamp IS NULL OR {date} < $2) ORDER BY {}",
+        select,
+        dataset.from,
+        dataset.order_by,
+        date = dataset.date_column,
+    )
+}
+
+// Spreadsheets run text starting with one of these as a formula, so such values get a leading
+// quote. Numbers, negative ones included, are left as they are.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+fn csv_value(value: &str) -> String {
+    let value = if value.starts_with(FORMULA_PREFIXES) && value.parse::<f64>().is_err() {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+// One CSV record with a CRLF line ending; NULLs are written as empty values
+pub fn csv_line<'a>(values: impl IntoIterator<Item = Option<&'a str>>) -> String {
+    let values: Vec<String> = values.into_iter().map(|v| v.map(csv_value).unwrap_or_default()).collect();
+    format!("{}\r\n", values.join(","))
+}
+
+pub struct Export {
+    pub dataset: &'static Dataset,
+    pub fields: Vec<&'static Field>,
+    pub format: ExportFormat,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+type Sender = mpsc::Sender<Result<Bytes, std::io::Error>>;
+
+// Send a chunk, returning false once the client has gone away
+async fn send(tx: &Sender, buffer: &mut String) -> bool {
+    let chunk = Bytes::from(std::mem::take(buffer));
+    tx.send(Ok(chunk)).await.is_ok()
+}
+
+async fn write_rows(db_pool: &PgPool, export: &Export, tx: &Sender) -> Result<(), sqlx::Error> {
+    let sql = build_query(export.dataset, &export.fields, export.format);
+    let mut rows = sqlx::query(&sql).bind(export.from).bind(export.to).fetch(db_pool);
+
+    let mut buffer = String::new();
+    if export.format == ExportFormat::Csv {
+        buffer.push_str(&csv_line(export.fields.iter().map(|f| Some(f.name))));
+    }
+    while let Some(row) = rows.try_next().await? {
+        match export.format {
+            ExportFormat::Ndjson => {
+                buffer.push_str(row.try_get::<&str, _>(0)?);
+                buffer.push('\n');
+            }
+            ExportFormat::Csv => {
+                let values = (0..export.fields.len()).map(|i| row.try_get::<Option<&str>, _>(i)).collect::<Result<Vec<_>, _>>()?;
+                buffer.push_str(&csv_line(values));
+            }
+        }
+        if buffer.len() >= CHUNK_BYTES && !send(tx, &mut buffer).await {
+            return Ok(());
+        }
+    }
+    if !buffer.is_empty() {
+        send(tx, &mut buffer).await;
+    }
+    Ok(())
+}
+
+// The export as a response body, read from a database cursor as the client consumes it so
+// the whole result is never held in memory. A database error part way through ends the
+// response with an error, which the client sees as a truncated transfer.
+pub fn stream(db_pool: PgPool, export: Export) -> ReceiverStream<Result<Bytes, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        if let Err(e) = write_rows(&db_pool, &export, &tx).await {
+            error!("Export of {} failed: {:?}", export.dataset.name, e);
+            let _ = tx.send(Err(std::io::Error::other("Export failed"))).await;
+        }
+    });
+    ReceiverStream::new(rx)
+}
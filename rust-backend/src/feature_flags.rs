@@ -0,0 +1,255 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web;
+use chrono::NaiveDateTime;
+use log::{error, warn};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::sync::Mutex;
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::AppState;
+
+// Switches admins flip at runtime, without a redeploy: whole subsystems, and a read-only
+// maintenance mode refusing every write outside the admin API. Flags live in the database and
+// are cached in Redis for every process, and for a few seconds in each process so requests
+// don't wait on Redis. Changing a flag clears both, so it applies everywhere within seconds.
+
+pub const UPLOADS: &str = "uploads";
+pub const SCRAPING: &str = "scraping"; // checked by the scraper before taking a job
+pub const WATCH_PARTIES: &str = "watch_parties";
+pub const COMMENTS: &str = "comments";
+pub const MAINTENANCE: &str = "maintenance";
+
+// Every flag and its value while it has no row
+pub const FLAGS: [(&str, bool); 5] = [
+    (UPLOADS, true),
+    (SCRAPING, true),
+    (WATCH_PARTIES, true),
+    (COMMENTS, true),
+    (MAINTENANCE, false),
+];
+
+pub const MAX_MESSAGE_CHARS: usize = 500;
+
+const CACHE_KEY: &str = "feature_flags";
+const REDIS_TTL_SECS: usize = 300;
+const LOCAL_TTL: Duration = Duration::from_secs(5);
+
+static LOCAL_CACHE: std::sync::Mutex<Option<(Instant, Flags)>> = std::sync::Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub message: Option<String>,
+    pub updated_by: Option<i32>,
+    pub updated_at: Option<NaiveDateTime>, // None while the flag has its default
+}
+
+// The value of every flag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flags {
+    pub flags: Vec<FeatureFlag>,
+}
+
+impl Flags {
+    // Defaults filled in for flags without a row; rows of flags no longer known are ignored
+    pub fn from_rows(rows: Vec<FeatureFlag>) -> Self {
+        let flags = FLAGS
+            .iter()
+            .map(|(name, default)| {
+                rows.iter().find(|row| row.name == *name).cloned().unwrap_or(FeatureFlag {
+                    name: name.to_string(),
+                    enabled: *default,
+                    message: None,
+                    updated_by: None,
+                    updated_at: None,
+                })
+            })
+            .collect();
+        Flags { flags }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FeatureFlag> {
+        self.flags.iter().find(|flag| flag.name == name)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|flag| flag.enabled)
+    }
+}
+
+pub fn is_known(name: &str) -> bool {
+    FLAGS.iter().any(|(known, _)| *known == name)
+}
+
+// Requests to a subsystem's paths, or only the ones changing something
+struct Gate {
+    flag: &'static str,
+    prefix: &'static str,
+    writes_only: bool,
+}
+
+// Reading comments keeps working with comments off, so players still show them
+const GATES: [Gate; 4] = [
+    Gate { flag: UPLOADS, prefix: "/api/uploads", writes_only: false },
+    Gate { flag: WATCH_PARTIES, prefix: "/api/watchparty", writes_only: false },
+    Gate { flag: WATCH_PARTIES, prefix: "/api/ws/watchparty", writes_only: false },
+    Gate { flag: COMMENTS, prefix: "/api/comments", writes_only: true },
+];
+
+// Admins have to be able to sign in and turn maintenance off again
+const MAINTENANCE_EXEMPT: [&str; 4] = ["/api/admin", "/api/auth/login", "/api/auth/refresh", "/api/auth/logout"];
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn is_write(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+// The flag refusing a request, if any
+pub fn blocking_flag<'a>(flags: &'a Flags, method: &Method, path: &str) -> Option<&'a FeatureFlag> {
+    if is_write(method) && !MAINTENANCE_EXEMPT.iter().any(|prefix| is_under(path, prefix)) {
+        if let Some(maintenance) = flags.get(MAINTENANCE).filter(|flag| flag.enabled) {
+            return Some(maintenance);
+        }
+    }
+    GATES
+        .iter()
+        .filter(|gate| is_under(path, gate.prefix) && (!gate.writes_only || is_write(method)))
+        .find_map(|gate| flags.get(gate.flag).filter(|flag| !flag.enabled))
+}
+
+pub fn unavailable_message(flag: &FeatureFlag) -> String {
+    if let Some(message) = flag.message.as_deref().filter(|m| !m.trim().is_empty()) {
+        return message.to_string();
+    }
+    match flag.name.as_str() {
+        MAINTENANCE => "The site is read-only for maintenance, please try again later".to_string(),
+        UPLOADS => "Uploads are turned off for now".to_string(),
+        WATCH_PARTIES => "Watch parties are turned off for now".to_string(),
+        COMMENTS => "Commenting is turned off for now".to_string(),
+        other => format!("{} is turned off for now", other),
+    }
+}
+
+fn locally_cached() -> Option<Flags> {
+    let cache = LOCAL_CACHE.lock().unwrap();
+    cache.as_ref().filter(|(at, _)| at.elapsed() < LOCAL_TTL).map(|(_, flags)| flags.clone())
+}
+
+fn cache_locally(flags: Option<Flags>) {
+    *LOCAL_CACHE.lock().unwrap() = flags.map(|flags| (Instant::now(), flags));
+}
+
+async fn redis_cached(redis_client: &redis::Client) -> redis::RedisResult<Option<Flags>> {
+    let cached: Option<String> = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.get(CACHE_KEY).await
+    })
+    .await?;
+    Ok(cached.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+async fn cache_in_redis(redis_client: &redis::Client, flags: &Flags) -> redis::RedisResult<()> {
+    let json = serde_json::to_string(flags).unwrap_or_default();
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.set_ex(CACHE_KEY, json, REDIS_TTL_SECS).await
+    })
+    .await
+}
+
+pub async fn load(db_pool: &PgPool, redis_client: Option<&redis::Client>) -> Result<Flags, sqlx::Error> {
+    if let Some(flags) = locally_cached() {
+        return Ok(flags);
+    }
+    if let Some(redis_client) = redis_client {
+        match redis_cached(redis_client).await {
+            Ok(Some(flags)) => {
+                cache_locally(Some(flags.clone()));
+                return Ok(flags);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Feature flag cache unavailable, reading the database: {:?}", e),
+        }
+    }
+
+    let rows = sqlx::query_as::<_, FeatureFlag>("SELECT name, enabled, message, updated_by, updated_at FROM feature_flags")
+        .fetch_all(db_pool)
+        .await?;
+    let flags = Flags::from_rows(rows);
+    if let Some(redis_client) = redis_client {
+        if let Err(e) = cache_in_redis(redis_client, &flags).await {
+            warn!("Failed to cache feature flags: {:?}", e);
+        }
+    }
+    cache_locally(Some(flags.clone()));
+    Ok(flags)
+}
+
+// Set a flag and drop the cached values. If Redis can't be reached, other processes may keep the
+// old value until their Redis copy expires.
+pub async fn set(
+    db_pool: &PgPool,
+    redis_client: Option<&redis::Client>,
+    name: &str,
+    enabled: bool,
+    message: Option<&str>,
+    updated_by: i32,
+) -> Result<Flags, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO feature_flags (name, enabled, message, updated_by, updated_at) VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (name) DO UPDATE
+         SET enabled = EXCLUDED.enabled, message = EXCLUDED.message, updated_by = EXCLUDED.updated_by, updated_at = EXCLUDED.updated_at"
+    )
+    .bind(name)
+    .bind(enabled)
+    .bind(message)
+    .bind(updated_by)
+    .execute(db_pool)
+    .await?;
+
+    if let Some(redis_client) = redis_client {
+        let result = circuit_breaker::redis(redis_client, |mut conn| async move {
+            conn.del::<_, ()>(CACHE_KEY).await
+        })
+        .await;
+        if let Err(e) = result {
+            error!("Failed to clear cached feature flags after changing {}: {:?}", name, e);
+        }
+    }
+    cache_locally(None);
+    load(db_pool, redis_client).await
+}
+
+// Middleware refusing requests to subsystems that are off, and writes during maintenance, with
+// a 503. Has to run on the unversioned path. Requests pass if the flags can't be loaded.
+pub async fn check_features(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if let Some(state) = req.app_data::<web::Data<Arc<Mutex<AppState>>>>().cloned() {
+        let (db_pool, redis_client) = {
+            let state = state.lock().await;
+            (state.db_pool.clone(), state.redis_client.clone())
+        };
+        match load(&db_pool, redis_client.as_ref()).await {
+            Ok(flags) => {
+                if let Some(flag) = blocking_flag(&flags, req.method(), req.path()) {
+                    return Ok(req.error_response(ApiError::ServiceUnavailable(unavailable_message(flag))));
+                }
+            }
+            Err(e) => error!("Failed to load feature flags, letting the request through: {:?}", e),
+        }
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
@@ -0,0 +1,138 @@
+use sqlx::PgPool;
+
+use crate::models::Video;
+
+// Most recent videos included in a feed
+pub const FEED_ITEM_LIMIT: i64 = 50;
+
+// Videos that feeds and sitemaps publish. Encrypted videos are left out since feed readers and
+// crawlers can't play them.
+pub const PUBLIC_VIDEOS_SQL: &str = "SELECT * FROM videos WHERE deleted_at IS NULL AND moderation_status = 'approved' AND NOT hls_encrypted";
+
+// A feed's channel: who or what it covers and where it lives
+pub struct Channel<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub link: &'a str,     // the channel's page on the site
+    pub feed_url: &'a str, // this feed, for atom:link rel="self"
+}
+
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines aren't allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// MIME type of the stored file, from its extension
+pub fn enclosure_type(s3_key: &str) -> &'static str {
+    match s3_key.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).as_deref() {
+        Some("webm") => "video/webm",
+        Some("mov") => "video/quicktime",
+        Some("mkv") => "video/x-matroska",
+        _ => "video/mp4",
+    }
+}
+
+// HH:MM:SS as itunes:duration expects
+fn format_duration(seconds: i32) -> String {
+    let seconds = seconds.max(0);
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
+
+fn item(video: &Video, author: Option<&str>, base_url: &str) -> String {
+    let link = format!("{}/video/{}", base_url, video.id);
+    let mut item = String::from("    <item>\n");
+    item.push_str(&format!("      <title>{}</title>\n", escape_xml(&video.title)));
+    item.push_str(&format!("      <link>{}</link>\n", escape_xml(&link)));
+    item.push_str(&format!("      <guid isPermaLink=\"true\">{}</guid>\n", escape_xml(&link)));
+    if let Some(description) = video.description.as_deref().filter(|d| !d.is_empty()) {
+        item.push_str(&format!("      <description>{}</description>\n", escape_xml(description)));
+    }
+    if let Some(uploaded) = video.upload_date {
+        item.push_str(&format!("      <pubDate>{}</pubDate>\n", uploaded.and_utc().to_rfc2822()));
+    }
+    if let Some(author) = author {
+        item.push_str(&format!("      <itunes:author>{}</itunes:author>\n", escape_xml(author)));
+    }
+    // The size isn't stored, and 0 is what feeds conventionally use for an unknown length
+    let stream_url = escape_xml(&format!("{}/api/videos/{}/stream", base_url, video.id));
+    item.push_str(&format!(
+        "      <enclosure url=\"{}\" length=\"0\" type=\"{}\"/>\n",
+        stream_url,
+        enclosure_type(&video.s3_key)
+    ));
+    let duration_attr = video.duration.map(|d| format!(" duration=\"{}\"", d.max(0))).unwrap_or_default();
+    item.push_str(&format!(
+        "      <media:content url=\"{}\" type=\"{}\" medium=\"video\"{}/>\n",
+        stream_url,
+        enclosure_type(&video.s3_key),
+        duration_attr
+    ));
+    if let Some(name) = video.thumbnail_url.as_deref().and_then(|key| key.rsplit('/').next()) {
+        let thumbnail = escape_xml(&format!("{}/api/thumbnails/{}", base_url, name));
+        item.push_str(&format!("      <media:thumbnail url=\"{}\"/>\n", thumbnail));
+        item.push_str(&format!("      <itunes:image href=\"{}\"/>\n", thumbnail));
+    }
+    if let Some(duration) = video.duration {
+        item.push_str(&format!("      <itunes:duration>{}</itunes:duration>\n", format_duration(duration)));
+    }
+    item.push_str("    </item>\n");
+    item
+}
+
+// Pair each video with its uploader's username
+pub async fn with_authors(db_pool: &PgPool, videos: Vec<Video>) -> Result<Vec<(Video, Option<String>)>, sqlx::Error> {
+    let uploader_ids: Vec<i32> = videos.iter().filter_map(|v| v.uploaded_by).collect();
+    let usernames: std::collections::HashMap<i32, String> =
+        sqlx::query_as::<_, (i32, String)>("SELECT id, username FROM users WHERE id = ANY($1)")
+            .bind(&uploader_ids)
+            .fetch_all(db_pool)
+            .await?
+            .into_iter()
+            .collect();
+
+    Ok(videos
+        .into_iter()
+        .map(|v| {
+            let author = v.uploaded_by.and_then(|id| usernames.get(&id).cloned());
+            (v, author)
+        })
+        .collect())
+}
+
+// RSS 2.0 with the iTunes and Media RSS extensions podcast apps look for. `videos` pairs each
+// video with its uploader's username.
+pub fn rss(channel: &Channel, videos: &[(Video, Option<String>)], base_url: &str) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" \
+         xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+         xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" \
+         xmlns:media=\"http://search.yahoo.com/mrss/\">\n  <channel>\n",
+    );
+    xml.push_str(&format!("    <title>{}</title>\n", escape_xml(channel.title)));
+    xml.push_str(&format!("    <link>{}</link>\n", escape_xml(channel.link)));
+    xml.push_str(&format!("    <description>{}</description>\n", escape_xml(channel.description)));
+    xml.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(channel.feed_url)
+    ));
+    if let Some(last) = videos.iter().filter_map(|(v, _)| v.upload_date).max() {
+        xml.push_str(&format!("    <lastBuildDate>{}</lastBuildDate>\n", last.and_utc().to_rfc2822()));
+    }
+    for (video, author) in videos {
+        xml.push_str(&item(video, author.as_deref(), base_url));
+    }
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::CONTENT_TYPE;
+use serde_json::{Map, Value};
+
+// Response field naming. Responses at the unversioned /api paths have always mixed the
+// snake_case of database columns with the camelCase of a few request types, and clients depend on
+// that, so they keep it. Responses under /api/v1 have every key of their JSON in camelCase (see
+// api_versions). Requests take either naming at any path, see models.rs.
+
+// Objects under these keys are keyed by data rather than field names (tag names in search
+// facets, a custom theme's color names, webhook and audit payloads), and keep their keys
+const VERBATIM_KEYS: [&str; 4] = ["facets", "theme", "payload", "details"];
+
+// "watched_secs" -> "watchedSecs". Keys that aren't snake_case field names are left alone.
+pub fn camel_case(key: &str) -> Cow<'_, str> {
+    let snake_case = key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !snake_case || !key.contains('_') {
+        return Cow::Borrowed(key);
+    }
+    let mut camel = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper = !camel.is_empty();
+        } else if upper {
+            camel.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    Cow::Owned(camel)
+}
+
+// Rename every key of a JSON document to camelCase, except inside VERBATIM_KEYS
+pub fn camelize(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut camelized = Map::with_capacity(object.len());
+            for (key, value) in object {
+                let value = if VERBATIM_KEYS.contains(&key.as_str()) { value } else { camelize(value) };
+                let key = match camel_case(&key) {
+                    Cow::Borrowed(_) => key,
+                    Cow::Owned(camel) => camel,
+                };
+                camelized.insert(key, value);
+            }
+            Value::Object(camelized)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(camelize).collect()),
+        value => value,
+    }
+}
+
+fn is_json(response: &ServiceResponse<impl MessageBody>) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+}
+
+// Camelize a JSON response's body; other responses are returned as they are
+pub async fn camelize_response(response: ServiceResponse<BoxBody>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if !is_json(&response) {
+        return Ok(response);
+    }
+    let (http_req, response) = response.into_parts();
+    let (response, body) = response.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(document) => serde_json::to_vec(&camelize(document)).map_or(bytes, Into::into),
+        Err(_) => bytes,
+    };
+    Ok(ServiceResponse::new(http_req, response.set_body(BoxBody::new(bytes))))
+}
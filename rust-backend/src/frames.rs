@@ -0,0 +1,146 @@
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+use log::{error, warn};
+use sqlx::PgPool;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::storage::{ObjectStore, StorageResult};
+
+// Single frames of a video, for editors and clip pickers. A frame is rendered on request by
+// seeking into one of the video's renditions in place, without downloading it, and kept in S3
+// under frames/{video_id}/ so each time is only rendered once. Times are rounded down to whole
+// seconds, which keeps the number of frames a video can have to its length.
+//
+// Rendering is expensive, so a user may render FRAME_RENDERS_PER_MINUTE frames a minute (counted
+// in Redis; without Redis nothing is counted) and each replica renders at most
+// FRAME_RENDER_CONCURRENCY at once. Frames already rendered aren't limited.
+
+pub const FRAME_PREFIX: &str = "frames/";
+
+// Renders a user may start per minute when FRAME_RENDERS_PER_MINUTE is not set
+const DEFAULT_RENDERS_PER_MINUTE: u64 = 30;
+
+// Renders running at once per replica when FRAME_RENDER_CONCURRENCY is not set
+const DEFAULT_RENDER_CONCURRENCY: usize = 2;
+
+// How long a render waits for a free slot before the request is turned away
+const RENDER_SLOT_WAIT: Duration = Duration::from_secs(10);
+
+// Frames are grabbed from the tallest rendition up to this height, plenty for a 1280 wide JPEG
+const SOURCE_MAX_HEIGHT: i32 = 720;
+
+// How long ffmpeg may read the rendition through its presigned URL
+pub const SOURCE_URL_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+// The requested time in milliseconds, rounded down to a whole second. Times at or past the end of
+// a video of known length have no frame.
+pub fn frame_time(t: f64, video_duration: Option<i32>) -> Result<i64, String> {
+    if !t.is_finite() || t < 0.0 || t > i32::MAX as f64 {
+        return Err("t must be a non-negative number of seconds".to_string());
+    }
+    let time_ms = t.floor() as i64 * 1000;
+    if let Some(video_duration) = video_duration.filter(|d| *d > 0) {
+        if time_ms >= i64::from(video_duration) * 1000 {
+            return Err(format!("t must be less than the video's duration of {} seconds", video_duration));
+        }
+    }
+    Ok(time_ms)
+}
+
+pub fn renders_per_minute() -> u64 {
+    env::var("FRAME_RENDERS_PER_MINUTE").ok().and_then(|v| v.parse::<u64>().ok()).filter(|v| *v > 0).unwrap_or(DEFAULT_RENDERS_PER_MINUTE)
+}
+
+fn render_concurrency() -> usize {
+    env::var("FRAME_RENDER_CONCURRENCY").ok().and_then(|v| v.parse::<usize>().ok()).filter(|v| *v > 0).unwrap_or(DEFAULT_RENDER_CONCURRENCY)
+}
+
+static RENDER_SLOTS: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(render_concurrency()));
+
+pub fn renders_key(user_id: i32, minute: i64) -> String {
+    format!("frame_renders:{}:{}", user_id, minute)
+}
+
+async fn count_render(redis_client: &redis::Client, user_id: i32, minute: i64) -> redis::RedisResult<u64> {
+    let (renders,): (u64,) = circuit_breaker::redis(redis_client, |mut conn| async move {
+        redis::pipe()
+            .atomic()
+            .incr(renders_key(user_id, minute), 1)
+            .expire(renders_key(user_id, minute), 60)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+    })
+    .await?;
+    Ok(renders)
+}
+
+// Count a render against the user's minute, refusing it once they are over the limit
+pub async fn check_render_rate(redis_client: Option<&redis::Client>, user_id: i32, now: i64) -> Result<(), ApiError> {
+    let Some(redis_client) = redis_client else {
+        return Ok(());
+    };
+    match count_render(redis_client, user_id, now / 60).await {
+        Ok(renders) if renders > renders_per_minute() => {
+            warn!("User {} is rendering frames faster than {} a minute", user_id, renders_per_minute());
+            Err(ApiError::TooManyRequests {
+                message: "Too many new frames requested, try again in a minute".to_string(),
+                retry_after_secs: (60 - now.rem_euclid(60)).max(1) as u64,
+            })
+        }
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("Frame render limit unavailable in Redis: {:?}", e);
+            Ok(())
+        }
+    }
+}
+
+// A slot to render in, held until the permit is dropped
+pub async fn render_slot() -> Result<SemaphorePermit<'static>, ApiError> {
+    match tokio::time::timeout(RENDER_SLOT_WAIT, RENDER_SLOTS.acquire()).await {
+        Ok(Ok(permit)) => Ok(permit),
+        _ => Err(ApiError::ServiceUnavailable("Too many frames are being rendered, try again shortly".to_string())),
+    }
+}
+
+// The S3 key to grab frames from: the tallest rendition up to SOURCE_MAX_HEIGHT, else the
+// smallest one, or the original if the video has none yet
+pub async fn frame_source(db_pool: &PgPool, video_id: i32, original: &str) -> Result<String, sqlx::Error> {
+    let rendition: Option<String> = sqlx::query_scalar(
+        "SELECT s3_key FROM video_renditions WHERE video_id = $1
+         ORDER BY height <= $2 DESC, CASE WHEN height <= $2 THEN -height ELSE height END
+         LIMIT 1"
+    )
+    .bind(video_id)
+    .bind(SOURCE_MAX_HEIGHT)
+    .fetch_optional(db_pool)
+    .await?;
+    Ok(rendition.unwrap_or_else(|| original.to_string()))
+}
+
+pub fn frames_prefix(video_id: i32) -> String {
+    format!("{}{}/", FRAME_PREFIX, video_id)
+}
+
+pub fn frame_s3_key(video_id: i32, time_ms: i64) -> String {
+    format!("{}{}.jpg", frames_prefix(video_id), time_ms)
+}
+
+// Every frame rendered for the video, for cleaning up once it is purged
+pub async fn frame_keys(storage: &dyn ObjectStore, bucket: &str, video_id: i32) -> StorageResult<Vec<String>> {
+    let prefix = frames_prefix(video_id);
+    let mut keys = Vec::new();
+    let mut continuation = None;
+    loop {
+        let page = storage.list(bucket, &prefix, continuation).await?;
+        keys.extend(page.objects.into_iter().map(|object| object.key));
+        match page.next {
+            Some(next) => continuation = Some(next),
+            None => return Ok(keys),
+        }
+    }
+}
@@ -1,711 +1,4428 @@
-use actix_web::{web, Responder, post, get};
+use actix_web::{web, Responder, HttpResponse, post, get, put, delete, head, patch};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentEncoding, EntityTag, ETag, IfNoneMatch, CONTENT_LANGUAGE, VARY};
 use serde_json::json;
 use tokio::sync::Mutex;
 use std::sync::Arc;
-use log::{info, error};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use log::{info, error, warn};
 use std::env;
 
-use crate::websocket::broadcast_comment;
-use crate::models::{RegisterRequest, LoginRequest, CommentRequest, Comment, Video, User, Claims, UserSettingsRequest, Category};
-use crate::job_queue::DurationExtractionJob;
+use crate::websocket::{broadcast_comment, broadcast_comment_to_watch_party};
+use crate::models::{RegisterRequest, LoginRequest, RefreshRequest, CommentRequest, CommentListQuery, CommentSort, CommentVoteRequest, CommentExportQuery, Comment, Video, User, Claims, GuestClaims, UserSettingsRequest, Category, SearchHistoryEntry, SavedSearch, SavedSearchRequest, AdvancedSearchRequest, SearchFilterQuery, CreateUploadRequest, UploadSession, WatchParty, WatchPartyGuestRequest, WatchPartyGuestClaimRequest, WatchPartyInviteRequest, WatchPartyJoinQuery, WatchPartyScheduleRequest, WebhookRequest, TranscriptQuery, VideoChapter, ChapterRequest, ChapterQuery, ThumbnailSelectRequest, ModerationRequest, ModerationQueueQuery, StorageClassRequest, ExportQuery, ChangesQuery, RecommendationImport, HomeFeedQuery, ExperimentRequest, ExperimentUpdateRequest, FeatureFlagRequest, ImportRequest, VideoDetail, AudioTrackUploadQuery, SphericalRequest, EncryptionRequest, HlsPlaylistQuery, HlsKeyQuery, RestrictionsRequest, DownloadQuery, SignedUrlQuery, VideoRendition, ClipRequest, FrameQuery, VideoClip, CompilationRequest, OEmbedQuery, TranslationRequest, UserSession, PushSubscriptionRequest, PushUnsubscribeRequest, ChannelLayout, VideoListQuery, JobAttemptsQuery, VideoUpdateRequest, PlaybackReportQuery, PremiereRequest, PlaybackTokenRequest, PlaybackTokenResponse, PlaybackTokenQuery};
+use crate::job_history;
+use crate::job_queue::{MediaJob, MediaJobKind};
+use crate::saved_searches::record_search;
+use crate::notifications::{create_notification, get_notifications};
+use crate::search::{build_advanced_search_query, MAX_SEARCH_LIMIT};
+use crate::search_index::{self, SearchBackend};
+use crate::error::{ApiError, is_unique_violation, violated_unique_constraint};
+use crate::trash::TRASH_RETENTION_DAYS;
+use crate::uploads;
+use crate::audio_tracks;
+use crate::audit;
+use crate::bandwidth;
+use crate::categories;
+use crate::changes;
+use crate::auth_cookies;
+use crate::channels;
+use crate::counters::{self, Counter};
+use crate::chapters;
+use crate::clips;
+use crate::comment_export::{self, CommentExportFormat};
+use crate::compilations;
+use crate::downloads;
+use crate::embed;
+use crate::exports;
+use crate::experiments;
+use crate::feature_flags;
+use crate::feeds;
+use crate::frames;
+use crate::hls;
+use crate::hotlinks;
+use crate::imports;
+use crate::jwt_keys;
+use crate::login_throttle;
+use crate::media::{self, WorkDir};
+use crate::migration_status;
+use crate::moderation;
+use crate::playback_tokens::{self, PlaybackToken};
+use crate::premieres;
+use crate::processing;
+use crate::recommendations;
+use crate::oembed::{self, OEmbed};
+use crate::request_metrics;
+use crate::restrictions;
+use crate::s3_events;
+use crate::sessions::{self, Refresh};
+use crate::signed_urls;
+use crate::spherical;
+use crate::sitemap;
+use crate::storage::ObjectStore;
+use crate::storage_tiering;
+use crate::telemetry;
+use crate::thumbnails;
+use crate::transcription;
+use crate::translations;
+use crate::user_settings;
+use crate::watermarks;
+use crate::watch_parties;
+use crate::web_push;
+use crate::webhooks;
+use crate::ws_stats;
 use crate::AppState;
 
+// Decode the bearer token from the Authorization header, or in cookie auth mode the session
+// cookie, returning the user id if it is valid
+pub(crate) fn authenticated_user_id(http_req: &actix_web::HttpRequest) -> Option<i32> {
+    authenticated_claims(http_req).map(|claims| claims.user_id)
+}
+
+// Claims of the request's token: the bearer token, or the session cookie's without one
+fn authenticated_claims(http_req: &actix_web::HttpRequest) -> Option<Claims> {
+    let bearer = http_req.headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    match bearer {
+        Some(token) => decode_claims(token),
+        None => auth_cookies::session_cookie_token(http_req).and_then(|token| decode_claims(&token)),
+    }
+}
+
+// Validate a JWT and return its claims. Revoked sessions are rejected by the
+// sessions::check_session middleware before handlers run.
+pub(crate) fn decode_claims(token: &str) -> Option<Claims> {
+    jwt_keys::keys().ok()?.decode::<Claims>(token)
+}
+
+// Validate a JWT and return its user id
+pub(crate) fn decode_user_id(token: &str) -> Option<i32> {
+    decode_claims(token).map(|claims| claims.user_id)
+}
+
+// Validate a watch party guest token and return its guest id. Whether the guest was since merged
+// into an account is checked against the database by callers.
+pub(crate) fn decode_guest_id(token: &str) -> Option<i32> {
+    jwt_keys::keys().ok()?.decode::<GuestClaims>(token).map(|claims| claims.guest_id)
+}
+
+// Like authenticated_user_id, but for routes that require a logged in user
+fn require_user_id(http_req: &actix_web::HttpRequest) -> Result<i32, ApiError> {
+    authenticated_user_id(http_req).ok_or(ApiError::Unauthorized)
+}
+
+async fn is_admin(db_pool: &sqlx::PgPool, user_id: i32) -> Result<bool, ApiError> {
+    let is_admin = sqlx::query_scalar::<_, bool>("SELECT is_admin FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await?;
+    Ok(is_admin.unwrap_or(false))
+}
+
+// Load a video the user may manage (its uploader or an admin)
+pub(crate) async fn owned_video(db_pool: &sqlx::PgPool, video_id: i32, user_id: i32) -> Result<Video, ApiError> {
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    if video.uploaded_by != Some(user_id) && !is_admin(db_pool, user_id).await? {
+        return Err(ApiError::Forbidden("Only the uploader or an admin can manage this video".to_string()));
+    }
+    Ok(video)
+}
+
+// Like require_user_id, but for admin-only routes
+async fn require_admin(db_pool: &sqlx::PgPool, http_req: &actix_web::HttpRequest) -> Result<i32, ApiError> {
+    let user_id = require_user_id(http_req)?;
+    if !is_admin(db_pool, user_id).await? {
+        return Err(ApiError::Forbidden("Admin access required".to_string()));
+    }
+    Ok(user_id)
+}
+
+fn issue_token(user_id: i32, session_id: &str, expires_at: chrono::NaiveDateTime) -> Result<String, ApiError> {
+    let claims = Claims {
+        user_id,
+        exp: expires_at.and_utc().timestamp() as usize,
+        sid: Some(session_id.to_string()),
+        jti: Some(uuid::Uuid::new_v4().to_string()),
+    };
+    jwt_keys::keys()?
+        .issue(&claims)
+        .map_err(|e| ApiError::Internal(format!("Failed to encode token: {:?}", e)))
+}
+
+fn issue_guest_token(guest_id: i32, expires_at: chrono::NaiveDateTime) -> Result<String, ApiError> {
+    let claims = GuestClaims {
+        guest_id,
+        exp: expires_at.and_utc().timestamp() as usize,
+    };
+    jwt_keys::keys()?
+        .issue(&claims)
+        .map_err(|e| ApiError::Internal(format!("Failed to encode guest token: {:?}", e)))
+}
+
+// A new access token for the session, and the refresh token for the one after. Both go in the
+// response body, or in cookie auth mode into httpOnly cookies with a CSRF token in the body instead.
+fn session_response(mut body: serde_json::Value, user_id: i32, session_id: &str, session_expires_at: chrono::NaiveDateTime, refresh_token: &str) -> Result<HttpResponse, ApiError> {
+    let expires_at = sessions::access_token_expiry(session_expires_at);
+    let token = issue_token(user_id, session_id, expires_at)?;
+    body["expiresAt"] = json!(expires_at.and_utc().to_rfc3339());
+
+    if auth_cookies::cookie_mode() {
+        body["csrfToken"] = json!(auth_cookies::csrf_token(session_id));
+        return Ok(HttpResponse::Ok()
+            .cookie(auth_cookies::session_cookie(&token, expires_at))
+            .cookie(auth_cookies::refresh_cookie(refresh_token, session_expires_at))
+            .json(body));
+    }
+    body["token"] = json!(token);
+    body["refreshToken"] = json!(refresh_token);
+    Ok(HttpResponse::Ok().json(body))
+}
+
+// Start a session for the user
+async fn signed_in_response(db_pool: &sqlx::PgPool, user: &User, message: &str, http_req: &actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let (session_id, expires_at) = sessions::create_session(db_pool, user.id, http_req).await?;
+    let refresh_token = sessions::issue_refresh_token(db_pool, &session_id).await?;
+    let body = json!({
+        "message": message,
+        "user": {
+            "id": user.id,
+            "username": user.username,
+            "email": user.email
+        }
+    });
+    session_response(body, user.id, &session_id, expires_at, &refresh_token)
+}
+
 #[post("/api/auth/register")]
 async fn register(
     req: web::Json<RegisterRequest>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> impl Responder {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let hashed_password = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST).unwrap();
-    let result = sqlx::query_as::<_, User>(
+    let hashed_password = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST)
+        .map_err(|e| ApiError::Internal(format!("Failed to hash password: {:?}", e)))?;
+    let user = sqlx::query_as::<_, User>(
         "INSERT INTO users (username, email, password, created_at) VALUES ($1, $2, $3, $4) RETURNING *"
     )
-    .bind(&req.username)
-    .bind(&req.email)
+    .bind(req.username.trim())
+    .bind(req.email.trim())
     .bind(&hashed_password)
     .bind(chrono::Utc::now().naive_utc())
     .fetch_one(&state.db_pool)
-    .await;
+    .await
+    .map_err(|e| match violated_unique_constraint(&e) {
+        // Both the original constraints and the case-insensitive indexes are named after the column
+        Some(constraint) if constraint.contains("email") => ApiError::Conflict("An account with that email already exists".to_string()),
+        Some(constraint) if constraint.contains("username") => ApiError::Conflict("That username is taken".to_string()),
+        _ if is_unique_violation(&e) => ApiError::Conflict("Username or email is already registered".to_string()),
+        _ => e.into(),
+    })?;
 
-    match result {
-        Ok(user) => {
-            let claims = Claims {
-                user_id: user.id,
-                exp: (chrono::Utc::now().naive_utc() + chrono::Duration::hours(24)).and_utc().timestamp() as usize,
-            };
-            let token = jsonwebtoken::encode(
-                &jsonwebtoken::Header::default(),
-                &claims,
-                &jsonwebtoken::EncodingKey::from_secret(
-                    env::var("JWT_SECRET")
-                        .unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string())
-                        .as_ref(),
-                ),
-            )
-            .unwrap();
-            web::Json(json!({
-                "message": "User registered successfully",
-                "user": {
-                    "id": user.id,
-                    "username": user.username,
-                    "email": user.email
-                },
-                "token": token
-            }))
-        }
-        Err(e) => {
-            error!("Error registering user: {:?}", e);
-            web::Json(json!({
-                "error": "Internal server error"
-            }))
-        }
-    }
+    signed_in_response(&state.db_pool, &user, "User registered successfully", &http_req).await
 }
 
 #[post("/api/auth/login")]
 async fn login(
     req: web::Json<LoginRequest>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> impl Responder {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let result = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE email = $1"
+    let ip = sessions::client_ip(&http_req);
+    login_throttle::check(state.redis_client.as_ref(), &req.username, ip.as_deref()).await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE LOWER(email) = LOWER($1)"
     )
-    .bind(&req.username)
-    .fetch_one(&state.db_pool)
-    .await;
-
-    match result {
-        Ok(user) => {
-            if bcrypt::verify(&req.password, &user.password).unwrap() {
-                let claims = Claims {
-                    user_id: user.id,
-                    exp: (chrono::Utc::now().naive_utc() + chrono::Duration::hours(24)).and_utc().timestamp() as usize,
-                };
-                let token = jsonwebtoken::encode(
-                    &jsonwebtoken::Header::default(),
-                    &claims,
-                    &jsonwebtoken::EncodingKey::from_secret(
-                        env::var("JWT_SECRET")
-                            .unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string())
-                            .as_ref(),
-                    ),
-                )
-                .unwrap();
-                web::Json(json!({
-                    "message": "Login successful",
-                    "user": {
-                        "id": user.id,
-                        "username": user.username,
-                        "email": user.email
-                    },
-                    "token": token
-                }))
-            } else {
-                web::Json(json!({
-                    "error": "Invalid credentials"
-                }))
-            }
+    .bind(req.username.trim())
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    let password_matches = match &user {
+        Some(user) => bcrypt::verify(&req.password, &user.password)
+            .map_err(|e| ApiError::Internal(format!("Failed to verify password: {:?}", e)))?,
+        None => false,
+    };
+    let user_id = user.as_ref().map(|user| user.id);
+    let Some(user) = user.filter(|_| password_matches) else {
+        // Unknown emails count too, so lockouts don't reveal which accounts exist
+        login_throttle::record_failure(&state.db_pool, state.redis_client.as_ref(), &req.username, ip.as_deref(), user_id).await;
+        return Err(ApiError::InvalidCredentials);
+    };
+    login_throttle::record_success(state.redis_client.as_ref(), &req.username).await;
+
+    signed_in_response(&state.db_pool, &user, "Login successful", &http_req).await
+}
+
+// Exchange a refresh token for a new access token and the next refresh token. Each refresh token
+// works once: presenting one again revokes its session, signing out whoever copied it along with
+// the client it was copied from.
+#[post("/api/auth/refresh")]
+async fn refresh_session(
+    json_req: Option<web::Json<RefreshRequest>>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let refresh_token = json_req
+        .and_then(|req| req.into_inner().refresh_token)
+        .or_else(|| auth_cookies::refresh_cookie_token(&http_req))
+        .ok_or(ApiError::Unauthorized)?;
+
+    match sessions::refresh(&state.db_pool, &refresh_token).await? {
+        Refresh::Rotated { user_id, session_id, expires_at, refresh_token } => {
+            session_response(json!({ "message": "Session refreshed" }), user_id, &session_id, expires_at, &refresh_token)
+        }
+        Refresh::Reused { session_id, expires_at } => {
+            sessions::deny(state.redis_client.as_ref(), &[(session_id, expires_at)]).await;
+            Err(ApiError::Unauthorized)
         }
-        Err(_) => web::Json(json!({
-            "error": "Invalid credentials"
-        })),
+        Refresh::Invalid => Err(ApiError::Unauthorized),
     }
 }
 
+// Revokes the session the request was made with, if any, along with its refresh token, denies
+// the token presented and clears the session cookies
 #[post("/api/auth/logout")]
-async fn logout() -> impl Responder {
-    web::Json(json!({
+async fn logout(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    if let Some(claims) = authenticated_claims(&http_req) {
+        if let Some(jti) = &claims.jti {
+            sessions::deny_token(state.redis_client.as_ref(), jti, claims.exp).await;
+        }
+        if let Some(session_id) = claims.sid {
+            if let Some(expires_at) = sessions::revoke_session(&state.db_pool, claims.user_id, &session_id).await? {
+                sessions::deny(state.redis_client.as_ref(), &[(session_id, expires_at)]).await;
+            }
+        }
+    }
+
+    let mut response = HttpResponse::Ok();
+    if auth_cookies::cookie_mode() {
+        response.cookie(auth_cookies::removal_cookie());
+        response.cookie(auth_cookies::refresh_removal_cookie());
+    }
+    Ok(response.json(json!({
         "message": "Logout successful"
-    }))
+    })))
+}
+
+// A CSRF token for the current session, for clients that lost the one from login (e.g. after
+// a page reload in cookie auth mode). Send it back as X-CSRF-Token on state-changing requests.
+#[get("/api/auth/csrf")]
+async fn get_csrf_token(http_req: actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    require_user_id(&http_req)?;
+    let session_id = sessions::current_session_id(&http_req).ok_or(ApiError::Unauthorized)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(json!({
+            "csrfToken": auth_cookies::csrf_token(&session_id)
+        })))
 }
 
+// Who the request's token (or session cookie) belongs to, for frontends restoring their session
+// state on load. Invalid and expired tokens, and those of deleted users, read as signed out;
+// revoked sessions get a 401 from sessions::check_session like on any other route.
 #[get("/api/auth/status")]
-async fn auth_status() -> impl Responder {
-    web::Json(json!({
-        "isAuthenticated": false
-    }))
+async fn auth_status(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let mut response = HttpResponse::Ok();
+    response.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+
+    let user = match authenticated_claims(&http_req) {
+        Some(claims) => sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
+            .bind(claims.user_id)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .map(|user| (user, claims)),
+        None => None,
+    };
+    let Some((user, claims)) = user else {
+        return Ok(response.json(json!({
+            "isAuthenticated": false
+        })));
+    };
+
+    let mut roles = vec!["user"];
+    if user.is_admin {
+        roles.push("admin");
+    }
+    let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0).map(|t| t.to_rfc3339());
+    Ok(response.json(json!({
+        "isAuthenticated": true,
+        "user": {
+            "id": user.id,
+            "username": user.username,
+            "email": user.email
+        },
+        "roles": roles,
+        "expiresAt": expires_at
+    })))
 }
 
 #[get("/api/status")]
 async fn status() -> impl Responder {
     web::Json(json!({
-        "status": "running"
+        "status": "running",
+        "websocket": crate::client_queue::metrics()
     }))
 }
 
+// Collection ETag for the video list: changes whenever a video is added, removed or updated.
+// Trashing and restoring bump updated_at, so MAX(updated_at) covers deleted rows too, and
+// titles are localized, so the negotiated languages are part of the tag, as is the processing
+// status filter.
+async fn video_list_etag(db_pool: &sqlx::PgPool, languages: &[String], processing_status: Option<&str>) -> Result<EntityTag, ApiError> {
+    let (count, last_updated) = sqlx::query_as::<_, (i64, Option<chrono::NaiveDateTime>)>(
+        "SELECT COUNT(*) FILTER (WHERE deleted_at IS NULL AND moderation_status = 'approved' AND ($1::TEXT IS NULL OR processing_status = $1)),
+                MAX(updated_at)
+         FROM videos"
+    )
+    .bind(processing_status)
+    .fetch_one(db_pool)
+    .await?;
+
+    let last_updated = last_updated.map(|t| t.and_utc().timestamp_micros()).unwrap_or(0);
+    let mut tag = format!("videos-{}-{}", count, last_updated);
+    if let Some(processing_status) = processing_status {
+        tag.push_str(&format!("-{}", processing_status));
+    }
+    if !languages.is_empty() {
+        tag.push_str(&format!("-{}", languages.join(".")));
+    }
+    Ok(EntityTag::new_strong(tag))
+}
+
 #[get("/api/videos")]
-async fn get_videos(state: web::Data<Arc<Mutex<AppState>>>) -> actix_web::HttpResponse {
+async fn get_videos(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    if_none_match: Option<web::Header<IfNoneMatch>>,
+    query: web::Query<VideoListQuery>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let result = sqlx::query_as::<_, Video>("SELECT * FROM videos ORDER BY upload_date DESC")
-        .fetch_all(&state.db_pool)
-        .await;
-
-    match result {
-        Ok(videos) => {
-            // Check for videos without duration and queue them for processing
-            if let Some(ref job_queue) = state.job_queue {
-                info!("Job queue is available, checking videos for duration extraction");
-                let bucket = std::env::var("S3_BUCKET")
-                    .or_else(|_| std::env::var("MINIO_BUCKET"))
-                    .unwrap_or_else(|_| "videos".to_string());
+    let languages = translations::preferred_languages(&http_req);
+    let processing_status = query.processing_status.as_deref()
+        .map(processing::parse_status)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    let etag = video_list_etag(&state.db_pool, &languages, processing_status).await?;
+    let unchanged = match if_none_match.map(|h| h.into_inner()) {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if unchanged {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header((VARY, "Accept-Language"))
+            .finish());
+    }
+
+    let mut videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE deleted_at IS NULL AND moderation_status = 'approved' AND ($1::TEXT IS NULL OR processing_status = $1)
+         ORDER BY upload_date DESC"
+    )
+    .bind(processing_status)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    // Check for videos without duration and queue them for processing
+    if let Some(ref job_queue) = state.job_queue {
+        info!("Job queue is available, checking videos for duration extraction");
+        let bucket = std::env::var("S3_BUCKET")
+            .or_else(|_| std::env::var("MINIO_BUCKET"))
+            .unwrap_or_else(|_| "videos".to_string());
+        
+        for video in &videos {
+            if video.duration.is_none() {
+                info!("Video {} has no duration, enqueueing job", video.id);
+                let job = MediaJob::new(MediaJobKind::Duration, video, &bucket);
                 
-                for video in &videos {
-                    if video.duration.is_none() {
-                        info!("Video {} has no duration, enqueueing job", video.id);
-                        let job = DurationExtractionJob {
-                            video_id: video.id,
-                            s3_key: video.s3_key.clone(),
-                            bucket: bucket.clone(),
-                        };
-                        
-                        match job_queue.enqueue_duration_extraction(job).await {
-                            Ok(_) => info!("Successfully enqueued duration extraction job for video {}", video.id),
-                            Err(e) => error!("Failed to enqueue duration extraction job for video {}: {:?}", video.id, e),
-                        }
-                    } else {
-                        info!("Video {} already has duration: {:?}", video.id, video.duration);
-                    }
+                match job_queue.enqueue(job).await {
+                    Ok(_) => info!("Successfully enqueued duration extraction job for video {}", video.id),
+                    Err(e) => error!("Failed to enqueue duration extraction job for video {}: {:?}", video.id, e),
                 }
             } else {
-                info!("Job queue is not available");
+                info!("Video {} already has duration: {:?}", video.id, video.duration);
             }
-            
-            actix_web::HttpResponse::Ok().json(videos)
-        }
-        Err(e) => {
-            error!("Error fetching videos: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
         }
+    } else {
+        info!("Job queue is not available");
     }
+
+    translations::localize(&state.db_pool, &mut videos, &languages).await?;
+    categories::attach(&state.db_pool, &mut videos).await?;
+    
+    // no-cache lets clients keep the list but revalidate it with If-None-Match on every request
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(etag))
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .insert_header((VARY, "Accept-Language"))
+        .json(videos))
 }
 
 #[get("/api/videos/{id}")]
 async fn get_video(
     path: web::Path<i32>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> actix_web::HttpResponse {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
     let video_id = path.into_inner();
-    let update_result = sqlx::query("UPDATE videos SET view_count = view_count + 1 WHERE id = $1")
+    let mut video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
         .bind(video_id)
-        .execute(&state.db_pool)
-        .await;
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
 
-    if let Err(e) = update_result {
-        error!("Error updating view count: {:?}", e);
-        return actix_web::HttpResponse::InternalServerError().json(json!({
-            "error": "Internal server error"
-        }));
+    // Only count views from clients allowed to watch
+    restrictions::check_playback(&video, &http_req)?;
+
+    if !counters::increment(&state.db_pool, state.redis_client.as_ref(), Counter::Views, video.id).await? {
+        // Written straight to Postgres, after the row above was read
+        video.view_count = Some(video.view_count.unwrap_or(0) + 1);
     }
+    sqlx::query("INSERT INTO video_views (video_id, user_id, country) VALUES ($1, $2, $3)")
+        .bind(video.id)
+        .bind(authenticated_user_id(&http_req))
+        .bind(restrictions::client_country(&http_req))
+        .execute(&state.db_pool)
+        .await?;
 
-    let result = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1")
-        .bind(video_id)
-        .fetch_one(&state.db_pool)
-        .await;
+    let mut videos = [video];
+    counters::with_pending(state.redis_client.as_ref(), &mut videos).await;
+    categories::attach(&state.db_pool, &mut videos).await?;
+    let language = translations::localize(&state.db_pool, &mut videos, &translations::preferred_languages(&http_req))
+        .await?
+        .pop()
+        .flatten();
+    let [video] = videos;
+    let audio_tracks = audio_tracks::list(&state.db_pool, video.id).await?;
+    let spherical = spherical::get(&state.db_pool, video.id).await?;
 
-    match result {
-        Ok(video) => actix_web::HttpResponse::Ok().json(video),
-        Err(e) => {
-            error!("Error fetching video: {:?}", e);
-            actix_web::HttpResponse::NotFound().json(json!({
-                "error": "Video not found"
-            }))
-        }
+    let mut response = HttpResponse::Ok();
+    response.insert_header((VARY, "Accept-Language"));
+    if let Some(language) = language {
+        response.insert_header((CONTENT_LANGUAGE, language));
     }
+    Ok(response.json(VideoDetail { video, audio_tracks, spherical }))
+}
+
+// A list of videos with titles and descriptions in the client's Accept-Language where translated
+async fn localized_list(state: &AppState, mut videos: Vec<Video>, http_req: &actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    translations::localize(&state.db_pool, &mut videos, &translations::preferred_languages(http_req)).await?;
+    counters::with_pending(state.redis_client.as_ref(), &mut videos).await;
+    categories::attach(&state.db_pool, &mut videos).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((VARY, "Accept-Language"))
+        .json(videos))
 }
 
 #[get("/api/videos/tag/{tag}")]
 async fn get_videos_by_tag(
     path: web::Path<String>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> actix_web::HttpResponse {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
     let tag = path.into_inner();
-    let result = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE $1 = ANY(tags)")
+    // Containment rather than = ANY(tags), which the GIN index on tags can't serve
+    let videos = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE tags @> ARRAY[$1]::TEXT[] AND deleted_at IS NULL AND moderation_status = 'approved'")
         .bind(&tag)
         .fetch_all(&state.db_pool)
-        .await;
+        .await?;
 
-    match result {
-        Ok(videos) => actix_web::HttpResponse::Ok().json(videos),
-        Err(e) => {
-            error!("Error fetching videos by tag: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
+    localized_list(&state, videos, &http_req).await
+}
+
+fn index_query(text: &str, filters: &SearchFilterQuery) -> search_index::SearchQuery {
+    search_index::SearchQuery {
+        text: text.to_string(),
+        tag: filters.tag.clone(),
+        category_id: filters.category,
+        limit: MAX_SEARCH_LIMIT,
     }
 }
 
+// Search the external index when SEARCH_BACKEND is set, else (or when it is unreachable) match
+// titles, descriptions, tags and translations in Postgres
 #[get("/api/videos/search/{query}")]
 async fn search_videos(
     path: web::Path<String>,
+    filters: web::Query<SearchFilterQuery>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> actix_web::HttpResponse {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
     let query = path.into_inner();
+
+    // Keep a search history for authenticated users
+    if let Some(user_id) = authenticated_user_id(&http_req) {
+        if let Err(e) = record_search(&state.db_pool, user_id, &query).await {
+            error!("Error recording search history for user {}: {:?}", user_id, e);
+        }
+    }
+
+    if let Some(backend) = search_index::backend_from_env() {
+        match backend.search(&index_query(&query, &filters)).await {
+            Ok(hits) => {
+                let videos = search_index::load_hits(&state.db_pool, &hits.video_ids).await?;
+                return localized_list(&state, videos, &http_req).await;
+            }
+            Err(e) => error!("{} search failed, searching in Postgres: {:?}", backend.name(), e),
+        }
+    }
+
     let search_pattern = format!("%{}%", query.to_lowercase());
-    
-    let result = sqlx::query_as::<_, Video>(
+    let order_by = match experiments::variant(&state.db_pool, experiments::SEARCH_RANKING, authenticated_user_id(&http_req)).await.as_str() {
+        "most_viewed" => "view_count DESC NULLS LAST, upload_date DESC",
+        _ => "upload_date DESC",
+    };
+
+    let videos = sqlx::query_as::<_, Video>(&format!(
         "SELECT * FROM videos 
-         WHERE LOWER(title) LIKE $1 
+         WHERE deleted_at IS NULL
+           AND moderation_status = 'approved'
+           AND (LOWER(title) LIKE $1 
             OR LOWER(description) LIKE $1 
             OR EXISTS (
                 SELECT 1 FROM unnest(tags) AS tag 
                 WHERE LOWER(tag) LIKE $1
             )
-         ORDER BY upload_date DESC"
-    )
+            OR EXISTS (
+                SELECT 1 FROM video_translations t
+                WHERE t.video_id = videos.id AND (LOWER(t.title) LIKE $1 OR LOWER(t.description) LIKE $1)
+            ))
+           AND ($2::TEXT IS NULL OR tags @> ARRAY[$2]::TEXT[])
+           AND ($3::INTEGER IS NULL OR EXISTS (
+                SELECT 1 FROM videos_categories vc WHERE vc.video_id = videos.id AND vc.category_id = $3
+            ))
+         ORDER BY {}",
+        order_by
+    ))
     .bind(&search_pattern)
+    .bind(&filters.tag)
+    .bind(filters.category)
     .fetch_all(&state.db_pool)
-    .await;
+    .await?;
+
+    localized_list(&state, videos, &http_req).await
+}
+
+// How many of a search's results have each tag and category, which only the external index
+// can tell
+#[get("/api/videos/search/{query}/facets")]
+async fn search_facets(
+    path: web::Path<String>,
+    filters: web::Query<SearchFilterQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let backend = search_index::backend_from_env()
+        .ok_or_else(|| ApiError::NotFound("Search facets need a search backend (SEARCH_BACKEND)".to_string()))?;
+    let hits = backend.search(&index_query(&path, &filters)).await.map_err(|e| {
+        error!("{} facet search failed: {:?}", backend.name(), e);
+        ApiError::ServiceUnavailable("Search backend is unavailable".to_string())
+    })?;
+    Ok(HttpResponse::Ok().json(hits.facets))
+}
+
+#[post("/api/videos/search")]
+async fn advanced_search_videos(
+    json_req: web::Json<AdvancedSearchRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+
+    if let (Some(min), Some(max)) = (json_req.min_duration, json_req.max_duration) {
+        if min > max {
+            return Err(ApiError::BadRequest("min_duration must not be greater than max_duration".to_string()));
+        }
+    }
+
+    if let (Some(after), Some(before)) = (json_req.uploaded_after, json_req.uploaded_before) {
+        if after > before {
+            return Err(ApiError::BadRequest("uploaded_after must not be later than uploaded_before".to_string()));
+        }
+    }
 
-    match result {
-        Ok(videos) => actix_web::HttpResponse::Ok().json(videos),
-        Err(e) => {
-            error!("Error searching videos: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
+    // Keep a search history for authenticated users
+    if let (Some(user_id), Some(text)) = (authenticated_user_id(&http_req), json_req.text.as_deref()) {
+        if !text.trim().is_empty() {
+            if let Err(e) = record_search(&state.db_pool, user_id, text.trim()).await {
+                error!("Error recording search history for user {}: {:?}", user_id, e);
+            }
         }
     }
+
+    let mut query = build_advanced_search_query(&json_req);
+    let videos = query.build_query_as::<Video>()
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    localized_list(&state, videos, &http_req).await
+}
+
+// RFC 9530 Repr-Digest value for a hex SHA-256, so clients can verify the file they received
+fn repr_digest(sha256_hex: &str) -> Option<String> {
+    use base64::Engine;
+    let bytes = hex::decode(sha256_hex).ok()?;
+    Some(format!("sha-256=:{}:", base64::engine::general_purpose::STANDARD.encode(bytes)))
+}
+
+// Fail with 503 while the video's original file is being restored from archive storage,
+// asking for the restore if nobody has yet
+async fn ensure_readable(state: &AppState, bucket: &str, video: &Video) -> Result<(), ApiError> {
+    match storage_tiering::request_restore(&state.db_pool, state.storage.as_ref(), bucket, video).await {
+        Ok(storage_tiering::Availability::Readable) => Ok(()),
+        Ok(storage_tiering::Availability::Restoring) => Err(ApiError::RestoreInProgress { retry_after_secs: storage_tiering::RESTORE_RETRY_AFTER_SECS }),
+        Err(e) => Err(ApiError::Internal(format!("Error restoring video {} from archive storage: {:?}", video.id, e))),
+    }
 }
 
 #[get("/api/videos/{id}/stream")]
 async fn stream_video(
     path: web::Path<i32>,
+    query: web::Query<SignedUrlQuery>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> impl Responder {
-    let state = state.lock().await;
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let video_id = path.into_inner();
-    let video_result = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1")
+    hotlinks::require_signature(query.expires, query.signature.as_deref())?;
+    signed_urls::verify_if_signed(&signed_urls::stream_path(video_id), query.expires, query.signature.as_deref(), chrono::Utc::now().timestamp())?;
+    let state = state.lock().await;
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
         .bind(video_id)
-        .fetch_one(&state.db_pool)
-        .await;
-
-    match video_result {
-        Ok(video) => {
-            let s3_key = video.s3_key;
-            
-            let bucket_name = env::var("S3_BUCKET")
-                .or_else(|_| env::var("MINIO_BUCKET"))
-                .unwrap_or_else(|_| "videos".to_string());
-            let get_object_output = state.s3_client.get_object()
-                .bucket(bucket_name)
-                .key(s3_key)
-                .send()
-                .await;
-
-            match get_object_output {
-                Ok(output) => {
-                    let body = output.body.collect().await.unwrap().into_bytes();
-                    actix_web::HttpResponse::Ok()
-                        .content_type("video/webm")
-                        .append_header((actix_web::http::header::ACCEPT_RANGES, "bytes"))
-                        .body(body)
-                }
-                Err(e) => {
-                    error!("Error streaming video from MinIO: {:?}", e);
-                    actix_web::HttpResponse::InternalServerError().json(json!({
-                        "error": "Internal server error"
-                    }))
-                }
-            }
-        }
-        Err(e) => {
-            error!("Error fetching video stream: {:?}", e);
-            actix_web::HttpResponse::NotFound().json(json!({
-                "error": "Video not found"
-            }))
-        }
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    // Encrypted videos are only played through the HLS playlist, never as the original file
+    if video.hls_encrypted {
+        return Err(ApiError::Forbidden("This video is only available as encrypted HLS".to_string()));
+    }
+    restrictions::check_playback(&video, &http_req)?;
+    premieres::check_started(&video, authenticated_user_id(&http_req))?;
+    let meter = bandwidth::check(state.redis_client.as_ref(), &http_req, authenticated_user_id(&http_req)).await?;
+
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    ensure_readable(&state, &bucket_name, &video).await?;
+    let output = state.storage.get(&bucket_name, &video.s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Error streaming video from storage: {:?}", e))))?;
+
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading video from storage: {:?}", e)))?;
+    meter.record(body.len() as u64).await;
+    // Video is already compressed; skip the compression middleware
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("video/webm")
+        .append_header((actix_web::http::header::ACCEPT_RANGES, "bytes"))
+        .insert_header(ContentEncoding::Identity);
+    if let Some(digest) = video.sha256.as_deref().and_then(repr_digest) {
+        response.insert_header(("Repr-Digest", digest));
     }
+    Ok(response.body(body))
 }
 
-#[post("/api/comments/{video_id}")]
-async fn post_comment(
+// The original file, or a rendition with ?quality=720p, as an attachment. Other users can only
+// download when the uploader allows it, and each connection is held to DOWNLOAD_RATE_LIMIT_BYTES
+// per second.
+#[get("/api/videos/{id}/download")]
+async fn download_video(
     path: web::Path<i32>,
-    json_req: web::Json<CommentRequest>,
+    query: web::Query<DownloadQuery>,
     state: web::Data<Arc<Mutex<AppState>>>,
     http_req: actix_web::HttpRequest,
-) -> actix_web::HttpResponse {
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
     let video_id = path.into_inner();
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
 
-    // Extract the JWT token from the Authorization header
-    let auth_header = http_req.headers().get(actix_web::http::header::AUTHORIZATION);
-    let token = auth_header.and_then(|h| h.to_str().ok()).and_then(|h| h.strip_prefix("Bearer ")).map(String::from);
-
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string());
-    let claims_result = token.and_then(|t| {
-        decode::<Claims>(
-            &t,
-            &DecodingKey::from_secret(jwt_secret.as_ref()),
-            &Validation::default(),
-        ).ok()
-    });
+    let user_id = authenticated_user_id(&http_req);
+    let manages_video = match user_id {
+        Some(user_id) => video.uploaded_by == Some(user_id) || is_admin(&state.db_pool, user_id).await?,
+        None => false,
+    };
+    if !manages_video {
+        if !video.allow_download || video.hls_encrypted || video.moderation_status != moderation::STATUS_APPROVED {
+            return Err(ApiError::Forbidden("Downloads are disabled for this video".to_string()));
+        }
+        restrictions::check_playback(&video, &http_req)?;
+        premieres::check_started(&video, user_id)?;
+    }
+    let meter = bandwidth::check(state.redis_client.as_ref(), &http_req, user_id).await?;
 
-    let claims = match claims_result {
-        Some(decoded) => decoded.claims,
-        None => {
-            return actix_web::HttpResponse::Forbidden().json(json!({
-                "error": "Unauthorized: Invalid or missing token"
-            }));
+    let quality = query.quality.as_deref().filter(|q| *q != "original");
+    let s3_key = match quality {
+        Some(quality) => {
+            sqlx::query_as::<_, VideoRendition>("SELECT * FROM video_renditions WHERE video_id = $1 AND name = $2")
+                .bind(video.id)
+                .bind(quality)
+                .fetch_optional(&state.db_pool)
+                .await?
+                .ok_or_else(|| ApiError::NotFound(format!("No {} rendition for this video", quality)))?
+                .s3_key
         }
+        None => video.s3_key.clone(),
     };
 
-    let user_id = claims.user_id;
-
-    // Log the incoming request for debugging
-    info!("Received comment request for video_id: {}, user_id: {}, text: {}, video_time: {}", video_id, user_id, json_req.text, json_req.video_time);
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    if quality.is_none() {
+        ensure_readable(&state, &bucket_name, &video).await?;
+    }
+    let output = state.storage.get(&bucket_name, &s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Error fetching download from storage: {:?}", e))))?;
 
-    let result = sqlx::query_as::<_, Comment>(
-        "INSERT INTO comments (video_id, user_id, content, video_time, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *"
-    )
-    .bind(video_id)
-    .bind(user_id)
-    .bind(&json_req.text)
-    .bind(json_req.video_time)
-    .bind(chrono::Utc::now().naive_utc())
-    .fetch_one(&state.db_pool)
-    .await;
+    let extension = s3_key.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("mp4");
+    let filename = downloads::attachment_filename(&video.title, quality, extension);
+    info!("Download of video {} ({}) started", video.id, quality.unwrap_or("original"));
 
-    match result {
-        Ok(comment) => {
-            // Clone necessary data for the background task
-            let comment_clone = comment.clone();
-            
-            // Get the video_clients_clone directly from the state we already have locked
-            let video_clients_clone = state.video_clients.lock().unwrap().clone();
-            
-            broadcast_comment(video_id, comment_clone, video_clients_clone);
-            
-            // Return the response immediately without waiting for broadcast
-            actix_web::HttpResponse::Ok().json(comment)
-        }
-        Err(e) => {
-            error!("Error posting comment: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(output.content_type.as_deref().unwrap_or("application/octet-stream"))
+        .insert_header((actix_web::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)))
+        .insert_header(ContentEncoding::Identity);
+    if let Some(content_length) = output.content_length {
+        response.no_chunking(content_length);
+        meter.record(content_length).await;
     }
+    Ok(response.streaming(downloads::throttled(output.body, downloads::rate_limit())))
 }
 
-#[get("/api/comments/{video_id}")]
-async fn get_comments(
+fn clip_response(video_id: i32, clip: &VideoClip) -> serde_json::Value {
+    json!({
+        "id": clip.id,
+        "format": clip.format,
+        "start": f64::from(clip.start_ms) / 1000.0,
+        "duration": f64::from(clip.duration_ms) / 1000.0,
+        "url": signed_urls::signed_url(&signed_urls::clip_path(video_id, clip.id), chrono::Utc::now().timestamp()),
+    })
+}
+
+// Render a short segment as an animated GIF or WebP for sharing. Anyone signed in who can watch
+// the video can make one; encrypted videos only by the people managing them. Renders share the
+// per-user rate and the slots in frames.rs with frame grabs.
+#[post("/api/videos/{id}/gif")]
+async fn create_clip(
     path: web::Path<i32>,
+    json_req: web::Json<ClipRequest>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> actix_web::HttpResponse {
-    let state = state.lock().await;
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
     let video_id = path.into_inner();
-    let result = sqlx::query_as::<_, Comment>("SELECT * FROM comments WHERE video_id = $1 ORDER BY video_time ASC")
+
+    // Rendering takes a while, so don't hold the app state lock meanwhile
+    let (db_pool, storage, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.storage.clone(), state.redis_client.clone())
+    };
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
         .bind(video_id)
-        .fetch_all(&state.db_pool)
-        .await;
+        .fetch_optional(&db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
 
-    match result {
-        Ok(comments) => actix_web::HttpResponse::Ok().json(comments),
-        Err(e) => {
-            error!("Error fetching comments: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
+    let manages_video = video.uploaded_by == Some(user_id) || is_admin(&db_pool, user_id).await?;
+    if !manages_video {
+        if video.hls_encrypted || video.moderation_status != moderation::STATUS_APPROVED {
+            return Err(ApiError::Forbidden("Clips can't be made of this video".to_string()));
         }
+        restrictions::check_playback(&video, &http_req)?;
+        premieres::check_started(&video, Some(user_id))?;
+    }
+
+    let format = clips::ClipFormat::parse(json_req.format.as_deref()).map_err(ApiError::BadRequest)?;
+    let (start_ms, duration_ms) = clips::clip_window(json_req.start, json_req.duration, video.duration)
+        .map_err(ApiError::BadRequest)?;
+    if let Some(clip) = clips::find_clip(&db_pool, video.id, start_ms, duration_ms, format).await? {
+        return Ok(HttpResponse::Ok().json(clip_response(video.id, &clip)));
+    }
+
+    frames::check_render_rate(redis_client.as_ref(), user_id, chrono::Utc::now().timestamp()).await?;
+    let _slot = frames::render_slot().await?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    // ffmpeg seeks in the source over HTTP rather than downloading all of it
+    let source_key = clips::clip_source(&db_pool, video.id, &video.s3_key).await?;
+    let source = storage.media_input(&bucket, &source_key, frames::SOURCE_URL_VALIDITY).await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Failed to open video {} for a clip: {:?}", video.id, e))))?;
+    let work_dir = WorkDir::create().await
+        .map_err(|e| ApiError::Internal(format!("Failed to create work directory: {:?}", e)))?;
+
+    let output = work_dir.file(&format!("clip.{}", format.extension()));
+    let (start, duration) = (f64::from(start_ms) / 1000.0, f64::from(duration_ms) / 1000.0);
+    media::render_clip(&source, &output, start, duration, format).await
+        .map_err(|e| ApiError::Internal(format!("Failed to render clip of video {}: {:?}", video.id, e)))?;
+    if tokio::fs::metadata(&output).await.map_or(true, |m| m.len() == 0) {
+        return Err(ApiError::BadRequest("No frames in the requested segment".to_string()));
     }
+
+    let s3_key = clips::clip_s3_key(video.id, start_ms, duration_ms, format);
+    media::upload_file(storage.as_ref(), &bucket, &s3_key, &output, format.content_type()).await
+        .map_err(|e| ApiError::Internal(format!("Failed to store clip of video {}: {:?}", video.id, e)))?;
+    let clip = clips::insert_clip(&db_pool, video.id, user_id, start_ms, duration_ms, format, &s3_key).await?;
+    info!("User {} made a {} clip of video {} ({}ms from {}ms)", user_id, clip.format, video.id, duration_ms, start_ms);
+    Ok(HttpResponse::Created().json(clip_response(video.id, &clip)))
 }
 
-#[post("/api/watchparty/{video_id}/join")]
-async fn join_watch_party(
+// A day; frames don't change, but access to the video can
+const FRAME_MAX_AGE_SECS: u32 = 24 * 60 * 60;
+
+// A JPEG of the frame at `t` seconds (rounded down to a whole second), for editors and clip
+// pickers. Rendered from a rendition the first time a second is asked for, within the limits in
+// frames.rs, then served from S3. Access is as for clips.
+#[get("/api/videos/{id}/frame")]
+async fn get_video_frame(
     path: web::Path<i32>,
-    _state: web::Data<Arc<Mutex<AppState>>>,
+    query: web::Query<FrameQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
     http_req: actix_web::HttpRequest,
-) -> actix_web::HttpResponse {
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
     let video_id = path.into_inner();
 
-    // Extract the JWT token from the Authorization header
-    let auth_header = http_req.headers().get(actix_web::http::header::AUTHORIZATION);
-    let token = auth_header.and_then(|h| h.to_str().ok()).and_then(|h| h.strip_prefix("Bearer ")).map(|t| t.to_owned());
-
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string());
-    let claims_result = token.and_then(|t| {
-        decode::<Claims>(
-            &t,
-            &DecodingKey::from_secret(jwt_secret.as_ref()),
-            &Validation::default(),
-        ).ok()
-    });
+    // Rendering takes a while, so don't hold the app state lock meanwhile
+    let (db_pool, storage, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.storage.clone(), state.redis_client.clone())
+    };
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
 
-    let claims = match claims_result {
-        Some(decoded) => decoded.claims,
-        None => {
-            return actix_web::HttpResponse::Forbidden().json(json!({
-                "error": "Unauthorized: Invalid or missing token"
-            }));
+    let manages_video = video.uploaded_by == Some(user_id) || is_admin(&db_pool, user_id).await?;
+    if !manages_video {
+        if video.hls_encrypted || video.moderation_status != moderation::STATUS_APPROVED {
+            return Err(ApiError::Forbidden("Frames can't be taken from this video".to_string()));
         }
-    };
+        restrictions::check_playback(&video, &http_req)?;
+        premieres::check_started(&video, Some(user_id))?;
+    }
 
-    let user_id = claims.user_id;
+    let time_ms = frames::frame_time(query.t, video.duration).map_err(ApiError::BadRequest)?;
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let s3_key = frames::frame_s3_key(video.id, time_ms);
 
-    actix_web::HttpResponse::Ok().json(json!({
-        "message": "Joined watch party",
-        "videoId": video_id,
-        "userId": user_id
-    }))
-}
+    let body = match storage.get(&bucket, &s3_key).await {
+        Ok(object) => object.bytes().await
+            .map_err(|e| ApiError::Internal(format!("Error reading frame from storage: {:?}", e)))?,
+        Err(e) if e.is_not_found() => {
+            frames::check_render_rate(redis_client.as_ref(), user_id, chrono::Utc::now().timestamp()).await?;
+            let _slot = frames::render_slot().await?;
 
-#[post("/api/watchparty/{video_id}/control")]
-async fn control_watch_party(
-    _path: web::Path<i32>,
-    req: web::Json<serde_json::Value>,
-    _state: web::Data<Arc<Mutex<AppState>>>,
-    _auth: web::Data<Arc<Mutex<Claims>>>,
-) -> actix_web::HttpResponse {
-    // let claims = auth.lock().await;
-    // let video_id = path.into_inner();
-    // let user_id = claims.user_id;
-    let action = req.get("action").and_then(|v| v.as_str()).unwrap_or("");
-    let time = req.get("time").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let source_key = frames::frame_source(&db_pool, video.id, &video.s3_key).await?;
+            let source = storage.media_input(&bucket, &source_key, frames::SOURCE_URL_VALIDITY).await
+                .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Failed to open video {} for a frame: {:?}", video.id, e))))?;
+            let work_dir = WorkDir::create().await
+                .map_err(|e| ApiError::Internal(format!("Failed to create work directory: {:?}", e)))?;
+            let output = work_dir.file("frame.jpg");
+            media::extract_frame(&source, time_ms as f64 / 1000.0, &output).await
+                .map_err(|e| ApiError::Internal(format!("Failed to extract frame of video {}: {:?}", video.id, e)))?;
+            // ffmpeg succeeds without writing anything when the time is past the last frame
+            let frame = match tokio::fs::read(&output).await {
+                Ok(frame) => bytes::Bytes::from(frame),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => bytes::Bytes::new(),
+                Err(e) => return Err(ApiError::Internal(format!("Failed to read frame of video {}: {:?}", video.id, e))),
+            };
+            if frame.is_empty() {
+                return Err(ApiError::BadRequest("No frame at the requested time".to_string()));
+            }
+            storage.put(&bucket, &s3_key, frame.clone(), "image/jpeg").await
+                .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Failed to store frame of video {}: {:?}", video.id, e))))?;
+            info!("Rendered frame {}ms of video {}", time_ms, video.id);
+            frame
+        }
+        Err(e) => return Err(e.into_api_error(|e| ApiError::Internal(format!("Error fetching frame from storage: {:?}", e)))),
+    };
 
-    // Broadcast control message to all connected clients for this video
-    // This would require WebSocket implementation
-    actix_web::HttpResponse::Ok().json(json!({
-        "message": "Control message sent",
-        "action": action,
-        "time": time
-    }))
+    // Frames are behind sign-in, so only the browser may keep them
+    Ok(HttpResponse::Ok()
+        .content_type("image/jpeg")
+        .insert_header(ContentEncoding::Identity)
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::MaxAge(FRAME_MAX_AGE_SECS)]))
+        .body(body))
 }
 
-#[get("/api/thumbnails/{thumbnail_key}")]
-async fn get_thumbnail(
+#[get("/api/videos/{id}/clips/{clip_id}")]
+async fn get_clip(
+    path: web::Path<(i32, i32)>,
+    query: web::Query<SignedUrlQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let (video_id, clip_id) = path.into_inner();
+    let (Some(expires), Some(signature)) = (query.expires, query.signature.as_deref()) else {
+        return Err(ApiError::Forbidden("Invalid URL signature".to_string()));
+    };
+    signed_urls::verify(&signed_urls::clip_path(video_id, clip_id), expires, signature, chrono::Utc::now().timestamp())?;
+
+    let state = state.lock().await;
+    let clip = sqlx::query_as::<_, VideoClip>(
+        "SELECT c.* FROM video_clips c JOIN videos v ON v.id = c.video_id
+         WHERE c.id = $1 AND c.video_id = $2 AND v.deleted_at IS NULL"
+    )
+    .bind(clip_id)
+    .bind(video_id)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Clip not found".to_string()))?;
+    let format = clips::ClipFormat::parse(Some(&clip.format)).map_err(ApiError::Internal)?;
+
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = state.storage.get(&bucket_name, &clip.s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| {
+            error!("Error fetching clip {} from storage: {:?}", clip.s3_key, e);
+            ApiError::NotFound("Clip not found".to_string())
+        }))?;
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading clip from storage: {:?}", e)))?;
+
+    // A clip never changes once rendered
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentEncoding::Identity)
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(THUMBNAIL_MAX_AGE_SECS),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ]))
+        .body(body))
+}
+
+// Queue a new video cut together from segments of the user's videos. Rendering happens in the
+// background; poll the returned compilation for its progress and, once completed, its video.
+#[post("/api/compilations")]
+async fn create_compilation(
+    json_req: web::Json<CompilationRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let admin = is_admin(&state.db_pool, user_id).await?;
+    let compilation = compilations::create(&state.db_pool, user_id, admin, &json_req).await?;
+    Ok(HttpResponse::Accepted()
+        .insert_header((actix_web::http::header::LOCATION, format!("/api/compilations/{}", compilation.id)))
+        .json(compilation))
+}
+
+#[get("/api/compilations")]
+async fn list_compilations(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    Ok(HttpResponse::Ok().json(compilations::list_compilations(&state.db_pool, user_id).await?))
+}
+
+#[get("/api/compilations/{id}")]
+async fn get_compilation(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let compilation = compilations::get_compilation(&state.db_pool, path.into_inner(), user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Compilation not found".to_string()))?;
+    let segments = compilations::segments(&state.db_pool, compilation.id).await?;
+    let mut body = json!(compilation);
+    body["segments"] = json!(segments
+        .iter()
+        .map(|s| json!({
+            "videoId": s.source_video_id,
+            "start": f64::from(s.start_ms) / 1000.0,
+            "end": f64::from(s.end_ms) / 1000.0,
+        }))
+        .collect::<Vec<_>>());
+    Ok(HttpResponse::Ok().json(body))
+}
+
+// oEmbed provider endpoint, so other sites and chat apps can unfurl links to videos
+#[get("/api/oembed")]
+async fn get_oembed(
+    query: web::Query<OEmbedQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    if query.format.as_deref().is_some_and(|f| f != "json") {
+        return Ok(HttpResponse::NotImplemented().finish());
+    }
+
+    let state = state.lock().await;
+    let base_url = oembed::public_base_url();
+    let video_id = oembed::video_id_from_url(&query.url, &base_url)
+        .ok_or_else(|| ApiError::NotFound("Not a video URL on this site".to_string()))?;
+
+    let video = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL AND moderation_status = 'approved'"
+    )
+    .bind(video_id)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    if !video.allow_embed {
+        return Err(ApiError::Forbidden("This video can't be embedded on other sites".to_string()));
+    }
+
+    let author_name = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(video.uploaded_by)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    let size = oembed::embed_size(query.maxwidth, query.maxheight);
+    Ok(HttpResponse::Ok().json(OEmbed::for_video(&video, author_name, &base_url, size)))
+}
+
+// Everything the embedded player needs in one request, see embed. Refused like oEmbed for
+// videos that can't be embedded, and like playback where the viewer's country is restricted or
+// the premiere hasn't started.
+#[get("/api/embed/{id}/config")]
+async fn get_embed_config(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
+    };
+    let video = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL AND moderation_status = 'approved'"
+    )
+    .bind(path.into_inner())
+    .fetch_optional(&db_pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    if !video.allow_embed {
+        return Err(ApiError::Forbidden("This video can't be embedded on other sites".to_string()));
+    }
+    restrictions::check_playback(&video, &http_req)?;
+    premieres::check_started(&video, authenticated_user_id(&http_req))?;
+
+    let chapters = chapters::list_chapters(&db_pool, video.id, false).await?;
+    let transcript = transcription::get_transcript(&db_pool, video.id).await?;
+    let flags = feature_flags::load(&db_pool, redis_client.as_ref()).await?;
+    let config = embed::EmbedConfig {
+        audio_tracks: audio_tracks::list(&db_pool, video.id).await?,
+        spherical: spherical::get(&db_pool, video.id).await?,
+        ..embed::config(&video, &chapters, transcript.as_ref(), &flags, &oembed::public_base_url(), chrono::Utc::now().naive_utc())
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .json(config))
+}
+
+// Edit a video's title, description, tags or categories
+#[patch("/api/videos/{id}")]
+async fn update_video(
+    path: web::Path<i32>,
+    json_req: web::Json<VideoUpdateRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    let title = match &json_req.title {
+        Some(title) if title.trim().is_empty() => return Err(ApiError::BadRequest("Title is required".to_string())),
+        Some(title) => title.trim().to_string(),
+        None => video.title,
+    };
+    let description = match &json_req.description {
+        Some(description) => Some(description.trim().to_string()).filter(|d| !d.is_empty()),
+        None => video.description,
+    };
+    let tags = match &json_req.tags {
+        Some(tags) => Some(tags.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect::<Vec<_>>()),
+        None => video.tags,
+    };
+    let category_ids = match &json_req.category_ids {
+        Some(category_ids) => Some(categories::validate(&state.db_pool, category_ids).await?),
+        None => None,
+    };
+
+    let mut tx = state.db_pool.begin().await?;
+    let video = sqlx::query_as::<_, Video>(
+        "UPDATE videos SET title = $1, description = $2, tags = $3 WHERE id = $4 RETURNING *"
+    )
+    .bind(&title)
+    .bind(&description)
+    .bind(&tags)
+    .bind(video.id)
+    .fetch_one(&mut tx)
+    .await?;
+    if let Some(category_ids) = &category_ids {
+        categories::set_for_video(&mut tx, video.id, category_ids).await?;
+    }
+    tx.commit().await?;
+
+    let mut videos = [video];
+    categories::attach(&state.db_pool, &mut videos).await?;
+    let [video] = videos;
+    info!("Video {} updated by user {}", video.id, user_id);
+    Ok(HttpResponse::Ok().json(video))
+}
+
+// Soft delete: the video moves to the uploader's trash and is purged after TRASH_RETENTION_DAYS
+#[delete("/api/videos/{id}")]
+async fn delete_video(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    if video.uploaded_by != Some(user_id) {
+        return Err(ApiError::Forbidden("Only the uploader can delete this video".to_string()));
+    }
+
+    sqlx::query("UPDATE videos SET deleted_at = $1 WHERE id = $2")
+        .bind(chrono::Utc::now().naive_utc())
+        .bind(video_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    info!("Video {} moved to trash by user {}", video_id, user_id);
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Video moved to trash",
+        "retentionDays": TRASH_RETENTION_DAYS
+    })))
+}
+
+#[post("/api/videos/{id}/restore")]
+async fn restore_video(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NOT NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found in trash".to_string()))?;
+
+    if video.uploaded_by != Some(user_id) {
+        return Err(ApiError::Forbidden("Only the uploader can restore this video".to_string()));
+    }
+
+    let video = sqlx::query_as::<_, Video>("UPDATE videos SET deleted_at = NULL WHERE id = $1 RETURNING *")
+        .bind(video_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    info!("Video {} restored from trash by user {}", video_id, user_id);
+    Ok(HttpResponse::Ok().json(video))
+}
+
+// Everything the user uploaded or scraped, including videos still processing or awaiting moderation
+#[get("/api/users/me/videos")]
+async fn get_my_videos(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let mut videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE uploaded_by = $1 AND deleted_at IS NULL ORDER BY upload_date DESC, id DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    categories::attach(&state.db_pool, &mut videos).await?;
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+#[get("/api/users/me/trash")]
+async fn get_my_trash(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let mut videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE uploaded_by = $1 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    categories::attach(&state.db_pool, &mut videos).await?;
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+// Re-run duration extraction, thumbnail generation, transcoding, scene detection and (when configured)
+// transcription, replacing existing results
+#[post("/api/videos/{id}/reprocess")]
+async fn reprocess_video(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    if video.uploaded_by != Some(user_id) && !is_admin(&state.db_pool, user_id).await? {
+        return Err(ApiError::Forbidden("Only the uploader or an admin can reprocess this video".to_string()));
+    }
+
+    let job_queue = state.job_queue.as_ref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Job queue is not available".to_string()))?;
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let kinds = job_queue.enqueue_processing(&video, &bucket, true).await
+        .map_err(|e| ApiError::Internal(format!("Failed to enqueue jobs for video {}: {:?}", video_id, e)))?;
+
+    info!("Video {} queued for reprocessing by user {}", video_id, user_id);
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "Reprocessing queued",
+        "jobs": kinds
+    })))
+}
+
+// The video's transcript as JSON, or as WebVTT captions with ?format=vtt. ?q= narrows the JSON
+// segments to those containing the text, for jumping to where something is said. Only for
+// clients who could play the video, since it gives away what is said in it.
+#[get("/api/videos/{id}/transcript")]
+async fn get_video_transcript(
+    path: web::Path<i32>,
+    query: web::Query<TranscriptQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+    let user_id = authenticated_user_id(&http_req);
+    let manages_video = match user_id {
+        Some(user_id) => video.uploaded_by == Some(user_id) || is_admin(&state.db_pool, user_id).await?,
+        None => false,
+    };
+    if !manages_video {
+        if video.moderation_status != moderation::STATUS_APPROVED {
+            return Err(ApiError::Forbidden("This video's transcript isn't available".to_string()));
+        }
+        restrictions::check_playback(&video, &http_req)?;
+        premieres::check_started(&video, user_id)?;
+    }
+
+    let mut transcript = transcription::get_transcript(&state.db_pool, video_id).await?
+        .ok_or_else(|| ApiError::NotFound("Transcript not found".to_string()))?;
+
+    match query.format.as_deref() {
+        Some("vtt") => return Ok(HttpResponse::Ok()
+            .content_type("text/vtt; charset=utf-8")
+            .body(transcript.vtt)),
+        Some("json") | None => {}
+        Some(other) => return Err(ApiError::BadRequest(format!("Unsupported transcript format '{}'", other))),
+    }
+
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let q = q.to_lowercase();
+        transcript.segments.0.retain(|segment| segment.text.to_lowercase().contains(&q));
+    }
+    Ok(HttpResponse::Ok().json(transcript))
+}
+
+// Accepted chapters, in order. The owner can pass ?proposed=true to also see pending
+// auto chapters from scene detection.
+#[get("/api/videos/{id}/chapters")]
+async fn get_video_chapters(
+    path: web::Path<i32>,
+    query: web::Query<ChapterQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+
+    let include_proposed = query.proposed.unwrap_or(false);
+    if include_proposed {
+        owned_video(&state.db_pool, video_id, require_user_id(&http_req)?).await?;
+    } else {
+        sqlx::query_scalar::<_, i32>("SELECT id FROM videos WHERE id = $1 AND deleted_at IS NULL")
+            .bind(video_id)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+    }
+
+    let chapters = chapters::list_chapters(&state.db_pool, video_id, include_proposed).await?;
+    Ok(HttpResponse::Ok().json(chapters))
+}
+
+fn validate_chapter(title: &str, start_time: f64) -> Result<(), ApiError> {
+    if title.trim().is_empty() {
+        return Err(ApiError::BadRequest("Chapter title is required".to_string()));
+    }
+    if !start_time.is_finite() || start_time < 0.0 {
+        return Err(ApiError::BadRequest("Chapter startTime must be a non-negative number of seconds".to_string()));
+    }
+    Ok(())
+}
+
+#[post("/api/videos/{id}/chapters")]
+async fn create_video_chapter(
+    path: web::Path<i32>,
+    json_req: web::Json<ChapterRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    let (Some(title), Some(start_time)) = (json_req.title.as_deref(), json_req.start_time) else {
+        return Err(ApiError::BadRequest("title and startTime are required".to_string()));
+    };
+    validate_chapter(title, start_time)?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let chapter = sqlx::query_as::<_, VideoChapter>(
+        "INSERT INTO video_chapters (video_id, title, start_time, source, accepted, created_at, updated_at)
+         VALUES ($1, $2, $3, 'manual', TRUE, $4, $4) RETURNING *"
+    )
+    .bind(video.id)
+    .bind(title.trim())
+    .bind(start_time)
+    .bind(now)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok(HttpResponse::Created().json(chapter))
+}
+
+// Edit a chapter's title or start time; `accepted: true` accepts a proposed auto chapter
+#[put("/api/videos/{id}/chapters/{chapter_id}")]
+async fn update_video_chapter(
+    path: web::Path<(i32, i32)>,
+    json_req: web::Json<ChapterRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, chapter_id) = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+    owned_video(&state.db_pool, video_id, user_id).await?;
+
+    let chapter = sqlx::query_as::<_, VideoChapter>("SELECT * FROM video_chapters WHERE id = $1 AND video_id = $2")
+        .bind(chapter_id)
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Chapter not found".to_string()))?;
+
+    let title = json_req.title.as_deref().map(str::trim).unwrap_or(&chapter.title);
+    let start_time = json_req.start_time.unwrap_or(chapter.start_time);
+    validate_chapter(title, start_time)?;
+
+    let chapter = sqlx::query_as::<_, VideoChapter>(
+        "UPDATE video_chapters SET title = $1, start_time = $2, accepted = $3, updated_at = $4 WHERE id = $5 RETURNING *"
+    )
+    .bind(title)
+    .bind(start_time)
+    .bind(json_req.accepted.unwrap_or(chapter.accepted))
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(chapter.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(chapter))
+}
+
+// Accept every pending auto chapter for the video at once
+#[post("/api/videos/{id}/chapters/accept")]
+async fn accept_video_chapters(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    sqlx::query("UPDATE video_chapters SET accepted = TRUE, updated_at = $1 WHERE video_id = $2 AND NOT accepted")
+        .bind(chrono::Utc::now().naive_utc())
+        .bind(video.id)
+        .execute(&state.db_pool)
+        .await?;
+
+    let chapters = chapters::list_chapters(&state.db_pool, video.id, false).await?;
+    Ok(HttpResponse::Ok().json(chapters))
+}
+
+// Delete a chapter, or reject a proposed one
+#[delete("/api/videos/{id}/chapters/{chapter_id}")]
+async fn delete_video_chapter(
+    path: web::Path<(i32, i32)>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, chapter_id) = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+    owned_video(&state.db_pool, video_id, user_id).await?;
+
+    let result = sqlx::query("DELETE FROM video_chapters WHERE id = $1 AND video_id = $2")
+        .bind(chapter_id)
+        .bind(video_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Chapter not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Chapter deleted"
+    })))
+}
+
+fn translation_language(language: &str) -> Result<String, ApiError> {
+    translations::normalize_language(language)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid language tag: {}", language)))
+}
+
+// Every translation of the video's title and description
+#[get("/api/videos/{id}/translations")]
+async fn get_video_translations(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    sqlx::query_scalar::<_, i32>("SELECT id FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let translations = translations::list_translations(&state.db_pool, video_id).await?;
+    Ok(HttpResponse::Ok().json(translations))
+}
+
+// Add or replace the title and description in one language
+#[put("/api/videos/{id}/translations/{language}")]
+async fn put_video_translation(
+    path: web::Path<(i32, String)>,
+    json_req: web::Json<TranslationRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, language) = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, video_id, user_id).await?;
+    let language = translation_language(&language)?;
+
+    let title = json_req.title.trim();
+    if title.is_empty() {
+        return Err(ApiError::BadRequest("Translated title is required".to_string()));
+    }
+    let description = json_req.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    let translation = translations::upsert_translation(&state.db_pool, video.id, &language, title, description).await?;
+    info!("Video {} {} translation updated by user {}", video.id, language, user_id);
+    Ok(HttpResponse::Ok().json(translation))
+}
+
+#[delete("/api/videos/{id}/translations/{language}")]
+async fn delete_video_translation(
+    path: web::Path<(i32, String)>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, language) = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, video_id, user_id).await?;
+    let language = translation_language(&language)?;
+
+    if !translations::delete_translation(&state.db_pool, video.id, &language).await? {
+        return Err(ApiError::NotFound("Translation not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Translation deleted"
+    })))
+}
+
+// Frames sampled by the thumbnail job, best scoring first, for the owner to choose from
+#[get("/api/videos/{id}/thumbnail-candidates")]
+async fn get_thumbnail_candidates(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    let candidates = thumbnails::list_candidates(&state.db_pool, video.id).await?;
+    Ok(HttpResponse::Ok().json(candidates))
+}
+
+// Make one of the candidates the video's thumbnail
+#[post("/api/videos/{id}/thumbnail-candidates")]
+async fn select_thumbnail_candidate(
+    path: web::Path<i32>,
+    json_req: web::Json<ThumbnailSelectRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    let s3_key = sqlx::query_scalar::<_, String>("SELECT s3_key FROM thumbnail_candidates WHERE id = $1 AND video_id = $2")
+        .bind(json_req.candidate_id)
+        .bind(video.id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Thumbnail candidate not found".to_string()))?;
+
+    sqlx::query("UPDATE videos SET thumbnail_url = $1 WHERE id = $2")
+        .bind(&s3_key)
+        .bind(video.id)
+        .execute(&state.db_pool)
+        .await?;
+    info!("Video {} thumbnail set to candidate {} by user {}", video.id, json_req.candidate_id, user_id);
+
+    let candidates = thumbnails::list_candidates(&state.db_pool, video.id).await?;
+    Ok(HttpResponse::Ok().json(candidates))
+}
+
+// Set where the video can be watched and whether other sites may embed it
+#[put("/api/videos/{id}/restrictions")]
+async fn set_video_restrictions(
+    path: web::Path<i32>,
+    json_req: web::Json<RestrictionsRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+
+    let allowed = match &json_req.allowed_countries {
+        Some(codes) => restrictions::normalize_countries(codes).map_err(ApiError::BadRequest)?,
+        None => video.allowed_countries,
+    };
+    let blocked = match &json_req.blocked_countries {
+        Some(codes) => restrictions::normalize_countries(codes).map_err(ApiError::BadRequest)?,
+        None => video.blocked_countries,
+    };
+    if allowed.iter().any(|c| blocked.contains(c)) {
+        return Err(ApiError::BadRequest("A country can't be both allowed and blocked".to_string()));
+    }
+
+    let video = sqlx::query_as::<_, Video>(
+        "UPDATE videos SET allowed_countries = $1, blocked_countries = $2, allow_embed = $3, allow_download = $4 WHERE id = $5 RETURNING *"
+    )
+    .bind(&allowed)
+    .bind(&blocked)
+    .bind(json_req.allow_embed.unwrap_or(video.allow_embed))
+    .bind(json_req.allow_download.unwrap_or(video.allow_download))
+    .bind(video.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    info!("Video {} restrictions updated by user {}", video.id, user_id);
+    Ok(HttpResponse::Ok().json(video))
+}
+
+// Turn AES-128 HLS encryption on or off. Enabling queues packaging; until it finishes the
+// video can't be played. Disabling drops the keys and encrypted segments.
+#[put("/api/videos/{id}/encryption")]
+async fn set_video_encryption(
+    path: web::Path<i32>,
+    json_req: web::Json<EncryptionRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    if json_req.enabled {
+        let job_queue = state.job_queue.as_ref()
+            .ok_or_else(|| ApiError::ServiceUnavailable("Job queue is not available".to_string()))?;
+        let video = sqlx::query_as::<_, Video>("UPDATE videos SET hls_encrypted = TRUE WHERE id = $1 RETURNING *")
+            .bind(video.id)
+            .fetch_one(&state.db_pool)
+            .await?;
+        job_queue.enqueue(MediaJob::new(MediaJobKind::PackageHls, &video, &bucket)).await
+            .map_err(|e| ApiError::Internal(format!("Failed to enqueue HLS packaging for video {}: {:?}", video.id, e)))?;
+
+        info!("Encryption enabled for video {} by user {}", video.id, user_id);
+        return Ok(HttpResponse::Accepted().json(video));
+    }
+
+    let mut tx = state.db_pool.begin().await?;
+    let video = sqlx::query_as::<_, Video>("UPDATE videos SET hls_encrypted = FALSE WHERE id = $1 RETURNING *")
+        .bind(video.id)
+        .fetch_one(&mut tx)
+        .await?;
+    let old_keys = hls::delete_package(&mut tx, video.id).await?;
+    tx.commit().await?;
+
+    for old_key in old_keys {
+        if let Err(e) = state.storage.delete(&bucket, &old_key).await {
+            error!("Failed to delete HLS segment {} for video {}: {:?}", old_key, video.id, e);
+        }
+    }
+    info!("Encryption disabled for video {} by user {}", video.id, user_id);
+    Ok(HttpResponse::Ok().json(video))
+}
+
+// An encrypted video the client may play, for its HLS endpoints
+async fn hls_video(state: &AppState, video_id: i32, http_req: &actix_web::HttpRequest) -> Result<Video, ApiError> {
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    restrictions::check_playback(&video, http_req)?;
+    premieres::check_started(&video, authenticated_user_id(http_req))?;
+    if !video.hls_encrypted {
+        return Err(ApiError::NotFound("No HLS playlist for this video".to_string()));
+    }
+    Ok(video)
+}
+
+fn hls_playlist_response(playlist: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/vnd.apple.mpegurl")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .body(playlist)
+}
+
+// The encrypted playlist: a media playlist, or for videos packaged with alternate audio a master
+// playlist pointing at the video and audio ones. A ?token= is copied onto the key URIs, through
+// any playlists in between, for players that can't send an Authorization header with key requests.
+// A ?playback_token= is copied onto the playlists in between, which need it too.
+#[get("/api/videos/{id}/hls/index.m3u8")]
+async fn get_hls_playlist(
+    path: web::Path<i32>,
+    query: web::Query<HlsPlaylistQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let video_id = path.into_inner();
+    hotlinks::require_signature(query.expires, query.signature.as_deref())?;
+    signed_urls::verify_if_signed(&signed_urls::hls_playlist_path(video_id), query.expires, query.signature.as_deref(), chrono::Utc::now().timestamp())?;
+    let state = state.lock().await;
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = hls_video(&state, video_id, &http_req).await?;
+
+    let tracks = hls::packaged_audio_tracks(&state.db_pool, video.id).await?;
+    if !tracks.is_empty() {
+        return Ok(hls_playlist_response(hls::build_master_playlist(video.id, &tracks, query.token.as_deref(), query.playback_token.as_deref())));
+    }
+    let segments = hls::list_segments(&state.db_pool, video.id).await?;
+    if segments.is_empty() {
+        return Err(ApiError::NotFound("No HLS playlist for this video".to_string()));
+    }
+    Ok(hls_playlist_response(hls::build_playlist(video.id, &segments, query.token.as_deref())))
+}
+
+// The video-only media playlist of a video packaged with alternate audio
+#[get("/api/videos/{id}/hls/video.m3u8")]
+async fn get_hls_video_playlist(
+    path: web::Path<i32>,
+    query: web::Query<HlsPlaylistQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = hls_video(&state, video_id, &http_req).await?;
+    let segments = hls::list_segments(&state.db_pool, video.id).await?;
+    if segments.is_empty() {
+        return Err(ApiError::NotFound("No HLS playlist for this video".to_string()));
+    }
+    Ok(hls_playlist_response(hls::build_playlist(video.id, &segments, query.token.as_deref())))
+}
+
+#[get("/api/videos/{id}/hls/audio/{track}/index.m3u8")]
+async fn get_hls_audio_playlist(
+    path: web::Path<(i32, i32)>,
+    query: web::Query<HlsPlaylistQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, track_index) = path.into_inner();
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = hls_video(&state, video_id, &http_req).await?;
+    let segments = hls::list_audio_segments(&state.db_pool, video.id, track_index).await?;
+    if segments.is_empty() {
+        return Err(ApiError::NotFound("No such audio track".to_string()));
+    }
+    Ok(hls_playlist_response(hls::build_audio_playlist(video.id, track_index, &segments, query.token.as_deref())))
+}
+
+fn hls_segment_index(segment: &str) -> Result<i32, ApiError> {
+    segment.strip_suffix(".ts")
+        .and_then(|n| n.parse::<i32>().ok())
+        .ok_or_else(|| ApiError::NotFound("Segment not found".to_string()))
+}
+
+async fn hls_segment_response(state: &AppState, s3_key: &str, http_req: &actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let meter = bandwidth::check(state.redis_client.as_ref(), http_req, authenticated_user_id(http_req)).await?;
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = state.storage.get(&bucket_name, s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Error fetching HLS segment from storage: {:?}", e))))?;
+
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading HLS segment from storage: {:?}", e)))?;
+    meter.record(body.len() as u64).await;
+    Ok(HttpResponse::Ok()
+        .content_type("video/mp2t")
+        .insert_header(ContentEncoding::Identity)
+        .body(body))
+}
+
+// Segments are useless without their key, so they are served without authentication. Their
+// bytes count against the client's streaming allowance like any other playback.
+#[get("/api/videos/{id}/hls/{segment}")]
+async fn get_hls_segment(
+    path: web::Path<(i32, String)>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, segment) = path.into_inner();
+    let segment_index = hls_segment_index(&segment)?;
+
+    let s3_key = sqlx::query_scalar::<_, String>(
+        "SELECT s.s3_key FROM video_hls_segments s JOIN videos v ON v.id = s.video_id
+         WHERE s.video_id = $1 AND s.segment_index = $2 AND v.deleted_at IS NULL"
+    )
+    .bind(video_id)
+    .bind(segment_index)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Segment not found".to_string()))?;
+
+    hls_segment_response(&state, &s3_key, &http_req).await
+}
+
+#[get("/api/videos/{id}/hls/audio/{track}/{segment}")]
+async fn get_hls_audio_segment(
+    path: web::Path<(i32, i32, String)>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, track_index, segment) = path.into_inner();
+    let segment_index = hls_segment_index(&segment)?;
+
+    let s3_key = sqlx::query_scalar::<_, String>(
+        "SELECT s.s3_key FROM video_hls_audio_segments s JOIN videos v ON v.id = s.video_id
+         WHERE s.video_id = $1 AND s.track_index = $2 AND s.segment_index = $3 AND v.deleted_at IS NULL"
+    )
+    .bind(video_id)
+    .bind(track_index)
+    .bind(segment_index)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Segment not found".to_string()))?;
+
+    hls_segment_response(&state, &s3_key, &http_req).await
+}
+
+// The AES-128 key for one rotation period of the playlist. The uploader and admins can always
+// fetch it; other logged in users only once the video has passed moderation.
+#[get("/api/videos/{id}/key")]
+async fn get_hls_key(
+    path: web::Path<i32>,
+    query: web::Query<HlsKeyQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = authenticated_user_id(&http_req)
+        .or_else(|| query.token.as_deref().and_then(decode_user_id))
+        .ok_or(ApiError::Unauthorized)?;
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let approved = video.moderation_status == moderation::STATUS_APPROVED;
+    if !approved && video.uploaded_by != Some(user_id) && !is_admin(&state.db_pool, user_id).await? {
+        return Err(ApiError::Forbidden("This video is not available".to_string()));
+    }
+    restrictions::check_playback(&video, &http_req)?;
+    premieres::check_started(&video, Some(user_id))?;
+
+    let key = hls::get_key(&state.db_pool, video.id, query.index).await?
+        .ok_or_else(|| ApiError::NotFound("Key not found".to_string()))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore, CacheDirective::Private]))
+        .body(key))
+}
+
+// A playback token for the stream and HLS playlist URLs, see playback_tokens. Sending the
+// stream's previous token renews it, keeping its place among the account's concurrent streams.
+#[post("/api/videos/{id}/playback-token")]
+async fn create_playback_token(
+    path: web::Path<i32>,
+    json_req: Option<web::Json<PlaybackTokenRequest>>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
+    };
+    let user_id = require_user_id(&http_req)?;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(path.into_inner())
+        .fetch_optional(&db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let approved = video.moderation_status == moderation::STATUS_APPROVED;
+    if !approved && video.uploaded_by != Some(user_id) && !is_admin(&db_pool, user_id).await? {
+        return Err(ApiError::Forbidden("This video is not available".to_string()));
+    }
+    restrictions::check_playback(&video, &http_req)?;
+    premieres::check_started(&video, Some(user_id))?;
+
+    // An expired token still renews its stream, which then has to find room again if it lapsed
+    let stream_id = match json_req.and_then(|req| req.into_inner().token) {
+        Some(previous) => playback_tokens::parse(&previous, video.id)
+            .filter(|token| token.user_id == user_id)
+            .ok_or_else(|| ApiError::BadRequest("Invalid playback token".to_string()))?
+            .stream_id,
+        None => playback_tokens::new_stream_id(),
+    };
+    let now = chrono::Utc::now().naive_utc();
+    let expires_at = now + chrono::Duration::seconds(playback_tokens::ttl_secs());
+    let token = PlaybackToken { user_id, stream_id, expires: expires_at.and_utc().timestamp() };
+    playback_tokens::admit(redis_client.as_ref(), &token, now.and_utc().timestamp()).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore, CacheDirective::Private]))
+        .json(PlaybackTokenResponse {
+            token: playback_tokens::issue(video.id, user_id, &token.stream_id, token.expires),
+            stream_id: token.stream_id,
+            expires_at,
+        }))
+}
+
+// Stop a stream, so another one can start before its token would have expired
+#[delete("/api/videos/{id}/playback-token")]
+async fn delete_playback_token(
+    path: web::Path<i32>,
+    json_req: web::Json<PlaybackTokenRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let redis_client = state.lock().await.redis_client.clone();
+    let user_id = require_user_id(&http_req)?;
+    let token = json_req.into_inner().token
+        .and_then(|token| playback_tokens::parse(&token, path.into_inner()))
+        .filter(|token| token.user_id == user_id)
+        .ok_or_else(|| ApiError::BadRequest("Invalid playback token".to_string()))?;
+
+    playback_tokens::release(redis_client.as_ref(), &token).await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Schedule the video's premiere, or cancel it with a null premiereAt. Its watch party room is
+// scheduled to start at the same time.
+#[put("/api/videos/{id}/premiere")]
+async fn set_video_premiere(
+    path: web::Path<i32>,
+    json_req: web::Json<PremiereRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+    let premiere_at = json_req.premiere_at
+        .map(|at| premieres::validate_premiere_at(at, chrono::Utc::now()))
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    let (video, room) = premieres::schedule(&state.db_pool, &video, user_id, premiere_at).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "video": video,
+        "watchParty": room
+    })))
+}
+
+// Override the projection detected in the upload, e.g. for a 360° video whose metadata was
+// stripped. A projection of "none" marks the video as flat.
+#[put("/api/videos/{id}/spherical")]
+async fn set_video_spherical(
+    path: web::Path<i32>,
+    json_req: web::Json<SphericalRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let video = owned_video(&state.db_pool, path.into_inner(), user_id).await?;
+    let requested = spherical::validate(&json_req).map_err(ApiError::BadRequest)?;
+
+    let saved = spherical::set_override(&state.db_pool, video.id, &requested).await?;
+    info!("User {} set video {} projection to {} ({})", user_id, video.id, saved.projection, saved.stereo_mode);
+    Ok(HttpResponse::Ok().json(saved))
+}
+
+// Every audio track of the video, for players offering a choice of language or audio description
+#[get("/api/videos/{id}/audio-tracks")]
+async fn list_audio_tracks(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(path.into_inner())
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+    restrictions::check_playback(&video, &http_req)?;
+
+    Ok(HttpResponse::Ok().json(audio_tracks::list(&state.db_pool, video.id).await?))
+}
+
+// Attach a standalone audio track, e.g. audio description, with the audio file as the body.
+// Encrypted videos are packaged again to add it to their HLS alternate audio.
+#[post("/api/videos/{id}/audio-tracks")]
+async fn upload_audio_track(
+    path: web::Path<i32>,
+    query: web::Query<AudioTrackUploadQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    let content_type = http_req.headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .filter(|ct| audio_tracks::is_audio_content_type(ct))
+        .ok_or_else(|| ApiError::BadRequest("The body must be an audio file with an audio/* Content-Type".to_string()))?
+        .to_string();
+    let language = query.language.as_deref()
+        .map(|l| audio_tracks::normalize_language(l).ok_or_else(|| ApiError::BadRequest("Invalid language tag".to_string())))
+        .transpose()?;
+    let label = audio_tracks::standalone_label(query.label.as_deref(), language.as_deref(), query.audio_description)
+        .map_err(ApiError::BadRequest)?;
+
+    // Receiving the file can take a while, so don't hold the app state lock meanwhile
+    let (db_pool, storage, job_queue) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.storage.clone(), state.job_queue.clone())
+    };
+    let video = owned_video(&db_pool, path.into_inner(), user_id).await?;
+    let body = audio_tracks::read_upload(payload).await?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let s3_key = audio_tracks::standalone_s3_key(video.id);
+    storage.put(&bucket, &s3_key, body, &content_type)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Failed to store audio track: {:?}", e))))?;
+    let track = audio_tracks::add_standalone(&db_pool, video.id, &label, language.as_deref(), query.audio_description, &s3_key, &content_type).await?;
+
+    if video.hls_encrypted {
+        if let Some(job_queue) = job_queue {
+            let job = MediaJob { force: true, ..MediaJob::new(MediaJobKind::PackageHls, &video, &bucket) };
+            if let Err(e) = job_queue.enqueue(job).await {
+                error!("Failed to enqueue HLS packaging for video {} after adding an audio track: {:?}", video.id, e);
+            }
+        }
+    }
+    info!("User {} added audio track {} ({}) to video {}", user_id, track.track_index, track.label, video.id);
+    Ok(HttpResponse::Created().json(track))
+}
+
+// The file of a standalone audio track, for players of the progressive renditions to play
+// alongside the video. Tracks of the upload itself are already in the renditions.
+#[get("/api/videos/{id}/audio-tracks/{track}")]
+async fn get_audio_track(
+    path: web::Path<(i32, i32)>,
+    query: web::Query<PlaybackTokenQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (video_id, track_index) = path.into_inner();
+    playback_tokens::check(state.redis_client.as_ref(), video_id, query.playback_token.as_deref(), chrono::Utc::now().timestamp()).await?;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+    if video.hls_encrypted {
+        return Err(ApiError::Forbidden("This video is only available as encrypted HLS".to_string()));
+    }
+    restrictions::check_playback(&video, &http_req)?;
+    premieres::check_started(&video, authenticated_user_id(&http_req))?;
+
+    let track = audio_tracks::get(&state.db_pool, video.id, track_index)
+        .await?
+        .filter(|track| track.standalone)
+        .ok_or_else(|| ApiError::NotFound("No standalone audio track with that index".to_string()))?;
+    let s3_key = track.s3_key.as_deref().unwrap_or_default();
+    let meter = bandwidth::check(state.redis_client.as_ref(), &http_req, authenticated_user_id(&http_req)).await?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = state.storage.get(&bucket, s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Error fetching audio track from storage: {:?}", e))))?;
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading audio track from storage: {:?}", e)))?;
+    meter.record(body.len() as u64).await;
+    Ok(HttpResponse::Ok()
+        .content_type(track.content_type.as_deref().unwrap_or("application/octet-stream"))
+        .insert_header(ContentEncoding::Identity)
+        .body(body))
+}
+
+#[delete("/api/videos/{id}/audio-tracks/{track}")]
+async fn delete_audio_track(
+    path: web::Path<(i32, i32)>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let (video_id, track_index) = path.into_inner();
+    let video = owned_video(&state.db_pool, video_id, user_id).await?;
+    let s3_key = audio_tracks::remove_standalone(&state.db_pool, video.id, track_index)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No standalone audio track with that index".to_string()))?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    // The track leaves the master playlist at once; packaging again clears out its segments
+    if video.hls_encrypted {
+        if let Some(job_queue) = state.job_queue.as_ref() {
+            let job = MediaJob { force: true, ..MediaJob::new(MediaJobKind::PackageHls, &video, &bucket) };
+            if let Err(e) = job_queue.enqueue(job).await {
+                error!("Failed to enqueue HLS packaging for video {} after removing an audio track: {:?}", video.id, e);
+            }
+        }
+    }
+    if let Err(e) = state.storage.delete(&bucket, &s3_key).await {
+        error!("Failed to delete audio track {} of video {}: {:?}", s3_key, video.id, e);
+    }
+    info!("User {} removed audio track {} from video {}", user_id, track_index, video.id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Resumable uploads follow the tus.io core protocol: create a session, then PATCH the file in
+// any number of chunks at the offset reported by HEAD, resuming there after a dropped connection.
+const TUS_VERSION: &str = "1.0.0";
+
+fn upload_headers(session: &UploadSession) -> Vec<(&'static str, String)> {
+    let expires = std::time::UNIX_EPOCH + std::time::Duration::from_secs(session.expires_at.and_utc().timestamp().max(0) as u64);
+    vec![
+        ("Tus-Resumable", TUS_VERSION.to_string()),
+        ("Upload-Offset", session.upload_offset.to_string()),
+        ("Upload-Length", session.upload_length.to_string()),
+        ("Upload-Expires", actix_web::http::header::HttpDate::from(expires).to_string()),
+    ]
+}
+
+#[post("/api/uploads")]
+async fn create_upload(
+    json_req: web::Json<CreateUploadRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let session = uploads::create_session(&state.db_pool, state.storage.as_ref(), &bucket, user_id, &json_req).await?;
+
+    let mut response = HttpResponse::Created();
+    response.insert_header((actix_web::http::header::LOCATION, format!("/api/uploads/{}", session.id)));
+    for header in upload_headers(&session) {
+        response.insert_header(header);
+    }
+    Ok(response.json(json!({
+        "id": session.id,
+        "uploadOffset": session.upload_offset,
+        "uploadLength": session.upload_length,
+        "expiresAt": session.expires_at
+    })))
+}
+
+#[head("/api/uploads/{id}")]
+async fn get_upload_offset(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let session = uploads::get_session(&state.db_pool, &path.into_inner(), user_id).await?;
+
+    let mut response = HttpResponse::Ok();
+    response.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+    for header in upload_headers(&session) {
+        response.insert_header(header);
+    }
+    if let Some(video_id) = session.video_id {
+        response.insert_header((actix_web::http::header::LOCATION, format!("/api/videos/{}", video_id)));
+    }
+    Ok(response.finish())
+}
+
+#[patch("/api/uploads/{id}")]
+async fn upload_chunk(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    let upload_id = path.into_inner();
+    let offset = http_req.headers()
+        .get("Upload-Offset")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<i64>().ok())
+        .ok_or_else(|| ApiError::BadRequest("Upload-Offset header is required".to_string()))?;
+
+    // Receiving a chunk can take minutes, so don't hold the app state lock meanwhile
+    let (db_pool, storage, job_queue) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.storage.clone(), state.job_queue.clone())
+    };
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    // No row lock while the body streams in, which can take minutes: append_chunk only records
+    // the chunk if the offset is still the one it started from
+    let mut session = uploads::get_session(&db_pool, &upload_id, user_id).await?;
+    if session.video_id.is_some() {
+        return Err(ApiError::Conflict("Upload is already complete".to_string()));
+    }
+    if offset != session.upload_offset {
+        return Err(ApiError::Conflict(format!(
+            "Upload-Offset {} does not match the current offset {}", offset, session.upload_offset
+        )));
+    }
+
+    uploads::append_chunk(&db_pool, storage.as_ref(), &bucket, &mut session, payload).await?;
+
+    let mut response = HttpResponse::NoContent();
+    if let Some(video) = uploads::complete_if_finished(&db_pool, storage.as_ref(), &bucket, &upload_id, user_id).await? {
+        if let Some(job_queue) = job_queue {
+            if let Err(e) = job_queue.enqueue_processing(&video, &bucket, false).await {
+                error!("Failed to enqueue processing for uploaded video {}: {:?}", video.id, e);
+            }
+        }
+        webhooks::video_created(&db_pool, &video).await;
+        response.insert_header((actix_web::http::header::LOCATION, format!("/api/videos/{}", video.id)));
+    }
+    for header in upload_headers(&session) {
+        response.insert_header(header);
+    }
+    Ok(response.finish())
+}
+
+#[delete("/api/uploads/{id}")]
+async fn cancel_upload(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let mut tx = state.db_pool.begin().await?;
+    let session = uploads::lock_session(&mut tx, &path.into_inner(), user_id).await?;
+    uploads::abort_session(state.storage.as_ref(), &bucket, &session).await?;
+    sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+        .bind(&session.id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent()
+        .insert_header(("Tus-Resumable", TUS_VERSION))
+        .finish())
+}
+
+#[post("/api/comments/{video_id}")]
+async fn post_comment(
+    path: web::Path<i32>,
+    json_req: web::Json<CommentRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let app_state = state.get_ref().clone();
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    // Log the incoming request for debugging
+    info!("Received comment request for video_id: {}, user_id: {}, text: {}, video_time: {}", video_id, user_id, json_req.text, json_req.video_time);
+
+    let comment = sqlx::query_as::<_, Comment>(
+        "INSERT INTO comments (video_id, user_id, content, video_time, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+    )
+    .bind(video_id)
+    .bind(user_id)
+    .bind(&json_req.text)
+    .bind(json_req.video_time)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    // Clone necessary data for the background task
+    let comment_clone = comment.clone();
+    
+    // Get the video_clients_clone directly from the state we already have locked
+    let video_clients_clone = state.video_clients.lock().unwrap().clone();
+    
+    broadcast_comment(video_id, comment_clone, video_clients_clone);
+    // Runs once this handler releases the state
+    broadcast_comment_to_watch_party(app_state, &comment);
+
+    if let Err(e) = webhooks::dispatch(&state.db_pool, webhooks::EVENT_COMMENT_CREATED, json!(comment)).await {
+        error!("Failed to queue comment.created webhooks for comment {}: {:?}", comment.id, e);
+    }
+    
+    // Return the response immediately without waiting for broadcast
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+#[get("/api/comments/{video_id}")]
+async fn get_comments(
+    path: web::Path<i32>,
+    query: web::Query<CommentListQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    // Each order is served by an index on comments, see the listing and comment vote migrations
+    let order_by = match query.sort.unwrap_or_default() {
+        CommentSort::Top => "(upvotes - downvotes) DESC, created_at DESC, id DESC",
+        CommentSort::Newest => "created_at DESC, id DESC",
+        CommentSort::Timestamp => "video_time ASC",
+    };
+    let comments = sqlx::query_as::<_, Comment>(&format!("SELECT * FROM comments WHERE video_id = $1 ORDER BY {}", order_by))
+        .bind(video_id)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(comments))
+}
+
+// The video's comments as a download: SubRip or WebVTT captions with ?format=srt|vtt, or JSON
+#[get("/api/videos/{id}/comments/export")]
+async fn export_comments(
+    path: web::Path<i32>,
+    query: web::Query<CommentExportQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let format = CommentExportFormat::parse(query.format.as_deref()).map_err(ApiError::BadRequest)?;
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+
+    sqlx::query_scalar::<_, i32>("SELECT id FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let comments = comment_export::comment_track(&state.db_pool, video_id).await?;
+    let body = match format {
+        CommentExportFormat::Srt => comment_export::to_srt(&comments),
+        CommentExportFormat::Vtt => comment_export::to_vtt(&comments),
+        CommentExportFormat::Json => json!({ "video_id": video_id, "comments": comments }).to_string(),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            actix_web::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"video-{}-comments.{}\"", video_id, format.extension()),
+        ))
+        .body(body))
+}
+
+// Vote a comment up or down; voting again replaces the user's earlier vote
+#[put("/api/comments/{comment_id}/vote")]
+async fn vote_comment(
+    path: web::Path<i32>,
+    json_req: web::Json<CommentVoteRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    if json_req.value != 1 && json_req.value != -1 {
+        return Err(ApiError::BadRequest("value must be 1 or -1".to_string()));
+    }
+    let state = state.lock().await;
+    let comment_id = path.into_inner();
+    let mut tx = state.db_pool.begin().await?;
+    sqlx::query("SELECT id FROM comments WHERE id = $1 FOR UPDATE")
+        .bind(comment_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Comment not found".to_string()))?;
+    sqlx::query(
+        "INSERT INTO comment_votes (comment_id, user_id, value) VALUES ($1, $2, $3)
+         ON CONFLICT (comment_id, user_id) DO UPDATE SET value = EXCLUDED.value
+         WHERE comment_votes.value <> EXCLUDED.value"
+    )
+    .bind(comment_id)
+    .bind(user_id)
+    .bind(json_req.value)
+    .execute(&mut tx)
+    .await?;
+    let comment = sqlx::query_as::<_, Comment>("SELECT * FROM comments WHERE id = $1")
+        .bind(comment_id)
+        .fetch_one(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+#[delete("/api/comments/{comment_id}/vote")]
+async fn unvote_comment(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    let state = state.lock().await;
+    let comment_id = path.into_inner();
+    let mut tx = state.db_pool.begin().await?;
+    sqlx::query("DELETE FROM comment_votes WHERE comment_id = $1 AND user_id = $2")
+        .bind(comment_id)
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+    let comment = sqlx::query_as::<_, Comment>("SELECT * FROM comments WHERE id = $1")
+        .bind(comment_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Comment not found".to_string()))?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+#[post("/api/watchparty/{video_id}/join")]
+async fn join_watch_party(
+    path: web::Path<i32>,
+    query: web::Query<WatchPartyJoinQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    if !watch_parties::can_join(&state.db_pool, video_id, Some(user_id), query.invite.as_deref()).await? {
+        return Err(ApiError::Forbidden("This watch party is invite-only".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Joined watch party",
+        "videoId": video_id,
+        "userId": user_id
+    })))
+}
+
+// A guest identity for joining watch parties without an account, a few an hour per IP. The token
+// goes in ?guest= when connecting to a party's WebSocket; guests show up there with their guest id
+// negated.
+#[post("/api/watchparty/guest")]
+async fn create_watch_party_guest(
+    json_req: web::Json<WatchPartyGuestRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let client = sessions::client_ip(&http_req).unwrap_or_else(|| "unknown".to_string());
+    watch_parties::check_guest_rate(state.redis_client.as_ref(), &client, chrono::Utc::now().timestamp()).await?;
+    let display_name = watch_parties::validate_display_name(&json_req.display_name).map_err(ApiError::BadRequest)?;
+
+    let guest = watch_parties::create_guest(&state.db_pool, &display_name).await?;
+    let expires_at = guest.created_at + chrono::Duration::days(watch_parties::GUEST_TOKEN_DAYS);
+    let token = issue_guest_token(guest.id, expires_at)?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "guest": {
+            "id": guest.id,
+            "displayName": guest.display_name,
+            "participantId": watch_parties::Participant::Guest(guest.id).room_id()
+        },
+        "guestToken": token,
+        "expiresAt": expires_at
+    })))
+}
+
+// Link a guest identity to the signed in account (after registering or logging in), moving the
+// guest's watch party history over. The guest token stops working.
+#[post("/api/watchparty/guest/claim")]
+async fn claim_watch_party_guest(
+    json_req: web::Json<WatchPartyGuestClaimRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let guest_id = decode_guest_id(&json_req.guest_token)
+        .ok_or_else(|| ApiError::BadRequest("Invalid or expired guest token".to_string()))?;
+
+    let sessions = watch_parties::claim_guest(&state.db_pool, guest_id, user_id)
+        .await?
+        .ok_or_else(|| ApiError::Conflict("This guest has already been linked to an account".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Guest history merged into your account",
+        "mergedSessions": sessions
+    })))
+}
+
+// Display names of the guests in a video's running watch party
+#[get("/api/watchparty/{video_id}/guests")]
+async fn get_watch_party_guests(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let guests = watch_parties::guests_in_room(&state.db_pool, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(guests))
+}
+
+// ICE (STUN/TURN) servers for watch party voice chat; signaling itself goes over the watch party WebSocket
+#[get("/api/watchparty/rtc-config")]
+async fn get_watch_party_rtc_config(http_req: actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    Ok(HttpResponse::Ok().json(watch_parties::rtc_config(user_id)))
+}
+
+// Invite users (notified through the notifications subsystem) or, with no userIds, create a
+// shareable invite link. Only the room's host can, see watch_parties::get_or_create_room.
+#[post("/api/watchparty/{video_id}/invite")]
+async fn invite_to_watch_party(
+    path: web::Path<i32>,
+    json_req: web::Json<WatchPartyInviteRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let expires_in_hours = json_req.expires_in_hours.unwrap_or(watch_parties::DEFAULT_INVITE_HOURS);
+    if !(1..=watch_parties::MAX_INVITE_HOURS).contains(&expires_in_hours) {
+        return Err(ApiError::BadRequest(format!(
+            "expiresInHours must be between 1 and {}", watch_parties::MAX_INVITE_HOURS
+        )));
+    }
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let mut room = match watch_parties::get_or_create_room(&state.db_pool, video_id, user_id).await? {
+        Some(room) if room.host_user_id == user_id => room,
+        _ => return Err(ApiError::Forbidden("Only the host can invite people to this watch party".to_string())),
+    };
+    if let Some(invite_only) = json_req.invite_only {
+        watch_parties::set_invite_only(&state.db_pool, video_id, invite_only).await?;
+        room.invite_only = invite_only;
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let user_ids: Vec<i32> = json_req.user_ids.iter().flatten().copied().filter(|id| seen.insert(*id)).collect();
+    if user_ids.len() > watch_parties::MAX_INVITED_USERS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} users can be invited at once", watch_parties::MAX_INVITED_USERS
+        )));
+    }
+    if user_ids.is_empty() {
+        let invite = watch_parties::create_invite(&state.db_pool, video_id, user_id, None, expires_in_hours).await?;
+        return Ok(HttpResponse::Created().json(json!({
+            "inviteOnly": room.invite_only,
+            "token": invite.token,
+            "inviteUrl": format!("/video/{}?invite={}", video_id, invite.token),
+            "expiresAt": invite.expires_at
+        })));
+    }
+
+    let mut invites = Vec::new();
+    for invited_user_id in user_ids {
+        if invited_user_id == user_id {
+            continue;
+        }
+        let invite = watch_parties::create_invite(&state.db_pool, video_id, user_id, Some(invited_user_id), expires_in_hours).await?;
+        let payload = json!({
+            "videoId": video_id,
+            "videoTitle": video.title,
+            "invitedBy": user_id,
+            "inviteUrl": format!("/video/{}?invite={}", video_id, invite.token),
+            "expiresAt": invite.expires_at
+        });
+        if let Err(e) = create_notification(&state.db_pool, invited_user_id, "watch_party_invite", payload).await {
+            error!("Failed to notify user {} of watch party invite: {:?}", invited_user_id, e);
+        }
+        invites.push(json!({
+            "userId": invited_user_id,
+            "expiresAt": invite.expires_at
+        }));
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "inviteOnly": room.invite_only,
+        "invites": invites
+    })))
+}
+
+// Schedule the party to start at a set time. Until then playback is locked and joined clients
+// get a countdown; at the start the server plays the video for everyone.
+#[post("/api/watchparty/{video_id}/schedule")]
+async fn schedule_watch_party(
+    path: web::Path<i32>,
+    json_req: web::Json<WatchPartyScheduleRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let scheduled_start = json_req.scheduled_start.map(|start| start.naive_utc());
+    if let Some(start) = scheduled_start {
+        let now = chrono::Utc::now().naive_utc();
+        if start <= now {
+            return Err(ApiError::BadRequest("scheduledStart must be in the future".to_string()));
+        }
+        if start > now + chrono::Duration::days(watch_parties::MAX_SCHEDULE_DAYS) {
+            return Err(ApiError::BadRequest(format!(
+                "scheduledStart must be within {} days", watch_parties::MAX_SCHEDULE_DAYS
+            )));
+        }
+    }
+
+    sqlx::query_scalar::<_, i32>("SELECT id FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    match watch_parties::get_or_create_room(&state.db_pool, video_id, user_id).await? {
+        Some(room) if room.host_user_id == user_id => {}
+        _ => return Err(ApiError::Forbidden("Only the host can schedule this watch party".to_string())),
+    }
+    let room = watch_parties::set_scheduled_start(&state.db_pool, video_id, scheduled_start).await?;
+    info!("User {} scheduled the watch party for video {} to start at {:?}", user_id, video_id, room.scheduled_start);
+
+    watch_party_response(&state.db_pool, room).await
+}
+
+// A watch party and its video, for clients who could watch the video. Invite-only rooms are
+// only shown to their host and invitees; to everyone else they don't exist.
+async fn visible_watch_party(
+    db_pool: &sqlx::PgPool,
+    video_id: i32,
+    invite: Option<&str>,
+    http_req: &actix_web::HttpRequest,
+) -> Result<(WatchParty, Video), ApiError> {
+    let not_found = || ApiError::NotFound("Watch party not found".to_string());
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(not_found)?;
+
+    let user_id = authenticated_user_id(http_req);
+    let manages_video = match user_id {
+        Some(user_id) => video.uploaded_by == Some(user_id) || is_admin(db_pool, user_id).await?,
+        None => false,
+    };
+    if !manages_video {
+        if video.moderation_status != moderation::STATUS_APPROVED {
+            return Err(not_found());
+        }
+        restrictions::check_playback(&video, http_req)?;
+    }
+
+    let room = watch_parties::get_room(db_pool, video_id).await?.ok_or_else(not_found)?;
+    if !manages_video && !watch_parties::can_join(db_pool, video_id, user_id, invite).await? {
+        return Err(not_found());
+    }
+    Ok((room, video))
+}
+
+// The room's host, access and schedule, with the scheduled start as a calendar event
+#[get("/api/watchparty/{video_id}")]
+async fn get_watch_party(
+    path: web::Path<i32>,
+    query: web::Query<WatchPartyJoinQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let (room, _) = visible_watch_party(&state.db_pool, path.into_inner(), query.invite.as_deref(), &http_req).await?;
+    watch_party_response(&state.db_pool, room).await
+}
+
+async fn watch_party_response(db_pool: &sqlx::PgPool, room: WatchParty) -> Result<HttpResponse, ApiError> {
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(room.video_id)
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let locked = room.scheduled_start.is_some_and(|start| start > now);
+    let calendar = watch_parties::calendar_event(&room, &video, &oembed::public_base_url()).map(|event| json!({
+        "title": event.title,
+        "description": event.description,
+        "start": event.start,
+        "end": event.end,
+        "url": event.url,
+        "icsUrl": format!("/api/watchparty/{}/calendar.ics", room.video_id)
+    }));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "videoId": room.video_id,
+        "hostUserId": room.host_user_id,
+        "inviteOnly": room.invite_only,
+        "scheduledStart": room.scheduled_start,
+        "startedAt": room.started_at,
+        "locked": locked,
+        "secondsUntilStart": room.scheduled_start.filter(|_| locked).map(|start| (start - now).num_seconds()),
+        "calendar": calendar
+    })))
+}
+
+// The scheduled start as an iCalendar file, for "add to calendar" links
+#[get("/api/watchparty/{video_id}/calendar.ics")]
+async fn get_watch_party_calendar(
+    path: web::Path<i32>,
+    query: web::Query<WatchPartyJoinQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let video_id = path.into_inner();
+    let (room, video) = visible_watch_party(&state.db_pool, video_id, query.invite.as_deref(), &http_req).await?;
+    let event = watch_parties::calendar_event(&room, &video, &oembed::public_base_url())
+        .ok_or_else(|| ApiError::NotFound("This watch party isn't scheduled".to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header((actix_web::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"watchparty-{}.ics\"", video_id)))
+        .body(event.to_ics(&oembed::provider_name())))
+}
+
+#[post("/api/watchparty/{video_id}/control")]
+async fn control_watch_party(
+    _path: web::Path<i32>,
+    req: web::Json<serde_json::Value>,
+    _state: web::Data<Arc<Mutex<AppState>>>,
+    _auth: web::Data<Arc<Mutex<Claims>>>,
+) -> HttpResponse {
+    // let claims = auth.lock().await;
+    // let video_id = path.into_inner();
+    // let user_id = claims.user_id;
+    let action = req.get("action").and_then(|v| v.as_str()).unwrap_or("");
+    let time = req.get("time").and_then(|v| v.as_f64()).unwrap_or(0.0);
+
+    // Broadcast control message to all connected clients for this video
+    // This would require WebSocket implementation
+    HttpResponse::Ok().json(json!({
+        "message": "Control message sent",
+        "action": action,
+        "time": time
+    }))
+}
+
+// One year, the conventional maximum for immutable assets
+const THUMBNAIL_MAX_AGE_SECS: u32 = 365 * 24 * 60 * 60;
+
+// Fetch a thumbnail from storage. Immutable ones are cached for good; `cache` is the policy otherwise.
+async fn thumbnail_response(storage: &dyn ObjectStore, s3_key: &str, cache: Option<CacheControl>) -> Result<HttpResponse, ApiError> {
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = storage.get(&bucket_name, s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| {
+            error!("Error fetching thumbnail from storage: {:?}", e);
+            ApiError::NotFound("Thumbnail not found".to_string())
+        }))?;
+
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading thumbnail from storage: {:?}", e)))?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/jpeg")
+        .insert_header(ContentEncoding::Identity)
+        .insert_header(cache.unwrap_or_else(|| CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(THUMBNAIL_MAX_AGE_SECS),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ])))
+        .body(body))
+}
+
+#[get("/api/thumbnails/{thumbnail_key}")]
+async fn get_thumbnail(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let s3_key = thumbnails::thumbnail_s3_key(&path.into_inner())
+        .ok_or_else(|| ApiError::BadRequest("Invalid thumbnail key".to_string()))?;
+    if !thumbnails::is_known_thumbnail(&state.db_pool, &s3_key).await? {
+        return Err(ApiError::NotFound("Thumbnail not found".to_string()));
+    }
+    // Thumbnail keys are unique per upload, so the content never changes and can be cached indefinitely
+    thumbnail_response(state.storage.as_ref(), &s3_key, None).await
+}
+
+// The signed thumbnail URL API responses hand out, resolved to the video's current thumbnail
+#[get("/api/videos/{id}/thumbnail")]
+async fn get_video_thumbnail(
+    path: web::Path<i32>,
+    query: web::Query<SignedUrlQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let video_id = path.into_inner();
+    let (Some(version), Some(expires), Some(signature)) = (query.v.as_deref(), query.expires, query.signature.as_deref()) else {
+        return Err(ApiError::Forbidden("Invalid URL signature".to_string()));
+    };
+    signed_urls::verify(&signed_urls::thumbnail_path(video_id, version), expires, signature, chrono::Utc::now().timestamp())?;
+
+    let state = state.lock().await;
+    let s3_key = sqlx::query_scalar::<_, Option<String>>("SELECT thumbnail_url FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .flatten()
+        .filter(|key| key.starts_with(thumbnails::THUMBNAIL_PREFIX))
+        .ok_or_else(|| ApiError::NotFound("Thumbnail not found".to_string()))?;
+
+    // The version names the thumbnail the URL was signed for; once the video's thumbnail has
+    // changed, the new one is served but must not be cached under the old URL
+    let cache = (signed_urls::thumbnail_version(&s3_key) != version).then(|| CacheControl(vec![CacheDirective::NoCache]));
+    thumbnail_response(state.storage.as_ref(), &s3_key, cache).await
+}
+
+#[get("/api/user/settings")]
+async fn get_user_settings(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "settings": user_settings::from_stored(user.settings)
+    })))
+}
+
+#[post("/api/user/settings")]
+async fn update_user_settings(
+    json_req: web::Json<UserSettingsRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let patch = user_settings::validate_update(&json_req).map_err(ApiError::BadRequest)?;
+    let settings = user_settings::update(&state.db_pool, user_id, patch)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Settings updated successfully",
+        "settings": settings
+    })))
+}
+
+#[put("/api/user/watermark")]
+async fn upload_watermark(
+    body: web::Bytes,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    watermarks::validate_image(&body).map_err(ApiError::BadRequest)?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let s3_key = watermarks::watermark_s3_key(user_id);
+    state.storage.put(&bucket, &s3_key, body.clone(), "image/png")
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Failed to store watermark: {:?}", e))))?;
+
+    if let Some(old_key) = watermarks::set_watermark(&state.db_pool, user_id, &s3_key).await? {
+        if let Err(e) = state.storage.delete(&bucket, &old_key).await {
+            error!("Failed to delete old watermark {} for user {}: {:?}", old_key, user_id, e);
+        }
+    }
+    info!("Watermark updated for user {}", user_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/api/user/watermark")]
+async fn get_watermark(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let s3_key = watermarks::watermark_key(&state.db_pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No watermark uploaded".to_string()))?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = state.storage.get(&bucket, &s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| {
+            error!("Error fetching watermark {} from storage: {:?}", s3_key, e);
+            ApiError::NotFound("Watermark not found".to_string())
+        }))?;
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading watermark from storage: {:?}", e)))?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(ContentEncoding::Identity)
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache]))
+        .body(body))
+}
+
+#[delete("/api/user/watermark")]
+async fn delete_watermark(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+    let s3_key = watermarks::remove_watermark(&state.db_pool, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("No watermark uploaded".to_string()))?;
+
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    if let Err(e) = state.storage.delete(&bucket, &s3_key).await {
+        error!("Failed to delete watermark {} for user {}: {:?}", s3_key, user_id, e);
+    }
+    info!("Watermark removed for user {}", user_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/api/categories")]
+async fn get_categories(state: web::Data<Arc<Mutex<AppState>>>) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let categories = sqlx::query_as::<_, Category>("SELECT * FROM categories ORDER BY name ASC")
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+#[get("/api/videos/category/{category_id}")]
+async fn get_videos_by_category(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let category_id = path.into_inner();
+    let videos = sqlx::query_as::<_, Video>(
+        "SELECT v.* FROM videos v JOIN videos_categories vc ON vc.video_id = v.id
+         WHERE vc.category_id = $1 AND v.deleted_at IS NULL AND v.moderation_status = 'approved'
+         ORDER BY v.upload_date DESC"
+    )
+        .bind(category_id)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    localized_list(&state, videos, &http_req).await
+}
+
+fn feed_response(xml: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/rss+xml; charset=utf-8")
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(300)]))
+        .body(xml)
+}
+
+// A user's channel page: the featured video, the uploader's sections and all of their public
+// videos, custom ordered ones first
+#[get("/api/users/{id}/videos")]
+async fn get_channel_videos(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = path.into_inner();
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    let mut videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE uploaded_by = $1 AND deleted_at IS NULL AND moderation_status = 'approved' ORDER BY upload_date DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+    translations::localize(&state.db_pool, &mut videos, &translations::preferred_languages(&http_req)).await?;
+
+    let layout = channels::load_layout(&state.db_pool, user_id).await?;
+    let (featured, sections, videos) = channels::arrange(videos, &layout);
+    Ok(HttpResponse::Ok()
+        .insert_header((VARY, "Accept-Language"))
+        .json(channels::ChannelPage {
+            user: channels::ChannelOwner { id: user_id, username },
+            featured,
+            sections,
+            videos,
+        }))
+}
+
+#[get("/api/users/me/channel")]
+async fn get_my_channel_layout(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let layout = channels::load_layout(&state.db_pool, user_id).await?;
+    Ok(HttpResponse::Ok().json(layout))
+}
+
+// Replace the channel's featured video, custom order and sections
+#[put("/api/users/me/channel")]
+async fn set_my_channel_layout(
+    json_req: web::Json<ChannelLayout>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let owned = channels::owned_video_ids(&state.db_pool, user_id).await?;
+    let layout = channels::validate_layout(&json_req, &owned).map_err(ApiError::BadRequest)?;
+    channels::save_layout(&state.db_pool, user_id, &layout).await?;
+
+    info!("Channel layout of user {} updated", user_id);
+    Ok(HttpResponse::Ok().json(layout))
+}
+
+// A user's uploads as RSS, for following a channel in a podcast app
+#[get("/api/users/{id}/feed.xml")]
+async fn get_user_feed(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = path.into_inner();
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    let videos = sqlx::query_as::<_, Video>(&format!("{} AND uploaded_by = $1 ORDER BY upload_date DESC LIMIT $2", feeds::PUBLIC_VIDEOS_SQL))
+        .bind(user_id)
+        .bind(feeds::FEED_ITEM_LIMIT)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    let base_url = oembed::public_base_url();
+    let title = format!("{} on {}", username, oembed::provider_name());
+    let description = format!("Videos uploaded by {}", username);
+    let feed_url = format!("{}/api/users/{}/feed.xml", base_url, user_id);
+    let channel = feeds::Channel { title: &title, description: &description, link: &base_url, feed_url: &feed_url };
+    let items: Vec<_> = videos.into_iter().map(|v| (v, Some(username.clone()))).collect();
+    Ok(feed_response(feeds::rss(&channel, &items, &base_url)))
+}
+
+#[get("/api/categories/{id}/feed.xml")]
+async fn get_category_feed(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let category_id = path.into_inner();
+    let category = sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = $1")
+        .bind(category_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Category not found".to_string()))?;
+
+    let videos = sqlx::query_as::<_, Video>(&format!(
+        "{} AND id IN (SELECT video_id FROM videos_categories WHERE category_id = $1) ORDER BY upload_date DESC LIMIT $2",
+        feeds::PUBLIC_VIDEOS_SQL
+    ))
+        .bind(category_id)
+        .bind(feeds::FEED_ITEM_LIMIT)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    let items = feeds::with_authors(&state.db_pool, videos).await?;
+
+    let base_url = oembed::public_base_url();
+    let title = format!("{} on {}", category.name, oembed::provider_name());
+    let description = category.description.clone().unwrap_or_else(|| format!("{} videos", category.name));
+    let link = format!("{}/categories/{}", base_url, category.id);
+    let feed_url = format!("{}/api/categories/{}/feed.xml", base_url, category.id);
+    let channel = feeds::Channel { title: &title, description: &description, link: &link, feed_url: &feed_url };
+    Ok(feed_response(feeds::rss(&channel, &items, &base_url)))
+}
+
+#[get("/api/tags/{tag}/feed.xml")]
+async fn get_tag_feed(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let tag = path.into_inner();
+    let videos = sqlx::query_as::<_, Video>(&format!("{} AND tags @> ARRAY[$1]::TEXT[] ORDER BY upload_date DESC LIMIT $2", feeds::PUBLIC_VIDEOS_SQL))
+        .bind(&tag)
+        .bind(feeds::FEED_ITEM_LIMIT)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    let items = feeds::with_authors(&state.db_pool, videos).await?;
+
+    let base_url = oembed::public_base_url();
+    let title = format!("#{} on {}", tag, oembed::provider_name());
+    let description = format!("Videos tagged {}", tag);
+    let link = format!("{}/tags/{}", base_url, urlencoding::encode(&tag));
+    let feed_url = format!("{}/api/tags/{}/feed.xml", base_url, urlencoding::encode(&tag));
+    let channel = feeds::Channel { title: &title, description: &description, link: &link, feed_url: &feed_url };
+    Ok(feed_response(feeds::rss(&channel, &items, &base_url)))
+}
+
+// Videos created, updated and deleted since a cursor, one JSON object per line. The cursor to
+// continue from is in X-Next-Cursor, also when there was nothing new.
+#[get("/api/changes")]
+async fn get_changes(
+    query: web::Query<ChangesQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let since = changes::Cursor::parse(query.since.as_deref()).map_err(ApiError::BadRequest)?;
+    let (changes, next) = changes::since(&state.db_pool, since, changes::clamp_limit(query.limit)).await?;
+
+    let mut body = String::new();
+    for change in &changes {
+        body.push_str(&serde_json::to_string(change).map_err(|e| ApiError::Internal(format!("Failed to encode change: {:?}", e)))?);
+        body.push('\n');
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(("X-Next-Cursor", next.to_string()))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(body))
+}
+
+// Serve a generated sitemap document from S3. Crawlers revalidate with If-Modified-Since, which
+// is answered from the database without touching S3.
+async fn sitemap_document(state: &AppState, http_req: &actix_web::HttpRequest, name: &str, content_type: &str) -> Result<HttpResponse, ApiError> {
+    use actix_web::http::header::{HttpDate, IfModifiedSince, LastModified, Header};
+
+    let (s3_key, last_modified) = sitemap::get_document(&state.db_pool, name).await?
+        .ok_or_else(|| ApiError::NotFound("Sitemap has not been generated yet".to_string()))?;
+    let last_modified = HttpDate::from(std::time::UNIX_EPOCH + std::time::Duration::from_secs(last_modified.and_utc().timestamp().max(0) as u64));
+
+    if let Ok(IfModifiedSince(since)) = IfModifiedSince::parse(http_req) {
+        if last_modified <= since {
+            return Ok(HttpResponse::NotModified().insert_header(LastModified(last_modified)).finish());
+        }
+    }
+
+    let bucket_name = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let output = state.storage.get(&bucket_name, &s3_key)
+        .await
+        .map_err(|e| e.into_api_error(|e| ApiError::Internal(format!("Error fetching {} from storage: {:?}", name, e))))?;
+    let body = output.bytes().await
+        .map_err(|e| ApiError::Internal(format!("Error reading {} from storage: {:?}", name, e)))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(LastModified(last_modified))
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(3600)]))
+        .body(body))
+}
+
+// Public keys login tokens are signed with, for other services to verify them. Empty while
+// tokens are signed with a shared HS256 secret.
+#[get("/.well-known/jwks.json")]
+async fn get_jwks() -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(300)]))
+        .json(jwt_keys::keys()?.jwks()))
+}
+
+#[get("/sitemap-videos.xml")]
+async fn get_video_sitemap(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    sitemap_document(&state, &http_req, sitemap::VIDEO_SITEMAP, "application/xml; charset=utf-8").await
+}
+
+#[get("/mrss.xml")]
+async fn get_mrss_feed(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    sitemap_document(&state, &http_req, sitemap::MRSS_FEED, "application/rss+xml; charset=utf-8").await
+}
+
+#[get("/api/users/me/searches")]
+async fn get_my_searches(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let history = sqlx::query_as::<_, SearchHistoryEntry>(
+        "SELECT * FROM search_history WHERE user_id = $1 ORDER BY created_at DESC LIMIT 50"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let saved = sqlx::query_as::<_, SavedSearch>(
+        "SELECT * FROM saved_searches WHERE user_id = $1 ORDER BY name ASC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "history": history,
+        "saved": saved
+    })))
+}
+
+#[post("/api/users/me/searches")]
+async fn save_search(
+    json_req: web::Json<SavedSearchRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    if json_req.name.trim().is_empty() || json_req.query.trim().is_empty() {
+        return Err(ApiError::BadRequest("Name and query are required".to_string()));
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let saved_search = sqlx::query_as::<_, SavedSearch>(
+        "INSERT INTO saved_searches (user_id, name, query, notify, last_checked_at, created_at)
+         VALUES ($1, $2, $3, $4, $5, $5)
+         ON CONFLICT (user_id, name) DO UPDATE SET query = EXCLUDED.query, notify = EXCLUDED.notify
+         RETURNING *"
+    )
+    .bind(user_id)
+    .bind(json_req.name.trim())
+    .bind(json_req.query.trim())
+    .bind(json_req.notify.unwrap_or(true))
+    .bind(now)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(saved_search))
+}
+
+#[delete("/api/users/me/searches/{id}")]
+async fn delete_saved_search(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let saved_search_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let result = sqlx::query("DELETE FROM saved_searches WHERE id = $1 AND user_id = $2")
+        .bind(saved_search_id)
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Saved search not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Saved search deleted"
+    })))
+}
+
+// Where the user is signed in; `current` marks the session making the request
+#[get("/api/users/me/sessions")]
+async fn get_my_sessions(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let current = sessions::current_session_id(&http_req);
+    let user_sessions: Vec<UserSession> = sessions::list_sessions(&state.db_pool, user_id, current.as_deref()).await?;
+    Ok(HttpResponse::Ok().json(user_sessions))
+}
+
+// Sign out everywhere else: revokes all of the user's sessions except the current one
+#[delete("/api/users/me/sessions")]
+async fn revoke_my_sessions(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let current = sessions::current_session_id(&http_req);
+    let revoked = sessions::revoke_all_sessions(&state.db_pool, user_id, current.as_deref()).await?;
+    sessions::deny(state.redis_client.as_ref(), &revoked).await;
+
+    info!("User {} revoked {} sessions", user_id, revoked.len());
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Sessions revoked",
+        "revoked": revoked.len()
+    })))
+}
+
+#[delete("/api/users/me/sessions/{id}")]
+async fn revoke_my_session(
     path: web::Path<String>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> impl Responder {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let session_id = path.into_inner();
+    let user_id = require_user_id(&http_req)?;
+
+    let expires_at = sessions::revoke_session(&state.db_pool, user_id, &session_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Session not found".to_string()))?;
+    sessions::deny(state.redis_client.as_ref(), &[(session_id, expires_at)]).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Session revoked"
+    })))
+}
+
+#[get("/api/users/me/notifications")]
+async fn get_my_notifications(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let notifications = get_notifications(&state.db_pool, user_id, 50).await?;
+    Ok(HttpResponse::Ok().json(notifications))
+}
+
+// The key browsers subscribe to push with (applicationServerKey), while Web Push is enabled
+#[get("/api/push/public-key")]
+async fn get_push_public_key() -> Result<HttpResponse, ApiError> {
+    let vapid = web_push::vapid().ok_or_else(|| ApiError::NotFound("Web Push is not enabled".to_string()))?;
+    Ok(HttpResponse::Ok().json(json!({
+        "publicKey": vapid.public_key()
+    })))
+}
+
+#[get("/api/users/me/push-subscriptions")]
+async fn list_push_subscriptions(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    let db_pool = state.lock().await.db_pool.clone();
+
+    let subscriptions = web_push::list_subscriptions(&db_pool, user_id).await?;
+    Ok(HttpResponse::Ok().json(subscriptions))
+}
+
+// Register the browser's PushSubscription, as its toJSON() gives it, for the user's notifications
+#[post("/api/users/me/push-subscriptions")]
+async fn create_push_subscription(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+    json_req: web::Json<PushSubscriptionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    if web_push::vapid().is_none() {
+        return Err(ApiError::NotFound("Web Push is not enabled".to_string()));
+    }
+    web_push::validate_subscription(&json_req).map_err(ApiError::BadRequest)?;
+
+    let user_agent = http_req.headers().get(actix_web::http::header::USER_AGENT).and_then(|h| h.to_str().ok());
+    let db_pool = state.lock().await.db_pool.clone();
+    let subscription = web_push::subscribe(&db_pool, user_id, &json_req, user_agent).await?;
+    Ok(HttpResponse::Created().json(subscription))
+}
+
+#[delete("/api/users/me/push-subscriptions")]
+async fn delete_push_subscription(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+    json_req: web::Json<PushUnsubscribeRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = require_user_id(&http_req)?;
+    let db_pool = state.lock().await.db_pool.clone();
+
+    if !web_push::unsubscribe(&db_pool, user_id, &json_req.endpoint).await? {
+        return Err(ApiError::NotFound("Push subscription not found".to_string()));
+    }
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Push subscription removed"
+    })))
+}
+
+// Watch parties the user took part in, newest first
+#[get("/api/users/me/watchparties")]
+async fn get_my_watch_parties(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_user_id(&http_req)?;
+
+    let history = watch_parties::history_for_user(&state.db_pool, user_id, 50).await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[get("/api/videos/{id}/watchparty-stats")]
+async fn get_watch_party_stats(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let thumbnail_key = path.into_inner();
-    
-    // Prepend "thumbnails/" if it's not already there
-    let s3_key = if thumbnail_key.starts_with("thumbnails/") {
-        thumbnail_key
-    } else {
-        format!("thumbnails/{}", thumbnail_key)
+    let video_id = path.into_inner();
+
+    sqlx::query_scalar::<_, i32>("SELECT id FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let stats = watch_parties::video_stats(&state.db_pool, video_id).await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+
+fn validate_webhook_request(req: &WebhookRequest) -> Result<(), ApiError> {
+    let url = req.url.trim();
+    if !(url.starts_with("https://") || url.starts_with("http://")) || url.contains(char::is_whitespace) {
+        return Err(ApiError::BadRequest("Webhook url must be an http or https URL".to_string()));
+    }
+    if req.events.is_empty() {
+        return Err(ApiError::BadRequest("At least one event is required".to_string()));
+    }
+    if let Some(event) = req.events.iter().find(|e| !webhooks::WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown webhook event '{}', expected one of: {}", event, webhooks::WEBHOOK_EVENTS.join(", ")
+        )));
+    }
+    if req.secret.as_deref().is_some_and(|s| s.trim().is_empty()) {
+        return Err(ApiError::BadRequest("Webhook secret cannot be empty".to_string()));
+    }
+    Ok(())
+}
+
+// Webhooks receive events for every video and comment, so only admins can manage them
+#[get("/api/webhooks")]
+async fn list_webhooks(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+
+    let webhooks = webhooks::list_webhooks(&state.db_pool, user_id).await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+// The signing secret is only returned here; a generated one is used if none is given
+#[post("/api/webhooks")]
+async fn create_webhook(
+    json_req: web::Json<WebhookRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+    validate_webhook_request(&json_req)?;
+
+    let secret = json_req.secret.clone().unwrap_or_else(webhooks::generate_secret);
+    let webhook = webhooks::create_webhook(
+        &state.db_pool,
+        user_id,
+        json_req.url.trim(),
+        &secret,
+        &json_req.events,
+        json_req.active.unwrap_or(true),
+    )
+    .await?;
+
+    let mut body = json!(webhook);
+    body["secret"] = json!(webhook.secret);
+    Ok(HttpResponse::Created().json(body))
+}
+
+#[get("/api/webhooks/{id}")]
+async fn get_webhook(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+
+    let webhook = webhooks::get_webhook(&state.db_pool, path.into_inner(), user_id).await?
+        .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+#[put("/api/webhooks/{id}")]
+async fn update_webhook(
+    path: web::Path<i32>,
+    json_req: web::Json<WebhookRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+    validate_webhook_request(&json_req)?;
+
+    let webhook = webhooks::update_webhook(
+        &state.db_pool,
+        path.into_inner(),
+        user_id,
+        json_req.url.trim(),
+        json_req.secret.as_deref(),
+        &json_req.events,
+        json_req.active.unwrap_or(true),
+    )
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+#[delete("/api/webhooks/{id}")]
+async fn delete_webhook(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+
+    if !webhooks::delete_webhook(&state.db_pool, path.into_inner(), user_id).await? {
+        return Err(ApiError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Webhook deleted"
+    })))
+}
+
+// Most recent deliveries for a webhook, including failed attempts and their errors
+#[get("/api/webhooks/{id}/deliveries")]
+async fn get_webhook_deliveries(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = require_admin(&state.db_pool, &http_req).await?;
+
+    let webhook = webhooks::get_webhook(&state.db_pool, path.into_inner(), user_id).await?
+        .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))?;
+    let deliveries = webhooks::list_deliveries(&state.db_pool, webhook.id, 50).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+// Videos awaiting moderation or flagged by the classifier, oldest first
+#[get("/api/admin/moderation")]
+async fn get_moderation_queue(
+    query: web::Query<ModerationQueueQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+
+    let statuses: Vec<&str> = match query.status.as_deref() {
+        None => vec![moderation::STATUS_PENDING, moderation::STATUS_FLAGGED],
+        Some(wanted @ (moderation::STATUS_PENDING | moderation::STATUS_FLAGGED)) => vec![wanted],
+        Some(other) => return Err(ApiError::BadRequest(format!("Unknown moderation status '{}'", other))),
     };
-    
+
+    let mut videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE moderation_status = ANY($1) AND deleted_at IS NULL ORDER BY upload_date ASC LIMIT 50"
+    )
+    .bind(&statuses)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    categories::attach(&state.db_pool, &mut videos).await?;
+
+    Ok(HttpResponse::Ok().json(videos))
+}
+
+// Approve or flag a video by hand. Overrides the classifier, including on later reprocessing.
+#[post("/api/admin/videos/{id}/moderation")]
+async fn moderate_video(
+    path: web::Path<i32>,
+    json_req: web::Json<ModerationRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let admin_id = require_admin(&state.db_pool, &http_req).await?;
+    let video_id = path.into_inner();
+
+    let new_status = match json_req.status.as_str() {
+        moderation::STATUS_APPROVED => moderation::STATUS_APPROVED,
+        moderation::STATUS_FLAGGED => moderation::STATUS_FLAGGED,
+        other => return Err(ApiError::BadRequest(format!("Moderation status must be approved or flagged, not '{}'", other))),
+    };
+    let reason = json_req.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+
+    if !moderation::set_override(&state.db_pool, video_id, admin_id, new_status, reason).await? {
+        return Err(ApiError::NotFound("Video not found".to_string()));
+    }
+
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    Ok(HttpResponse::Ok().json(video))
+}
+
+// Videos whose original file is outside the STANDARD storage class
+#[get("/api/admin/storage")]
+async fn get_storage_tiers(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+    Ok(HttpResponse::Ok().json(storage_tiering::list_storage(&state.db_pool).await?))
+}
+
+// Move a video's original file to another storage class. Moving it to STANDARD restores it,
+// which for archive classes finishes in the background.
+#[post("/api/admin/videos/{id}/storage")]
+async fn set_storage_class(
+    path: web::Path<i32>,
+    json_req: web::Json<StorageClassRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+    let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(path.into_inner())
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Video not found".to_string()))?;
+
+    let storage_class = json_req.storage_class.trim().to_ascii_uppercase();
+    let current = storage_tiering::storage(&state.db_pool, video.id).await?;
+    let current_class = current.as_ref().map_or(storage_tiering::STANDARD, |s| s.storage_class.as_str());
     let bucket_name = env::var("S3_BUCKET")
         .or_else(|_| env::var("MINIO_BUCKET"))
         .unwrap_or_else(|_| "videos".to_string());
-    let get_object_output = state.s3_client.get_object()
-        .bucket(bucket_name)
-        .key(s3_key)
-        .send()
-        .await;
 
-    match get_object_output {
-        Ok(output) => {
-            let body = output.body.collect().await.unwrap().into_bytes();
-            actix_web::HttpResponse::Ok()
-                .content_type("image/jpeg")
-                .body(body)
-        }
-        Err(e) => {
-            error!("Error fetching thumbnail from MinIO: {:?}", e);
-            actix_web::HttpResponse::NotFound().json(json!({
-                "error": "Thumbnail not found"
-            }))
-        }
+    if storage_class == storage_tiering::STANDARD {
+        let availability = storage_tiering::request_restore(&state.db_pool, state.storage.as_ref(), &bucket_name, &video)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Error restoring video {}: {:?}", video.id, e)))?;
+        let storage = storage_tiering::storage(&state.db_pool, video.id).await?;
+        return Ok(HttpResponse::Accepted().json(json!({
+            "video_id": video.id,
+            "storage_class": current_class,
+            "restoring": storage.is_some(),
+            "readable": availability == storage_tiering::Availability::Readable,
+        })));
     }
+    if !storage_tiering::COLD_CLASSES.contains(&storage_class.as_str()) {
+        return Err(ApiError::BadRequest(format!("Storage class must be STANDARD or one of {}", storage_tiering::COLD_CLASSES.join(", "))));
+    }
+    // Archived files have to be restored before they can be copied anywhere else
+    if storage_tiering::needs_restore(current_class) {
+        return Err(ApiError::Conflict(format!("Video is in {}; restore it to STANDARD first", current_class)));
+    }
+
+    storage_tiering::transition(&state.db_pool, state.storage.as_ref(), &bucket_name, &video, &storage_class)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Error moving video {} to {}: {:?}", video.id, storage_class, e)))?;
+    let storage = storage_tiering::storage(&state.db_pool, video.id).await?;
+    Ok(HttpResponse::Ok().json(storage))
 }
 
-#[get("/api/user/settings")]
-async fn get_user_settings(
+// Stream videos, users or views as CSV or NDJSON, e.g.
+// /api/admin/exports/views?format=ndjson&fields=video_id,viewed_at&from=2025-08-01&to=2025-08-31
+#[get("/api/admin/exports/{dataset}")]
+async fn export_dataset(
+    path: web::Path<String>,
+    query: web::Query<ExportQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let db_pool = {
+        let state = state.lock().await;
+        require_admin(&state.db_pool, &http_req).await?;
+        state.db_pool.clone()
+    };
+
+    let dataset = exports::dataset(&path)
+        .ok_or_else(|| ApiError::NotFound(format!("No export named '{}'", path)))?;
+    let format = exports::ExportFormat::parse(query.format.as_deref()).map_err(ApiError::BadRequest)?;
+    let fields = exports::select_fields(dataset, query.fields.as_deref()).map_err(ApiError::BadRequest)?;
+    let (from, to) = exports::date_range(query.from.as_deref(), query.to.as_deref()).map_err(ApiError::BadRequest)?;
+
+    let filename = format!("{}-{}.{}", dataset.name, chrono::Utc::now().format("%Y%m%d"), format.extension());
+    info!("Export of {} as {} started", dataset.name, format.extension());
+    let export = exports::Export { dataset, fields, format, from, to };
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((actix_web::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .streaming(exports::stream(db_pool, export)))
+}
+
+// Interaction matrices exported for training recommendation models, newest first
+#[get("/api/admin/recommendations/exports")]
+async fn list_recommendation_exports(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+    Ok(HttpResponse::Ok().json(recommendations::list_exports(&state.db_pool).await?))
+}
+
+// Export the interaction matrix now rather than waiting for the schedule
+#[post("/api/admin/recommendations/exports")]
+async fn create_recommendation_export(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, storage) = {
+        let state = state.lock().await;
+        require_admin(&state.db_pool, &http_req).await?;
+        (state.db_pool.clone(), state.storage.clone())
+    };
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let export = recommendations::export_interactions(&db_pool, storage.as_ref(), &bucket)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Error exporting user interactions: {:?}", e)))?;
+    Ok(HttpResponse::Created().json(export))
+}
+
+// Replace users' recommendation lists with ones computed by a model
+#[put("/api/admin/recommendations")]
+async fn import_recommendations(
+    json_req: web::Json<RecommendationImport>,
     state: web::Data<Arc<Mutex<AppState>>>,
     http_req: actix_web::HttpRequest,
-) -> actix_web::HttpResponse {
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
+    let admin_id = require_admin(&state.db_pool, &http_req).await?;
+    recommendations::validate_import(&json_req).map_err(ApiError::BadRequest)?;
 
-    // Extract the JWT token from the Authorization header
-    let auth_header = http_req.headers().get(actix_web::http::header::AUTHORIZATION);
-    let token = auth_header.and_then(|h| h.to_str().ok()).and_then(|h| h.strip_prefix("Bearer ")).map(String::from);
+    let summary = recommendations::import(&state.db_pool, &json_req).await?;
+    info!("Admin {} imported {} recommendations for {} users", admin_id, summary.recommendations, summary.users);
+    Ok(HttpResponse::Ok().json(summary))
+}
 
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string());
-    let claims_result = token.and_then(|t| {
-        decode::<Claims>(
-            &t,
-            &DecodingKey::from_secret(jwt_secret.as_ref()),
-            &Validation::default(),
-        ).ok()
-    });
+// The signed-in user's recommendations, topped up with popular videos
+#[get("/api/feed/home")]
+async fn get_home_feed(
+    query: web::Query<HomeFeedQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let user_id = authenticated_user_id(&http_req);
+    let ranking = experiments::variant(&state.db_pool, experiments::HOME_FEED_RANKING, user_id).await;
+    let videos = recommendations::home_feed(&state.db_pool, user_id, recommendations::clamp_home_limit(query.limit), &ranking).await?;
+    localized_list(&state, videos, &http_req).await
+}
 
-    let claims = match claims_result {
-        Some(decoded) => decoded.claims,
-        None => {
-            return actix_web::HttpResponse::Forbidden().json(json!({
-                "error": "Unauthorized: Invalid or missing token"
-            }));
-        }
+// Which subsystems are on and whether the site is in maintenance, for clients to hide what
+// can't be used
+#[get("/api/features")]
+async fn get_features(
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
     };
+    let flags = feature_flags::load(&db_pool, redis_client.as_ref()).await?;
+    let features: serde_json::Map<String, serde_json::Value> = flags
+        .flags
+        .iter()
+        .map(|flag| (flag.name.clone(), json!({ "enabled": flag.enabled, "message": flag.message })))
+        .collect();
+    Ok(HttpResponse::Ok().json(features))
+}
 
-    let user_id = claims.user_id;
+#[get("/api/admin/feature-flags")]
+async fn list_feature_flags(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
+    };
+    require_admin(&db_pool, &http_req).await?;
+    Ok(HttpResponse::Ok().json(feature_flags::load(&db_pool, redis_client.as_ref()).await?.flags))
+}
 
-    let result = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
-        .bind(user_id)
-        .fetch_one(&state.db_pool)
-        .await;
+// Turn a subsystem on or off, or maintenance mode on or off
+#[put("/api/admin/feature-flags/{name}")]
+async fn set_feature_flag(
+    path: web::Path<String>,
+    json_req: web::Json<FeatureFlagRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
+    };
+    let admin_id = require_admin(&db_pool, &http_req).await?;
+    let name = path.into_inner();
+    if !feature_flags::is_known(&name) {
+        return Err(ApiError::NotFound(format!("There is no feature flag named '{}'", name)));
+    }
+    let message = json_req.message.as_deref().map(str::trim).filter(|m| !m.is_empty());
+    if message.is_some_and(|m| m.chars().count() > feature_flags::MAX_MESSAGE_CHARS) {
+        return Err(ApiError::BadRequest(format!("message can be at most {} characters", feature_flags::MAX_MESSAGE_CHARS)));
+    }
 
-    match result {
-        Ok(user) => {
-            actix_web::HttpResponse::Ok().json(json!({
-                "settings": user.settings.unwrap_or(json!({}))
-            }))
-        }
-        Err(e) => {
-            error!("Error fetching user settings: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
+    let flags = feature_flags::set(&db_pool, redis_client.as_ref(), &name, json_req.enabled, message, admin_id).await?;
+    let details = json!({ "flag": name, "enabled": json_req.enabled, "message": message });
+    if let Err(e) = audit::record(&db_pool, audit::EVENT_FEATURE_FLAG_CHANGED, Some(admin_id), sessions::client_ip(&http_req).as_deref(), details).await {
+        error!("Failed to audit feature flag change by admin {}: {:?}", admin_id, e);
     }
+    info!("Admin {} turned feature flag {} {}", admin_id, name, if json_req.enabled { "on" } else { "off" });
+    Ok(HttpResponse::Ok().json(flags.get(&name)))
 }
 
-#[post("/api/user/settings")]
-async fn update_user_settings(
-    json_req: web::Json<UserSettingsRequest>,
+#[get("/api/admin/experiments")]
+async fn list_experiments(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+    let mut reports = Vec::new();
+    for experiment in experiments::list(&state.db_pool).await? {
+        reports.push(experiments::report(&state.db_pool, experiment).await?);
+    }
+    Ok(HttpResponse::Ok().json(reports))
+}
+
+// Define an experiment, which starts straight away
+#[post("/api/admin/experiments")]
+async fn create_experiment(
+    json_req: web::Json<ExperimentRequest>,
     state: web::Data<Arc<Mutex<AppState>>>,
     http_req: actix_web::HttpRequest,
-) -> actix_web::HttpResponse {
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
+    let admin_id = require_admin(&state.db_pool, &http_req).await?;
+    experiments::validate(&json_req).map_err(ApiError::BadRequest)?;
 
-    // Extract the JWT token from the Authorization header
-    let auth_header = http_req.headers().get(actix_web::http::header::AUTHORIZATION);
-    let token = auth_header.and_then(|h| h.to_str().ok()).and_then(|h| h.strip_prefix("Bearer ")).map(String::from);
+    let experiment = experiments::create(&state.db_pool, &json_req).await.map_err(|e| {
+        if is_unique_violation(&e) {
+            ApiError::Conflict(format!("An experiment named '{}' already exists", json_req.key))
+        } else {
+            ApiError::from(e)
+        }
+    })?;
+    info!("Admin {} started experiment {}", admin_id, experiment.key);
+    Ok(HttpResponse::Created().json(experiments::report(&state.db_pool, experiment).await?))
+}
 
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string());
-    let claims_result = token.and_then(|t| {
-        decode::<Claims>(
-            &t,
-            &DecodingKey::from_secret(jwt_secret.as_ref()),
-            &Validation::default(),
-        ).ok()
-    });
+// An experiment with how many users have seen each variant
+#[get("/api/admin/experiments/{key}")]
+async fn get_experiment(
+    path: web::Path<String>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+    let experiment = experiments::get(&state.db_pool, &path).await?
+        .ok_or_else(|| ApiError::NotFound("Experiment not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(experiments::report(&state.db_pool, experiment).await?))
+}
+
+#[patch("/api/admin/experiments/{key}")]
+async fn update_experiment(
+    path: web::Path<String>,
+    json_req: web::Json<ExperimentUpdateRequest>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    let admin_id = require_admin(&state.db_pool, &http_req).await?;
+    let experiment = experiments::update(&state.db_pool, &path, &json_req).await?
+        .ok_or_else(|| ApiError::NotFound("Experiment not found".to_string()))?;
+    if let Some(active) = json_req.active {
+        info!("Admin {} {} experiment {}", admin_id, if active { "started" } else { "stopped" }, experiment.key);
+    }
+    Ok(HttpResponse::Ok().json(experiments::report(&state.db_pool, experiment).await?))
+}
+
+// Create videos for files already in the bucket, e.g. when migrating an existing library.
+// Imported videos are attributed to the admin running the import.
+#[post("/api/admin/imports")]
+async fn import_videos(
+    json_req: Option<web::Json<ImportRequest>>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    // Listing a large bucket takes a while, so don't hold the app state lock meanwhile
+    let (db_pool, storage, job_queue) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.storage.clone(), state.job_queue.clone())
+    };
+    let admin_id = require_admin(&db_pool, &http_req).await?;
+    let request = json_req.map(web::Json::into_inner).unwrap_or_default();
+    let prefix = request.prefix.unwrap_or_else(imports::import_prefix);
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let report = imports::import_objects(
+        &db_pool,
+        storage.as_ref(),
+        job_queue.as_ref(),
+        &bucket,
+        &prefix,
+        Some(admin_id),
+        request.dry_run,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
 
-    let claims = match claims_result {
-        Some(decoded) => decoded.claims,
-        None => {
-            return actix_web::HttpResponse::Forbidden().json(json!({
-                "error": "Unauthorized: Invalid or missing token"
-            }));
+// S3 event notifications for the bucket, from a webhook, a queue poller or an SNS subscription.
+// Created video files are imported after a grace period, see s3_events.
+#[post("/api/internal/s3-events")]
+async fn receive_s3_events(
+    body: web::Bytes,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let token = s3_events::events_token()
+        .ok_or_else(|| ApiError::NotFound("S3 event ingestion is not enabled".to_string()))?;
+    let notification = if http_req.headers().contains_key("x-amz-sns-message-type") {
+        let message: s3_events::SnsMessage = serde_json::from_slice(&body)
+            .map_err(|e| ApiError::BadRequest(format!("Not an SNS message: {}", e)))?;
+        if let Err(e) = s3_events::authenticate_sns(&message).await {
+            warn!("Refused SNS message {} for S3 events: {}", message.message_id, e);
+            return Err(ApiError::Unauthorized);
+        }
+        match message.message_type.as_str() {
+            "Notification" => s3_events::parse_notification(message.message.as_bytes())
+                .map_err(|e| ApiError::BadRequest(format!("Not an S3 event notification: {}", e)))?,
+            "SubscriptionConfirmation" => {
+                s3_events::confirm_subscription(&message).await.map_err(|e| {
+                    ApiError::Internal(format!("Failed to confirm the SNS subscription to {}: {}", message.topic_arn, e))
+                })?;
+                info!("Confirmed the SNS subscription to {} for S3 events", message.topic_arn);
+                return Ok(HttpResponse::Ok().finish());
+            }
+            _ => return Ok(HttpResponse::Ok().finish()),
+        }
+    } else {
+        let authorization = http_req.headers().get(actix_web::http::header::AUTHORIZATION).and_then(|h| h.to_str().ok());
+        if !s3_events::token_matches(authorization, &token) {
+            return Err(ApiError::Unauthorized);
         }
+        s3_events::parse_notification(&body)
+            .map_err(|e| ApiError::BadRequest(format!("Not an S3 event notification: {}", e)))?
     };
 
-    let user_id = claims.user_id;
+    let db_pool = state.lock().await.db_pool.clone();
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+    let objects = s3_events::created_objects(&notification, &bucket, &imports::import_prefix());
+    s3_events::record(&db_pool, &bucket, &objects).await?;
+    Ok(HttpResponse::Accepted().json(json!({
+        "events": notification.records.len(),
+        "recorded": objects.len()
+    })))
+}
 
-    // Get current settings
-    let current_user_result = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
-        .bind(user_id)
-        .fetch_one(&state.db_pool)
-        .await;
+// Queue depths, jobs being worked on and the latest failures. Queue depths are null while Redis
+// is unavailable.
+#[get("/api/admin/jobs")]
+async fn get_job_status(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, job_queue) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.job_queue.clone())
+    };
+    require_admin(&db_pool, &http_req).await?;
 
-    let mut current_settings = match current_user_result {
-        Ok(user) => user.settings.unwrap_or(json!({})),
-        Err(e) => {
-            error!("Error fetching current user settings: {:?}", e);
-            return actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }));
-        }
+    let queues = match job_queue {
+        Some(job_queue) => match job_queue.queue_depths().await {
+            Ok(depths) => Some(depths.into_iter().collect::<std::collections::BTreeMap<_, _>>()),
+            Err(e) => {
+                error!("Failed to read job queue depths: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let in_flight = job_history::in_flight(&db_pool).await?;
+    let recent_failures = job_history::recent_failures(&db_pool, job_history::RECENT_FAILURES).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "queues": queues,
+        "in_flight": in_flight,
+        "recent_failures": recent_failures
+    })))
+}
+
+// Connected WebSocket clients per comment room and watch party on this instance, message rates
+// and Redis subscriptions
+#[get("/api/admin/ws/stats")]
+async fn get_ws_stats(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let (db_pool, redis_client, videos, mut watch_parties) = {
+        let state = state.lock().await;
+        let videos = ws_stats::rooms(&state.video_clients.lock().unwrap());
+        let watch_parties = ws_stats::rooms(&state.watchparty_clients.lock().unwrap());
+        (state.db_pool.clone(), state.redis_client.clone(), videos, watch_parties)
     };
+    require_admin(&db_pool, &http_req).await?;
 
-    // Update theme if provided
-    if let Some(theme) = &json_req.theme {
-        current_settings["theme"] = theme.clone();
+    if let Some(redis_client) = redis_client {
+        ws_stats::count_redis_subscribers(&redis_client, &mut watch_parties).await;
     }
+    Ok(HttpResponse::Ok().json(ws_stats::snapshot(videos, watch_parties)))
+}
 
-    // Update the user's settings
-    let result = sqlx::query("UPDATE users SET settings = $1 WHERE id = $2")
-        .bind(&current_settings)
-        .bind(user_id)
-        .execute(&state.db_pool)
-        .await;
+// Latency per route on this instance since it started, slowest in total first
+#[get("/api/admin/requests/stats")]
+async fn get_request_stats(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let db_pool = state.lock().await.db_pool.clone();
+    require_admin(&db_pool, &http_req).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "bucket_bounds_ms": request_metrics::BUCKET_BOUNDS_MS,
+        "slow_request_ms": request_metrics::slow_request_threshold().as_millis() as u64,
+        "routes": request_metrics::snapshot()
+    })))
+}
 
-    match result {
-        Ok(_) => {
-            actix_web::HttpResponse::Ok().json(json!({
-                "message": "Settings updated successfully",
-                "settings": current_settings
-            }))
-        }
-        Err(e) => {
-            error!("Error updating user settings: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
-    }
+// Every attempt at a video's processing jobs, e.g. ?kind=thumbnail for just its thumbnails
+#[get("/api/admin/videos/{id}/jobs")]
+async fn get_video_job_attempts(
+    path: web::Path<i32>,
+    query: web::Query<JobAttemptsQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let state = state.lock().await;
+    require_admin(&state.db_pool, &http_req).await?;
+
+    let kind = match query.kind.as_deref() {
+        None => None,
+        Some(kind) => Some(MediaJobKind::parse(kind).ok_or_else(|| ApiError::BadRequest(format!("Unknown job kind '{}'", kind)))?),
+    };
+    let attempts = job_history::attempts(&state.db_pool, path.into_inner(), kind).await?;
+    Ok(HttpResponse::Ok().json(attempts))
 }
 
-#[get("/api/categories")]
-async fn get_categories(state: web::Data<Arc<Mutex<AppState>>>) -> actix_web::HttpResponse {
+// Applied and pending migrations compared with the ones this build was compiled with
+#[get("/api/admin/migrations")]
+async fn get_migrations(
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let result = sqlx::query_as::<_, Category>("SELECT * FROM categories ORDER BY name ASC")
-        .fetch_all(&state.db_pool)
-        .await;
+    require_admin(&state.db_pool, &http_req).await?;
 
-    match result {
-        Ok(categories) => actix_web::HttpResponse::Ok().json(categories),
-        Err(e) => {
-            error!("Error fetching categories: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
-    }
+    let migrations = migration_status::migration_statuses(&state.db_pool).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "up_to_date": migration_status::up_to_date(&migrations),
+        "migrations": migrations
+    })))
 }
 
-#[get("/api/videos/category/{category_id}")]
-async fn get_videos_by_category(
-    path: web::Path<i32>,
+// Of `video_ids`, the videos the client could play now
+async fn playable_video_ids(
+    db_pool: &sqlx::PgPool,
+    video_ids: &[i32],
+    user_id: Option<i32>,
+    http_req: &actix_web::HttpRequest,
+) -> Result<std::collections::HashSet<i32>, ApiError> {
+    let videos = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = ANY($1) AND deleted_at IS NULL")
+        .bind(video_ids)
+        .fetch_all(db_pool)
+        .await?;
+    let admin = match user_id {
+        Some(user_id) => is_admin(db_pool, user_id).await?,
+        None => false,
+    };
+    Ok(videos
+        .into_iter()
+        .filter(|video| {
+            admin
+                || (user_id.is_some() && video.uploaded_by == user_id)
+                || (video.moderation_status == moderation::STATUS_APPROVED
+                    && restrictions::check_playback(video, http_req).is_ok()
+                    && premieres::check_started(video, user_id).is_ok())
+        })
+        .map(|video| video.id)
+        .collect())
+}
+
+// Quality-of-experience beacons from players, one or an array of them. Logging in is optional.
+// Clients are rate limited, and beacons for videos they couldn't play are dropped.
+#[post("/api/telemetry/playback")]
+async fn record_playback_telemetry(
+    body: web::Bytes,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let mut beacons = telemetry::parse_beacons(&body).map_err(ApiError::BadRequest)?;
+    let (db_pool, redis_client) = {
+        let state = state.lock().await;
+        (state.db_pool.clone(), state.redis_client.clone())
+    };
+    let user_id = authenticated_user_id(&http_req);
+    let client = match user_id {
+        Some(user_id) => format!("user:{}", user_id),
+        None => format!("ip:{}", sessions::client_ip(&http_req).unwrap_or_else(|| "unknown".to_string())),
+    };
+    telemetry::check_rate(redis_client.as_ref(), &client, beacons.len(), chrono::Utc::now().timestamp()).await?;
+
+    let video_ids: Vec<i32> = beacons.iter().map(|beacon| beacon.video_id).collect();
+    let playable = playable_video_ids(&db_pool, &video_ids, user_id, &http_req).await?;
+    beacons.retain(|beacon| playable.contains(&beacon.video_id));
+    telemetry::record(&db_pool, &beacons, user_id, restrictions::client_country(&http_req).as_deref()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Playback quality per rendition over the last `days` (7 by default), for one video with
+// ?videoId= or across all of them
+#[get("/api/admin/telemetry/playback")]
+async fn get_playback_report(
+    query: web::Query<PlaybackReportQuery>,
     state: web::Data<Arc<Mutex<AppState>>>,
-) -> actix_web::HttpResponse {
+    http_req: actix_web::HttpRequest,
+) -> Result<HttpResponse, ApiError> {
     let state = state.lock().await;
-    let category_id = path.into_inner();
-    let result = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE category_id = $1 ORDER BY upload_date DESC")
-        .bind(category_id)
-        .fetch_all(&state.db_pool)
-        .await;
+    require_admin(&state.db_pool, &http_req).await?;
 
-    match result {
-        Ok(videos) => actix_web::HttpResponse::Ok().json(videos),
-        Err(e) => {
-            error!("Error fetching videos by category: {:?}", e);
-            actix_web::HttpResponse::InternalServerError().json(json!({
-                "error": "Internal server error"
-            }))
-        }
+    let retention_days = telemetry::retention_days();
+    let days = query.days.unwrap_or(telemetry::DEFAULT_REPORT_DAYS);
+    if !(1..=retention_days).contains(&days) {
+        return Err(ApiError::BadRequest(format!("days must be between 1 and {}", retention_days)));
     }
+    let renditions = telemetry::rendition_report(&state.db_pool, query.video_id, days).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "video_id": query.video_id,
+        "days": days,
+        "renditions": renditions
+    })))
 }
 
-
+// Every version's routes. There is only v1 so far, which api_versions::route_version serves
+// under /api/v1 as well as at the unversioned paths.
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(configure_routes_v1);
+}
+
+pub fn configure_routes_v1(cfg: &mut web::ServiceConfig) {
     cfg.service(register)
        .service(login)
+       .service(refresh_session)
        .service(logout)
+       .service(get_csrf_token)
        .service(auth_status)
        .service(status)
        .service(get_videos)
        .service(get_video)
        .service(get_videos_by_tag)
+       .service(search_facets)
        .service(search_videos)
+       .service(advanced_search_videos)
        .service(stream_video)
+       .service(update_video)
+       .service(delete_video)
+       .service(restore_video)
+       // Ahead of get_channel_videos, which would otherwise take "me" as a user id
+       .service(get_my_videos)
+       .service(get_my_trash)
+       .service(reprocess_video)
+       .service(get_video_transcript)
+       .service(get_video_chapters)
+       .service(create_video_chapter)
+       .service(accept_video_chapters)
+       .service(update_video_chapter)
+       .service(delete_video_chapter)
+       .service(get_video_translations)
+       .service(put_video_translation)
+       .service(delete_video_translation)
+       .service(get_thumbnail_candidates)
+       .service(select_thumbnail_candidate)
+       .service(set_video_restrictions)
+       .service(download_video)
+       .service(create_clip)
+       .service(get_video_frame)
+       .service(create_compilation)
+       .service(list_compilations)
+       .service(get_compilation)
+       .service(get_clip)
+       .service(get_oembed)
+       .service(get_embed_config)
+       .service(get_channel_videos)
+       .service(get_my_channel_layout)
+       .service(set_my_channel_layout)
+       .service(get_user_feed)
+       .service(get_category_feed)
+       .service(get_tag_feed)
+       .service(get_changes)
+       .service(get_home_feed)
+       .service(list_recommendation_exports)
+       .service(create_recommendation_export)
+       .service(import_recommendations)
+       .service(list_experiments)
+       .service(create_experiment)
+       .service(get_experiment)
+       .service(update_experiment)
+       .service(get_jwks)
+       .service(get_video_sitemap)
+       .service(get_mrss_feed)
+       .service(set_video_encryption)
+       .service(get_hls_playlist)
+       .service(get_hls_video_playlist)
+       .service(get_hls_segment)
+       .service(get_hls_audio_playlist)
+       .service(get_hls_audio_segment)
+       .service(get_hls_key)
+       .service(create_playback_token)
+       .service(delete_playback_token)
+       .service(set_video_spherical)
+       .service(set_video_premiere)
+       .service(list_audio_tracks)
+       .service(upload_audio_track)
+       .service(get_audio_track)
+       .service(delete_audio_track)
+       .service(create_upload)
+       .service(get_upload_offset)
+       .service(upload_chunk)
+       .service(cancel_upload)
        .service(post_comment)
        .service(get_comments)
+       .service(vote_comment)
+       .service(unvote_comment)
+       .service(export_comments)
+       .service(create_watch_party_guest)
+       .service(claim_watch_party_guest)
        .service(join_watch_party)
+       .service(invite_to_watch_party)
+       .service(get_watch_party_rtc_config)
+       .service(get_watch_party_guests)
+       .service(get_my_watch_parties)
+       .service(get_watch_party_stats)
+       .service(schedule_watch_party)
+       // After rtc-config, which {video_id} would otherwise swallow
+       .service(get_watch_party)
+       .service(get_watch_party_calendar)
        .service(control_watch_party)
        .service(get_thumbnail)
+       .service(get_video_thumbnail)
        .service(get_user_settings)
        .service(update_user_settings)
+       .service(upload_watermark)
+       .service(get_watermark)
+       .service(delete_watermark)
        .service(get_categories)
-       .service(get_videos_by_category);
+       .service(get_videos_by_category)
+       .service(get_my_searches)
+       .service(save_search)
+       .service(delete_saved_search)
+       .service(get_my_sessions)
+       .service(revoke_my_sessions)
+       .service(revoke_my_session)
+       .service(get_my_notifications)
+       .service(get_push_public_key)
+       .service(list_push_subscriptions)
+       .service(create_push_subscription)
+       .service(delete_push_subscription)
+       .service(list_webhooks)
+       .service(create_webhook)
+       .service(get_webhook)
+       .service(update_webhook)
+       .service(delete_webhook)
+       .service(get_webhook_deliveries)
+       .service(get_moderation_queue)
+       .service(export_dataset)
+       .service(get_features)
+       .service(list_feature_flags)
+       .service(set_feature_flag)
+       .service(import_videos)
+       .service(receive_s3_events)
+       .service(get_migrations)
+       .service(get_job_status)
+       .service(get_ws_stats)
+       .service(get_request_stats)
+       .service(get_video_job_attempts)
+       .service(moderate_video)
+       .service(get_storage_tiers)
+       .service(set_storage_class)
+       .service(record_playback_telemetry)
+       .service(get_playback_report)
+       .configure(crate::sse::configure_sse_routes);
 }
@@ -0,0 +1,275 @@
+use std::env;
+use openssl::symm::{encrypt, Cipher};
+use sqlx::PgPool;
+
+use crate::audio_tracks;
+use crate::models::{VideoAudioTrack, VideoHlsSegment};
+
+// Segment length and how many segments share a key, overridable with HLS_SEGMENT_SECS and
+// HLS_KEY_ROTATION_SEGMENTS
+const DEFAULT_SEGMENT_SECS: u32 = 6;
+const DEFAULT_KEY_ROTATION_SEGMENTS: i32 = 10;
+
+pub const KEY_BYTES: usize = 16;
+
+pub fn segment_secs() -> u32 {
+    env::var("HLS_SEGMENT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(DEFAULT_SEGMENT_SECS)
+}
+
+pub fn key_rotation_segments() -> i32 {
+    env::var("HLS_KEY_ROTATION_SEGMENTS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(DEFAULT_KEY_ROTATION_SEGMENTS)
+}
+
+pub fn generate_key() -> Result<[u8; KEY_BYTES], openssl::error::ErrorStack> {
+    let mut key = [0u8; KEY_BYTES];
+    openssl::rand::rand_bytes(&mut key)?;
+    Ok(key)
+}
+
+// With no IV attribute on #EXT-X-KEY, players use the segment's media sequence number,
+// big-endian and zero padded to 16 bytes
+pub fn segment_iv(segment_index: i32) -> [u8; 16] {
+    let mut iv = [0u8; 16];
+    iv[12..].copy_from_slice(&(segment_index as u32).to_be_bytes());
+    iv
+}
+
+// AES-128-CBC with PKCS7 padding, as HLS METHOD=AES-128 expects
+pub fn encrypt_segment(key: &[u8], segment_index: i32, data: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    encrypt(Cipher::aes_128_cbc(), key, Some(&segment_iv(segment_index)), data)
+}
+
+// (duration, uri) for each segment of a media playlist, in order
+pub fn parse_media_playlist(playlist: &str) -> Vec<(f64, String)> {
+    let mut segments = Vec::new();
+    let mut duration = None;
+    for line in playlist.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            duration = info.split(',').next().and_then(|d| d.trim().parse::<f64>().ok());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            if let Some(duration) = duration.take() {
+                segments.push((duration, line.to_string()));
+            }
+        }
+    }
+    segments
+}
+
+// The HLS characteristic marking an audio description rendition
+const DESCRIBES_VIDEO: &str = "public.accessibility.describes-video";
+
+// Single-variant master playlists still need a BANDWIDTH; players have nothing to choose
+// between, so a nominal figure does
+const MASTER_BANDWIDTH: u64 = 5_000_000;
+
+// The media playlist served to players. Segments and keys point back at the API; a token the
+// playlist was requested with is passed on to the key URIs.
+pub fn build_playlist(video_id: i32, segments: &[VideoHlsSegment], token: Option<&str>) -> String {
+    media_playlist(video_id, &format!("/api/videos/{}/hls", video_id), segments, token)
+}
+
+// The media playlist of one alternate audio track
+pub fn build_audio_playlist(video_id: i32, track_index: i32, segments: &[VideoHlsSegment], token: Option<&str>) -> String {
+    media_playlist(video_id, &format!("/api/videos/{}/hls/audio/{}", video_id, track_index), segments, token)
+}
+
+fn media_playlist(video_id: i32, segment_base: &str, segments: &[VideoHlsSegment], token: Option<&str>) -> String {
+    let target_duration = segments.iter().map(|s| s.duration.ceil() as u64).max().unwrap_or(1);
+    let token_param = token.map(|t| format!("&token={}", urlencoding::encode(t))).unwrap_or_default();
+
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n",
+        target_duration
+    );
+    let mut current_key = None;
+    for segment in segments {
+        if current_key != Some(segment.key_index) {
+            playlist.push_str(&format!(
+                "#EXT-X-KEY:METHOD=AES-128,URI=\"/api/videos/{}/key?index={}{}\"\n",
+                video_id, segment.key_index, token_param
+            ));
+            current_key = Some(segment.key_index);
+        }
+        playlist.push_str(&format!(
+            "#EXTINF:{:.3},\n{}/{}.ts\n",
+            segment.duration, segment_base, segment.segment_index
+        ));
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+// Quoted attribute values can't hold quotes or line breaks
+fn quoted(value: &str) -> String {
+    value.chars().filter(|c| !c.is_control()).map(|c| if c == '"' { '\'' } else { c }).collect()
+}
+
+// The query passing the key token and playback token on to the playlists a master playlist
+// points at
+fn child_playlist_query(token: Option<&str>, playback_token: Option<&str>) -> String {
+    let params: Vec<String> = [("token", token), ("playback_token", playback_token)]
+        .iter()
+        .filter_map(|(name, value)| value.map(|v| format!("{}={}", name, urlencoding::encode(v))))
+        .collect();
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", params.join("&"))
+    }
+}
+
+// The master playlist of a video packaged with alternate audio: the video-only playlist as the
+// one variant, and an audio rendition per track. NAMEs must be unique within the group, so
+// repeated labels get the track number added.
+pub fn build_master_playlist(video_id: i32, tracks: &[VideoAudioTrack], token: Option<&str>, playback_token: Option<&str>) -> String {
+    let token_query = child_playlist_query(token, playback_token);
+    let mut playlist = "#EXTM3U\n#EXT-X-VERSION:3\n".to_string();
+    for track in tracks {
+        let mut name = quoted(&track.label);
+        if tracks.iter().filter(|t| t.label == track.label).count() > 1 {
+            name = format!("{} ({})", name, track.track_index + 1);
+        }
+        // Audio description is never the default; players pick it for viewers who asked for it
+        let default = if track.is_default && !track.audio_description { "YES" } else { "NO" };
+        playlist.push_str(&format!("#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"{}\",DEFAULT={},AUTOSELECT=YES", name, default));
+        if let Some(language) = &track.language {
+            playlist.push_str(&format!(",LANGUAGE=\"{}\"", quoted(language)));
+        }
+        if track.audio_description {
+            playlist.push_str(&format!(",CHARACTERISTICS=\"{}\"", DESCRIBES_VIDEO));
+        }
+        if let Some(channels) = track.channels {
+            playlist.push_str(&format!(",CHANNELS=\"{}\"", channels));
+        }
+        playlist.push_str(&format!(
+            ",URI=\"/api/videos/{}/hls/audio/{}/index.m3u8{}\"\n",
+            video_id, track.track_index, token_query
+        ));
+    }
+    playlist.push_str(&format!(
+        "#EXT-X-STREAM-INF:BANDWIDTH={},AUDIO=\"audio\"\n/api/videos/{}/hls/video.m3u8{}\n",
+        MASTER_BANDWIDTH, video_id, token_query
+    ));
+    playlist
+}
+
+pub async fn list_segments(db_pool: &PgPool, video_id: i32) -> Result<Vec<VideoHlsSegment>, sqlx::Error> {
+    sqlx::query_as::<_, VideoHlsSegment>(
+        "SELECT * FROM video_hls_segments WHERE video_id = $1 ORDER BY segment_index ASC"
+    )
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn list_audio_segments(db_pool: &PgPool, video_id: i32, track_index: i32) -> Result<Vec<VideoHlsSegment>, sqlx::Error> {
+    sqlx::query_as::<_, VideoHlsSegment>(
+        "SELECT video_id, segment_index, duration, s3_key, key_index FROM video_hls_audio_segments
+         WHERE video_id = $1 AND track_index = $2 ORDER BY segment_index ASC"
+    )
+    .bind(video_id)
+    .bind(track_index)
+    .fetch_all(db_pool)
+    .await
+}
+
+// The audio tracks the video was packaged with as alternate renditions, none if its audio is
+// muxed into the video segments
+pub async fn packaged_audio_tracks(db_pool: &PgPool, video_id: i32) -> Result<Vec<VideoAudioTrack>, sqlx::Error> {
+    sqlx::query_as::<_, VideoAudioTrack>(&format!(
+        "SELECT {} FROM video_audio_tracks t
+         WHERE t.video_id = $1
+           AND EXISTS (SELECT 1 FROM video_hls_audio_segments s WHERE s.video_id = t.video_id AND s.track_index = t.track_index)
+         ORDER BY t.track_index ASC", audio_tracks::TRACK_COLUMNS
+    ))
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn get_key(db_pool: &PgPool, video_id: i32, key_index: i32) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    sqlx::query_scalar::<_, Vec<u8>>("SELECT key_bytes FROM video_hls_keys WHERE video_id = $1 AND key_index = $2")
+        .bind(video_id)
+        .bind(key_index)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Swap in a freshly packaged set of keys and segments, with the segments of any alternate audio
+// tracks by track index. Returns the S3 keys of the segments replaced so the caller can delete them.
+pub async fn replace_package(
+    db_pool: &PgPool,
+    video_id: i32,
+    keys: &[(i32, [u8; KEY_BYTES])],
+    segments: &[VideoHlsSegment],
+    audio_segments: &[(i32, VideoHlsSegment)],
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let old_keys = delete_package(&mut tx, video_id).await?;
+
+    let now = chrono::Utc::now().naive_utc();
+    for (key_index, key) in keys {
+        sqlx::query("INSERT INTO video_hls_keys (video_id, key_index, key_bytes, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(video_id)
+            .bind(key_index)
+            .bind(&key[..])
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+    }
+    for segment in segments {
+        sqlx::query(
+            "INSERT INTO video_hls_segments (video_id, segment_index, duration, s3_key, key_index) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(video_id)
+        .bind(segment.segment_index)
+        .bind(segment.duration)
+        .bind(&segment.s3_key)
+        .bind(segment.key_index)
+        .execute(&mut tx)
+        .await?;
+    }
+    for (track_index, segment) in audio_segments {
+        sqlx::query(
+            "INSERT INTO video_hls_audio_segments (video_id, track_index, segment_index, duration, s3_key, key_index)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(video_id)
+        .bind(track_index)
+        .bind(segment.segment_index)
+        .bind(segment.duration)
+        .bind(&segment.s3_key)
+        .bind(segment.key_index)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(old_keys)
+}
+
+// Remove a video's keys and segment rows, audio included, returning the segments' S3 keys
+pub async fn delete_package(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, video_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query("DELETE FROM video_hls_keys WHERE video_id = $1")
+        .bind(video_id)
+        .execute(&mut *tx)
+        .await?;
+    let mut s3_keys: Vec<String> = sqlx::query_scalar("DELETE FROM video_hls_segments WHERE video_id = $1 RETURNING s3_key")
+        .bind(video_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    let audio_keys: Vec<String> = sqlx::query_scalar("DELETE FROM video_hls_audio_segments WHERE video_id = $1 RETURNING s3_key")
+        .bind(video_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    s3_keys.extend(audio_keys);
+    Ok(s3_keys)
+}
@@ -0,0 +1,109 @@
+use std::env;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{ORIGIN, REFERER};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::HttpRequest;
+
+use crate::error::ApiError;
+use crate::restrictions;
+
+// Deployment-wide hotlink protection, on top of each uploader's allow_embed setting:
+//
+// HOTLINK_PROTECTION=on refuses media requests (streams, downloads, HLS, thumbnails, frames,
+// clips, audio tracks) made from pages on other sites, going by Origin or Referer. The site's
+// own CORS_ALLOWED_ORIGINS are always allowed, partners can be added to HOTLINK_ALLOWED_ORIGINS.
+// Requests with neither header (direct visits, native apps, feed readers) get through.
+//
+// HOTLINK_REQUIRE_SIGNED_URLS=on also refuses plain stream and playlist URLs, so only the signed
+// ones API responses hand out play, and only until they expire (see SIGNED_URL_TTL_SECS). Feed
+// enclosures and sitemap links are plain URLs, so they stop playing with it on.
+
+fn enabled(name: &str) -> bool {
+    env::var(name).is_ok_and(|v| matches!(v.trim().to_lowercase().as_str(), "on" | "true" | "1"))
+}
+
+pub fn protection_enabled() -> bool {
+    enabled("HOTLINK_PROTECTION")
+}
+
+pub fn signed_urls_required() -> bool {
+    enabled("HOTLINK_REQUIRE_SIGNED_URLS")
+}
+
+// Origins allowed to use the media: the site's own and HOTLINK_ALLOWED_ORIGINS
+pub fn allowed_origins() -> Vec<String> {
+    let own = env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let partners = env::var("HOTLINK_ALLOWED_ORIGINS").unwrap_or_default();
+    own.split(',')
+        .chain(partners.split(','))
+        .map(|o| o.trim().trim_end_matches('/').to_string())
+        .filter(|o| !o.is_empty())
+        .collect()
+}
+
+// Whether a request for `path` fetches media rather than metadata about it
+pub fn is_media_path(method: &Method, path: &str) -> bool {
+    if *method != Method::GET && *method != Method::HEAD {
+        return false;
+    }
+    if path.starts_with("/api/thumbnails/") {
+        return true;
+    }
+    let Some(rest) = path.strip_prefix("/api/videos/") else {
+        return false;
+    };
+    let mut parts = rest.splitn(3, '/');
+    let (Some(id), Some(resource)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    if id.parse::<i32>().is_err() {
+        return false;
+    }
+    match (resource, parts.next()) {
+        ("stream" | "download" | "frame" | "thumbnail" | "key", None) => true,
+        ("hls", Some(_)) | ("clips", Some(_)) => true,
+        // The list of tracks is metadata; a track's own path is its file
+        ("audio-tracks", Some(track)) => !track.is_empty(),
+        _ => false,
+    }
+}
+
+// Whether an Origin or Referer value, if any, belongs to an allowed site. "null" origins from
+// sandboxed frames never do.
+pub fn source_allowed(source: Option<&str>, allowed: &[String]) -> bool {
+    let Some(source) = source else {
+        return true;
+    };
+    restrictions::origin_of(source).is_some_and(|origin| allowed.iter().any(|o| o.eq_ignore_ascii_case(origin)))
+}
+
+fn request_source(http_req: &HttpRequest) -> Option<&str> {
+    let headers = http_req.headers();
+    headers.get(ORIGIN).or_else(|| headers.get(REFERER)).map(|v| v.to_str().unwrap_or("null"))
+}
+
+// Refuse unsigned playback URLs when HOTLINK_REQUIRE_SIGNED_URLS is on. Signed ones are then
+// checked by signed_urls::verify_if_signed as usual.
+pub fn require_signature(expires: Option<i64>, signature: Option<&str>) -> Result<(), ApiError> {
+    if signed_urls_required() && (expires.is_none() || signature.is_none()) {
+        return Err(ApiError::Forbidden("This link has to be signed".to_string()));
+    }
+    Ok(())
+}
+
+// Middleware refusing media requests from other sites while HOTLINK_PROTECTION is on
+pub async fn check_hotlink(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if protection_enabled()
+        && is_media_path(req.method(), req.path())
+        && !source_allowed(request_source(req.request()), &allowed_origins())
+    {
+        return Ok(req.error_response(ApiError::Forbidden("Media from this site can't be used on other sites".to_string())));
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
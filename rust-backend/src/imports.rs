@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::env;
+use std::sync::Arc;
+use log::{error, info};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::error::ApiError;
+use crate::job_queue::JobQueue;
+use crate::models::Video;
+use crate::moderation;
+use crate::storage::ObjectStore;
+use crate::webhooks;
+
+pub use crate::storage::ListedObject;
+
+// Where uploads are stored, so the prefix to scan when IMPORT_S3_PREFIX is not set
+pub const DEFAULT_PREFIX: &str = "videos/";
+
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mov", "webm", "mkv", "avi", "mpeg"];
+
+pub fn import_prefix() -> String {
+    env::var("IMPORT_S3_PREFIX").unwrap_or_else(|_| DEFAULT_PREFIX.to_string())
+}
+
+pub fn is_video_key(key: &str) -> bool {
+    key.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .is_some_and(|(_, ext)| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+// A title from the object's file name: "talks/2019_Keynote-final.mp4" becomes "2019 Keynote final"
+pub fn title_from_key(key: &str) -> String {
+    let name = key.rsplit('/').next().unwrap_or(key);
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let stem = urlencoding::decode(stem).map_or_else(|_| stem.to_string(), |s| s.into_owned());
+    let words: Vec<&str> = stem.split(|c: char| c == '_' || c == '-' || c == '.' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.is_empty() {
+        "Untitled video".to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+// The listed objects that are video files not yet known to the database
+pub fn objects_to_import<'a>(objects: &'a [ListedObject], known_keys: &HashSet<String>) -> Vec<&'a ListedObject> {
+    objects
+        .iter()
+        .filter(|o| o.size > 0 && is_video_key(&o.key) && !known_keys.contains(&o.key))
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedObject {
+    pub s3_key: String,
+    pub title: String,
+    pub video_id: Option<i32>, // None in a dry run
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub prefix: String,
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub already_imported: usize,
+    pub imported: Vec<ImportedObject>,
+}
+
+fn s3_error(action: &str, e: impl std::fmt::Debug) -> ApiError {
+    ApiError::Internal(format!("Failed to {}: {:?}", action, e))
+}
+
+// Keys already used by a video, a rendition or an upload in progress, which must not become
+// videos of their own
+pub(crate) async fn known_keys(db_pool: &PgPool, keys: &[String]) -> Result<HashSet<String>, sqlx::Error> {
+    let known: Vec<String> = sqlx::query_scalar(
+        "SELECT s3_key FROM videos WHERE s3_key = ANY($1)
+         UNION SELECT s3_key FROM video_renditions WHERE s3_key = ANY($1)
+         UNION SELECT s3_key FROM upload_sessions WHERE s3_key = ANY($1)"
+    )
+    .bind(keys)
+    .fetch_all(db_pool)
+    .await?;
+    Ok(known.into_iter().collect())
+}
+
+// Insert a video for the object unless one appeared for it meanwhile, e.g. from a concurrent import
+pub(crate) async fn insert_video(db_pool: &PgPool, object: &ListedObject, title: &str, uploaded_by: Option<i32>) -> Result<Option<Video>, sqlx::Error> {
+    sqlx::query_as::<_, Video>(
+        "INSERT INTO videos (title, s3_key, uploaded_by, upload_date, moderation_status)
+         SELECT $1, $2, $3, $4, $5
+         WHERE NOT EXISTS (SELECT 1 FROM videos WHERE s3_key = $2)
+         RETURNING *"
+    )
+    .bind(title)
+    .bind(&object.key)
+    .bind(uploaded_by)
+    .bind(object.last_modified.unwrap_or_else(|| chrono::Utc::now().naive_utc()))
+    .bind(moderation::initial_status())
+    .fetch_optional(db_pool)
+    .await
+}
+
+// Scan `prefix` in the bucket and create a video for each video file the database doesn't know
+// yet, queueing the usual processing (duration, thumbnail, renditions...) for it. With
+// `dry_run` nothing is written and the report lists what would be imported.
+pub async fn import_objects(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    job_queue: Option<&Arc<JobQueue>>,
+    bucket: &str,
+    prefix: &str,
+    uploaded_by: Option<i32>,
+    dry_run: bool,
+) -> Result<ImportReport, ApiError> {
+    let mut report = ImportReport { prefix: prefix.to_string(), dry_run, ..ImportReport::default() };
+    let mut continuation_token = None;
+
+    loop {
+        let page = storage
+            .list(bucket, prefix, continuation_token.take())
+            .await
+            .map_err(|e| e.into_api_error(|e| s3_error("list objects to import", e)))?;
+
+        let objects = page.objects;
+        report.scanned += objects.len();
+
+        let keys: Vec<String> = objects.iter().map(|o| o.key.clone()).collect();
+        let known = known_keys(db_pool, &keys).await?;
+        report.already_imported += objects.iter().filter(|o| known.contains(&o.key)).count();
+
+        for object in objects_to_import(&objects, &known) {
+            let title = title_from_key(&object.key);
+            if dry_run {
+                report.imported.push(ImportedObject { s3_key: object.key.clone(), title, video_id: None });
+                continue;
+            }
+            let Some(video) = insert_video(db_pool, object, &title, uploaded_by).await? else {
+                report.already_imported += 1;
+                continue;
+            };
+            if let Some(job_queue) = job_queue {
+                if let Err(e) = job_queue.enqueue_processing(&video, bucket, false).await {
+                    error!("Failed to enqueue processing for imported video {}: {:?}", video.id, e);
+                }
+            }
+            webhooks::video_created(db_pool, &video).await;
+            report.imported.push(ImportedObject { s3_key: video.s3_key, title: video.title, video_id: Some(video.id) });
+        }
+
+        match page.next {
+            Some(token) => continuation_token = Some(token),
+            None => break,
+        }
+    }
+
+    info!(
+        "Import of s3://{}/{}: {} objects scanned, {} {}, {} already imported",
+        bucket,
+        prefix,
+        report.scanned,
+        report.imported.len(),
+        if dry_run { "to import" } else { "imported" },
+        report.already_imported,
+    );
+    Ok(report)
+}
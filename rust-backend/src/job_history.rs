@@ -0,0 +1,97 @@
+use log::error;
+use sqlx::PgPool;
+
+use crate::job_queue::{MediaJob, MediaJobKind};
+use crate::models::JobAttempt;
+
+pub const OUTCOME_RUNNING: &str = "running";
+pub const OUTCOME_SUCCEEDED: &str = "succeeded";
+pub const OUTCOME_FAILED: &str = "failed";
+
+// How many failures GET /api/admin/jobs lists
+pub const RECENT_FAILURES: i64 = 50;
+
+// Record that a worker picked up the job. None when it couldn't be recorded, e.g. because the
+// video is gone; the job runs regardless.
+pub async fn start_attempt(db_pool: &PgPool, job: &MediaJob, worker: &str) -> Option<i64> {
+    let result = sqlx::query_scalar(
+        "INSERT INTO media_job_attempts (video_id, kind, attempt, worker) VALUES ($1, $2, $3, $4) RETURNING id"
+    )
+    .bind(job.video_id)
+    .bind(job.kind.as_str())
+    .bind(job.attempt as i32)
+    .bind(worker)
+    .fetch_one(db_pool)
+    .await;
+    match result {
+        Ok(id) => Some(id),
+        Err(e) => {
+            error!("Failed to record {:?} job attempt for video ID {}: {:?}", job.kind, job.video_id, e);
+            None
+        }
+    }
+}
+
+// Record how an attempt ended. `error` is None when it succeeded.
+pub async fn finish_attempt(db_pool: &PgPool, attempt_id: Option<i64>, error: Option<&str>, will_retry: bool) {
+    let Some(attempt_id) = attempt_id else {
+        return;
+    };
+    let outcome = if error.is_some() { OUTCOME_FAILED } else { OUTCOME_SUCCEEDED };
+    let result = sqlx::query(
+        "UPDATE media_job_attempts SET outcome = $2, error = $3, will_retry = $4, finished_at = NOW() WHERE id = $1"
+    )
+    .bind(attempt_id)
+    .bind(outcome)
+    .bind(error)
+    .bind(will_retry)
+    .execute(db_pool)
+    .await;
+    if let Err(e) = result {
+        error!("Failed to record the outcome of job attempt {}: {:?}", attempt_id, e);
+    }
+}
+
+// Close the running attempts of a job whose worker stopped before finishing it, once another
+// worker has claimed the job
+pub async fn abandon(db_pool: &PgPool, video_id: i32, kind: MediaJobKind) {
+    let result = sqlx::query(
+        "UPDATE media_job_attempts SET outcome = 'failed', error = 'Worker stopped before finishing', will_retry = TRUE, finished_at = NOW()
+         WHERE video_id = $1 AND kind = $2 AND outcome = 'running'"
+    )
+    .bind(video_id)
+    .bind(kind.as_str())
+    .execute(db_pool)
+    .await;
+    if let Err(e) = result {
+        error!("Failed to close abandoned {:?} job attempts for video ID {}: {:?}", kind, video_id, e);
+    }
+}
+
+// Attempts workers are on now, oldest first. One a worker died during stays here until another
+// worker claims its job.
+pub async fn in_flight(db_pool: &PgPool) -> Result<Vec<JobAttempt>, sqlx::Error> {
+    sqlx::query_as::<_, JobAttempt>("SELECT * FROM media_job_attempts WHERE outcome = 'running' ORDER BY started_at ASC")
+        .fetch_all(db_pool)
+        .await
+}
+
+pub async fn recent_failures(db_pool: &PgPool, limit: i64) -> Result<Vec<JobAttempt>, sqlx::Error> {
+    sqlx::query_as::<_, JobAttempt>(
+        "SELECT * FROM media_job_attempts WHERE outcome = 'failed' ORDER BY finished_at DESC, id DESC LIMIT $1"
+    )
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Every attempt at a video's jobs, or at just one kind of them, in the order they were made
+pub async fn attempts(db_pool: &PgPool, video_id: i32, kind: Option<MediaJobKind>) -> Result<Vec<JobAttempt>, sqlx::Error> {
+    sqlx::query_as::<_, JobAttempt>(
+        "SELECT * FROM media_job_attempts WHERE video_id = $1 AND ($2::TEXT IS NULL OR kind = $2) ORDER BY started_at ASC, id ASC"
+    )
+    .bind(video_id)
+    .bind(kind.map(MediaJobKind::as_str))
+    .fetch_all(db_pool)
+    .await
+}
@@ -1,53 +1,369 @@
-use serde::{Deserialize, Serialize};
+use serde_json::json;
 use log::{info, error, warn};
 use std::time::Duration;
 use tokio::time::sleep;
 use sqlx::PgPool;
-use aws_sdk_s3::Client as S3Client;
+use crate::circuit_breaker;
 use crate::video_utils::extract_video_metadata_from_s3;
-use crate::models::Video;
+use crate::models::{Video, VideoHlsSegment};
+use crate::media::{self, HlsStreams, WorkDir};
+use crate::services::{download_object_parallel, ParallelDownloadConfig};
+use crate::storage::{Storage, StorageError};
+use crate::audio_tracks;
+use crate::chapters;
+use crate::encoding_ladder::{self, Complexity};
+use crate::hls;
+use crate::job_history;
+use crate::loudness;
+use crate::moderation;
+use crate::processing;
+use crate::spherical;
+use crate::thumbnails;
+use crate::transcription::{self, TranscriptionBackend};
+use crate::watermarks;
+use crate::webhooks;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DurationExtractionJob {
-    pub video_id: i32,
-    pub s3_key: String,
-    pub bucket: String,
+// Jobs are entries of a Redis stream read through a consumer group. A worker acknowledges an entry
+// only once its job has finished, so the job of a worker that died stays pending until another
+// worker claims it. Any number of workers, in the web process or run with --worker, share the
+// stream, and a lock per video and job kind keeps two of them from running duplicate jobs of the
+// same video at once.
+const MEDIA_JOBS_STREAM: &str = "media_jobs:stream";
+const MEDIA_JOBS_GROUP: &str = "media_workers";
+// Lists jobs were queued on before the stream. Jobs left in them are moved onto it at startup.
+const LEGACY_QUEUES: [&str; 2] = ["media_jobs", "duration_extraction_jobs"];
+// How long a worker waits for a new job before checking for jobs to claim again
+const READ_BLOCK_MILLIS: u64 = 30_000;
+
+// How often to look for pending videos that nothing queued moderation for
+const MODERATION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Releases a job lock only if this worker still holds it, as it may have expired and been taken
+pub(crate) const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+// Extends a job lock and resets its entry's idle time, only if this worker still holds the lock.
+// KEYS: lock, stream. ARGV: worker ID, TTL in milliseconds, group, entry ID.
+const RENEW_LOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    redis.call("PEXPIRE", KEYS[1], ARGV[2])
+    redis.call("XCLAIM", KEYS[2], ARGV[3], ARGV[1], 0, ARGV[4], "JUSTID")
+    return 1
+end
+return 0
+"#;
+
+// How long a worker may hold a job lock, and how long a job may go unacknowledged before another
+// worker claims it, from JOB_LOCK_TTL_SECS. Both are renewed while the job runs, so this only
+// bounds how long the job of a worker that stopped waits to be claimed.
+fn job_lock_ttl() -> Duration {
+    let secs = std::env::var("JOB_LOCK_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(300);
+    Duration::from_secs(secs)
 }
 
+// Whether the web process runs the job processors itself, from EMBEDDED_WORKERS. Set it to false
+// when dedicated --worker processes do the processing.
+pub fn embedded_workers_enabled() -> bool {
+    std::env::var("EMBEDDED_WORKERS")
+        .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "false" | "0" | "no" | "off"))
+        .unwrap_or(true)
+}
+
+fn job_lock_key(job: &MediaJob) -> String {
+    format!("media_job_lock:{}:{}", job.video_id, job.kind.as_str())
+}
+
+// (id, job JSON) of each of a list of stream entries. An entry deleted from the stream since it
+// was read comes without its fields.
+fn parse_entries(entries: &redis::Value) -> Vec<(String, Option<String>)> {
+    let redis::Value::Bulk(entries) = entries else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let redis::Value::Bulk(parts) = entry else {
+                return None;
+            };
+            let id: String = redis::from_redis_value(parts.first()?).ok()?;
+            let fields: Vec<String> = parts.get(1).and_then(|f| redis::from_redis_value(f).ok()).unwrap_or_default();
+            let job = fields.chunks(2).find(|f| f[0] == "job").and_then(|f| f.get(1).cloned());
+            Some((id, job))
+        })
+        .collect()
+}
+
+async fn add_to_stream(conn: &mut redis::aio::Connection, job_json: &str) -> redis::RedisResult<String> {
+    redis::cmd("XADD")
+        .arg(MEDIA_JOBS_STREAM)
+        .arg("*")
+        .arg("job")
+        .arg(job_json)
+        .query_async(conn)
+        .await
+}
+
+// Acknowledge an entry and drop it, so the stream only holds jobs that haven't finished
+async fn acknowledge(conn: &mut redis::aio::Connection, entry_id: &str) -> redis::RedisResult<()> {
+    redis::pipe()
+        .atomic()
+        .cmd("XACK").arg(MEDIA_JOBS_STREAM).arg(MEDIA_JOBS_GROUP).arg(entry_id).ignore()
+        .cmd("XDEL").arg(MEDIA_JOBS_STREAM).arg(entry_id).ignore()
+        .query_async(conn)
+        .await
+}
+
+// Put a job at the end of the stream in place of the entry it was read from
+async fn requeue(conn: &mut redis::aio::Connection, entry_id: &str, job_json: &str) -> redis::RedisResult<()> {
+    redis::pipe()
+        .atomic()
+        .cmd("XADD").arg(MEDIA_JOBS_STREAM).arg("*").arg("job").arg(job_json).ignore()
+        .cmd("XACK").arg(MEDIA_JOBS_STREAM).arg(MEDIA_JOBS_GROUP).arg(entry_id).ignore()
+        .cmd("XDEL").arg(MEDIA_JOBS_STREAM).arg(entry_id).ignore()
+        .query_async(conn)
+        .await
+}
+
+// A job read from the stream
+struct StreamEntry {
+    id: String,
+    job_json: Option<String>,
+    // Whether it was claimed from a worker that left it unfinished
+    claimed: bool,
+}
+
+// Shared so other services can queue work for the backend's workers
+pub use videostreaming_models::media_jobs::{MediaJob, MediaJobKind};
+
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct JobQueue {
     redis_client: redis::Client,
     db_pool: PgPool,
-    s3_client: S3Client,
+    storage: Storage,
+    // Identifies this process's worker in job locks and attempts, e.g. "backend-7f9c-3fa2b1c0"
+    worker_id: String,
 }
 
 impl JobQueue {
-    pub fn new(redis_client: redis::Client, db_pool: PgPool, s3_client: S3Client) -> Arc<Self> {
+    pub fn new(redis_client: redis::Client, db_pool: PgPool, storage: Storage) -> Arc<Self> {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
+        let worker_id = format!("{}-{}", host, &uuid::Uuid::new_v4().simple().to_string()[..8]);
         Arc::new(Self {
             redis_client,
             db_pool,
-            s3_client,
+            storage,
+            worker_id,
         })
     }
 
-    pub async fn enqueue_duration_extraction(&self, job: DurationExtractionJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut conn = self.redis_client.get_async_connection().await?;
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+
+    // Take the job's lock, unless another worker is running the same job of the same video
+    async fn lock_job(&self, conn: &mut redis::aio::Connection, job: &MediaJob) -> redis::RedisResult<bool> {
+        let locked: Option<String> = redis::cmd("SET")
+            .arg(job_lock_key(job))
+            .arg(&self.worker_id)
+            .arg("NX")
+            .arg("PX")
+            .arg(job_lock_ttl().as_millis() as u64)
+            .query_async(conn)
+            .await?;
+        Ok(locked.is_some())
+    }
+
+    async fn unlock_job(&self, conn: &mut redis::aio::Connection, job: &MediaJob) {
+        let released: redis::RedisResult<i32> = redis::Script::new(RELEASE_LOCK_SCRIPT)
+            .key(job_lock_key(job))
+            .arg(&self.worker_id)
+            .invoke_async(conn)
+            .await;
+        if let Err(e) = released {
+            error!("Failed to release the {:?} job lock for video ID {}: {:?}", job.kind, job.video_id, e);
+        }
+    }
+
+    // Renew the job's lock and claim on its entry every third of the TTL for as long as it runs, so
+    // no other worker takes it over however long it takes. Stops if the lock was lost anyway.
+    async fn keep_job_alive(&self, entry_id: &str, job: &MediaJob) {
+        let ttl = job_lock_ttl();
+        loop {
+            sleep(ttl / 3).await;
+            let renewed: redis::RedisResult<i32> = circuit_breaker::redis(&self.redis_client, |mut conn| async move {
+                redis::Script::new(RENEW_LOCK_SCRIPT)
+                    .key(job_lock_key(job))
+                    .key(MEDIA_JOBS_STREAM)
+                    .arg(&self.worker_id)
+                    .arg(ttl.as_millis() as u64)
+                    .arg(MEDIA_JOBS_GROUP)
+                    .arg(entry_id)
+                    .invoke_async(&mut conn)
+                    .await
+            })
+            .await;
+            match renewed {
+                Ok(1) => {}
+                Ok(_) => {
+                    warn!("Lost the {:?} job lock for video ID {} while running it", job.kind, job.video_id);
+                    return std::future::pending().await;
+                }
+                // Retried next time, well before the lock expires
+                Err(e) => warn!("Failed to renew the {:?} job lock for video ID {}: {:?}", job.kind, job.video_id, e),
+            }
+        }
+    }
+
+    pub async fn enqueue(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = circuit_breaker::redis_connection(&self.redis_client).await?;
         let job_json = serde_json::to_string(&job)?;
+        // Recorded first so a worker picking the job up right away can't be overtaken
+        processing::track(&self.db_pool, job.video_id, job.kind, processing::JOB_QUEUED, None).await;
         
-        redis::cmd("LPUSH")
-            .arg("duration_extraction_jobs")
-            .arg(&job_json)
-            .query_async::<_, i32>(&mut conn)
-            .await?;
+        if let Err(e) = add_to_stream(&mut conn, &job_json).await {
+            processing::track(&self.db_pool, job.video_id, job.kind, processing::JOB_FAILED, Some(&e.to_string())).await;
+            return Err(e.into());
+        }
         
-        info!("Enqueued duration extraction job for video ID {}", job.video_id);
+        info!("Enqueued {:?} job for video ID {}", job.kind, job.video_id);
+        Ok(())
+    }
+
+    // Queue every processing step for a video. With `force`, existing results are replaced.
+    pub async fn enqueue_processing(&self, video: &Video, bucket: &str, force: bool) -> Result<Vec<MediaJobKind>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut kinds = vec![
+            MediaJobKind::Duration,
+            MediaJobKind::Thumbnail,
+            MediaJobKind::Transcode,
+            MediaJobKind::DetectScenes,
+            MediaJobKind::AnalyzeLoudness,
+        ];
+        if TranscriptionBackend::from_env().is_some() {
+            kinds.push(MediaJobKind::Transcribe);
+        }
+        if moderation::is_enabled() {
+            kinds.push(MediaJobKind::Moderate);
+        }
+        if video.hls_encrypted {
+            kinds.push(MediaJobKind::PackageHls);
+        }
+        processing::restart(&self.db_pool, video.id).await?;
+        for kind in &kinds {
+            self.enqueue(MediaJob {
+                force,
+                ..MediaJob::new(*kind, video, bucket)
+            }).await?;
+        }
+        Ok(kinds)
+    }
+
+    // Jobs waiting to be picked up, and jobs picked up but not finished yet
+    pub async fn queue_depths(&self) -> Result<Vec<(&'static str, i64)>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = circuit_breaker::redis_connection(&self.redis_client).await?;
+        let length: i64 = redis::cmd("XLEN").arg(MEDIA_JOBS_STREAM).query_async(&mut conn).await?;
+        let summary: Vec<redis::Value> = redis::cmd("XPENDING")
+            .arg(MEDIA_JOBS_STREAM)
+            .arg(MEDIA_JOBS_GROUP)
+            .query_async(&mut conn)
+            .await?;
+        let pending: i64 = summary.first().map(redis::from_redis_value).transpose()?.unwrap_or(0);
+        Ok(vec![("waiting", length - pending), ("pending", pending)])
+    }
+
+    // Create the consumer group unless it exists, and move jobs left in the old lists onto the stream
+    async fn prepare_stream(&self) -> redis::RedisResult<()> {
+        let mut conn = circuit_breaker::redis_connection(&self.redis_client).await?;
+        let created: redis::RedisResult<()> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(MEDIA_JOBS_STREAM)
+            .arg(MEDIA_JOBS_GROUP)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query_async(&mut conn)
+            .await;
+        match created {
+            Ok(()) => info!("Created the {} consumer group", MEDIA_JOBS_GROUP),
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(e),
+        }
+
+        for queue in LEGACY_QUEUES {
+            let mut moved = 0;
+            // Oldest first, the order BRPOP took them in
+            while let Some(job_json) = redis::cmd("RPOP").arg(queue).query_async::<_, Option<String>>(&mut conn).await? {
+                if let Err(e) = add_to_stream(&mut conn, &job_json).await {
+                    redis::cmd("RPUSH").arg(queue).arg(&job_json).query_async::<_, i32>(&mut conn).await?;
+                    return Err(e);
+                }
+                moved += 1;
+            }
+            if moved > 0 {
+                info!("Moved {} jobs from the {} list onto the job stream", moved, queue);
+            }
+        }
         Ok(())
     }
 
-    pub async fn process_duration_extraction_jobs(&self) {
-        info!("Starting duration extraction job processor");
+    // The next job: one another worker left unfinished for longer than the lock TTL, else a new
+    // one, waiting a while for it to arrive
+    async fn next_entry(&self, conn: &mut redis::aio::Connection) -> redis::RedisResult<Option<StreamEntry>> {
+        let claimed: Vec<redis::Value> = redis::cmd("XAUTOCLAIM")
+            .arg(MEDIA_JOBS_STREAM)
+            .arg(MEDIA_JOBS_GROUP)
+            .arg(&self.worker_id)
+            .arg(job_lock_ttl().as_millis() as u64)
+            .arg("0-0")
+            .arg("COUNT")
+            .arg(1)
+            .query_async(conn)
+            .await?;
+        if let Some((id, job_json)) = claimed.get(1).map(parse_entries).and_then(|entries| entries.into_iter().next()) {
+            return Ok(Some(StreamEntry { id, job_json, claimed: true }));
+        }
+
+        let read: redis::Value = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(MEDIA_JOBS_GROUP)
+            .arg(&self.worker_id)
+            .arg("COUNT")
+            .arg(1)
+            .arg("BLOCK")
+            .arg(READ_BLOCK_MILLIS)
+            .arg("STREAMS")
+            .arg(MEDIA_JOBS_STREAM)
+            .arg(">")
+            .query_async(conn)
+            .await?;
+        // Nil on timeout, else [[stream, [entry]]]
+        let redis::Value::Bulk(streams) = read else {
+            return Ok(None);
+        };
+        let entry = streams.first().and_then(|stream| match stream {
+            redis::Value::Bulk(parts) => parts.get(1).map(parse_entries),
+            _ => None,
+        });
+        Ok(entry
+            .and_then(|entries| entries.into_iter().next())
+            .map(|(id, job_json)| StreamEntry { id, job_json, claimed: false }))
+    }
+
+    pub async fn process_media_jobs(&self) {
+        info!("Starting media job processor");
+
+        while let Err(e) = self.prepare_stream().await {
+            error!("Failed to set up the media job stream: {:?}", e);
+            sleep(Duration::from_secs(10)).await;
+        }
         
         loop {
             match self.process_next_job().await {
@@ -67,7 +383,7 @@ impl JobQueue {
 
     async fn process_next_job(&self) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
         // Get Redis connection with retry logic
-        let mut conn = match self.redis_client.get_async_connection().await {
+        let mut conn = match circuit_breaker::redis_connection(&self.redis_client).await {
             Ok(conn) => conn,
             Err(e) => {
                 error!("Failed to get Redis connection: {:?}", e);
@@ -77,66 +393,139 @@ impl JobQueue {
             }
         };
         
-        // Use BRPOP to block until a job is available (with timeout)
-        let result: Option<(String, String)> = match redis::cmd("BRPOP")
-            .arg("duration_extraction_jobs")
-            .arg(30) // 30 second timeout
-            .query_async(&mut conn)
-            .await
-        {
-            Ok(res) => res,
+        let entry = match self.next_entry(&mut conn).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(false), // No job available (timeout)
+            // Redis lost the group, e.g. after a restart without persistence
+            Err(e) if e.code() == Some("NOGROUP") => {
+                warn!("The {} consumer group is missing, creating it again", MEDIA_JOBS_GROUP);
+                self.prepare_stream().await?;
+                return Ok(true);
+            }
             Err(e) => {
-                error!("Redis BRPOP command failed: {:?}", e);
+                error!("Failed to read the media job stream: {:?}", e);
                 return Ok(false);
             }
         };
 
-        if let Some((_, job_json)) = result {
-            // Parse the job JSON
-            let job: DurationExtractionJob = match serde_json::from_str(&job_json) {
-                Ok(job) => job,
-                Err(e) => {
-                    error!("Failed to parse job JSON: {:?}", e);
-                    return Ok(true); // Consider the job processed (but failed)
+        let Some(job_json) = entry.job_json else {
+            acknowledge(&mut conn, &entry.id).await?;
+            return Ok(true);
+        };
+        // Parse the job JSON
+        let mut job: MediaJob = match serde_json::from_str(&job_json) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("Failed to parse job JSON: {:?}", e);
+                acknowledge(&mut conn, &entry.id).await?;
+                return Ok(true); // Consider the job processed (but failed)
+            }
+        };
+        
+        let video_id = job.video_id; // Store video_id before moving job
+        let kind = job.kind;
+
+        // A duplicate of a job another worker is running waits its turn at the end of the stream
+        match self.lock_job(&mut conn, &job).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("{:?} job for video ID {} is running on another worker, re-enqueueing", kind, video_id);
+                requeue(&mut conn, &entry.id, &job_json).await?;
+                sleep(Duration::from_secs(1)).await;
+                return Ok(true);
+            }
+            Err(e) => {
+                requeue(&mut conn, &entry.id, &job_json).await?;
+                return Err(e.into());
+            }
+        }
+
+        if entry.claimed {
+            // With the lock taken, no other worker is running it: the attempt left running is the dead worker's
+            warn!("Claimed {:?} job for video ID {} from a worker that stopped", kind, video_id);
+            job_history::abandon(&self.db_pool, video_id, kind).await;
+            job.attempt += 1;
+        }
+
+        let lock = job.clone();
+        let retry = MediaJob { attempt: job.attempt + 1, ..job.clone() };
+        info!("Worker {} processing {:?} job for video ID {} (attempt {})", self.worker_id, kind, video_id, job.attempt);
+        processing::track(&self.db_pool, video_id, kind, processing::JOB_RUNNING, None).await;
+        let attempt_id = job_history::start_attempt(&self.db_pool, &job, &self.worker_id).await;
+        
+        let run = async {
+            match kind {
+                MediaJobKind::Duration => self.extract_and_update_duration(job).await,
+                MediaJobKind::Thumbnail => self.generate_thumbnail(job).await,
+                MediaJobKind::Transcode => self.transcode_renditions(job).await,
+                MediaJobKind::Transcribe => self.transcribe(job).await,
+                MediaJobKind::DetectScenes => self.detect_scenes(job).await,
+                MediaJobKind::Moderate => self.moderate(job).await,
+                MediaJobKind::PackageHls => self.package_hls(job).await,
+                MediaJobKind::AnalyzeLoudness => self.analyze_loudness(job).await,
+            }
+        };
+        let result = tokio::select! {
+            result = run => result,
+            _ = self.keep_job_alive(&entry.id, &lock) => unreachable!("renewal runs until the job is done"),
+        };
+
+        // Jobs can take long enough for Redis to drop an idle connection. Without one the entry
+        // stays pending and is claimed again later.
+        let mut conn = circuit_breaker::redis_connection(&self.redis_client).await?;
+        match result {
+            Ok(_) => {
+                info!("Successfully processed {:?} job for video ID {}", kind, video_id);
+                processing::track(&self.db_pool, video_id, kind, processing::JOB_DONE, None).await;
+                job_history::finish_attempt(&self.db_pool, attempt_id, None, false).await;
+                if let Err(e) = acknowledge(&mut conn, &entry.id).await {
+                    error!("Failed to acknowledge {:?} job for video ID {}: {:?}", kind, video_id, e);
                 }
-            };
-            
-            let video_id = job.video_id; // Store video_id before moving job
-            info!("Processing duration extraction job for video ID {}", video_id);
-            
-            match self.extract_and_update_duration(job).await {
-                Ok(_) => {
-                    info!("Successfully processed duration extraction job");
+                let data = json!({ "videoId": video_id, "job": kind });
+                if let Err(e) = webhooks::dispatch(&self.db_pool, webhooks::EVENT_VIDEO_PROCESSED, data).await {
+                    error!("Failed to queue video.processed webhooks for video {}: {:?}", video_id, e);
                 }
-                Err(e) => {
-                    // Check if the error is due to S3 object not found (404)
-                    let error_string = format!("{:?}", e);
-                    if error_string.contains("NoSuchKey") || error_string.contains("404") {
-                        warn!("S3 object not found for video ID {}, not re-enqueueing job", video_id);
-                    } else {
-                        error!("Failed to process duration extraction job: {:?}", e);
-                        
-                        // Implement retry logic - push the original job back to the queue
-                        info!("Re-enqueueing failed job for video ID {}", video_id);
-                        if let Err(push_err) = redis::cmd("LPUSH")
-                            .arg("duration_extraction_jobs")
-                            .arg(&job_json)
-                            .query_async::<_, i32>(&mut conn)
-                            .await
-                        {
-                            error!("Failed to re-enqueue job: {:?}", push_err);
-                        }
+            }
+            Err(e) => {
+                // Jobs whose source object is gone would only fail again
+                let missing_object = e.downcast_ref::<StorageError>().is_some_and(StorageError::is_not_found);
+                let data = json!({
+                    "videoId": video_id,
+                    "job": kind,
+                    "error": e.to_string(),
+                    "willRetry": !missing_object,
+                });
+                if let Err(e) = webhooks::dispatch(&self.db_pool, webhooks::EVENT_JOB_FAILED, data).await {
+                    error!("Failed to queue job.failed webhooks for video {}: {:?}", video_id, e);
+                }
+
+                let state = if missing_object { processing::JOB_FAILED } else { processing::JOB_QUEUED };
+                processing::track(&self.db_pool, video_id, kind, state, Some(&e.to_string())).await;
+                job_history::finish_attempt(&self.db_pool, attempt_id, Some(&e.to_string()), !missing_object).await;
+
+                if missing_object {
+                    warn!("Object not found for video ID {}, not re-enqueueing job", video_id);
+                    if let Err(e) = acknowledge(&mut conn, &entry.id).await {
+                        error!("Failed to acknowledge {:?} job for video ID {}: {:?}", kind, video_id, e);
+                    }
+                } else {
+                    error!("Failed to process {:?} job: {:?}", kind, e);
+                    
+                    // Implement retry logic - put the job back on the stream as its next attempt
+                    info!("Re-enqueueing failed job for video ID {}", video_id);
+                    let retry_json = serde_json::to_string(&retry).unwrap_or(job_json);
+                    if let Err(push_err) = requeue(&mut conn, &entry.id, &retry_json).await {
+                        error!("Failed to re-enqueue job: {:?}", push_err);
                     }
                 }
             }
-            
-            Ok(true) // Job was processed
-        } else {
-            Ok(false) // No job available (timeout)
         }
+        self.unlock_job(&mut conn, &lock).await;
+        
+        Ok(true) // Job was processed
     }
 
-    async fn extract_and_update_duration(&self, job: DurationExtractionJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn extract_and_update_duration(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Check if video still needs duration extraction
         let video_result = match sqlx::query_as::<_, Video>(
             "SELECT * FROM videos WHERE id = $1"
@@ -161,7 +550,7 @@ impl JobQueue {
         };
 
         // Check if duration is already set
-        if let Some(duration) = video.duration {
+        if let Some(duration) = video.duration.filter(|_| !job.force) {
             info!("Video ID {} already has duration: {} seconds, skipping", job.video_id, duration);
             return Ok(());
         }
@@ -174,7 +563,7 @@ impl JobQueue {
         let mut last_error = None;
 
         while retry_count < max_retries {
-            match extract_video_metadata_from_s3(&self.s3_client, &job.bucket, &job.s3_key).await {
+            match extract_video_metadata_from_s3(self.storage.as_ref(), &job.bucket, &job.s3_key).await {
                 Ok(duration) => {
                     info!("Extracted duration {} seconds for video ID {}", duration, job.video_id);
                     
@@ -220,24 +609,485 @@ impl JobQueue {
         // All retries failed
         if let Some(e) = last_error {
             error!("All {} attempts to extract duration for video ID {} failed", max_retries, job.video_id);
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            return Err(Box::new(std::io::Error::other(
                 format!("Failed to extract duration after {} attempts: {}", max_retries, e)
             )) as Box<dyn std::error::Error + Send + Sync>);
         }
 
         // This should never happen, but just in case
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(Box::new(std::io::Error::other(
             "Unknown error in duration extraction"
         )) as Box<dyn std::error::Error + Send + Sync>)
     }
 
+    // Look up a video a job refers to, skipping videos that were deleted or trashed since it was queued
+    async fn job_video(&self, job: &MediaJob) -> Result<Option<Video>, Box<dyn std::error::Error + Send + Sync>> {
+        let video = sqlx::query_as::<_, Video>("SELECT * FROM videos WHERE id = $1 AND deleted_at IS NULL")
+            .bind(job.video_id)
+            .fetch_optional(&self.db_pool)
+            .await?;
+
+        if video.is_none() {
+            warn!("Video ID {} no longer exists, skipping {:?} job", job.video_id, job.kind);
+        }
+        Ok(video)
+    }
+
+    async fn generate_thumbnail(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let video = match self.job_video(&job).await? {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+
+        if video.thumbnail_url.is_some() && !job.force {
+            info!("Video ID {} already has a thumbnail, skipping", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+
+        // Sample frames across the video, score them and keep them all as candidates the owner
+        // can choose from, defaulting to the best
+        let duration = match media::probe_duration(&source).await? {
+            Some(duration) => Some(duration),
+            None => video.duration.map(f64::from),
+        };
+        let mut candidates: Vec<(String, f64, f64)> = Vec::new();
+        for (i, offset) in thumbnails::candidate_offsets(duration, thumbnails::candidate_count()).into_iter().enumerate() {
+            let gray = media::extract_gray_frame(&source, offset, thumbnails::SCORE_WIDTH, thumbnails::SCORE_HEIGHT).await?;
+            if gray.is_empty() {
+                continue;
+            }
+            let score = thumbnails::score_frame(&gray, thumbnails::SCORE_WIDTH, thumbnails::SCORE_HEIGHT);
+
+            let frame = work_dir.file(&format!("candidate-{}.jpg", i));
+            media::extract_frame(&source, offset, &frame).await?;
+            let key = format!("thumbnails/{}.jpg", uuid::Uuid::new_v4());
+            media::upload_file(self.storage.as_ref(), &job.bucket, &key, &frame, "image/jpeg").await?;
+            candidates.push((key, offset, score.score));
+        }
+
+        // Nothing decodable at the sampled offsets (e.g. a very short video); fall back to the first frame
+        if candidates.is_empty() {
+            let thumbnail = work_dir.file("thumbnail.jpg");
+            media::extract_thumbnail(&source, &thumbnail).await?;
+            let key = format!("thumbnails/{}.jpg", uuid::Uuid::new_v4());
+            media::upload_file(self.storage.as_ref(), &job.bucket, &key, &thumbnail, "image/jpeg").await?;
+            candidates.push((key, 0.0, 0.0));
+        }
+
+        let best = candidates.iter()
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(key, _, _)| key.clone())
+            .unwrap_or_default();
+        let old_candidates = thumbnails::replace_candidates(&self.db_pool, job.video_id, &candidates, &best).await?;
+        info!("Generated {} thumbnail candidates for video ID {}, selected {}", candidates.len(), job.video_id, best);
+
+        // Remove the thumbnails these replaced; external URLs are left alone
+        let old_keys = old_candidates.into_iter()
+            .chain(video.thumbnail_url.filter(|t| t.starts_with("thumbnails/")))
+            .filter(|key| !candidates.iter().any(|(new_key, _, _)| new_key == key))
+            .collect::<std::collections::HashSet<_>>();
+        for old_key in old_keys {
+            if let Err(e) = self.storage.delete(&job.bucket, &old_key).await {
+                warn!("Failed to delete old thumbnail {} for video ID {}: {:?}", old_key, job.video_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn transcode_renditions(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let video = match self.job_video(&job).await? {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+
+        let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM video_renditions WHERE video_id = $1")
+            .bind(job.video_id)
+            .fetch_all(&self.db_pool)
+            .await?;
+
+        // A forced transcode analyzes the source again; until a video has been analyzed it is
+        // due every configured rendition
+        let profile = match job.force {
+            true => None,
+            false => encoding_ladder::profile(&self.db_pool, job.video_id).await?,
+        };
+        let wanted = profile.as_ref().map_or_else(media::rendition_heights, |p| p.heights.clone());
+        if !job.force && wanted.iter().all(|height| existing.contains(&media::rendition_name(*height))) {
+            info!("Video ID {} already has all renditions, skipping", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+        // Renditions carry every audio track of the source, listed with the video, but lose any
+        // 360° metadata, which is kept for clients instead
+        let tracks = media::probe_audio_tracks(&source, job.video_id).await?;
+        audio_tracks::replace(&self.db_pool, job.video_id, &tracks).await?;
+        let detected = media::probe_spherical(&source).await?;
+        spherical::store_detected(&self.db_pool, job.video_id, detected.as_ref()).await?;
+
+        let (ladder, crf) = match profile {
+            Some(profile) => (profile.heights, profile.crf),
+            None if encoding_ladder::enabled() => {
+                let stats = encoding_ladder::analyze(&source).await?;
+                let complexity = encoding_ladder::classify(&stats);
+                let ladder = encoding_ladder::select_ladder(&media::rendition_heights(), stats.height, complexity);
+                encoding_ladder::save_profile(&self.db_pool, job.video_id, &stats, complexity, &ladder).await?;
+                info!(
+                    "Video ID {} is {} complexity (motion {:?}, {:?} bits per pixel), ladder {:?}",
+                    job.video_id, complexity.as_str(), stats.motion, stats.bits_per_pixel(), ladder
+                );
+                self.remove_renditions_outside(&job, &existing, &ladder).await?;
+                (ladder, complexity.crf())
+            }
+            None => {
+                let source_height = media::probe_height(&source).await?;
+                let ladder = encoding_ladder::select_ladder(&media::rendition_heights(), source_height, Complexity::Medium);
+                (ladder, Complexity::Medium.crf())
+            }
+        };
+        let pending: Vec<i32> = ladder
+            .into_iter()
+            .filter(|height| job.force || !existing.contains(&media::rendition_name(*height)))
+            .collect();
+
+        let watermark = match watermarks::for_video(&self.db_pool, &video).await? {
+            Some((key, settings)) => {
+                let image = work_dir.file("watermark.png");
+                download_object_parallel(self.storage.as_ref(), &job.bucket, &key, &image, ParallelDownloadConfig::from_env()).await?;
+                info!("Watermarking renditions of video ID {} with {}", job.video_id, key);
+                Some((image, settings))
+            }
+            None => None,
+        };
+
+        for height in pending {
+            let name = media::rendition_name(height);
+            let output = work_dir.file(&format!("{}.mp4", name));
+            media::transcode(&source, &output, height, crf, watermark.as_ref().map(|(image, settings)| (image.as_path(), settings))).await?;
+
+            let rendition_key = media::rendition_s3_key(job.video_id, &name);
+            media::upload_file(self.storage.as_ref(), &job.bucket, &rendition_key, &output, "video/mp4").await?;
+
+            sqlx::query(
+                "INSERT INTO video_renditions (video_id, name, height, s3_key, created_at)
+                 VALUES ($1, $2, $3, $4, NOW())
+                 ON CONFLICT (video_id, name) DO UPDATE SET height = EXCLUDED.height, s3_key = EXCLUDED.s3_key, created_at = EXCLUDED.created_at"
+            )
+            .bind(job.video_id)
+            .bind(&name)
+            .bind(height)
+            .bind(&rendition_key)
+            .execute(&self.db_pool)
+            .await?;
+            info!("Stored {} rendition for video ID {}", name, job.video_id);
+
+            // Free the space before the next rendition
+            let _ = tokio::fs::remove_file(&output).await;
+        }
+        Ok(())
+    }
+
+    // Drop renditions a new ladder no longer includes, e.g. the middle rungs of a video found to
+    // be simple content
+    async fn remove_renditions_outside(&self, job: &MediaJob, existing: &[String], ladder: &[i32]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let stale: Vec<&String> = existing
+            .iter()
+            .filter(|name| !ladder.iter().any(|height| media::rendition_name(*height) == **name))
+            .collect();
+        for name in stale {
+            let key: Option<String> = sqlx::query_scalar("DELETE FROM video_renditions WHERE video_id = $1 AND name = $2 RETURNING s3_key")
+                .bind(job.video_id)
+                .bind(name)
+                .fetch_optional(&self.db_pool)
+                .await?;
+            let Some(key) = key else {
+                continue;
+            };
+            if let Err(e) = self.storage.delete(&job.bucket, &key).await {
+                warn!("Failed to delete {} rendition {} for video ID {}: {:?}", name, key, job.video_id, e);
+            }
+            info!("Removed {} rendition for video ID {}, no longer in its ladder", name, job.video_id);
+        }
+        Ok(())
+    }
+
+    async fn transcribe(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let backend = match TranscriptionBackend::from_env() {
+            Some(backend) => backend,
+            None => {
+                warn!("Transcription is not configured, skipping transcription of video ID {}", job.video_id);
+                return Ok(());
+            }
+        };
+        if self.job_video(&job).await?.is_none() {
+            return Ok(());
+        }
+
+        if !job.force && transcription::get_transcript(&self.db_pool, job.video_id).await?.is_some() {
+            info!("Video ID {} already has a transcript, skipping", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        let audio = work_dir.file("audio.wav");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+        if !media::probe_has_audio(&source).await? {
+            info!("Video ID {} has no audio track, skipping transcription", job.video_id);
+            return Ok(());
+        }
+        media::extract_audio(&source, &audio).await?;
+
+        let (language, segments) = backend.transcribe(&audio).await?;
+        transcription::store_transcript(&self.db_pool, job.video_id, backend.name(), language.as_deref(), &segments).await?;
+        Ok(())
+    }
+
+    async fn analyze_loudness(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.job_video(&job).await?.is_none() {
+            return Ok(());
+        }
+        if !job.force && loudness::has_loudness(&self.db_pool, job.video_id).await? {
+            info!("Video ID {} already has loudness measurements, skipping", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+        let measured = if media::probe_has_audio(&source).await? {
+            media::measure_loudness(&source).await?
+        } else {
+            None
+        };
+        match measured {
+            Some(l) => info!("Video ID {} measures {:.1} LUFS", job.video_id, l.integrated_lufs),
+            None => info!("Video ID {} has no measurable audio", job.video_id),
+        }
+        loudness::store(&self.db_pool, job.video_id, measured).await?;
+        Ok(())
+    }
+
+    async fn detect_scenes(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let video = match self.job_video(&job).await? {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+
+        if !job.force && chapters::has_auto_chapters(&self.db_pool, job.video_id).await? {
+            info!("Video ID {} already has auto chapters, skipping scene detection", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+        if media::probe_height(&source).await?.is_none() {
+            info!("Video ID {} has no video stream, skipping scene detection", job.video_id);
+            return Ok(());
+        }
+
+        let starts = chapters::detect_chapter_starts(&source, video.duration.map(f64::from)).await?;
+        chapters::store_proposals(&self.db_pool, job.video_id, &starts).await?;
+        Ok(())
+    }
+
+    async fn moderate(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let classifier = match moderation::classifier_from_env() {
+            Some(classifier) => classifier,
+            None => {
+                warn!("Moderation is not configured, skipping moderation of video ID {}", job.video_id);
+                return Ok(());
+            }
+        };
+        let video = match self.job_video(&job).await? {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+        if video.moderation_status != moderation::STATUS_PENDING && !job.force {
+            info!("Video ID {} is already {}, skipping moderation", job.video_id, video.moderation_status);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+
+        let mut frames = Vec::new();
+        if media::probe_height(&source).await?.is_some() {
+            let duration = match media::probe_duration(&source).await? {
+                Some(duration) => Some(duration),
+                None => video.duration.map(f64::from),
+            };
+            for (i, offset) in thumbnails::candidate_offsets(duration, moderation::frame_count()).into_iter().enumerate() {
+                let frame = work_dir.file(&format!("frame-{}.jpg", i));
+                media::extract_frame(&source, offset, &frame).await?;
+                // Offsets past the last frame produce no image
+                if tokio::fs::metadata(&frame).await.is_ok() {
+                    frames.push((offset, frame));
+                }
+            }
+        }
+
+        let verdict = moderation::classify_frames(classifier.as_ref(), &frames).await?;
+        moderation::apply_verdict(&self.db_pool, job.video_id, &verdict).await?;
+        Ok(())
+    }
+
+    // Package the video as HLS and encrypt each segment, starting a new key every
+    // HLS_KEY_ROTATION_SEGMENTS segments
+    async fn package_hls(&self, job: MediaJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let video = match self.job_video(&job).await? {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+        if !video.hls_encrypted {
+            info!("Encryption was turned off for video ID {}, skipping HLS packaging", job.video_id);
+            return Ok(());
+        }
+        if !job.force && !hls::list_segments(&self.db_pool, job.video_id).await?.is_empty() {
+            info!("Video ID {} is already packaged as HLS, skipping", job.video_id);
+            return Ok(());
+        }
+
+        let work_dir = WorkDir::create().await?;
+        let source = work_dir.file("source");
+        download_object_parallel(self.storage.as_ref(), &job.bucket, &job.s3_key, &source, ParallelDownloadConfig::from_env()).await?;
+        let probed = media::probe_audio_tracks(&source, job.video_id).await?;
+        audio_tracks::replace(&self.db_pool, job.video_id, &probed).await?;
+        let tracks = audio_tracks::list(&self.db_pool, job.video_id).await?;
+
+        // A single audio track stays muxed into the video segments, which every player handles.
+        // With more, each gets its own playlist for players to switch between.
+        let alternate_audio = tracks.len() > 1;
+        let playlist = work_dir.file("index.m3u8");
+        let streams = if alternate_audio { HlsStreams::VideoOnly } else { HlsStreams::Muxed };
+        media::package_hls(&source, &playlist, hls::segment_secs(), streams).await?;
+
+        let prefix = format!("hls/{}/{}", job.video_id, uuid::Uuid::new_v4());
+        let mut keys = Vec::new();
+        let segments = self.store_encrypted_segments(&job, &work_dir, &playlist, &prefix, &mut keys).await?;
+        let mut audio_segments = Vec::new();
+        if alternate_audio {
+            for track in &tracks {
+                let playlist = work_dir.file(&format!("audio{}.m3u8", track.track_index));
+                match &track.s3_key {
+                    // Standalone tracks are packaged from their own file
+                    Some(track_key) => {
+                        let track_source = work_dir.file(&format!("audio{}", track.track_index));
+                        download_object_parallel(self.storage.as_ref(), &job.bucket, track_key, &track_source, ParallelDownloadConfig::from_env()).await?;
+                        media::package_hls(&track_source, &playlist, hls::segment_secs(), HlsStreams::Audio(0)).await?;
+                    }
+                    None => {
+                        media::package_hls(&source, &playlist, hls::segment_secs(), HlsStreams::Audio(track.track_index)).await?;
+                    }
+                }
+                let track_prefix = format!("{}/audio{}", prefix, track.track_index);
+                let track_segments = self.store_encrypted_segments(&job, &work_dir, &playlist, &track_prefix, &mut keys).await?;
+                audio_segments.extend(track_segments.into_iter().map(|segment| (track.track_index, segment)));
+            }
+        }
+
+        let old_keys = hls::replace_package(&self.db_pool, job.video_id, &keys, &segments, &audio_segments).await?;
+        info!(
+            "Packaged video ID {} as {} encrypted HLS segments and {} audio segments with {} keys",
+            job.video_id, segments.len(), audio_segments.len(), keys.len()
+        );
+
+        for old_key in old_keys {
+            if let Err(e) = self.storage.delete(&job.bucket, &old_key).await {
+                warn!("Failed to delete old HLS segment {} for video ID {}: {:?}", old_key, job.video_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    // Encrypt and upload the segments of a packaged playlist under `prefix`, adding keys as the
+    // rotation calls for them. Segments of every playlist with the same index share a key.
+    async fn store_encrypted_segments(
+        &self,
+        job: &MediaJob,
+        work_dir: &WorkDir,
+        playlist: &std::path::Path,
+        prefix: &str,
+        keys: &mut Vec<(i32, [u8; hls::KEY_BYTES])>,
+    ) -> Result<Vec<VideoHlsSegment>, Box<dyn std::error::Error + Send + Sync>> {
+        let rotation = hls::key_rotation_segments();
+        let mut segments = Vec::new();
+        let playlist_text = tokio::fs::read_to_string(playlist).await?;
+        for (segment_index, (duration, uri)) in hls::parse_media_playlist(&playlist_text).into_iter().enumerate() {
+            let segment_index = segment_index as i32;
+            let key_index = segment_index / rotation;
+            if keys.len() <= key_index as usize {
+                keys.push((key_index, hls::generate_key()?));
+            }
+
+            let plain = tokio::fs::read(work_dir.file(&uri)).await?;
+            let encrypted = hls::encrypt_segment(&keys[key_index as usize].1, segment_index, &plain)?;
+            let s3_key = format!("{}/{}.ts", prefix, segment_index);
+            self.storage.put(&job.bucket, &s3_key, encrypted.into(), "video/mp2t").await?;
+
+            segments.push(VideoHlsSegment { video_id: job.video_id, segment_index, duration, s3_key, key_index });
+        }
+        Ok(segments)
+    }
+
+    // Queue moderation for pending videos nobody queued it for, such as the scraper's. The job's
+    // row is claimed in the same statement, so replicas sweeping at once queue each video once.
+    pub async fn queue_unmoderated(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "WITH claimed AS (
+                 INSERT INTO video_processing_jobs (video_id, kind, state, updated_at)
+                 SELECT id, $1, $2, NOW() FROM videos WHERE moderation_status = $3 AND deleted_at IS NULL
+                 ON CONFLICT (video_id, kind) DO NOTHING
+                 RETURNING video_id
+             )
+             SELECT v.* FROM videos v JOIN claimed ON claimed.video_id = v.id ORDER BY v.id"
+        )
+        .bind(MediaJobKind::Moderate.as_str())
+        .bind(processing::JOB_QUEUED)
+        .bind(moderation::STATUS_PENDING)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let bucket = std::env::var("S3_BUCKET")
+            .or_else(|_| std::env::var("MINIO_BUCKET"))
+            .unwrap_or_else(|_| "videos".to_string());
+        for video in &videos {
+            if let Err(e) = self.enqueue(MediaJob::new(MediaJobKind::Moderate, video, &bucket)).await {
+                error!("Failed to enqueue moderation of video ID {}: {:?}", video.id, e);
+            }
+        }
+        Ok(videos.len())
+    }
+
+    // Sweep for unmoderated videos while moderation is on
+    pub async fn run_moderation_sweeper(&self) {
+        if !moderation::is_enabled() {
+            return;
+        }
+        info!("Starting moderation sweeper (interval: {} seconds)", MODERATION_SWEEP_INTERVAL.as_secs());
+        loop {
+            match self.queue_unmoderated().await {
+                Ok(0) => {}
+                Ok(n) => info!("Queued moderation of {} videos added without it", n),
+                Err(e) => error!("Error sweeping for unmoderated videos: {:?}", e),
+            }
+            sleep(MODERATION_SWEEP_INTERVAL).await;
+        }
+    }
+
     pub async fn queue_missing_durations(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         info!("Queuing duration extraction jobs for videos without duration");
         
         let videos = sqlx::query_as::<_, Video>(
-            "SELECT * FROM videos WHERE duration IS NULL ORDER BY id ASC"
+            "SELECT * FROM videos WHERE duration IS NULL AND deleted_at IS NULL ORDER BY id ASC"
         )
         .fetch_all(&self.db_pool)
         .await?;
@@ -247,35 +1097,23 @@ impl JobQueue {
             .unwrap_or_else(|_| "videos".to_string());
         
         for video in videos {
-            // Check if S3 object exists before enqueueing
-            match self.s3_client
-                .head_object()
-                .bucket(&bucket)
-                .key(&video.s3_key)
-                .send()
-                .await
-            {
+            // Check if the object exists before enqueueing
+            match self.storage.head(&bucket, &video.s3_key).await {
                 Ok(_) => {
                     // Object exists, enqueue the job
-                    let job = DurationExtractionJob {
-                        video_id: video.id,
-                        s3_key: video.s3_key.clone(),
-                        bucket: bucket.clone(),
-                    };
+                    let job = MediaJob::new(MediaJobKind::Duration, &video, &bucket);
                     
-                    if let Err(e) = self.enqueue_duration_extraction(job).await {
+                    if let Err(e) = self.enqueue(job).await {
                         error!("Failed to enqueue job for video ID {}: {:?}", video.id, e);
                     }
                 },
+                Err(e) if e.is_not_found() => {
+                    warn!("Object {} does not exist for video ID {}, skipping job enqueueing", video.s3_key, video.id);
+                    continue;
+                }
                 Err(e) => {
-                    // Check if it's a 404 error (NoSuchKey) by examining the error string
-                    let error_string = format!("{:?}", e);
-                    if error_string.contains("NoSuchKey") || error_string.contains("404") {
-                        warn!("S3 object {} does not exist for video ID {}, skipping job enqueueing", video.s3_key, video.id);
-                        continue;
-                    }
                     // For other errors, log and continue
-                    error!("Failed to check S3 object existence for video ID {}: {:?}", video.id, e);
+                    error!("Failed to check object existence for video ID {}: {:?}", video.id, e);
                 }
             }
         }
@@ -0,0 +1,219 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use openssl::pkey::{Id, PKey, Private, Public};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::ApiError;
+
+// The keys login tokens are signed and verified with. One key signs; every key in the set
+// verifies, so rotating in a new signing key leaves tokens issued with the old one valid until
+// they expire. Each token names its key in the `kid` header; tokens from before kids were
+// issued are tried against every key of their algorithm.
+//
+// JWT_ALGORITHM is HS256 (default), RS256 or EdDSA.
+// - HS256 signs with JWT_SECRET; JWT_PREVIOUS_SECRETS (comma separated) still verify.
+// - RS256 and EdDSA read every <kid>.pem in JWT_KEY_DIR. JWT_ACTIVE_KID (default: the last kid
+//   in sorted order) signs and must be a private key; the others may be public keys kept to
+//   verify tokens signed before a rotation. JWT_SECRET and JWT_PREVIOUS_SECRETS, if set, still
+//   verify HS256 tokens so sessions survive switching algorithms; unset them once those expire.
+
+pub struct VerifyingKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    key: DecodingKey,
+    jwk: Option<Value>, // public keys only; HMAC secrets are never published
+}
+
+pub struct KeySet {
+    signing_kid: String,
+    signing_algorithm: Algorithm,
+    signing: EncodingKey,
+    verifying: Vec<VerifyingKey>,
+}
+
+pub fn parse_algorithm(name: &str) -> Result<Algorithm, String> {
+    match name.to_ascii_uppercase().as_str() {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "EDDSA" => Ok(Algorithm::EdDSA),
+        _ => Err(format!("Unsupported JWT_ALGORITHM {}, expected HS256, RS256 or EdDSA", name)),
+    }
+}
+
+// Derived from the secret, so the kid stays the same across restarts without revealing it
+fn hmac_kid(secret: &str) -> String {
+    format!("hs-{}", hex::encode(&Sha256::digest(secret.as_bytes())[..4]))
+}
+
+fn hmac_keys(secrets: &[String]) -> Vec<VerifyingKey> {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .map(|secret| VerifyingKey {
+            kid: hmac_kid(secret),
+            algorithm: Algorithm::HS256,
+            key: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+        })
+        .collect()
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// The verifying half of a PEM key, private or public, with its JWK
+fn public_key(kid: &str, algorithm: Algorithm, pem: &[u8]) -> Result<VerifyingKey, String> {
+    let pkey: PKey<Public> = match PKey::<Private>::private_key_from_pem(pem) {
+        Ok(private) => PKey::public_key_from_der(&private.public_key_to_der().map_err(|e| e.to_string())?),
+        Err(_) => PKey::public_key_from_pem(pem),
+    }
+    .map_err(|e| format!("Key {} is not a PEM private or public key: {}", kid, e))?;
+
+    let (key, jwk) = match (algorithm, pkey.id()) {
+        (Algorithm::RS256, Id::RSA) => {
+            let rsa = pkey.rsa().map_err(|e| e.to_string())?;
+            let (n, e) = (base64url(&rsa.n().to_vec()), base64url(&rsa.e().to_vec()));
+            let key = DecodingKey::from_rsa_components(&n, &e).map_err(|e| e.to_string())?;
+            (key, json!({ "kty": "RSA", "kid": kid, "alg": "RS256", "use": "sig", "n": n, "e": e }))
+        }
+        (Algorithm::EdDSA, Id::ED25519) => {
+            let x = base64url(&pkey.raw_public_key().map_err(|e| e.to_string())?);
+            let key = DecodingKey::from_ed_components(&x).map_err(|e| e.to_string())?;
+            (key, json!({ "kty": "OKP", "crv": "Ed25519", "kid": kid, "alg": "EdDSA", "use": "sig", "x": x }))
+        }
+        _ => return Err(format!("Key {} is not a {:?} key", kid, algorithm)),
+    };
+    Ok(VerifyingKey { kid: kid.to_string(), algorithm, key, jwk: Some(jwk) })
+}
+
+impl KeySet {
+    // HS256 signing with `secret`; `previous` secrets still verify
+    pub fn hmac(secret: &str, previous: &[String]) -> Result<KeySet, String> {
+        if secret.is_empty() {
+            return Err("JWT_SECRET is empty".to_string());
+        }
+        let mut verifying = hmac_keys(&[secret.to_string()]);
+        verifying.extend(hmac_keys(previous));
+        Ok(KeySet {
+            signing_kid: hmac_kid(secret),
+            signing_algorithm: Algorithm::HS256,
+            signing: EncodingKey::from_secret(secret.as_bytes()),
+            verifying,
+        })
+    }
+
+    // RS256 or EdDSA signing with the `active_kid` key of `pems` (kid, PEM). `legacy_secrets`
+    // verify HS256 tokens issued before switching algorithms.
+    pub fn asymmetric(algorithm: Algorithm, pems: &[(String, Vec<u8>)], active_kid: &str, legacy_secrets: &[String]) -> Result<KeySet, String> {
+        let active_pem = &pems
+            .iter()
+            .find(|(kid, _)| kid == active_kid)
+            .ok_or_else(|| format!("No key {} to sign with", active_kid))?
+            .1;
+        // jsonwebtoken only finds out when signing that it was given a public key
+        if PKey::private_key_from_pem(active_pem).is_err() {
+            return Err(format!("Key {} can't sign tokens, it is not a PEM private key", active_kid));
+        }
+        let signing = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(active_pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(active_pem),
+            _ => return Err(format!("{:?} is not an asymmetric algorithm", algorithm)),
+        }
+        .map_err(|e| format!("Key {} can't sign {:?} tokens: {}", active_kid, algorithm, e))?;
+
+        let mut verifying = pems
+            .iter()
+            .map(|(kid, pem)| public_key(kid, algorithm, pem))
+            .collect::<Result<Vec<_>, _>>()?;
+        verifying.extend(hmac_keys(legacy_secrets));
+        Ok(KeySet { signing_kid: active_kid.to_string(), signing_algorithm: algorithm, signing, verifying })
+    }
+
+    pub fn from_env() -> Result<KeySet, String> {
+        let algorithm = parse_algorithm(&env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()))?;
+        let previous: Vec<String> = env::var("JWT_PREVIOUS_SECRETS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        if algorithm == Algorithm::HS256 {
+            let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET is not set".to_string())?;
+            return KeySet::hmac(&secret, &previous);
+        }
+
+        let dir = env::var("JWT_KEY_DIR").map_err(|_| format!("JWT_KEY_DIR is required for {:?}", algorithm))?;
+        let pems = read_pems(Path::new(&dir))?;
+        let active_kid = match env::var("JWT_ACTIVE_KID") {
+            Ok(kid) => kid,
+            Err(_) => pems.last().map(|(kid, _)| kid.clone()).ok_or_else(|| format!("No .pem keys in {}", dir))?,
+        };
+        let legacy: Vec<String> = env::var("JWT_SECRET").into_iter().chain(previous).collect();
+        KeySet::asymmetric(algorithm, &pems, &active_kid, &legacy)
+    }
+
+    pub fn signing_kid(&self) -> &str {
+        &self.signing_kid
+    }
+
+    pub fn issue<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.signing_algorithm);
+        header.kid = Some(self.signing_kid.clone());
+        jsonwebtoken::encode(&header, claims, &self.signing)
+    }
+
+    // The claims of a token signed by any key in the set, if it is valid and unexpired
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Option<T> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        self.verifying
+            .iter()
+            .filter(|k| k.algorithm == header.alg && header.kid.as_deref().is_none_or(|kid| kid == k.kid))
+            .find_map(|k| jsonwebtoken::decode::<T>(token, &k.key, &Validation::new(k.algorithm)).ok())
+            .map(|decoded| decoded.claims)
+    }
+
+    // JSON Web Key Set of the public keys, for other services to verify tokens with
+    pub fn jwks(&self) -> Value {
+        json!({ "keys": self.verifying.iter().filter_map(|k| k.jwk.clone()).collect::<Vec<_>>() })
+    }
+}
+
+// (kid, PEM) of each .pem file in `dir`, sorted by kid
+fn read_pems(dir: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read JWT_KEY_DIR {}: {}", dir.display(), e))?;
+    let mut pems = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("pem") {
+            continue;
+        }
+        let Some(kid) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let pem = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        pems.push((kid.to_string(), pem));
+    }
+    pems.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(pems)
+}
+
+static KEYS: LazyLock<Result<KeySet, String>> = LazyLock::new(KeySet::from_env);
+
+// The configured key set. Checked at startup, so this only fails in tools and tests.
+pub fn keys() -> Result<&'static KeySet, ApiError> {
+    KEYS.as_ref().map_err(|e| ApiError::Internal(format!("JWT keys are not configured: {}", e)))
+}
+
+// Why the keys couldn't be loaded, for startup to report instead of a generic API error
+pub fn configuration_error() -> Option<String> {
+    KEYS.as_ref().err().map(|e| format!("JWT keys are not configured: {}", e))
+}
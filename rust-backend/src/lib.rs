@@ -5,20 +5,92 @@ pub mod models;
 pub mod handlers;
 pub mod websocket;
 pub mod services;
+pub mod storage;
 pub mod redis_service;
+pub mod request_metrics;
 pub mod video_utils;
 pub mod job_queue;
+pub mod job_history;
+pub mod media;
+pub mod encoding_ladder;
+pub mod notifications;
+pub mod saved_searches;
+pub mod search;
+pub mod search_index;
+pub mod error;
+pub mod trash;
+pub mod storage_tiering;
+pub mod uploads;
+pub mod watch_parties;
+pub mod categories;
+pub mod watch_party_polls;
+pub mod premieres;
+pub mod client_queue;
+pub mod counters;
+pub mod comment_replay;
+pub mod comment_export;
+pub mod compilations;
+pub mod sse;
+pub mod webhooks;
+pub mod web_push;
+pub mod transcription;
+pub mod chapters;
+pub mod clips;
+pub mod frames;
+pub mod thumbnails;
+pub mod moderation;
+pub mod processing;
+pub mod hls;
+pub mod hotlinks;
+pub mod audio_tracks;
+pub mod bandwidth;
+pub mod playback_tokens;
+pub mod spherical;
+pub mod loudness;
+pub mod restrictions;
+pub mod downloads;
+pub mod oembed;
+pub mod embed;
+pub mod feeds;
+pub mod changes;
+pub mod sitemap;
+pub mod translations;
+pub mod sessions;
+pub mod trusted_proxies;
+pub mod auth_cookies;
+pub mod field_case;
+pub mod api_versions;
+pub mod audit;
+pub mod login_throttle;
+pub mod channels;
+pub mod exports;
+pub mod recommendations;
+pub mod experiments;
+pub mod imports;
+pub mod watch_folder;
+pub mod s3_events;
+pub mod jwt_keys;
+pub mod maintenance;
+pub mod feature_flags;
+pub mod migration_status;
+pub mod circuit_breaker;
+pub mod signed_urls;
+pub mod user_settings;
+pub mod watermarks;
+pub mod ws_stats;
+pub mod telemetry;
 
 use sqlx::PgPool;
-use aws_sdk_s3::Client;
 use crate::job_queue::JobQueue;
+use crate::client_queue::ClientSender;
+use crate::storage::Storage;
 use std::sync::Arc;
 
 pub struct AppState {
     pub db_pool: PgPool,
-    pub s3_client: Client,
+    pub storage: Storage,
     pub redis_client: Option<redis::Client>,
     pub job_queue: Option<Arc<JobQueue>>,
-    pub video_clients: StdMutex<HashMap<i32, Vec<tokio::sync::mpsc::Sender<String>>>>,
-    pub watchparty_clients: StdMutex<HashMap<i32, Vec<tokio::sync::mpsc::Sender<String>>>>,
+    pub video_clients: StdMutex<HashMap<i32, Vec<ClientSender>>>,
+    pub watchparty_clients: StdMutex<HashMap<i32, Vec<ClientSender>>>,
 }
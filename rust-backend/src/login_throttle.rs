@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use log::{error, warn};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+
+use crate::circuit_breaker;
+use crate::audit;
+use crate::error::ApiError;
+
+// How many failures a subject gets before each further attempt has to wait, and how many
+// before it is locked out. An IP can fail more often than one account; it may be shared.
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    pub name: &'static str,
+    pub free_attempts: u64,
+    pub lockout_threshold: u64,
+}
+
+pub const ACCOUNT_POLICY: Policy = Policy { name: "account", free_attempts: 3, lockout_threshold: 10 };
+pub const IP_POLICY: Policy = Policy { name: "ip", free_attempts: 10, lockout_threshold: 50 };
+
+// Longest progressive delay before the lockout proper kicks in
+const MAX_DELAY_SECS: u64 = 60;
+
+// Failures are forgotten after this long without another one
+const FAILURE_WINDOW_SECS: u64 = 15 * 60;
+
+// Lockout length when LOGIN_LOCKOUT_SECS is not set
+const DEFAULT_LOCKOUT_SECS: u64 = 15 * 60;
+
+pub fn lockout_duration() -> u64 {
+    env::var("LOGIN_LOCKOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(DEFAULT_LOCKOUT_SECS)
+}
+
+// Seconds the subject must wait after its `failures`th failure: nothing at first, then
+// doubling from 1s up to MAX_DELAY_SECS, then the full lockout from the threshold on
+pub fn block_secs(policy: Policy, failures: u64, lockout_secs: u64) -> u64 {
+    if failures >= policy.lockout_threshold {
+        lockout_secs
+    } else if failures <= policy.free_attempts {
+        0
+    } else {
+        (1u64 << (failures - policy.free_attempts - 1).min(16)).min(MAX_DELAY_SECS)
+    }
+}
+
+fn subjects(email: &str, ip: Option<&str>) -> Vec<(Policy, String)> {
+    let mut subjects = vec![(ACCOUNT_POLICY, format!("account:{}", email.trim().to_lowercase()))];
+    if let Some(ip) = ip {
+        subjects.push((IP_POLICY, format!("ip:{}", ip)));
+    }
+    subjects
+}
+
+fn failures_key(subject: &str) -> String {
+    format!("login_failures:{}", subject)
+}
+
+fn blocked_key(subject: &str) -> String {
+    format!("login_blocked:{}", subject)
+}
+
+// Stand-in for Redis when it isn't configured or reachable, so a single instance is still
+// protected
+struct LocalCounter {
+    failures: u64,
+    forget_at: Instant,
+    blocked_until: Option<Instant>,
+}
+
+static LOCAL_COUNTERS: LazyLock<std::sync::Mutex<HashMap<String, LocalCounter>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn local_blocked_secs(subject: &str) -> u64 {
+    let now = Instant::now();
+    let counters = LOCAL_COUNTERS.lock().unwrap();
+    counters
+        .get(subject)
+        .and_then(|c| c.blocked_until)
+        .filter(|until| *until > now)
+        .map_or(0, |until| (until - now).as_secs_f64().ceil() as u64)
+}
+
+fn local_record_failure(subject: &str, policy: Policy, lockout_secs: u64) -> (u64, u64) {
+    let now = Instant::now();
+    let mut counters = LOCAL_COUNTERS.lock().unwrap();
+    counters.retain(|_, c| c.forget_at > now || c.blocked_until.is_some_and(|until| until > now));
+
+    let counter = counters.entry(subject.to_string()).or_insert(LocalCounter { failures: 0, forget_at: now, blocked_until: None });
+    counter.failures += 1;
+    counter.forget_at = now + Duration::from_secs(FAILURE_WINDOW_SECS);
+    let block = block_secs(policy, counter.failures, lockout_secs);
+    if block > 0 {
+        counter.blocked_until = Some(now + Duration::from_secs(block));
+    }
+    (counter.failures, block)
+}
+
+fn local_clear(subject: &str) {
+    LOCAL_COUNTERS.lock().unwrap().remove(subject);
+}
+
+async fn redis_blocked_secs(redis_client: &redis::Client, subject: &str) -> redis::RedisResult<u64> {
+    let ttl: i64 = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.ttl(blocked_key(subject)).await
+    })
+    .await?;
+    Ok(ttl.max(0) as u64)
+}
+
+async fn redis_record_failure(redis_client: &redis::Client, subject: &str, policy: Policy, lockout_secs: u64) -> redis::RedisResult<(u64, u64)> {
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        let (failures,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(failures_key(subject), 1)
+            .expire(failures_key(subject), FAILURE_WINDOW_SECS as usize)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        let block = block_secs(policy, failures, lockout_secs);
+        if block > 0 {
+            conn.set_ex::<_, _, ()>(blocked_key(subject), 1, block as usize).await?;
+        }
+        Ok((failures, block))
+    })
+    .await
+}
+
+async fn redis_clear(redis_client: &redis::Client, subject: &str) -> redis::RedisResult<()> {
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.del::<_, ()>(&[failures_key(subject), blocked_key(subject)]).await
+    })
+    .await
+}
+
+async fn blocked_secs(redis_client: Option<&redis::Client>, subject: &str) -> u64 {
+    if let Some(redis_client) = redis_client {
+        match redis_blocked_secs(redis_client, subject).await {
+            Ok(secs) => return secs,
+            Err(e) => error!("Login throttle unavailable in Redis, using local counters: {:?}", e),
+        }
+    }
+    local_blocked_secs(subject)
+}
+
+// Refuse the attempt if the account or the client's IP is waiting out a delay or lockout
+pub async fn check(redis_client: Option<&redis::Client>, email: &str, ip: Option<&str>) -> Result<(), ApiError> {
+    let mut wait = 0;
+    for (_, subject) in subjects(email, ip) {
+        wait = wait.max(blocked_secs(redis_client, &subject).await);
+    }
+    if wait > 0 {
+        return Err(ApiError::TooManyRequests {
+            message: format!("Too many failed login attempts. Try again in {} seconds.", wait),
+            retry_after_secs: wait,
+        });
+    }
+    Ok(())
+}
+
+// Count a failed attempt against the account and IP, and audit-log any lockout it causes
+pub async fn record_failure(
+    db_pool: &PgPool,
+    redis_client: Option<&redis::Client>,
+    email: &str,
+    ip: Option<&str>,
+    user_id: Option<i32>,
+) {
+    let lockout_secs = lockout_duration();
+    for (policy, subject) in subjects(email, ip) {
+        let recorded = match redis_client {
+            Some(redis_client) => match redis_record_failure(redis_client, &subject, policy, lockout_secs).await {
+                Ok(recorded) => recorded,
+                Err(e) => {
+                    error!("Login throttle unavailable in Redis, using local counters: {:?}", e);
+                    local_record_failure(&subject, policy, lockout_secs)
+                }
+            },
+            None => local_record_failure(&subject, policy, lockout_secs),
+        };
+
+        let (failures, block) = recorded;
+        if failures == policy.lockout_threshold {
+            warn!("Locked out login {} after {} failures for {}s", subject, failures, block);
+            let details = serde_json::json!({
+                "scope": policy.name,
+                "email": email.trim().to_lowercase(),
+                "failures": failures,
+                "lockoutSecs": block
+            });
+            let audited_user = if policy.name == ACCOUNT_POLICY.name { user_id } else { None };
+            if let Err(e) = audit::record(db_pool, audit::EVENT_LOGIN_LOCKOUT, audited_user, ip, details).await {
+                error!("Failed to audit-log lockout of {}: {:?}", subject, e);
+            }
+        }
+    }
+}
+
+// A successful login clears the account's failures. The IP's are kept, so one valid account
+// can't be used to reset the count while guessing at others.
+pub async fn record_success(redis_client: Option<&redis::Client>, email: &str) {
+    let subject = format!("account:{}", email.trim().to_lowercase());
+    if let Some(redis_client) = redis_client {
+        if let Err(e) = redis_clear(redis_client, &subject).await {
+            error!("Failed to clear login failures for {}: {:?}", subject, e);
+        }
+    }
+    local_clear(&subject);
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use sqlx::PgPool;
+
+// Integrated loudness (EBU R128) of each video's audio, measured with ffmpeg's loudnorm filter
+// and stored on the video. Players bring videos to a common level by applying their target
+// minus loudness_lufs as gain, kept below 0 dBTP by the true peak.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    pub integrated_lufs: f64,
+    pub range_lu: Option<f64>,
+    pub true_peak_dbtp: Option<f64>,
+}
+
+// The measurements loudnorm prints as JSON at the end of its output when run with
+// print_format=json. None when there are none, or the audio is silent throughout, which
+// loudnorm reports as -inf.
+pub fn parse_loudnorm_output(stderr: &str) -> Option<Loudness> {
+    let start = stderr.rfind('{')?;
+    let end = start + stderr[start..].find('}')?;
+    let values: HashMap<String, String> = serde_json::from_str(&stderr[start..=end]).ok()?;
+    let value = |name: &str| values.get(name)?.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+    Some(Loudness {
+        integrated_lufs: value("input_i")?,
+        range_lu: value("input_lra"),
+        true_peak_dbtp: value("input_tp"),
+    })
+}
+
+pub async fn has_loudness(db_pool: &PgPool, video_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT loudness_lufs IS NOT NULL FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await
+        .map(|has| has.unwrap_or(false))
+}
+
+// Store the video's measurements, clearing them when it has no measurable audio
+pub async fn store(db_pool: &PgPool, video_id: i32, loudness: Option<Loudness>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE videos SET loudness_lufs = $2, loudness_range_lu = $3, true_peak_dbtp = $4 WHERE id = $1")
+        .bind(video_id)
+        .bind(loudness.map(|l| l.integrated_lufs))
+        .bind(loudness.and_then(|l| l.range_lu))
+        .bind(loudness.and_then(|l| l.true_peak_dbtp))
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
@@ -1,15 +1,15 @@
-use actix_web::{web, App, HttpServer, http};
+use actix_web::{web, App, HttpServer, http, middleware};
 use actix_cors::Cors;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use std::collections::HashMap;
 use tokio::sync::Mutex;
 use std::sync::Arc;
 use log::{info, error};
-use env_logger;
 use std::env;
 
 // Import from the crate root
-use video_streaming_backend::{AppState, job_queue, handlers, websocket, services};
+use video_streaming_backend::{AppState, auth_cookies, compilations, api_versions, counters, feature_flags, imports, job_queue, jwt_keys, handlers, hotlinks, recommendations, maintenance, migration_status, request_metrics, s3_events, websocket, services, saved_searches, search_index, sessions, signed_urls, sitemap, storage, storage_tiering, telemetry, trash, uploads, watch_folder, watch_parties, web_push, webhooks};
 
 async fn run_migrations() -> Result<(), sqlx::Error> {
     let database_url = std::env::var("DATABASE_URL")
@@ -18,39 +18,250 @@ async fn run_migrations() -> Result<(), sqlx::Error> {
     let pool = sqlx::postgres::PgPool::connect(&database_url).await?;
     
     info!("Connected to database, running migrations...");
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    migration_status::MIGRATOR.run(&pool).await?;
     
     pool.close().await;
     Ok(())
 }
 
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Same as the migrate subcommand, for existing deployment scripts
+    #[arg(long, hide = true)]
+    migrate: bool,
+
+    /// Same as the worker subcommand
+    #[arg(long)]
+    worker: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the HTTP and WebSocket servers (the default)
+    Serve,
+    /// Run only the job processors, without the HTTP and WebSocket servers
+    Worker,
+    /// Apply pending database migrations
+    Migrate,
+    /// Create an admin account, or make an existing user an admin
+    CreateAdmin {
+        username: String,
+        /// Required when the account doesn't exist yet
+        #[arg(long)]
+        email: Option<String>,
+        /// Read from stdin when not given and the account doesn't exist yet
+        #[arg(long, env = "ADMIN_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Rebuild the database indexes and statistics search relies on
+    ReindexSearch,
+    /// Queue processing again for videos without a duration or thumbnail
+    RequeueJobs {
+        /// Only this video, whether or not its processing finished
+        #[arg(long)]
+        video: Option<i32>,
+        /// Replace existing results instead of filling in missing ones
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check that every video's files exist in S3
+    VerifyS3,
+    /// Create videos for files already in the bucket
+    ImportS3 {
+        /// Defaults to IMPORT_S3_PREFIX, or videos/
+        prefix: Option<String>,
+        /// List what would be imported without creating anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+type CommandResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+fn bucket_name() -> String {
+    env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string())
+}
+
+async fn create_admin(username: &str, email: Option<&str>, password: Option<String>) -> CommandResult {
+    let db_pool = services::init_db_pool().await;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE LOWER(username) = LOWER($1))")
+        .bind(username)
+        .fetch_one(&db_pool)
+        .await?;
+    let password = match password {
+        Some(password) => Some(password),
+        None if !exists => {
+            eprint!("Password for {}: ", username);
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            Some(line.trim_end_matches(['\r', '\n']).to_string()).filter(|p| !p.is_empty())
+        }
+        None => None,
+    };
+
+    match maintenance::create_admin(&db_pool, username, email, password.as_deref()).await? {
+        maintenance::AdminAccount::Created(id) => println!("Created admin {} (user {})", username, id),
+        maintenance::AdminAccount::Promoted(id) => println!("{} (user {}) is now an admin", username, id),
+    }
+    Ok(())
+}
+
+async fn requeue_jobs(video: Option<i32>, force: bool) -> CommandResult {
+    let db_pool = services::init_db_pool().await;
+    let storage = storage::init_storage().await;
+    let redis_client = video_streaming_backend::redis_service::init_redis_client()?;
+    let job_queue = job_queue::JobQueue::new(redis_client, db_pool.clone(), storage.clone());
+
+    let queued = maintenance::requeue_jobs(&db_pool, storage.as_ref(), &job_queue, &bucket_name(), video, force).await?;
+    println!("Queued processing for {} videos", queued);
+    Ok(())
+}
+
+async fn verify_s3() -> CommandResult {
+    let db_pool = services::init_db_pool().await;
+    let storage = storage::init_storage().await;
+
+    let missing = maintenance::verify_s3(&db_pool, storage.as_ref(), &bucket_name()).await?;
+    for object in &missing {
+        println!("video {} ({}): {} is missing", object.video_id, object.kind, object.s3_key);
+    }
+    if !missing.is_empty() {
+        return Err(format!("{} objects are missing from S3", missing.len()).into());
+    }
+    println!("All video files are present in S3");
+    Ok(())
+}
+
+// Processing jobs are queued when Redis is reachable
+async fn import_s3(prefix: Option<String>, dry_run: bool) -> CommandResult {
+    let prefix = prefix.unwrap_or_else(imports::import_prefix);
+    let db_pool = services::init_db_pool().await;
+    let storage = storage::init_storage().await;
+    let job_queue = match video_streaming_backend::redis_service::init_redis_client() {
+        Ok(client) => Some(job_queue::JobQueue::new(client, db_pool.clone(), storage.clone())),
+        Err(e) => {
+            error!("Redis is unavailable, imported videos will not be processed: {:?}", e);
+            None
+        }
+    };
+
+    let report = imports::import_objects(&db_pool, storage.as_ref(), job_queue.as_ref(), &bucket_name(), &prefix, None, dry_run)
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+// Processes media jobs and compilations alongside any other workers and web processes sharing the
+// database and Redis. Unlike the web process, a worker can't do anything without Redis, so it
+// fails instead of retrying in the background.
+async fn worker() -> CommandResult {
+    let db_pool = services::init_db_pool().await;
+    migration_status::check_at_startup(&db_pool).await?;
+    // Webhook payloads carry signed media URLs
+    signed_urls::signing_secret()?;
+    let storage = storage::init_storage().await;
+    let redis_client = video_streaming_backend::redis_service::init_redis_client()?;
+    let job_queue = job_queue::JobQueue::new(redis_client, db_pool.clone(), storage.clone());
+    info!("Starting worker {}", job_queue.worker_id());
+
+    tokio::join!(
+        job_queue.process_media_jobs(),
+        compilations::run_compilation_worker(db_pool, storage, Some(job_queue.clone())),
+    );
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    env_logger::init();
-    
-    // Check for migration flag
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 && args[1] == "--migrate" {
-        info!("Running database migrations...");
-        if let Err(e) = run_migrations().await {
-            error!("Migration failed: {:?}", e);
-            std::process::exit(1);
+    request_metrics::init_logger();
+
+    let cli = Cli::parse();
+    let command = match cli.command {
+        Some(command) => command,
+        None if cli.migrate => Command::Migrate,
+        None if cli.worker => Command::Worker,
+        None => Command::Serve,
+    };
+
+    let (name, result): (&str, CommandResult) = match command {
+        Command::Serve => return serve().await,
+        Command::Worker => ("Worker", worker().await),
+        Command::Migrate => {
+            info!("Running database migrations...");
+            let result = run_migrations().await.map_err(Into::into);
+            if result.is_ok() {
+                info!("Migrations completed successfully!");
+            }
+            ("Migration", result)
+        }
+        Command::CreateAdmin { username, email, password } => ("create-admin", create_admin(&username, email.as_deref(), password).await),
+        Command::ReindexSearch => {
+            let db_pool = services::init_db_pool().await;
+            ("reindex-search", maintenance::reindex_search(&db_pool).await)
         }
-        info!("Migrations completed successfully!");
-        return Ok(());
+        Command::RequeueJobs { video, force } => ("requeue-jobs", requeue_jobs(video, force).await),
+        Command::VerifyS3 => ("verify-s3", verify_s3().await),
+        Command::ImportS3 { prefix, dry_run } => ("import-s3", import_s3(prefix, dry_run).await),
+    };
+    if let Err(e) = result {
+        error!("{} failed: {}", name, e);
+        std::process::exit(1);
     }
+    Ok(())
+}
+
+// Every key the web process signs with: login tokens, media URLs and playback tokens, and CSRF
+// tokens in cookie mode. None has a default, and all are checked so each missing one is reported.
+fn secret_problems() -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(e) = jwt_keys::configuration_error() {
+        problems.push(e);
+    }
+    if let Err(e) = signed_urls::signing_secret() {
+        problems.push(e);
+    }
+    if auth_cookies::cookie_mode() {
+        if let Err(e) = auth_cookies::csrf_secret() {
+            problems.push(e);
+        }
+    }
+    problems
+}
+
+async fn serve() -> std::io::Result<()> {
     let db_pool = services::init_db_pool().await;
-    let s3_client = services::init_s3_client().await;
+
+    // Don't serve against a schema this build doesn't match (see MIGRATION_CHECK)
+    if let Err(e) = migration_status::check_at_startup(&db_pool).await {
+        error!("Refusing to start: {}", e);
+        std::process::exit(1);
+    }
+    let problems = secret_problems();
+    if !problems.is_empty() {
+        error!("Refusing to start: {}", problems.join("; "));
+        std::process::exit(1);
+    }
+    if let Ok(keys) = jwt_keys::keys() {
+        info!("Signing login tokens with key {}", keys.signing_kid());
+    }
+    let storage = storage::init_storage().await;
     
     // Ensure the videos bucket exists
-    services::ensure_bucket_exists(&s3_client).await;
+    services::ensure_bucket_exists(storage.as_ref()).await;
     
     // Initialize Redis client and job queue with retry logic
     let (redis_client, job_queue) = match video_streaming_backend::redis_service::init_redis_client() {
         Ok(client) => {
             info!("Successfully connected to Redis");
-            let job_queue = job_queue::JobQueue::new(client.clone(), db_pool.clone(), s3_client.clone());
+            let job_queue = job_queue::JobQueue::new(client.clone(), db_pool.clone(), storage.clone());
             (Some(client), Some(job_queue))
         },
         Err(e) => {
@@ -58,7 +269,7 @@ async fn main() -> std::io::Result<()> {
             
             // Start a background task to retry Redis connection
             let db_pool_clone = db_pool.clone();
-            let s3_client_clone = s3_client.clone();
+            let storage_clone = storage.clone();
             tokio::spawn(async move {
                 let mut retry_count = 0;
                 loop {
@@ -71,7 +282,7 @@ async fn main() -> std::io::Result<()> {
                             info!("Successfully connected to Redis after {} retries", retry_count);
                             
                             // Create job queue
-                            let job_queue = job_queue::JobQueue::new(client.clone(), db_pool_clone.clone(), s3_client_clone.clone());
+                            let job_queue = job_queue::JobQueue::new(client.clone(), db_pool_clone.clone(), storage_clone.clone());
                             
                             // Queue existing videos without duration
                             if let Err(e) = job_queue.queue_missing_durations().await {
@@ -79,12 +290,13 @@ async fn main() -> std::io::Result<()> {
                             }
                             
                             // Start background job processor
-                            let job_queue_processor = job_queue.clone();
-                            tokio::spawn(async move {
-                                job_queue_processor.process_duration_extraction_jobs().await;
-                            });
-                            
-                            info!("Started background job processor for duration extraction after Redis reconnection");
+                            if job_queue::embedded_workers_enabled() {
+                                let job_queue_processor = job_queue.clone();
+                                tokio::spawn(async move {
+                                    job_queue_processor.process_media_jobs().await;
+                                });
+                                info!("Started background media job processor after Redis reconnection");
+                            }
                             break;
                         },
                         Err(e) => {
@@ -100,9 +312,127 @@ async fn main() -> std::io::Result<()> {
         }
     };
     
+    // Start the saved search notifier
+    let saved_search_db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        saved_searches::run_saved_search_notifier(saved_search_db_pool).await;
+    });
+
+    // Start the trash purger
+    let trash_db_pool = db_pool.clone();
+    let trash_storage = storage.clone();
+    tokio::spawn(async move {
+        trash::run_trash_purger(trash_db_pool, trash_storage).await;
+    });
+
+    // Start storage tiering
+    let tiering_db_pool = db_pool.clone();
+    let tiering_storage = storage.clone();
+    tokio::spawn(async move {
+        storage_tiering::run_storage_tiering(tiering_db_pool, tiering_storage).await;
+    });
+
+    // Start the expired upload cleanup
+    let upload_db_pool = db_pool.clone();
+    let upload_storage = storage.clone();
+    tokio::spawn(async move {
+        uploads::run_upload_cleanup(upload_db_pool, upload_storage).await;
+    });
+
+    // Start the playback telemetry cleanup
+    let telemetry_db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        telemetry::run_telemetry_cleanup(telemetry_db_pool).await;
+    });
+
+    // Start the webhook dispatcher
+    let webhook_db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        webhooks::run_webhook_dispatcher(webhook_db_pool).await;
+    });
+
+    // Start sending notifications through Web Push, if it's configured
+    let push_db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        web_push::run_push_dispatcher(push_db_pool).await;
+    });
+
+    // Start syncing the external search index, if there is one
+    if let Some(backend) = search_index::backend_from_env() {
+        let search_db_pool = db_pool.clone();
+        tokio::spawn(async move {
+            search_index::run_search_indexer(search_db_pool, backend).await;
+        });
+    }
+
+    // Start the recommendation training data exporter
+    let recommendation_db_pool = db_pool.clone();
+    let recommendation_storage = storage.clone();
+    tokio::spawn(async move {
+        recommendations::run_recommendation_exporter(recommendation_db_pool, recommendation_storage).await;
+    });
+
+    // Start the sitemap generator
+    let sitemap_db_pool = db_pool.clone();
+    let sitemap_storage = storage.clone();
+    tokio::spawn(async move {
+        sitemap::run_sitemap_generator(sitemap_db_pool, sitemap_storage).await;
+    });
+
+    // Start the compilation worker, unless dedicated workers run it (see EMBEDDED_WORKERS)
+    if job_queue::embedded_workers_enabled() {
+        let compilation_db_pool = db_pool.clone();
+        let compilation_storage = storage.clone();
+        let compilation_job_queue = job_queue.clone();
+        tokio::spawn(async move {
+            compilations::run_compilation_worker(compilation_db_pool, compilation_storage, compilation_job_queue).await;
+        });
+    }
+
+    // Start ingesting files dropped into WATCH_FOLDER, if one is set
+    let watch_folder_db_pool = db_pool.clone();
+    let watch_folder_storage = storage.clone();
+    let watch_folder_job_queue = job_queue.clone();
+    tokio::spawn(async move {
+        watch_folder::run_watch_folder(watch_folder_db_pool, watch_folder_storage, watch_folder_job_queue).await;
+    });
+
+    // Start importing objects reported by S3 event notifications, if they're enabled
+    let s3_event_db_pool = db_pool.clone();
+    let s3_event_storage = storage.clone();
+    let s3_event_job_queue = job_queue.clone();
+    tokio::spawn(async move {
+        s3_events::run_s3_event_ingester(s3_event_db_pool, s3_event_storage, s3_event_job_queue).await;
+    });
+
+    // Rebuild the revoked session denylist in case Redis lost it
+    if let Some(ref client) = redis_client {
+        let session_db_pool = db_pool.clone();
+        let session_redis_client = client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = sessions::restore_denylist(&session_db_pool, &session_redis_client).await {
+                error!("Failed to restore the session denylist: {:?}", e);
+            }
+        });
+
+        // Start ending watch party sessions left open by replicas that went away
+        let session_sweep_db_pool = db_pool.clone();
+        let session_sweep_redis_client = client.clone();
+        tokio::spawn(async move {
+            watch_parties::run_session_sweeper(session_sweep_db_pool, session_sweep_redis_client).await;
+        });
+
+        // Start the counter flusher
+        let counter_db_pool = db_pool.clone();
+        let counter_redis_client = client.clone();
+        tokio::spawn(async move {
+            counters::run_counter_flusher(counter_db_pool, counter_redis_client).await;
+        });
+    }
+
     let app_state = Arc::new(Mutex::new(AppState {
         db_pool,
-        s3_client,
+        storage,
         redis_client,
         job_queue,
         video_clients: std::sync::Mutex::new(HashMap::new()),
@@ -119,16 +449,31 @@ async fn main() -> std::io::Result<()> {
                 error!("Failed to queue missing durations: {:?}", e);
             }
         });
-        
-        // Start background job processor
-        let job_queue_processor = job_queue_ref.clone();
+
+        // Queue moderation of videos the scraper adds
+        let job_queue_sweeper = job_queue_ref.clone();
         tokio::spawn(async move {
-            job_queue_processor.process_duration_extraction_jobs().await;
+            job_queue_sweeper.run_moderation_sweeper().await;
         });
         
-        info!("Started background job processor for duration extraction");
+        // Start background job processor
+        if job_queue::embedded_workers_enabled() {
+            let job_queue_processor = job_queue_ref.clone();
+            tokio::spawn(async move {
+                job_queue_processor.process_media_jobs().await;
+            });
+            info!("Started background media job processor");
+        } else {
+            info!("EMBEDDED_WORKERS is off, leaving media jobs to worker processes");
+        }
     }
 
+    // Start the watch party scheduler
+    let scheduler_app_state = app_state.clone();
+    tokio::spawn(async move {
+        websocket::run_watch_party_scheduler(scheduler_app_state).await;
+    });
+
     let app_state_clone = app_state.clone();
 
     info!("Starting HTTP server on 0.0.0.0:5050");
@@ -136,9 +481,12 @@ async fn main() -> std::io::Result<()> {
         let allowed_origins = std::env::var("CORS_ALLOWED_ORIGINS")
             .unwrap_or_else(|_| "http://localhost:3000".to_string());
         
+        // HEAD, PATCH and the Upload-* headers are used by resumable uploads, X-CSRF-Token by cookie auth
         let mut cors = Cors::default()
-            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
+            .allowed_methods(vec!["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"])
             .allowed_headers(vec![http::header::CONTENT_TYPE, http::header::AUTHORIZATION])
+            .allowed_headers(vec!["Upload-Offset", "Tus-Resumable", auth_cookies::CSRF_HEADER])
+            .expose_headers(vec!["Location", "Upload-Offset", "Upload-Length", "Upload-Expires", "Tus-Resumable", "Deprecation", "Sunset", "Link"])
             .supports_credentials();
 
         // Add each origin from the comma-separated list
@@ -147,6 +495,21 @@ async fn main() -> std::io::Result<()> {
         }
 
         App::new()
+            // HOTLINK_PROTECTION: media requests from other sites' pages are refused
+            .wrap(middleware::from_fn(hotlinks::check_hotlink))
+            // Cookie auth mode: state-changing requests must carry the session's CSRF token
+            .wrap(middleware::from_fn(auth_cookies::check_csrf))
+            // Subsystems turned off and maintenance mode answer with a 503, see feature_flags
+            .wrap(middleware::from_fn(feature_flags::check_features))
+            // Rejects tokens of revoked sessions before any handler sees them
+            .wrap(middleware::from_fn(sessions::check_session))
+            // Serves /api/v1 from the same routes, and marks deprecated endpoints. Outside the
+            // middleware above, which go by the unversioned path.
+            .wrap(middleware::from_fn(api_versions::route_version))
+            // Negotiates gzip/brotli from Accept-Encoding; media handlers opt out with Content-Encoding: identity
+            .wrap(middleware::Compress::default())
+            // Per-route latency histograms and slow request logging, see SLOW_REQUEST_MS
+            .wrap(middleware::from_fn(request_metrics::record_request))
             .wrap(cors)
             .app_data(web::Data::new(app_state.clone()))
             .configure(handlers::configure_routes)
@@ -170,6 +533,8 @@ async fn main() -> std::io::Result<()> {
         }
 
         App::new()
+            .wrap(middleware::from_fn(feature_flags::check_features))
+            .wrap(middleware::from_fn(sessions::check_session))
             .wrap(cors)
             .app_data(web::Data::new(app_state_clone.clone()))
             .configure(websocket::configure_ws_routes)
@@ -0,0 +1,131 @@
+use log::{info, warn};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::job_queue::JobQueue;
+use crate::models::Video;
+use crate::storage::ObjectStore;
+
+type MaintenanceResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Tables the search endpoints query directly; there is no separate search index to rebuild
+const SEARCH_TABLES: [&str; 3] = ["videos", "video_translations", "categories"];
+
+#[derive(Debug, PartialEq)]
+pub enum AdminAccount {
+    Created(i32),
+    Promoted(i32),
+}
+
+// Make the user an admin, creating the account first if there is none with that username in any
+// letter case. An existing account's password is only changed when one is given.
+pub async fn create_admin(db_pool: &PgPool, username: &str, email: Option<&str>, password: Option<&str>) -> MaintenanceResult<AdminAccount> {
+    let hashed_password = password.map(|p| bcrypt::hash(p, bcrypt::DEFAULT_COST)).transpose()?;
+
+    let promoted: Option<i32> = sqlx::query_scalar(
+        "UPDATE users SET is_admin = TRUE, password = COALESCE($2, password) WHERE LOWER(username) = LOWER($1) RETURNING id"
+    )
+    .bind(username)
+    .bind(&hashed_password)
+    .fetch_optional(db_pool)
+    .await?;
+    if let Some(id) = promoted {
+        return Ok(AdminAccount::Promoted(id));
+    }
+
+    let email = email.ok_or("--email is required to create a new account")?;
+    let hashed_password = hashed_password.ok_or("A password is required to create a new account")?;
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO users (username, email, password, created_at, is_admin) VALUES ($1, $2, $3, $4, TRUE) RETURNING id"
+    )
+    .bind(username)
+    .bind(email)
+    .bind(&hashed_password)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(db_pool)
+    .await?;
+    Ok(AdminAccount::Created(id))
+}
+
+// Rebuild the indexes of the tables search reads and refresh their planner statistics, e.g.
+// after a large import or when search queries have become slow
+pub async fn reindex_search(db_pool: &PgPool) -> MaintenanceResult<()> {
+    for table in SEARCH_TABLES {
+        info!("Reindexing {}", table);
+        sqlx::query(&format!("REINDEX TABLE {}", table)).execute(db_pool).await?;
+        sqlx::query(&format!("ANALYZE {}", table)).execute(db_pool).await?;
+    }
+    Ok(())
+}
+
+// Videos whose processing never finished: no duration or no thumbnail. With `video_id`, just
+// that video, finished or not.
+pub async fn videos_to_requeue(db_pool: &PgPool, video_id: Option<i32>) -> Result<Vec<Video>, sqlx::Error> {
+    sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos
+         WHERE deleted_at IS NULL
+           AND (id = $1 OR ($1 IS NULL AND (duration IS NULL OR thumbnail_url IS NULL)))
+         ORDER BY id ASC"
+    )
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+async fn object_exists(storage: &dyn ObjectStore, bucket: &str, key: &str) -> MaintenanceResult<bool> {
+    match storage.head(bucket, key).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.is_not_found() => Ok(false),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+// Queue every processing step again for the videos from videos_to_requeue, returning how many
+// were queued. Videos whose file is missing from storage are skipped, as their jobs could only fail.
+pub async fn requeue_jobs(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    job_queue: &JobQueue,
+    bucket: &str,
+    video_id: Option<i32>,
+    force: bool,
+) -> MaintenanceResult<usize> {
+    let mut queued = 0;
+    for video in videos_to_requeue(db_pool, video_id).await? {
+        if !object_exists(storage, bucket, &video.s3_key).await? {
+            warn!("Object {} does not exist for video ID {}, not requeueing", video.s3_key, video.id);
+            continue;
+        }
+        let kinds = job_queue.enqueue_processing(&video, bucket, force).await?;
+        info!("Queued {:?} for video {}", kinds, video.id);
+        queued += 1;
+    }
+    Ok(queued)
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingObject {
+    pub video_id: i32,
+    pub kind: String, // "original" or a rendition name
+    pub s3_key: String,
+}
+
+// Every video's original file and renditions that the database refers to but storage doesn't have
+pub async fn verify_s3(db_pool: &PgPool, storage: &dyn ObjectStore, bucket: &str) -> MaintenanceResult<Vec<MissingObject>> {
+    let objects = sqlx::query_as::<_, (i32, String, String)>(
+        "SELECT id, 'original', s3_key FROM videos WHERE deleted_at IS NULL
+         UNION ALL
+         SELECT r.video_id, r.name, r.s3_key FROM video_renditions r JOIN videos v ON v.id = r.video_id WHERE v.deleted_at IS NULL
+         ORDER BY 1, 2"
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut missing = Vec::new();
+    for (video_id, kind, s3_key) in objects {
+        if !object_exists(storage, bucket, &s3_key).await? {
+            missing.push(MissingObject { video_id, kind, s3_key });
+        }
+    }
+    Ok(missing)
+}
@@ -0,0 +1,398 @@
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::Duration;
+use log::{info, error};
+use tokio::process::Command;
+
+use crate::audio_tracks;
+use crate::clips::{self, ClipFormat};
+use crate::encoding_ladder::SourceStats;
+use crate::loudness::{self, Loudness};
+use crate::models::{VideoAudioTrack, WatermarkSettings};
+use crate::spherical::{self, Spherical};
+use crate::storage::ObjectStore;
+use crate::watermarks;
+
+type MediaResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Rendition heights transcoded when TRANSCODE_RENDITIONS (e.g. "1080,720,480") is not set
+const DEFAULT_RENDITION_HEIGHTS: &[i32] = &[720, 480];
+
+// Thumbnails are grabbed this far into the video to skip black intro frames
+const THUMBNAIL_OFFSET_SECS: &str = "1";
+
+// Limits on ffprobe, and on grabbing one frame, which only decode a little of the input
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const FRAME_TIMEOUT: Duration = Duration::from_secs(60);
+// Clips are rendered on request, so a slow source can't hold a render slot for long
+const CLIP_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+pub fn rendition_heights() -> Vec<i32> {
+    let heights: Vec<i32> = std::env::var("TRANSCODE_RENDITIONS")
+        .ok()
+        .map(|v| v.split(',').filter_map(|h| h.trim().parse::<i32>().ok()).filter(|h| *h > 0).collect())
+        .unwrap_or_default();
+
+    if heights.is_empty() {
+        DEFAULT_RENDITION_HEIGHTS.to_vec()
+    } else {
+        heights
+    }
+}
+
+pub fn rendition_name(height: i32) -> String {
+    format!("{}p", height)
+}
+
+pub fn rendition_s3_key(video_id: i32, name: &str) -> String {
+    format!("renditions/{}/{}.mp4", video_id, name)
+}
+
+// Temporary working directory for a single job, removed with its contents when dropped
+pub struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    pub async fn create() -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("media-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&path).await?;
+        Ok(Self { path })
+    }
+
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Failed to remove work directory {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+pub async fn upload_file(storage: &dyn ObjectStore, bucket: &str, s3_key: &str, path: &Path, content_type: &str) -> MediaResult<()> {
+    storage.put_file(bucket, s3_key, path, content_type).await?;
+    Ok(())
+}
+
+// Height of the first video stream, if ffprobe can read it
+pub async fn probe_height(input: &Path) -> MediaResult<Option<i32>> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=height", "-of", "csv=p=0"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse::<i32>().ok())
+}
+
+// Whether the file has at least one audio stream
+pub async fn probe_has_audio(input: &Path) -> MediaResult<bool> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "a", "-show_entries", "stream=index", "-of", "csv=p=0"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
+
+// Every audio stream, in file order, with its language and title tags
+pub async fn probe_audio_tracks(input: &Path, video_id: i32) -> MediaResult<Vec<VideoAudioTrack>> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "a"])
+        .args(["-show_entries", "stream=codec_name,channels:stream_tags=language,title:stream_disposition=default,visual_impaired", "-of", "json"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    Ok(audio_tracks::parse_probe_output(video_id, &String::from_utf8_lossy(&output.stdout))?)
+}
+
+// 360° projection of the first video stream, None for flat video
+pub async fn probe_spherical(input: &Path) -> MediaResult<Option<Spherical>> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream_side_data:stream_tags=stereo_mode", "-of", "json"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    Ok(spherical::parse_probe_output(&String::from_utf8_lossy(&output.stdout))?)
+}
+
+// Container duration in seconds, if ffprobe can read it
+pub async fn probe_duration(input: &Path) -> MediaResult<Option<f64>> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-show_entries", "format=duration", "-of", "csv=p=0"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse::<f64>().ok())
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeStream {
+    width: Option<i32>,
+    height: Option<i32>,
+    avg_frame_rate: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ProbeFormat {
+    bit_rate: Option<String>,
+}
+
+// "30000/1001" style frame rates; "0/0" when ffprobe doesn't know
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+// Dimensions, frame rate and bitrate of the first video stream. The bitrate is the stream's own
+// if the container records it, otherwise the whole file's. Motion is left for the caller to sample.
+pub async fn probe_source(input: &Path) -> MediaResult<SourceStats> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,avg_frame_rate,bit_rate:format=bit_rate", "-of", "json"])
+        .arg(input);
+    let output = run_for_output(cmd, "ffprobe", PROBE_TIMEOUT).await?;
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout)?;
+    let stream = probe.streams.into_iter().next();
+    let bitrate = stream
+        .as_ref()
+        .and_then(|s| s.bit_rate.as_deref())
+        .or(probe.format.as_ref().and_then(|f| f.bit_rate.as_deref()))
+        .and_then(|b| b.parse::<u64>().ok());
+    Ok(SourceStats {
+        width: stream.as_ref().and_then(|s| s.width),
+        height: stream.as_ref().and_then(|s| s.height),
+        fps: stream.as_ref().and_then(|s| s.avg_frame_rate.as_deref()).and_then(parse_frame_rate),
+        bitrate,
+        motion: None,
+    })
+}
+
+// A single JPEG frame at `offset` seconds, scaled like thumbnails. The input can be a file or a
+// URL ffmpeg reads with ranged requests.
+pub async fn extract_frame(input: impl AsRef<OsStr>, offset: f64, output: &Path) -> MediaResult<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-ss", &format!("{:.3}", offset), "-i"])
+        .arg(input)
+        .args(["-frames:v", "1", "-vf", "scale='min(1280,iw)':-2", "-q:v", "3"])
+        .arg(output);
+    run_for_output(cmd, "ffmpeg", FRAME_TIMEOUT).await.map(|_| ())
+}
+
+// A single 8-bit grayscale frame at `offset` seconds, scaled to width x height, as raw bytes.
+// Empty if the offset is past the last frame.
+pub async fn extract_gray_frame(input: &Path, offset: f64, width: usize, height: usize) -> MediaResult<Vec<u8>> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-v", "error", "-ss", &format!("{:.3}", offset), "-i"])
+        .arg(input)
+        .args(["-frames:v", "1", "-vf", &format!("scale={}:{}", width, height)])
+        .args(["-pix_fmt", "gray", "-f", "rawvideo", "pipe:1"]);
+    let output = run_for_output(cmd, "ffmpeg", FRAME_TIMEOUT).await?;
+    Ok(output.stdout)
+}
+
+pub async fn extract_thumbnail(input: &Path, output: &Path) -> MediaResult<()> {
+    let grab = |offset: &'static str| {
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-y", "-v", "error", "-ss", offset, "-i"])
+            .arg(input)
+            .args(["-frames:v", "1", "-vf", "scale='min(1280,iw)':-2", "-q:v", "3"])
+            .arg(output);
+        cmd
+    };
+
+    run(grab(THUMBNAIL_OFFSET_SECS), "ffmpeg").await?;
+
+    // Seeking past the end of very short videos produces no frame, so fall back to the first one
+    if tokio::fs::metadata(output).await.is_err() {
+        run(grab("0"), "ffmpeg").await?;
+    }
+    Ok(())
+}
+
+// Transcode to an H.264/AAC MP4 of the given height at the given CRF, with the moov atom up front
+// for streaming, optionally overlaying a watermark image
+pub async fn transcode(input: &Path, output: &Path, height: i32, crf: i32, watermark: Option<(&Path, &WatermarkSettings)>) -> MediaResult<()> {
+    info!("Transcoding {} to {}p at CRF {}", input.display(), height, crf);
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-i"]).arg(input);
+    match watermark {
+        Some((image, settings)) => {
+            cmd.arg("-i")
+                .arg(image)
+                .args(["-filter_complex", &watermarks::overlay_filter(height, settings)])
+                .args(["-map", "[out]", "-map", "0:a?"]);
+        }
+        None => {
+            cmd.args(["-vf", &format!("scale=-2:{}", height)])
+                .args(["-map", "0:v:0", "-map", "0:a?"]);
+        }
+    }
+    cmd.args(["-c:v", "libx264", "-preset", "veryfast", "-crf", &crf.to_string()])
+        .args(["-c:a", "aac", "-b:a", "128k"])
+        .args(["-movflags", "+faststart"])
+        .arg(output);
+    run(cmd, "ffmpeg").await
+}
+
+// Which streams of the input go into an HLS package
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HlsStreams {
+    Muxed, // video with the default audio track
+    VideoOnly,
+    Audio(i32), // one audio track, by its position among the audio streams
+}
+
+// Package as VOD HLS: MPEG-TS segments of about `segment_secs` each, H.264 and/or AAC, written
+// next to `playlist` and named after it, e.g. index-00000.ts, index-00001.ts, ...
+pub async fn package_hls(input: &Path, playlist: &Path, segment_secs: u32, streams: HlsStreams) -> MediaResult<()> {
+    info!("Packaging {} as HLS ({:?})", input.display(), streams);
+    let stem = playlist.file_stem().and_then(|s| s.to_str()).unwrap_or("seg");
+    let segment_pattern = playlist.with_file_name(format!("{}-%05d.ts", stem));
+    // Keyframes on segment boundaries so segments come out the requested length
+    let force_key_frames = format!("expr:gte(t,n_forced*{})", segment_secs);
+    let video_args = ["-c:v", "libx264", "-preset", "veryfast", "-crf", "23", "-force_key_frames", force_key_frames.as_str()];
+    let audio_args = ["-c:a", "aac", "-b:a", "128k"];
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-i"]).arg(input);
+    match streams {
+        HlsStreams::Muxed => {
+            cmd.args(video_args).args(audio_args);
+        }
+        HlsStreams::VideoOnly => {
+            cmd.args(["-map", "0:v:0", "-an"]).args(video_args);
+        }
+        HlsStreams::Audio(track) => {
+            cmd.args(["-map", &format!("0:a:{}", track), "-vn"]).args(audio_args);
+        }
+    }
+    cmd.args(["-f", "hls", "-hls_time", &segment_secs.to_string(), "-hls_playlist_type", "vod"])
+        .arg("-hls_segment_filename")
+        .arg(&segment_pattern)
+        .arg(playlist);
+    run(cmd, "ffmpeg").await
+}
+
+// An endlessly looping animated GIF or WebP of `duration` seconds from `start`, without audio
+pub async fn render_clip(input: impl AsRef<OsStr>, output: &Path, start: f64, duration: f64, format: ClipFormat) -> MediaResult<()> {
+    info!("Rendering {:.3}s {} clip from {:.3}s", duration, format.extension(), start);
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-ss", &format!("{:.3}", start), "-t", &format!("{:.3}", duration), "-i"])
+        .arg(input)
+        .args(["-an", "-filter_complex", &clips::clip_filter(format)]);
+    if format == ClipFormat::Webp {
+        cmd.args(["-c:v", "libwebp", "-lossless", "0", "-quality", "70"]);
+    }
+    cmd.args(["-loop", "0"]).arg(output);
+    run_for_output(cmd, "ffmpeg", CLIP_TIMEOUT).await.map(|_| ())
+}
+
+// Compilation segments are all re-encoded to this, letterboxed as needed, so they can be joined
+// without re-encoding again
+const SEGMENT_FILTER: &str = "scale=1280:720:force_original_aspect_ratio=decrease,pad=1280:720:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=30,format=yuv420p";
+
+// `duration` seconds from `start` as a 720p, 30 fps H.264 MP4 with stereo 48 kHz AAC. Sources
+// without audio get silence, so every segment has the same streams.
+pub async fn normalize_segment(input: &Path, output: &Path, start: f64, duration: f64, has_audio: bool) -> MediaResult<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-ss", &format!("{:.3}", start), "-t", &format!("{:.3}", duration), "-i"])
+        .arg(input);
+    if !has_audio {
+        cmd.args(["-f", "lavfi", "-i", "anullsrc=channel_layout=stereo:sample_rate=48000"])
+            .args(["-map", "0:v:0", "-map", "1:a:0", "-shortest"]);
+    }
+    cmd.args(["-vf", SEGMENT_FILTER])
+        .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"])
+        .args(["-c:a", "aac", "-b:a", "128k", "-ar", "48000", "-ac", "2"])
+        .arg(output);
+    run(cmd, "ffmpeg").await
+}
+
+// Join the files named in a concat demuxer `list` (lines of `file '<path>'`), which must share
+// codecs and parameters, into a streamable MP4
+pub async fn concat(list: &Path, output: &Path) -> MediaResult<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-f", "concat", "-safe", "0", "-i"])
+        .arg(list)
+        .args(["-c", "copy", "-movflags", "+faststart"])
+        .arg(output);
+    run(cmd, "ffmpeg").await
+}
+
+// Mono 16 kHz PCM WAV, the input speech-to-text models expect
+pub async fn extract_audio(input: &Path, output: &Path) -> MediaResult<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-i"])
+        .arg(input)
+        .args(["-vn", "-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"])
+        .arg(output);
+    run(cmd, "ffmpeg").await
+}
+
+// `duration` seconds of a WAV from `start`, without re-encoding. PCM seeks to the sample.
+pub async fn cut_audio(input: &Path, output: &Path, start: f64, duration: f64) -> MediaResult<()> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-ss", &format!("{:.3}", start), "-t", &format!("{:.3}", duration), "-i"])
+        .arg(input)
+        .args(["-c", "copy"])
+        .arg(output);
+    run(cmd, "ffmpeg").await
+}
+
+// EBU R128 measurements of the first audio stream, None if it has no measurable audio. Decodes
+// the whole file, but no video frames.
+pub async fn measure_loudness(input: &Path) -> MediaResult<Option<Loudness>> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-nostats", "-i"])
+        .arg(input)
+        .args(["-vn", "-sn", "-dn", "-af", "loudnorm=print_format=json", "-f", "null", "-"]);
+    let output = run_for_output(cmd, "ffmpeg", command_timeout()).await?;
+    Ok(loudness::parse_loudnorm_output(&String::from_utf8_lossy(&output.stderr)))
+}
+
+// How long any other ffmpeg or ffprobe run may take when MEDIA_COMMAND_TIMEOUT_SECS is not set
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 6 * 60 * 60;
+
+pub fn command_timeout() -> Duration {
+    let secs = std::env::var("MEDIA_COMMAND_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+pub(crate) async fn run(cmd: Command, program: &str) -> MediaResult<()> {
+    run_for_output(cmd, program, command_timeout()).await.map(|_| ())
+}
+
+// Runs `cmd` to completion and returns its output if it succeeded. The process is killed when it
+// takes longer than `timeout`, or when the caller gives up on it, e.g. a request that went away.
+pub(crate) async fn run_for_output(mut cmd: Command, program: &str, timeout: Duration) -> MediaResult<Output> {
+    cmd.kill_on_drop(true);
+    let output = match tokio::time::timeout(timeout, cmd.output()).await {
+        Ok(output) => output?,
+        Err(_) => return Err(Box::new(std::io::Error::new(ErrorKind::TimedOut, format!("{} took longer than {}s", program, timeout.as_secs())))),
+    };
+    if !output.status.success() {
+        return Err(command_error(program, &output.stderr));
+    }
+    Ok(output)
+}
+
+fn command_error(program: &str, stderr: &[u8]) -> Box<dyn std::error::Error + Send + Sync> {
+    let stderr = String::from_utf8_lossy(stderr);
+    Box::new(std::io::Error::other(format!("{} failed: {}", program, stderr.trim())))
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::env;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+
+// The migrations compiled into this build
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,           // in this build, not yet applied
+    Failed,            // recorded by sqlx as not having completed
+    ChecksumMismatch,  // applied, but the file has changed since
+    Unknown,           // applied, but not in this build, e.g. by a newer release
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<DateTime<Utc>>,
+}
+
+// A row of sqlx's _sqlx_migrations table
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub success: bool,
+    pub checksum: Vec<u8>,
+}
+
+// (version, description, checksum) of each up migration in this build
+pub fn embedded_migrations() -> Vec<(i64, String, Vec<u8>)> {
+    MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| (m.version, m.description.to_string(), m.checksum.to_vec()))
+        .collect()
+}
+
+// Every migration known to the build or the database, by version
+pub fn compare(embedded: &[(i64, String, Vec<u8>)], applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    let applied_by_version: HashMap<i64, &AppliedMigration> = applied.iter().map(|m| (m.version, m)).collect();
+
+    let mut statuses: Vec<MigrationStatus> = embedded
+        .iter()
+        .map(|(version, description, checksum)| {
+            let applied = applied_by_version.get(version);
+            let state = match applied {
+                None => MigrationState::Pending,
+                Some(m) if !m.success => MigrationState::Failed,
+                Some(m) if m.checksum != *checksum => MigrationState::ChecksumMismatch,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: *version,
+                description: description.clone(),
+                state,
+                installed_on: applied.map(|m| m.installed_on),
+            }
+        })
+        .collect();
+
+    statuses.extend(
+        applied
+            .iter()
+            .filter(|m| !embedded.iter().any(|(version, _, _)| *version == m.version))
+            .map(|m| MigrationStatus {
+                version: m.version,
+                description: m.description.clone(),
+                state: MigrationState::Unknown,
+                installed_on: Some(m.installed_on),
+            }),
+    );
+    statuses.sort_by_key(|s| s.version);
+    statuses
+}
+
+// Whether the schema is exactly what this build expects
+pub fn up_to_date(statuses: &[MigrationStatus]) -> bool {
+    statuses.iter().all(|s| s.state == MigrationState::Applied)
+}
+
+pub async fn applied_migrations(db_pool: &PgPool) -> Result<Vec<AppliedMigration>, sqlx::Error> {
+    // A database that has never been migrated has no table yet
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(db_pool)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+    sqlx::query_as::<_, AppliedMigration>(
+        "SELECT version, description, installed_on, success, checksum FROM _sqlx_migrations ORDER BY version"
+    )
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn migration_statuses(db_pool: &PgPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    Ok(compare(&embedded_migrations(), &applied_migrations(db_pool).await?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuardMode {
+    Strict,
+    Warn,
+    Off,
+}
+
+// MIGRATION_CHECK: strict (default) refuses to serve on drift, warn only logs it, off skips the check
+pub fn guard_mode() -> GuardMode {
+    match env::var("MIGRATION_CHECK").map(|v| v.to_ascii_lowercase()).as_deref() {
+        Ok("warn") => GuardMode::Warn,
+        Ok("off") => GuardMode::Off,
+        _ => GuardMode::Strict,
+    }
+}
+
+// The drift that should stop this build from serving: migrations it needs that aren't (cleanly)
+// applied. Migrations only a newer build knows about are expected during a rolling deploy.
+pub fn blocking_drift(statuses: &[MigrationStatus]) -> Vec<&MigrationStatus> {
+    statuses
+        .iter()
+        .filter(|s| matches!(s.state, MigrationState::Pending | MigrationState::Failed | MigrationState::ChecksumMismatch))
+        .collect()
+}
+
+// Compare the database with this build's migrations before serving. Returns an error describing
+// the drift when the server should not start.
+pub async fn check_at_startup(db_pool: &PgPool) -> Result<(), String> {
+    let mode = guard_mode();
+    if mode == GuardMode::Off {
+        return Ok(());
+    }
+    let statuses = migration_statuses(db_pool)
+        .await
+        .map_err(|e| format!("Failed to read applied migrations: {:?}", e))?;
+
+    for status in statuses.iter().filter(|s| s.state == MigrationState::Unknown) {
+        warn!("Migration {} ({}) is applied but not part of this build", status.version, status.description);
+    }
+    let drift = blocking_drift(&statuses);
+    if drift.is_empty() {
+        info!("Database schema is up to date ({} migrations)", statuses.len());
+        return Ok(());
+    }
+
+    for status in &drift {
+        error!("Migration {} ({}) is {:?}", status.version, status.description, status.state);
+    }
+    let message = format!("{} migrations are not applied as this build expects; run the migrate command", drift.len());
+    match mode {
+        GuardMode::Strict => Err(message),
+        _ => {
+            error!("{} (serving anyway as MIGRATION_CHECK=warn)", message);
+            Ok(())
+        }
+    }
+}
@@ -2,15 +2,12 @@ use serde::{Deserialize, Serialize};
 use chrono::NaiveDateTime;
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct User {
-    pub id: i32,
-    pub username: String,
-    pub email: String,
-    pub password: String,
-    pub created_at: Option<NaiveDateTime>,
-    pub settings: Option<serde_json::Value>,
-}
+// Shared with the scraper, which writes videos too
+pub use videostreaming_models::{Comment, User, Video};
+
+// Request bodies and query strings name their fields in camelCase, and take the snake_case
+// names some clients send as aliases. Responses keep their keys; field_case renames them for
+// /api/v1.
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
@@ -18,6 +15,14 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+// In cookie auth mode the refresh token comes from its cookie instead
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshRequest {
+    #[serde(alias = "refresh_token")]
+    pub refresh_token: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterRequest {
     pub username: String,
@@ -26,53 +31,997 @@ pub struct RegisterRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct Video {
+pub struct VideoRendition {
+    pub id: i32,
+    pub video_id: i32,
+    pub name: String, // e.g. "720p"
+    pub height: i32,
+    pub s3_key: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Where a video's original file is stored, for videos moved out of STANDARD
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct VideoStorage {
+    pub video_id: i32,
+    pub storage_class: String, // an S3 storage class, e.g. GLACIER
+    pub restore_requested_at: Option<NaiveDateTime>,
+    pub transitioned_at: NaiveDateTime,
+}
+
+// The latest run of one of a video's processing jobs
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProcessingJob {
+    pub video_id: i32,
+    pub kind: String, // a job kind, e.g. "transcode"
+    pub state: String, // queued, running, done or failed
+    pub error: Option<String>, // why the last attempt failed, if it did
+    pub updated_at: NaiveDateTime,
+}
+
+// One time a worker picked up a media job
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct JobAttempt {
+    pub id: i64,
+    pub video_id: i32,
+    pub kind: String, // a job kind, e.g. "transcode"
+    pub attempt: i32, // 1 for the first try, counting up with each retry
+    pub outcome: String, // running, succeeded or failed
+    pub error: Option<String>,
+    pub will_retry: bool, // whether a failed attempt was queued again
+    pub worker: Option<String>, // the worker that ran it
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobAttemptsQuery {
+    pub kind: Option<String>, // a job kind, e.g. "thumbnail"; every kind when omitted
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClassRequest {
+    #[serde(alias = "storage_class")]
+    pub storage_class: String,
+}
+
+// The rendition ladder chosen for a video's source
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EncodingProfile {
+    pub video_id: i32,
+    pub complexity: String, // low, medium or high
+    pub bitrate_kbps: Option<i32>,
+    pub motion: Option<f64>,
+    pub heights: Vec<i32>,
+    pub crf: i32,
+    pub analyzed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub start: f64, // seconds
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct VideoTranscript {
+    pub video_id: i32,
+    pub backend: String, // whisper_cpp or api
+    pub language: Option<String>,
+    pub segments: sqlx::types::Json<Vec<TranscriptSegment>>,
+    #[serde(skip_serializing)]
+    pub vtt: String, // served separately as text/vtt
+    pub content: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptQuery {
+    pub format: Option<String>, // "vtt" for WebVTT captions instead of JSON
+    pub q: Option<String>, // only return segments containing this text
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct VideoChapter {
     pub id: i32,
+    pub video_id: i32,
+    pub title: String,
+    pub start_time: f64, // seconds
+    pub source: String, // manual or auto
+    pub accepted: bool, // auto chapters start out as unaccepted proposals
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct VideoTranslation {
+    pub video_id: i32,
+    pub language: String, // lower-cased BCP 47 tag, e.g. "fr" or "pt-br"
     pub title: String,
     pub description: Option<String>,
-    pub s3_key: String,
-    pub thumbnail_url: Option<String>,
-    pub uploaded_by: Option<i32>,
-    pub upload_date: Option<NaiveDateTime>,
-    pub tags: Option<Vec<String>>,
-    pub view_count: Option<i32>,
-    pub category_id: Option<i32>,
-    pub duration: Option<i32>, // Duration in seconds
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranslationRequest {
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterRequest {
+    pub title: Option<String>,
+    #[serde(alias = "start_time")]
+    pub start_time: Option<f64>,
+    pub accepted: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChapterQuery {
+    pub proposed: Option<bool>, // include unaccepted auto chapters (owner only)
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct Category {
+pub struct ThumbnailCandidate {
     pub id: i32,
+    pub video_id: i32,
+    pub s3_key: String,
+    pub time_offset: f64, // seconds into the video
+    pub score: f64,
+    pub selected: bool, // whether it is the video's current thumbnail
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailSelectRequest {
+    #[serde(alias = "candidate_id")]
+    pub candidate_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModerationRequest {
+    pub status: String, // approved or flagged
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoListQuery {
+    #[serde(alias = "processing_status")]
+    pub processing_status: Option<String>, // uploaded, processing, ready or failed; all when omitted
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationQueueQuery {
+    pub status: Option<String>, // pending or flagged; both when omitted
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>, // csv (default) or ndjson
+    pub fields: Option<String>, // comma-separated; every field when absent
+    pub from: Option<String>,   // YYYY-MM-DD, inclusive
+    pub to: Option<String>,     // YYYY-MM-DD, inclusive
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendationList {
+    #[serde(alias = "user_id")]
+    pub user_id: i32,
+    #[serde(alias = "video_ids")]
+    pub video_ids: Vec<i32>, // best first; [] clears the user's list
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecommendationImport {
+    pub model: Option<String>, // recorded with each list, e.g. a training run's name
+    pub lists: Vec<RecommendationList>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExperimentVariant {
     pub name: String,
+    pub weight: i32, // share of users relative to the other variants' weights
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExperimentRequest {
+    pub key: String,
     pub description: Option<String>,
-    pub created_at: Option<NaiveDateTime>,
-    pub icon_svg: Option<String>,
+    pub variants: Vec<ExperimentVariant>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
-pub struct Comment {
+#[derive(Debug, Deserialize)]
+pub struct FeatureFlagRequest {
+    pub enabled: bool,
+    pub message: Option<String>, // shown while the subsystem is off or maintenance is on
+}
+
+// Fields left out are unchanged
+#[derive(Debug, Deserialize)]
+pub struct ExperimentUpdateRequest {
+    pub active: Option<bool>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HomeFeedQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    pub since: Option<String>, // a cursor from an earlier response; the start of the log when absent
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRequest {
+    pub prefix: Option<String>, // IMPORT_S3_PREFIX or "videos/" when absent
+    #[serde(alias = "dry_run", default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct VideoHlsSegment {
+    pub video_id: i32,
+    pub segment_index: i32,
+    pub duration: f64, // seconds
+    pub s3_key: String,
+    pub key_index: i32,
+}
+
+// One audio track of a video: a stream of its original upload, which renditions keep in this
+// order, or a standalone file uploaded for it, served from /api/videos/{id}/audio-tracks/{track}
+#[derive(Debug, Clone, PartialEq, Serialize, FromRow)]
+pub struct VideoAudioTrack {
+    #[serde(skip_serializing)]
+    pub video_id: i32,
+    pub track_index: i32,
+    pub language: Option<String>, // as tagged in the file, e.g. "eng"; None when untagged
+    pub label: String,
+    pub codec: Option<String>,
+    pub channels: Option<i32>,
+    pub is_default: bool,
+    pub audio_description: bool, // narrates what's on screen, for blind and low-vision viewers
+    pub standalone: bool,
+    #[serde(skip_serializing)]
+    pub s3_key: Option<String>, // set on standalone tracks
+    #[serde(skip_serializing)]
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioTrackUploadQuery {
+    pub label: Option<String>,
+    pub language: Option<String>,
+    #[serde(alias = "audio_description", default)]
+    pub audio_description: bool,
+}
+
+// How a 360° or VR video is mapped onto the sphere
+#[derive(Debug, Clone, PartialEq, Serialize, FromRow)]
+pub struct VideoSpherical {
+    #[serde(skip_serializing)]
+    pub video_id: i32,
+    pub projection: String, // none, equirectangular or cubemap
+    pub stereo_mode: String, // mono, top_bottom or left_right
+    pub source: String, // detected or uploader
+    pub updated_at: NaiveDateTime,
+}
+
+// An uploader's override; a projection of "none" marks the video as flat
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SphericalRequest {
+    pub projection: String,
+    #[serde(alias = "stereo_mode")]
+    pub stereo_mode: Option<String>, // mono when omitted
+}
+
+// A client's quality-of-experience report for one playback, or part of one; clients may send
+// several over a playback with the same sessionId
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackBeacon {
+    #[serde(alias = "video_id")]
+    pub video_id: i32,
+    #[serde(alias = "session_id")]
+    pub session_id: Option<String>,
+    pub rendition: Option<String>, // the rendition playing, e.g. "720p"
+    #[serde(alias = "startup_ms")]
+    pub startup_ms: Option<i32>, // from pressing play to the first frame
+    #[serde(alias = "rebuffer_count")]
+    pub rebuffer_count: Option<i32>,
+    #[serde(alias = "rebuffer_ms")]
+    pub rebuffer_ms: Option<i32>,
+    #[serde(alias = "bitrate_kbps")]
+    pub bitrate_kbps: Option<i32>, // measured throughput, not the rendition's nominal bitrate
+    #[serde(alias = "watched_secs")]
+    pub watched_secs: Option<f64>,
+    #[serde(alias = "dropped_frames")]
+    pub dropped_frames: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackReportQuery {
+    #[serde(alias = "video_id")]
+    pub video_id: Option<i32>, // every video when omitted
+    pub days: Option<i64>,
+}
+
+// Playback quality of one rendition over a report's period
+#[derive(Debug, Serialize, FromRow)]
+pub struct RenditionPlaybackStats {
+    pub rendition: String,
+    pub beacons: i64,
+    pub sessions: i64,
+    pub avg_startup_ms: Option<f64>,
+    pub p50_startup_ms: Option<f64>,
+    pub p95_startup_ms: Option<f64>,
+    pub avg_rebuffer_count: Option<f64>,
+    pub rebuffer_ratio: Option<f64>, // time spent rebuffering over time watched
+    pub avg_bitrate_kbps: Option<f64>,
+    pub p10_bitrate_kbps: Option<f64>,
+    pub watched_hours: f64,
+}
+
+// GET /api/videos/{id}: the video plus what the player needs to offer a choice of audio and to
+// pick a VR-capable player
+#[derive(Debug, Serialize)]
+pub struct VideoDetail {
+    #[serde(flatten)]
+    pub video: Video,
+    pub audio_tracks: Vec<VideoAudioTrack>,
+    pub spherical: Option<VideoSpherical>, // null for flat videos
+}
+
+// A channel page's layout: the featured video, videos to show first (in this order, ahead of
+// the rest by upload date) and named sections. Used whole; fields left out of a PUT are cleared.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelLayout {
+    #[serde(alias = "featured_video_id")]
+    pub featured_video_id: Option<i32>,
+    #[serde(alias = "video_order", default)]
+    pub video_order: Vec<i32>,
+    #[serde(default)]
+    pub sections: Vec<ChannelLayoutSection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelLayoutSection {
+    pub title: String,
+    #[serde(alias = "video_ids")]
+    pub video_ids: Vec<i32>,
+}
+
+// Fields left out are unchanged
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoUpdateRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(alias = "category_ids")]
+    pub category_ids: Option<Vec<i32>>, // replaces the video's categories; [] removes them all
+}
+
+// Fields left out are unchanged
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestrictionsRequest {
+    #[serde(alias = "allowed_countries")]
+    pub allowed_countries: Option<Vec<String>>,
+    #[serde(alias = "blocked_countries")]
+    pub blocked_countries: Option<Vec<String>>,
+    #[serde(alias = "allow_embed")]
+    pub allow_embed: Option<bool>,
+    #[serde(alias = "allow_download")]
+    pub allow_download: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    pub quality: Option<String>, // a rendition name such as "720p"; the original file when absent
+    pub playback_token: Option<String>, // see playback_tokens
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptionRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HlsPlaylistQuery {
+    pub token: Option<String>,
+    pub expires: Option<i64>, // set on signed playback URLs
+    pub signature: Option<String>,
+    pub playback_token: Option<String>, // see playback_tokens
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClipRequest {
+    pub start: f64,              // seconds into the video
+    pub duration: Option<f64>,   // seconds; clips::DEFAULT_CLIP_SECS when absent
+    pub format: Option<String>,  // gif (default) or webp
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FrameQuery {
+    pub t: f64, // seconds into the video
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct VideoClip {
     pub id: i32,
     pub video_id: i32,
+    pub created_by: Option<i32>,
+    pub start_ms: i32,
+    pub duration_ms: i32,
+    pub format: String,
+    #[serde(skip_serializing)]
+    pub s3_key: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompilationRequest {
+    pub title: String,
+    pub description: Option<String>,
+    pub segments: Vec<CompilationSegmentRequest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilationSegmentRequest {
+    #[serde(alias = "video_id")]
+    pub video_id: i32,
+    pub start: f64, // seconds
+    pub end: f64,   // seconds
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Compilation {
+    pub id: i32,
     pub user_id: i32,
-    pub content: String,
-    pub video_time: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: String, // pending, processing, completed or failed
+    pub progress: i32,  // percent
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub video_id: Option<i32>, // the new video, once completed
+    #[serde(skip_serializing)]
+    pub lease_expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CompilationSegment {
+    pub compilation_id: i32,
+    pub position: i32,
+    pub source_video_id: i32,
+    pub start_ms: i32,
+    pub end_ms: i32,
+}
+
+// Query of a URL from signed_urls
+#[derive(Debug, Deserialize)]
+pub struct SignedUrlQuery {
+    pub v: Option<String>, // thumbnail version
+    pub expires: Option<i64>,
+    pub signature: Option<String>,
+    pub playback_token: Option<String>, // see playback_tokens
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaybackTokenQuery {
+    pub playback_token: Option<String>, // see playback_tokens
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HlsKeyQuery {
+    pub index: i32,
+    pub token: Option<String>, // for players that can't set an Authorization header
+}
+
+// The previous token of a stream being renewed, or of one being stopped
+#[derive(Debug, Deserialize)]
+pub struct PlaybackTokenRequest {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaybackTokenResponse {
+    pub token: String,
+    pub stream_id: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UploadSession {
+    pub id: String,
+    pub user_id: i32,
+    pub s3_key: String,
+    pub s3_upload_id: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub parts: serde_json::Value, // [{"part_number": 1, "e_tag": "..."}]
+    #[serde(skip)]
+    pub pending_data: Vec<u8>,
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub category_ids: Vec<i32>, // assigned to the video once the upload completes
+    pub video_id: Option<i32>,
     pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadRequest {
+    pub filename: String,
+    pub size: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(alias = "category_ids", default)]
+    pub category_ids: Vec<i32>, // primary first, up to MAX_VIDEO_CATEGORIES
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub icon_svg: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CommentRequest {
     pub text: String,
-    #[serde(rename = "videoTime")]
+    #[serde(alias = "video_time")]
     pub video_time: i32,
 }
 
+// Order of /api/comments/{video_id}. Overlay clients replay comments along the video, so
+// video_time stays the default.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentSort {
+    Top,
+    Newest,
+    #[default]
+    Timestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentListQuery {
+    pub sort: Option<CommentSort>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentExportQuery {
+    pub format: Option<String>, // srt, vtt or json (default)
+}
+
+// An up (1) or down (-1) vote on a comment
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentVoteRequest {
+    pub value: i16,
+}
+
+// Token claims are signed as they are; renaming a field would invalidate every issued token
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub user_id: i32,
     pub exp: usize,
+    // user_sessions id; tokens without one can't be revoked and just expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    // Unique per token, for denying a single token (see sessions::deny_token)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
 }
 
+// Claims of a watch party guest token. They have no user_id, so a guest token never passes for
+// a login token (or the other way round).
 #[derive(Debug, Serialize, Deserialize)]
+pub struct GuestClaims {
+    pub guest_id: i32,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UserSession {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub current: bool, // the session the request was made with
+}
+
+// A user's preferences. Only the fields the user has set are stored; the rest read as their
+// defaults, so changing a default reaches everyone who never chose otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UserSettings {
+    pub theme: ThemeSetting,
+    #[serde(alias = "playback_speed")]
+    pub playback_speed: f64,
+    pub autoplay: bool,
+    #[serde(alias = "preferred_quality")]
+    pub preferred_quality: String, // "auto" or a rendition name, e.g. "720p"
+    #[serde(alias = "captions_language")]
+    pub captions_language: Option<String>, // None when captions are off
+    pub volume: f64,
+    pub watermark: WatermarkSettings,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        UserSettings {
+            theme: ThemeSetting::default(),
+            playback_speed: 1.0,
+            autoplay: false,
+            preferred_quality: "auto".to_string(),
+            captions_language: None,
+            volume: 1.0,
+            watermark: WatermarkSettings::default(),
+        }
+    }
+}
+
+// Whether and how the uploader's watermark image is burned into their videos' renditions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatermarkSettings {
+    pub enabled: bool,
+    pub position: WatermarkPosition,
+    pub opacity: f64,
+}
+
+impl Default for WatermarkSettings {
+    fn default() -> Self {
+        WatermarkSettings { enabled: false, position: WatermarkPosition::BottomRight, opacity: 0.8 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// One of the frontend's predefined themes by name ("system" follows the OS), or a custom theme
+// with every color given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeSetting {
+    pub name: String,
+    #[serde(alias = "is_custom", default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_custom: bool,
+    #[serde(alias = "is_dark", default, skip_serializing_if = "Option::is_none")]
+    pub is_dark: Option<bool>,
+    #[serde(flatten)]
+    pub colors: std::collections::BTreeMap<String, String>,
+}
+
+impl Default for ThemeSetting {
+    fn default() -> Self {
+        ThemeSetting { name: "system".to_string(), is_custom: false, is_dark: None, colors: Default::default() }
+    }
+}
+
+// Fields left out are unchanged. An empty captionsLanguage turns captions off.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserSettingsRequest {
-    pub theme: Option<serde_json::Value>,
+    pub theme: Option<ThemeSetting>,
+    #[serde(alias = "playback_speed")]
+    pub playback_speed: Option<f64>,
+    pub autoplay: Option<bool>,
+    #[serde(alias = "preferred_quality")]
+    pub preferred_quality: Option<String>,
+    #[serde(alias = "captions_language")]
+    pub captions_language: Option<String>,
+    pub volume: Option<f64>,
+    pub watermark: Option<WatermarkSettings>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SearchHistoryEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub query: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub query: String,
+    pub notify: bool,
+    pub last_checked_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedSearchRequest {
+    pub name: String,
+    pub query: String,
+    pub notify: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchParty {
+    pub video_id: i32,
+    pub host_user_id: i32,
+    pub invite_only: bool,
+    pub created_at: NaiveDateTime,
+    pub scheduled_start: Option<NaiveDateTime>, // playback is locked until then
+    pub started_at: Option<NaiveDateTime>,      // when the server issued the scheduled play
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchPartyInvite {
+    pub id: i32,
+    pub video_id: i32,
+    pub token: String,
+    pub invited_user_id: Option<i32>, // None for shareable invite links
+    pub created_by: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchPartyInviteRequest {
+    #[serde(alias = "user_ids")]
+    pub user_ids: Option<Vec<i32>>,
+    #[serde(alias = "expires_in_hours")]
+    pub expires_in_hours: Option<i64>,
+    #[serde(alias = "invite_only")]
+    pub invite_only: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OEmbedQuery {
+    pub url: String,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+    pub format: Option<String>, // only json is supported
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatchPartyJoinQuery {
+    pub invite: Option<String>,
+}
+
+// A null scheduledStart cancels the schedule, unlocking playback
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchPartyScheduleRequest {
+    #[serde(alias = "scheduled_start")]
+    pub scheduled_start: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// A null premiereAt cancels the premiere, opening playback straight away
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PremiereRequest {
+    #[serde(alias = "premiere_at")]
+    pub premiere_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchPartySession {
+    pub id: i32,
+    pub video_id: i32,
+    pub host_user_id: Option<i32>,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>, // None while the party is still running
+    pub peak_participants: i32,
+}
+
+// A watch party session as seen in a participant's history
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchPartyHistoryEntry {
+    pub session_id: i32,
+    pub video_id: i32,
+    pub video_title: String,
+    pub host_user_id: Option<i32>,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+    pub peak_participants: i32,
+    pub participant_count: i64,
+    pub joined_at: NaiveDateTime,
+    pub polls: serde_json::Value, // results of the polls the host saved, in the order they closed
+}
+
+// Someone taking part in watch parties without an account
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchPartyGuest {
+    pub id: i32,
+    pub display_name: String,
+    pub created_at: NaiveDateTime,
+    pub claimed_by: Option<i32>, // the account the guest was merged into
+    pub claimed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchPartyGuestRequest {
+    #[serde(alias = "display_name")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchPartyGuestClaimRequest {
+    #[serde(alias = "guest_token")]
+    pub guest_token: String,
+}
+
+// A guest currently in a video's watch party. participant_id is the id their messages carry.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchPartyGuestPresence {
+    #[serde(alias = "participant_id")]
+    pub participant_id: i32,
+    #[serde(alias = "display_name")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WatchPartyVideoStats {
+    pub video_id: i32,
+    pub party_count: i64,
+    pub participant_count: i64,
+    pub peak_participants: i32,
+    pub last_party_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: i32,
+    pub user_id: i32,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String, // only returned when the webhook is created
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: Option<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String, // pending, succeeded or failed
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub read_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+// A browser registered for Web Push. The keys are only used to encrypt payloads, so they are
+// never sent back.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PushSubscription {
+    pub id: i32,
+    pub user_id: i32,
+    pub endpoint: String,
+    #[serde(skip_serializing)]
+    pub p256dh: String,
+    #[serde(skip_serializing)]
+    pub auth: String,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// The browser's PushSubscription as its toJSON() gives it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushSubscriptionRequest {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushUnsubscribeRequest {
+    pub endpoint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    Newest,
+    Oldest,
+    MostViewed,
+    Title,
+    Duration,
+}
+
+// Narrows /api/videos/search/{query} to a tag or category
+#[derive(Debug, Deserialize)]
+pub struct SearchFilterQuery {
+    pub tag: Option<String>,
+    pub category: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvancedSearchRequest {
+    pub text: Option<String>,
+    #[serde(alias = "tags_all")]
+    pub tags_all: Option<Vec<String>>,
+    #[serde(alias = "tags_any")]
+    pub tags_any: Option<Vec<String>>,
+    #[serde(alias = "category_id")]
+    pub category_id: Option<i32>,
+    #[serde(alias = "min_duration")]
+    pub min_duration: Option<i32>,
+    #[serde(alias = "max_duration")]
+    pub max_duration: Option<i32>,
+    #[serde(alias = "uploaded_after")]
+    pub uploaded_after: Option<NaiveDateTime>,
+    #[serde(alias = "uploaded_before")]
+    pub uploaded_before: Option<NaiveDateTime>,
+    #[serde(alias = "uploaded_by")]
+    pub uploaded_by: Option<i32>,
+    pub sort: Option<SearchSort>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
 }
@@ -0,0 +1,150 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use futures::future::BoxFuture;
+use log::info;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+pub use videostreaming_models::moderation::{initial_status, STATUS_APPROVED, STATUS_FLAGGED, STATUS_PENDING};
+
+type ModerationResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Frames at or above this score flag the video, unless MODERATION_THRESHOLD says otherwise
+const DEFAULT_THRESHOLD: f64 = 0.8;
+
+// Frames sampled per video when MODERATION_FRAMES is not set
+const DEFAULT_FRAME_COUNT: usize = 6;
+
+// Scores a single frame for unsafe content, from 0.0 (clean) to 1.0 (certainly unsafe)
+pub trait ContentClassifier: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn classify<'a>(&'a self, frame: &'a Path) -> BoxFuture<'a, ModerationResult<f64>>;
+}
+
+// Posts each JPEG frame to MODERATION_API_URL and reads {"score": <0..1>} back
+pub struct HttpClassifier {
+    url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct HttpClassifierResponse {
+    score: f64,
+}
+
+impl ContentClassifier for HttpClassifier {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn classify<'a>(&'a self, frame: &'a Path) -> BoxFuture<'a, ModerationResult<f64>> {
+        Box::pin(async move {
+            let body = tokio::fs::read(frame).await?;
+            let mut request = self.client.post(&self.url).header("Content-Type", "image/jpeg").body(body);
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request.send().await?.error_for_status()?;
+            let result: HttpClassifierResponse = response.json().await?;
+            Ok(result.score)
+        })
+    }
+}
+
+// The classifier selected by MODERATION_CLASSIFIER. Moderation is off (uploads are approved
+// straight away) when it is unset.
+pub fn classifier_from_env() -> Option<Box<dyn ContentClassifier>> {
+    match env::var("MODERATION_CLASSIFIER").as_deref() {
+        Ok("http") => Some(Box::new(HttpClassifier {
+            url: env::var("MODERATION_API_URL").ok()?,
+            api_key: env::var("MODERATION_API_KEY").ok(),
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(30))
+                .build()
+                .ok()?,
+        })),
+        _ => None,
+    }
+}
+
+pub fn is_enabled() -> bool {
+    classifier_from_env().is_some()
+}
+
+pub fn frame_count() -> usize {
+    env::var("MODERATION_FRAMES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(DEFAULT_FRAME_COUNT)
+}
+
+fn threshold() -> f64 {
+    env::var("MODERATION_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub status: &'static str,
+    pub reason: Option<String>,
+    pub max_score: f64,
+}
+
+// Classify every frame and flag the video if any of them reaches the threshold
+pub async fn classify_frames(classifier: &dyn ContentClassifier, frames: &[(f64, PathBuf)]) -> ModerationResult<Verdict> {
+    let threshold = threshold();
+    let mut worst: Option<(f64, f64)> = None; // (offset, score)
+    for (offset, frame) in frames {
+        let score = classifier.classify(frame).await?;
+        if worst.is_none_or(|(_, s)| score > s) {
+            worst = Some((*offset, score));
+        }
+    }
+
+    Ok(match worst {
+        Some((offset, score)) if score >= threshold => Verdict {
+            status: STATUS_FLAGGED,
+            reason: Some(format!("{} classifier scored the frame at {:.1}s {:.2}", classifier.name(), offset, score)),
+            max_score: score,
+        },
+        Some((_, score)) => Verdict { status: STATUS_APPROVED, reason: None, max_score: score },
+        None => Verdict { status: STATUS_APPROVED, reason: Some("No frames to classify".to_string()), max_score: 0.0 },
+    })
+}
+
+// Record the classifier's verdict. An admin's decision always stands, so videos an admin has
+// already moderated are left alone.
+pub async fn apply_verdict(db_pool: &PgPool, video_id: i32, verdict: &Verdict) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE videos SET moderation_status = $1, moderation_reason = $2, moderated_at = $3 WHERE id = $4 AND moderated_by IS NULL"
+    )
+    .bind(verdict.status)
+    .bind(&verdict.reason)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(video_id)
+    .execute(db_pool)
+    .await?;
+
+    info!("Moderation verdict for video ID {}: {} (max score {:.2})", video_id, verdict.status, verdict.max_score);
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn set_override(db_pool: &PgPool, video_id: i32, admin_id: i32, status: &str, reason: Option<&str>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE videos SET moderation_status = $1, moderation_reason = $2, moderated_at = $3, moderated_by = $4 WHERE id = $5 AND deleted_at IS NULL"
+    )
+    .bind(status)
+    .bind(reason)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(admin_id)
+    .bind(video_id)
+    .execute(db_pool)
+    .await?;
+
+    info!("Video {} moderation set to {} by admin {}", video_id, status, admin_id);
+    Ok(result.rows_affected() > 0)
+}
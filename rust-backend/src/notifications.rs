@@ -0,0 +1,58 @@
+use sqlx::{PgExecutor, PgPool};
+use log::info;
+
+use crate::models::Notification;
+
+// Store a notification for a user. Delivery (polling, WebSocket, etc.) reads from this table.
+// Users with a push subscription also get it through Web Push, see web_push. Takes a transaction
+// too, for notifications that must only exist if the rest of a change does.
+pub async fn create_notification<'c>(
+    executor: impl PgExecutor<'c>,
+    user_id: i32,
+    kind: &str,
+    payload: serde_json::Value,
+) -> Result<Notification, sqlx::Error> {
+    let notification = sqlx::query_as::<_, Notification>(
+        "INSERT INTO notifications (user_id, kind, payload, created_at, push_pending)
+         VALUES ($1, $2, $3, $4, EXISTS (SELECT 1 FROM push_subscriptions WHERE user_id = $1))
+         RETURNING *"
+    )
+    .bind(user_id)
+    .bind(kind)
+    .bind(&payload)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(executor)
+    .await?;
+
+    info!("Created {} notification {} for user {}", kind, notification.id, user_id);
+    Ok(notification)
+}
+
+pub async fn get_notifications(db_pool: &PgPool, user_id: i32, limit: i64) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as::<_, Notification>(
+        "SELECT * FROM notifications WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2"
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Notifications created after `after_id`, oldest first, for streaming delivery
+pub async fn get_notifications_after(db_pool: &PgPool, user_id: i32, after_id: i32, limit: i64) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as::<_, Notification>(
+        "SELECT * FROM notifications WHERE user_id = $1 AND id > $2 ORDER BY id ASC LIMIT $3"
+    )
+    .bind(user_id)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn latest_notification_id(db_pool: &PgPool, user_id: i32) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>("SELECT COALESCE(MAX(id), 0) FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(db_pool)
+        .await
+}
@@ -0,0 +1,107 @@
+use std::env;
+use serde::Serialize;
+
+use crate::models::Video;
+
+// Player size when the consumer sets no maxwidth/maxheight
+const DEFAULT_WIDTH: u32 = 640;
+const DEFAULT_HEIGHT: u32 = 360;
+
+// Thumbnails are scaled to at most 1280 wide; sources are assumed to be 16:9
+const THUMBNAIL_WIDTH: u32 = 1280;
+const THUMBNAIL_HEIGHT: u32 = 720;
+
+// Where the site is served, e.g. https://videos.example.com. The frontend and /api share this
+// origin behind nginx.
+pub fn public_base_url() -> String {
+    env::var("PUBLIC_BASE_URL")
+        .unwrap_or_else(|_| "http://localhost".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn provider_name() -> String {
+    env::var("PROVIDER_NAME").unwrap_or_else(|_| "VideoStreaming".to_string())
+}
+
+// The video a page URL on this site points at: /video/{id} or the /embed/{id} player
+pub fn video_id_from_url(url: &str, base_url: &str) -> Option<i32> {
+    let base = base_url.trim_end_matches('/');
+    let rest = url.get(..base.len()).filter(|prefix| prefix.eq_ignore_ascii_case(base)).map(|_| &url[base.len()..])?;
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let id = path.strip_prefix("/video/").or_else(|| path.strip_prefix("/embed/"))?;
+    id.trim_end_matches('/').parse::<i32>().ok().filter(|id| *id > 0)
+}
+
+// The largest 16:9 player no bigger than the default that fits the consumer's limits
+pub fn embed_size(max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let mut width = DEFAULT_WIDTH;
+    if let Some(max_width) = max_width.filter(|w| *w > 0) {
+        width = width.min(max_width);
+    }
+    if let Some(max_height) = max_height.filter(|h| *h > 0) {
+        width = width.min(max_height * 16 / 9);
+    }
+    let width = width.max(16);
+    (width, (width * DEFAULT_HEIGHT / DEFAULT_WIDTH).max(9))
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// oEmbed 1.0 "video" response
+#[derive(Debug, Serialize)]
+pub struct OEmbed {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_name: Option<String>,
+    pub provider_name: String,
+    pub provider_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_height: Option<u32>,
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OEmbed {
+    pub fn for_video(video: &Video, author_name: Option<String>, base_url: &str, size: (u32, u32)) -> Self {
+        let (width, height) = size;
+        let embed_url = format!("{}/embed/{}", base_url, video.id);
+        let html = format!(
+            "<iframe src=\"{}\" width=\"{}\" height=\"{}\" title=\"{}\" frameborder=\"0\" allow=\"autoplay; fullscreen; picture-in-picture\" allowfullscreen></iframe>",
+            embed_url, width, height, escape_attribute(&video.title)
+        );
+        let thumbnail_url = video.thumbnail_url
+            .as_deref()
+            .and_then(|key| key.rsplit('/').next())
+            .map(|name| format!("{}/api/thumbnails/{}", base_url, name));
+
+        Self {
+            version: "1.0",
+            kind: "video",
+            title: video.title.clone(),
+            author_name,
+            provider_name: provider_name(),
+            provider_url: base_url.to_string(),
+            thumbnail_width: thumbnail_url.as_ref().map(|_| THUMBNAIL_WIDTH),
+            thumbnail_height: thumbnail_url.as_ref().map(|_| THUMBNAIL_HEIGHT),
+            thumbnail_url,
+            html,
+            width,
+            height,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::env;
+use log::{error, warn};
+use redis::AsyncCommands;
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::signed_urls;
+
+// Account-sharing limits for private deployments. With PLAYBACK_TOKENS_REQUIRED=on the stream,
+// HLS playlist, download and audio track endpoints refuse requests without a playback token
+// (?playback_token=), which only signed-in users get from POST /api/videos/{id}/playback-token.
+// A token is for one video and one stream and expires after PLAYBACK_TOKEN_TTL_SECS; players
+// renew it with the previous one to keep their stream going.
+//
+// MAX_CONCURRENT_STREAMS caps the streams an account has going at once. Each stream is kept in
+// Redis under playback_streams:{user_id} until its latest token expires or the player stops it,
+// and a token for another stream is refused with a 429 while the account is at the cap. Without
+// Redis nothing is counted. Playback already started isn't cut off; encrypted HLS keys and
+// segments don't need a token.
+
+// Token lifetime when PLAYBACK_TOKEN_TTL_SECS is not set
+pub const DEFAULT_TTL_SECS: i64 = 5 * 60;
+
+// Forgets the account's expired streams, then adds or renews `stream` unless that would take the
+// account past the cap. Returns 0 when it did, else the expiry of the stream ending soonest.
+const ADMIT_STREAM_SCRIPT: &str = r#"
+redis.call("ZREMRANGEBYSCORE", KEYS[1], "-inf", ARGV[1])
+local max = tonumber(ARGV[4])
+if max > 0 and not redis.call("ZSCORE", KEYS[1], ARGV[3]) and redis.call("ZCARD", KEYS[1]) >= max then
+    local soonest = redis.call("ZRANGE", KEYS[1], 0, 0, "WITHSCORES")
+    return tonumber(soonest[2])
+end
+redis.call("ZADD", KEYS[1], ARGV[2], ARGV[3])
+redis.call("EXPIREAT", KEYS[1], ARGV[2])
+return 0
+"#;
+
+pub fn required() -> bool {
+    env::var("PLAYBACK_TOKENS_REQUIRED").is_ok_and(|v| matches!(v.trim().to_lowercase().as_str(), "on" | "true" | "1"))
+}
+
+pub fn ttl_secs() -> i64 {
+    env::var("PLAYBACK_TOKEN_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_TTL_SECS)
+}
+
+// The cap on an account's concurrent streams, None when there isn't one
+pub fn max_concurrent_streams() -> Option<u32> {
+    env::var("MAX_CONCURRENT_STREAMS").ok().and_then(|v| v.parse::<u32>().ok()).filter(|max| *max > 0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackToken {
+    pub user_id: i32,
+    pub stream_id: String,
+    pub expires: i64,
+}
+
+// What a token signs: the video is part of it, so a token can't be used for another one
+fn signed_path(video_id: i32, user_id: i32, stream_id: &str) -> String {
+    format!("playback:{}:{}:{}", video_id, user_id, stream_id)
+}
+
+// A token for `stream_id` of `user_id` playing `video_id`, as {user}.{stream}.{expires}.{signature}
+pub fn issue(video_id: i32, user_id: i32, stream_id: &str, expires: i64) -> String {
+    let signature = signed_urls::sign(&signed_path(video_id, user_id, stream_id), expires);
+    format!("{}.{}.{}.{}", user_id, stream_id, expires, signature)
+}
+
+pub fn new_stream_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+// The token's contents if it was issued for `video_id`, whether or not it has expired
+pub fn parse(token: &str, video_id: i32) -> Option<PlaybackToken> {
+    let mut parts = token.split('.');
+    let (Some(user_id), Some(stream_id), Some(expires), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let user_id = user_id.parse::<i32>().ok()?;
+    let expires = expires.parse::<i64>().ok()?;
+    if stream_id.is_empty() || !signed_urls::signature_matches(&signed_path(video_id, user_id, stream_id), expires, signature) {
+        return None;
+    }
+    Some(PlaybackToken { user_id, stream_id: stream_id.to_string(), expires })
+}
+
+pub fn verify(token: &str, video_id: i32, now: i64) -> Result<PlaybackToken, ApiError> {
+    let token = parse(token, video_id).ok_or_else(|| ApiError::Forbidden("Invalid playback token".to_string()))?;
+    if now >= token.expires {
+        return Err(ApiError::Forbidden("This playback token has expired".to_string()));
+    }
+    Ok(token)
+}
+
+pub fn streams_key(user_id: i32) -> String {
+    format!("playback_streams:{}", user_id)
+}
+
+pub fn limit_reached(max: u32, retry_after_secs: u64) -> ApiError {
+    let message = if max == 1 {
+        "This account is already playing a video somewhere else".to_string()
+    } else {
+        format!("This account is already playing {} videos, the most it may at once", max)
+    };
+    ApiError::TooManyRequests { message, retry_after_secs }
+}
+
+// Count the stream against its account, refusing it while the account is at the cap. Tokens are
+// still handed out when Redis can't be reached.
+pub async fn admit(redis_client: Option<&redis::Client>, token: &PlaybackToken, now: i64) -> Result<(), ApiError> {
+    let (Some(redis_client), Some(max)) = (redis_client, max_concurrent_streams()) else {
+        return Ok(());
+    };
+    let result: redis::RedisResult<i64> = circuit_breaker::redis(redis_client, |mut conn| async move {
+        redis::Script::new(ADMIT_STREAM_SCRIPT)
+            .key(streams_key(token.user_id))
+            .arg(now)
+            .arg(token.expires)
+            .arg(&token.stream_id)
+            .arg(max)
+            .invoke_async(&mut conn)
+            .await
+    })
+    .await;
+    match result {
+        Ok(0) => Ok(()),
+        Ok(soonest_expiry) => {
+            warn!("User {} is at the limit of {} concurrent streams", token.user_id, max);
+            Err(limit_reached(max, (soonest_expiry - now).max(1) as u64))
+        }
+        Err(e) => {
+            error!("Concurrent stream tracking unavailable in Redis: {:?}", e);
+            Ok(())
+        }
+    }
+}
+
+// Whether the token's stream is still going, i.e. the player hasn't stopped it
+pub async fn is_active(redis_client: Option<&redis::Client>, token: &PlaybackToken) -> bool {
+    let (Some(redis_client), Some(_)) = (redis_client, max_concurrent_streams()) else {
+        return true;
+    };
+    let score: redis::RedisResult<Option<f64>> = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.zscore(streams_key(token.user_id), &token.stream_id).await
+    })
+    .await;
+    match score {
+        Ok(score) => score.is_some(),
+        Err(e) => {
+            error!("Concurrent stream tracking unavailable in Redis: {:?}", e);
+            true
+        }
+    }
+}
+
+// Free the stream's place, for players stopping before their token expires
+pub async fn release(redis_client: Option<&redis::Client>, token: &PlaybackToken) {
+    let Some(redis_client) = redis_client else {
+        return;
+    };
+    let result: redis::RedisResult<()> = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.zrem(streams_key(token.user_id), &token.stream_id).await
+    })
+    .await;
+    if let Err(e) = result {
+        error!("Failed to release stream {} of user {}: {:?}", token.stream_id, token.user_id, e);
+    }
+}
+
+// Refuse a playback request without a valid token while tokens are required
+pub async fn check(redis_client: Option<&redis::Client>, video_id: i32, token: Option<&str>, now: i64) -> Result<(), ApiError> {
+    if !required() {
+        return Ok(());
+    }
+    let token = token.ok_or_else(|| ApiError::Forbidden("A playback token is required".to_string()))?;
+    let token = verify(token, video_id, now)?;
+    if !is_active(redis_client, &token).await {
+        return Err(ApiError::Forbidden("This stream was stopped".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::info;
+use sqlx::PgPool;
+
+use crate::error::ApiError;
+use crate::models::{Video, WatchParty};
+use crate::watch_parties;
+
+// Uploaders can premiere a video at a set time. Until then it is listed with a countdown
+// (premieres_in_secs) but only its uploader can play it, and its watch party room is scheduled
+// for the premiere, so whoever gathers there is started together by the watch party scheduler.
+
+pub fn upcoming(video: &Video, now: NaiveDateTime) -> bool {
+    video.premiere_at.is_some_and(|at| at > now)
+}
+
+// Refuse media for a video that hasn't premiered yet, except to its uploader
+pub fn check_started(video: &Video, user_id: Option<i32>) -> Result<(), ApiError> {
+    let Some(premiere_at) = video.premiere_at.filter(|_| upcoming(video, Utc::now().naive_utc())) else {
+        return Ok(());
+    };
+    if user_id.is_some() && user_id == video.uploaded_by {
+        return Ok(());
+    }
+    Err(ApiError::Forbidden(format!("This video premieres at {}", premiere_at.and_utc().to_rfc3339())))
+}
+
+// A premiere time as requested, which has to be in the future and no further off than a watch
+// party can be scheduled
+pub fn validate_premiere_at(premiere_at: DateTime<Utc>, now: DateTime<Utc>) -> Result<NaiveDateTime, String> {
+    if premiere_at <= now {
+        return Err("premiereAt must be in the future".to_string());
+    }
+    if premiere_at > now + chrono::Duration::days(watch_parties::MAX_SCHEDULE_DAYS) {
+        return Err(format!("premiereAt must be within {} days", watch_parties::MAX_SCHEDULE_DAYS));
+    }
+    Ok(premiere_at.naive_utc())
+}
+
+// Set or cancel (None) the video's premiere, scheduling its watch party room to match. A room
+// nobody has opened yet is created with `host_user_id` as host.
+pub async fn schedule(
+    db_pool: &PgPool,
+    video: &Video,
+    host_user_id: i32,
+    premiere_at: Option<NaiveDateTime>,
+) -> Result<(Video, Option<WatchParty>), sqlx::Error> {
+    let updated = sqlx::query_as::<_, Video>("UPDATE videos SET premiere_at = $1 WHERE id = $2 RETURNING *")
+        .bind(premiere_at)
+        .bind(video.id)
+        .fetch_one(db_pool)
+        .await?;
+
+    let room = match premiere_at {
+        Some(at) => {
+            watch_parties::get_or_create_room(db_pool, video.id, host_user_id).await?;
+            Some(watch_parties::set_scheduled_start(db_pool, video.id, Some(at)).await?)
+        }
+        // Only drop the room's schedule if it was the premiere's
+        None => match watch_parties::get_room(db_pool, video.id).await? {
+            Some(room) if room.scheduled_start.is_some() && room.scheduled_start == video.premiere_at => {
+                Some(watch_parties::set_scheduled_start(db_pool, video.id, None).await?)
+            }
+            room => room,
+        },
+    };
+
+    match premiere_at {
+        Some(at) => info!("Video {} premieres at {}", video.id, at),
+        None => info!("Premiere of video {} cancelled", video.id),
+    }
+    Ok((updated, room))
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::time::Duration;
+use log::error;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::job_queue::MediaJobKind;
+use crate::models::ProcessingJob;
+
+// A video's processing status follows its jobs: uploaded until one of them starts, processing
+// while any is queued or running, ready once all are done, and failed if one was given up on.
+// Reprocessing a video runs through the same states again.
+
+pub const STATUS_UPLOADED: &str = "uploaded";
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_READY: &str = "ready";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const STATUSES: [&str; 4] = [STATUS_UPLOADED, STATUS_PROCESSING, STATUS_READY, STATUS_FAILED];
+
+pub const JOB_QUEUED: &str = "queued";
+pub const JOB_RUNNING: &str = "running";
+pub const JOB_DONE: &str = "done";
+pub const JOB_FAILED: &str = "failed";
+
+// What status streams send whenever a video's processing status changes
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProcessingUpdate {
+    pub video_id: i32,
+    pub processing_status: String,
+    pub jobs: Vec<ProcessingJobState>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProcessingJobState {
+    pub kind: String,
+    pub state: String,
+    pub error: Option<String>,
+}
+
+// How often status streams check for changes, from PROCESSING_STATUS_POLL_SECS
+pub fn poll_interval() -> Duration {
+    let secs = env::var("PROCESSING_STATUS_POLL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(2);
+    Duration::from_secs(secs)
+}
+
+// Validate a ?processing_status= filter
+pub fn parse_status(status: &str) -> Result<&'static str, String> {
+    STATUSES
+        .into_iter()
+        .find(|s| s.eq_ignore_ascii_case(status.trim()))
+        .ok_or_else(|| format!("processing_status must be one of {}", STATUSES.join(", ")))
+}
+
+// Record a job's state and derive the video's status from all of its jobs. Returns the status.
+pub async fn record(db_pool: &PgPool, video_id: i32, kind: MediaJobKind, state: &str, error: Option<&str>) -> Result<String, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    // Workers finishing jobs of the same video at once take turns, so the last one sees every job
+    sqlx::query("SELECT 1 FROM videos WHERE id = $1 FOR UPDATE")
+        .bind(video_id)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO video_processing_jobs (video_id, kind, state, error, updated_at) VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (video_id, kind) DO UPDATE SET state = EXCLUDED.state, error = EXCLUDED.error, updated_at = EXCLUDED.updated_at"
+    )
+    .bind(video_id)
+    .bind(kind.as_str())
+    .bind(state)
+    .bind(error)
+    .execute(&mut tx)
+    .await?;
+
+    let status: String = sqlx::query_scalar(
+        "SELECT CASE
+             WHEN EXISTS (SELECT 1 FROM video_processing_jobs WHERE video_id = $1 AND state = 'failed') THEN 'failed'
+             WHEN NOT EXISTS (SELECT 1 FROM video_processing_jobs WHERE video_id = $1 AND state IN ('queued', 'running')) THEN 'ready'
+             WHEN EXISTS (SELECT 1 FROM video_processing_jobs WHERE video_id = $1 AND state IN ('running', 'done')) THEN 'processing'
+             ELSE 'uploaded'
+         END"
+    )
+    .bind(video_id)
+    .fetch_one(&mut tx)
+    .await?;
+    // Only on change, as every update bumps updated_at and with it the video list's ETag
+    sqlx::query("UPDATE videos SET processing_status = $2 WHERE id = $1 AND processing_status <> $2")
+        .bind(video_id)
+        .bind(&status)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(status)
+}
+
+// Like record, for the job pipeline: tracking processing must not stop the job itself
+pub async fn track(db_pool: &PgPool, video_id: i32, kind: MediaJobKind, state: &str, error: Option<&str>) {
+    if let Err(e) = record(db_pool, video_id, kind, state, error).await {
+        error!("Failed to record {:?} job as {} for video ID {}: {:?}", kind, state, video_id, e);
+    }
+}
+
+// Start over when every job is queued again, so a reprocessed video doesn't keep the results of
+// jobs that no longer run
+pub async fn restart(db_pool: &PgPool, video_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM video_processing_jobs WHERE video_id = $1")
+        .bind(video_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn jobs(db_pool: &PgPool, video_id: i32) -> Result<Vec<ProcessingJob>, sqlx::Error> {
+    sqlx::query_as::<_, ProcessingJob>("SELECT * FROM video_processing_jobs WHERE video_id = $1 ORDER BY kind")
+        .bind(video_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+// The video's status and jobs, or None if there is no such video
+pub async fn current(db_pool: &PgPool, video_id: i32) -> Result<Option<ProcessingUpdate>, sqlx::Error> {
+    let status: Option<String> = sqlx::query_scalar("SELECT processing_status FROM videos WHERE id = $1 AND deleted_at IS NULL")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await?;
+    let Some(processing_status) = status else {
+        return Ok(None);
+    };
+    let jobs = jobs(db_pool, video_id)
+        .await?
+        .into_iter()
+        .map(|job| ProcessingJobState { kind: job.kind, state: job.state, error: job.error })
+        .collect();
+    Ok(Some(ProcessingUpdate { video_id, processing_status, jobs }))
+}
@@ -0,0 +1,255 @@
+use std::env;
+use std::time::Duration;
+use chrono::NaiveDateTime;
+use futures::TryStreamExt;
+use log::{error, info};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use tokio::time::sleep;
+
+use crate::exports::csv_line;
+use crate::feeds;
+use crate::models::{RecommendationImport, Video};
+use crate::storage::{ObjectStore, Storage};
+
+type RecommendationResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Training data out, recommendations back in. Every RECOMMENDATION_EXPORT_INTERVAL_SECS the
+// interactions of signed-in users over the last RECOMMENDATION_EXPORT_DAYS are written to S3 as
+// a CSV matrix, one row per user and video. Lists a model computes from them are imported by
+// admins and served by /api/feed/home, topped up with popular videos.
+
+const DEFAULT_EXPORT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_EXPORT_DAYS: i64 = 90;
+const EXPORT_PREFIX: &str = "exports/interactions";
+
+pub const MAX_LISTS_PER_IMPORT: usize = 10_000;
+pub const MAX_VIDEOS_PER_LIST: usize = 200;
+const MAX_MODEL_CHARS: usize = 100;
+
+pub const DEFAULT_HOME_LIMIT: i64 = 30;
+pub const MAX_HOME_LIMIT: i64 = 100;
+
+pub const INTERACTION_FIELDS: [&str; 7] = ["user_id", "video_id", "views", "watched_secs", "watch_fraction", "comments", "last_interaction_at"];
+
+// Views and comments count once each; watched seconds are summed from playback beacons, so the
+// watch fraction can pass 1 for rewatched videos and is capped there
+const INTERACTIONS_SQL: &str = "
+    WITH views AS (
+        SELECT user_id, video_id, COUNT(*) AS views, MAX(viewed_at) AS last_at
+        FROM video_views WHERE user_id IS NOT NULL AND viewed_at >= $1 GROUP BY user_id, video_id
+    ), watched AS (
+        SELECT user_id, video_id, SUM(watched_secs) AS watched_secs, MAX(created_at) AS last_at
+        FROM playback_events WHERE user_id IS NOT NULL AND watched_secs IS NOT NULL AND created_at >= $1 GROUP BY user_id, video_id
+    ), commented AS (
+        SELECT user_id, video_id, COUNT(*) AS comments, MAX(created_at) AS last_at
+        FROM comments WHERE created_at >= $1 GROUP BY user_id, video_id
+    )
+    SELECT i.user_id::text, i.video_id::text,
+           COALESCE(i.views, 0)::text,
+           round(COALESCE(i.watched_secs, 0)::numeric, 1)::text,
+           round(LEAST(COALESCE(i.watched_secs, 0) / NULLIF(v.duration, 0), 1)::numeric, 3)::text,
+           COALESCE(i.comments, 0)::text,
+           to_char(i.last_at, 'YYYY-MM-DD\"T\"HH24:MI:SS')
+    FROM (
+        SELECT user_id, video_id, views.views, watched.watched_secs, commented.comments,
+               GREATEST(views.last_at, watched.last_at, commented.last_at) AS last_at
+        FROM views
+        FULL JOIN watched USING (user_id, video_id)
+        FULL JOIN commented USING (user_id, video_id)
+    ) i
+    JOIN videos v ON v.id = i.video_id AND v.deleted_at IS NULL
+    ORDER BY i.user_id, i.video_id";
+
+fn export_interval() -> Duration {
+    let secs = env::var("RECOMMENDATION_EXPORT_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_EXPORT_INTERVAL_SECS);
+    Duration::from_secs(secs)
+}
+
+pub fn export_days() -> i64 {
+    env::var("RECOMMENDATION_EXPORT_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_EXPORT_DAYS)
+}
+
+pub fn export_key(now: NaiveDateTime) -> String {
+    format!("{}/{}.csv", EXPORT_PREFIX, now.format("%Y-%m-%dT%H%M%S"))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RecommendationExport {
+    pub id: i32,
+    pub s3_key: String,
+    pub row_count: i64,
+    pub window_start: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+// Write the interaction matrix to S3 and record it
+pub async fn export_interactions(db_pool: &PgPool, storage: &dyn ObjectStore, bucket: &str) -> RecommendationResult<RecommendationExport> {
+    let now = chrono::Utc::now().naive_utc();
+    let window_start = now - chrono::Duration::days(export_days());
+
+    let mut csv = csv_line(INTERACTION_FIELDS.iter().map(|f| Some(*f)));
+    let mut row_count: i64 = 0;
+    let mut rows = sqlx::query(INTERACTIONS_SQL).bind(window_start).fetch(db_pool);
+    while let Some(row) = rows.try_next().await? {
+        let values = (0..INTERACTION_FIELDS.len()).map(|i| row.try_get::<Option<&str>, _>(i)).collect::<Result<Vec<_>, _>>()?;
+        csv.push_str(&csv_line(values));
+        row_count += 1;
+    }
+    drop(rows);
+
+    let s3_key = export_key(now);
+    storage.put(bucket, &s3_key, csv.into_bytes().into(), "text/csv; charset=utf-8").await?;
+    let export = sqlx::query_as::<_, RecommendationExport>(
+        "INSERT INTO recommendation_exports (s3_key, row_count, window_start) VALUES ($1, $2, $3) RETURNING *"
+    )
+    .bind(&s3_key)
+    .bind(row_count)
+    .bind(window_start)
+    .fetch_one(db_pool)
+    .await?;
+    info!("Exported {} user interactions to {}", row_count, s3_key);
+    Ok(export)
+}
+
+pub async fn list_exports(db_pool: &PgPool) -> Result<Vec<RecommendationExport>, sqlx::Error> {
+    sqlx::query_as::<_, RecommendationExport>("SELECT * FROM recommendation_exports ORDER BY created_at DESC, id DESC LIMIT 100")
+        .fetch_all(db_pool)
+        .await
+}
+
+// Whether the last export is older than the interval. Processes sharing the database check
+// this, so a deployment exports about once per interval however many of them run.
+async fn export_due(db_pool: &PgPool, interval: Duration) -> Result<bool, sqlx::Error> {
+    let last: Option<NaiveDateTime> = sqlx::query_scalar("SELECT MAX(created_at) FROM recommendation_exports")
+        .fetch_one(db_pool)
+        .await?;
+    let interval = chrono::Duration::seconds(interval.as_secs() as i64);
+    Ok(last.is_none_or(|last| chrono::Utc::now().naive_utc() - last >= interval))
+}
+
+pub async fn run_recommendation_exporter(db_pool: PgPool, storage: Storage) {
+    let interval = export_interval();
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    info!("Starting recommendation exporter (interval: {} seconds)", interval.as_secs());
+
+    loop {
+        match export_due(&db_pool, interval).await {
+            Ok(true) => {
+                if let Err(e) = export_interactions(&db_pool, storage.as_ref(), &bucket).await {
+                    error!("Error exporting user interactions: {:?}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => error!("Error checking for a due interaction export: {:?}", e),
+        }
+        // Check again well within the interval, so a missed export isn't a whole interval late
+        sleep(interval.min(Duration::from_secs(3600))).await;
+    }
+}
+
+pub fn validate_import(import: &RecommendationImport) -> Result<(), String> {
+    if import.lists.is_empty() || import.lists.len() > MAX_LISTS_PER_IMPORT {
+        return Err(format!("Send between 1 and {} lists at a time", MAX_LISTS_PER_IMPORT));
+    }
+    if import.model.as_deref().is_some_and(|m| m.trim().is_empty() || m.chars().count() > MAX_MODEL_CHARS) {
+        return Err(format!("model must be 1 to {} characters", MAX_MODEL_CHARS));
+    }
+    if let Some(list) = import.lists.iter().find(|l| l.video_ids.len() > MAX_VIDEOS_PER_LIST) {
+        return Err(format!("The list for user {} has more than {} videos", list.user_id, MAX_VIDEOS_PER_LIST));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportSummary {
+    pub users: usize,
+    pub recommendations: u64, // videos that existed; unknown ones are skipped
+}
+
+// Replace the lists of the users imported for. Users that don't exist are skipped along with
+// their list, as are videos that don't.
+pub async fn import(db_pool: &PgPool, import: &RecommendationImport) -> Result<ImportSummary, sqlx::Error> {
+    let model = import.model.as_deref().map(str::trim);
+    let mut tx = db_pool.begin().await?;
+    let mut users = 0;
+    let mut recommendations = 0;
+    for list in &import.lists {
+        sqlx::query("DELETE FROM user_recommendations WHERE user_id = $1")
+            .bind(list.user_id)
+            .execute(&mut tx)
+            .await?;
+        let result = sqlx::query(
+            "INSERT INTO user_recommendations (user_id, video_id, rank, model)
+             SELECT $1, r.video_id, MIN(r.rank)::INTEGER, $3
+             FROM unnest($2::INT[]) WITH ORDINALITY AS r(video_id, rank)
+             JOIN videos v ON v.id = r.video_id
+             WHERE EXISTS (SELECT 1 FROM users WHERE id = $1)
+             GROUP BY r.video_id"
+        )
+        .bind(list.user_id)
+        .bind(&list.video_ids)
+        .bind(model)
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() > 0 {
+            users += 1;
+        }
+        recommendations += result.rows_affected();
+    }
+    tx.commit().await?;
+    Ok(ImportSummary { users, recommendations })
+}
+
+pub fn clamp_home_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_HOME_LIMIT).clamp(1, MAX_HOME_LIMIT)
+}
+
+// The user's imported recommendations that are still public, then the most viewed public
+// videos (the newest with the "recent" ranking) to fill up to `limit`. Anonymous visitors get
+// only the latter.
+pub async fn home_feed(db_pool: &PgPool, user_id: Option<i32>, limit: i64, ranking: &str) -> Result<Vec<Video>, sqlx::Error> {
+    let mut videos = match user_id {
+        Some(user_id) => sqlx::query_as::<_, Video>(&format!(
+            "SELECT videos.* FROM ({}) videos JOIN user_recommendations r ON r.video_id = videos.id
+             WHERE r.user_id = $1 ORDER BY r.rank LIMIT $2",
+            feeds::PUBLIC_VIDEOS_SQL
+        ))
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(db_pool)
+        .await?,
+        None => Vec::new(),
+    };
+
+    let missing = limit - videos.len() as i64;
+    if missing > 0 {
+        let seen: Vec<i32> = videos.iter().map(|v| v.id).collect();
+        let order_by = match ranking {
+            "recent" => "upload_date DESC NULLS LAST, id DESC",
+            _ => "view_count DESC NULLS LAST, upload_date DESC",
+        };
+        let popular = sqlx::query_as::<_, Video>(&format!(
+            "{} AND NOT (id = ANY($1)) ORDER BY {} LIMIT $2",
+            feeds::PUBLIC_VIDEOS_SQL,
+            order_by
+        ))
+        .bind(&seen)
+        .bind(missing)
+        .fetch_all(db_pool)
+        .await?;
+        videos.extend(popular);
+    }
+    Ok(videos)
+}
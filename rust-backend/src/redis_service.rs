@@ -4,6 +4,9 @@ use log::{info, error};
 use serde::{Serialize, Deserialize};
 use futures::StreamExt;
 
+use crate::circuit_breaker;
+use crate::ws_stats;
+
 // Define a struct for the message that will be published to Redis
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WatchPartyMessage {
@@ -13,6 +16,14 @@ pub struct WatchPartyMessage {
     pub action: String,
     pub time: Option<f64>,
     pub source_id: String,
+    // Only set on watchPartyReaction messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    // WebRTC signaling (watchPartySignal) is only delivered to target_user_id when it is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_user_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
 }
 
 // Initialize the Redis client with retry logic
@@ -47,7 +58,7 @@ pub fn init_redis_client() -> RedisResult<Client> {
 
 // Publish a message to a Redis channel
 pub async fn publish_message(client: &Client, channel: &str, message: &WatchPartyMessage) -> RedisResult<()> {
-    let mut con = client.get_async_connection().await?;
+    let mut con = circuit_breaker::redis_connection(client).await?;
     let message_json = serde_json::to_string(message).unwrap_or_else(|e| {
         error!("Failed to serialize message: {:?}", e);
         "{}".to_string()
@@ -58,8 +69,9 @@ pub async fn publish_message(client: &Client, channel: &str, message: &WatchPart
     Ok(())
 }
 
-// Subscribe to a Redis channel and process messages
-pub async fn subscribe_to_channel(client: &Client, channel: String, callback: impl Fn(WatchPartyMessage) + Send + 'static) -> RedisResult<()> {
+// Subscribe to a Redis channel and process messages until the callback returns false, i.e. once
+// whoever it delivers to has gone
+pub async fn subscribe_to_channel(client: &Client, channel: String, callback: impl Fn(WatchPartyMessage) -> bool + Send + 'static) -> RedisResult<()> {
     let client_clone = client.clone();
     
     // Run the subscription in a separate task
@@ -68,7 +80,7 @@ pub async fn subscribe_to_channel(client: &Client, channel: String, callback: im
         info!("Subscribing to Redis channel: {}", channel_name);
         
         // Create a pubsub connection
-        let conn = match client_clone.get_async_connection().await {
+        let conn = match circuit_breaker::redis_connection(&client_clone).await {
             Ok(conn) => conn,
             Err(e) => {
                 error!("Failed to get Redis connection: {:?}", e);
@@ -84,6 +96,8 @@ pub async fn subscribe_to_channel(client: &Client, channel: String, callback: im
             return;
         }
         
+        ws_stats::subscription_opened();
+
         // Process incoming messages
         let mut msg_stream = pubsub.on_message();
         while let Some(msg) = msg_stream.next().await {
@@ -99,13 +113,17 @@ pub async fn subscribe_to_channel(client: &Client, channel: String, callback: im
             match serde_json::from_str::<WatchPartyMessage>(&payload) {
                 Ok(message) => {
                     info!("Received message on channel {}: {:?}", channel_name, message);
-                    callback(message);
+                    if !callback(message) {
+                        break;
+                    }
                 },
                 Err(e) => {
                     error!("Failed to parse message: {:?}", e);
                 }
             }
         }
+        ws_stats::subscription_closed();
+        info!("Unsubscribed from Redis channel: {}", channel_name);
     });
     
     Ok(())
@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use log::{warn, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+
+// Latency histograms per route on this instance, and a warning for every request slower than
+// SLOW_REQUEST_MS listing the SQL it ran. The statements come from sqlx's own statement logging:
+// the logger installed by init_logger keeps those logged by the task serving a request. Work
+// the handler spawns onto other tasks, and time spent streaming a response body, aren't counted.
+
+pub const BUCKET_BOUNDS_MS: [u64; 12] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000];
+
+const DEFAULT_SLOW_REQUEST_MS: u64 = 1000;
+const DEFAULT_SLOW_QUERY_MS: u64 = 1000;
+const MAX_CAPTURED_STATEMENTS: usize = 50;
+
+// Requests that didn't match a route share one histogram, so scanners can't grow the map
+const UNMATCHED_ROUTE: &str = "(unmatched)";
+
+fn millis_from_env(name: &str, default: u64) -> Duration {
+    let ms = env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default);
+    Duration::from_millis(ms)
+}
+
+pub fn slow_request_threshold() -> Duration {
+    millis_from_env("SLOW_REQUEST_MS", DEFAULT_SLOW_REQUEST_MS)
+}
+
+// Statements slower than this are logged by sqlx at warn, whatever the request
+pub fn slow_query_threshold() -> Duration {
+    millis_from_env("SLOW_QUERY_MS", DEFAULT_SLOW_QUERY_MS)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Histogram {
+    pub count: u64,
+    pub errors: u64, // 5xx responses
+    pub total_ms: f64,
+    pub max_ms: f64,
+    // Requests per bucket: up to each of BUCKET_BOUNDS_MS, then one for anything slower
+    pub buckets: Vec<u64>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self { count: 0, errors: 0, total_ms: 0.0, max_ms: 0.0, buckets: vec![0; BUCKET_BOUNDS_MS.len() + 1] }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, elapsed: Duration, server_error: bool) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let bucket = BUCKET_BOUNDS_MS.iter().position(|bound| ms <= *bound as f64).unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.errors += u64::from(server_error);
+        self.total_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    // Upper bound of the bucket holding the q-quantile, or the slowest request for the last one
+    pub fn quantile_ms(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(BUCKET_BOUNDS_MS.get(bucket).map_or(self.max_ms, |bound| (*bound as f64).min(self.max_ms)));
+            }
+        }
+        Some(self.max_ms)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteStats {
+    pub method: String,
+    pub route: String,
+    pub count: u64,
+    pub errors: u64,
+    pub avg_ms: f64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    pub max_ms: f64,
+    pub buckets: Vec<u64>,
+}
+
+static ROUTES: LazyLock<Mutex<HashMap<(String, String), Histogram>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+tokio::task_local! {
+    static STATEMENTS: RefCell<Vec<String>>;
+}
+
+pub fn record(method: &str, route: &str, elapsed: Duration, server_error: bool) {
+    ROUTES.lock().unwrap()
+        .entry((method.to_string(), route.to_string()))
+        .or_default()
+        .record(elapsed, server_error);
+}
+
+// Every route seen since the process started, those taking the most time in total first
+pub fn snapshot() -> Vec<RouteStats> {
+    let routes = ROUTES.lock().unwrap();
+    let mut stats: Vec<RouteStats> = routes
+        .iter()
+        .map(|((method, route), histogram)| RouteStats {
+            method: method.clone(),
+            route: route.clone(),
+            count: histogram.count,
+            errors: histogram.errors,
+            avg_ms: histogram.total_ms / histogram.count.max(1) as f64,
+            p50_ms: histogram.quantile_ms(0.5),
+            p95_ms: histogram.quantile_ms(0.95),
+            p99_ms: histogram.quantile_ms(0.99),
+            max_ms: histogram.max_ms,
+            buckets: histogram.buckets.clone(),
+        })
+        .collect();
+    stats.sort_by(|a, b| (b.avg_ms * b.count as f64).total_cmp(&(a.avg_ms * a.count as f64)));
+    stats
+}
+
+fn is_statement_log(target: &str) -> bool {
+    target.starts_with("sqlx::query")
+}
+
+// Wraps the process's logger to keep the statements sqlx logs while a request is being served
+pub struct StatementCapture<L> {
+    inner: L,
+}
+
+impl<L: Log> Log for StatementCapture<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        (is_statement_log(metadata.target()) && STATEMENTS.try_with(|_| ()).is_ok()) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if is_statement_log(record.target()) {
+            let _ = STATEMENTS.try_with(|statements| {
+                let mut statements = statements.borrow_mut();
+                if statements.len() < MAX_CAPTURED_STATEMENTS {
+                    statements.push(record.args().to_string());
+                }
+            });
+        }
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// env_logger as configured by RUST_LOG, with statement capture. The global level is raised to
+// debug so sqlx emits its statement logs; env_logger still filters what gets printed.
+pub fn init_logger() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(LevelFilter::Debug);
+    log::set_boxed_logger(Box::new(StatementCapture { inner })).expect("Logger already initialized");
+    log::set_max_level(max_level);
+}
+
+// Middleware timing each request into its route's histogram and logging slow ones
+pub async fn record_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let (result, statements) = STATEMENTS
+        .scope(RefCell::new(Vec::new()), async {
+            let result = next.call(req).await;
+            (result, STATEMENTS.with(|statements| statements.take()))
+        })
+        .await;
+    let response = result?;
+    let elapsed = started.elapsed();
+
+    let route = response.request().match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let status = response.status();
+    record(&method, &route, elapsed, status.is_server_error());
+
+    if elapsed >= slow_request_threshold() {
+        let mut message = format!("Slow request: {} {} ({}) answered {} in {}ms", method, path, route, status.as_u16(), elapsed.as_millis());
+        if statements.is_empty() {
+            message.push_str(", no SQL");
+        }
+        for (i, statement) in statements.iter().enumerate() {
+            message.push_str(&format!("\n  [{}] {}", i + 1, statement.replace('\n', "\n      ")));
+        }
+        warn!("{}", message);
+    }
+    Ok(response.map_into_boxed_body())
+}
@@ -0,0 +1,115 @@
+use std::env;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use actix_web::http::header::{ORIGIN, REFERER};
+use actix_web::HttpRequest;
+use log::{info, warn};
+
+use crate::error::ApiError;
+use crate::models::Video;
+use crate::trusted_proxies;
+
+// GeoLite2/GeoIP2 Country database at GEOIP_DATABASE, loaded on first use. Without one (and
+// without GEOIP_COUNTRY_HEADER) every client's country is unknown.
+static GEOIP: LazyLock<Option<maxminddb::Reader<Vec<u8>>>> = LazyLock::new(|| {
+    let path = env::var("GEOIP_DATABASE").ok()?;
+    match maxminddb::Reader::open_readfile(&path) {
+        Ok(reader) => {
+            info!("Loaded GeoIP database from {}", path);
+            Some(reader)
+        }
+        Err(e) => {
+            warn!("Failed to load GeoIP database from {}: {:?}", path, e);
+            None
+        }
+    }
+});
+
+// Upper-cased ISO 3166-1 alpha-2 code, or None if it doesn't look like one
+pub fn normalize_country(code: &str) -> Option<String> {
+    let code = code.trim();
+    (code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic())).then(|| code.to_ascii_uppercase())
+}
+
+pub fn normalize_countries(codes: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized = codes
+        .iter()
+        .map(|code| normalize_country(code).ok_or_else(|| format!("Invalid country code: {}", code)))
+        .collect::<Result<Vec<_>, _>>()?;
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+pub fn lookup_country(ip: IpAddr) -> Option<String> {
+    let reader = GEOIP.as_ref()?;
+    let record: maxminddb::geoip2::Country = reader.lookup(ip).ok()?;
+    record.country?.iso_code.and_then(normalize_country)
+}
+
+// The client's country. A CDN or proxy that already resolves it (e.g. Cloudflare's CF-IPCountry)
+// can be trusted with GEOIP_COUNTRY_HEADER, which is then only read from TRUSTED_PROXIES;
+// otherwise the client address is looked up. Either way a client can't claim another country
+// with its own headers.
+pub fn client_country(http_req: &HttpRequest) -> Option<String> {
+    if let Ok(header) = env::var("GEOIP_COUNTRY_HEADER") {
+        if trusted_proxies::from_trusted_proxy(http_req) {
+            if let Some(country) = http_req.headers().get(header.as_str()).and_then(|v| v.to_str().ok()) {
+                return normalize_country(country);
+            }
+        }
+    }
+
+    lookup_country(trusted_proxies::client_ip(http_req)?)
+}
+
+// Clients in an unknown country get past a block list but not an allow list
+pub fn country_allowed(allowed: &[String], blocked: &[String], country: Option<&str>) -> bool {
+    match country {
+        Some(country) => {
+            (allowed.is_empty() || allowed.iter().any(|c| c == country)) && !blocked.iter().any(|c| c == country)
+        }
+        None => allowed.is_empty(),
+    }
+}
+
+// scheme://host[:port] of an absolute URL
+pub(crate) fn origin_of(url: &str) -> Option<&str> {
+    let host_start = url.find("://")? + 3;
+    let end = url[host_start..].find('/').map_or(url.len(), |i| host_start + i);
+    Some(&url[..end])
+}
+
+// Whether the request comes from a page on another site, going by its Origin or Referer.
+// The site's own pages are the CORS_ALLOWED_ORIGINS; requests with neither header (direct
+// links, native apps) aren't embeds.
+pub fn is_foreign_embed(http_req: &HttpRequest) -> bool {
+    let headers = http_req.headers();
+    let Some(source) = headers.get(ORIGIN).or_else(|| headers.get(REFERER)).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let Some(origin) = origin_of(source) else {
+        // Sandboxed frames send "Origin: null"
+        return true;
+    };
+
+    let own_origins = env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    !own_origins
+        .split(',')
+        .map(|o| o.trim().trim_end_matches('/'))
+        .any(|o| o.eq_ignore_ascii_case(origin))
+}
+
+// Enforce the uploader's country and embed restrictions on a request for the video
+pub fn check_playback(video: &Video, http_req: &HttpRequest) -> Result<(), ApiError> {
+    if !video.allow_embed && is_foreign_embed(http_req) {
+        return Err(ApiError::Forbidden("This video can't be embedded on other sites".to_string()));
+    }
+    if !video.allowed_countries.is_empty() || !video.blocked_countries.is_empty() {
+        let country = client_country(http_req);
+        if !country_allowed(&video.allowed_countries, &video.blocked_countries, country.as_deref()) {
+            return Err(ApiError::Forbidden("This video is not available in your country".to_string()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use log::{error, info, warn};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::imports::{self, ImportedObject, ListedObject};
+use crate::job_queue::JobQueue;
+use crate::storage::{ObjectStore, Storage};
+use crate::webhooks;
+
+// Videos for objects uploaded to the bucket out of band. S3 (or MinIO) posts its event
+// notifications to /api/internal/s3-events, directly as a webhook or forwarded from an SQS queue,
+// authenticated with S3_EVENTS_TOKEN. An SNS topic can't send the token, so SNS messages are
+// checked against their signature instead and only accepted from S3_EVENTS_SNS_TOPIC_ARN. Created
+// video files under IMPORT_S3_PREFIX are recorded and imported like /api/admin/imports does.
+//
+// Uploads, compilations and the watched folder store their object before creating its video, so
+// an event is only acted on after S3_EVENTS_GRACE_SECS, by when the video exists if it's ours.
+
+const DEFAULT_GRACE_SECS: u64 = 60;
+const INGEST_INTERVAL: Duration = Duration::from_secs(15);
+const INGEST_BATCH: i64 = 100;
+const SNS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Notifications are S3's wire format, so they keep its field names
+#[derive(Debug, Deserialize)]
+pub struct S3EventNotification {
+    #[serde(rename = "Records", default)]
+    pub records: Vec<S3EventRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct S3EventRecord {
+    #[serde(rename = "eventName")]
+    pub event_name: String,
+    #[serde(rename = "eventTime")]
+    pub event_time: Option<String>,
+    pub s3: S3Entity,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct S3Entity {
+    pub bucket: S3Bucket,
+    pub object: S3Object,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct S3Bucket {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct S3Object {
+    pub key: String,
+    #[serde(default)]
+    pub size: i64,
+}
+
+// SNS wraps the notification in an envelope, as a JSON string. Pollers forwarding from an SQS
+// queue subscribed to the topic pass it on as is.
+#[derive(Debug, Deserialize)]
+struct SnsEnvelope {
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+// A message posted by SNS itself, see
+// https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    pub subject: Option<String>,
+    pub message: String,
+    pub timestamp: String,
+    pub signature_version: String,
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(rename = "SubscribeURL")]
+    pub subscribe_url: Option<String>,
+    pub token: Option<String>,
+}
+
+// Certificates by URL. SNS rotates them rarely, and fetching one per message would be slow.
+static SIGNING_CERTS: LazyLock<std::sync::Mutex<HashMap<String, Vec<u8>>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+pub fn events_token() -> Option<String> {
+    env::var("S3_EVENTS_TOKEN").ok().filter(|t| !t.trim().is_empty())
+}
+
+pub fn grace_period() -> Duration {
+    let secs = env::var("S3_EVENTS_GRACE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_GRACE_SECS);
+    Duration::from_secs(secs)
+}
+
+// The Authorization header carries the token, bare as MinIO sends it or as a bearer token.
+// Digests are compared so the comparison takes as long however much of the token matches.
+pub fn token_matches(authorization: Option<&str>, expected: &str) -> bool {
+    let Some(header) = authorization.map(str::trim) else {
+        return false;
+    };
+    let token = header.strip_prefix("Bearer ").unwrap_or(header);
+    Sha256::digest(token.as_bytes()) == Sha256::digest(expected.as_bytes())
+}
+
+// A notification, or one inside an SNS envelope. S3's test events have no records.
+pub fn parse_notification(body: &[u8]) -> Result<S3EventNotification, serde_json::Error> {
+    if let Ok(envelope) = serde_json::from_slice::<SnsEnvelope>(body) {
+        if let Ok(notification) = serde_json::from_str(&envelope.message) {
+            return Ok(notification);
+        }
+    }
+    serde_json::from_slice(body)
+}
+
+pub fn sns_topic_arn() -> Option<String> {
+    env::var("S3_EVENTS_SNS_TOPIC_ARN").ok().filter(|t| !t.trim().is_empty())
+}
+
+// The fields SNS signs, in order, each as "Name\nvalue\n". Notifications sign their subject and
+// subscription messages their subscribe URL and token.
+pub fn sns_string_to_sign(message: &SnsMessage) -> String {
+    let notification = message.message_type == "Notification";
+    let fields = [
+        ("Message", Some(&message.message)),
+        ("MessageId", Some(&message.message_id)),
+        ("Subject", message.subject.as_ref().filter(|_| notification)),
+        ("SubscribeURL", message.subscribe_url.as_ref().filter(|_| !notification)),
+        ("Timestamp", Some(&message.timestamp)),
+        ("Token", message.token.as_ref().filter(|_| !notification)),
+        ("TopicArn", Some(&message.topic_arn)),
+        ("Type", Some(&message.message_type)),
+    ];
+    fields
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{}\n{}\n", name, value)))
+        .collect()
+}
+
+// An https URL on an SNS regional endpoint such as sns.us-east-1.amazonaws.com. The region has to
+// look like one, so S3 hosts for a bucket named "sns" don't pass.
+pub fn is_sns_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let labels: Vec<&str> = host.split('.').collect();
+    let region_like = |region: &str| {
+        let parts: Vec<&str> = region.split('-').collect();
+        parts.len() >= 3
+            && parts[..parts.len() - 1].iter().all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_lowercase()))
+            && parts[parts.len() - 1].parse::<u8>().is_ok()
+    };
+    url.scheme() == "https"
+        && url.port().is_none()
+        && matches!(labels.as_slice(), ["sns", region, "amazonaws", "com"] | ["sns", region, "amazonaws", "com", "cn"] if region_like(region))
+}
+
+// Whether `cert_pem` signed the message. Version 1 signatures are SHA1, version 2 SHA256.
+pub fn sns_signature_valid(message: &SnsMessage, cert_pem: &[u8]) -> bool {
+    let digest = match message.signature_version.as_str() {
+        "1" => MessageDigest::sha1(),
+        "2" => MessageDigest::sha256(),
+        _ => return false,
+    };
+    let Ok(signature) = base64::engine::general_purpose::STANDARD.decode(&message.signature) else {
+        return false;
+    };
+    let verify = || -> Result<bool, ErrorStack> {
+        let key = X509::from_pem(cert_pem)?.public_key()?;
+        let mut verifier = Verifier::new(digest, &key)?;
+        verifier.update(sns_string_to_sign(message).as_bytes())?;
+        verifier.verify(&signature)
+    };
+    verify().unwrap_or(false)
+}
+
+async fn signing_cert(url: &str) -> Result<Vec<u8>, String> {
+    if let Some(cert) = SIGNING_CERTS.lock().unwrap().get(url) {
+        return Ok(cert.clone());
+    }
+    let client = reqwest::Client::builder().timeout(SNS_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("fetching the signing certificate returned {}", response.status()));
+    }
+    let cert = response.bytes().await.map_err(|e| e.to_string())?.to_vec();
+    SIGNING_CERTS.lock().unwrap().insert(url.to_string(), cert.clone());
+    Ok(cert)
+}
+
+// Accept the message only from the configured topic and with a valid signature from an SNS
+// certificate
+pub async fn authenticate_sns(message: &SnsMessage) -> Result<(), String> {
+    let topic_arn = sns_topic_arn().ok_or("S3_EVENTS_SNS_TOPIC_ARN is not set")?;
+    if message.topic_arn != topic_arn {
+        return Err(format!("unexpected topic {}", message.topic_arn));
+    }
+    if !is_sns_url(&message.signing_cert_url) || !message.signing_cert_url.ends_with(".pem") {
+        return Err(format!("signing certificate {} is not from SNS", message.signing_cert_url));
+    }
+    let cert = signing_cert(&message.signing_cert_url).await?;
+    if !sns_signature_valid(message, &cert) {
+        return Err("invalid signature".to_string());
+    }
+    Ok(())
+}
+
+// Visit the subscribe URL of an authenticated SubscriptionConfirmation, which starts delivery
+pub async fn confirm_subscription(message: &SnsMessage) -> Result<(), String> {
+    let url = message.subscribe_url.as_deref().ok_or("no SubscribeURL")?;
+    if !is_sns_url(url) {
+        return Err(format!("subscribe URL {} is not on SNS", url));
+    }
+    let client = reqwest::Client::builder().timeout(SNS_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("confirming the subscription returned {}", response.status()));
+    }
+    Ok(())
+}
+
+// Keys arrive URL-encoded, with spaces as '+'
+pub fn decode_key(key: &str) -> String {
+    let key = key.replace('+', " ");
+    urlencoding::decode(&key).map_or_else(|_| key.clone(), |k| k.into_owned())
+}
+
+fn parse_event_time(value: &str) -> Option<NaiveDateTime> {
+    chrono::DateTime::parse_from_rfc3339(value).ok().map(|t| t.naive_utc())
+}
+
+// The video files the notification reports created in `bucket` under `prefix`
+pub fn created_objects(notification: &S3EventNotification, bucket: &str, prefix: &str) -> Vec<ListedObject> {
+    notification
+        .records
+        .iter()
+        .filter(|r| r.event_name.trim_start_matches("s3:").starts_with("ObjectCreated:"))
+        .filter(|r| r.s3.bucket.name == bucket)
+        .map(|r| ListedObject {
+            key: decode_key(&r.s3.object.key),
+            size: r.s3.object.size,
+            last_modified: r.event_time.as_deref().and_then(parse_event_time),
+        })
+        .filter(|o| o.size > 0 && o.key.starts_with(prefix) && imports::is_video_key(&o.key))
+        .collect()
+}
+
+// Record the objects to import. An object created again is looked at again.
+pub async fn record(db_pool: &PgPool, bucket: &str, objects: &[ListedObject]) -> Result<u64, sqlx::Error> {
+    let mut recorded = 0;
+    for object in objects {
+        recorded += sqlx::query(
+            "INSERT INTO s3_object_events (bucket, s3_key, size, event_time) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (bucket, s3_key) DO UPDATE
+             SET size = EXCLUDED.size, event_time = EXCLUDED.event_time, received_at = NOW(), processed_at = NULL"
+        )
+        .bind(bucket)
+        .bind(&object.key)
+        .bind(object.size)
+        .bind(object.last_modified)
+        .execute(db_pool)
+        .await?
+        .rows_affected();
+    }
+    Ok(recorded)
+}
+
+// Import the objects recorded longer than `grace` ago that no video, rendition or upload uses
+// and that are still in the bucket. Each is claimed first, so processes sharing the database
+// don't import one twice.
+pub async fn ingest_due(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    job_queue: Option<&Arc<JobQueue>>,
+    bucket: &str,
+    grace: Duration,
+) -> Result<Vec<ImportedObject>, sqlx::Error> {
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(grace.as_secs() as i64);
+    let due: Vec<(String, i64, Option<NaiveDateTime>)> = sqlx::query_as(
+        "UPDATE s3_object_events SET processed_at = NOW()
+         WHERE (bucket, s3_key) IN (
+             SELECT bucket, s3_key FROM s3_object_events
+             WHERE bucket = $1 AND processed_at IS NULL AND received_at <= $2
+             ORDER BY received_at
+             LIMIT $3
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING s3_key, size, event_time"
+    )
+    .bind(bucket)
+    .bind(cutoff)
+    .bind(INGEST_BATCH)
+    .fetch_all(db_pool)
+    .await?;
+
+    let keys: Vec<String> = due.iter().map(|(key, _, _)| key.clone()).collect();
+    let known = imports::known_keys(db_pool, &keys).await?;
+    let mut imported = Vec::new();
+    for (key, size, event_time) in due {
+        if known.contains(&key) {
+            continue;
+        }
+        match storage.head(bucket, &key).await {
+            Ok(_) => {}
+            Err(e) if e.is_not_found() => continue,
+            Err(e) => {
+                warn!("Failed to check s3://{}/{} before importing it: {:?}", bucket, key, e);
+                continue;
+            }
+        }
+        let object = ListedObject { key, size, last_modified: event_time };
+        let Some(video) = imports::insert_video(db_pool, &object, &imports::title_from_key(&object.key), None).await? else {
+            continue;
+        };
+        sqlx::query("UPDATE s3_object_events SET video_id = $1 WHERE bucket = $2 AND s3_key = $3")
+            .bind(video.id)
+            .bind(bucket)
+            .bind(&video.s3_key)
+            .execute(db_pool)
+            .await?;
+        if let Some(job_queue) = job_queue {
+            if let Err(e) = job_queue.enqueue_processing(&video, bucket, false).await {
+                error!("Failed to enqueue processing for video {} from an S3 event: {:?}", video.id, e);
+            }
+        }
+        webhooks::video_created(db_pool, &video).await;
+        info!("Created video {} for s3://{}/{} from an S3 event", video.id, bucket, video.s3_key);
+        imported.push(ImportedObject { s3_key: video.s3_key, title: video.title, video_id: Some(video.id) });
+    }
+    Ok(imported)
+}
+
+pub async fn run_s3_event_ingester(db_pool: PgPool, storage: Storage, job_queue: Option<Arc<JobQueue>>) {
+    if events_token().is_none() {
+        return;
+    }
+    let grace = grace_period();
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    info!("Starting S3 event ingester (grace period: {} seconds)", grace.as_secs());
+
+    loop {
+        if let Err(e) = ingest_due(&db_pool, storage.as_ref(), job_queue.as_ref(), &bucket, grace).await {
+            error!("Error importing objects from S3 events: {:?}", e);
+        }
+        sleep(INGEST_INTERVAL).await;
+    }
+}
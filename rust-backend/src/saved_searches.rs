@@ -0,0 +1,115 @@
+use sqlx::PgPool;
+use log::{info, error};
+use std::time::Duration;
+use tokio::time::sleep;
+use serde_json::json;
+
+use crate::models::{SavedSearch, Video};
+use crate::notifications::create_notification;
+
+// Record a query in the user's search history
+pub async fn record_search(db_pool: &PgPool, user_id: i32, query: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO search_history (user_id, query, created_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(query)
+        .bind(chrono::Utc::now().naive_utc())
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Find videos that became visible after `since` and match a search query, using the same
+// matching rules as the /api/videos/search route. A video held for moderation becomes visible
+// when it is approved, which can be long after its upload.
+pub async fn find_new_matches(
+    db_pool: &PgPool,
+    query: &str,
+    since: chrono::NaiveDateTime,
+) -> Result<Vec<Video>, sqlx::Error> {
+    let search_pattern = format!("%{}%", query.to_lowercase());
+
+    sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos
+         WHERE GREATEST(upload_date, moderated_at) > $2
+           AND deleted_at IS NULL
+           AND moderation_status = 'approved'
+           AND (LOWER(title) LIKE $1
+            OR LOWER(description) LIKE $1
+            OR EXISTS (
+                SELECT 1 FROM unnest(tags) AS tag
+                WHERE LOWER(tag) LIKE $1
+            ))
+         ORDER BY GREATEST(upload_date, moderated_at) DESC"
+    )
+    .bind(&search_pattern)
+    .bind(since)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Evaluate every saved search with notifications enabled once and notify owners of new matches.
+// Every replica runs this, so each search is first claimed by moving its checkpoint on from the
+// value read; a replica that finds it already moved skips the search, and the notification is
+// written in the same transaction so a failed one leaves the search to be checked again.
+pub async fn check_saved_searches(db_pool: &PgPool) -> Result<(), sqlx::Error> {
+    let saved_searches = sqlx::query_as::<_, SavedSearch>(
+        "SELECT * FROM saved_searches WHERE notify = TRUE ORDER BY id ASC"
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    for saved_search in saved_searches {
+        // Take the checkpoint before querying so videos uploaded mid-check are picked up next time
+        let checked_at = chrono::Utc::now().naive_utc();
+        let mut tx = db_pool.begin().await?;
+        let claimed = sqlx::query("UPDATE saved_searches SET last_checked_at = $1 WHERE id = $2 AND last_checked_at = $3")
+            .bind(checked_at)
+            .bind(saved_search.id)
+            .bind(saved_search.last_checked_at)
+            .execute(&mut tx)
+            .await?
+            .rows_affected()
+            == 1;
+        if !claimed {
+            continue;
+        }
+
+        let matches = find_new_matches(db_pool, &saved_search.query, saved_search.last_checked_at).await?;
+        if !matches.is_empty() {
+            info!("Saved search {} has {} new matching videos", saved_search.id, matches.len());
+            let video_ids: Vec<i32> = matches.iter().map(|video| video.id).collect();
+            create_notification(
+                &mut tx,
+                saved_search.user_id,
+                "saved_search_match",
+                json!({
+                    "savedSearchId": saved_search.id,
+                    "name": saved_search.name,
+                    "query": saved_search.query,
+                    "videoIds": video_ids
+                }),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+// Periodically evaluate saved searches. Interval is configurable via SAVED_SEARCH_CHECK_INTERVAL_SECS.
+pub async fn run_saved_search_notifier(db_pool: PgPool) {
+    let interval_secs = std::env::var("SAVED_SEARCH_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300);
+
+    info!("Starting saved search notifier (interval: {} seconds)", interval_secs);
+
+    loop {
+        if let Err(e) = check_saved_searches(&db_pool).await {
+            error!("Error checking saved searches: {:?}", e);
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
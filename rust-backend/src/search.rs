@@ -0,0 +1,76 @@
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::models::{AdvancedSearchRequest, SearchSort};
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 50;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+
+// Compile a structured search request into a parameterized query over the videos table.
+// Every user-supplied value is bound, never interpolated.
+pub fn build_advanced_search_query(req: &AdvancedSearchRequest) -> QueryBuilder<'_, Postgres> {
+    let mut query = QueryBuilder::new("SELECT * FROM videos WHERE deleted_at IS NULL AND moderation_status = 'approved'");
+
+    if let Some(text) = req.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let search_pattern = format!("%{}%", text.to_lowercase());
+        query.push(" AND (LOWER(title) LIKE ")
+            .push_bind(search_pattern.clone())
+            .push(" OR LOWER(description) LIKE ")
+            .push_bind(search_pattern.clone())
+            .push(" OR EXISTS (SELECT 1 FROM unnest(tags) AS tag WHERE LOWER(tag) LIKE ")
+            .push_bind(search_pattern)
+            .push(") OR EXISTS (SELECT 1 FROM video_transcripts t WHERE t.video_id = videos.id AND t.search_vector @@ plainto_tsquery('simple', ")
+            .push_bind(text.to_string())
+            .push(")))");
+    }
+
+    if let Some(tags_all) = req.tags_all.as_ref().filter(|tags| !tags.is_empty()) {
+        query.push(" AND tags @> ").push_bind(tags_all.clone());
+    }
+
+    if let Some(tags_any) = req.tags_any.as_ref().filter(|tags| !tags.is_empty()) {
+        query.push(" AND tags && ").push_bind(tags_any.clone());
+    }
+
+    if let Some(category_id) = req.category_id {
+        query.push(" AND EXISTS (SELECT 1 FROM videos_categories vc WHERE vc.video_id = videos.id AND vc.category_id = ")
+            .push_bind(category_id)
+            .push(")");
+    }
+
+    if let Some(min_duration) = req.min_duration {
+        query.push(" AND duration >= ").push_bind(min_duration);
+    }
+
+    if let Some(max_duration) = req.max_duration {
+        query.push(" AND duration <= ").push_bind(max_duration);
+    }
+
+    if let Some(uploaded_after) = req.uploaded_after {
+        query.push(" AND upload_date >= ").push_bind(uploaded_after);
+    }
+
+    if let Some(uploaded_before) = req.uploaded_before {
+        query.push(" AND upload_date <= ").push_bind(uploaded_before);
+    }
+
+    if let Some(uploaded_by) = req.uploaded_by {
+        query.push(" AND uploaded_by = ").push_bind(uploaded_by);
+    }
+
+    // Sort columns come from a fixed set, so they can be pushed as SQL
+    let order_by = match req.sort.unwrap_or(SearchSort::Newest) {
+        SearchSort::Newest => "upload_date DESC NULLS LAST",
+        SearchSort::Oldest => "upload_date ASC NULLS LAST",
+        SearchSort::MostViewed => "view_count DESC NULLS LAST",
+        SearchSort::Title => "title ASC",
+        SearchSort::Duration => "duration ASC NULLS LAST",
+    };
+    query.push(" ORDER BY ").push(order_by).push(", id ASC");
+
+    let limit = req.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+    let offset = req.offset.unwrap_or(0).max(0);
+    query.push(" LIMIT ").push_bind(limit);
+    query.push(" OFFSET ").push_bind(offset);
+
+    query
+}
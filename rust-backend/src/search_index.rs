@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::time::Duration;
+use futures::future::BoxFuture;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::time::sleep;
+
+use crate::categories;
+use crate::changes::{self, Change, Cursor};
+use crate::feeds;
+use crate::models::Video;
+
+type IndexResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// An optional external search engine behind /api/videos/search/{query}, selected by
+// SEARCH_BACKEND, for typo tolerance and facet counts Postgres LIKE can't give. The index is
+// kept in sync from the change feed (see changes.rs) by run_search_indexer, its position stored
+// in search_index_cursors. When the engine can't be reached, search falls back to Postgres.
+
+// Changes read from the feed per sync
+const SYNC_BATCH: i64 = 500;
+const SYNC_IDLE_SECS: u64 = 5;
+
+// Attributes facets are counted over, which are also the ones results can be filtered by
+pub const FACETS: [&str; 2] = ["tags", "category_ids"];
+
+// What the engine stores per public video
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Document {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub category_ids: Vec<i32>,
+    pub uploaded_by: Option<i32>,
+    pub upload_date: Option<i64>, // unix seconds, so it can be sorted on
+    pub duration: Option<i32>,
+}
+
+impl Document {
+    pub fn from_video(video: &Video) -> Self {
+        Document {
+            id: video.id,
+            title: video.title.clone(),
+            description: video.description.clone(),
+            tags: video.tags.clone().unwrap_or_default(),
+            category_ids: video.category_ids.clone().unwrap_or_default(),
+            uploaded_by: video.uploaded_by,
+            upload_date: video.upload_date.map(|d| d.and_utc().timestamp()),
+            duration: video.duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub tag: Option<String>,
+    pub category_id: Option<i32>,
+    pub limit: i64,
+}
+
+// Facet value counts, e.g. {"tags": {"music": 4}}
+pub type Facets = BTreeMap<String, BTreeMap<String, u64>>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchHits {
+    pub video_ids: Vec<i32>, // most relevant first
+    pub facets: Facets,
+}
+
+pub trait SearchBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn search<'a>(&'a self, query: &'a SearchQuery) -> BoxFuture<'a, IndexResult<SearchHits>>;
+    fn upsert<'a>(&'a self, documents: &'a [Document]) -> BoxFuture<'a, IndexResult<()>>;
+    fn delete<'a>(&'a self, video_ids: &'a [i32]) -> BoxFuture<'a, IndexResult<()>>;
+}
+
+// A Meilisearch filter expression for the query's tag and category, if any
+pub fn meilisearch_filter(query: &SearchQuery) -> Option<String> {
+    let mut clauses = Vec::new();
+    if let Some(tag) = &query.tag {
+        clauses.push(format!("tags = \"{}\"", tag.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    if let Some(category_id) = query.category_id {
+        clauses.push(format!("category_ids = {}", category_id));
+    }
+    (!clauses.is_empty()).then(|| clauses.join(" AND "))
+}
+
+// Talks to Meilisearch at MEILISEARCH_URL, in the MEILISEARCH_INDEX index (videos by default)
+pub struct MeilisearchBackend {
+    url: String,
+    index: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct MeilisearchHit {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MeilisearchResponse {
+    hits: Vec<MeilisearchHit>,
+    #[serde(default)]
+    facet_distribution: Facets,
+}
+
+impl MeilisearchBackend {
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}/indexes/{}{}", self.url, self.index, path));
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    // Make the facets filterable and searchable fields ranked, creating the index if needed
+    pub async fn configure(&self) -> IndexResult<()> {
+        self.request(reqwest::Method::PATCH, "/settings")
+            .json(&json!({
+                "searchableAttributes": ["title", "tags", "description"],
+                "filterableAttributes": FACETS,
+                "sortableAttributes": ["upload_date", "duration"],
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl SearchBackend for MeilisearchBackend {
+    fn name(&self) -> &'static str {
+        "meilisearch"
+    }
+
+    fn search<'a>(&'a self, query: &'a SearchQuery) -> BoxFuture<'a, IndexResult<SearchHits>> {
+        Box::pin(async move {
+            let response: MeilisearchResponse = self.request(reqwest::Method::POST, "/search")
+                .json(&json!({
+                    "q": query.text,
+                    "filter": meilisearch_filter(query),
+                    "facets": FACETS,
+                    "limit": query.limit,
+                    "attributesToRetrieve": ["id"],
+                }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            Ok(SearchHits {
+                video_ids: response.hits.into_iter().map(|hit| hit.id).collect(),
+                facets: response.facet_distribution,
+            })
+        })
+    }
+
+    fn upsert<'a>(&'a self, documents: &'a [Document]) -> BoxFuture<'a, IndexResult<()>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::POST, "/documents?primaryKey=id")
+                .json(documents)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, video_ids: &'a [i32]) -> BoxFuture<'a, IndexResult<()>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::POST, "/documents/delete-batch")
+                .json(video_ids)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+// The backend selected by SEARCH_BACKEND. Search stays in Postgres when it is unset.
+pub fn backend_from_env() -> Option<MeilisearchBackend> {
+    match env::var("SEARCH_BACKEND").as_deref() {
+        Ok("meilisearch") => Some(MeilisearchBackend {
+            url: env::var("MEILISEARCH_URL").ok()?.trim_end_matches('/').to_string(),
+            index: env::var("MEILISEARCH_INDEX").unwrap_or_else(|_| "videos".to_string()),
+            api_key: env::var("MEILISEARCH_API_KEY").ok(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .ok()?,
+        }),
+        _ => None,
+    }
+}
+
+// What a batch of changes does to the index: each video's latest change wins
+pub fn plan_sync(changes: &[Change]) -> (Vec<Document>, Vec<i32>) {
+    let mut latest: HashMap<i32, &Change> = HashMap::new();
+    for change in changes {
+        latest.insert(change.video_id, change);
+    }
+    let mut upserts = Vec::new();
+    let mut deletes = Vec::new();
+    for change in latest.into_values() {
+        match &change.video {
+            Some(video) => upserts.push(Document::from_video(video)),
+            None => deletes.push(change.video_id),
+        }
+    }
+    upserts.sort_by_key(|d| d.id);
+    deletes.sort_unstable();
+    (upserts, deletes)
+}
+
+// Apply the next batch of changes to the index. The cursor row stays locked meanwhile, so of
+// several processes running the indexer only one syncs at a time. Returns how many changes
+// were applied.
+pub async fn sync_once(db_pool: &PgPool, backend: &dyn SearchBackend) -> IndexResult<usize> {
+    sqlx::query("INSERT INTO search_index_cursors (backend) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(backend.name())
+        .execute(db_pool)
+        .await?;
+
+    let mut tx = db_pool.begin().await?;
+    let cursor = match sqlx::query_scalar::<_, Option<String>>("SELECT cursor FROM search_index_cursors WHERE backend = $1 FOR UPDATE SKIP LOCKED")
+        .bind(backend.name())
+        .fetch_optional(&mut tx)
+        .await?
+    {
+        Some(cursor) => cursor,
+        None => return Ok(0), // another process is syncing
+    };
+
+    // Without a cursor the index starts out with every public video
+    let Some(cursor) = cursor else {
+        let head = changes::head(db_pool).await?;
+        let indexed = reindex_all(db_pool, backend).await?;
+        set_cursor(&mut tx, backend, head).await?;
+        tx.commit().await?;
+        info!("Indexed all {} public videos in {}", indexed, backend.name());
+        return Ok(indexed);
+    };
+
+    let since = Cursor::parse(Some(&cursor))?;
+    let (changes, next) = changes::since(db_pool, since, SYNC_BATCH).await?;
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    let (upserts, deletes) = plan_sync(&changes);
+    if !upserts.is_empty() {
+        backend.upsert(&upserts).await?;
+    }
+    if !deletes.is_empty() {
+        backend.delete(&deletes).await?;
+    }
+
+    set_cursor(&mut tx, backend, next).await?;
+    tx.commit().await?;
+    Ok(changes.len())
+}
+
+async fn set_cursor(tx: &mut Transaction<'_, Postgres>, backend: &dyn SearchBackend, cursor: Cursor) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE search_index_cursors SET cursor = $1, synced_at = NOW() WHERE backend = $2")
+        .bind(cursor.to_string())
+        .bind(backend.name())
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+// Send every public video to the index, in batches
+async fn reindex_all(db_pool: &PgPool, backend: &dyn SearchBackend) -> IndexResult<usize> {
+    let mut after_id = 0;
+    let mut indexed = 0;
+    loop {
+        let mut videos = sqlx::query_as::<_, Video>(&format!("{} AND id > $1 ORDER BY id LIMIT $2", feeds::PUBLIC_VIDEOS_SQL))
+            .bind(after_id)
+            .bind(SYNC_BATCH)
+            .fetch_all(db_pool)
+            .await?;
+        let Some(last) = videos.last() else {
+            return Ok(indexed);
+        };
+        after_id = last.id;
+        categories::attach(db_pool, &mut videos).await?;
+        let documents: Vec<Document> = videos.iter().map(Document::from_video).collect();
+        backend.upsert(&documents).await?;
+        indexed += documents.len();
+    }
+}
+
+pub async fn run_search_indexer(db_pool: PgPool, backend: MeilisearchBackend) {
+    info!("Starting search indexer for {}", backend.name());
+    if let Err(e) = backend.configure().await {
+        error!("Error configuring the {} index: {:?}", backend.name(), e);
+    }
+
+    loop {
+        match sync_once(&db_pool, &backend).await {
+            Ok(0) => sleep(Duration::from_secs(SYNC_IDLE_SECS)).await,
+            Ok(applied) => info!("Applied {} video changes to the {} index", applied, backend.name()),
+            Err(e) => {
+                error!("Error syncing the {} index: {:?}", backend.name(), e);
+                sleep(Duration::from_secs(SYNC_IDLE_SECS)).await;
+            }
+        }
+    }
+}
+
+// Put videos loaded for a search in the order the engine ranked them
+pub fn order_by_hits(mut videos: Vec<Video>, video_ids: &[i32]) -> Vec<Video> {
+    let rank: HashMap<i32, usize> = video_ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    videos.retain(|v| rank.contains_key(&v.id));
+    videos.sort_by_key(|v| rank[&v.id]);
+    videos
+}
+
+// The videos the engine found, as they are in Postgres now. Any it still has that have since
+// stopped being listed are left out.
+pub async fn load_hits(db_pool: &PgPool, video_ids: &[i32]) -> Result<Vec<Video>, sqlx::Error> {
+    let videos = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE id = ANY($1) AND deleted_at IS NULL AND moderation_status = 'approved'"
+    )
+    .bind(video_ids)
+    .fetch_all(db_pool)
+    .await?;
+    Ok(order_by_hits(videos, video_ids))
+}
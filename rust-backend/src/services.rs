@@ -1,13 +1,22 @@
-use sqlx::{PgPool, Pool, Postgres};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{ConnectOptions, PgPool, Pool, Postgres};
 use std::env;
+use std::str::FromStr;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Credentials;
 use aws_types::region::Region;
 use aws_config;
 
+use crate::storage::{ObjectInfo, ObjectStore};
+
 pub async fn init_db_pool() -> Pool<Postgres> {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgPool::connect(&database_url)
+    let mut options = PgConnectOptions::from_str(&database_url).expect("DATABASE_URL is not a valid Postgres URL");
+    // Statements are logged at debug for request_metrics to attach to slow requests
+    options
+        .log_statements(log::LevelFilter::Debug)
+        .log_slow_statements(log::LevelFilter::Warn, crate::request_metrics::slow_query_threshold());
+    PgPool::connect_with(options)
         .await
         .expect("Failed to connect to database")
 }
@@ -51,34 +60,188 @@ pub async fn init_s3_client() -> Client {
     Client::from_conf(s3_config)
 }
 
-pub async fn ensure_bucket_exists(client: &Client) {
+pub async fn ensure_bucket_exists(storage: &dyn ObjectStore) {
     // In production, use the bucket name from environment variable (set by Terraform)
     // In development, fall back to local MinIO bucket name
     let bucket_name = std::env::var("S3_BUCKET")
         .or_else(|_| std::env::var("MINIO_BUCKET"))
         .unwrap_or_else(|_| "videos".to_string());
     
-    log::info!("Using S3 bucket: {}", bucket_name);
-    
-    // In AWS, buckets are created by Terraform, so we don't need to create them
-    // Just verify we can access the bucket
-    if std::env::var("MINIO_ENDPOINT").is_ok() {
-        // Local development - try to create bucket
-        match client.create_bucket().bucket(&bucket_name).send().await {
-            Ok(_) => log::info!("Bucket created successfully: {}", bucket_name),
-            Err(err) => {
-                if err.to_string().contains("BucketAlreadyExists") || err.to_string().contains("BucketAlreadyOwnedByYou") {
-                    log::info!("Bucket already exists: {}", bucket_name);
-                } else {
-                    log::warn!("Error creating bucket {}: {:?}", bucket_name, err);
-                }
-            }
+    log::info!("Using {} bucket: {}", storage.name(), bucket_name);
+    storage.ensure_bucket(&bucket_name).await;
+}
+
+type DownloadResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+// Settings for download_object_parallel. Configured via S3_DOWNLOAD_PARALLELISM (concurrent
+// range requests, default 4) and S3_DOWNLOAD_PART_MB (size of each range, default 16).
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelDownloadConfig {
+    pub parallelism: usize,
+    pub part_size: u64,
+}
+
+impl ParallelDownloadConfig {
+    pub fn from_env() -> Self {
+        let parallelism = env::var("S3_DOWNLOAD_PARALLELISM")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(4);
+        let part_mb = env::var("S3_DOWNLOAD_PART_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(16);
+
+        Self {
+            parallelism,
+            part_size: part_mb * BYTES_PER_MB,
         }
-    } else {
-        // Production - bucket should already exist, just verify access
-        match client.head_bucket().bucket(&bucket_name).send().await {
-            Ok(_) => log::info!("Successfully connected to S3 bucket: {}", bucket_name),
-            Err(err) => log::error!("Cannot access S3 bucket {}: {:?}", bucket_name, err),
+    }
+}
+
+// Split an object of `len` bytes into inclusive byte ranges of at most `part_size` bytes
+pub fn part_ranges(len: u64, part_size: u64) -> Vec<(u64, u64)> {
+    let part_size = part_size.max(1);
+    (0..len)
+        .step_by(part_size as usize)
+        .map(|start| (start, (start + part_size).min(len) - 1))
+        .collect()
+}
+
+// MD5 hex digest an ETag stands for, if any. ETags of multipart uploads ("<hash>-<parts>")
+// and of KMS-encrypted objects are not content MD5s.
+pub fn etag_md5(etag: &str, kms_encrypted: bool) -> Option<String> {
+    let etag = etag.trim_matches('"');
+    if kms_encrypted || etag.len() != 32 || !etag.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(etag.to_ascii_lowercase())
+}
+
+// Download an object into `dest` with concurrent ranged GETs, writing each range at its
+// offset so memory use stays bounded. Every range is pinned to the ETag seen up front, and the
+// finished file is checked against the object's SHA-256 checksum or MD5 ETag when available.
+pub async fn download_object_parallel(
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    key: &str,
+    dest: &std::path::Path,
+    config: ParallelDownloadConfig,
+) -> DownloadResult<()> {
+    use futures::{StreamExt, TryStreamExt};
+
+    let head = storage.head(bucket, key).await?;
+    let len = head.size;
+    let etag = head.etag.clone();
+
+    let file = tokio::fs::File::create(dest).await?;
+    file.set_len(len).await?;
+    drop(file);
+
+    let ranges = part_ranges(len, config.part_size);
+    log::info!("Downloading {}/{} ({} bytes) in {} parts", bucket, key, len, ranges.len());
+
+    futures::stream::iter(ranges)
+        .map(|(start, end)| download_range(storage, bucket, key, etag.as_deref(), dest, start, end))
+        .buffer_unordered(config.parallelism)
+        .try_collect::<()>()
+        .await?;
+
+    let ObjectInfo { sha256, md5, .. } = head;
+    if sha256.is_none() && md5.is_none() {
+        return Ok(());
+    }
+
+    let (actual_sha256, actual_md5) = hash_file(dest.to_path_buf()).await?;
+    if let Some(expected) = sha256 {
+        if expected != actual_sha256 {
+            return Err(Box::new(std::io::Error::other(format!(
+                "SHA-256 mismatch for {}/{}: expected {}, got {}", bucket, key, expected, actual_sha256
+            ))));
+        }
+    } else if let Some(expected) = md5 {
+        if expected != actual_md5 {
+            return Err(Box::new(std::io::Error::other(format!(
+                "MD5 mismatch for {}/{}: expected {}, got {}", bucket, key, expected, actual_md5
+            ))));
         }
     }
+    Ok(())
+}
+
+async fn download_range(
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    key: &str,
+    etag: Option<&str>,
+    dest: &std::path::Path,
+    start: u64,
+    end: u64,
+) -> DownloadResult<()> {
+    use futures::TryStreamExt;
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+    let mut body = storage.get_range(bucket, key, start, end, etag).await?.body;
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(dest).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let mut written = 0;
+    while let Some(chunk) = body.try_next().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    if written != end - start + 1 {
+        return Err(Box::new(std::io::Error::other(format!(
+            "Short read for {}/{} bytes {}-{}: got {} bytes", bucket, key, start, end, written
+        ))));
+    }
+    Ok(())
+}
+
+// Hex SHA-256 of an object, streamed from storage without touching disk
+pub async fn object_sha256(storage: &dyn ObjectStore, bucket: &str, key: &str) -> DownloadResult<String> {
+    use futures::TryStreamExt;
+    use sha2::{Digest, Sha256};
+
+    let mut body = storage.get(bucket, key).await?.body;
+    let mut sha256 = Sha256::new();
+    while let Some(chunk) = body.try_next().await? {
+        sha256.update(&chunk);
+    }
+    Ok(hex::encode(sha256.finalize()))
+}
+
+// Base64 SHA-256 (as S3 reports it) and hex MD5 of a file
+async fn hash_file(path: std::path::PathBuf) -> DownloadResult<(String, String)> {
+    use base64::Engine;
+    use md5::Md5;
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let hashes = tokio::task::spawn_blocking(move || -> std::io::Result<(String, String)> {
+        let mut file = std::fs::File::open(path)?;
+        let mut sha256 = Sha256::new();
+        let mut md5 = Md5::new();
+        let mut buffer = vec![0u8; BYTES_PER_MB as usize];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            sha256.update(&buffer[..read]);
+            md5.update(&buffer[..read]);
+        }
+        Ok((
+            base64::engine::general_purpose::STANDARD.encode(sha256.finalize()),
+            hex::encode(md5.finalize()),
+        ))
+    })
+    .await??;
+    Ok(hashes)
 }
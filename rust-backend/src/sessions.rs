@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, USER_AGENT};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest};
+use chrono::NaiveDateTime;
+use log::{error, info};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use crate::circuit_breaker;
+use crate::auth_cookies::session_cookie_token;
+use crate::error::ApiError;
+use crate::handlers::decode_claims;
+use crate::models::UserSession;
+use crate::trusted_proxies;
+use crate::AppState;
+
+// Access tokens are valid for this long
+pub const ACCESS_TOKEN_HOURS: i64 = 24;
+
+// Sessions last this long, their refresh tokens getting new access tokens until then
+pub const SESSION_DAYS: i64 = 30;
+
+// last_seen_at is only written once it is this stale, so active clients don't cost an UPDATE
+// on every request
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+
+fn denylist_key(session_id: &str) -> String {
+    format!("revoked_session:{}", session_id)
+}
+
+fn token_denylist_key(jti: &str) -> String {
+    format!("revoked_token:{}", jti)
+}
+
+// The bearer token from the Authorization header, the session cookie in cookie auth mode, or
+// the ?token= query parameter used where headers can't be set (EventSource, HLS key URIs,
+// WebSockets)
+pub fn request_token(http_req: &HttpRequest) -> Option<String> {
+    let bearer = http_req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.to_string());
+    }
+    if let Some(token) = session_cookie_token(http_req) {
+        return Some(token);
+    }
+    web::Query::<HashMap<String, String>>::from_query(http_req.query_string())
+        .ok()
+        .and_then(|query| query.into_inner().remove("token"))
+}
+
+// The session the request's token belongs to
+pub fn current_session_id(http_req: &HttpRequest) -> Option<String> {
+    request_token(http_req).and_then(|token| decode_claims(&token)).and_then(|claims| claims.sid)
+}
+
+// The client's address without the port, forwarded headers only believed from TRUSTED_PROXIES
+pub fn client_ip(http_req: &HttpRequest) -> Option<String> {
+    trusted_proxies::client_ip(http_req).map(|ip| ip.to_string())
+}
+
+// When an access token issued now for a session expiring at `session_expires_at` expires
+pub fn access_token_expiry(session_expires_at: NaiveDateTime) -> NaiveDateTime {
+    (chrono::Utc::now().naive_utc() + chrono::Duration::hours(ACCESS_TOKEN_HOURS)).min(session_expires_at)
+}
+
+// Record a new session for a login or registration. The user's expired sessions are cleared
+// out at the same time.
+pub async fn create_session(db_pool: &PgPool, user_id: i32, http_req: &HttpRequest) -> Result<(String, NaiveDateTime), sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let expires_at = now + chrono::Duration::days(SESSION_DAYS);
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let user_agent = http_req.headers().get(USER_AGENT).and_then(|h| h.to_str().ok());
+
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("DELETE FROM user_sessions WHERE user_id = $1 AND expires_at <= $2")
+        .bind(user_id)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO user_sessions (id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $5, $6)"
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(user_agent)
+    .bind(client_ip(http_req))
+    .bind(now)
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((session_id, expires_at))
+}
+
+// The user's live sessions, most recently used first
+pub async fn list_sessions(db_pool: &PgPool, user_id: i32, current: Option<&str>) -> Result<Vec<UserSession>, sqlx::Error> {
+    sqlx::query_as::<_, UserSession>(
+        "SELECT id, user_agent, ip_address, created_at, last_seen_at, expires_at, COALESCE(id = $2, FALSE) AS current
+         FROM user_sessions
+         WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > $3
+         ORDER BY last_seen_at DESC"
+    )
+    .bind(user_id)
+    .bind(current)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_all(db_pool)
+    .await
+}
+
+// Revoke one of the user's sessions. Returns its expiry, or None if there was no such live session.
+pub async fn revoke_session(db_pool: &PgPool, user_id: i32, session_id: &str) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    sqlx::query_scalar::<_, NaiveDateTime>(
+        "UPDATE user_sessions SET revoked_at = $1 WHERE id = $2 AND user_id = $3 AND revoked_at IS NULL AND expires_at > $1
+         RETURNING expires_at"
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_optional(db_pool)
+    .await
+}
+
+// Revoke every live session of the user's except `keep`, returning (id, expires_at) of each
+pub async fn revoke_all_sessions(db_pool: &PgPool, user_id: i32, keep: Option<&str>) -> Result<Vec<(String, NaiveDateTime)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, NaiveDateTime)>(
+        "UPDATE user_sessions SET revoked_at = $1
+         WHERE user_id = $2 AND revoked_at IS NULL AND expires_at > $1 AND id IS DISTINCT FROM $3
+         RETURNING id, expires_at"
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(user_id)
+    .bind(keep)
+    .fetch_all(db_pool)
+    .await
+}
+
+fn new_refresh_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+fn refresh_token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// A new refresh token for the session
+pub async fn issue_refresh_token(db_pool: &PgPool, session_id: &str) -> Result<String, sqlx::Error> {
+    let token = new_refresh_token();
+    sqlx::query("INSERT INTO refresh_tokens (token_hash, session_id) VALUES ($1, $2)")
+        .bind(refresh_token_hash(&token))
+        .bind(session_id)
+        .execute(db_pool)
+        .await?;
+    Ok(token)
+}
+
+#[derive(Debug)]
+pub enum Refresh {
+    // The token was used up for a new one
+    Rotated { user_id: i32, session_id: String, expires_at: NaiveDateTime, refresh_token: String },
+    // The token had been used before, so its session was revoked
+    Reused { session_id: String, expires_at: NaiveDateTime },
+    // Unknown, or its session is revoked or expired
+    Invalid,
+}
+
+// Exchange a refresh token for the next one of its session
+pub async fn refresh(db_pool: &PgPool, token: &str) -> Result<Refresh, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut tx = db_pool.begin().await?;
+    let found = sqlx::query_as::<_, (String, bool, i32, NaiveDateTime)>(
+        "SELECT r.session_id, r.used_at IS NOT NULL, s.user_id, s.expires_at
+         FROM refresh_tokens r JOIN user_sessions s ON s.id = r.session_id
+         WHERE r.token_hash = $1 AND s.revoked_at IS NULL AND s.expires_at > $2
+         FOR UPDATE OF r"
+    )
+    .bind(refresh_token_hash(token))
+    .bind(now)
+    .fetch_optional(&mut tx)
+    .await?;
+    let Some((session_id, used, user_id, expires_at)) = found else {
+        return Ok(Refresh::Invalid);
+    };
+
+    if used {
+        sqlx::query("UPDATE user_sessions SET revoked_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(&session_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        info!("Refresh token of session {} was used twice; revoked the session", session_id);
+        return Ok(Refresh::Reused { session_id, expires_at });
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = $1 WHERE token_hash = $2")
+        .bind(now)
+        .bind(refresh_token_hash(token))
+        .execute(&mut tx)
+        .await?;
+    let refresh_token = new_refresh_token();
+    sqlx::query("INSERT INTO refresh_tokens (token_hash, session_id) VALUES ($1, $2)")
+        .bind(refresh_token_hash(&refresh_token))
+        .bind(&session_id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Refresh::Rotated { user_id, session_id, expires_at, refresh_token })
+}
+
+// Add a single access token to the Redis denylist until it expires, e.g. the one a client logged
+// out with. Without Redis only revoking its session stops it.
+pub async fn deny_token(redis_client: Option<&redis::Client>, jti: &str, exp: usize) {
+    let Some(redis_client) = redis_client else {
+        return;
+    };
+    let ttl = (exp as i64 - chrono::Utc::now().timestamp()).max(1) as usize;
+    let result = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.set_ex::<_, _, ()>(token_denylist_key(jti), 1, ttl).await
+    })
+    .await;
+    if let Err(e) = result {
+        error!("Failed to add token {} to the denylist: {:?}", jti, e);
+    }
+}
+
+async fn is_token_denied(redis_client: &redis::Client, jti: &str) -> bool {
+    let denied = circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.exists(token_denylist_key(jti)).await
+    })
+    .await;
+    denied.unwrap_or_else(|e| {
+        error!("Token denylist unavailable: {:?}", e);
+        false
+    })
+}
+
+// Add revoked sessions to the Redis denylist until their tokens would have expired anyway.
+// Failures are logged; the database still records the revocation.
+pub async fn deny(redis_client: Option<&redis::Client>, sessions: &[(String, NaiveDateTime)]) {
+    let Some(redis_client) = redis_client else {
+        return;
+    };
+    if sessions.is_empty() {
+        return;
+    }
+    let now = chrono::Utc::now().naive_utc();
+    let mut pipe = redis::pipe();
+    for (session_id, expires_at) in sessions {
+        let ttl = (*expires_at - now).num_seconds().max(1) as usize;
+        pipe.set_ex(denylist_key(session_id), 1, ttl).ignore();
+    }
+    let result = circuit_breaker::redis(redis_client, |mut conn| async move {
+        pipe.query_async::<_, ()>(&mut conn).await
+    })
+    .await;
+    if let Err(e) = result {
+        error!("Failed to add {} sessions to the denylist: {:?}", sessions.len(), e);
+    }
+}
+
+async fn is_denied(redis_client: &redis::Client, session_id: &str) -> redis::RedisResult<bool> {
+    circuit_breaker::redis(redis_client, |mut conn| async move {
+        conn.exists(denylist_key(session_id)).await
+    })
+    .await
+}
+
+// Whether a session has been revoked. The Redis denylist answers when it is reachable; without
+// Redis the session's row is checked instead. Sessions with no row are treated as revoked.
+pub async fn is_revoked(db_pool: &PgPool, redis_client: Option<&redis::Client>, session_id: &str) -> Result<bool, sqlx::Error> {
+    if let Some(redis_client) = redis_client {
+        match is_denied(redis_client, session_id).await {
+            Ok(denied) => return Ok(denied),
+            Err(e) => error!("Session denylist unavailable, checking the database: {:?}", e),
+        }
+    }
+
+    let revoked = sqlx::query_scalar::<_, bool>("SELECT revoked_at IS NOT NULL FROM user_sessions WHERE id = $1")
+        .bind(session_id)
+        .fetch_optional(db_pool)
+        .await?;
+    Ok(revoked.unwrap_or(true))
+}
+
+async fn touch(db_pool: &PgPool, session_id: &str, ip_address: Option<&str>) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    sqlx::query(
+        "UPDATE user_sessions SET last_seen_at = $1, ip_address = COALESCE($2, ip_address) WHERE id = $3 AND last_seen_at < $4"
+    )
+    .bind(now)
+    .bind(ip_address)
+    .bind(session_id)
+    .bind(now - chrono::Duration::seconds(LAST_SEEN_RESOLUTION_SECS))
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
+
+// Re-populate the denylist from the database, e.g. after Redis lost its data
+pub async fn restore_denylist(db_pool: &PgPool, redis_client: &redis::Client) -> Result<usize, sqlx::Error> {
+    let revoked = sqlx::query_as::<_, (String, NaiveDateTime)>(
+        "SELECT id, expires_at FROM user_sessions WHERE revoked_at IS NOT NULL AND expires_at > $1"
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_all(db_pool)
+    .await?;
+
+    deny(Some(redis_client), &revoked).await;
+    info!("Restored {} revoked sessions to the denylist", revoked.len());
+    Ok(revoked.len())
+}
+
+// Middleware rejecting requests made with a revoked session's token or a denied token, and
+// keeping each session's last seen time and IP current. Requests without a valid token pass
+// through for the handlers to deal with.
+pub async fn check_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let claims = request_token(req.request()).and_then(|token| decode_claims(&token));
+    let session_id = claims.as_ref().and_then(|claims| claims.sid.clone());
+    let state = req.app_data::<web::Data<Arc<Mutex<AppState>>>>().cloned();
+
+    if let (Some(session_id), Some(state)) = (session_id, state) {
+        let (db_pool, redis_client) = {
+            let state = state.lock().await;
+            (state.db_pool.clone(), state.redis_client.clone())
+        };
+        match is_revoked(&db_pool, redis_client.as_ref(), &session_id).await {
+            Ok(false) => {}
+            Ok(true) => return Ok(req.error_response(ApiError::Unauthorized)),
+            Err(e) => return Ok(req.error_response(ApiError::from(e))),
+        }
+        let jti = claims.as_ref().and_then(|claims| claims.jti.as_deref());
+        if let (Some(jti), Some(redis_client)) = (jti, redis_client.as_ref()) {
+            if is_token_denied(redis_client, jti).await {
+                return Ok(req.error_response(ApiError::Unauthorized));
+            }
+        }
+        if let Err(e) = touch(&db_pool, &session_id, client_ip(req.request()).as_deref()).await {
+            error!("Failed to update last seen time of session {}: {:?}", session_id, e);
+        }
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
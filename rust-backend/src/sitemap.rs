@@ -0,0 +1,175 @@
+use std::time::Duration;
+use chrono::NaiveDateTime;
+use log::{error, info};
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::feeds::{self, escape_xml};
+use crate::models::Video;
+use crate::oembed;
+use crate::storage::{ObjectStore, Storage};
+
+type SitemapResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Document names, which are also the paths they're served at
+pub const VIDEO_SITEMAP: &str = "sitemap-videos.xml";
+pub const MRSS_FEED: &str = "mrss.xml";
+
+// A single sitemap file may list at most 50,000 URLs
+const SITEMAP_URL_LIMIT: i64 = 50_000;
+const MRSS_ITEM_LIMIT: i64 = 1000;
+
+// Google's limits for these video sitemap fields
+const TITLE_LIMIT: usize = 100;
+const DESCRIPTION_LIMIT: usize = 2048;
+const TAG_LIMIT: usize = 32;
+
+fn truncate(value: &str, limit: usize) -> String {
+    value.chars().take(limit).collect()
+}
+
+fn s3_key(name: &str) -> String {
+    format!("sitemaps/{}", name)
+}
+
+fn video_entry(video: &Video, base_url: &str) -> String {
+    let mut entry = String::from("  <url>\n");
+    entry.push_str(&format!("    <loc>{}/video/{}</loc>\n", escape_xml(base_url), video.id));
+    entry.push_str(&format!("    <lastmod>{}</lastmod>\n", video.updated_at.and_utc().format("%Y-%m-%dT%H:%M:%S+00:00")));
+
+    // Google requires a thumbnail for the video extension; the page is still listed without one
+    if let Some(name) = video.thumbnail_url.as_deref().and_then(|key| key.rsplit('/').next()) {
+        entry.push_str("    <video:video>\n");
+        entry.push_str(&format!("      <video:thumbnail_loc>{}/api/thumbnails/{}</video:thumbnail_loc>\n", escape_xml(base_url), escape_xml(name)));
+        entry.push_str(&format!("      <video:title>{}</video:title>\n", escape_xml(&truncate(&video.title, TITLE_LIMIT))));
+        let description = video.description.as_deref().filter(|d| !d.trim().is_empty()).unwrap_or(&video.title);
+        entry.push_str(&format!("      <video:description>{}</video:description>\n", escape_xml(&truncate(description, DESCRIPTION_LIMIT))));
+        entry.push_str(&format!("      <video:content_loc>{}/api/videos/{}/stream</video:content_loc>\n", escape_xml(base_url), video.id));
+        if video.allow_embed {
+            entry.push_str(&format!("      <video:player_loc>{}/embed/{}</video:player_loc>\n", escape_xml(base_url), video.id));
+        }
+        if let Some(duration) = video.duration.filter(|d| *d > 0) {
+            entry.push_str(&format!("      <video:duration>{}</video:duration>\n", duration));
+        }
+        if let Some(views) = video.view_count {
+            entry.push_str(&format!("      <video:view_count>{}</video:view_count>\n", views.max(0)));
+        }
+        if let Some(uploaded) = video.upload_date {
+            entry.push_str(&format!("      <video:publication_date>{}</video:publication_date>\n", uploaded.and_utc().format("%Y-%m-%dT%H:%M:%S+00:00")));
+        }
+        for tag in video.tags.iter().flatten().take(TAG_LIMIT) {
+            entry.push_str(&format!("      <video:tag>{}</video:tag>\n", escape_xml(tag)));
+        }
+        // Google takes either an allow or a deny list, not both; an allow list already excludes
+        // everything else
+        if !video.allowed_countries.is_empty() {
+            entry.push_str(&format!("      <video:restriction relationship=\"allow\">{}</video:restriction>\n", video.allowed_countries.join(" ")));
+        } else if !video.blocked_countries.is_empty() {
+            entry.push_str(&format!("      <video:restriction relationship=\"deny\">{}</video:restriction>\n", video.blocked_countries.join(" ")));
+        }
+        entry.push_str("    </video:video>\n");
+    }
+    entry.push_str("  </url>\n");
+    entry
+}
+
+pub fn build_video_sitemap(videos: &[Video], base_url: &str) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" \
+         xmlns:video=\"http://www.google.com/schemas/sitemap-video/1.1\">\n",
+    );
+    for video in videos {
+        xml.push_str(&video_entry(video, base_url));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+// The catalogue's watermark if either document is older than it (or missing). Any change to a
+// video bumps its updated_at, so the latest one says when the catalogue last changed.
+pub async fn stale_watermark(db_pool: &PgPool) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    let watermark: NaiveDateTime = sqlx::query_scalar("SELECT COALESCE(MAX(updated_at), 'epoch'::timestamp) FROM videos")
+        .fetch_one(db_pool)
+        .await?;
+    let current: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sitemap_documents WHERE name = ANY($1) AND last_modified >= $2")
+        .bind([VIDEO_SITEMAP, MRSS_FEED])
+        .bind(watermark)
+        .fetch_one(db_pool)
+        .await?;
+    Ok((current < 2).then_some(watermark))
+}
+
+pub async fn record_document(db_pool: &PgPool, name: &str, s3_key: &str, last_modified: NaiveDateTime) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO sitemap_documents (name, s3_key, last_modified, generated_at) VALUES ($1, $2, $3, NOW())
+         ON CONFLICT (name) DO UPDATE SET s3_key = EXCLUDED.s3_key, last_modified = EXCLUDED.last_modified, generated_at = EXCLUDED.generated_at"
+    )
+    .bind(name)
+    .bind(s3_key)
+    .bind(last_modified)
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
+
+// (s3_key, last_modified) of a generated document
+pub async fn get_document(db_pool: &PgPool, name: &str) -> Result<Option<(String, NaiveDateTime)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, NaiveDateTime)>("SELECT s3_key, last_modified FROM sitemap_documents WHERE name = $1")
+        .bind(name)
+        .fetch_optional(db_pool)
+        .await
+}
+
+async fn upload(storage: &dyn ObjectStore, bucket: &str, key: &str, content_type: &str, body: String) -> SitemapResult<()> {
+    storage.put(bucket, key, body.into_bytes().into(), content_type).await?;
+    Ok(())
+}
+
+// Rebuild the sitemap and MRSS feed if the catalogue changed since they were last written
+pub async fn generate_if_stale(db_pool: &PgPool, storage: &dyn ObjectStore, bucket: &str) -> SitemapResult<bool> {
+    let Some(watermark) = stale_watermark(db_pool).await? else {
+        return Ok(false);
+    };
+    let base_url = oembed::public_base_url();
+
+    let videos = sqlx::query_as::<_, Video>(&format!("{} ORDER BY upload_date DESC LIMIT $1", feeds::PUBLIC_VIDEOS_SQL))
+        .bind(SITEMAP_URL_LIMIT)
+        .fetch_all(db_pool)
+        .await?;
+    let listed = videos.len();
+    let sitemap = build_video_sitemap(&videos, &base_url);
+    upload(storage, bucket, &s3_key(VIDEO_SITEMAP), "application/xml", sitemap).await?;
+    record_document(db_pool, VIDEO_SITEMAP, &s3_key(VIDEO_SITEMAP), watermark).await?;
+
+    let recent: Vec<Video> = videos.into_iter().take(MRSS_ITEM_LIMIT as usize).collect();
+    let items = feeds::with_authors(db_pool, recent).await?;
+    let title = oembed::provider_name();
+    let description = format!("Latest videos on {}", title);
+    let feed_url = format!("{}/{}", base_url, MRSS_FEED);
+    let channel = feeds::Channel { title: &title, description: &description, link: &base_url, feed_url: &feed_url };
+    let mrss = feeds::rss(&channel, &items, &base_url);
+    upload(storage, bucket, &s3_key(MRSS_FEED), "application/rss+xml", mrss).await?;
+    record_document(db_pool, MRSS_FEED, &s3_key(MRSS_FEED), watermark).await?;
+
+    info!("Regenerated video sitemap ({} videos) and MRSS feed (catalogue modified {})", listed, watermark);
+    Ok(true)
+}
+
+pub async fn run_sitemap_generator(db_pool: PgPool, storage: Storage) {
+    let interval_secs = std::env::var("SITEMAP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600);
+    let bucket = std::env::var("S3_BUCKET")
+        .or_else(|_| std::env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    info!("Starting sitemap generator (interval: {} seconds)", interval_secs);
+
+    loop {
+        if let Err(e) = generate_if_stale(&db_pool, storage.as_ref(), &bucket).await {
+            error!("Error generating sitemap: {:?}", e);
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::models::{SphericalRequest, VideoSpherical};
+
+// 360° and VR videos carry their projection in the upload's metadata (the sv3d and st3d boxes of
+// MP4, or WebM's projection element and StereoMode), which ffprobe reports as side data of the
+// video stream. It is read when renditions are transcoded, since the renditions themselves don't
+// keep it, and returned with the video so clients can switch to a VR-capable player. Uploaders
+// can override it when the file says nothing or says it wrong.
+
+pub const PROJECTIONS: &[&str] = &["none", "equirectangular", "cubemap"];
+pub const STEREO_MODES: &[&str] = &["mono", "top_bottom", "left_right"];
+
+pub const SOURCE_DETECTED: &str = "detected";
+pub const SOURCE_UPLOADER: &str = "uploader";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spherical {
+    pub projection: String,
+    pub stereo_mode: String,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    #[serde(default)]
+    side_data_list: Vec<HashMap<String, serde_json::Value>>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+// ffprobe's names for projections, e.g. "tiled equirectangular", onto ours
+fn projection_name(projection: &str) -> Option<&'static str> {
+    let projection = projection.trim().to_lowercase();
+    if projection.contains("equirectangular") {
+        Some("equirectangular")
+    } else if projection.contains("cubemap") {
+        Some("cubemap")
+    } else {
+        None
+    }
+}
+
+// Stereo 3D side data types ("top and bottom") or WebM stereo_mode tags ("bottom_top"). Layouts
+// players can't split are treated as mono.
+fn stereo_mode_name(mode: &str) -> &'static str {
+    let mode = mode.trim().to_lowercase();
+    if mode.starts_with("top and bottom") || mode == "top_bottom" || mode == "bottom_top" {
+        "top_bottom"
+    } else if mode.starts_with("side by side") || mode == "left_right" || mode == "right_left" {
+        "left_right"
+    } else {
+        "mono"
+    }
+}
+
+// The projection of the first video stream, from ffprobe's JSON with its side data and tags.
+// None for flat video.
+pub fn parse_probe_output(json: &str) -> Result<Option<Spherical>, serde_json::Error> {
+    let probe: ProbeOutput = serde_json::from_str(json)?;
+    let Some(stream) = probe.streams.into_iter().next() else {
+        return Ok(None);
+    };
+    let side_data = |kind: &str, field: &str| {
+        stream.side_data_list
+            .iter()
+            .find(|data| data.get("side_data_type").and_then(|t| t.as_str()) == Some(kind))
+            .and_then(|data| data.get(field))
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+
+    let Some(projection) = side_data("Spherical Mapping", "projection").as_deref().and_then(projection_name) else {
+        return Ok(None);
+    };
+    let stereo_mode = side_data("Stereo 3D", "type")
+        .or_else(|| stream.tags.get("stereo_mode").cloned())
+        .map_or("mono", |mode| stereo_mode_name(&mode));
+    Ok(Some(Spherical { projection: projection.to_string(), stereo_mode: stereo_mode.to_string() }))
+}
+
+pub fn validate(request: &SphericalRequest) -> Result<Spherical, String> {
+    let projection = request.projection.trim().to_lowercase();
+    if !PROJECTIONS.contains(&projection.as_str()) {
+        return Err(format!("projection must be one of {}", PROJECTIONS.join(", ")));
+    }
+    let stereo_mode = request.stereo_mode.as_deref().map(|m| m.trim().to_lowercase()).unwrap_or_else(|| "mono".to_string());
+    if !STEREO_MODES.contains(&stereo_mode.as_str()) {
+        return Err(format!("stereoMode must be one of {}", STEREO_MODES.join(", ")));
+    }
+    Ok(Spherical { projection, stereo_mode })
+}
+
+// The video's projection, None if it is flat
+pub async fn get(db_pool: &PgPool, video_id: i32) -> Result<Option<VideoSpherical>, sqlx::Error> {
+    sqlx::query_as::<_, VideoSpherical>("SELECT * FROM video_spherical WHERE video_id = $1 AND projection <> 'none'")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Record what was detected in the upload, unless the uploader has set the projection themselves
+pub async fn store_detected(db_pool: &PgPool, video_id: i32, detected: Option<&Spherical>) -> Result<(), sqlx::Error> {
+    let Some(detected) = detected else {
+        sqlx::query("DELETE FROM video_spherical WHERE video_id = $1 AND source = $2")
+            .bind(video_id)
+            .bind(SOURCE_DETECTED)
+            .execute(db_pool)
+            .await?;
+        return Ok(());
+    };
+    sqlx::query(
+        "INSERT INTO video_spherical (video_id, projection, stereo_mode, source, updated_at) VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (video_id) DO UPDATE SET projection = EXCLUDED.projection, stereo_mode = EXCLUDED.stereo_mode, updated_at = NOW()
+         WHERE video_spherical.source = $4"
+    )
+    .bind(video_id)
+    .bind(&detected.projection)
+    .bind(&detected.stereo_mode)
+    .bind(SOURCE_DETECTED)
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_override(db_pool: &PgPool, video_id: i32, spherical: &Spherical) -> Result<VideoSpherical, sqlx::Error> {
+    sqlx::query_as::<_, VideoSpherical>(
+        "INSERT INTO video_spherical (video_id, projection, stereo_mode, source, updated_at) VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (video_id) DO UPDATE SET projection = EXCLUDED.projection, stereo_mode = EXCLUDED.stereo_mode,
+             source = EXCLUDED.source, updated_at = NOW()
+         RETURNING *"
+    )
+    .bind(video_id)
+    .bind(&spherical.projection)
+    .bind(&spherical.stereo_mode)
+    .bind(SOURCE_UPLOADER)
+    .fetch_one(db_pool)
+    .await
+}
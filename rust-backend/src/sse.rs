@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use actix_web::http::header::{self, ContentEncoding};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use futures::stream::{self, Stream};
+use log::{error, info};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use crate::client_queue::{client_queue, ClientReceiver, ClientSender};
+use crate::error::ApiError;
+use crate::handlers::{authenticated_user_id, decode_user_id, owned_video};
+use crate::notifications::{get_notifications_after, latest_notification_id};
+use crate::processing::{self, ProcessingUpdate};
+use crate::AppState;
+
+// Comment lines sent on quiet streams so proxies don't time the connection out
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// Notifications fetched per poll; anything beyond this is picked up by the next poll
+const NOTIFICATION_BATCH_SIZE: i64 = 100;
+
+fn notification_poll_interval() -> Duration {
+    let secs = env::var("SSE_NOTIFICATION_POLL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(5);
+    Duration::from_secs(secs)
+}
+
+// EventSource can't set an Authorization header, so the JWT may also come as ?token=
+#[derive(Deserialize)]
+struct SseAuthQuery {
+    token: Option<String>,
+}
+
+fn sse_event(id: Option<i32>, event: &str, data: &str) -> Bytes {
+    let mut frame = String::new();
+    if let Some(id) = id {
+        frame.push_str(&format!("id: {}\n", id));
+    }
+    frame.push_str(&format!("event: {}\ndata: {}\n\n", event, data));
+    Bytes::from(frame)
+}
+
+fn keepalive() -> Bytes {
+    Bytes::from_static(b": keepalive\n\n")
+}
+
+fn sse_response<S>(stream: S) -> HttpResponse
+where
+    S: Stream<Item = Result<Bytes, actix_web::Error>> + 'static,
+{
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Compression and proxy buffering would hold events back until a buffer fills
+        .insert_header(ContentEncoding::Identity)
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
+
+// A comment stream's slot in video_clients, removed when the client goes away
+struct CommentSubscription {
+    video_id: i32,
+    state: Arc<Mutex<AppState>>,
+    tx: ClientSender,
+    rx: ClientReceiver,
+}
+
+impl Drop for CommentSubscription {
+    fn drop(&mut self) {
+        let state = self.state.clone();
+        let video_id = self.video_id;
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let state = state.lock().await;
+            let mut clients = state.video_clients.lock().unwrap();
+            if let Some(client_list) = clients.get_mut(&video_id) {
+                client_list.retain(|tx_ref| !tx_ref.same_channel(&tx));
+                if client_list.is_empty() {
+                    clients.remove(&video_id);
+                }
+            }
+            info!("SSE comment client disconnected for video_id: {}", video_id);
+        });
+    }
+}
+
+// Same comment events as /api/ws/comments/{video_id}, for clients that can't use WebSockets
+#[get("/api/sse/comments/{video_id}")]
+async fn sse_comments(
+    path: web::Path<i32>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, ApiError> {
+    let video_id = path.into_inner();
+    let (tx, rx) = client_queue();
+
+    state.lock().await.video_clients.lock().unwrap()
+        .entry(video_id)
+        .or_default()
+        .push(tx.clone());
+    info!("SSE comment client connected for video_id: {}", video_id);
+
+    let subscription = CommentSubscription {
+        video_id,
+        state: state.get_ref().clone(),
+        tx,
+        rx,
+    };
+    let stream = stream::unfold(subscription, |mut subscription| async move {
+        let chunk = match tokio::time::timeout(KEEPALIVE_INTERVAL, subscription.rx.recv()).await {
+            Ok(Some(comment)) => sse_event(None, "comment", &comment),
+            // The queue overflowed; end the stream and let EventSource reconnect
+            Ok(None) => return None,
+            Err(_) => keepalive(),
+        };
+        Some((Ok(chunk), subscription))
+    });
+
+    Ok(sse_response(stream))
+}
+
+struct NotificationPoller {
+    db_pool: PgPool,
+    user_id: i32,
+    last_id: i32,
+    pending: VecDeque<Bytes>,
+    last_sent: Instant,
+}
+
+// New notifications for the logged in user. Event ids are notification ids, so a reconnecting
+// EventSource resumes from Last-Event-ID without missing or repeating any.
+#[get("/api/sse/notifications")]
+async fn sse_notifications(
+    query: web::Query<SseAuthQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)
+        .or_else(|| query.token.as_deref().and_then(decode_user_id))
+        .ok_or(ApiError::Unauthorized)?;
+    let db_pool = state.lock().await.db_pool.clone();
+
+    let last_event_id = http_req.headers()
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<i32>().ok());
+    let last_id = match last_event_id {
+        Some(id) => id,
+        None => latest_notification_id(&db_pool, user_id).await?,
+    };
+
+    let poller = NotificationPoller {
+        db_pool,
+        user_id,
+        last_id,
+        pending: VecDeque::new(),
+        last_sent: Instant::now(),
+    };
+    let interval = notification_poll_interval();
+    let stream = stream::unfold(poller, move |mut poller| async move {
+        loop {
+            if let Some(chunk) = poller.pending.pop_front() {
+                poller.last_sent = Instant::now();
+                return Some((Ok(chunk), poller));
+            }
+            if poller.last_sent.elapsed() >= KEEPALIVE_INTERVAL {
+                poller.last_sent = Instant::now();
+                return Some((Ok(keepalive()), poller));
+            }
+
+            tokio::time::sleep(interval).await;
+            match get_notifications_after(&poller.db_pool, poller.user_id, poller.last_id, NOTIFICATION_BATCH_SIZE).await {
+                Ok(notifications) => {
+                    for notification in notifications {
+                        poller.last_id = notification.id;
+                        let data = serde_json::to_string(&notification).unwrap_or_default();
+                        poller.pending.push_back(sse_event(Some(notification.id), "notification", &data));
+                    }
+                }
+                Err(e) => error!("Failed to poll notifications for user {}: {}", poller.user_id, e),
+            }
+        }
+    });
+
+    Ok(sse_response(stream))
+}
+
+struct ProcessingPoller {
+    db_pool: PgPool,
+    video_id: i32,
+    last: Option<ProcessingUpdate>,
+    last_sent: Instant,
+}
+
+// A video's processing status, for its uploader or an admin. The current status is sent on
+// connect and again whenever it or one of the video's jobs changes; the stream ends if the
+// video is deleted.
+#[get("/api/sse/videos/{id}/processing")]
+async fn sse_processing(
+    path: web::Path<i32>,
+    query: web::Query<SseAuthQuery>,
+    state: web::Data<Arc<Mutex<AppState>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let video_id = path.into_inner();
+    let user_id = authenticated_user_id(&http_req)
+        .or_else(|| query.token.as_deref().and_then(decode_user_id))
+        .ok_or(ApiError::Unauthorized)?;
+    let db_pool = state.lock().await.db_pool.clone();
+    owned_video(&db_pool, video_id, user_id).await?;
+
+    let poller = ProcessingPoller {
+        db_pool,
+        video_id,
+        last: None,
+        last_sent: Instant::now(),
+    };
+    let interval = processing::poll_interval();
+    let stream = stream::unfold(poller, move |mut poller| async move {
+        loop {
+            match processing::current(&poller.db_pool, poller.video_id).await {
+                Ok(None) => return None,
+                Ok(Some(update)) if poller.last.as_ref() != Some(&update) => {
+                    let data = serde_json::to_string(&update).unwrap_or_default();
+                    poller.last = Some(update);
+                    poller.last_sent = Instant::now();
+                    return Some((Ok(sse_event(None, "processing", &data)), poller));
+                }
+                Ok(Some(_)) => {}
+                Err(e) => error!("Failed to poll processing status for video {}: {}", poller.video_id, e),
+            }
+            if poller.last_sent.elapsed() >= KEEPALIVE_INTERVAL {
+                poller.last_sent = Instant::now();
+                return Some((Ok(keepalive()), poller));
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+
+    Ok(sse_response(stream))
+}
+
+pub fn configure_sse_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(sse_comments)
+       .service(sse_notifications)
+       .service(sse_processing);
+}
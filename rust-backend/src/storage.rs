@@ -0,0 +1,774 @@
+use std::env;
+use std::fmt::Debug;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+    ChecksumMode, CompletedMultipartUpload, CompletedPart, GlacierJobParameters, MetadataDirective, RestoreRequest,
+    ServerSideEncryption, StorageClass, Tier,
+};
+use bytes::{Bytes, BytesMut};
+use chrono::NaiveDateTime;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use log::{error, info, warn};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::circuit_breaker::{self, CallError};
+use crate::error::ApiError;
+use crate::services;
+
+// Object storage behind a trait, so the rest of the backend doesn't care where files live.
+// STORAGE_BACKEND picks the implementation: "s3" (the default) for AWS S3 or MinIO, or "local"
+// to keep objects as plain files under STORAGE_PATH, for development and small self-hosted
+// deployments without MinIO. Buckets are directories there.
+
+pub type Storage = Arc<dyn ObjectStore>;
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+pub type BodyStream = BoxStream<'static, StorageResult<Bytes>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("{0} not found")]
+    NotFound(String),
+
+    // The backend is down or too slow; the request may succeed later
+    #[error("{0}")]
+    Unavailable(String),
+
+    // e.g. storage classes on the local backend
+    #[error("{0}")]
+    Unsupported(String),
+
+    #[error("{0}")]
+    Backend(String),
+}
+
+impl StorageError {
+    // 503 when the backend was skipped or too slow, otherwise `f` of the error
+    pub fn into_api_error(self, f: impl FnOnce(StorageError) -> ApiError) -> ApiError {
+        match self {
+            StorageError::Unavailable(message) => ApiError::ServiceUnavailable(message),
+            other => f(other),
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, StorageError::NotFound(_))
+    }
+}
+
+// An object being read
+pub struct Object {
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    pub body: BodyStream,
+}
+
+impl Object {
+    pub async fn bytes(self) -> StorageResult<Bytes> {
+        let mut buffer = BytesMut::with_capacity(self.content_length.unwrap_or(0) as usize);
+        let mut body = self.body;
+        while let Some(chunk) = body.try_next().await? {
+            buffer.extend_from_slice(&chunk);
+        }
+        Ok(buffer.freeze())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjectInfo {
+    pub size: u64,
+    pub etag: Option<String>,
+    pub sha256: Option<String>, // base64 SHA-256 of the whole object, if the backend keeps one
+    pub md5: Option<String>,    // hex MD5 of the whole object, if the backend keeps one
+    pub restore: Option<String>, // S3's x-amz-restore header
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedObject {
+    pub key: String,
+    pub size: i64,
+    pub last_modified: Option<NaiveDateTime>,
+}
+
+// One page of a listing; `next` continues it
+#[derive(Debug, Default)]
+pub struct ListPage {
+    pub objects: Vec<ListedObject>,
+    pub next: Option<String>,
+}
+
+pub trait ObjectStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn ensure_bucket<'a>(&'a self, bucket: &'a str) -> BoxFuture<'a, ()>;
+
+    fn get<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<Object>>;
+
+    // Bytes `start` to `end` inclusive, failing if the object's ETag is no longer `if_match`
+    fn get_range<'a>(&'a self, bucket: &'a str, key: &'a str, start: u64, end: u64, if_match: Option<&'a str>) -> BoxFuture<'a, StorageResult<Object>>;
+
+    fn head<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<ObjectInfo>>;
+
+    // Where ffmpeg can read the object from for `valid_for`, seeking with ranged reads instead of
+    // downloading it whole: a presigned URL, or a local path
+    fn media_input<'a>(&'a self, bucket: &'a str, key: &'a str, valid_for: Duration) -> BoxFuture<'a, StorageResult<String>>;
+
+    fn put<'a>(&'a self, bucket: &'a str, key: &'a str, body: Bytes, content_type: &'a str) -> BoxFuture<'a, StorageResult<()>>;
+
+    fn put_file<'a>(&'a self, bucket: &'a str, key: &'a str, path: &'a Path, content_type: &'a str) -> BoxFuture<'a, StorageResult<()>>;
+
+    // Deleting an object that doesn't exist is not an error
+    fn delete<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<()>>;
+
+    fn list<'a>(&'a self, bucket: &'a str, prefix: &'a str, continuation: Option<String>) -> BoxFuture<'a, StorageResult<ListPage>>;
+
+    // Multipart uploads: parts of at least 5 MB except the last, numbered from 1. Returns the
+    // upload id.
+    fn create_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, content_type: &'a str) -> BoxFuture<'a, StorageResult<String>>;
+
+    // Returns the part's ETag, to be passed to complete_multipart
+    fn upload_part<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str, part_number: i32, body: Bytes) -> BoxFuture<'a, StorageResult<String>>;
+
+    fn complete_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str, parts: &'a [(i32, String)]) -> BoxFuture<'a, StorageResult<()>>;
+
+    // Aborting an upload that no longer exists is not an error
+    fn abort_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str) -> BoxFuture<'a, StorageResult<()>>;
+
+    fn set_storage_class<'a>(&'a self, _bucket: &'a str, _key: &'a str, _storage_class: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            Err(StorageError::Unsupported(format!("The {} storage backend has no storage classes", self.name())))
+        })
+    }
+
+    // Start restoring an archived object for `days`. Asking again while a restore is underway
+    // is not an error.
+    fn restore<'a>(&'a self, _bucket: &'a str, _key: &'a str, _days: i32) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            Err(StorageError::Unsupported(format!("The {} storage backend has no storage classes", self.name())))
+        })
+    }
+}
+
+// The backend configured by STORAGE_BACKEND
+pub async fn init_storage() -> Storage {
+    match env::var("STORAGE_BACKEND").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+        "local" => {
+            let root = env::var("STORAGE_PATH").unwrap_or_else(|_| "./storage".to_string());
+            info!("Using local storage in {}", root);
+            Arc::new(LocalStorage::new(root))
+        }
+        other => {
+            if !other.is_empty() && other != "s3" {
+                warn!("Unknown STORAGE_BACKEND {}, using S3", other);
+            }
+            Arc::new(S3Storage::new(services::init_s3_client().await))
+        }
+    }
+}
+
+pub struct S3Storage {
+    client: S3Client,
+}
+
+impl S3Storage {
+    pub fn new(client: S3Client) -> Self {
+        Self { client }
+    }
+}
+
+fn is_missing(debug: &str) -> bool {
+    debug.contains("NoSuchKey") || debug.contains("NotFound") || debug.contains("NoSuchUpload")
+}
+
+fn from_sdk<E: Debug, R: Debug>(what: &str, e: SdkError<E, R>) -> StorageError {
+    let debug = format!("{:?}", e);
+    if is_missing(&debug) {
+        StorageError::NotFound(what.to_string())
+    } else {
+        StorageError::Backend(debug)
+    }
+}
+
+fn from_call<E: Debug, R: Debug>(what: &str, e: CallError<SdkError<E, R>>) -> StorageError {
+    match e {
+        CallError::Open(name) => StorageError::Unavailable(format!("{} is unavailable, try again later", name)),
+        CallError::TimedOut(name) => StorageError::Unavailable(format!("{} did not respond in time, try again later", name)),
+        CallError::Failed(e) => from_sdk(what, e),
+    }
+}
+
+fn s3_body(body: ByteStream) -> BodyStream {
+    body.map_err(|e| StorageError::Backend(format!("{:?}", e))).boxed()
+}
+
+fn content_length(length: i64) -> Option<u64> {
+    (length > 0).then_some(length as u64)
+}
+
+// Requests that should answer quickly go through the S3 circuit breaker. Uploads of object
+// bodies don't, as their time grows with the body.
+impl ObjectStore for S3Storage {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    fn ensure_bucket<'a>(&'a self, bucket: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            // In AWS, buckets are created by Terraform, so we don't need to create them
+            // Just verify we can access the bucket
+            if env::var("MINIO_ENDPOINT").is_ok() {
+                // Local development - try to create bucket
+                match self.client.create_bucket().bucket(bucket).send().await {
+                    Ok(_) => info!("Bucket created successfully: {}", bucket),
+                    Err(err) => {
+                        if err.to_string().contains("BucketAlreadyExists") || err.to_string().contains("BucketAlreadyOwnedByYou") {
+                            info!("Bucket already exists: {}", bucket);
+                        } else {
+                            warn!("Error creating bucket {}: {:?}", bucket, err);
+                        }
+                    }
+                }
+            } else {
+                // Production - bucket should already exist, just verify access
+                match self.client.head_bucket().bucket(bucket).send().await {
+                    Ok(_) => info!("Successfully connected to S3 bucket: {}", bucket),
+                    Err(err) => error!("Cannot access S3 bucket {}: {:?}", bucket, err),
+                }
+            }
+        })
+    }
+
+    fn get<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<Object>> {
+        Box::pin(async move {
+            let output = circuit_breaker::s3(self.client.get_object().bucket(bucket).key(key).send())
+                .await
+                .map_err(|e| from_call(key, e))?;
+            Ok(Object {
+                content_type: output.content_type().map(str::to_string),
+                content_length: content_length(output.content_length()),
+                body: s3_body(output.body),
+            })
+        })
+    }
+
+    fn get_range<'a>(&'a self, bucket: &'a str, key: &'a str, start: u64, end: u64, if_match: Option<&'a str>) -> BoxFuture<'a, StorageResult<Object>> {
+        Box::pin(async move {
+            let request = self.client
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .range(format!("bytes={}-{}", start, end))
+                .set_if_match(if_match.map(str::to_string));
+            let output = circuit_breaker::s3(request.send()).await.map_err(|e| from_call(key, e))?;
+            Ok(Object {
+                content_type: output.content_type().map(str::to_string),
+                content_length: content_length(output.content_length()),
+                body: s3_body(output.body),
+            })
+        })
+    }
+
+    fn head<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<ObjectInfo>> {
+        Box::pin(async move {
+            let head = circuit_breaker::s3(self.client.head_object().bucket(bucket).key(key).checksum_mode(ChecksumMode::Enabled).send())
+                .await
+                .map_err(|e| from_call(key, e))?;
+            let kms_encrypted = matches!(head.server_side_encryption(), Some(ServerSideEncryption::AwsKms));
+            Ok(ObjectInfo {
+                size: head.content_length().max(0) as u64,
+                etag: head.e_tag().map(str::to_string),
+                // Composite checksums of multipart uploads ("<base64>-<parts>") can't be checked against the whole file
+                sha256: head.checksum_sha256().filter(|c| !c.contains('-')).map(str::to_string),
+                md5: head.e_tag().and_then(|etag| services::etag_md5(etag, kms_encrypted)),
+                restore: head.restore().map(str::to_string),
+            })
+        })
+    }
+
+    fn media_input<'a>(&'a self, bucket: &'a str, key: &'a str, valid_for: Duration) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            let config = PresigningConfig::expires_in(valid_for).map_err(|e| StorageError::Backend(format!("{:?}", e)))?;
+            let request = self.client.get_object().bucket(bucket).key(key).presigned(config).await.map_err(|e| from_sdk(key, e))?;
+            Ok(request.uri().to_string())
+        })
+    }
+
+    fn put<'a>(&'a self, bucket: &'a str, key: &'a str, body: Bytes, content_type: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            self.client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(body.into())
+                .content_type(content_type)
+                .send()
+                .await
+                .map_err(|e| from_sdk(key, e))?;
+            Ok(())
+        })
+    }
+
+    fn put_file<'a>(&'a self, bucket: &'a str, key: &'a str, path: &'a Path, content_type: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            let body = ByteStream::from_path(path).await.map_err(|e| StorageError::Backend(format!("{:?}", e)))?;
+            self.client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(body)
+                .content_type(content_type)
+                .send()
+                .await
+                .map_err(|e| from_sdk(key, e))?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            circuit_breaker::s3(self.client.delete_object().bucket(bucket).key(key).send())
+                .await
+                .map_err(|e| from_call(key, e))?;
+            Ok(())
+        })
+    }
+
+    fn list<'a>(&'a self, bucket: &'a str, prefix: &'a str, continuation: Option<String>) -> BoxFuture<'a, StorageResult<ListPage>> {
+        Box::pin(async move {
+            let page = circuit_breaker::s3(self.client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation)
+                .send())
+                .await
+                .map_err(|e| from_call(prefix, e))?;
+
+            let objects = page
+                .contents()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|o| {
+                    Some(ListedObject {
+                        key: o.key()?.to_string(),
+                        size: o.size(),
+                        last_modified: o.last_modified()
+                            .and_then(|t| chrono::DateTime::from_timestamp(t.secs(), t.subsec_nanos()))
+                            .map(|t| t.naive_utc()),
+                    })
+                })
+                .collect();
+            let next = if page.is_truncated() { page.next_continuation_token().map(str::to_string) } else { None };
+            Ok(ListPage { objects, next })
+        })
+    }
+
+    fn create_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, content_type: &'a str) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            let multipart = circuit_breaker::s3(self.client
+                .create_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .content_type(content_type)
+                .send())
+                .await
+                .map_err(|e| from_call(key, e))?;
+            multipart
+                .upload_id()
+                .map(str::to_string)
+                .ok_or_else(|| StorageError::Backend("S3 returned no upload id".to_string()))
+        })
+    }
+
+    fn upload_part<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str, part_number: i32, body: Bytes) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            let output = self.client
+                .upload_part()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body.into())
+                .send()
+                .await
+                .map_err(|e| from_sdk(key, e))?;
+            output
+                .e_tag()
+                .map(str::to_string)
+                .ok_or_else(|| StorageError::Backend(format!("S3 returned no ETag for part {}", part_number)))
+        })
+    }
+
+    fn complete_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str, parts: &'a [(i32, String)]) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            let completed = CompletedMultipartUpload::builder()
+                .set_parts(Some(
+                    parts.iter()
+                        .map(|(part_number, e_tag)| CompletedPart::builder().part_number(*part_number).e_tag(e_tag).build())
+                        .collect(),
+                ))
+                .build();
+            self.client
+                .complete_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(completed)
+                .send()
+                .await
+                .map_err(|e| from_sdk(key, e))?;
+            Ok(())
+        })
+    }
+
+    fn abort_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            match circuit_breaker::s3(self.client.abort_multipart_upload().bucket(bucket).key(key).upload_id(upload_id).send()).await {
+                Ok(_) => Ok(()),
+                // Already aborted or cleaned up by a bucket lifecycle rule
+                Err(e) => match from_call(key, e) {
+                    StorageError::NotFound(_) => Ok(()),
+                    other => Err(other),
+                },
+            }
+        })
+    }
+
+    // Copies the object onto itself. Objects above 5 GB can't be copied in one request.
+    fn set_storage_class<'a>(&'a self, bucket: &'a str, key: &'a str, storage_class: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            circuit_breaker::s3(self.client
+                .copy_object()
+                .bucket(bucket)
+                .key(key)
+                .copy_source(format!("{}/{}", bucket, urlencoding::encode(key)))
+                .metadata_directive(MetadataDirective::Copy)
+                .storage_class(StorageClass::from(storage_class))
+                .send())
+                .await
+                .map_err(|e| from_call(key, e))?;
+            Ok(())
+        })
+    }
+
+    fn restore<'a>(&'a self, bucket: &'a str, key: &'a str, days: i32) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            let request = RestoreRequest::builder()
+                .days(days)
+                .glacier_job_parameters(GlacierJobParameters::builder().tier(Tier::Standard).build())
+                .build();
+            match circuit_breaker::s3(self.client.restore_object().bucket(bucket).key(key).restore_request(request).send()).await {
+                Ok(_) => Ok(()),
+                // Someone else asked for it already, e.g. another replica
+                Err(CallError::Failed(e)) if format!("{:?}", e).contains("RestoreAlreadyInProgress") => Ok(()),
+                Err(e) => Err(from_call(key, e)),
+            }
+        })
+    }
+}
+
+// Uploads in progress are kept here, outside every bucket
+const LOCAL_UPLOADS_DIR: &str = ".uploads";
+
+const LOCAL_READ_CHUNK: usize = 256 * 1024;
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // Where an object lives. Keys are relative paths that can't leave the bucket.
+    fn path(&self, bucket: &str, key: &str) -> StorageResult<PathBuf> {
+        let is_plain = |s: &str| {
+            !s.is_empty() && !s.contains('\\') && Path::new(s).components().all(|c| matches!(c, Component::Normal(_)))
+        };
+        if !is_plain(bucket) || bucket.contains('/') || bucket.starts_with('.') || !is_plain(key) {
+            return Err(StorageError::Backend(format!("Invalid object key {}/{}", bucket, key)));
+        }
+        Ok(self.root.join(bucket).join(key))
+    }
+
+    fn upload_dir(&self, upload_id: &str) -> StorageResult<PathBuf> {
+        if uuid::Uuid::parse_str(upload_id).is_err() {
+            return Err(StorageError::NotFound(format!("Upload {}", upload_id)));
+        }
+        Ok(self.root.join(LOCAL_UPLOADS_DIR).join(upload_id))
+    }
+
+    // Writes go to a temporary file next to the object that is renamed over it once complete,
+    // so readers never see half of one
+    async fn temp_path(&self, path: &Path) -> StorageResult<PathBuf> {
+        let parent = path.parent().ok_or_else(|| StorageError::Backend(format!("Invalid object path {}", path.display())))?;
+        tokio::fs::create_dir_all(parent).await.map_err(io_error)?;
+        Ok(parent.join(format!(".{}.tmp", uuid::Uuid::new_v4())))
+    }
+
+    async fn commit(&self, temp: &Path, path: &Path, written: std::io::Result<()>) -> StorageResult<()> {
+        let result = match written {
+            Ok(()) => tokio::fs::rename(temp, path).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(temp).await;
+        }
+        result.map_err(io_error)
+    }
+
+    async fn write(&self, path: &Path, body: &[u8]) -> StorageResult<()> {
+        let temp = self.temp_path(path).await?;
+        let written = tokio::fs::write(&temp, body).await;
+        self.commit(&temp, path, written).await
+    }
+
+    async fn open(&self, bucket: &str, key: &str) -> StorageResult<(tokio::fs::File, u64)> {
+        let path = self.path(bucket, key)?;
+        let file = tokio::fs::File::open(&path).await.map_err(|e| not_found_or(key, e))?;
+        let metadata = file.metadata().await.map_err(io_error)?;
+        if !metadata.is_file() {
+            return Err(StorageError::NotFound(key.to_string()));
+        }
+        Ok((file, metadata.len()))
+    }
+}
+
+fn io_error(e: std::io::Error) -> StorageError {
+    StorageError::Backend(e.to_string())
+}
+
+fn not_found_or(what: &str, e: std::io::Error) -> StorageError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        StorageError::NotFound(what.to_string())
+    } else {
+        io_error(e)
+    }
+}
+
+// Objects don't keep a content type on disk, so it is guessed from the extension
+pub fn content_type_for_key(key: &str) -> Option<&'static str> {
+    let extension = key.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "ts" => "video/mp2t",
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "vtt" => "text/vtt",
+        _ => return None,
+    })
+}
+
+// Up to `len` bytes of the file from where it is positioned, in chunks
+fn file_body(file: tokio::fs::File, len: u64) -> BodyStream {
+    stream::try_unfold((file.take(len), vec![0u8; LOCAL_READ_CHUNK]), |(mut reader, mut buffer)| async move {
+        let read = reader.read(&mut buffer).await.map_err(io_error)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let chunk = Bytes::copy_from_slice(&buffer[..read]);
+        Ok(Some((chunk, (reader, buffer))))
+    })
+    .boxed()
+}
+
+impl ObjectStore for LocalStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn ensure_bucket<'a>(&'a self, bucket: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            match tokio::fs::create_dir_all(self.root.join(bucket)).await {
+                Ok(()) => info!("Using local bucket {}", self.root.join(bucket).display()),
+                Err(e) => error!("Cannot create local bucket {}: {}", self.root.join(bucket).display(), e),
+            }
+        })
+    }
+
+    fn get<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<Object>> {
+        Box::pin(async move {
+            let (file, len) = self.open(bucket, key).await?;
+            Ok(Object {
+                content_type: content_type_for_key(key).map(str::to_string),
+                content_length: Some(len),
+                body: file_body(file, len),
+            })
+        })
+    }
+
+    fn get_range<'a>(&'a self, bucket: &'a str, key: &'a str, start: u64, end: u64, _if_match: Option<&'a str>) -> BoxFuture<'a, StorageResult<Object>> {
+        Box::pin(async move {
+            let (mut file, len) = self.open(bucket, key).await?;
+            if start > end || start >= len {
+                return Err(StorageError::Backend(format!("Range {}-{} is outside {} ({} bytes)", start, end, key, len)));
+            }
+            let range_len = end.min(len - 1) - start + 1;
+            file.seek(std::io::SeekFrom::Start(start)).await.map_err(io_error)?;
+            Ok(Object {
+                content_type: content_type_for_key(key).map(str::to_string),
+                content_length: Some(range_len),
+                body: file_body(file, range_len),
+            })
+        })
+    }
+
+    fn head<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<ObjectInfo>> {
+        Box::pin(async move {
+            let (_, len) = self.open(bucket, key).await?;
+            Ok(ObjectInfo { size: len, ..ObjectInfo::default() })
+        })
+    }
+
+    fn media_input<'a>(&'a self, bucket: &'a str, key: &'a str, _valid_for: Duration) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            self.open(bucket, key).await?;
+            // file: so ffmpeg never mistakes a key with a colon for a protocol
+            Ok(format!("file:{}", self.path(bucket, key)?.to_string_lossy()))
+        })
+    }
+
+    fn put<'a>(&'a self, bucket: &'a str, key: &'a str, body: Bytes, _content_type: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            self.write(&self.path(bucket, key)?, &body).await
+        })
+    }
+
+    fn put_file<'a>(&'a self, bucket: &'a str, key: &'a str, source: &'a Path, _content_type: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            let path = self.path(bucket, key)?;
+            let temp = self.temp_path(&path).await?;
+            let written = tokio::fs::copy(source, &temp).await.map(|_| ());
+            self.commit(&temp, &path, written).await
+        })
+    }
+
+    fn delete<'a>(&'a self, bucket: &'a str, key: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.path(bucket, key)?).await {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(io_error(e)),
+            }
+        })
+    }
+
+    // Everything in one page, sorted by key
+    fn list<'a>(&'a self, bucket: &'a str, prefix: &'a str, _continuation: Option<String>) -> BoxFuture<'a, StorageResult<ListPage>> {
+        Box::pin(async move {
+            let bucket_dir = self.root.join(bucket);
+            let mut objects = Vec::new();
+            let mut dirs = vec![bucket_dir.clone()];
+            while let Some(dir) = dirs.pop() {
+                let mut entries = match tokio::fs::read_dir(&dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(io_error(e)),
+                };
+                while let Some(entry) = entries.next_entry().await.map_err(io_error)? {
+                    // Temporary files of writes in progress
+                    if entry.file_name().to_string_lossy().starts_with('.') {
+                        continue;
+                    }
+                    let metadata = entry.metadata().await.map_err(io_error)?;
+                    if metadata.is_dir() {
+                        dirs.push(entry.path());
+                        continue;
+                    }
+                    let Ok(relative) = entry.path().strip_prefix(&bucket_dir).map(Path::to_path_buf) else {
+                        continue;
+                    };
+                    let key = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                    if !key.starts_with(prefix) {
+                        continue;
+                    }
+                    let last_modified = metadata
+                        .modified()
+                        .ok()
+                        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).naive_utc());
+                    objects.push(ListedObject { key, size: metadata.len() as i64, last_modified });
+                }
+            }
+            objects.sort_by(|a, b| a.key.cmp(&b.key));
+            Ok(ListPage { objects, next: None })
+        })
+    }
+
+    fn create_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, _content_type: &'a str) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            self.path(bucket, key)?;
+            let upload_id = uuid::Uuid::new_v4().to_string();
+            tokio::fs::create_dir_all(self.upload_dir(&upload_id)?).await.map_err(io_error)?;
+            Ok(upload_id)
+        })
+    }
+
+    fn upload_part<'a>(&'a self, _bucket: &'a str, _key: &'a str, upload_id: &'a str, part_number: i32, body: Bytes) -> BoxFuture<'a, StorageResult<String>> {
+        Box::pin(async move {
+            use md5::{Digest, Md5};
+
+            let dir = self.upload_dir(upload_id)?;
+            if !tokio::fs::try_exists(&dir).await.map_err(io_error)? {
+                return Err(StorageError::NotFound(format!("Upload {}", upload_id)));
+            }
+            let etag = hex::encode(Md5::digest(&body));
+            self.write(&dir.join(format!("{:05}", part_number)), &body).await?;
+            Ok(etag)
+        })
+    }
+
+    fn complete_multipart<'a>(&'a self, bucket: &'a str, key: &'a str, upload_id: &'a str, parts: &'a [(i32, String)]) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            let dir = self.upload_dir(upload_id)?;
+            let path = self.path(bucket, key)?;
+            for (part_number, _) in parts {
+                if !tokio::fs::try_exists(dir.join(format!("{:05}", part_number))).await.map_err(io_error)? {
+                    return Err(StorageError::NotFound(format!("Part {} of upload {}", part_number, upload_id)));
+                }
+            }
+
+            let temp = self.temp_path(&path).await?;
+            let written = async {
+                let mut output = tokio::fs::File::create(&temp).await?;
+                for (part_number, _) in parts {
+                    let mut part = tokio::fs::File::open(dir.join(format!("{:05}", part_number))).await?;
+                    tokio::io::copy(&mut part, &mut output).await?;
+                }
+                output.sync_all().await
+            }
+            .await;
+            self.commit(&temp, &path, written).await?;
+            if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                warn!("Failed to remove parts of upload {}: {}", upload_id, e);
+            }
+            Ok(())
+        })
+    }
+
+    fn abort_multipart<'a>(&'a self, _bucket: &'a str, _key: &'a str, upload_id: &'a str) -> BoxFuture<'a, StorageResult<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_dir_all(self.upload_dir(upload_id)?).await {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(io_error(e)),
+            }
+        })
+    }
+}
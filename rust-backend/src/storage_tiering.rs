@@ -0,0 +1,225 @@
+use std::env;
+use std::time::Duration;
+use log::{error, info, warn};
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::models::{Video, VideoStorage};
+use crate::storage::{ObjectStore, Storage};
+
+// Original files of videos nobody has watched for a while are moved to a cheaper S3 storage
+// class by copying each object onto itself. Files in an archive class have to be restored before
+// they can be read again: streaming one starts the restore and answers 503 until it is done, and
+// the tiering job moves restored files back to STANDARD. Renditions and HLS segments stay where
+// they are. Tiering is off unless STORAGE_TIERING_CLASS is set, e.g. to GLACIER; MinIO only
+// knows STANDARD, and local storage has no classes at all.
+
+pub const STANDARD: &str = "STANDARD";
+
+// Classes videos can be moved to. Objects above 5 GB can't be copied in one request and fail to
+// transition.
+pub const COLD_CLASSES: [&str; 5] = ["STANDARD_IA", "ONEZONE_IA", "GLACIER_IR", "GLACIER", "DEEP_ARCHIVE"];
+
+// How long clients are told to wait while a file is restored. Standard tier restores take hours.
+pub const RESTORE_RETRY_AFTER_SECS: u64 = 30 * 60;
+
+// Days a restored archive copy stays readable, should moving it back to STANDARD keep failing
+const RESTORE_DAYS: i32 = 7;
+
+// Videos transitioned per run, so one run doesn't copy the whole bucket
+const TRANSITION_BATCH: i64 = 100;
+
+type TieringResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    Readable,
+    Restoring,
+}
+
+// Classes whose objects can only be read after a restore
+pub fn needs_restore(storage_class: &str) -> bool {
+    matches!(storage_class, "GLACIER" | "DEEP_ARCHIVE")
+}
+
+// The class to move cold videos to, from STORAGE_TIERING_CLASS
+pub fn cold_class() -> Option<String> {
+    let class = env::var("STORAGE_TIERING_CLASS").ok()?.trim().to_ascii_uppercase();
+    if COLD_CLASSES.contains(&class.as_str()) {
+        Some(class)
+    } else {
+        if !class.is_empty() {
+            warn!("Ignoring unknown STORAGE_TIERING_CLASS {}", class);
+        }
+        None
+    }
+}
+
+// Days without views after which a video counts as cold, from STORAGE_TIERING_COLD_DAYS
+pub fn cold_after_days() -> i64 {
+    env::var("STORAGE_TIERING_COLD_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(90)
+}
+
+// Whether an x-amz-restore header says the restored copy is ready, e.g.
+// `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`
+pub fn restore_completed(header: Option<&str>) -> bool {
+    header.is_some_and(|h| h.contains("ongoing-request=\"false\""))
+}
+
+pub async fn storage(db_pool: &PgPool, video_id: i32) -> Result<Option<VideoStorage>, sqlx::Error> {
+    sqlx::query_as::<_, VideoStorage>("SELECT * FROM video_storage WHERE video_id = $1")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+pub async fn list_storage(db_pool: &PgPool) -> Result<Vec<VideoStorage>, sqlx::Error> {
+    sqlx::query_as::<_, VideoStorage>("SELECT * FROM video_storage ORDER BY transitioned_at DESC, video_id DESC")
+        .fetch_all(db_pool)
+        .await
+}
+
+// Videos outside the trash, still in STANDARD, uploaded and last viewed over `days` days ago
+pub async fn cold_videos(db_pool: &PgPool, days: i64, limit: i64) -> Result<Vec<Video>, sqlx::Error> {
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::days(days);
+    sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos v
+         WHERE v.deleted_at IS NULL
+           AND v.upload_date < $1
+           AND NOT EXISTS (SELECT 1 FROM video_storage s WHERE s.video_id = v.id)
+           AND NOT EXISTS (SELECT 1 FROM video_views vv WHERE vv.video_id = v.id AND vv.viewed_at >= $1)
+         ORDER BY v.id
+         LIMIT $2"
+    )
+    .bind(cutoff)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Move a video's file to another class now. Moving to STANDARD only works once the file is
+// readable; use request_restore for that.
+pub async fn transition(db_pool: &PgPool, store: &dyn ObjectStore, bucket: &str, video: &Video, storage_class: &str) -> TieringResult<()> {
+    store.set_storage_class(bucket, &video.s3_key, storage_class).await?;
+    if storage_class == STANDARD {
+        sqlx::query("DELETE FROM video_storage WHERE video_id = $1")
+            .bind(video.id)
+            .execute(db_pool)
+            .await?;
+    } else {
+        sqlx::query(
+            "INSERT INTO video_storage (video_id, storage_class, restore_requested_at, transitioned_at) VALUES ($1, $2, NULL, NOW())
+             ON CONFLICT (video_id) DO UPDATE SET storage_class = EXCLUDED.storage_class, restore_requested_at = NULL, transitioned_at = EXCLUDED.transitioned_at"
+        )
+        .bind(video.id)
+        .bind(storage_class)
+        .execute(db_pool)
+        .await?;
+    }
+    info!("Moved video ID {} to {}", video.id, storage_class);
+    Ok(())
+}
+
+// Mark the video to be moved back to STANDARD, starting an S3 restore first if its class needs
+// one, and return whether the file can be read meanwhile. Streams call this, so videos people
+// watch again end up back in STANDARD. Calling it again while a restore is underway does nothing.
+pub async fn request_restore(db_pool: &PgPool, store: &dyn ObjectStore, bucket: &str, video: &Video) -> TieringResult<Availability> {
+    let Some(storage) = storage(db_pool, video.id).await? else {
+        return Ok(Availability::Readable);
+    };
+    let availability = if needs_restore(&storage.storage_class) { Availability::Restoring } else { Availability::Readable };
+    if storage.restore_requested_at.is_some() {
+        return Ok(availability);
+    }
+
+    if needs_restore(&storage.storage_class) {
+        store.restore(bucket, &video.s3_key, RESTORE_DAYS).await?;
+    }
+    sqlx::query("UPDATE video_storage SET restore_requested_at = NOW() WHERE video_id = $1 AND restore_requested_at IS NULL")
+        .bind(video.id)
+        .execute(db_pool)
+        .await?;
+    info!("Requested restore of video ID {} from {}", video.id, storage.storage_class);
+    Ok(availability)
+}
+
+// Move files whose restore was asked for back to STANDARD, once archived ones have been restored.
+// Returns how many were moved.
+pub async fn complete_restores(db_pool: &PgPool, store: &dyn ObjectStore, bucket: &str) -> TieringResult<usize> {
+    let pending: Vec<(i32, String, String)> = sqlx::query_as(
+        "SELECT s.video_id, s.storage_class, v.s3_key FROM video_storage s JOIN videos v ON v.id = s.video_id
+         WHERE s.restore_requested_at IS NOT NULL ORDER BY s.restore_requested_at"
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut restored = 0;
+    for (video_id, storage_class, s3_key) in pending {
+        if needs_restore(&storage_class) {
+            match store.head(bucket, &s3_key).await {
+                Ok(head) if restore_completed(head.restore.as_deref()) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    error!("Failed to check the restore of video ID {}: {:?}", video_id, e);
+                    continue;
+                }
+            }
+        }
+        if let Err(e) = store.set_storage_class(bucket, &s3_key, STANDARD).await {
+            error!("Failed to move video ID {} back to STANDARD: {:?}", video_id, e);
+            continue;
+        }
+        sqlx::query("DELETE FROM video_storage WHERE video_id = $1")
+            .bind(video_id)
+            .execute(db_pool)
+            .await?;
+        info!("Restored video ID {} from {} to STANDARD", video_id, storage_class);
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+// Finish restores, then move cold videos to STORAGE_TIERING_CLASS if it is set. Returns how many
+// videos were restored and transitioned.
+pub async fn run_once(db_pool: &PgPool, store: &dyn ObjectStore, bucket: &str) -> TieringResult<(usize, usize)> {
+    let restored = complete_restores(db_pool, store, bucket).await?;
+    let Some(storage_class) = cold_class() else {
+        return Ok((restored, 0));
+    };
+
+    let mut transitioned = 0;
+    for video in cold_videos(db_pool, cold_after_days(), TRANSITION_BATCH).await? {
+        match transition(db_pool, store, bucket, &video, &storage_class).await {
+            Ok(()) => transitioned += 1,
+            Err(e) => error!("Failed to move video ID {} to {}: {:?}", video.id, storage_class, e),
+        }
+    }
+    Ok((restored, transitioned))
+}
+
+// Periodically run the tiering policy. Interval is configurable via STORAGE_TIERING_INTERVAL_SECS.
+pub async fn run_storage_tiering(db_pool: PgPool, store: Storage) {
+    let interval_secs = env::var("STORAGE_TIERING_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(3600);
+    let bucket = env::var("S3_BUCKET")
+        .or_else(|_| env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    info!("Starting storage tiering (interval: {} seconds, cold class: {:?})", interval_secs, cold_class());
+
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        match run_once(&db_pool, store.as_ref(), &bucket).await {
+            Ok((0, 0)) => {}
+            Ok((restored, transitioned)) => info!("Storage tiering restored {} and moved {} videos", restored, transitioned),
+            Err(e) => error!("Error running storage tiering: {:?}", e),
+        }
+    }
+}
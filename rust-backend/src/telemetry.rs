@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use log::{error, info, warn};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::models::{PlaybackBeacon, RenditionPlaybackStats};
+
+// Playback quality beacons from clients: startup time, rebuffering and the rendition chosen by
+// their adaptive bitrate logic. Aggregated per rendition they show operators where the encoding
+// ladder leaves viewers stalling or stuck on low rungs. Beacons are kept for
+// TELEMETRY_RETENTION_DAYS.
+//
+// The endpoint is open to anyone, so each client (its user, or its IP when signed out) may send
+// TELEMETRY_BEACONS_PER_MINUTE beacons a minute, counted in Redis or, without it, per replica.
+
+pub const MAX_BEACONS_PER_REQUEST: usize = 50;
+const MAX_RENDITION_CHARS: usize = 32;
+const MAX_SESSION_ID_CHARS: usize = 64;
+// Anything longer is a stuck player rather than a slow start
+const MAX_STARTUP_MS: i32 = 10 * 60 * 1000;
+const MAX_WATCHED_SECS: f64 = 24.0 * 60.0 * 60.0;
+
+const DEFAULT_RETENTION_DAYS: i64 = 90;
+pub const DEFAULT_REPORT_DAYS: i64 = 7;
+const CLEANUP_INTERVAL_SECS: u64 = 60 * 60;
+
+// Players report every few seconds at most, so this only stops floods
+const DEFAULT_BEACONS_PER_MINUTE: u64 = 120;
+
+pub fn beacons_per_minute() -> u64 {
+    env::var("TELEMETRY_BEACONS_PER_MINUTE").ok().and_then(|v| v.parse::<u64>().ok()).filter(|v| *v > 0).unwrap_or(DEFAULT_BEACONS_PER_MINUTE)
+}
+
+fn beacons_key(client: &str, minute: i64) -> String {
+    format!("telemetry_beacons:{}:{}", client, minute)
+}
+
+// Stand-in for Redis: each client's count for the current minute
+static LOCAL_COUNTS: LazyLock<Mutex<HashMap<String, (i64, u64)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn count_locally(client: &str, minute: i64, beacons: u64) -> u64 {
+    let mut counts = LOCAL_COUNTS.lock().unwrap();
+    counts.retain(|_, (counted_minute, _)| *counted_minute == minute);
+    let (_, count) = counts.entry(client.to_string()).or_insert((minute, 0));
+    *count += beacons;
+    *count
+}
+
+async fn count_in_redis(redis_client: &redis::Client, client: &str, minute: i64, beacons: u64) -> redis::RedisResult<u64> {
+    let (count,): (u64,) = circuit_breaker::redis(redis_client, |mut conn| async move {
+        redis::pipe()
+            .atomic()
+            .incr(beacons_key(client, minute), beacons)
+            .expire(beacons_key(client, minute), 60)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+    })
+    .await?;
+    Ok(count)
+}
+
+// Count the beacons against the client's minute, refusing them once it is over the limit
+pub async fn check_rate(redis_client: Option<&redis::Client>, client: &str, beacons: usize, now: i64) -> Result<(), ApiError> {
+    let minute = now / 60;
+    let count = match redis_client {
+        Some(redis_client) => match count_in_redis(redis_client, client, minute, beacons as u64).await {
+            Ok(count) => count,
+            Err(e) => {
+                error!("Telemetry rate limit unavailable in Redis, counting locally: {:?}", e);
+                count_locally(client, minute, beacons as u64)
+            }
+        },
+        None => count_locally(client, minute, beacons as u64),
+    };
+    if count > beacons_per_minute() {
+        warn!("{} is sending more than {} playback beacons a minute", client, beacons_per_minute());
+        return Err(ApiError::TooManyRequests {
+            message: "Too many playback beacons, try again in a minute".to_string(),
+            retry_after_secs: (60 - now.rem_euclid(60)).max(1) as u64,
+        });
+    }
+    Ok(())
+}
+
+pub fn retention_days() -> i64 {
+    env::var("TELEMETRY_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|d| *d > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Beacons {
+    One(PlaybackBeacon),
+    Many(Vec<PlaybackBeacon>),
+}
+
+fn validate(beacon: &PlaybackBeacon) -> Result<(), String> {
+    let counts = [
+        ("startupMs", beacon.startup_ms),
+        ("rebufferCount", beacon.rebuffer_count),
+        ("rebufferMs", beacon.rebuffer_ms),
+        ("bitrateKbps", beacon.bitrate_kbps),
+        ("droppedFrames", beacon.dropped_frames),
+    ];
+    if let Some((name, _)) = counts.iter().find(|(_, value)| value.is_some_and(|v| v < 0)) {
+        return Err(format!("{} can't be negative", name));
+    }
+    if beacon.startup_ms.is_some_and(|ms| ms > MAX_STARTUP_MS) {
+        return Err(format!("startupMs must be at most {}", MAX_STARTUP_MS));
+    }
+    if beacon.watched_secs.is_some_and(|secs| !secs.is_finite() || !(0.0..=MAX_WATCHED_SECS).contains(&secs)) {
+        return Err(format!("watchedSecs must be between 0 and {}", MAX_WATCHED_SECS));
+    }
+    if beacon.rendition.as_deref().is_some_and(|r| r.trim().is_empty() || r.chars().count() > MAX_RENDITION_CHARS) {
+        return Err(format!("rendition must be 1 to {} characters", MAX_RENDITION_CHARS));
+    }
+    if beacon.session_id.as_deref().is_some_and(|s| s.is_empty() || s.chars().count() > MAX_SESSION_ID_CHARS) {
+        return Err(format!("sessionId must be 1 to {} characters", MAX_SESSION_ID_CHARS));
+    }
+    Ok(())
+}
+
+// One beacon or an array of them. The body is parsed here rather than by the JSON extractor as
+// navigator.sendBeacon posts it as text/plain.
+pub fn parse_beacons(body: &[u8]) -> Result<Vec<PlaybackBeacon>, String> {
+    let beacons = match serde_json::from_slice::<Beacons>(body) {
+        Ok(Beacons::One(beacon)) => vec![beacon],
+        Ok(Beacons::Many(beacons)) => beacons,
+        Err(_) => return Err("Expected a playback beacon or an array of them".to_string()),
+    };
+    if beacons.is_empty() || beacons.len() > MAX_BEACONS_PER_REQUEST {
+        return Err(format!("Send between 1 and {} beacons at a time", MAX_BEACONS_PER_REQUEST));
+    }
+    beacons.iter().try_for_each(validate)?;
+    Ok(beacons)
+}
+
+// Store the beacons, skipping any for videos that don't exist, and return how many were kept.
+// Callers drop beacons for videos the client couldn't play first.
+pub async fn record(db_pool: &PgPool, beacons: &[PlaybackBeacon], user_id: Option<i32>, country: Option<&str>) -> Result<u64, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let mut recorded = 0;
+    for beacon in beacons {
+        let result = sqlx::query(
+            "INSERT INTO playback_events (video_id, user_id, session_id, rendition, startup_ms, rebuffer_count, rebuffer_ms,
+                                          bitrate_kbps, watched_secs, dropped_frames, country)
+             SELECT id, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11 FROM videos WHERE id = $1 AND deleted_at IS NULL"
+        )
+        .bind(beacon.video_id)
+        .bind(user_id)
+        .bind(&beacon.session_id)
+        .bind(beacon.rendition.as_deref().map(str::trim))
+        .bind(beacon.startup_ms)
+        .bind(beacon.rebuffer_count)
+        .bind(beacon.rebuffer_ms)
+        .bind(beacon.bitrate_kbps)
+        .bind(beacon.watched_secs)
+        .bind(beacon.dropped_frames)
+        .bind(country)
+        .execute(&mut tx)
+        .await?;
+        recorded += result.rows_affected();
+    }
+    tx.commit().await?;
+    Ok(recorded)
+}
+
+// Per-rendition playback quality since `days` ago, for one video or all of them, busiest first.
+// Beacons that didn't say which rendition was playing are grouped as "unknown".
+pub async fn rendition_report(db_pool: &PgPool, video_id: Option<i32>, days: i64) -> Result<Vec<RenditionPlaybackStats>, sqlx::Error> {
+    sqlx::query_as::<_, RenditionPlaybackStats>(
+        "SELECT COALESCE(rendition, 'unknown') AS rendition,
+                COUNT(*) AS beacons,
+                COUNT(DISTINCT session_id) AS sessions,
+                AVG(startup_ms)::FLOAT8 AS avg_startup_ms,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY startup_ms) AS p50_startup_ms,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY startup_ms) AS p95_startup_ms,
+                AVG(rebuffer_count)::FLOAT8 AS avg_rebuffer_count,
+                SUM(rebuffer_ms) / 1000.0 / NULLIF(SUM(watched_secs), 0) AS rebuffer_ratio,
+                AVG(bitrate_kbps)::FLOAT8 AS avg_bitrate_kbps,
+                percentile_cont(0.1) WITHIN GROUP (ORDER BY bitrate_kbps) AS p10_bitrate_kbps,
+                COALESCE(SUM(watched_secs), 0) / 3600.0 AS watched_hours
+         FROM playback_events
+         WHERE created_at >= NOW() - make_interval(days => $1::INT) AND ($2::INT IS NULL OR video_id = $2)
+         GROUP BY 1
+         ORDER BY beacons DESC, rendition ASC"
+    )
+    .bind(days as i32)
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+async fn purge_expired(db_pool: &PgPool, days: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM playback_events WHERE created_at < NOW() - make_interval(days => $1::INT)")
+        .bind(days as i32)
+        .execute(db_pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn run_telemetry_cleanup(db_pool: PgPool) {
+    info!("Starting playback telemetry cleanup (retention: {} days)", retention_days());
+    loop {
+        match purge_expired(&db_pool, retention_days()).await {
+            Ok(0) => {}
+            Ok(purged) => info!("Purged {} expired playback beacons", purged),
+            Err(e) => error!("Error purging playback beacons: {:?}", e),
+        }
+        sleep(Duration::from_secs(CLEANUP_INTERVAL_SECS)).await;
+    }
+}
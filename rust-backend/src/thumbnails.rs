@@ -0,0 +1,170 @@
+use sqlx::PgPool;
+
+use crate::models::ThumbnailCandidate;
+
+// Candidate frames sampled per video when THUMBNAIL_CANDIDATES is not set
+pub const DEFAULT_CANDIDATE_COUNT: usize = 6;
+
+// Frames are scored at this size; enough detail for focus and edges, cheap to decode
+pub const SCORE_WIDTH: usize = 160;
+pub const SCORE_HEIGHT: usize = 90;
+
+// Every thumbnail the job queue writes lives under this prefix
+pub const THUMBNAIL_PREFIX: &str = "thumbnails/";
+
+// Generated names are a UUID and an extension; anything much longer is not one of ours
+pub const MAX_THUMBNAIL_NAME_LEN: usize = 128;
+
+// Gradient strength above which a pixel counts as an edge
+const EDGE_THRESHOLD: i32 = 40;
+
+// Edge density at which a frame gets full marks; busier frames aren't rewarded further
+const TARGET_EDGE_DENSITY: f64 = 0.2;
+
+pub fn candidate_count() -> usize {
+    std::env::var("THUMBNAIL_CANDIDATES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(DEFAULT_CANDIDATE_COUNT)
+}
+
+// Evenly spaced offsets between 5% and 95% of the video, avoiding intros, fades and credits.
+// Videos of unknown or very short duration get a single frame near the start.
+pub fn candidate_offsets(duration: Option<f64>, count: usize) -> Vec<f64> {
+    match duration {
+        Some(duration) if duration >= 2.0 && count > 1 => {
+            let start = duration * 0.05;
+            let step = duration * 0.9 / (count - 1) as f64;
+            (0..count).map(|i| start + step * i as f64).collect()
+        }
+        Some(duration) if duration >= 2.0 => vec![duration / 2.0],
+        _ => vec![0.0],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameScore {
+    pub brightness: f64,   // 1.0 at mid-grey, 0.0 at black or white
+    pub contrast: f64,     // grey level spread, 0.0 for a flat frame
+    pub sharpness: f64,    // variance of the Laplacian, squashed into 0..1
+    pub edge_density: f64, // share of edge pixels relative to TARGET_EDGE_DENSITY, capped at 1.0
+    pub score: f64,
+}
+
+// Score an 8-bit grayscale frame for use as a thumbnail. Dark, washed out, blurry and
+// featureless frames (fades, title cards, motion blur) score low. There is no face detector
+// here, so edge density stands in for "something is in frame".
+pub fn score_frame(gray: &[u8], width: usize, height: usize) -> FrameScore {
+    let pixels = gray.len().min(width * height);
+    if pixels == 0 || width < 3 || height < 3 {
+        return FrameScore { brightness: 0.0, contrast: 0.0, sharpness: 0.0, edge_density: 0.0, score: 0.0 };
+    }
+    let gray = &gray[..pixels];
+    let height = pixels / width;
+
+    let mean = gray.iter().map(|p| *p as f64).sum::<f64>() / pixels as f64;
+    let variance = gray.iter().map(|p| (*p as f64 - mean).powi(2)).sum::<f64>() / pixels as f64;
+
+    let at = |x: usize, y: usize| gray[y * width + x] as i32;
+    let mut laplacians = Vec::with_capacity((width - 2) * (height - 2));
+    let mut edges = 0usize;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let center = at(x, y);
+            laplacians.push((at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4 * center) as f64);
+            let gradient = (at(x + 1, y) - at(x - 1, y)).abs() + (at(x, y + 1) - at(x, y - 1)).abs();
+            if gradient > EDGE_THRESHOLD {
+                edges += 1;
+            }
+        }
+    }
+    let lap_mean = laplacians.iter().sum::<f64>() / laplacians.len() as f64;
+    let lap_variance = laplacians.iter().map(|l| (l - lap_mean).powi(2)).sum::<f64>() / laplacians.len() as f64;
+
+    let mut brightness = 1.0 - (mean - 128.0).abs() / 128.0;
+    // Near-black and near-white frames are almost never what anyone wants
+    if !(20.0..=235.0).contains(&mean) {
+        brightness *= 0.1;
+    }
+    let contrast = (variance.sqrt() / 64.0).min(1.0);
+    let sharpness = lap_variance / (lap_variance + 500.0);
+    let edge_density = (edges as f64 / laplacians.len() as f64 / TARGET_EDGE_DENSITY).min(1.0);
+
+    let score = 0.4 * sharpness + 0.3 * brightness + 0.2 * edge_density + 0.1 * contrast;
+    FrameScore { brightness, contrast, sharpness, edge_density, score }
+}
+
+// The S3 key for a thumbnail name from a URL, with or without the thumbnails/ prefix. None unless
+// it is a single path segment of letters, digits, '-', '_' and '.', so it can't leave the prefix.
+pub fn thumbnail_s3_key(requested: &str) -> Option<String> {
+    let name = requested.strip_prefix(THUMBNAIL_PREFIX).unwrap_or(requested);
+    let valid = !name.is_empty()
+        && name.len() <= MAX_THUMBNAIL_NAME_LEN
+        && !name.starts_with('.')
+        && !name.contains("..")
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    valid.then(|| format!("{}{}", THUMBNAIL_PREFIX, name))
+}
+
+// Whether some video uses the key as its thumbnail or one of its candidates, so only objects
+// the database hands out can be read through the thumbnail endpoint
+pub async fn is_known_thumbnail(db_pool: &PgPool, s3_key: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM videos WHERE thumbnail_url = $1)
+             OR EXISTS (SELECT 1 FROM thumbnail_candidates WHERE s3_key = $1)"
+    )
+    .bind(s3_key)
+    .fetch_one(db_pool)
+    .await
+}
+
+pub async fn list_candidates(db_pool: &PgPool, video_id: i32) -> Result<Vec<ThumbnailCandidate>, sqlx::Error> {
+    sqlx::query_as::<_, ThumbnailCandidate>(
+        "SELECT c.id, c.video_id, c.s3_key, c.time_offset, c.score, c.s3_key = v.thumbnail_url AS selected, c.created_at
+         FROM thumbnail_candidates c JOIN videos v ON v.id = c.video_id
+         WHERE c.video_id = $1 ORDER BY c.score DESC, c.id ASC"
+    )
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Replace a video's candidates and make `selected_key` its thumbnail. Returns the S3 keys of
+// the previous candidates so the caller can delete them.
+pub async fn replace_candidates(
+    db_pool: &PgPool,
+    video_id: i32,
+    candidates: &[(String, f64, f64)], // (s3_key, time_offset, score)
+    selected_key: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let old_keys: Vec<String> = sqlx::query_scalar("DELETE FROM thumbnail_candidates WHERE video_id = $1 RETURNING s3_key")
+        .bind(video_id)
+        .fetch_all(&mut tx)
+        .await?;
+
+    let now = chrono::Utc::now().naive_utc();
+    for (s3_key, time_offset, score) in candidates {
+        sqlx::query(
+            "INSERT INTO thumbnail_candidates (video_id, s3_key, time_offset, score, created_at) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(video_id)
+        .bind(s3_key)
+        .bind(time_offset)
+        .bind(score)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    sqlx::query("UPDATE videos SET thumbnail_url = $1 WHERE id = $2")
+        .bind(selected_key)
+        .bind(video_id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(old_keys)
+}
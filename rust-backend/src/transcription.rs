@@ -0,0 +1,294 @@
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+use log::info;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::process::Command;
+
+use crate::media;
+use crate::models::{TranscriptSegment, VideoTranscript};
+
+type TranscriptionResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+const DEFAULT_WHISPER_CPP_BIN: &str = "whisper-cli";
+const DEFAULT_API_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+const DEFAULT_API_MODEL: &str = "whisper-1";
+
+// Long videos can take a while to transcribe remotely
+const API_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+// Audio is sent to the API in pieces this long. 16 kHz mono WAV is about 1.9 MB a minute, so ten
+// minutes stays under the 25 MB that OpenAI's endpoint accepts.
+pub const API_CHUNK_SECS: f64 = 10.0 * 60.0;
+
+// Where speech-to-text runs, chosen with TRANSCRIPTION_BACKEND. Transcription is off when unset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptionBackend {
+    // A local whisper.cpp build: WHISPER_CPP_BIN (default whisper-cli) with the WHISPER_MODEL ggml file
+    WhisperCpp { binary: String, model: String },
+    // An OpenAI-compatible /audio/transcriptions endpoint
+    Api { url: String, api_key: Option<String>, model: String },
+}
+
+impl TranscriptionBackend {
+    pub fn from_env() -> Option<Self> {
+        match env::var("TRANSCRIPTION_BACKEND").as_deref() {
+            Ok("whisper_cpp") => Some(TranscriptionBackend::WhisperCpp {
+                binary: env::var("WHISPER_CPP_BIN").unwrap_or_else(|_| DEFAULT_WHISPER_CPP_BIN.to_string()),
+                model: env::var("WHISPER_MODEL").ok()?,
+            }),
+            Ok("api") => Some(TranscriptionBackend::Api {
+                url: env::var("TRANSCRIPTION_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+                api_key: env::var("TRANSCRIPTION_API_KEY").ok(),
+                model: env::var("TRANSCRIPTION_API_MODEL").unwrap_or_else(|_| DEFAULT_API_MODEL.to_string()),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TranscriptionBackend::WhisperCpp { .. } => "whisper_cpp",
+            TranscriptionBackend::Api { .. } => "api",
+        }
+    }
+
+    // Transcribe a 16 kHz mono WAV (see media::extract_audio), returning the detected language
+    // and timed segments. TRANSCRIPTION_LANGUAGE pins the language instead of detecting it.
+    pub async fn transcribe(&self, audio: &Path) -> TranscriptionResult<(Option<String>, Vec<TranscriptSegment>)> {
+        let language = env::var("TRANSCRIPTION_LANGUAGE").ok();
+        match self {
+            TranscriptionBackend::WhisperCpp { binary, model } => {
+                transcribe_whisper_cpp(binary, model, language.as_deref(), audio).await
+            }
+            TranscriptionBackend::Api { url, api_key, model } => {
+                transcribe_api(url, api_key.as_deref(), model, language.as_deref(), audio).await
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WhisperCppOutput {
+    result: Option<WhisperCppResult>,
+    transcription: Vec<WhisperCppSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppResult {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppSegment {
+    offsets: WhisperCppOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppOffsets {
+    from: i64, // milliseconds
+    to: i64,
+}
+
+async fn transcribe_whisper_cpp(
+    binary: &str,
+    model: &str,
+    language: Option<&str>,
+    audio: &Path,
+) -> TranscriptionResult<(Option<String>, Vec<TranscriptSegment>)> {
+    // -oj writes <prefix>.json next to the audio, inside the job's work directory
+    let prefix = audio.with_extension("");
+    let mut cmd = Command::new(binary);
+    cmd.args(["-m", model, "-l", language.unwrap_or("auto"), "-oj", "-np", "-f"])
+        .arg(audio)
+        .arg("-of")
+        .arg(&prefix);
+    media::run(cmd, binary).await?;
+
+    let json = tokio::fs::read(prefix.with_extension("json")).await?;
+    let output: WhisperCppOutput = serde_json::from_slice(&json)?;
+    let segments = output.transcription
+        .into_iter()
+        .map(|s| TranscriptSegment {
+            start: s.offsets.from as f64 / 1000.0,
+            end: s.offsets.to as f64 / 1000.0,
+            text: s.text,
+        })
+        .collect();
+    Ok((output.result.and_then(|r| r.language), clean_segments(segments)))
+}
+
+#[derive(Deserialize)]
+struct ApiOutput {
+    language: Option<String>,
+    #[serde(default)]
+    segments: Vec<TranscriptSegment>,
+    text: Option<String>,
+}
+
+// Where each piece of audio that long starts
+pub fn chunk_starts(duration: f64, chunk_secs: f64) -> Vec<f64> {
+    let chunks = (duration / chunk_secs).ceil().max(1.0) as usize;
+    (0..chunks).map(|i| i as f64 * chunk_secs).collect()
+}
+
+// Transcribe the audio in API_CHUNK_SECS pieces, shifting each piece's segments to where it
+// starts. The language is the first one detected.
+async fn transcribe_api(
+    url: &str,
+    api_key: Option<&str>,
+    model: &str,
+    language: Option<&str>,
+    audio: &Path,
+) -> TranscriptionResult<(Option<String>, Vec<TranscriptSegment>)> {
+    let duration = media::probe_duration(audio).await?.unwrap_or(0.0);
+    let starts = chunk_starts(duration, API_CHUNK_SECS);
+    if starts.len() == 1 {
+        return transcribe_api_file(url, api_key, model, language, audio).await;
+    }
+
+    let chunk = audio.with_file_name("chunk.wav");
+    let mut detected = None;
+    let mut segments = Vec::new();
+    for start in starts {
+        media::cut_audio(audio, &chunk, start, API_CHUNK_SECS).await?;
+        let (chunk_language, chunk_segments) = transcribe_api_file(url, api_key, model, language, &chunk).await?;
+        detected = detected.or(chunk_language);
+        segments.extend(chunk_segments.into_iter().map(|s| TranscriptSegment { start: s.start + start, end: s.end + start, ..s }));
+    }
+    Ok((detected, segments))
+}
+
+async fn transcribe_api_file(
+    url: &str,
+    api_key: Option<&str>,
+    model: &str,
+    language: Option<&str>,
+    audio: &Path,
+) -> TranscriptionResult<(Option<String>, Vec<TranscriptSegment>)> {
+    let audio_bytes = tokio::fs::read(audio).await?;
+    let mut fields = vec![("model", model), ("response_format", "verbose_json")];
+    if let Some(language) = language {
+        fields.push(("language", language));
+    }
+    let boundary = format!("----transcription-{}", uuid::Uuid::new_v4().simple());
+    let body = multipart_body(&boundary, &fields, "audio.wav", "audio/wav", &audio_bytes);
+
+    let client = reqwest::Client::builder().timeout(API_TIMEOUT).build()?;
+    let mut request = client
+        .post(url)
+        .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+        .body(body);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(Box::new(std::io::Error::other(format!("transcription API returned {}: {}", status, text.trim()))));
+    }
+
+    let output: ApiOutput = response.json().await?;
+    let mut segments = output.segments;
+    // Some services only return plain text; keep it as a single untimed segment
+    if segments.is_empty() {
+        if let Some(text) = output.text {
+            segments.push(TranscriptSegment { start: 0.0, end: 0.0, text });
+        }
+    }
+    Ok((output.language, clean_segments(segments)))
+}
+
+// multipart/form-data with text fields followed by a single file part
+fn multipart_body(boundary: &str, fields: &[(&str, &str)], file_name: &str, content_type: &str, file: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(file.len() + 1024);
+    for (name, value) in fields {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value
+        ).as_bytes());
+    }
+    body.extend_from_slice(format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+        boundary, file_name, content_type
+    ).as_bytes());
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+// Trim whitespace and drop empty segments (whisper emits these for silence)
+fn clean_segments(segments: Vec<TranscriptSegment>) -> Vec<TranscriptSegment> {
+    segments
+        .into_iter()
+        .map(|s| TranscriptSegment { text: s.text.trim().to_string(), ..s })
+        .filter(|s| !s.text.is_empty())
+        .collect()
+}
+
+// HH:MM:SS.mmm as WebVTT cue timings require
+pub fn format_vtt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        (millis / 60_000) % 60,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+pub fn to_vtt(segments: &[TranscriptSegment]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for segment in segments {
+        // "-->" would end the cue timing line early, and blank lines would end the cue
+        let text = segment.text.replace("-->", "->").replace("\n\n", "\n");
+        vtt.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            format_vtt_timestamp(segment.start),
+            format_vtt_timestamp(segment.end.max(segment.start)),
+            text
+        ));
+    }
+    vtt
+}
+
+pub async fn store_transcript(
+    db_pool: &PgPool,
+    video_id: i32,
+    backend: &str,
+    language: Option<&str>,
+    segments: &[TranscriptSegment],
+) -> Result<(), sqlx::Error> {
+    let content = segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+    sqlx::query(
+        "INSERT INTO video_transcripts (video_id, backend, language, segments, vtt, content, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
+         ON CONFLICT (video_id) DO UPDATE SET backend = EXCLUDED.backend, language = EXCLUDED.language,
+             segments = EXCLUDED.segments, vtt = EXCLUDED.vtt, content = EXCLUDED.content, updated_at = EXCLUDED.updated_at"
+    )
+    .bind(video_id)
+    .bind(backend)
+    .bind(language)
+    .bind(sqlx::types::Json(segments))
+    .bind(to_vtt(segments))
+    .bind(&content)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(db_pool)
+    .await?;
+
+    info!("Stored {} segment transcript for video ID {}", segments.len(), video_id);
+    Ok(())
+}
+
+pub async fn get_transcript(db_pool: &PgPool, video_id: i32) -> Result<Option<VideoTranscript>, sqlx::Error> {
+    sqlx::query_as::<_, VideoTranscript>(
+        "SELECT video_id, backend, language, segments, vtt, content, created_at, updated_at FROM video_transcripts WHERE video_id = $1"
+    )
+    .bind(video_id)
+    .fetch_optional(db_pool)
+    .await
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use actix_web::HttpRequest;
+use sqlx::PgPool;
+
+use crate::models::{Video, VideoTranslation};
+
+// Accept-Language ranges considered per request; browsers send a handful at most
+const MAX_PREFERENCES: usize = 8;
+
+// Lower-cased language tag, or None if it isn't shaped like one (e.g. "en", "pt-BR", "zh-Hant-TW")
+pub fn normalize_language(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_ascii_lowercase();
+    let mut subtags = tag.split('-');
+    let primary = subtags.next()?;
+    let valid = (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some(tag)
+}
+
+// Language ranges from an Accept-Language header, most preferred first. Ranges with q=0 and
+// the "*" wildcard are dropped; ties keep header order.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut ranges: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let language = normalize_language(pieces.next()?)?;
+            let quality = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((language, quality))
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut preferred: Vec<String> = Vec::new();
+    for (language, _) in ranges {
+        if !preferred.contains(&language) {
+            preferred.push(language);
+        }
+    }
+    preferred.truncate(MAX_PREFERENCES);
+    preferred
+}
+
+pub fn preferred_languages(http_req: &HttpRequest) -> Vec<String> {
+    http_req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(parse_accept_language)
+        .unwrap_or_default()
+}
+
+// The best available translation for the preferred ranges, RFC 4647 lookup style: for each range
+// in order try the exact tag, then the range with subtags dropped ("pt-br" -> "pt"), then any
+// more specific tag under it ("pt" -> "pt-br")
+pub fn pick<'a>(preferred: &[String], available: &[&'a str]) -> Option<&'a str> {
+    for range in preferred {
+        let mut candidate = range.as_str();
+        loop {
+            if let Some(found) = available.iter().find(|a| **a == candidate) {
+                return Some(found);
+            }
+            match candidate.rfind('-') {
+                Some(i) => candidate = &candidate[..i],
+                None => break,
+            }
+        }
+        let prefix = format!("{}-", range);
+        if let Some(found) = available.iter().find(|a| a.starts_with(&prefix)) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+// Swap in the best translated title and description for each video. Returns the language
+// chosen for each, None where the original was kept.
+pub async fn localize(db_pool: &PgPool, videos: &mut [Video], preferred: &[String]) -> Result<Vec<Option<String>>, sqlx::Error> {
+    if preferred.is_empty() || videos.is_empty() {
+        return Ok(vec![None; videos.len()]);
+    }
+
+    let ids: Vec<i32> = videos.iter().map(|v| v.id).collect();
+    let mut by_video: HashMap<i32, Vec<VideoTranslation>> = HashMap::new();
+    for translation in sqlx::query_as::<_, VideoTranslation>("SELECT * FROM video_translations WHERE video_id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(db_pool)
+        .await?
+    {
+        by_video.entry(translation.video_id).or_default().push(translation);
+    }
+
+    Ok(videos
+        .iter_mut()
+        .map(|video| {
+            let translations = by_video.remove(&video.id)?;
+            let available: Vec<&str> = translations.iter().map(|t| t.language.as_str()).collect();
+            let language = pick(preferred, &available)?.to_string();
+            let translation = translations.into_iter().find(|t| t.language == language)?;
+            video.title = translation.title;
+            video.description = translation.description.or(video.description.take());
+            Some(language)
+        })
+        .collect())
+}
+
+pub async fn list_translations(db_pool: &PgPool, video_id: i32) -> Result<Vec<VideoTranslation>, sqlx::Error> {
+    sqlx::query_as::<_, VideoTranslation>("SELECT * FROM video_translations WHERE video_id = $1 ORDER BY language ASC")
+        .bind(video_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+// Translations are part of the video's metadata, so changing one bumps videos.updated_at for
+// list ETags and the sitemap
+async fn touch_video(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, video_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE videos SET updated_at = NOW() WHERE id = $1")
+        .bind(video_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+pub async fn upsert_translation(
+    db_pool: &PgPool,
+    video_id: i32,
+    language: &str,
+    title: &str,
+    description: Option<&str>,
+) -> Result<VideoTranslation, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let translation = sqlx::query_as::<_, VideoTranslation>(
+        "INSERT INTO video_translations (video_id, language, title, description, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $5)
+         ON CONFLICT (video_id, language) DO UPDATE SET title = EXCLUDED.title, description = EXCLUDED.description, updated_at = EXCLUDED.updated_at
+         RETURNING *"
+    )
+    .bind(video_id)
+    .bind(language)
+    .bind(title)
+    .bind(description)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(&mut tx)
+    .await?;
+    touch_video(&mut tx, video_id).await?;
+    tx.commit().await?;
+    Ok(translation)
+}
+
+pub async fn delete_translation(db_pool: &PgPool, video_id: i32, language: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let result = sqlx::query("DELETE FROM video_translations WHERE video_id = $1 AND language = $2")
+        .bind(video_id)
+        .bind(language)
+        .execute(&mut tx)
+        .await?;
+    if result.rows_affected() > 0 {
+        touch_video(&mut tx, video_id).await?;
+    }
+    tx.commit().await?;
+    Ok(result.rows_affected() > 0)
+}
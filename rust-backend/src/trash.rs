@@ -0,0 +1,118 @@
+use sqlx::PgPool;
+use log::{info, error};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::frames;
+use crate::models::Video;
+use crate::storage::{ObjectStore, Storage};
+
+// How long a soft-deleted video stays in the trash before it is purged
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+// Permanently remove videos that have been in the trash longer than the retention period,
+// along with their stored objects. Returns the number of videos purged.
+pub async fn purge_expired_videos(db_pool: &PgPool, storage: &dyn ObjectStore) -> Result<usize, sqlx::Error> {
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::days(TRASH_RETENTION_DAYS);
+    let expired = sqlx::query_as::<_, Video>(
+        "SELECT * FROM videos WHERE deleted_at IS NOT NULL AND deleted_at < $1 ORDER BY deleted_at ASC"
+    )
+    .bind(cutoff)
+    .fetch_all(db_pool)
+    .await?;
+
+    let bucket = std::env::var("S3_BUCKET")
+        .or_else(|_| std::env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let mut purged = 0;
+    for video in expired {
+        // Thumbnails uploaded by the scraper are stored as S3 keys; external URLs are left alone
+        let mut keys = vec![video.s3_key.clone()];
+        if let Some(thumbnail) = video.thumbnail_url.as_ref().filter(|t| t.starts_with("thumbnails/")) {
+            keys.push(thumbnail.clone());
+        }
+        // Rendition, thumbnail candidate, HLS segment, audio track and clip rows go with the video via ON DELETE CASCADE,
+        // their objects don't
+        let rendition_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM video_renditions WHERE video_id = $1")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(rendition_keys);
+        let thumbnail_candidate_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM thumbnail_candidates WHERE video_id = $1")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(thumbnail_candidate_keys);
+        let hls_segment_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM video_hls_segments WHERE video_id = $1")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(hls_segment_keys);
+        let hls_audio_segment_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM video_hls_audio_segments WHERE video_id = $1")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(hls_audio_segment_keys);
+        let audio_track_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM video_audio_tracks WHERE video_id = $1 AND s3_key IS NOT NULL")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(audio_track_keys);
+        let clip_keys: Vec<String> = sqlx::query_scalar("SELECT s3_key FROM video_clips WHERE video_id = $1")
+            .bind(video.id)
+            .fetch_all(db_pool)
+            .await?;
+        keys.extend(clip_keys);
+        // Rendered frames have no rows at all
+        match frames::frame_keys(storage, &bucket, video.id).await {
+            Ok(frame_keys) => keys.extend(frame_keys),
+            Err(e) => {
+                error!("Failed to list frames of video {}: {:?}", video.id, e);
+                continue;
+            }
+        }
+        // The selected thumbnail is also a candidate
+        keys.sort();
+        keys.dedup();
+
+        // Keep the row if storage cleanup fails so the next run retries it
+        let mut delete_failed = false;
+        for key in keys {
+            if let Err(e) = storage.delete(&bucket, &key).await {
+                error!("Failed to delete object {} for video {}: {:?}", key, video.id, e);
+                delete_failed = true;
+            }
+        }
+        if delete_failed {
+            continue;
+        }
+
+        sqlx::query("DELETE FROM videos WHERE id = $1")
+            .bind(video.id)
+            .execute(db_pool)
+            .await?;
+        purged += 1;
+    }
+
+    Ok(purged)
+}
+
+// Periodically purge expired videos from the trash. Interval is configurable via TRASH_PURGE_INTERVAL_SECS.
+pub async fn run_trash_purger(db_pool: PgPool, storage: Storage) {
+    let interval_secs = std::env::var("TRASH_PURGE_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600);
+
+    info!("Starting trash purger (interval: {} seconds)", interval_secs);
+
+    loop {
+        match purge_expired_videos(&db_pool, storage.as_ref()).await {
+            Ok(0) => {}
+            Ok(purged) => info!("Purged {} videos from the trash", purged),
+            Err(e) => error!("Error purging trash: {:?}", e),
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
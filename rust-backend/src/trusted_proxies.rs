@@ -0,0 +1,129 @@
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use actix_web::http::header::{HeaderName, FORWARDED};
+use actix_web::HttpRequest;
+use log::warn;
+
+// Which address a request came from. Forwarded and X-Forwarded-For can be set to anything by the
+// client itself, so they're only believed when the connection comes from one of the proxies or
+// load balancers in TRUSTED_PROXIES (comma separated addresses or CIDR ranges, e.g.
+// 127.0.0.1,10.0.0.0/16). Hops are walked back from the connection's peer while they're trusted;
+// the first untrusted one is the client. Without TRUSTED_PROXIES the peer is the client.
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    // An address, or a range like 10.0.0.0/16
+    pub fn parse(value: &str) -> Option<IpRange> {
+        let (addr, prefix_len) = match value.trim().split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value.trim(), None),
+        };
+        let network = addr.parse::<IpAddr>().ok()?.to_canonical();
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.parse::<u8>().ok().filter(|len| *len <= max_len)?,
+            None => max_len,
+        };
+        Some(IpRange { network, prefix_len })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, ip, bits) = match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => (u32::from(network) as u128, u32::from(ip) as u128, 32),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip), 128),
+            _ => return false,
+        };
+        if self.prefix_len == 0 {
+            return true;
+        }
+        let shift = bits - self.prefix_len;
+        network >> shift == ip >> shift
+    }
+}
+
+pub fn trusted_proxies() -> Vec<IpRange> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let range = IpRange::parse(entry);
+            if range.is_none() {
+                warn!("Ignoring TRUSTED_PROXIES entry {}, expected an address or CIDR range", entry.trim());
+            }
+            range
+        })
+        .collect()
+}
+
+// An address as proxies write it: with or without a port, IPv6 possibly in brackets or quotes
+pub fn parse_hop(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).and_then(|v| v.parse::<IpAddr>().ok())
+}
+
+// The hops the request passed through according to its headers, client first. Forwarded wins
+// over X-Forwarded-For when a proxy sends both.
+pub fn forwarded_hops(http_req: &HttpRequest) -> Vec<String> {
+    let headers = http_req.headers();
+    let forwarded: Vec<String> = headers
+        .get_all(FORWARDED)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("for").then(|| value.trim().to_string())
+            })
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().to_string())
+        .filter(|hop| !hop.is_empty())
+        .collect()
+}
+
+// The client behind `peer`, believing hops only as far as the proxies they came through are
+// trusted. A hop that isn't an address stops the walk at the proxy that sent it.
+pub fn resolve_client_ip(peer: IpAddr, hops: &[String], trusted: &[IpRange]) -> IpAddr {
+    let mut client = peer.to_canonical();
+    for hop in hops.iter().rev() {
+        if !trusted.iter().any(|range| range.contains(client)) {
+            break;
+        }
+        match parse_hop(hop) {
+            Some(ip) => client = ip.to_canonical(),
+            None => break,
+        }
+    }
+    client
+}
+
+// Whether the connection comes from a trusted proxy, whose own headers can be believed
+pub fn from_trusted_proxy(http_req: &HttpRequest) -> bool {
+    http_req.peer_addr().is_some_and(|peer| trusted_proxies().iter().any(|range| range.contains(peer.ip())))
+}
+
+pub fn client_ip(http_req: &HttpRequest) -> Option<IpAddr> {
+    let peer = http_req.peer_addr()?.ip();
+    Some(resolve_client_ip(peer, &forwarded_hops(http_req), &trusted_proxies()))
+}
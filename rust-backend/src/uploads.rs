@@ -0,0 +1,387 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use log::{info, warn, error};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::categories;
+use crate::error::ApiError;
+use crate::models::{CreateUploadRequest, UploadSession, Video};
+use crate::moderation;
+use crate::services::object_sha256;
+use crate::storage::{ObjectStore, Storage};
+
+// Sessions expire this long after they were last written to
+pub const UPLOAD_EXPIRY_HOURS: i64 = 24;
+
+// Size of each S3 part. S3 requires every part except the last to be at least 5 MB.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+const BYTES_PER_MB: i64 = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadedPart {
+    part_number: i32,
+    e_tag: String,
+}
+
+fn max_upload_bytes() -> i64 {
+    std::env::var("MAX_UPLOAD_MB")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(10 * 1024)
+        * BYTES_PER_MB
+}
+
+fn content_type_for(filename: &str) -> Option<(&'static str, &'static str)> {
+    let extension = filename.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "mp4" | "m4v" => Some(("mp4", "video/mp4")),
+        "webm" => Some(("webm", "video/webm")),
+        "mov" => Some(("mov", "video/quicktime")),
+        "mkv" => Some(("mkv", "video/x-matroska")),
+        _ => None,
+    }
+}
+
+fn next_expiry() -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc() + chrono::Duration::hours(UPLOAD_EXPIRY_HOURS)
+}
+
+fn s3_error(action: &str, e: impl std::fmt::Debug) -> ApiError {
+    ApiError::Internal(format!("Failed to {}: {:?}", action, e))
+}
+
+pub async fn create_session(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    user_id: i32,
+    request: &CreateUploadRequest,
+) -> Result<UploadSession, ApiError> {
+    if request.title.trim().is_empty() {
+        return Err(ApiError::BadRequest("Title is required".to_string()));
+    }
+    if request.size <= 0 {
+        return Err(ApiError::BadRequest("size must be positive".to_string()));
+    }
+    if request.size > max_upload_bytes() {
+        return Err(ApiError::BadRequest(format!("Uploads are limited to {} MB", max_upload_bytes() / BYTES_PER_MB)));
+    }
+    let (extension, content_type) = content_type_for(&request.filename)
+        .ok_or_else(|| ApiError::BadRequest("Unsupported file type; expected mp4, webm, mov or mkv".to_string()))?;
+    let category_ids = categories::validate(db_pool, &request.category_ids).await?;
+
+    let s3_key = format!("videos/{}.{}", uuid::Uuid::new_v4(), extension);
+    // Parts and completion can legitimately take long, but starting an upload shouldn't, so this
+    // is where unreachable storage is noticed
+    let s3_upload_id = storage
+        .create_multipart(bucket, &s3_key, content_type)
+        .await
+        .map_err(|e| e.into_api_error(|e| s3_error("start multipart upload", e)))?;
+
+    let session = sqlx::query_as::<_, UploadSession>(
+        "INSERT INTO upload_sessions (id, user_id, s3_key, s3_upload_id, upload_length, title, description, tags, category_ids, created_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         RETURNING *"
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(user_id)
+    .bind(&s3_key)
+    .bind(&s3_upload_id)
+    .bind(request.size)
+    .bind(request.title.trim())
+    .bind(&request.description)
+    .bind(request.tags.clone().unwrap_or_default())
+    .bind(&category_ids)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(next_expiry())
+    .fetch_one(db_pool)
+    .await?;
+
+    info!("Created upload session {} for user {} ({} bytes)", session.id, user_id, session.upload_length);
+    Ok(session)
+}
+
+pub async fn get_session(db_pool: &PgPool, id: &str, user_id: i32) -> Result<UploadSession, ApiError> {
+    let session = sqlx::query_as::<_, UploadSession>(
+        "SELECT * FROM upload_sessions WHERE id = $1 AND user_id = $2"
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db_pool)
+    .await?;
+    check_session(session)
+}
+
+// Like get_session, but locks the row for completing or cancelling the upload
+pub async fn lock_session(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &str,
+    user_id: i32,
+) -> Result<UploadSession, ApiError> {
+    let session = sqlx::query_as::<_, UploadSession>(
+        "SELECT * FROM upload_sessions WHERE id = $1 AND user_id = $2 FOR UPDATE"
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    check_session(session)
+}
+
+fn check_session(session: Option<UploadSession>) -> Result<UploadSession, ApiError> {
+    let session = session.ok_or_else(|| ApiError::NotFound("Upload not found".to_string()))?;
+    if session.video_id.is_none() && session.expires_at < chrono::Utc::now().naive_utc() {
+        return Err(ApiError::NotFound("Upload has expired".to_string()));
+    }
+    Ok(session)
+}
+
+async fn upload_part(
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    session: &UploadSession,
+    part_number: i32,
+    data: Vec<u8>,
+) -> Result<UploadedPart, ApiError> {
+    let e_tag = storage
+        .upload_part(bucket, &session.s3_key, &session.s3_upload_id, part_number, data.into())
+        .await
+        .map_err(|e| s3_error("upload part", e))?;
+
+    Ok(UploadedPart { part_number, e_tag })
+}
+
+// Append the request body to the session, flushing full parts to storage as they fill up.
+// Everything that arrived is kept even if the client disconnects or storage fails part way,
+// so the client can resume from the offset stored on the session. Nothing is locked while the
+// body arrives; of two requests for the same offset only the first to finish is recorded, and the
+// other gets a 409 to re-check the offset.
+pub async fn append_chunk(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    session: &mut UploadSession,
+    mut payload: actix_web::web::Payload,
+) -> Result<(), ApiError> {
+    let mut parts: Vec<UploadedPart> = serde_json::from_value(session.parts.clone())
+        .map_err(|e| ApiError::Internal(format!("Corrupt parts for upload {}: {}", session.id, e)))?;
+    let mut buffer = std::mem::take(&mut session.pending_data);
+    let expected_offset = session.upload_offset;
+    let mut received: i64 = 0;
+    let mut failure = None;
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                warn!("Upload {} interrupted after {} bytes: {}", session.id, received, e);
+                failure = Some(ApiError::BadRequest("Upload interrupted".to_string()));
+                break;
+            }
+        };
+
+        if session.upload_offset + received + chunk.len() as i64 > session.upload_length {
+            failure = Some(ApiError::BadRequest("Request body exceeds the declared upload size".to_string()));
+            break;
+        }
+        buffer.extend_from_slice(&chunk);
+        received += chunk.len() as i64;
+
+        while buffer.len() >= PART_SIZE {
+            let part_number = parts.len() as i32 + 1;
+            match upload_part(storage, bucket, session, part_number, buffer[..PART_SIZE].to_vec()).await {
+                Ok(part) => {
+                    parts.push(part);
+                    buffer.drain(..PART_SIZE);
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        if failure.is_some() {
+            break;
+        }
+    }
+
+    session.upload_offset += received;
+    session.pending_data = buffer;
+    session.parts = serde_json::to_value(&parts)
+        .map_err(|e| ApiError::Internal(format!("Failed to serialize parts: {}", e)))?;
+    session.expires_at = next_expiry();
+
+    // Only recorded if no other request moved the offset while this one was receiving
+    let recorded = sqlx::query(
+        "UPDATE upload_sessions SET upload_offset = $1, parts = $2, pending_data = $3, expires_at = $4
+         WHERE id = $5 AND upload_offset = $6 AND video_id IS NULL"
+    )
+    .bind(session.upload_offset)
+    .bind(&session.parts)
+    .bind(&session.pending_data)
+    .bind(session.expires_at)
+    .bind(&session.id)
+    .bind(expected_offset)
+    .execute(db_pool)
+    .await?;
+    if recorded.rows_affected() == 0 {
+        warn!("Upload {} moved past offset {} while a chunk was being received", session.id, expected_offset);
+        return Err(ApiError::Conflict(format!(
+            "Upload-Offset {} is no longer the current offset", expected_offset
+        )));
+    }
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// Once every byte has arrived, upload the remainder as the last part, complete the multipart
+// upload and create the video. Returns None while the upload is still incomplete. Runs in its
+// own transaction so a failure here doesn't lose the progress already recorded.
+pub async fn complete_if_finished(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    bucket: &str,
+    id: &str,
+    user_id: i32,
+) -> Result<Option<Video>, ApiError> {
+    let mut tx = db_pool.begin().await?;
+    let mut session = lock_session(&mut tx, id, user_id).await?;
+    if session.video_id.is_some() || session.upload_offset < session.upload_length {
+        return Ok(None);
+    }
+
+    let mut parts: Vec<UploadedPart> = serde_json::from_value(session.parts.clone())
+        .map_err(|e| ApiError::Internal(format!("Corrupt parts for upload {}: {}", session.id, e)))?;
+    if !session.pending_data.is_empty() || parts.is_empty() {
+        let part_number = parts.len() as i32 + 1;
+        let data = std::mem::take(&mut session.pending_data);
+        parts.push(upload_part(storage, bucket, &session, part_number, data).await?);
+    }
+
+    let completed: Vec<(i32, String)> = parts.iter().map(|p| (p.part_number, p.e_tag.clone())).collect();
+    storage
+        .complete_multipart(bucket, &session.s3_key, &session.s3_upload_id, &completed)
+        .await
+        .map_err(|e| s3_error("complete multipart upload", e))?;
+
+    // The file arrives over many requests, so hash the assembled object rather than the chunks
+    let sha256 = object_sha256(storage, bucket, &session.s3_key).await
+        .map_err(|e| s3_error("checksum uploaded video", e))?;
+
+    // Reject exact duplicates of a video the uploader already has. Other users' videos don't
+    // count; matching them would throw away this upload and reveal which videos they have.
+    let duplicate: Option<i32> = sqlx::query_scalar(
+        "SELECT id FROM videos WHERE sha256 = $1 AND uploaded_by = $2 AND deleted_at IS NULL LIMIT 1"
+    )
+    .bind(&sha256)
+    .bind(session.user_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    if let Some(existing_id) = duplicate {
+        if let Err(e) = storage.delete(bucket, &session.s3_key).await {
+            error!("Failed to delete duplicate upload {}: {:?}", session.s3_key, e);
+        }
+        sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+            .bind(&session.id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        info!("Upload {} rejected as a duplicate of video {}", session.id, existing_id);
+        return Err(ApiError::Conflict(format!("You already uploaded this video (video {})", existing_id)));
+    }
+
+    let mut video = sqlx::query_as::<_, Video>(
+        "INSERT INTO videos (title, description, s3_key, uploaded_by, tags, upload_date, sha256, moderation_status)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING *"
+    )
+    .bind(&session.title)
+    .bind(&session.description)
+    .bind(&session.s3_key)
+    .bind(session.user_id)
+    .bind(session.tags.clone().unwrap_or_default())
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(&sha256)
+    .bind(moderation::initial_status())
+    .fetch_one(&mut tx)
+    .await?;
+    categories::set_for_video(&mut tx, video.id, &session.category_ids).await?;
+    video.category_ids = Some(session.category_ids.clone());
+
+    sqlx::query("UPDATE upload_sessions SET video_id = $1, parts = $2, pending_data = ''::bytea WHERE id = $3")
+        .bind(video.id)
+        .bind(serde_json::to_value(&parts).unwrap_or_default())
+        .bind(&session.id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    info!("Upload {} completed as video {}", session.id, video.id);
+    Ok(Some(video))
+}
+
+pub async fn abort_session(storage: &dyn ObjectStore, bucket: &str, session: &UploadSession) -> Result<(), ApiError> {
+    if session.video_id.is_some() {
+        return Ok(());
+    }
+    storage
+        .abort_multipart(bucket, &session.s3_key, &session.s3_upload_id)
+        .await
+        .map_err(|e| e.into_api_error(|e| s3_error("abort multipart upload", e)))
+}
+
+// Abort expired unfinished uploads and forget expired sessions. Returns the number removed.
+pub async fn purge_expired_uploads(db_pool: &PgPool, storage: &dyn ObjectStore) -> Result<usize, sqlx::Error> {
+    let expired = sqlx::query_as::<_, UploadSession>(
+        "SELECT * FROM upload_sessions WHERE expires_at < $1"
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_all(db_pool)
+    .await?;
+
+    let bucket = std::env::var("S3_BUCKET")
+        .or_else(|_| std::env::var("MINIO_BUCKET"))
+        .unwrap_or_else(|_| "videos".to_string());
+
+    let mut purged = 0;
+    for session in expired {
+        // Keep the row if the abort fails so the next run retries it
+        if let Err(e) = abort_session(storage, &bucket, &session).await {
+            error!("Failed to abort expired upload {}: {}", session.id, e);
+            continue;
+        }
+        sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+            .bind(&session.id)
+            .execute(db_pool)
+            .await?;
+        purged += 1;
+    }
+
+    Ok(purged)
+}
+
+// Periodically clean up expired uploads. Interval is configurable via UPLOAD_CLEANUP_INTERVAL_SECS.
+pub async fn run_upload_cleanup(db_pool: PgPool, storage: Storage) {
+    let interval_secs = std::env::var("UPLOAD_CLEANUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600);
+
+    info!("Starting upload cleanup (interval: {} seconds)", interval_secs);
+
+    loop {
+        match purge_expired_uploads(&db_pool, storage.as_ref()).await {
+            Ok(0) => {}
+            Ok(purged) => info!("Removed {} expired uploads", purged),
+            Err(e) => error!("Error removing expired uploads: {:?}", e),
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
@@ -0,0 +1,123 @@
+use log::warn;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::models::{ThemeSetting, UserSettings, UserSettingsRequest};
+use crate::translations::normalize_language;
+use crate::watermarks;
+
+pub const MIN_PLAYBACK_SPEED: f64 = 0.25;
+pub const MAX_PLAYBACK_SPEED: f64 = 4.0;
+pub const MAX_THEME_NAME_CHARS: usize = 64;
+
+// Colors a custom theme sets, as named by the frontend
+pub const THEME_COLORS: [&str; 8] = ["primary", "secondary", "accent", "background", "surface", "text", "textSecondary", "plyrColor"];
+
+// Settings as stored, with defaults for whatever isn't set. Stored settings are validated on
+// write, so this only falls back to the defaults if the column was edited by hand.
+pub fn from_stored(stored: Option<Value>) -> UserSettings {
+    let Some(stored) = stored else {
+        return UserSettings::default();
+    };
+    serde_json::from_value(stored).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable user settings: {}", e);
+        UserSettings::default()
+    })
+}
+
+// "#rgb" or "#rrggbb"
+fn is_hex_color(color: &str) -> bool {
+    let Some(digits) = color.strip_prefix('#') else {
+        return false;
+    };
+    (digits.len() == 3 || digits.len() == 6) && digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn validate_theme(theme: &ThemeSetting) -> Result<ThemeSetting, String> {
+    let name = theme.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_THEME_NAME_CHARS {
+        return Err(format!("Theme names must be 1 to {} characters", MAX_THEME_NAME_CHARS));
+    }
+    if !theme.is_custom {
+        if !theme.colors.is_empty() {
+            return Err("Only custom themes can set colors".to_string());
+        }
+        return Ok(ThemeSetting { name: name.to_string(), ..theme.clone() });
+    }
+    if let Some(key) = theme.colors.keys().find(|key| !THEME_COLORS.contains(&key.as_str())) {
+        return Err(format!("Unknown theme color {}", key));
+    }
+    for key in THEME_COLORS {
+        match theme.colors.get(key) {
+            Some(color) if is_hex_color(color) => {}
+            Some(_) => return Err(format!("Theme color {} must be a hex color such as #1a2b3c", key)),
+            None => return Err(format!("Custom themes must set {}", key)),
+        }
+    }
+    Ok(ThemeSetting { name: name.to_string(), ..theme.clone() })
+}
+
+// "auto", or a rendition name like "720p"
+fn validate_quality(quality: &str) -> Result<String, String> {
+    let quality = quality.trim().to_ascii_lowercase();
+    let is_rendition = quality
+        .strip_suffix('p')
+        .and_then(|height| height.parse::<u32>().ok())
+        .is_some_and(|height| (144..=4320).contains(&height));
+    if quality == "auto" || is_rendition {
+        Ok(quality)
+    } else {
+        Err("preferredQuality must be \"auto\" or a rendition such as \"720p\"".to_string())
+    }
+}
+
+// The stored settings to change for a request, as a JSON object of validated values. Captions
+// being turned off is a null, which the update removes.
+pub fn validate_update(request: &UserSettingsRequest) -> Result<Map<String, Value>, String> {
+    let mut patch = Map::new();
+    if let Some(theme) = &request.theme {
+        patch.insert("theme".to_string(), serde_json::to_value(validate_theme(theme)?).map_err(|e| e.to_string())?);
+    }
+    if let Some(speed) = request.playback_speed {
+        if !(MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED).contains(&speed) {
+            return Err(format!("playbackSpeed must be between {} and {}", MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED));
+        }
+        patch.insert("playbackSpeed".to_string(), speed.into());
+    }
+    if let Some(autoplay) = request.autoplay {
+        patch.insert("autoplay".to_string(), autoplay.into());
+    }
+    if let Some(quality) = &request.preferred_quality {
+        patch.insert("preferredQuality".to_string(), validate_quality(quality)?.into());
+    }
+    if let Some(language) = &request.captions_language {
+        let language = if language.trim().is_empty() {
+            Value::Null
+        } else {
+            normalize_language(language).ok_or_else(|| format!("Invalid captionsLanguage {}", language))?.into()
+        };
+        patch.insert("captionsLanguage".to_string(), language);
+    }
+    if let Some(volume) = request.volume {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err("volume must be between 0 and 1".to_string());
+        }
+        patch.insert("volume".to_string(), volume.into());
+    }
+    if let Some(watermark) = &request.watermark {
+        watermarks::validate_settings(watermark)?;
+        patch.insert("watermark".to_string(), serde_json::to_value(watermark).map_err(|e| e.to_string())?);
+    }
+    Ok(patch)
+}
+
+// Apply validated changes in a single UPDATE, so concurrent updates of different settings don't
+// overwrite each other. Returns the user's settings afterwards, or None if there is no such user.
+pub async fn update(db_pool: &PgPool, user_id: i32, patch: Map<String, Value>) -> Result<Option<UserSettings>, sqlx::Error> {
+    let stored: Option<Value> = sqlx::query_scalar("UPDATE users SET settings = jsonb_strip_nulls(settings || $1) WHERE id = $2 RETURNING settings")
+        .bind(Value::Object(patch))
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await?;
+    Ok(stored.map(|stored| from_stored(Some(stored))))
+}
@@ -345,7 +345,7 @@ fn parse_trak_box(data: &[u8]) -> Option<(u32, u32)> {
 }
 
 pub async fn extract_video_metadata_from_s3(
-    s3_client: &aws_sdk_s3::Client,
+    storage: &dyn crate::storage::ObjectStore,
     bucket: &str,
     s3_key: &str,
 ) -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
@@ -354,15 +354,11 @@ pub async fn extract_video_metadata_from_s3(
     // Download the video file temporarily
     let temp_file_path = format!("/tmp/{}", uuid::Uuid::new_v4());
     
-    let get_object_output = s3_client
-        .get_object()
-        .bucket(bucket)
-        .key(s3_key)
-        .send()
-        .await?;
-    
-    let body = get_object_output.body.collect().await?.into_bytes();
-    tokio::fs::write(&temp_file_path, body).await?;
+    let config = crate::services::ParallelDownloadConfig::from_env();
+    if let Err(e) = crate::services::download_object_parallel(storage, bucket, s3_key, std::path::Path::new(&temp_file_path), config).await {
+        let _ = tokio::fs::remove_file(&temp_file_path).await;
+        return Err(e);
+    }
     
     // Extract duration using our pure Rust metadata parser
     let duration_result = extract_video_duration(&temp_file_path).await;
@@ -374,8 +370,7 @@ pub async fn extract_video_metadata_from_s3(
     
     match duration_result {
         Ok(duration) => Ok(duration),
-        Err(e) => Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(e) => Err(Box::new(std::io::Error::other(
             format!("Duration extraction failed: {}", e)
         )) as Box<dyn std::error::Error + Send + Sync>)
     }
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use log::{error, info, warn};
+use notify::{Config, EventKind, PollWatcher, RecursiveMode, Watcher};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::imports::{is_video_key, title_from_key};
+use crate::job_queue::JobQueue;
+use crate::models::Video;
+use crate::moderation;
+use crate::storage::{content_type_for_key, ObjectStore, Storage};
+use crate::webhooks;
+
+type IngestResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Bulk imports without the HTTP upload path. Video files dropped into WATCH_FOLDER are moved to
+// the bucket as new videos, owned by WATCH_FOLDER_USER_ID if set, and processed like uploads.
+//
+// Files copied from a NAS arrive over minutes, so a file is only taken once its size and
+// modification time have stayed the same for WATCH_FOLDER_SETTLE_SECS. Network mounts don't
+// deliver file system events, so WATCH_FOLDER_POLL=true scans the folder instead.
+//
+// A file being ingested is first renamed into .ingesting/, which only one of several processes
+// watching the same folder can do. Files that fail are moved to .failed/ to be looked at. Both
+// keep the original name behind the id of the ingest, so names never collide, and files a stopped
+// process left in .ingesting/ are put back at startup.
+
+const DEFAULT_SETTLE_SECS: u64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+// Claims older than this were left by a process that stopped mid-ingest. Generous, as the upload
+// of a large file happens while it is claimed.
+pub const STALE_CLAIM: Duration = Duration::from_secs(6 * 60 * 60);
+
+pub const INGESTING_DIR: &str = ".ingesting";
+pub const FAILED_DIR: &str = ".failed";
+
+#[derive(Debug, Clone)]
+pub struct WatchFolderConfig {
+    pub folder: PathBuf,
+    pub settle: Duration,
+    pub poll: bool,
+    pub uploaded_by: Option<i32>,
+    pub bucket: String,
+}
+
+impl WatchFolderConfig {
+    pub fn from_env() -> Option<Self> {
+        let folder = env::var("WATCH_FOLDER").ok().filter(|f| !f.trim().is_empty())?;
+        let settle = env::var("WATCH_FOLDER_SETTLE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_SETTLE_SECS);
+        Some(Self {
+            folder: PathBuf::from(folder.trim()),
+            settle: Duration::from_secs(settle),
+            poll: env::var("WATCH_FOLDER_POLL").is_ok_and(|v| v.eq_ignore_ascii_case("true") || v == "1"),
+            uploaded_by: env::var("WATCH_FOLDER_USER_ID").ok().and_then(|v| v.parse().ok()),
+            bucket: env::var("S3_BUCKET")
+                .or_else(|_| env::var("MINIO_BUCKET"))
+                .unwrap_or_else(|_| "videos".to_string()),
+        })
+    }
+}
+
+// Video files directly in the folder; hidden ones are partial copies or our own directories
+pub fn is_candidate(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| !name.starts_with('.') && is_video_key(name))
+}
+
+// What a file looked like when it last changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileState {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+// Files seen in the folder, waiting for their copy to finish
+#[derive(Debug, Default)]
+pub struct PendingFiles {
+    files: HashMap<PathBuf, (FileState, Instant)>,
+}
+
+impl PendingFiles {
+    pub fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    // Record the file's current state, restarting its wait whenever it changed
+    pub fn observe(&mut self, path: &Path, state: FileState, now: Instant) {
+        match self.files.get_mut(path) {
+            Some((seen, since)) if *seen != state => {
+                *seen = state;
+                *since = now;
+            }
+            Some(_) => {}
+            None => {
+                self.files.insert(path.to_path_buf(), (state, now));
+            }
+        }
+    }
+
+    // The non-empty files that haven't changed for `settle`
+    pub fn settled(&self, settle: Duration, now: Instant) -> Vec<PathBuf> {
+        let mut settled: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(_, (state, since))| state.size > 0 && now.duration_since(*since) >= settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled.sort();
+        settled
+    }
+}
+
+// A claimed file is named "<id>-<original name>"; <id> also names its object in the bucket
+fn claimed_name(id: Uuid, file_name: &str) -> String {
+    format!("{}-{}", id, file_name)
+}
+
+// The id and original name of a claimed file. Files claimed before the name was kept are just
+// "<id>.<extension>", so that is all that's left of their name.
+pub fn parse_claimed_name(name: &str) -> Option<(Uuid, &str)> {
+    let id = Uuid::parse_str(name.get(..36)?).ok()?;
+    match &name[36..] {
+        rest if rest.len() > 1 && rest.starts_with('-') => Some((id, &rest[1..])),
+        rest if rest.len() > 1 && rest.starts_with('.') => Some((id, name)),
+        _ => None,
+    }
+}
+
+fn s3_key_for(id: Uuid, file_name: &str) -> String {
+    let extension = file_name.rsplit_once('.').map_or("mp4", |(_, ext)| ext).to_ascii_lowercase();
+    format!("videos/{}.{}", id, extension)
+}
+
+async fn file_state(path: &Path) -> Option<FileState> {
+    let metadata = tokio::fs::metadata(path).await.ok().filter(|m| m.is_file())?;
+    Some(FileState { size: metadata.len(), modified: metadata.modified().ok() })
+}
+
+// Move the file out of the watched folder under a unique name, or None when another process
+// already did
+async fn claim(folder: &Path, path: &Path, claimed_name: &str) -> std::io::Result<Option<PathBuf>> {
+    let claimed = folder.join(INGESTING_DIR).join(claimed_name);
+    match tokio::fs::rename(path, &claimed).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    // Renaming keeps the copy's modification time, but a claim's age counts from now
+    let touched = match tokio::fs::OpenOptions::new().write(true).open(&claimed).await {
+        Ok(file) => file.into_std().await.set_modified(SystemTime::now()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = touched {
+        warn!("Failed to mark {} as just claimed: {:?}", claimed.display(), e);
+    }
+    Ok(Some(claimed))
+}
+
+// The claimed name is unique, so earlier failures of a file with the same name are kept
+async fn move_to_failed(folder: &Path, claimed: &Path) {
+    let failed = folder.join(FAILED_DIR).join(claimed.file_name().unwrap_or_default());
+    if let Err(e) = tokio::fs::rename(claimed, &failed).await {
+        error!("Failed to move {} to {}: {:?}", claimed.display(), failed.display(), e);
+    }
+}
+
+async fn create_video(db_pool: &PgPool, title: &str, s3_key: &str, uploaded_by: Option<i32>) -> Result<Video, sqlx::Error> {
+    sqlx::query_as::<_, Video>(
+        "INSERT INTO videos (title, s3_key, uploaded_by, upload_date, moderation_status)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *"
+    )
+    .bind(title)
+    .bind(s3_key)
+    .bind(uploaded_by)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(moderation::initial_status())
+    .fetch_one(db_pool)
+    .await
+}
+
+// Upload a settled file as a new video and queue its processing. Returns None when the file was
+// taken by another process first.
+pub async fn ingest_file(
+    db_pool: &PgPool,
+    storage: &dyn ObjectStore,
+    job_queue: Option<&Arc<JobQueue>>,
+    config: &WatchFolderConfig,
+    path: &Path,
+) -> IngestResult<Option<Video>> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    let id = Uuid::new_v4();
+    let s3_key = s3_key_for(id, &file_name);
+
+    let Some(claimed) = claim(&config.folder, path, &claimed_name(id, &file_name)).await? else {
+        return Ok(None);
+    };
+
+    let content_type = content_type_for_key(&s3_key).unwrap_or("application/octet-stream");
+    let uploaded = storage.put_file(&config.bucket, &s3_key, &claimed, content_type).await;
+    if let Err(e) = uploaded {
+        move_to_failed(&config.folder, &claimed).await;
+        return Err(format!("Failed to upload {}: {:?}", file_name, e).into());
+    }
+
+    let video = match create_video(db_pool, &title_from_key(&file_name), &s3_key, config.uploaded_by).await {
+        Ok(video) => video,
+        Err(e) => {
+            if let Err(e) = storage.delete(&config.bucket, &s3_key).await {
+                warn!("Failed to remove {} after its video couldn't be created: {:?}", s3_key, e);
+            }
+            move_to_failed(&config.folder, &claimed).await;
+            return Err(e.into());
+        }
+    };
+
+    if let Some(job_queue) = job_queue {
+        if let Err(e) = job_queue.enqueue_processing(&video, &config.bucket, false).await {
+            error!("Failed to enqueue processing for ingested video {}: {:?}", video.id, e);
+        }
+    }
+    webhooks::video_created(db_pool, &video).await;
+    if let Err(e) = tokio::fs::remove_file(&claimed).await {
+        warn!("Failed to remove {} after ingesting it: {:?}", claimed.display(), e);
+    }
+    info!("Ingested {} from the watched folder as video {} ({})", file_name, video.id, s3_key);
+    Ok(Some(video))
+}
+
+// Put files claimed longer than `stale_after` ago back in the folder under their original name,
+// to be ingested again. One whose video was created before its process stopped is just removed.
+pub async fn recover_claims(db_pool: &PgPool, folder: &Path, stale_after: Duration) -> IngestResult<usize> {
+    let cutoff = SystemTime::now() - stale_after;
+    let mut entries = tokio::fs::read_dir(folder.join(INGESTING_DIR)).await?;
+    let mut recovered = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some((id, file_name)) = parse_claimed_name(&name) else {
+            continue;
+        };
+        let modified = entry.metadata().await?.modified()?;
+        if modified > cutoff {
+            continue;
+        }
+
+        let claimed = entry.path();
+        let ingested: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM videos WHERE s3_key = $1)")
+            .bind(s3_key_for(id, file_name))
+            .fetch_one(db_pool)
+            .await?;
+        if ingested {
+            tokio::fs::remove_file(&claimed).await?;
+            info!("Removed {}, ingested before its process stopped", claimed.display());
+            continue;
+        }
+
+        let mut restored = folder.join(file_name);
+        if tokio::fs::try_exists(&restored).await.unwrap_or(true) {
+            restored = folder.join(&name);
+        }
+        match tokio::fs::rename(&claimed, &restored).await {
+            Ok(()) => {
+                info!("Put {} back as {} to be ingested again", claimed.display(), restored.display());
+                recovered += 1;
+            }
+            // Another process recovered it first
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(recovered)
+}
+
+// Files already in the folder, e.g. dropped while no process was running
+async fn scan(folder: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut entries = tokio::fs::read_dir(folder).await?;
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if is_candidate(&entry.path()) {
+            paths.push(entry.path());
+        }
+    }
+    Ok(paths)
+}
+
+fn start_watcher(config: &WatchFolderConfig, tx: mpsc::UnboundedSender<PathBuf>) -> notify::Result<Box<dyn Watcher + Send>> {
+    let handler = move |result: notify::Result<notify::Event>| match result {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Watched folder error: {:?}", e),
+    };
+    let mut watcher: Box<dyn Watcher + Send> = if config.poll {
+        Box::new(PollWatcher::new(handler, Config::default().with_poll_interval(POLL_INTERVAL))?)
+    } else {
+        Box::new(notify::recommended_watcher(handler)?)
+    };
+    watcher.watch(&config.folder, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+pub async fn run_watch_folder(db_pool: PgPool, storage: Storage, job_queue: Option<Arc<JobQueue>>) {
+    let Some(config) = WatchFolderConfig::from_env() else {
+        return;
+    };
+    for dir in [INGESTING_DIR, FAILED_DIR] {
+        if let Err(e) = tokio::fs::create_dir_all(config.folder.join(dir)).await {
+            error!("Not watching {}: can't create {}: {:?}", config.folder.display(), dir, e);
+            return;
+        }
+    }
+
+    if let Err(e) = recover_claims(&db_pool, &config.folder, STALE_CLAIM).await {
+        error!("Failed to recover files left in {}: {:?}", config.folder.join(INGESTING_DIR).display(), e);
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    // Dropping the watcher stops it, so it lives as long as this loop
+    let _watcher = match start_watcher(&config, tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("Failed to watch {}: {:?}", config.folder.display(), e);
+            return;
+        }
+    };
+    info!(
+        "Watching {} for videos to ingest ({}, settling for {} seconds)",
+        config.folder.display(),
+        if config.poll { "polling" } else { "file system events" },
+        config.settle.as_secs()
+    );
+
+    let mut pending = PendingFiles::default();
+    match scan(&config.folder).await {
+        Ok(paths) => {
+            for path in paths {
+                if let Some(state) = file_state(&path).await {
+                    pending.observe(&path, state, Instant::now());
+                }
+            }
+        }
+        Err(e) => error!("Failed to scan {}: {:?}", config.folder.display(), e),
+    }
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(1).max(config.settle / 4).min(POLL_INTERVAL));
+    loop {
+        tokio::select! {
+            Some(path) = rx.recv() => {
+                if is_candidate(&path) && path.parent() == Some(config.folder.as_path()) {
+                    match file_state(&path).await {
+                        Some(state) => pending.observe(&path, state, Instant::now()),
+                        None => pending.remove(&path),
+                    }
+                }
+            }
+            _ = ticker.tick() => {
+                // Copies can finish without another event, so pending files are looked at again
+                let now = Instant::now();
+                let paths: Vec<PathBuf> = pending.files.keys().cloned().collect();
+                for path in paths {
+                    match file_state(&path).await {
+                        Some(state) => pending.observe(&path, state, now),
+                        None => pending.remove(&path),
+                    }
+                }
+                for path in pending.settled(config.settle, now) {
+                    pending.remove(&path);
+                    if let Err(e) = ingest_file(&db_pool, storage.as_ref(), job_queue.as_ref(), &config, &path).await {
+                        error!("Failed to ingest {}: {:?}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+}
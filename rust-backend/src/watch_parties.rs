@@ -0,0 +1,690 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use sqlx::PgPool;
+use log::{error, info, warn};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::circuit_breaker;
+use crate::error::ApiError;
+use crate::models::{Video, WatchParty, WatchPartyGuest, WatchPartyGuestPresence, WatchPartyInvite, WatchPartySession, WatchPartyHistoryEntry, WatchPartyVideoStats};
+
+// Invites last a day unless the host asks otherwise, and never more than a week
+pub const DEFAULT_INVITE_HOURS: i64 = 24;
+pub const MAX_INVITE_HOURS: i64 = 7 * 24;
+
+// Users the host can invite in one request
+pub const MAX_INVITED_USERS: usize = 50;
+
+// How far ahead a party can be scheduled
+pub const MAX_SCHEDULE_DAYS: i64 = 90;
+
+// A scheduled play that comes due after nobody was around for this long is dropped rather than
+// starting the video for whoever joins later
+const START_GRACE_SECS: i64 = 60;
+
+pub async fn get_room(db_pool: &PgPool, video_id: i32) -> Result<Option<WatchParty>, sqlx::Error> {
+    sqlx::query_as::<_, WatchParty>("SELECT * FROM watch_parties WHERE video_id = $1")
+        .bind(video_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Return the room for a video, creating it if nobody has opened it yet. A new room is hosted by
+// `user_id` when they uploaded the video or are an admin, and by the uploader otherwise, so
+// anyone else opening it is just a participant. None when the room would have no host.
+pub async fn get_or_create_room(db_pool: &PgPool, video_id: i32, user_id: i32) -> Result<Option<WatchParty>, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO watch_parties (video_id, host_user_id, created_at)
+         SELECT id, host_user_id, $3 FROM (
+             SELECT v.id, CASE WHEN v.uploaded_by = $2 OR EXISTS (SELECT 1 FROM users WHERE id = $2 AND is_admin)
+                 THEN $2 ELSE v.uploaded_by END AS host_user_id
+             FROM videos v WHERE v.id = $1
+         ) room
+         WHERE host_user_id IS NOT NULL
+         ON CONFLICT (video_id) DO NOTHING"
+    )
+    .bind(video_id)
+    .bind(user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(db_pool)
+    .await?;
+
+    get_room(db_pool, video_id).await
+}
+
+pub async fn set_invite_only(db_pool: &PgPool, video_id: i32, invite_only: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE watch_parties SET invite_only = $1 WHERE video_id = $2")
+        .bind(invite_only)
+        .bind(video_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Schedule the party to start at `scheduled_start`, or cancel the schedule with None.
+// Rescheduling arms the automatic play again.
+pub async fn set_scheduled_start(
+    db_pool: &PgPool,
+    video_id: i32,
+    scheduled_start: Option<chrono::NaiveDateTime>,
+) -> Result<WatchParty, sqlx::Error> {
+    sqlx::query_as::<_, WatchParty>(
+        "UPDATE watch_parties SET scheduled_start = $2, started_at = NULL WHERE video_id = $1 RETURNING *"
+    )
+    .bind(video_id)
+    .bind(scheduled_start)
+    .fetch_one(db_pool)
+    .await
+}
+
+// When the party starts, if it is scheduled and hasn't yet. Playback is locked until then.
+pub async fn locked_until(db_pool: &PgPool, video_id: i32) -> Result<Option<chrono::NaiveDateTime>, sqlx::Error> {
+    sqlx::query_scalar::<_, chrono::NaiveDateTime>(
+        "SELECT scheduled_start FROM watch_parties WHERE video_id = $1 AND scheduled_start > $2"
+    )
+    .bind(video_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_optional(db_pool)
+    .await
+}
+
+// Claim the scheduled parties among `video_ids` whose start has come, returning the ones to play.
+// A party is only claimed once, so with several replicas exactly one of them starts it.
+pub async fn claim_due_starts(db_pool: &PgPool, video_ids: &[i32]) -> Result<Vec<WatchParty>, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let claimed = sqlx::query_as::<_, WatchParty>(
+        "UPDATE watch_parties SET started_at = $2
+         WHERE video_id = ANY($1) AND started_at IS NULL AND scheduled_start <= $2
+         RETURNING *"
+    )
+    .bind(video_ids)
+    .bind(now)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(claimed
+        .into_iter()
+        .filter(|room| room.scheduled_start.is_some_and(|start| now - start <= chrono::Duration::seconds(START_GRACE_SECS)))
+        .collect())
+}
+
+// Scheduled parties among `video_ids` still counting down
+pub async fn pending_starts(db_pool: &PgPool, video_ids: &[i32]) -> Result<Vec<WatchParty>, sqlx::Error> {
+    sqlx::query_as::<_, WatchParty>(
+        "SELECT * FROM watch_parties WHERE video_id = ANY($1) AND started_at IS NULL AND scheduled_start > $2"
+    )
+    .bind(video_ids)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_all(db_pool)
+    .await
+}
+
+// Create an invite for a specific user, or a shareable link when `invited_user_id` is None
+pub async fn create_invite(
+    db_pool: &PgPool,
+    video_id: i32,
+    created_by: i32,
+    invited_user_id: Option<i32>,
+    expires_in_hours: i64,
+) -> Result<WatchPartyInvite, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let invite = sqlx::query_as::<_, WatchPartyInvite>(
+        "INSERT INTO watch_party_invites (video_id, token, invited_user_id, created_by, expires_at, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *"
+    )
+    .bind(video_id)
+    .bind(uuid::Uuid::new_v4().simple().to_string())
+    .bind(invited_user_id)
+    .bind(created_by)
+    .bind(now + chrono::Duration::hours(expires_in_hours))
+    .bind(now)
+    .fetch_one(db_pool)
+    .await?;
+
+    info!("User {} created watch party invite {} for video {}", created_by, invite.id, video_id);
+    Ok(invite)
+}
+
+// Whether a user (or anonymous client holding an invite link) may join a video's watch party.
+// Rooms that aren't invite-only are open to everyone.
+pub async fn can_join(
+    db_pool: &PgPool,
+    video_id: i32,
+    user_id: Option<i32>,
+    invite_token: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let room = match get_room(db_pool, video_id).await? {
+        Some(room) if room.invite_only => room,
+        _ => return Ok(true),
+    };
+    if user_id == Some(room.host_user_id) {
+        return Ok(true);
+    }
+
+    // A personal invite for this user, or a link invite (personal invites only work for their recipient)
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (
+            SELECT 1 FROM watch_party_invites
+            WHERE video_id = $1
+              AND expires_at > $2
+              AND ((invited_user_id IS NOT NULL AND invited_user_id = $3)
+                OR (token = $4 AND invited_user_id IS NULL))
+        )"
+    )
+    .bind(video_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(user_id)
+    .bind(invite_token)
+    .fetch_one(db_pool)
+    .await
+}
+
+// Someone in a watch party: a registered user, or a guest holding a guest token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Participant {
+    User(i32),
+    Guest(i32),
+}
+
+impl Participant {
+    // The id the participant goes by in room messages. Guests use their guest id negated, so they
+    // can't be mistaken for a user.
+    pub fn room_id(self) -> i32 {
+        match self {
+            Participant::User(user_id) => user_id,
+            Participant::Guest(guest_id) => -guest_id,
+        }
+    }
+
+    // The table tracking this kind of participant's sessions, its id column and the id
+    fn row(self) -> (&'static str, &'static str, i32) {
+        match self {
+            Participant::User(user_id) => ("watch_party_participants", "user_id", user_id),
+            Participant::Guest(guest_id) => ("watch_party_guest_participants", "guest_id", guest_id),
+        }
+    }
+}
+
+// Record a participant connecting to a video's watch party, starting a session if none is running.
+// Anonymous connections without a guest token aren't counted.
+pub async fn record_join(db_pool: &PgPool, video_id: i32, participant: Participant) -> Result<WatchPartySession, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut tx = db_pool.begin().await?;
+
+    // A guest opening a party leaves it without a host until a user claims the room
+    let host_user_id = match (get_room(db_pool, video_id).await?, participant) {
+        (Some(room), _) => Some(room.host_user_id),
+        (None, Participant::User(user_id)) => Some(user_id),
+        (None, Participant::Guest(_)) => None,
+    };
+    sqlx::query(
+        "INSERT INTO watch_party_sessions (video_id, host_user_id, started_at)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (video_id) WHERE ended_at IS NULL DO NOTHING"
+    )
+    .bind(video_id)
+    .bind(host_user_id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    let session = sqlx::query_as::<_, WatchPartySession>(
+        "SELECT * FROM watch_party_sessions WHERE video_id = $1 AND ended_at IS NULL FOR UPDATE"
+    )
+    .bind(video_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let (table, column, id) = participant.row();
+    sqlx::query(&format!(
+        "INSERT INTO {table} (session_id, {column}, connections, joined_at)
+         VALUES ($1, $2, 1, $3)
+         ON CONFLICT (session_id, {column}) DO UPDATE SET connections = {table}.connections + 1, left_at = NULL"
+    ))
+    .bind(session.id)
+    .bind(id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    let session = sqlx::query_as::<_, WatchPartySession>(
+        "UPDATE watch_party_sessions
+         SET peak_participants = GREATEST(peak_participants,
+             (SELECT COUNT(*) FROM watch_party_participants WHERE session_id = $1 AND connections > 0)::INTEGER
+             + (SELECT COUNT(*) FROM watch_party_guest_participants WHERE session_id = $1 AND connections > 0)::INTEGER)
+         WHERE id = $1
+         RETURNING *"
+    )
+    .bind(session.id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(session)
+}
+
+// Record a participant's connection closing, ending the session once nobody is left
+pub async fn record_leave(db_pool: &PgPool, video_id: i32, participant: Participant) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut tx = db_pool.begin().await?;
+
+    let session = sqlx::query_as::<_, WatchPartySession>(
+        "SELECT * FROM watch_party_sessions WHERE video_id = $1 AND ended_at IS NULL FOR UPDATE"
+    )
+    .bind(video_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    let Some(session) = session else {
+        return Ok(());
+    };
+
+    let (table, column, id) = participant.row();
+    let left = sqlx::query(&format!(
+        "UPDATE {table}
+         SET connections = GREATEST(connections - 1, 0),
+             left_at = CASE WHEN connections <= 1 THEN $3 ELSE left_at END
+         WHERE session_id = $1 AND {column} = $2"
+    ))
+    .bind(session.id)
+    .bind(id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    // A guest who claimed their identity mid-party had their connections moved to the account
+    if left.rows_affected() == 0 {
+        if let Participant::Guest(guest_id) = participant {
+            sqlx::query(
+                "UPDATE watch_party_participants
+                 SET connections = GREATEST(connections - 1, 0),
+                     left_at = CASE WHEN connections <= 1 THEN $3 ELSE left_at END
+                 WHERE session_id = $1 AND user_id = (SELECT claimed_by FROM watch_party_guests WHERE id = $2)"
+            )
+            .bind(session.id)
+            .bind(guest_id)
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+        }
+    }
+
+    let ended = sqlx::query(
+        "UPDATE watch_party_sessions SET ended_at = $2
+         WHERE id = $1
+           AND NOT EXISTS (SELECT 1 FROM watch_party_participants WHERE session_id = $1 AND connections > 0)
+           AND NOT EXISTS (SELECT 1 FROM watch_party_guest_participants WHERE session_id = $1 AND connections > 0)"
+    )
+    .bind(session.id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    if ended.rows_affected() > 0 {
+        info!("Watch party session {} for video {} ended", session.id, video_id);
+    }
+    Ok(())
+}
+
+// How often open sessions are checked for having nobody left connected
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// End open sessions nobody is connected to any more. Connection counts go stale when a replica
+// dies without recording its clients leaving, so liveness comes from Redis instead: every
+// watch party connection, on any replica, subscribes to its video's channel.
+pub async fn close_abandoned_sessions(db_pool: &PgPool, redis_client: &redis::Client) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let open: Vec<(i32, i32)> = sqlx::query_as("SELECT id, video_id FROM watch_party_sessions WHERE ended_at IS NULL")
+        .fetch_all(db_pool)
+        .await?;
+    if open.is_empty() {
+        return Ok(0);
+    }
+
+    let mut numsub = redis::cmd("PUBSUB");
+    numsub.arg("NUMSUB");
+    for (_, video_id) in &open {
+        numsub.arg(crate::redis_service::get_video_channel(*video_id));
+    }
+    // Replies with each channel followed by its count
+    let counts: Vec<(String, i64)> = circuit_breaker::redis(redis_client, |mut conn| async move {
+        numsub.query_async(&mut conn).await
+    })
+    .await?;
+    let abandoned: Vec<i32> = open.iter().zip(counts).filter(|(_, (_, count))| *count == 0).map(|((id, _), _)| *id).collect();
+    if abandoned.is_empty() {
+        return Ok(0);
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut tx = db_pool.begin().await?;
+    for table in ["watch_party_participants", "watch_party_guest_participants"] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET connections = 0, left_at = COALESCE(left_at, $2)
+             WHERE session_id = ANY($1) AND connections > 0"
+        ))
+        .bind(&abandoned)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    }
+    let ended = sqlx::query("UPDATE watch_party_sessions SET ended_at = $2 WHERE id = ANY($1) AND ended_at IS NULL")
+        .bind(&abandoned)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(ended.rows_affected())
+}
+
+// Close abandoned sessions at startup and then every few minutes. Every replica runs this; the
+// updates are idempotent.
+pub async fn run_session_sweeper(db_pool: PgPool, redis_client: redis::Client) {
+    loop {
+        match close_abandoned_sessions(&db_pool, &redis_client).await {
+            Ok(0) => {}
+            Ok(ended) => info!("Ended {} watch party sessions nobody was connected to", ended),
+            Err(e) => error!("Error closing abandoned watch party sessions: {:?}", e),
+        }
+        tokio::time::sleep(SESSION_SWEEP_INTERVAL).await;
+    }
+}
+
+// Guest tokens last a month; a guest past that starts over as a new one
+pub const GUEST_TOKEN_DAYS: i64 = 30;
+pub const MAX_DISPLAY_NAME_CHARS: usize = 32;
+
+// Anyone can ask for a guest identity, so each IP may ask WATCH_PARTY_GUESTS_PER_HOUR times an
+// hour, counted in Redis or, without it, per replica
+const DEFAULT_GUESTS_PER_HOUR: u64 = 10;
+
+pub fn guests_per_hour() -> u64 {
+    env::var("WATCH_PARTY_GUESTS_PER_HOUR").ok().and_then(|v| v.parse::<u64>().ok()).filter(|v| *v > 0).unwrap_or(DEFAULT_GUESTS_PER_HOUR)
+}
+
+fn guests_key(client: &str, hour: i64) -> String {
+    format!("watch_party_guests:{}:{}", client, hour)
+}
+
+// Stand-in for Redis: each client's count for the current hour
+static LOCAL_GUEST_COUNTS: LazyLock<Mutex<HashMap<String, (i64, u64)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn count_guest_locally(client: &str, hour: i64) -> u64 {
+    let mut counts = LOCAL_GUEST_COUNTS.lock().unwrap();
+    counts.retain(|_, (counted_hour, _)| *counted_hour == hour);
+    let (_, count) = counts.entry(client.to_string()).or_insert((hour, 0));
+    *count += 1;
+    *count
+}
+
+async fn count_guest_in_redis(redis_client: &redis::Client, client: &str, hour: i64) -> redis::RedisResult<u64> {
+    let (count,): (u64,) = circuit_breaker::redis(redis_client, |mut conn| async move {
+        redis::pipe()
+            .atomic()
+            .incr(guests_key(client, hour), 1)
+            .expire(guests_key(client, hour), 60 * 60)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+    })
+    .await?;
+    Ok(count)
+}
+
+// Count a request for a guest identity against the client's hour, refusing it once over the limit
+pub async fn check_guest_rate(redis_client: Option<&redis::Client>, client: &str, now: i64) -> Result<(), ApiError> {
+    let hour = now / 3600;
+    let count = match redis_client {
+        Some(redis_client) => match count_guest_in_redis(redis_client, client, hour).await {
+            Ok(count) => count,
+            Err(e) => {
+                error!("Guest rate limit unavailable in Redis, counting locally: {:?}", e);
+                count_guest_locally(client, hour)
+            }
+        },
+        None => count_guest_locally(client, hour),
+    };
+    if count > guests_per_hour() {
+        warn!("{} asked for more than {} watch party guest identities in an hour", client, guests_per_hour());
+        return Err(ApiError::TooManyRequests {
+            message: "Too many guest identities requested, try again later".to_string(),
+            retry_after_secs: (3600 - now.rem_euclid(3600)).max(1) as u64,
+        });
+    }
+    Ok(())
+}
+
+// The display name a guest asked for, trimmed, or why it can't be used
+pub fn validate_display_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+        return Err(format!("displayName must be 1 to {} characters", MAX_DISPLAY_NAME_CHARS));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("displayName can't contain control characters".to_string());
+    }
+    Ok(name.to_string())
+}
+
+pub async fn create_guest(db_pool: &PgPool, display_name: &str) -> Result<WatchPartyGuest, sqlx::Error> {
+    sqlx::query_as::<_, WatchPartyGuest>(
+        "INSERT INTO watch_party_guests (display_name, created_at) VALUES ($1, $2) RETURNING *"
+    )
+    .bind(display_name)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(db_pool)
+    .await
+}
+
+// A guest that can still join parties, i.e. one that hasn't been merged into an account
+pub async fn active_guest(db_pool: &PgPool, guest_id: i32) -> Result<Option<WatchPartyGuest>, sqlx::Error> {
+    sqlx::query_as::<_, WatchPartyGuest>("SELECT * FROM watch_party_guests WHERE id = $1 AND claimed_by IS NULL")
+        .bind(guest_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Merge a guest's watch party history into a user's account, returning how many sessions were
+// moved, or None if the guest is gone or was already claimed. Sessions both took part in keep the
+// earlier join. The guest's token stops working afterwards.
+pub async fn claim_guest(db_pool: &PgPool, guest_id: i32, user_id: i32) -> Result<Option<u64>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let claimed = sqlx::query(
+        "UPDATE watch_party_guests SET claimed_by = $2, claimed_at = $3 WHERE id = $1 AND claimed_by IS NULL"
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(&mut tx)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let merged = sqlx::query(
+        "INSERT INTO watch_party_participants (session_id, user_id, connections, joined_at, left_at)
+         SELECT session_id, $2, connections, joined_at, left_at FROM watch_party_guest_participants WHERE guest_id = $1
+         ON CONFLICT (session_id, user_id) DO UPDATE SET
+             connections = watch_party_participants.connections + EXCLUDED.connections,
+             joined_at = LEAST(watch_party_participants.joined_at, EXCLUDED.joined_at),
+             left_at = CASE WHEN watch_party_participants.connections + EXCLUDED.connections > 0 THEN NULL
+                            ELSE GREATEST(watch_party_participants.left_at, EXCLUDED.left_at) END"
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query("DELETE FROM watch_party_guest_participants WHERE guest_id = $1")
+        .bind(guest_id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+    info!("User {} claimed watch party guest {} ({} sessions)", user_id, guest_id, merged.rows_affected());
+    Ok(Some(merged.rows_affected()))
+}
+
+// Guests connected to the video's running watch party, so clients can put names to their ids
+pub async fn guests_in_room(db_pool: &PgPool, video_id: i32) -> Result<Vec<WatchPartyGuestPresence>, sqlx::Error> {
+    sqlx::query_as::<_, WatchPartyGuestPresence>(
+        "SELECT -g.id AS participant_id, g.display_name
+         FROM watch_party_guest_participants p
+         JOIN watch_party_sessions s ON s.id = p.session_id
+         JOIN watch_party_guests g ON g.id = p.guest_id
+         WHERE s.video_id = $1 AND s.ended_at IS NULL AND p.connections > 0
+         ORDER BY p.joined_at"
+    )
+    .bind(video_id)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn history_for_user(db_pool: &PgPool, user_id: i32, limit: i64) -> Result<Vec<WatchPartyHistoryEntry>, sqlx::Error> {
+    sqlx::query_as::<_, WatchPartyHistoryEntry>(
+        "SELECT s.id AS session_id, s.video_id, v.title AS video_title, s.host_user_id, s.started_at, s.ended_at,
+                s.peak_participants, p.joined_at,
+                (SELECT COUNT(*) FROM watch_party_participants WHERE session_id = s.id)
+                  + (SELECT COUNT(*) FROM watch_party_guest_participants WHERE session_id = s.id) AS participant_count,
+                COALESCE((SELECT json_agg(json_build_object(
+                    'question', wp.question, 'options', wp.options, 'counts', wp.counts,
+                    'total_votes', wp.total_votes, 'closed_at', wp.closed_at) ORDER BY wp.closed_at)
+                 FROM watch_party_polls wp WHERE wp.session_id = s.id), '[]'::json) AS polls
+         FROM watch_party_participants p
+         JOIN watch_party_sessions s ON s.id = p.session_id
+         JOIN videos v ON v.id = s.video_id
+         WHERE p.user_id = $1 AND v.deleted_at IS NULL
+         ORDER BY s.started_at DESC
+         LIMIT $2"
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn video_stats(db_pool: &PgPool, video_id: i32) -> Result<WatchPartyVideoStats, sqlx::Error> {
+    sqlx::query_as::<_, WatchPartyVideoStats>(
+        "SELECT $1 AS video_id,
+                COUNT(*) AS party_count,
+                (SELECT COUNT(DISTINCT p.user_id) FROM watch_party_participants p
+                 JOIN watch_party_sessions ps ON ps.id = p.session_id WHERE ps.video_id = $1)
+                  + (SELECT COUNT(DISTINCT p.guest_id) FROM watch_party_guest_participants p
+                 JOIN watch_party_sessions ps ON ps.id = p.session_id WHERE ps.video_id = $1) AS participant_count,
+                COALESCE(MAX(peak_participants), 0) AS peak_participants,
+                MAX(started_at) AS last_party_at
+         FROM watch_party_sessions
+         WHERE video_id = $1"
+    )
+    .bind(video_id)
+    .fetch_one(db_pool)
+    .await
+}
+
+// Parties without a known duration are put in calendars as an hour long
+const DEFAULT_EVENT_SECS: i64 = 60 * 60;
+
+// A scheduled party as a calendar event. Times are UTC; `url` is the party's page on the site.
+#[derive(Debug, Serialize)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub title: String,
+    pub description: String,
+    pub start: chrono::NaiveDateTime,
+    pub end: chrono::NaiveDateTime,
+    pub url: String,
+}
+
+pub fn calendar_event(room: &WatchParty, video: &Video, base_url: &str) -> Option<CalendarEvent> {
+    let start = room.scheduled_start?;
+    let duration = video.duration.filter(|d| *d > 0).map(i64::from).unwrap_or(DEFAULT_EVENT_SECS);
+    let host = base_url.split("://").nth(1).unwrap_or(base_url);
+    Some(CalendarEvent {
+        uid: format!("watchparty-{}@{}", room.video_id, host),
+        title: format!("Watch party: {}", video.title),
+        description: video.description.clone().unwrap_or_default(),
+        start,
+        end: start + chrono::Duration::seconds(duration),
+        url: format!("{}/video/{}", base_url, room.video_id),
+    })
+}
+
+// Backslashes, semicolons, commas and newlines must be escaped in iCalendar text values
+fn escape_ics(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+impl CalendarEvent {
+    // A single-event iCalendar file (RFC 5545) to add the party to a calendar
+    pub fn to_ics(&self, provider: &str) -> String {
+        let format = "%Y%m%dT%H%M%SZ";
+        let mut ics = [
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:-//{}//Watch Party//EN", escape_ics(provider)),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", self.uid),
+            format!("DTSTAMP:{}", chrono::Utc::now().format(format)),
+            format!("DTSTART:{}", self.start.format(format)),
+            format!("DTEND:{}", self.end.format(format)),
+            format!("SUMMARY:{}", escape_ics(&self.title)),
+            format!("DESCRIPTION:{}", escape_ics(&self.description)),
+            format!("URL:{}", self.url),
+            "END:VEVENT".to_string(),
+            "END:VCALENDAR".to_string(),
+        ]
+        .join("\r\n");
+        ics.push_str("\r\n");
+        ics
+    }
+}
+
+// Public STUN server used for voice chat when STUN_URLS is not set
+const DEFAULT_STUN_URL: &str = "stun:stun.l.google.com:19302";
+
+// Lifetime of TURN credentials minted from TURN_SHARED_SECRET
+const TURN_CREDENTIAL_TTL_SECS: i64 = 12 * 60 * 60;
+
+fn url_list(var: &str) -> Vec<String> {
+    env::var(var)
+        .map(|v| v.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// ICE servers for WebRTC voice chat. TURN credentials are either static (TURN_USERNAME /
+// TURN_PASSWORD) or short-lived ones derived from TURN_SHARED_SECRET using the coturn REST API
+// scheme, so the shared secret itself never reaches the browser.
+pub fn rtc_config(user_id: i32) -> serde_json::Value {
+    let mut stun_urls = url_list("STUN_URLS");
+    if stun_urls.is_empty() {
+        stun_urls.push(DEFAULT_STUN_URL.to_string());
+    }
+    let mut ice_servers = vec![json!({ "urls": stun_urls })];
+
+    let turn_urls = url_list("TURN_URLS");
+    if !turn_urls.is_empty() {
+        if let Ok(secret) = env::var("TURN_SHARED_SECRET") {
+            let expires_at = chrono::Utc::now().timestamp() + TURN_CREDENTIAL_TTL_SECS;
+            let username = format!("{}:{}", expires_at, user_id);
+            let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+            mac.update(username.as_bytes());
+            let credential = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+            ice_servers.push(json!({ "urls": turn_urls, "username": username, "credential": credential }));
+        } else if let (Ok(username), Ok(credential)) = (env::var("TURN_USERNAME"), env::var("TURN_PASSWORD")) {
+            ice_servers.push(json!({ "urls": turn_urls, "username": username, "credential": credential }));
+        }
+    }
+
+    json!({ "iceServers": ice_servers })
+}
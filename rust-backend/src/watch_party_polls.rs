@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use chrono::NaiveDateTime;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::circuit_breaker;
+
+// Open polls live in Redis so every replica tallies the same votes: the poll as JSON under
+// watchparty:poll:{id} and a hash of user id to chosen option under watchparty:poll:{id}:votes.
+// Without Redis they are kept in memory, which is only right for a single instance.
+
+pub const MAX_OPTIONS: usize = 10;
+const MAX_QUESTION_CHARS: usize = 200;
+const MAX_OPTION_CHARS: usize = 100;
+
+// Polls nobody closes are dropped after a day
+const POLL_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Poll {
+    pub id: String,
+    pub video_id: i32,
+    pub created_by: i32,
+    pub question: String,
+    pub options: Vec<String>,
+    pub persist: bool, // save the results to the session history when the poll closes
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PollResults {
+    pub poll_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    pub counts: Vec<i64>, // votes per option, in the order of `options`
+    pub total_votes: i64,
+    pub closed: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PollError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("Poll not found or already closed")]
+    NotFound,
+
+    #[error("Only the host can do that")]
+    NotHost,
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+static MEMORY_POLLS: LazyLock<Mutex<HashMap<String, (Poll, HashMap<i32, usize>)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn poll_key(poll_id: &str) -> String {
+    format!("watchparty:poll:{}", poll_id)
+}
+
+fn votes_key(poll_id: &str) -> String {
+    format!("watchparty:poll:{}:votes", poll_id)
+}
+
+// A trimmed question and 2 to MAX_OPTIONS distinct, non-empty options
+pub fn new_poll(video_id: i32, created_by: i32, question: &str, options: &[String], persist: bool) -> Result<Poll, PollError> {
+    let question = question.trim();
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(PollError::Invalid(format!("A poll needs a question of at most {} characters", MAX_QUESTION_CHARS)));
+    }
+    let options: Vec<String> = options.iter().map(|o| o.trim().to_string()).collect();
+    if options.len() < 2 || options.len() > MAX_OPTIONS {
+        return Err(PollError::Invalid(format!("A poll needs between 2 and {} options", MAX_OPTIONS)));
+    }
+    if options.iter().any(|o| o.is_empty() || o.chars().count() > MAX_OPTION_CHARS) {
+        return Err(PollError::Invalid(format!("Poll options must be 1 to {} characters", MAX_OPTION_CHARS)));
+    }
+    if options.iter().enumerate().any(|(i, o)| options[..i].contains(o)) {
+        return Err(PollError::Invalid("Poll options must be different".to_string()));
+    }
+
+    Ok(Poll {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        video_id,
+        created_by,
+        question: question.to_string(),
+        options,
+        persist,
+        created_at: chrono::Utc::now().naive_utc(),
+    })
+}
+
+fn tally(poll: &Poll, votes: impl IntoIterator<Item = usize>, closed: bool) -> PollResults {
+    let mut counts = vec![0; poll.options.len()];
+    for option in votes {
+        if let Some(count) = counts.get_mut(option) {
+            *count += 1;
+        }
+    }
+    PollResults {
+        poll_id: poll.id.clone(),
+        question: poll.question.clone(),
+        options: poll.options.clone(),
+        total_votes: counts.iter().sum(),
+        counts,
+        closed,
+    }
+}
+
+async fn load_poll(conn: &mut redis::aio::Connection, poll_id: &str) -> Result<Poll, PollError> {
+    let json: Option<String> = redis::cmd("GET").arg(poll_key(poll_id)).query_async(conn).await?;
+    json.and_then(|json| serde_json::from_str(&json).ok()).ok_or(PollError::NotFound)
+}
+
+async fn load_votes(conn: &mut redis::aio::Connection, poll_id: &str) -> Result<Vec<usize>, PollError> {
+    let votes: HashMap<i32, usize> = redis::cmd("HGETALL").arg(votes_key(poll_id)).query_async(conn).await?;
+    Ok(votes.into_values().collect())
+}
+
+pub async fn open(redis_client: Option<&redis::Client>, poll: &Poll) -> Result<(), PollError> {
+    info!("User {} opened poll {} in the watch party for video {}", poll.created_by, poll.id, poll.video_id);
+    let Some(redis_client) = redis_client else {
+        let mut polls = MEMORY_POLLS.lock().unwrap();
+        let expired_before = poll.created_at - chrono::Duration::seconds(POLL_TTL_SECS as i64);
+        polls.retain(|_, (open, _)| open.created_at > expired_before);
+        polls.insert(poll.id.clone(), (poll.clone(), HashMap::new()));
+        return Ok(());
+    };
+    let mut conn = circuit_breaker::redis_connection(redis_client).await?;
+    let json = serde_json::to_string(poll).unwrap_or_default();
+    redis::cmd("SET").arg(poll_key(&poll.id)).arg(json).arg("EX").arg(POLL_TTL_SECS).query_async::<_, ()>(&mut conn).await?;
+    Ok(())
+}
+
+// Record the user's vote, replacing any earlier one, and return the running tally
+pub async fn vote(
+    redis_client: Option<&redis::Client>,
+    video_id: i32,
+    poll_id: &str,
+    user_id: i32,
+    option: usize,
+) -> Result<PollResults, PollError> {
+    let Some(redis_client) = redis_client else {
+        let mut polls = MEMORY_POLLS.lock().unwrap();
+        let (poll, votes) = polls.get_mut(poll_id).filter(|(poll, _)| poll.video_id == video_id).ok_or(PollError::NotFound)?;
+        if option >= poll.options.len() {
+            return Err(PollError::Invalid("No such poll option".to_string()));
+        }
+        votes.insert(user_id, option);
+        return Ok(tally(poll, votes.values().copied(), false));
+    };
+
+    let mut conn = circuit_breaker::redis_connection(redis_client).await?;
+    let poll = load_poll(&mut conn, poll_id).await?;
+    if poll.video_id != video_id {
+        return Err(PollError::NotFound);
+    }
+    if option >= poll.options.len() {
+        return Err(PollError::Invalid("No such poll option".to_string()));
+    }
+    redis::pipe()
+        .cmd("HSET").arg(votes_key(poll_id)).arg(user_id).arg(option).ignore()
+        .cmd("EXPIRE").arg(votes_key(poll_id)).arg(POLL_TTL_SECS).ignore()
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    let votes = load_votes(&mut conn, poll_id).await?;
+    Ok(tally(&poll, votes, false))
+}
+
+// Close the poll and return its final results. Only its creator may close it, and only once.
+pub async fn close(
+    redis_client: Option<&redis::Client>,
+    video_id: i32,
+    poll_id: &str,
+    user_id: i32,
+) -> Result<(Poll, PollResults), PollError> {
+    let Some(redis_client) = redis_client else {
+        let mut polls = MEMORY_POLLS.lock().unwrap();
+        let (poll, _) = polls.get(poll_id).filter(|(poll, _)| poll.video_id == video_id).ok_or(PollError::NotFound)?;
+        if poll.created_by != user_id {
+            return Err(PollError::NotHost);
+        }
+        let (poll, votes) = polls.remove(poll_id).ok_or(PollError::NotFound)?;
+        let results = tally(&poll, votes.into_values(), true);
+        return Ok((poll, results));
+    };
+
+    let mut conn = circuit_breaker::redis_connection(redis_client).await?;
+    let poll = load_poll(&mut conn, poll_id).await?;
+    if poll.video_id != video_id {
+        return Err(PollError::NotFound);
+    }
+    if poll.created_by != user_id {
+        return Err(PollError::NotHost);
+    }
+    // Whoever deletes the poll closes it, should two replicas race
+    let deleted: i64 = redis::cmd("DEL").arg(poll_key(poll_id)).query_async(&mut conn).await?;
+    if deleted == 0 {
+        return Err(PollError::NotFound);
+    }
+    let votes = load_votes(&mut conn, poll_id).await?;
+    redis::cmd("DEL").arg(votes_key(poll_id)).query_async::<_, ()>(&mut conn).await?;
+    let results = tally(&poll, votes, true);
+    Ok((poll, results))
+}
+
+// Save a closed poll's results with the watch party session running now, if there is one
+pub async fn save_results(db_pool: &PgPool, poll: &Poll, results: &PollResults) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO watch_party_polls (session_id, video_id, question, options, counts, total_votes, created_by, created_at, closed_at)
+         SELECT id, $1, $2, $3, $4, $5, $6, $7, $8 FROM watch_party_sessions WHERE video_id = $1 AND ended_at IS NULL"
+    )
+    .bind(poll.video_id)
+    .bind(&poll.question)
+    .bind(serde_json::json!(poll.options))
+    .bind(serde_json::json!(results.counts))
+    .bind(results.total_votes as i32)
+    .bind(poll.created_by)
+    .bind(poll.created_at)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use sqlx::PgPool;
+
+use crate::models::{Video, WatermarkPosition, WatermarkSettings};
+use crate::user_settings;
+
+// Uploaders can have a PNG watermark burned into their videos' renditions. The image is stored
+// per uploader; whether it is used, where and how opaque is part of their settings. Renditions
+// already transcoded keep what they had until the video is reprocessed.
+
+// Largest watermark image accepted, and largest width or height
+pub const MAX_WATERMARK_BYTES: usize = 256 * 1024;
+pub const MAX_WATERMARK_DIMENSION: u32 = 2048;
+
+pub const MIN_OPACITY: f64 = 0.1;
+
+// Watermarks are scaled to this fraction of the rendition height and kept this fraction of it
+// away from the edges, so they look the same at every resolution
+const HEIGHT_FRACTION: f64 = 0.1;
+const MARGIN_FRACTION: f64 = 0.03;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Width and height from the PNG's IHDR chunk, which has to come first
+pub fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || bytes[..8] != PNG_SIGNATURE || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+    Some((width, height))
+}
+
+pub fn validate_image(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() > MAX_WATERMARK_BYTES {
+        return Err(format!("Watermarks must be at most {} KiB", MAX_WATERMARK_BYTES / 1024));
+    }
+    match png_dimensions(bytes) {
+        None => Err("Watermarks must be PNG images".to_string()),
+        Some((width, height)) if width == 0 || height == 0 || width > MAX_WATERMARK_DIMENSION || height > MAX_WATERMARK_DIMENSION => {
+            Err(format!("Watermarks must be at most {0}x{0} pixels", MAX_WATERMARK_DIMENSION))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+pub fn validate_settings(settings: &WatermarkSettings) -> Result<(), String> {
+    if !(MIN_OPACITY..=1.0).contains(&settings.opacity) {
+        return Err(format!("Watermark opacity must be between {} and 1", MIN_OPACITY));
+    }
+    Ok(())
+}
+
+// A new key per upload, so renditions being transcoded keep the image they started with
+pub fn watermark_s3_key(user_id: i32) -> String {
+    format!("watermarks/{}/{}.png", user_id, uuid::Uuid::new_v4())
+}
+
+// ffmpeg filter graph scaling the video (input 0) to `height` and overlaying the watermark
+// (input 1), labelled [out]
+pub fn overlay_filter(height: i32, settings: &WatermarkSettings) -> String {
+    let watermark_height = ((height as f64 * HEIGHT_FRACTION).round() as i32).max(1);
+    let margin = (height as f64 * MARGIN_FRACTION).round() as i32;
+    let (x, y) = match settings.position {
+        WatermarkPosition::TopLeft => (format!("{}", margin), format!("{}", margin)),
+        WatermarkPosition::TopRight => (format!("W-w-{}", margin), format!("{}", margin)),
+        WatermarkPosition::BottomLeft => (format!("{}", margin), format!("H-h-{}", margin)),
+        WatermarkPosition::BottomRight => (format!("W-w-{}", margin), format!("H-h-{}", margin)),
+    };
+    format!(
+        "[0:v]scale=-2:{height}[video];[1:v]scale=-1:{watermark_height},format=rgba,colorchannelmixer=aa={opacity:.2}[watermark];[video][watermark]overlay={x}:{y}[out]",
+        height = height,
+        watermark_height = watermark_height,
+        opacity = settings.opacity,
+        x = x,
+        y = y,
+    )
+}
+
+pub async fn watermark_key(db_pool: &PgPool, user_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT s3_key FROM channel_watermarks WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// Record the user's new watermark image, returning the key of the one it replaced
+pub async fn set_watermark(db_pool: &PgPool, user_id: i32, s3_key: &str) -> Result<Option<String>, sqlx::Error> {
+    let previous = watermark_key(db_pool, user_id).await?;
+    sqlx::query(
+        "INSERT INTO channel_watermarks (user_id, s3_key, updated_at) VALUES ($1, $2, NOW())
+         ON CONFLICT (user_id) DO UPDATE SET s3_key = EXCLUDED.s3_key, updated_at = EXCLUDED.updated_at"
+    )
+    .bind(user_id)
+    .bind(s3_key)
+    .execute(db_pool)
+    .await?;
+    Ok(previous.filter(|key| key != s3_key))
+}
+
+// Returns the key of the removed image, if there was one
+pub async fn remove_watermark(db_pool: &PgPool, user_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("DELETE FROM channel_watermarks WHERE user_id = $1 RETURNING s3_key")
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+// The watermark image key and settings to transcode the video's renditions with, if its
+// uploader has a watermark and has it enabled
+pub async fn for_video(db_pool: &PgPool, video: &Video) -> Result<Option<(String, WatermarkSettings)>, sqlx::Error> {
+    let Some(uploader) = video.uploaded_by else {
+        return Ok(None);
+    };
+    let row: Option<(String, serde_json::Value)> = sqlx::query_as(
+        "SELECT w.s3_key, u.settings FROM channel_watermarks w JOIN users u ON u.id = w.user_id WHERE w.user_id = $1"
+    )
+    .bind(uploader)
+    .fetch_optional(db_pool)
+    .await?;
+    Ok(row.and_then(|(s3_key, settings)| {
+        let settings = user_settings::from_stored(Some(settings)).watermark;
+        settings.enabled.then_some((s3_key, settings))
+    }))
+}
@@ -0,0 +1,458 @@
+use std::env;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::{error, info, warn};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::derive::Deriver;
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::symm::{encrypt_aead, Cipher};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::time::sleep;
+
+use crate::models::{Notification, PushSubscription, PushSubscriptionRequest};
+
+// Notifications also delivered through Web Push, for browsers that aren't on the site. Browsers
+// subscribe with the VAPID public key and register their push service endpoint here; a
+// dispatcher sends each new notification of a subscribed user to every endpoint they registered,
+// encrypted for the browser (RFC 8291) and signed with the VAPID key (RFC 8292).
+//
+// VAPID_PUBLIC_KEY and VAPID_PRIVATE_KEY are base64url, as `web-push generate-vapid-keys`
+// prints them. VAPID_SUBJECT is a mailto: or https: contact for push services.
+
+// Push services keep an undelivered message this long; older notifications aren't sent at all
+const PUSH_TTL_SECS: i64 = 24 * 60 * 60;
+
+// VAPID tokens may be valid for at most a day
+const VAPID_TOKEN_SECS: i64 = 12 * 60 * 60;
+
+const DEFAULT_SUBJECT: &str = "mailto:admin@localhost";
+const MAX_ENDPOINT_CHARS: usize = 2048;
+const MAX_BODY_CHARS: usize = 200;
+
+const DISPATCH_BATCH_SIZE: i64 = 50;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The whole payload is one record of at most this size
+const RECORD_SIZE: u32 = 4096;
+const SALT_BYTES: usize = 16;
+const AUTH_SECRET_BYTES: usize = 16;
+const PUBLIC_KEY_BYTES: usize = 65;
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Browsers send keys unpadded, but some libraries pad them
+fn decode_base64url(value: &str) -> Option<Vec<u8>> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value.trim().trim_end_matches('=')).ok()
+}
+
+fn p256() -> Result<EcGroup, ErrorStack> {
+    EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+// The application server's key pair browsers subscribe with
+pub struct Vapid {
+    public_key: String,
+    signing: EncodingKey,
+    subject: String,
+}
+
+#[derive(Serialize)]
+struct VapidClaims<'a> {
+    aud: &'a str,
+    exp: i64,
+    sub: &'a str,
+}
+
+impl Vapid {
+    pub fn new(public_key: &str, private_key: &str, subject: &str) -> Result<Vapid, String> {
+        if !subject.starts_with("mailto:") && !subject.starts_with("https://") {
+            return Err("VAPID_SUBJECT must be a mailto: or https: URL".to_string());
+        }
+        let public = decode_base64url(public_key).ok_or("VAPID_PUBLIC_KEY is not base64url")?;
+        let private = decode_base64url(private_key).ok_or("VAPID_PRIVATE_KEY is not base64url")?;
+
+        let group = p256().map_err(|e| e.to_string())?;
+        let mut ctx = BigNumContext::new().map_err(|e| e.to_string())?;
+        let point = EcPoint::from_bytes(&group, &public, &mut ctx).map_err(|_| "VAPID_PUBLIC_KEY is not a P-256 public key")?;
+        let scalar = BigNum::from_slice(&private).map_err(|e| e.to_string())?;
+        let key = EcKey::from_private_components(&group, &scalar, &point).map_err(|e| e.to_string())?;
+        key.check_key().map_err(|_| "VAPID_PRIVATE_KEY is not the private key of VAPID_PUBLIC_KEY")?;
+
+        let pem = PKey::from_ec_key(key).and_then(|k| k.private_key_to_pem_pkcs8()).map_err(|e| e.to_string())?;
+        let signing = EncodingKey::from_ec_pem(&pem).map_err(|e| e.to_string())?;
+        Ok(Vapid { public_key: base64url(&public), signing, subject: subject.to_string() })
+    }
+
+    // None when Web Push isn't configured
+    pub fn from_env() -> Option<Result<Vapid, String>> {
+        let public_key = env::var("VAPID_PUBLIC_KEY").ok().filter(|k| !k.trim().is_empty())?;
+        let private_key = env::var("VAPID_PRIVATE_KEY").ok().filter(|k| !k.trim().is_empty())?;
+        let subject = env::var("VAPID_SUBJECT").unwrap_or_else(|_| DEFAULT_SUBJECT.to_string());
+        Some(Vapid::new(&public_key, &private_key, subject.trim()))
+    }
+
+    // The applicationServerKey browsers subscribe with
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    // Authorization header for a request to `endpoint`, valid for VAPID_TOKEN_SECS from `now`
+    pub fn authorization(&self, endpoint: &str, now: i64) -> Result<String, String> {
+        let aud = audience(endpoint).ok_or_else(|| format!("Invalid push endpoint {}", endpoint))?;
+        let claims = VapidClaims { aud: &aud, exp: now + VAPID_TOKEN_SECS, sub: &self.subject };
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::ES256), &claims, &self.signing).map_err(|e| e.to_string())?;
+        Ok(format!("vapid t={}, k={}", token, self.public_key))
+    }
+}
+
+static VAPID: LazyLock<Option<Vapid>> = LazyLock::new(|| match Vapid::from_env()? {
+    Ok(vapid) => Some(vapid),
+    Err(e) => {
+        error!("Web Push is disabled: {}", e);
+        None
+    }
+});
+
+// The configured VAPID keys, or None while Web Push is off
+pub fn vapid() -> Option<&'static Vapid> {
+    VAPID.as_ref()
+}
+
+// The origin of a push service endpoint, which VAPID tokens are issued for
+pub fn audience(endpoint: &str) -> Option<String> {
+    let url = reqwest::Url::parse(endpoint).ok()?;
+    (url.scheme() == "https" && url.host().is_some()).then(|| url.origin().ascii_serialization())
+}
+
+// Whether `ip` is on the public internet. Endpoints are whatever users register, so pushes are
+// never sent to loopback, private, link-local or other internal addresses.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            let shared = a == 100 && (64..128).contains(&b); // carrier-grade NAT, 100.64.0.0/10
+            !(a == 0 || ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_broadcast()
+                || ip.is_multicast() || ip.is_documentation() || shared)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            let unique_local = first & 0xfe00 == 0xfc00;
+            let link_local = first & 0xffc0 == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local)
+        }
+    }
+}
+
+// The endpoint's host, as an address when it is one
+fn endpoint_host(url: &reqwest::Url) -> Option<Result<IpAddr, String>> {
+    let host = url.host_str()?;
+    let unbracketed = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    Some(unbracketed.parse::<IpAddr>().map_err(|_| host.trim_end_matches('.').to_lowercase()))
+}
+
+// Refuse endpoints that name an internal host outright; see resolve_endpoint for other names
+fn names_public_host(endpoint: &str) -> bool {
+    match reqwest::Url::parse(endpoint).ok().as_ref().and_then(endpoint_host) {
+        Some(Ok(ip)) => is_public_ip(ip),
+        Some(Err(domain)) => domain != "localhost" && !domain.ends_with(".localhost"),
+        None => false,
+    }
+}
+
+// Resolve the endpoint's host right before pushing to it, refusing it unless every address is
+// public, since a name can point anywhere and be changed after the subscription was checked
+pub async fn resolve_endpoint(endpoint: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(endpoint).map_err(|e| e.to_string())?;
+    let addresses: Vec<IpAddr> = match endpoint_host(&url) {
+        Some(Ok(ip)) => vec![ip],
+        Some(Err(domain)) => {
+            let port = url.port_or_known_default().unwrap_or(443);
+            tokio::net::lookup_host((domain.as_str(), port))
+                .await
+                .map_err(|e| format!("Failed to resolve {}: {}", domain, e))?
+                .map(|addr| addr.ip())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    if addresses.is_empty() || !addresses.iter().all(|ip| is_public_ip(*ip)) {
+        return Err(format!("{} doesn't resolve to a public address", url.host_str().unwrap_or_default()));
+    }
+    Ok(())
+}
+
+pub fn validate_subscription(request: &PushSubscriptionRequest) -> Result<(), String> {
+    if request.endpoint.len() > MAX_ENDPOINT_CHARS || audience(&request.endpoint).is_none() || !names_public_host(&request.endpoint) {
+        return Err("endpoint must be an https URL of a push service".to_string());
+    }
+    let p256dh = decode_base64url(&request.keys.p256dh).filter(|key| key.len() == PUBLIC_KEY_BYTES);
+    let is_point = p256dh.is_some_and(|key| {
+        let (Ok(group), Ok(mut ctx)) = (p256(), BigNumContext::new()) else {
+            return false;
+        };
+        EcPoint::from_bytes(&group, &key, &mut ctx).is_ok()
+    });
+    if !is_point {
+        return Err("keys.p256dh must be a base64url P-256 public key".to_string());
+    }
+    if decode_base64url(&request.keys.auth).is_none_or(|auth| auth.len() != AUTH_SECRET_BYTES) {
+        return Err("keys.auth must be a base64url 16 byte secret".to_string());
+    }
+    Ok(())
+}
+
+// The aes128gcm content coding of RFC 8188 with the Web Push key derivation of RFC 8291, for the
+// browser's public key `ua_public` and auth secret, from the one-off `sender` key and `salt`
+pub fn encrypt_with(payload: &[u8], ua_public: &[u8], auth_secret: &[u8], sender: &EcKey<Private>, salt: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+    let group = p256()?;
+    let mut ctx = BigNumContext::new()?;
+    let ua_point = EcPoint::from_bytes(&group, ua_public, &mut ctx)?;
+    let ua_key = PKey::from_ec_key(EcKey::from_public_key(&group, &ua_point)?)?;
+    let sender_public = sender.public_key().to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)?;
+    let sender_key = PKey::from_ec_key(sender.clone())?;
+    let mut deriver = Deriver::new(&sender_key)?;
+    deriver.set_peer(&ua_key)?;
+    let shared_secret = deriver.derive_to_vec()?;
+
+    let prk_key = hmac_sha256(auth_secret, &[&shared_secret]);
+    let ikm = hmac_sha256(&prk_key, &[b"WebPush: info\0", ua_public, &sender_public, &[1]]);
+    let prk = hmac_sha256(salt, &[&ikm]);
+    let cek = hmac_sha256(&prk, &[b"Content-Encoding: aes128gcm\0", &[1]]);
+    let nonce = hmac_sha256(&prk, &[b"Content-Encoding: nonce\0", &[1]]);
+
+    // A single, and so last, record: the payload and its 0x02 delimiter, unpadded
+    let mut plaintext = payload.to_vec();
+    plaintext.push(2);
+    let mut tag = [0u8; 16];
+    let ciphertext = encrypt_aead(Cipher::aes_128_gcm(), &cek[..16], Some(&nonce[..12]), &[], &plaintext, &mut tag)?;
+
+    let mut body = Vec::with_capacity(SALT_BYTES + 5 + sender_public.len() + ciphertext.len() + tag.len());
+    body.extend_from_slice(salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(sender_public.len() as u8);
+    body.extend_from_slice(&sender_public);
+    body.extend_from_slice(&ciphertext);
+    body.extend_from_slice(&tag);
+    Ok(body)
+}
+
+// Encrypt `payload` for a subscription with a fresh key and salt
+pub fn encrypt(payload: &[u8], p256dh: &str, auth: &str) -> Result<Vec<u8>, String> {
+    let ua_public = decode_base64url(p256dh).ok_or("Invalid p256dh key")?;
+    let auth_secret = decode_base64url(auth).ok_or("Invalid auth secret")?;
+    let sender = p256().and_then(|group| EcKey::generate(&group)).map_err(|e| e.to_string())?;
+    let mut salt = [0u8; SALT_BYTES];
+    openssl::rand::rand_bytes(&mut salt).map_err(|e| e.to_string())?;
+    encrypt_with(payload, &ua_public, &auth_secret, &sender, &salt).map_err(|e| e.to_string())
+}
+
+// What the service worker shows for a notification
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushMessage {
+    pub notification_id: i32,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub url: Option<String>,
+}
+
+pub fn message_for(notification: &Notification) -> PushMessage {
+    let payload = &notification.payload;
+    let text = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let (title, body, url) = match notification.kind.as_str() {
+        "watch_party_invite" => (
+            "Watch party invite".to_string(),
+            format!("You're invited to watch {}", text("videoTitle")),
+            Some(text("inviteUrl")).filter(|url| !url.is_empty()),
+        ),
+        "saved_search_match" => {
+            let video_ids: Vec<i64> = payload
+                .get("videoIds")
+                .and_then(|v| v.as_array())
+                .map(|ids| ids.iter().filter_map(|id| id.as_i64()).collect())
+                .unwrap_or_default();
+            let body = match video_ids.len() {
+                1 => "A new video matches your saved search".to_string(),
+                n => format!("{} new videos match your saved search", n),
+            };
+            let url = match video_ids.as_slice() {
+                [id] => format!("/video/{}", id),
+                _ => "/home".to_string(),
+            };
+            (format!("New videos for \"{}\"", text("name")), body, Some(url))
+        }
+        kind => ("New notification".to_string(), kind.replace('_', " "), None),
+    };
+    PushMessage {
+        notification_id: notification.id,
+        kind: notification.kind.clone(),
+        title,
+        body: body.chars().take(MAX_BODY_CHARS).collect(),
+        url,
+    }
+}
+
+// Register a browser for the user. Endpoints are unique to a browser profile, so one registered
+// again, e.g. after someone else signs in on it, moves to the new user.
+pub async fn subscribe(
+    db_pool: &PgPool,
+    user_id: i32,
+    request: &PushSubscriptionRequest,
+    user_agent: Option<&str>,
+) -> Result<PushSubscription, sqlx::Error> {
+    let subscription = sqlx::query_as::<_, PushSubscription>(
+        "INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth, user_agent) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (endpoint) DO UPDATE
+         SET user_id = EXCLUDED.user_id, p256dh = EXCLUDED.p256dh, auth = EXCLUDED.auth, user_agent = EXCLUDED.user_agent
+         RETURNING *"
+    )
+    .bind(user_id)
+    .bind(&request.endpoint)
+    .bind(request.keys.p256dh.trim())
+    .bind(request.keys.auth.trim())
+    .bind(user_agent)
+    .fetch_one(db_pool)
+    .await?;
+
+    info!("Registered push subscription {} for user {}", subscription.id, user_id);
+    Ok(subscription)
+}
+
+pub async fn unsubscribe(db_pool: &PgPool, user_id: i32, endpoint: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM push_subscriptions WHERE user_id = $1 AND endpoint = $2")
+        .bind(user_id)
+        .bind(endpoint)
+        .execute(db_pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_subscriptions(db_pool: &PgPool, user_id: i32) -> Result<Vec<PushSubscription>, sqlx::Error> {
+    sqlx::query_as::<_, PushSubscription>("SELECT * FROM push_subscriptions WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+enum SendOutcome {
+    Sent,
+    Gone, // the browser unsubscribed or the subscription expired
+    Failed(String),
+}
+
+async fn send(client: &reqwest::Client, vapid: &Vapid, subscription: &PushSubscription, message: &[u8]) -> SendOutcome {
+    let body = match encrypt(message, &subscription.p256dh, &subscription.auth) {
+        Ok(body) => body,
+        Err(e) => return SendOutcome::Failed(format!("Failed to encrypt: {}", e)),
+    };
+    if let Err(e) = resolve_endpoint(&subscription.endpoint).await {
+        return SendOutcome::Failed(e);
+    }
+    let authorization = match vapid.authorization(&subscription.endpoint, chrono::Utc::now().timestamp()) {
+        Ok(authorization) => authorization,
+        Err(e) => return SendOutcome::Failed(e),
+    };
+    let result = client
+        .post(&subscription.endpoint)
+        .header("Authorization", authorization)
+        .header("Content-Encoding", "aes128gcm")
+        .header("Content-Type", "application/octet-stream")
+        .header("TTL", PUSH_TTL_SECS.to_string())
+        .body(body)
+        .send()
+        .await;
+    match result {
+        Ok(response) if response.status().is_success() => SendOutcome::Sent,
+        Ok(response) if matches!(response.status().as_u16(), 404 | 410) => SendOutcome::Gone,
+        Ok(response) => SendOutcome::Failed(format!("HTTP {}", response.status())),
+        Err(e) => SendOutcome::Failed(e.to_string()),
+    }
+}
+
+// Send one batch of pending notifications and return how many were taken. Each is claimed before
+// sending, so processes sharing the database don't push one twice; a failed push isn't retried,
+// the notification is still delivered in the app.
+pub async fn deliver_pending(db_pool: &PgPool, client: &reqwest::Client, vapid: &Vapid) -> Result<usize, sqlx::Error> {
+    let notifications = sqlx::query_as::<_, Notification>(
+        "UPDATE notifications SET push_pending = FALSE
+         WHERE id IN (SELECT id FROM notifications WHERE push_pending ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED)
+         RETURNING *"
+    )
+    .bind(DISPATCH_BATCH_SIZE)
+    .fetch_all(db_pool)
+    .await?;
+
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(PUSH_TTL_SECS);
+    for notification in notifications.iter().filter(|n| n.created_at > cutoff) {
+        let message = serde_json::to_vec(&message_for(notification)).unwrap_or_default();
+        for subscription in list_subscriptions(db_pool, notification.user_id).await? {
+            match send(client, vapid, &subscription, &message).await {
+                SendOutcome::Sent => {}
+                SendOutcome::Gone => {
+                    info!("Removing expired push subscription {} of user {}", subscription.id, subscription.user_id);
+                    sqlx::query("DELETE FROM push_subscriptions WHERE id = $1")
+                        .bind(subscription.id)
+                        .execute(db_pool)
+                        .await?;
+                }
+                SendOutcome::Failed(e) => warn!(
+                    "Failed to push notification {} to subscription {}: {}",
+                    notification.id, subscription.id, e
+                ),
+            }
+        }
+    }
+
+    Ok(notifications.len())
+}
+
+pub async fn run_push_dispatcher(db_pool: PgPool) {
+    let Some(vapid) = vapid() else {
+        return;
+    };
+    let interval_secs = env::var("PUSH_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5);
+
+    info!("Starting Web Push dispatcher (interval: {} seconds)", interval_secs);
+
+    // Push services answer directly; following a redirect would get around resolve_endpoint
+    let client = reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build Web Push HTTP client");
+
+    loop {
+        match deliver_pending(&db_pool, &client, vapid).await {
+            // A full batch means more may be waiting, so go again straight away
+            Ok(n) if n as i64 >= DISPATCH_BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(e) => error!("Error sending push notifications: {:?}", e),
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
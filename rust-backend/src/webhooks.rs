@@ -0,0 +1,293 @@
+use std::env;
+use std::time::Duration;
+use sqlx::PgPool;
+use log::{info, warn, error};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde_json::json;
+use tokio::time::sleep;
+
+use videostreaming_models::webhook_events;
+
+use crate::models::{Video, Webhook, WebhookDelivery};
+
+pub const EVENT_VIDEO_CREATED: &str = webhook_events::EVENT_VIDEO_CREATED;
+pub const EVENT_VIDEO_PROCESSED: &str = "video.processed";
+pub const EVENT_COMMENT_CREATED: &str = "comment.created";
+pub const EVENT_JOB_FAILED: &str = "job.failed";
+
+pub const WEBHOOK_EVENTS: [&str; 4] = [
+    EVENT_VIDEO_CREATED,
+    EVENT_VIDEO_PROCESSED,
+    EVENT_COMMENT_CREATED,
+    EVENT_JOB_FAILED,
+];
+
+// A delivery is given up on after this many attempts; retries back off from RETRY_BASE_SECS
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 6;
+const RETRY_BASE_SECS: i64 = 30;
+
+// Deliveries claimed per dispatcher round
+const DELIVERY_BATCH_SIZE: i64 = 20;
+
+// Claimed deliveries are pushed this far into the future so other instances skip them
+// while they're in flight
+const DELIVERY_LEASE_SECS: i64 = 60;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Response bodies kept in the delivery log are cut to this length
+const MAX_LOGGED_ERROR_CHARS: usize = 500;
+
+// Hex HMAC-SHA256 over "{timestamp}.{body}", sent as X-Webhook-Signature: sha256=<signature>.
+// Including the timestamp lets receivers reject replayed deliveries.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn generate_secret() -> String {
+    format!("whsec_{}", uuid::Uuid::new_v4().simple())
+}
+
+// Delay before the next attempt after `attempts` failures: 30s, 1m, 2m, 4m, 8m
+pub fn retry_delay_secs(attempts: i32) -> i64 {
+    RETRY_BASE_SECS << (attempts - 1).clamp(0, 10)
+}
+
+pub async fn list_webhooks(db_pool: &PgPool, user_id: i32) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(db_pool)
+        .await
+}
+
+pub async fn get_webhook(db_pool: &PgPool, webhook_id: i32, user_id: i32) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1 AND user_id = $2")
+        .bind(webhook_id)
+        .bind(user_id)
+        .fetch_optional(db_pool)
+        .await
+}
+
+pub async fn create_webhook(
+    db_pool: &PgPool,
+    user_id: i32,
+    url: &str,
+    secret: &str,
+    events: &[String],
+    active: bool,
+) -> Result<Webhook, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let webhook = sqlx::query_as::<_, Webhook>(
+        "INSERT INTO webhooks (user_id, url, secret, events, active, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $6) RETURNING *"
+    )
+    .bind(user_id)
+    .bind(url)
+    .bind(secret)
+    .bind(events)
+    .bind(active)
+    .bind(now)
+    .fetch_one(db_pool)
+    .await?;
+
+    info!("Created webhook {} for user {} ({})", webhook.id, user_id, url);
+    Ok(webhook)
+}
+
+// Replace a webhook's URL, events and active flag. The secret is only changed when one is given.
+pub async fn update_webhook(
+    db_pool: &PgPool,
+    webhook_id: i32,
+    user_id: i32,
+    url: &str,
+    secret: Option<&str>,
+    events: &[String],
+    active: bool,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(
+        "UPDATE webhooks SET url = $1, secret = COALESCE($2, secret), events = $3, active = $4, updated_at = $5 \
+         WHERE id = $6 AND user_id = $7 RETURNING *"
+    )
+    .bind(url)
+    .bind(secret)
+    .bind(events)
+    .bind(active)
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(webhook_id)
+    .bind(user_id)
+    .fetch_optional(db_pool)
+    .await
+}
+
+pub async fn delete_webhook(db_pool: &PgPool, webhook_id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND user_id = $2")
+        .bind(webhook_id)
+        .bind(user_id)
+        .execute(db_pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_deliveries(db_pool: &PgPool, webhook_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2"
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .fetch_all(db_pool)
+    .await
+}
+
+// Queue `event` for every active webhook subscribed to it. Sending happens in the dispatcher,
+// so callers never wait on (or fail because of) a subscriber's endpoint.
+pub async fn dispatch(db_pool: &PgPool, event: &str, data: serde_json::Value) -> Result<u64, sqlx::Error> {
+    let queued = webhook_events::queue(db_pool, event, &data).await?;
+    if queued > 0 {
+        info!("Queued {} event for {} webhooks", event, queued);
+    }
+    Ok(queued)
+}
+
+// Raise video.created for a video just created, by any path. Failing to queue it doesn't undo
+// the video, so it is only logged.
+pub async fn video_created(db_pool: &PgPool, video: &Video) {
+    match webhook_events::video_created(db_pool, video).await {
+        Ok(0) => {}
+        Ok(queued) => info!("Queued {} event for {} webhooks", EVENT_VIDEO_CREATED, queued),
+        Err(e) => error!("Failed to queue video.created webhooks for video {}: {:?}", video.id, e),
+    }
+}
+
+// Send one round of due deliveries and return how many were attempted
+pub async fn deliver_due(db_pool: &PgPool, client: &reqwest::Client) -> Result<usize, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+        "UPDATE webhook_deliveries SET next_attempt_at = $1 WHERE id IN ( \
+             SELECT id FROM webhook_deliveries WHERE status = 'pending' AND next_attempt_at <= $2 \
+             ORDER BY next_attempt_at LIMIT $3 FOR UPDATE SKIP LOCKED \
+         ) RETURNING *"
+    )
+    .bind(now + chrono::Duration::seconds(DELIVERY_LEASE_SECS))
+    .bind(now)
+    .bind(DELIVERY_BATCH_SIZE)
+    .fetch_all(db_pool)
+    .await?;
+
+    for delivery in &deliveries {
+        let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1")
+            .bind(delivery.webhook_id)
+            .fetch_optional(db_pool)
+            .await?;
+        match webhook {
+            Some(webhook) => deliver(db_pool, client, &webhook, delivery).await?,
+            // Deleted between dispatch and delivery; the cascade removes the row anyway
+            None => continue,
+        }
+    }
+
+    Ok(deliveries.len())
+}
+
+async fn deliver(
+    db_pool: &PgPool,
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+) -> Result<(), sqlx::Error> {
+    let body = json!({
+        "id": delivery.id,
+        "event": delivery.event,
+        "createdAt": delivery.created_at,
+        "data": delivery.payload,
+    })
+    .to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign_payload(&webhook.secret, timestamp, &body);
+
+    let result = client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", &delivery.event)
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await;
+
+    let (response_status, error) = match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+        Ok(response) => {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            let text: String = text.chars().take(MAX_LOGGED_ERROR_CHARS).collect();
+            (Some(status.as_u16() as i32), Some(format!("HTTP {}: {}", status, text)))
+        }
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let now = chrono::Utc::now().naive_utc();
+    match error {
+        None => {
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = 'succeeded', attempts = $1, response_status = $2, last_error = NULL, delivered_at = $3 WHERE id = $4"
+            )
+            .bind(attempts)
+            .bind(response_status)
+            .bind(now)
+            .bind(delivery.id)
+            .execute(db_pool)
+            .await?;
+        }
+        Some(error) => {
+            let status = if attempts >= MAX_DELIVERY_ATTEMPTS { "failed" } else { "pending" };
+            let next_attempt_at = now + chrono::Duration::seconds(retry_delay_secs(attempts));
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = $1, attempts = $2, response_status = $3, last_error = $4, next_attempt_at = $5 WHERE id = $6"
+            )
+            .bind(status)
+            .bind(attempts)
+            .bind(response_status)
+            .bind(&error)
+            .bind(next_attempt_at)
+            .bind(delivery.id)
+            .execute(db_pool)
+            .await?;
+
+            if status == "failed" {
+                warn!("Giving up on webhook delivery {} to {} after {} attempts: {}", delivery.id, webhook.url, attempts, error);
+            } else {
+                warn!("Webhook delivery {} to {} failed (attempt {}): {}", delivery.id, webhook.url, attempts, error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_webhook_dispatcher(db_pool: PgPool) {
+    let interval_secs = env::var("WEBHOOK_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5);
+
+    info!("Starting webhook dispatcher (interval: {} seconds)", interval_secs);
+
+    let client = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()
+        .expect("Failed to build webhook HTTP client");
+
+    loop {
+        match deliver_due(&db_pool, &client).await {
+            // A full batch means more may be waiting, so go again straight away
+            Ok(n) if n as i64 >= DELIVERY_BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(e) => error!("Error delivering webhooks: {:?}", e),
+        }
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
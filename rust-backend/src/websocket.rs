@@ -2,41 +2,137 @@ use actix_web::{web, get, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use actix::ActorContext;
 use actix::AsyncContext;
-use tokio::sync::mpsc;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, LazyLock}, time::Instant};
 use tokio::sync::Mutex;
 use log::{info, error, warn};
 
-use crate::models::Comment;
+use crate::client_queue::{client_queue, Backlog, ClientReceiver, ClientSender, MAX_UNSENT_BYTES};
+use crate::comment_replay;
+use crate::models::{Comment, WatchParty, WatchPartyGuest};
+use crate::processing::{self, ProcessingUpdate};
 use crate::redis_service::{WatchPartyMessage, get_video_channel, publish_message, subscribe_to_channel};
+use crate::watch_parties::{self, Participant};
+use crate::watch_party_polls::{self, PollError};
+use crate::ws_stats;
 use crate::AppState;
 
-pub fn broadcast_comment(video_id: i32, comment: Comment, clients: HashMap<i32, Vec<tokio::sync::mpsc::Sender<String>>>) {
-    if let Some(client_list) = clients.get(&video_id).cloned() {
+pub fn broadcast_comment(video_id: i32, comment: Comment, clients: HashMap<i32, Vec<ClientSender>>) {
+    if let Some(client_list) = clients.get(&video_id) {
+        let comment_json = serde_json::to_string(&comment).unwrap_or_else(|_| String::from("Error serializing comment"));
         for tx in client_list {
-            let comment_json = serde_json::to_string(&comment).unwrap_or_else(|_| String::from("Error serializing comment"));
-            // Clone the comment_json for each task
-            let msg = comment_json.clone();
-            tokio::spawn(async move {
-                let _ = tx.send(msg).await;
-            });
+            tx.send(comment_json.clone());
         }
     }
 }
 
+// Pass a comment on to the video's watch party as a `comment` message, so party members see it
+// without also connecting to the comments socket
+pub fn broadcast_comment_to_watch_party(state: Arc<Mutex<AppState>>, comment: &Comment) {
+    let message = WatchPartyMessage {
+        type_field: "comment".to_string(),
+        video_id: comment.video_id,
+        user_id: comment.user_id,
+        action: "comment".to_string(),
+        time: Some(comment.video_time as f64),
+        source_id: format!("comment_{}", comment.id),
+        emoji: None,
+        target_user_id: None,
+        payload: serde_json::to_value(comment).ok(),
+    };
+    let msg_json = serde_json::to_string(&message).unwrap_or_default();
+    tokio::spawn(broadcast_to_room(state, comment.video_id, None, message, msg_json));
+}
+
+// Drain a client's queue into its actor, pausing while the connection has too much unsent. A
+// client that isn't reading then backs up its own queue, where the overflow policy applies,
+// rather than the actor's unbounded write buffer.
+fn forward_queue<A>(mut rx: ClientReceiver, addr: actix::Addr<A>, backlog: Backlog)
+where
+    A: actix::Actor<Context = ws::WebsocketContext<A>> + actix::Handler<WsMessage> + actix::Handler<QueueOverflow>,
+{
+    actix::spawn(async move {
+        while backlog.wait_below(MAX_UNSENT_BYTES).await {
+            let Some(msg) = rx.recv().await else {
+                break;
+            };
+            if addr.send(WsMessage(msg)).await.is_err() {
+                return;
+            }
+        }
+        if rx.overflowed() {
+            addr.do_send(QueueOverflow);
+        }
+    });
+}
+
+fn close_for_overflow<A>(ctx: &mut ws::WebsocketContext<A>)
+where
+    A: actix::Actor<Context = ws::WebsocketContext<A>>,
+{
+    ctx.close(Some(ws::CloseReason {
+        code: ws::CloseCode::Again,
+        description: Some("Client is too slow to keep up".to_string()),
+    }));
+    ctx.stop();
+}
+
 struct VideoWebSocket {
     video_id: i32,
     state: Arc<Mutex<AppState>>,
-    tx: mpsc::Sender<String>,
+    tx: ClientSender,
+    rx: Option<ClientReceiver>,
+    backlog: Backlog,
+    replay_limit: i64,
+    position: Option<i32>, // playback position reported on connect
+}
+
+impl VideoWebSocket {
+    // Send the comments around `position` (or the latest) as one batch, through the client's
+    // queue like live comments
+    fn replay(&self, position: Option<i32>) {
+        let state = self.state.clone();
+        let video_id = self.video_id;
+        let tx = self.tx.clone();
+        let limit = self.replay_limit;
+        tokio::spawn(async move {
+            let db_pool = state.lock().await.db_pool.clone();
+            match comment_replay::replay_comments(&db_pool, video_id, position, limit).await {
+                Ok(comments) => {
+                    tx.send(comment_replay::replay_message(position, &comments));
+                }
+                Err(e) => error!("Failed to load comments to replay for video_id {}: {:?}", video_id, e),
+            }
+        });
+    }
+}
+
+impl actix::Handler<WsMessage> for VideoWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
+        self.backlog.written(msg.0.len());
+        ctx.text(msg.0);
+    }
+}
+
+impl actix::Handler<QueueOverflow> for VideoWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, _msg: QueueOverflow, ctx: &mut Self::Context) {
+        close_for_overflow(ctx);
+    }
 }
 
 impl actix::Actor for VideoWebSocket {
     type Context = ws::WebsocketContext<Self>;
 
-    fn started(&mut self, _ctx: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         let state = self.state.clone();
         let video_id = self.video_id;
         let tx = self.tx.clone();
+        if let Some(rx) = self.rx.take() {
+            forward_queue(rx, ctx.address(), self.backlog.clone());
+        }
         tokio::spawn(async move {
             let state = state.lock().await;
             let mut clients = state.video_clients.lock().unwrap();
@@ -45,6 +141,10 @@ impl actix::Actor for VideoWebSocket {
                 .push(tx);
             info!("WebSocket client connected for video_id: {}", video_id);
         });
+        // Registered first, so a comment posted meanwhile may arrive twice but is never missed
+        if self.replay_limit > 0 {
+            self.replay(self.position);
+        }
     }
 
     fn stopped(&mut self, ctx: &mut Self::Context) {
@@ -71,7 +171,15 @@ impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for VideoWebSo
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
             Ok(ws::Message::Text(text)) => {
+                ws_stats::record_received();
                 info!("Received WebSocket message for video_id {}: {}", self.video_id, text);
+                // After a seek the client reports its new position to get the comments around it
+                if let Ok(message) = serde_json::from_str::<CommentSocketMessage>(&text) {
+                    if message.kind == "seek" {
+                        self.replay(message.position);
+                        return;
+                    }
+                }
                 // Echo back for testing or handle client messages if needed
                 ctx.text(text)
             }
@@ -84,55 +192,93 @@ impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for VideoWebSo
     }
 }
 
+#[derive(Deserialize)]
+struct CommentSocketMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    position: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct CommentReplayQuery {
+    limit: Option<i64>,    // comments replayed on connect, 0 for none
+    position: Option<i32>, // playback position in seconds; the latest comments when absent
+}
+
 #[get("/api/ws/comments/{video_id}")]
 async fn websocket_comments(
     path: web::Path<i32>,
+    query: web::Query<CommentReplayQuery>,
     req: HttpRequest,
     stream: web::Payload,
     state: web::Data<Arc<Mutex<AppState>>>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let video_id = path.into_inner();
-    let (tx, mut rx) = mpsc::channel(100);
+    let (tx, rx) = client_queue();
+    let backlog = Backlog::default();
 
-    let resp = ws::start(
-        VideoWebSocket {
-            video_id,
-            state: state.get_ref().clone(),
-            tx,
-        },
-        &req,
-        stream,
-    )?;
-
-    // Spawn a task to send messages from the channel to the WebSocket client
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            // This is a placeholder; in a real implementation, you would send the message to the WebSocket client
-            info!("Sending message to WebSocket client for video_id {}: {}", video_id, msg);
-            // Here, you would typically send the message to the WebSocket context, but since we can't access it directly,
-            // this is handled by the actor's context in a real implementation.
-        }
-    });
-
-    Ok(resp)
+    let actor = VideoWebSocket {
+        video_id,
+        state: state.get_ref().clone(),
+        tx,
+        rx: Some(rx),
+        backlog: backlog.clone(),
+        replay_limit: comment_replay::replay_limit(query.limit),
+        position: query.position,
+    };
+    // ws::start, with the outgoing stream tracked so the queue can tell when the client lags
+    Ok(ws::handshake(&req)?.streaming(backlog.track(ws::WebsocketContext::create(actor, stream))))
 }
 
 use serde::{Deserialize, Serialize};
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use std::env;
 
 // Message type for the WebSocket actor
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 struct WsMessage(String);
 
+// Sent to an actor whose client queue overflowed under the disconnect policy
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+struct QueueOverflow;
+
 // Watch Party WebSocket for synchronization
 struct WatchPartyWebSocket {
     video_id: i32,
-    user_id: Option<i32>,
+    user_id: Option<i32>, // for guests, the participant id they go by in the room
+    guest: Option<WatchPartyGuest>,
     state: Arc<Mutex<AppState>>,
-    tx: mpsc::Sender<String>,
+    tx: ClientSender,
+    rx: Option<ClientReceiver>,
+    backlog: Backlog,
     authenticated: bool,
+    format: WireFormat,
+    session_changes: Option<tokio::sync::mpsc::UnboundedSender<SessionChange>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SessionChange {
+    Join(Participant),
+    Leave(Participant),
+}
+
+// Encoding for a watch party connection, chosen with ?format= when connecting. MessagePack
+// frames carry the same fields as the JSON ones and cut bandwidth for busy rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WireFormat {
+    #[default]
+    Json,
+    #[serde(alias = "messagepack")]
+    MsgPack,
+}
+
+impl actix::Handler<QueueOverflow> for WatchPartyWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, _msg: QueueOverflow, ctx: &mut Self::Context) {
+        close_for_overflow(ctx);
+    }
 }
 
 // Handle messages sent to the actor
@@ -140,8 +286,16 @@ impl actix::Handler<WsMessage> for WatchPartyWebSocket {
     type Result = ();
 
     fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
+        // Signaling is relayed to the whole room but only delivered to the peer it is addressed to
+        if msg.0.contains("\"watchPartySignal\"") {
+            match serde_json::from_str::<WatchPartyMessage>(&msg.0) {
+                Ok(signal) if signal.target_user_id.is_none() || signal.target_user_id == self.user_id => {}
+                _ => return,
+            }
+        }
+
         // Forward the message to the WebSocket client
-        ctx.text(msg.0);
+        self.send_to_client(ctx, msg.0);
     }
 }
 
@@ -153,6 +307,28 @@ impl actix::Actor for WatchPartyWebSocket {
         let video_id = self.video_id;
         let tx = self.tx.clone();
         let addr = ctx.address();
+
+        if let Some(participant) = self.participant() {
+            self.record_session_join(participant);
+        }
+
+        // Introduce guests to the room, who have no username to look up
+        if let Some(guest) = &self.guest {
+            let participant_id = Participant::Guest(guest.id).room_id();
+            let message = WatchPartyMessage {
+                type_field: "watchPartyGuest".to_string(),
+                video_id,
+                user_id: participant_id,
+                action: "join".to_string(),
+                time: None,
+                source_id: format!("user_{}_joined", participant_id),
+                emoji: None,
+                target_user_id: None,
+                payload: Some(serde_json::json!({ "displayName": guest.display_name })),
+            };
+            let msg_json = serde_json::to_string(&message).unwrap_or_default();
+            tokio::spawn(broadcast_to_room(self.state.clone(), video_id, None, message, msg_json));
+        }
         
         // Register this client in the watchparty_clients map
         tokio::spawn(async move {
@@ -167,36 +343,32 @@ impl actix::Actor for WatchPartyWebSocket {
                   clients.get(&video_id).map(|list| list.len()).unwrap_or(0));
         });
         
-        // Create a receiver for this client
-        let (client_tx, mut client_rx) = mpsc::channel::<String>(100);
-        
-        // Store the sender in the watchparty_clients map
-        let state_clone = self.state.clone();
-        let video_id_clone = self.video_id;
+        // Forward queued messages (local broadcasts and Redis) to the WebSocket
+        if let Some(rx) = self.rx.take() {
+            forward_queue(rx, addr, self.backlog.clone());
+        }
+
+        // Let a client joining a scheduled party know how long is left straight away
+        let state_for_countdown = self.state.clone();
+        let tx_for_countdown = self.tx.clone();
         tokio::spawn(async move {
-            let state = state_clone.lock().await;
-            let mut clients = state.watchparty_clients.lock().unwrap();
-            
-            clients.entry(video_id_clone)
-                .or_insert_with(Vec::new)
-                .push(client_tx);
-            
-            info!("Added client channel to watchparty_clients map for video_id: {}", video_id_clone);
-        });
-        
-        // Spawn a task to forward messages from the channel to the WebSocket
-        let addr_clone = addr.clone();
-        actix::spawn(async move {
-            while let Some(msg) = client_rx.recv().await {
-                info!("Forwarding message to WebSocket client for video_id {}: {}", video_id, msg);
-                addr_clone.do_send(WsMessage(msg));
+            let db_pool = state_for_countdown.lock().await.db_pool.clone();
+            match watch_parties::pending_starts(&db_pool, &[video_id]).await {
+                Ok(rooms) => {
+                    for room in rooms {
+                        if let Some(scheduled_start) = room.scheduled_start {
+                            tx_for_countdown.send(countdown_message(&room, scheduled_start));
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to look up the schedule of the watch party for video_id {}: {:?}", video_id, e),
             }
         });
         
         // Subscribe to Redis channel for this video_id if Redis is available
         let state_for_redis = self.state.clone();
         let video_id_for_redis = self.video_id;
-        let addr_for_redis = addr.clone();
+        let tx_for_redis = self.tx.clone();
         
         tokio::spawn(async move {
             let state_guard = state_for_redis.lock().await;
@@ -224,8 +396,8 @@ impl actix::Actor for WatchPartyWebSocket {
                     
                     info!("Received message from Redis channel {}: {}", channel_name_for_closure, msg_json);
                     
-                    // Send the message to the WebSocket client
-                    addr_for_redis.do_send(WsMessage(msg_json));
+                    // Queue the message for the WebSocket client, unsubscribing once it has disconnected
+                    tx_for_redis.send(msg_json)
                 }).await {
                     Ok(_) => info!("Successfully subscribed to Redis channel: {}", channel_name_for_match),
                     Err(e) => error!("Failed to subscribe to Redis channel {}: {:?}", channel_name_for_match, e),
@@ -240,6 +412,10 @@ impl actix::Actor for WatchPartyWebSocket {
         let state = self.state.clone();
         let video_id = self.video_id;
         let tx = self.tx.clone();
+
+        if let Some(participant) = self.participant() {
+            self.record_session_leave(participant);
+        }
         tokio::spawn(async move {
             let state = state.lock().await;
             let mut clients = state.watchparty_clients.lock().unwrap();
@@ -263,138 +439,671 @@ impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for WatchParty
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
             Ok(ws::Message::Text(text)) => {
-                info!("Received WatchParty WebSocket message for video_id {}: {}", self.video_id, text);
+                ws_stats::record_received();
+                self.handle_text(text.to_string(), ctx)
+            }
+            Ok(ws::Message::Binary(bytes)) if self.format == WireFormat::MsgPack => {
+                ws_stats::record_received();
+                // Binary clients send the same messages as JSON clients, MessagePack-encoded
+                match rmp_serde::from_slice::<serde_json::Value>(&bytes) {
+                    Ok(value) => self.handle_text(value.to_string(), ctx),
+                    Err(e) => warn!("Ignoring undecodable MessagePack message for video_id {}: {}", self.video_id, e),
+                }
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => (),
+        }
+    }
+}
+
+impl WatchPartyWebSocket {
+    fn handle_text(&mut self, text: String, ctx: &mut ws::WebsocketContext<Self>) {
+        info!("Received WatchParty WebSocket message for video_id {}: {}", self.video_id, text);
+        
+        // Try to parse as an auth message first
+        if let Ok(auth_msg) = serde_json::from_str::<serde_json::Value>(&text) {
+            if auth_msg["type"] == "auth" && auth_msg["token"].is_string() {
+                let token = auth_msg["token"].as_str().unwrap();
+                let claims_result = crate::handlers::decode_user_id(token);
                 
-                // Try to parse as an auth message first
-                if let Ok(auth_msg) = serde_json::from_str::<serde_json::Value>(&text) {
-                    if auth_msg["type"] == "auth" && auth_msg["token"].is_string() {
-                        let token = auth_msg["token"].as_str().unwrap();
-                        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secure_jwt_secret_key_12345".to_string());
-                        let claims_result = decode::<crate::models::Claims>(
-                            token,
-                            &DecodingKey::from_secret(jwt_secret.as_ref()),
-                            &Validation::default(),
-                        ).ok().map(|decoded| decoded.claims.user_id);
-                        
-                        if let Some(user_id) = claims_result {
-                            self.user_id = Some(user_id);
-                            self.authenticated = true;
-                            info!("WatchParty WebSocket authenticated for user_id: {}", user_id);
-                            return;
+                if let Some(user_id) = claims_result {
+                    // A guest signing in carries on in the party as their account
+                    match self.participant() {
+                        None => self.record_session_join(Participant::User(user_id)),
+                        Some(guest @ Participant::Guest(_)) => {
+                            self.record_session_leave(guest);
+                            self.record_session_join(Participant::User(user_id));
                         }
+                        Some(Participant::User(_)) => {}
                     }
-                }
-                
-                // If not authenticated and not an auth message, ignore
-                if !self.authenticated && self.user_id.is_none() {
-                    info!("Ignoring message from unauthenticated WatchParty WebSocket");
+                    self.guest = None;
+                    self.user_id = Some(user_id);
+                    self.authenticated = true;
+                    info!("WatchParty WebSocket authenticated for user_id: {}", user_id);
                     return;
                 }
-                
-                // Handle control messages
-                if let Ok(control_msg) = serde_json::from_str::<ControlMessage>(&text) {
-                    info!("Processing control message: action={}, time={:?}", control_msg.action, control_msg.time);
-                    let state = self.state.clone();
-                    let video_id = self.video_id;
-                    let user_id = self.user_id.unwrap_or(-1);
-                    // Generate a unique source_id for this message
-                    let timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis();
-                    let source_id = format!("user_{}_time_{}", user_id, timestamp);
-                    
-                    // Create the control message with user info
-                    let control_msg_with_user = ControlMessageWithUser {
-                        type_field: "watchPartyControl".to_string(),
-                        action: control_msg.action.clone(),
-                        time: control_msg.time,
-                        user_id,
-                        video_id,
-                        source_id: source_id.clone(),
+            }
+        }
+        
+        // If not authenticated and not an auth message, ignore
+        if !self.authenticated && self.user_id.is_none() {
+            info!("Ignoring message from unauthenticated WatchParty WebSocket");
+            return;
+        }
+        
+        // Reactions are broadcast to the rest of the room and never stored
+        if let Ok(reaction) = serde_json::from_str::<ReactionMessage>(&text) {
+            if reaction.kind == "reaction" {
+                self.handle_reaction(reaction, ctx);
+                return;
+            }
+        }
+
+        // Typing indicators for the room chat are relayed like reactions
+        if let Ok(typing) = serde_json::from_str::<TypingMessage>(&text) {
+            if typing.kind == "typing" {
+                self.handle_typing(typing);
+                return;
+            }
+        }
+
+        // Polls are run by the host and voted on by everyone in the room
+        if let Ok(poll) = serde_json::from_str::<PollMessage>(&text) {
+            if poll.kind == "poll" {
+                self.handle_poll(poll);
+                return;
+            }
+        }
+
+        // WebRTC signaling for voice chat
+        if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
+            if signal.kind == "signal" {
+                self.handle_signal(signal, ctx);
+                return;
+            }
+        }
+
+        // Handle control messages
+        if let Ok(control_msg) = serde_json::from_str::<ControlMessage>(&text) {
+            info!("Processing control message: action={}, time={:?}", control_msg.action, control_msg.time);
+            let state = self.state.clone();
+            let video_id = self.video_id;
+            let user_id = self.user_id.unwrap_or(-1);
+            // Generate a unique source_id for this message
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let source_id = format!("user_{}_time_{}", user_id, timestamp);
+            
+            // Create the control message with user info
+            let control_msg_with_user = ControlMessageWithUser {
+                type_field: "watchPartyControl".to_string(),
+                action: control_msg.action.clone(),
+                time: control_msg.time,
+                user_id,
+                video_id,
+                source_id: source_id.clone(),
+            };
+            
+            // Convert to JSON string for sending to clients
+            let msg_json = serde_json::to_string(&control_msg_with_user)
+                .unwrap_or_else(|_| text.to_string());
+            
+            let redis_message = WatchPartyMessage {
+                type_field: "watchPartyControl".to_string(),
+                video_id,
+                user_id,
+                action: control_msg_with_user.action.clone(),
+                time: control_msg_with_user.time,
+                source_id,
+                emoji: None,
+                target_user_id: None,
+                payload: None,
+            };
+            // Use a separate async task to handle broadcasting without blocking the current context
+            let sender_tx = self.tx.clone();
+            tokio::spawn(async move {
+                // Scheduled parties hold playback until they start
+                let db_pool = state.lock().await.db_pool.clone();
+                match watch_parties::locked_until(&db_pool, video_id).await {
+                    Ok(Some(scheduled_start)) => {
+                        sender_tx.send(serde_json::json!({
+                            "type_field": "watchPartyError",
+                            "error": "This watch party hasn't started yet",
+                            "scheduledStart": scheduled_start
+                        }).to_string());
+                        return;
+                    }
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to check the schedule of the watch party for video_id {}: {:?}", video_id, e),
+                }
+
+                info!("Broadcasting control message from user_id={} to all clients for video_id={}", user_id, video_id);
+
+                // Echo back the enhanced message with source_id to the sender
+                // This ensures the sender gets the same message format as other clients
+                sender_tx.send(msg_json.clone());
+                broadcast_to_room(state, video_id, Some(sender_tx), redis_message, msg_json).await;
+            });
+        } else {
+            // For non-control messages, just echo back the original text
+            self.send_to_client(ctx, text);
+        }
+    }
+
+    // Send a JSON message to the client in the encoding it negotiated at connect time
+    fn send_to_client(&self, ctx: &mut ws::WebsocketContext<Self>, json: String) {
+        match self.format {
+            WireFormat::Json => {
+                self.backlog.written(json.len());
+                ctx.text(json);
+            }
+            WireFormat::MsgPack => match serde_json::from_str::<serde_json::Value>(&json)
+                .map_err(|e| e.to_string())
+                .and_then(|value| rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()))
+            {
+                Ok(bytes) => {
+                    self.backlog.written(bytes.len());
+                    ctx.binary(bytes);
+                }
+                Err(e) => error!("Failed to encode MessagePack message for video_id {}: {}", self.video_id, e),
+            },
+        }
+    }
+
+    fn participant(&self) -> Option<Participant> {
+        match (&self.guest, self.user_id) {
+            (Some(guest), _) => Some(Participant::Guest(guest.id)),
+            (None, Some(user_id)) => Some(Participant::User(user_id)),
+            (None, None) => None,
+        }
+    }
+
+    // Count this connection towards the video's watch party session history
+    fn record_session_join(&mut self, participant: Participant) {
+        self.record_session(SessionChange::Join(participant));
+    }
+
+    fn record_session_leave(&mut self, participant: Participant) {
+        self.record_session(SessionChange::Leave(participant));
+    }
+
+    // Changes are written one at a time by a task per connection, so a leave can't be recorded
+    // before the join it follows
+    fn record_session(&mut self, change: SessionChange) {
+        let changes = self.session_changes.get_or_insert_with(|| {
+            let (changes, mut pending) = tokio::sync::mpsc::unbounded_channel();
+            let state = self.state.clone();
+            let video_id = self.video_id;
+            tokio::spawn(async move {
+                let db_pool = state.lock().await.db_pool.clone();
+                while let Some(change) = pending.recv().await {
+                    let result = match change {
+                        SessionChange::Join(participant) => watch_parties::record_join(&db_pool, video_id, participant).await.map(|_| ()),
+                        SessionChange::Leave(participant) => watch_parties::record_leave(&db_pool, video_id, participant).await,
                     };
-                    
-                    // Convert to JSON string for sending to clients
-                    let msg_json = serde_json::to_string(&control_msg_with_user)
-                        .unwrap_or_else(|_| text.to_string());
-                    
-                    info!("Broadcasting control message from user_id={} to all clients for video_id={}", user_id, video_id);
+                    if let Err(e) = result {
+                        error!("Failed to record {:?} in the watch party for video_id {}: {}", change, video_id, e);
+                    }
+                }
+            });
+            changes
+        });
+        let _ = changes.send(change);
+    }
 
-                    // Echo back the enhanced message with source_id to the sender
-                    // This ensures the sender gets the same message format as other clients
-                    ctx.text(msg_json.clone());
-                    
-                    // Use a separate async task to handle broadcasting without blocking the current context
-                    let sender_tx = self.tx.clone();
-                    tokio::spawn(async move {
-                        // Get the client list and clone it to avoid holding the mutex across await points
-                        let (client_list, redis_client) = {
-                            let state_guard = state.lock().await;
-                            let clients = state_guard.watchparty_clients.lock().unwrap();
-                            (clients.get(&video_id).cloned(), state_guard.redis_client.clone())
-                        };
-
-                        // Create a Redis message
-                        let redis_message = WatchPartyMessage {
-                            type_field: "watchPartyControl".to_string(),
-                            video_id,
-                            user_id,
-                            action: control_msg_with_user.action.clone(),
-                            time: control_msg_with_user.time,
-                            source_id: source_id.clone(),
-                        };
-
-                        // Publish to Redis if available
-                        if let Some(redis_client) = redis_client {
-                            let publish_channel = get_video_channel(video_id);
-                            match publish_message(&redis_client, &publish_channel, &redis_message).await {
-                                Ok(_) => info!("Successfully published message to Redis channel: {}", publish_channel),
-                                Err(e) => error!("Failed to publish message to Redis channel {}: {:?}", publish_channel, e),
-                            }
-                        } else {
-                            warn!("Redis client not available, skipping Redis publish for video_id: {}", video_id);
-                            
-                            // If Redis is not available, fall back to local broadcasting
-                            // Now send messages if we have clients
-                            if let Some(client_list) = client_list {
-                                info!("Found {} clients for video_id={}", client_list.len(), video_id);
-                                
-                                // For each client in the watchparty_clients HashMap for this video_id
-                                for (i, tx) in client_list.iter().enumerate() {
-                                    // Skip sending the message back to the sender to avoid infinite loops
-                                    if tx.same_channel(&sender_tx) {
-                                        info!("Skipping sender (client {}) for video_id={}", i, video_id);
-                                        continue;
-                                    }
-                                    
-                                    // Send the message to the client's channel
-                                    // This will be received by the task in the actor's started method
-                                    // which will then forward it to the WebSocket connection
-                                    let result = tx.send(msg_json.clone()).await;
-                                    match result {
-                                        Ok(_) => info!("Successfully sent message to client {} for video_id={}", i, video_id),
-                                        Err(e) => info!("Failed to send message to client {} for video_id={}: {:?}", i, video_id, e),
-                                    }
+    fn handle_reaction(&mut self, reaction: ReactionMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some(user_id) = self.user_id else {
+            info!("Ignoring reaction from anonymous WatchParty WebSocket");
+            return;
+        };
+
+        let emoji = reaction.emoji.trim();
+        if emoji.is_empty() || emoji.chars().count() > MAX_REACTION_CHARS || emoji.chars().any(|c| c.is_ascii_alphanumeric()) {
+            self.send_to_client(ctx, serde_json::json!({ "type_field": "watchPartyError", "error": "Invalid reaction" }).to_string());
+            return;
+        }
+
+        if !allow_reaction(user_id) {
+            self.send_to_client(ctx, serde_json::json!({ "type_field": "watchPartyError", "error": "Too many reactions, slow down" }).to_string());
+            return;
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let message = WatchPartyMessage {
+            type_field: "watchPartyReaction".to_string(),
+            video_id: self.video_id,
+            user_id,
+            action: "reaction".to_string(),
+            time: reaction.time,
+            source_id: format!("user_{}_time_{}", user_id, timestamp),
+            emoji: Some(emoji.to_string()),
+            target_user_id: None,
+            payload: None,
+        };
+        let msg_json = serde_json::to_string(&message).unwrap_or_default();
+        tokio::spawn(broadcast_to_room(self.state.clone(), self.video_id, Some(self.tx.clone()), message, msg_json));
+    }
+}
+
+impl WatchPartyWebSocket {
+    // Tell the rest of the room the user started or stopped typing. Clients repeat "typing" while
+    // the user keeps typing and hide the indicator when it stops or goes stale.
+    fn handle_typing(&mut self, typing: TypingMessage) {
+        let Some(user_id) = self.user_id else {
+            return;
+        };
+        if !allow_typing(user_id, self.video_id, typing.typing) {
+            return;
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let message = WatchPartyMessage {
+            type_field: "watchPartyTyping".to_string(),
+            video_id: self.video_id,
+            user_id,
+            action: if typing.typing { "start" } else { "stop" }.to_string(),
+            time: None,
+            source_id: format!("user_{}_time_{}", user_id, timestamp),
+            emoji: None,
+            target_user_id: None,
+            payload: None,
+        };
+        let msg_json = serde_json::to_string(&message).unwrap_or_default();
+        tokio::spawn(broadcast_to_room(self.state.clone(), self.video_id, Some(self.tx.clone()), message, msg_json));
+    }
+
+    // Create, vote on or close a poll. Every change goes to the whole room, the sender included,
+    // as a watchPartyPoll message: "create" with the poll, then "results" after each vote and
+    // "closed" with the final results.
+    fn handle_poll(&mut self, message: PollMessage) {
+        let Some(user_id) = self.user_id else {
+            info!("Ignoring poll message from anonymous WatchParty WebSocket");
+            return;
+        };
+        // Guests have no account to host a room with
+        if self.guest.is_some() && message.action != "vote" {
+            self.tx.send(serde_json::json!({ "type_field": "watchPartyError", "error": "Guests can only vote in polls" }).to_string());
+            return;
+        }
+        let state = self.state.clone();
+        let video_id = self.video_id;
+        let tx = self.tx.clone();
+
+        tokio::spawn(async move {
+            let (db_pool, redis_client) = {
+                let state_guard = state.lock().await;
+                (state_guard.db_pool.clone(), state_guard.redis_client.clone())
+            };
+            let result = match message.action.as_str() {
+                "create" => create_poll(&db_pool, redis_client.as_ref(), video_id, user_id, &message).await,
+                "vote" => match (message.poll_id.as_deref(), message.option) {
+                    (Some(poll_id), Some(option)) => watch_party_polls::vote(redis_client.as_ref(), video_id, poll_id, user_id, option)
+                        .await
+                        .map(|results| ("results", serde_json::to_value(results).ok())),
+                    _ => Err(PollError::Invalid("A vote needs a pollId and an option".to_string())),
+                },
+                "close" => match message.poll_id.as_deref() {
+                    Some(poll_id) => match watch_party_polls::close(redis_client.as_ref(), video_id, poll_id, user_id).await {
+                        Ok((poll, results)) => {
+                            if poll.persist {
+                                if let Err(e) = watch_party_polls::save_results(&db_pool, &poll, &results).await {
+                                    error!("Failed to save the results of poll {} for video_id {}: {:?}", poll.id, video_id, e);
                                 }
-                            } else {
-                                info!("No clients found for video_id={}", video_id);
                             }
+                            Ok(("closed", serde_json::to_value(results).ok()))
                         }
-                    });
-                } else {
-                    // For non-control messages, just echo back the original text
-                    ctx.text(text);
+                        Err(e) => Err(e),
+                    },
+                    None => Err(PollError::Invalid("Closing a poll needs a pollId".to_string())),
+                },
+                _ => Err(PollError::Invalid("Unknown poll action".to_string())),
+            };
+
+            let (action, payload) = match result {
+                Ok(update) => update,
+                Err(e) => {
+                    let error = match e {
+                        PollError::Redis(_) | PollError::Database(_) => {
+                            error!("Failed to update a poll for video_id {}: {}", video_id, e);
+                            "Polls are unavailable right now".to_string()
+                        }
+                        e => e.to_string(),
+                    };
+                    tx.send(serde_json::json!({ "type_field": "watchPartyError", "error": error }).to_string());
+                    return;
                 }
+            };
+
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let message = WatchPartyMessage {
+                type_field: "watchPartyPoll".to_string(),
+                video_id,
+                user_id,
+                action: action.to_string(),
+                time: None,
+                source_id: format!("user_{}_time_{}", user_id, timestamp),
+                emoji: None,
+                target_user_id: None,
+                payload,
+            };
+            let msg_json = serde_json::to_string(&message).unwrap_or_default();
+            broadcast_to_room(state, video_id, None, message, msg_json).await;
+        });
+    }
+
+    fn handle_signal(&mut self, signal: SignalMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some(user_id) = self.user_id else {
+            info!("Ignoring signal from anonymous WatchParty WebSocket");
+            return;
+        };
+
+        // join/leave announce a participant to the whole room; the rest go to a single peer
+        let target_user_id = match signal.signal.as_str() {
+            "join" | "leave" => None,
+            "offer" | "answer" | "ice" => match signal.to {
+                Some(to) if to != user_id => Some(to),
+                _ => {
+                    self.send_to_client(ctx, serde_json::json!({ "type_field": "watchPartyError", "error": "Signal is missing a target peer" }).to_string());
+                    return;
+                }
+            },
+            _ => {
+                self.send_to_client(ctx, serde_json::json!({ "type_field": "watchPartyError", "error": "Unknown signal type" }).to_string());
+                return;
             }
-            Ok(ws::Message::Close(reason)) => {
-                ctx.close(reason);
-                ctx.stop();
+        };
+
+        if signal.payload.as_ref().map(|p| p.to_string().len()).unwrap_or(0) > MAX_SIGNAL_PAYLOAD_BYTES {
+            self.send_to_client(ctx, serde_json::json!({ "type_field": "watchPartyError", "error": "Signal payload too large" }).to_string());
+            return;
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let message = WatchPartyMessage {
+            type_field: "watchPartySignal".to_string(),
+            video_id: self.video_id,
+            user_id,
+            action: signal.signal,
+            time: None,
+            source_id: format!("user_{}_time_{}", user_id, timestamp),
+            emoji: None,
+            target_user_id,
+            payload: signal.payload,
+        };
+        let msg_json = serde_json::to_string(&message).unwrap_or_default();
+        tokio::spawn(broadcast_to_room(self.state.clone(), self.video_id, Some(self.tx.clone()), message, msg_json));
+    }
+}
+
+// Only the room's host may start a poll; see watch_parties::get_or_create_room for who that is
+async fn create_poll(
+    db_pool: &sqlx::PgPool,
+    redis_client: Option<&redis::Client>,
+    video_id: i32,
+    user_id: i32,
+    message: &PollMessage,
+) -> Result<(&'static str, Option<serde_json::Value>), PollError> {
+    let poll = watch_party_polls::new_poll(
+        video_id,
+        user_id,
+        message.question.as_deref().unwrap_or_default(),
+        message.options.as_deref().unwrap_or_default(),
+        message.persist,
+    )?;
+    match watch_parties::get_or_create_room(db_pool, video_id, user_id).await? {
+        Some(room) if room.host_user_id == user_id => {}
+        _ => return Err(PollError::NotHost),
+    }
+    watch_party_polls::open(redis_client, &poll).await?;
+    Ok(("create", serde_json::to_value(&poll).ok()))
+}
+
+// Publish a watch party message through Redis so every replica sees it, or deliver it to
+// this instance's other clients directly when Redis isn't configured. `sender_tx` is the client
+// it came from, if any.
+async fn broadcast_to_room(
+    state: Arc<Mutex<AppState>>,
+    video_id: i32,
+    sender_tx: Option<ClientSender>,
+    redis_message: WatchPartyMessage,
+    msg_json: String,
+) {
+    // Get the client list and clone it to avoid holding the mutex across await points
+    let (client_list, redis_client) = {
+        let state_guard = state.lock().await;
+        let clients = state_guard.watchparty_clients.lock().unwrap();
+        (clients.get(&video_id).cloned(), state_guard.redis_client.clone())
+    };
+
+    if let Some(redis_client) = redis_client {
+        let publish_channel = get_video_channel(video_id);
+        match publish_message(&redis_client, &publish_channel, &redis_message).await {
+            Ok(_) => info!("Successfully published message to Redis channel: {}", publish_channel),
+            Err(e) => error!("Failed to publish message to Redis channel {}: {:?}", publish_channel, e),
+        }
+        return;
+    }
+
+    warn!("Redis client not available, skipping Redis publish for video_id: {}", video_id);
+
+    // If Redis is not available, fall back to local broadcasting
+    let Some(client_list) = client_list else {
+        info!("No clients found for video_id={}", video_id);
+        return;
+    };
+    info!("Found {} clients for video_id={}", client_list.len(), video_id);
+
+    for (i, tx) in client_list.iter().enumerate() {
+        // Skip sending the message back to the sender to avoid infinite loops
+        if sender_tx.as_ref().is_some_and(|sender| tx.same_channel(sender)) {
+            info!("Skipping sender (client {}) for video_id={}", i, video_id);
+            continue;
+        }
+
+        // The actor's forwarding task picks this up and writes it to the WebSocket connection
+        if tx.send(msg_json.clone()) {
+            info!("Queued message for client {} for video_id={}", i, video_id);
+        } else {
+            info!("Client {} for video_id={} is disconnected, skipping", i, video_id);
+        }
+    }
+}
+
+// How often the scheduler checks for parties to count down and start
+const SCHEDULE_TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Countdowns go out every COUNTDOWN_INTERVAL_SECS, then every second for the last
+// COUNTDOWN_FINAL_SECS
+const COUNTDOWN_INTERVAL_SECS: i64 = 30;
+const COUNTDOWN_FINAL_SECS: i64 = 10;
+
+fn countdown_message(room: &WatchParty, scheduled_start: chrono::NaiveDateTime) -> String {
+    let remaining = (scheduled_start - chrono::Utc::now().naive_utc()).num_seconds().max(0);
+    let message = WatchPartyMessage {
+        type_field: "watchPartyCountdown".to_string(),
+        video_id: room.video_id,
+        user_id: room.host_user_id,
+        action: "countdown".to_string(),
+        time: None,
+        source_id: format!("schedule_{}", room.video_id),
+        emoji: None,
+        target_user_id: None,
+        payload: Some(serde_json::json!({ "scheduledStart": scheduled_start, "secondsRemaining": remaining })),
+    };
+    serde_json::to_string(&message).unwrap_or_default()
+}
+
+// One pass of the scheduler over the watch parties this instance has clients in: issue the
+// initial play for parties whose start has come and count down the rest. Each replica counts
+// down for its own clients, while a party is only started by whichever replica claims it.
+pub async fn tick_scheduled_starts(state: &Arc<Mutex<AppState>>) {
+    let (db_pool, rooms) = {
+        let state_guard = state.lock().await;
+        let clients = state_guard.watchparty_clients.lock().unwrap();
+        (state_guard.db_pool.clone(), clients.clone())
+    };
+    if rooms.is_empty() {
+        return;
+    }
+    let video_ids: Vec<i32> = rooms.keys().copied().collect();
+
+    match watch_parties::claim_due_starts(&db_pool, &video_ids).await {
+        Ok(started) => {
+            for room in started {
+                info!("Starting scheduled watch party for video_id {}", room.video_id);
+                let message = WatchPartyMessage {
+                    type_field: "watchPartyControl".to_string(),
+                    video_id: room.video_id,
+                    user_id: room.host_user_id,
+                    action: "play".to_string(),
+                    time: Some(0.0),
+                    source_id: format!("schedule_{}", room.video_id),
+                    emoji: None,
+                    target_user_id: None,
+                    payload: None,
+                };
+                let msg_json = serde_json::to_string(&message).unwrap_or_default();
+                broadcast_to_room(state.clone(), room.video_id, None, message, msg_json).await;
+            }
+        }
+        Err(e) => error!("Failed to start scheduled watch parties: {:?}", e),
+    }
+
+    match watch_parties::pending_starts(&db_pool, &video_ids).await {
+        Ok(pending) => {
+            let now = chrono::Utc::now().naive_utc();
+            for room in pending {
+                let Some(scheduled_start) = room.scheduled_start else {
+                    continue;
+                };
+                let remaining = (scheduled_start - now).num_seconds();
+                if remaining > COUNTDOWN_FINAL_SECS && remaining % COUNTDOWN_INTERVAL_SECS != 0 {
+                    continue;
+                }
+                let msg_json = countdown_message(&room, scheduled_start);
+                for tx in rooms.get(&room.video_id).into_iter().flatten() {
+                    tx.send(msg_json.clone());
+                }
             }
-            _ => (),
         }
+        Err(e) => error!("Failed to count down scheduled watch parties: {:?}", e),
+    }
+}
+
+pub async fn run_watch_party_scheduler(state: Arc<Mutex<AppState>>) {
+    info!("Starting watch party scheduler (interval: {:?})", SCHEDULE_TICK);
+    let mut interval = tokio::time::interval(SCHEDULE_TICK);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        tick_scheduled_starts(&state).await;
     }
 }
 
+// Each user may send REACTION_LIMIT reactions per REACTION_WINDOW, across all of their connections
+const REACTION_LIMIT: usize = 5;
+const REACTION_WINDOW: std::time::Duration = std::time::Duration::from_secs(3);
+const MAX_REACTION_CHARS: usize = 8;
+
+static RECENT_REACTIONS: LazyLock<std::sync::Mutex<HashMap<i32, VecDeque<Instant>>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn allow_reaction(user_id: i32) -> bool {
+    let now = Instant::now();
+    let mut recent = RECENT_REACTIONS.lock().unwrap();
+    // Forget users with nothing left in the window, so the map doesn't keep every user seen
+    recent.retain(|_, sent| sent.back().is_some_and(|t| now.duration_since(*t) < REACTION_WINDOW));
+    let sent = recent.entry(user_id).or_default();
+    while sent.front().is_some_and(|t| now.duration_since(*t) >= REACTION_WINDOW) {
+        sent.pop_front();
+    }
+    if sent.len() >= REACTION_LIMIT {
+        return false;
+    }
+    sent.push_back(now);
+    true
+}
+
+// A user still typing is announced again at most once per TYPING_INTERVAL per room; starting and
+// stopping always go through
+const TYPING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+static TYPING: LazyLock<std::sync::Mutex<HashMap<(i32, i32), (bool, Instant)>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn allow_typing(user_id: i32, video_id: i32, typing: bool) -> bool {
+    let now = Instant::now();
+    let mut last = TYPING.lock().unwrap();
+    // Forget users who stopped typing a while ago, so the map doesn't keep every user seen
+    last.retain(|_, (_, at)| now.duration_since(*at) < TYPING_INTERVAL * 30);
+    let allowed = match last.get(&(user_id, video_id)) {
+        Some((was_typing, at)) => *was_typing != typing || now.duration_since(*at) >= TYPING_INTERVAL,
+        None => typing,
+    };
+    if allowed {
+        last.insert((user_id, video_id), (typing, now));
+    }
+    allowed
+}
+
+// SDP offers with many codecs run to a few KB; anything far larger isn't a real signal
+const MAX_SIGNAL_PAYLOAD_BYTES: usize = 16 * 1024;
+
+#[derive(Deserialize)]
+struct SignalMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    signal: String,
+    to: Option<i32>,
+    payload: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PollMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    action: String, // create, vote or close
+    question: Option<String>,
+    options: Option<Vec<String>>,
+    #[serde(rename = "pollId")]
+    poll_id: Option<String>,
+    option: Option<usize>, // index into the poll's options
+    #[serde(default)]
+    persist: bool,
+}
+
+#[derive(Deserialize)]
+struct TypingMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    typing: bool, // false once the user stops typing or sends their message
+}
+
+#[derive(Deserialize)]
+struct ReactionMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    emoji: String,
+    time: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ControlMessage {
     action: String,
@@ -411,47 +1120,189 @@ struct ControlMessageWithUser {
     source_id: String, // Add a source_id field to identify the origin of the message
 }
 
+// Invite-only rooms are checked before the upgrade, so clients joining them pass their
+// JWT and invite token in the query string rather than in a later auth message. Guests pass
+// the token from POST /api/watchparty/guest as ?guest=.
+#[derive(Deserialize)]
+struct WatchPartyConnectQuery {
+    token: Option<String>,
+    invite: Option<String>,
+    guest: Option<String>,
+    #[serde(default)]
+    format: WireFormat,
+}
+
 #[get("/api/ws/watchparty/{video_id}")]
 async fn websocket_watchparty(
     path: web::Path<i32>,
+    query: web::Query<WatchPartyConnectQuery>,
     req: HttpRequest,
     stream: web::Payload,
     state: web::Data<Arc<Mutex<AppState>>>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let video_id = path.into_inner();
+
+    let user_id = query.token.as_deref().and_then(crate::handlers::decode_user_id);
+
+    let db_pool = state.lock().await.db_pool.clone();
+    let guest = match (user_id, query.guest.as_deref()) {
+        (None, Some(guest_token)) => {
+            let active = match crate::handlers::decode_guest_id(guest_token) {
+                Some(guest_id) => watch_parties::active_guest(&db_pool, guest_id).await,
+                None => Ok(None),
+            };
+            match active {
+                Ok(Some(guest)) => Some(guest),
+                // Claimed guests sign in with their account instead
+                Ok(None) => {
+                    return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+                        "error": "Invalid or expired guest token",
+                        "code": "unauthorized"
+                    })));
+                }
+                Err(e) => {
+                    error!("Failed to look up watch party guest for video_id {}: {}", video_id, e);
+                    return Ok(HttpResponse::InternalServerError().finish());
+                }
+            }
+        }
+        _ => None,
+    };
+
+    match crate::watch_parties::can_join(&db_pool, video_id, user_id, query.invite.as_deref()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("Rejected WatchParty WebSocket for invite-only video_id: {}", video_id);
+            return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+                "error": "This watch party is invite-only",
+                "code": "forbidden"
+            })));
+        }
+        Err(e) => {
+            error!("Failed to check watch party access for video_id {}: {}", video_id, e);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    }
     
-    // Create a channel for this specific WebSocket connection
-    let (tx, mut _rx) = mpsc::channel(100);
+    // Create the queue this connection's broadcasts go through
+    let (tx, rx) = client_queue();
+    let backlog = Backlog::default();
     
     info!("Setting up new WebSocket connection for video_id: {}", video_id);
     
-    // Initialize the WebSocket actor with no user_id and not authenticated
-    // The client will send an auth message with the token after connecting
+    // Clients that didn't pass a token in the query string authenticate with an auth message after connecting.
+    // The actor registers itself in watchparty_clients when it starts.
+    let participant_id = user_id.or_else(|| guest.as_ref().map(|guest| Participant::Guest(guest.id).room_id()));
     let ws = WatchPartyWebSocket {
         video_id,
-        user_id: None,
+        user_id: participant_id,
+        guest,
         state: state.get_ref().clone(),
-        tx: tx.clone(), // Clone the sender for the actor
-        authenticated: false,
+        tx,
+        rx: Some(rx),
+        backlog: backlog.clone(),
+        authenticated: participant_id.is_some(),
+        format: query.format,
+        session_changes: None,
     };
     
-    // Start the WebSocket actor
-    let resp = ws::start(ws, &req, stream)?;
-    
-    // Store the sender in the watchparty_clients map
-    tokio::spawn(async move {
-        let state = state.get_ref().clone();
-        let state_guard = state.lock().await;
-        let mut clients = state_guard.watchparty_clients.lock().unwrap();
-        
-        clients.entry(video_id)
-            .or_insert_with(Vec::new)
-            .push(tx);
-        
-        info!("Added WebSocket client to watchparty_clients map for video_id: {}", video_id);
-    });
-    
-    Ok(resp)
+    Ok(ws::handshake(&req)?.streaming(backlog.track(ws::WebsocketContext::create(ws, stream))))
+}
+
+// Sends a video's processing status on connect and whenever it or one of its jobs changes,
+// closing once the video is deleted. Same updates as /api/sse/videos/{id}/processing.
+struct ProcessingWebSocket {
+    video_id: i32,
+    db_pool: sqlx::PgPool,
+    last: Option<ProcessingUpdate>,
+    polling: bool,
+}
+
+impl ProcessingWebSocket {
+    fn poll(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        use actix::ActorFutureExt;
+
+        if self.polling {
+            return;
+        }
+        self.polling = true;
+        let db_pool = self.db_pool.clone();
+        let video_id = self.video_id;
+        let current = async move { processing::current(&db_pool, video_id).await };
+        ctx.spawn(actix::fut::wrap_future::<_, Self>(current).map(|result, act, ctx| {
+            act.polling = false;
+            match result {
+                Ok(None) => {
+                    ctx.close(Some(ws::CloseReason {
+                        code: ws::CloseCode::Normal,
+                        description: Some("Video was deleted".to_string()),
+                    }));
+                    ctx.stop();
+                }
+                Ok(Some(update)) if act.last.as_ref() != Some(&update) => {
+                    ctx.text(serde_json::to_string(&update).unwrap_or_default());
+                    act.last = Some(update);
+                }
+                Ok(Some(_)) => {}
+                Err(e) => error!("Failed to poll processing status for video_id {}: {:?}", act.video_id, e),
+            }
+        }));
+    }
+}
+
+impl actix::Actor for ProcessingWebSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.poll(ctx);
+        ctx.run_interval(processing::poll_interval(), |act, ctx| act.poll(ctx));
+    }
+}
+
+impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for ProcessingWebSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProcessingConnectQuery {
+    token: Option<String>,
+}
+
+// For the video's uploader or an admin; browsers pass their JWT as ?token=
+#[get("/api/ws/videos/{id}/processing")]
+async fn websocket_processing(
+    path: web::Path<i32>,
+    query: web::Query<ProcessingConnectQuery>,
+    req: HttpRequest,
+    stream: web::Payload,
+    state: web::Data<Arc<Mutex<AppState>>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let video_id = path.into_inner();
+    let user_id = crate::handlers::authenticated_user_id(&req)
+        .or_else(|| query.token.as_deref().and_then(crate::handlers::decode_user_id))
+        .ok_or(crate::error::ApiError::Unauthorized)?;
+    let db_pool = state.lock().await.db_pool.clone();
+    crate::handlers::owned_video(&db_pool, video_id, user_id).await?;
+
+    ws::start(
+        ProcessingWebSocket {
+            video_id,
+            db_pool,
+            last: None,
+            polling: false,
+        },
+        &req,
+        stream,
+    )
 }
 
 #[get("/api/ws/health")]
@@ -465,5 +1316,6 @@ async fn websocket_health() -> Result<HttpResponse, actix_web::Error> {
 pub fn configure_ws_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(websocket_comments)
        .service(websocket_watchparty)
+       .service(websocket_processing)
        .service(websocket_health);
 }
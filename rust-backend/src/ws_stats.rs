@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::error;
+use serde::Serialize;
+
+use crate::circuit_breaker;
+use crate::client_queue::{self, ClientSender, QueueMetrics};
+use crate::redis_service::get_video_channel;
+
+// Rates are averaged over this many seconds
+const RATE_WINDOW_SECS: u64 = 60;
+
+// Messages per second over the last RATE_WINDOW_SECS, one slot per second. A slot is reset when
+// it's reused, and a count landing during the reset can be lost, which is fine for a gauge.
+struct Throughput {
+    total: AtomicU64,
+    counts: [AtomicU64; RATE_WINDOW_SECS as usize],
+    seconds: [AtomicU64; RATE_WINDOW_SECS as usize],
+}
+
+impl Throughput {
+    const fn new() -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            counts: [const { AtomicU64::new(0) }; RATE_WINDOW_SECS as usize],
+            seconds: [const { AtomicU64::new(0) }; RATE_WINDOW_SECS as usize],
+        }
+    }
+
+    fn record(&self, now: u64) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        let slot = (now % RATE_WINDOW_SECS) as usize;
+        if self.seconds[slot].swap(now, Ordering::Relaxed) != now {
+            self.counts[slot].store(0, Ordering::Relaxed);
+        }
+        self.counts[slot].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn per_second(&self, now: u64) -> f64 {
+        let recent: u64 = (0..RATE_WINDOW_SECS as usize)
+            .filter(|&slot| now.saturating_sub(self.seconds[slot].load(Ordering::Relaxed)) < RATE_WINDOW_SECS)
+            .map(|slot| self.counts[slot].load(Ordering::Relaxed))
+            .sum();
+        recent as f64 / RATE_WINDOW_SECS as f64
+    }
+}
+
+static SENT: Throughput = Throughput::new();
+static RECEIVED: Throughput = Throughput::new();
+static REDIS_SUBSCRIPTIONS: AtomicI64 = AtomicI64::new(0);
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// A message queued for a WebSocket client
+pub fn record_sent() {
+    SENT.record(now_secs());
+}
+
+// A message a WebSocket client sent us
+pub fn record_received() {
+    RECEIVED.record(now_secs());
+}
+
+pub fn subscription_opened() {
+    REDIS_SUBSCRIPTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn subscription_closed() {
+    REDIS_SUBSCRIPTIONS.fetch_sub(1, Ordering::Relaxed);
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomStats {
+    pub video_id: i32,
+    pub clients: usize,
+    // Subscribers to the room's Redis channel across all replicas; null for comment rooms and
+    // without Redis
+    pub redis_subscribers: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThroughputStats {
+    pub sent_total: u64,
+    pub received_total: u64,
+    pub sent_per_sec: f64,
+    pub received_per_sec: f64,
+    pub window_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WsStats {
+    pub total_clients: usize,
+    pub videos: Vec<RoomStats>,
+    pub watch_parties: Vec<RoomStats>,
+    pub messages: ThroughputStats,
+    pub queues: QueueMetrics,
+    // Redis channel subscriptions this instance holds open for its watch party clients
+    pub redis_subscriptions: i64,
+}
+
+// Client counts per room from a connection registry, busiest first
+pub fn rooms(clients: &HashMap<i32, Vec<ClientSender>>) -> Vec<RoomStats> {
+    let mut rooms: Vec<RoomStats> = clients
+        .iter()
+        .map(|(video_id, senders)| RoomStats {
+            video_id: *video_id,
+            clients: senders.iter().filter(|tx| !tx.is_closed()).count(),
+            redis_subscribers: None,
+        })
+        .filter(|room| room.clients > 0)
+        .collect();
+    rooms.sort_by(|a, b| b.clients.cmp(&a.clients).then(a.video_id.cmp(&b.video_id)));
+    rooms
+}
+
+// Fill in each watch party's Redis subscriber count with PUBSUB NUMSUB, leaving them unset if
+// Redis can't be reached
+pub async fn count_redis_subscribers(redis_client: &redis::Client, rooms: &mut [RoomStats]) {
+    if rooms.is_empty() {
+        return;
+    }
+    let mut conn = match circuit_breaker::redis_connection(redis_client).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("Failed to connect to Redis for WebSocket stats: {:?}", e);
+            return;
+        }
+    };
+    let mut cmd = redis::cmd("PUBSUB");
+    cmd.arg("NUMSUB");
+    for room in rooms.iter() {
+        cmd.arg(get_video_channel(room.video_id));
+    }
+    // Replies with each channel followed by its count
+    match cmd.query_async::<_, Vec<(String, i64)>>(&mut conn).await {
+        Ok(counts) => {
+            for (room, (_, count)) in rooms.iter_mut().zip(counts) {
+                room.redis_subscribers = Some(count);
+            }
+        }
+        Err(e) => error!("Failed to count Redis subscribers for WebSocket stats: {:?}", e),
+    }
+}
+
+pub fn snapshot(videos: Vec<RoomStats>, watch_parties: Vec<RoomStats>) -> WsStats {
+    let now = now_secs();
+    WsStats {
+        total_clients: videos.iter().chain(&watch_parties).map(|room| room.clients).sum(),
+        videos,
+        watch_parties,
+        messages: ThroughputStats {
+            sent_total: SENT.total.load(Ordering::Relaxed),
+            received_total: RECEIVED.total.load(Ordering::Relaxed),
+            sent_per_sec: SENT.per_second(now),
+            received_per_sec: RECEIVED.per_second(now),
+            window_secs: RATE_WINDOW_SECS,
+        },
+        queues: client_queue::metrics(),
+        redis_subscriptions: REDIS_SUBSCRIPTIONS.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,48 @@
+use actix_web::test::TestRequest;
+
+use video_streaming_backend::translations;
+
+fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|t| t.to_string()).collect()
+}
+
+#[test]
+fn normalizes_language_tags() {
+    assert_eq!(translations::normalize_language(" pt-BR ").as_deref(), Some("pt-br"));
+    assert_eq!(translations::normalize_language("zh-Hant-TW").as_deref(), Some("zh-hant-tw"));
+    assert_eq!(translations::normalize_language("en_US"), None);
+    assert_eq!(translations::normalize_language("e"), None);
+    assert_eq!(translations::normalize_language("en-"), None);
+    assert_eq!(translations::normalize_language("*"), None);
+}
+
+#[test]
+fn parses_accept_language_by_quality() {
+    assert_eq!(
+        translations::parse_accept_language("fr;q=0.5, en-GB, de;q=0.8, *;q=0.1"),
+        tags(&["en-gb", "de", "fr"])
+    );
+    // Ties keep header order; q=0 means "not acceptable"; duplicates are dropped
+    assert_eq!(translations::parse_accept_language("es, it, ja;q=0, ES"), tags(&["es", "it"]));
+    assert!(translations::parse_accept_language("").is_empty());
+}
+
+#[test]
+fn picks_the_closest_translation() {
+    let available = ["en", "pt-br", "zh-hant"];
+    assert_eq!(translations::pick(&tags(&["en-us"]), &available), Some("en"));
+    assert_eq!(translations::pick(&tags(&["pt"]), &available), Some("pt-br"));
+    assert_eq!(translations::pick(&tags(&["zh-hant-tw"]), &available), Some("zh-hant"));
+    assert_eq!(translations::pick(&tags(&["de", "pt-br", "en"]), &available), Some("pt-br"));
+    assert_eq!(translations::pick(&tags(&["de"]), &available), None);
+    assert_eq!(translations::pick(&[], &available), None);
+}
+
+#[test]
+fn reads_preferred_languages_from_the_request() {
+    let req = TestRequest::default()
+        .insert_header(("Accept-Language", "de-CH, de;q=0.9"))
+        .to_http_request();
+    assert_eq!(translations::preferred_languages(&req), tags(&["de-ch", "de"]));
+    assert!(translations::preferred_languages(&TestRequest::default().to_http_request()).is_empty());
+}
@@ -0,0 +1,85 @@
+use actix_web::{middleware, test, web, App, HttpResponse};
+use chrono::NaiveDate;
+use serde_json::json;
+
+use video_streaming_backend::api_versions::{
+    deprecation_headers, legacy_deprecation, route_deprecation, route_version, unversioned_path, DeprecatedRoute, Deprecation,
+};
+
+fn date(value: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn v1_paths_map_to_unversioned_routes() {
+    assert_eq!(unversioned_path("/api/v1/videos/3"), Some("/api/videos/3".to_string()));
+    assert_eq!(unversioned_path("/api/v1"), Some("/api".to_string()));
+    assert_eq!(unversioned_path("/api/v10/videos"), None);
+    assert_eq!(unversioned_path("/api/videos/3"), None);
+    assert_eq!(unversioned_path("/mrss.xml"), None);
+}
+
+#[test]
+fn deprecations_are_announced_in_headers() {
+    let deprecation = Deprecation {
+        since: date("2026-01-01"),
+        sunset: Some(date("2026-07-01")),
+        successor: Some("/api/v1/videos".to_string()),
+    };
+    let headers: Vec<(String, String)> = deprecation_headers(&deprecation).into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+    assert_eq!(headers, vec![
+        ("deprecation".to_string(), "@1767225600".to_string()),
+        ("sunset".to_string(), "Wed, 01 Jul 2026 00:00:00 GMT".to_string()),
+        ("link".to_string(), "</api/v1/videos>; rel=\"successor-version\"".to_string()),
+    ]);
+}
+
+#[test]
+fn unversioned_paths_are_deprecated_once_a_date_is_set() {
+    assert_eq!(legacy_deprecation("/api/videos/3", None, None), None);
+    assert_eq!(legacy_deprecation("/api/videos/3", Some(date("2026-01-01")), None), Some(Deprecation {
+        since: date("2026-01-01"),
+        sunset: None,
+        successor: Some("/api/v1/videos/3".to_string()),
+    }));
+    assert_eq!(legacy_deprecation("/mrss.xml", Some(date("2026-01-01")), None), None);
+}
+
+#[test]
+fn listed_routes_are_deprecated() {
+    let routes = [DeprecatedRoute {
+        method: "GET",
+        route: "/api/videos/tag/{tag}",
+        since: "2026-01-01",
+        sunset: Some("2026-04-01"),
+        successor: Some("/api/v1/videos/search"),
+    }];
+    let deprecation = route_deprecation("GET", "/api/videos/tag/{tag}", &routes).unwrap();
+    assert_eq!(deprecation.sunset, Some(date("2026-04-01")));
+    assert_eq!(route_deprecation("DELETE", "/api/videos/tag/{tag}", &routes), None);
+    assert_eq!(route_deprecation("GET", "/api/videos/{id}", &routes), None);
+}
+
+#[actix_web::test]
+async fn v1_is_served_by_the_same_routes_in_camel_case() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(route_version))
+            .route("/api/videos/{id}", web::get().to(|path: web::Path<i32>| async move {
+                HttpResponse::Ok().json(json!({ "id": path.into_inner(), "view_count": 7 }))
+            }))
+    ).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/videos/5").to_request()).await;
+    assert!(resp.headers().get("Deprecation").is_none());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body, json!({ "id": 5, "view_count": 7 }));
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/v1/videos/5?x=1").to_request()).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body, json!({ "id": 5, "viewCount": 7 }));
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/v2/videos/5").to_request()).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,98 @@
+use video_streaming_backend::audio_tracks::{is_audio_content_type, normalize_language, parse_probe_output, standalone_label};
+
+const FFPROBE_OUTPUT: &str = r#"{
+    "programs": [],
+    "streams": [
+        {
+            "codec_name": "aac",
+            "channels": 2,
+            "disposition": { "default": 0, "dub": 0 },
+            "tags": { "language": "eng", "title": "English" }
+        },
+        {
+            "codec_name": "ac3",
+            "channels": 6,
+            "disposition": { "default": 1, "dub": 1 },
+            "tags": { "language": "FRA" }
+        },
+        {
+            "codec_name": "aac",
+            "channels": 2,
+            "disposition": { "default": 0, "dub": 0, "visual_impaired": 1 },
+            "tags": { "language": "und" }
+        }
+    ]
+}"#;
+
+#[test]
+fn tracks_are_listed_in_stream_order() {
+    let tracks = parse_probe_output(4, FFPROBE_OUTPUT).unwrap();
+    assert_eq!(tracks.len(), 3);
+    assert_eq!(tracks.iter().map(|t| t.track_index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert!(tracks.iter().all(|t| t.video_id == 4));
+
+    assert_eq!(tracks[0].language.as_deref(), Some("eng"));
+    assert_eq!(tracks[0].label, "English");
+    assert_eq!(tracks[0].codec.as_deref(), Some("aac"));
+    assert_eq!(tracks[1].channels, Some(6));
+}
+
+#[test]
+fn labels_fall_back_to_language_then_position() {
+    let tracks = parse_probe_output(4, FFPROBE_OUTPUT).unwrap();
+    assert_eq!(tracks[1].label, "fra");
+    assert_eq!(tracks[2].language, None);
+    assert_eq!(tracks[2].label, "Track 3");
+}
+
+#[test]
+fn default_track_is_the_flagged_one_or_the_first() {
+    let tracks = parse_probe_output(4, FFPROBE_OUTPUT).unwrap();
+    assert_eq!(tracks.iter().filter(|t| t.is_default).map(|t| t.track_index).collect::<Vec<_>>(), vec![1]);
+
+    let unflagged = FFPROBE_OUTPUT.replace("\"default\": 1", "\"default\": 0");
+    let tracks = parse_probe_output(4, &unflagged).unwrap();
+    assert_eq!(tracks.iter().filter(|t| t.is_default).map(|t| t.track_index).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn files_without_audio_have_no_tracks() {
+    assert!(parse_probe_output(4, r#"{"programs": [], "streams": []}"#).unwrap().is_empty());
+    assert!(parse_probe_output(4, "{}").unwrap().is_empty());
+    assert!(parse_probe_output(4, "not json").is_err());
+}
+
+#[test]
+fn languages_are_plain_lower_case_tags() {
+    assert_eq!(normalize_language(" ENG "), Some("eng".to_string()));
+    assert_eq!(normalize_language("pt-BR"), Some("pt-br".to_string()));
+    assert_eq!(normalize_language("und"), None);
+    assert_eq!(normalize_language(""), None);
+    assert_eq!(normalize_language("en\",URI=\"x"), None);
+    assert_eq!(normalize_language("en--us"), None);
+}
+
+#[test]
+fn visually_impaired_streams_are_audio_description() {
+    let tracks = parse_probe_output(4, FFPROBE_OUTPUT).unwrap();
+    assert_eq!(tracks.iter().map(|t| t.audio_description).collect::<Vec<_>>(), vec![false, false, true]);
+    assert!(tracks.iter().all(|t| !t.standalone && t.s3_key.is_none()));
+}
+
+#[test]
+fn standalone_labels_default_by_kind() {
+    assert_eq!(standalone_label(Some("  Described "), Some("eng"), true), Ok("Described".to_string()));
+    assert_eq!(standalone_label(None, Some("eng"), true), Ok("Audio description".to_string()));
+    assert_eq!(standalone_label(Some(" "), Some("deu"), false), Ok("deu".to_string()));
+    assert!(standalone_label(None, None, false).is_err());
+    assert!(standalone_label(Some(&"x".repeat(101)), None, true).is_err());
+}
+
+#[test]
+fn standalone_tracks_must_be_audio() {
+    assert!(is_audio_content_type("audio/mpeg"));
+    assert!(is_audio_content_type("Audio/AAC; charset=binary"));
+    assert!(is_audio_content_type("video/mp4"));
+    assert!(!is_audio_content_type("image/png"));
+    assert!(!is_audio_content_type("text/plain"));
+}
@@ -0,0 +1,51 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use actix_web::ResponseError;
+use chrono::NaiveDate;
+
+use video_streaming_backend::bandwidth::{cap_exceeded, counter_key, request_subject, secs_until_reset, subject, Scope};
+
+#[test]
+fn signed_in_users_are_counted_by_account_and_others_by_ip() {
+    assert_eq!(subject(Some(7), Some("203.0.113.5")), Some((Scope::User, "user:7".to_string())));
+    assert_eq!(subject(None, Some("203.0.113.5")), Some((Scope::Ip, "ip:203.0.113.5".to_string())));
+    assert_eq!(subject(None, None), None);
+}
+
+#[test]
+fn anonymous_clients_cant_pick_their_own_address() {
+    let spoofed = |forwarded_for: &str| {
+        TestRequest::default()
+            .peer_addr("203.0.113.5:40000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded_for))
+            .to_http_request()
+    };
+    let expected = Some((Scope::Ip, "ip:203.0.113.5".to_string()));
+    assert_eq!(request_subject(&spoofed("198.51.100.1"), None), expected);
+    assert_eq!(request_subject(&spoofed("198.51.100.2"), None), expected);
+}
+
+#[test]
+fn counters_are_kept_per_day() {
+    let date = NaiveDate::from_ymd_opt(2025, 9, 4).unwrap();
+    assert_eq!(counter_key(date, "user:7"), "bandwidth:2025-09-04:user:7");
+}
+
+#[test]
+fn allowances_reset_at_midnight_utc() {
+    let date = NaiveDate::from_ymd_opt(2025, 9, 4).unwrap();
+    assert_eq!(secs_until_reset(date.and_hms_opt(23, 59, 0).unwrap()), 60);
+    assert_eq!(secs_until_reset(date.and_hms_opt(0, 0, 0).unwrap()), 24 * 60 * 60);
+}
+
+#[test]
+fn exceeded_caps_are_refused_with_429_or_402() {
+    let error = cap_exceeded(Scope::Ip, 5_000_000_000, 120, true);
+    assert_eq!(error.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error.error_response().headers().get("Retry-After").unwrap(), "120");
+
+    assert_eq!(cap_exceeded(Scope::User, 5_000_000_000, 120, false).status_code(), StatusCode::TOO_MANY_REQUESTS);
+    let error = cap_exceeded(Scope::User, 5_000_000_000, 120, true);
+    assert_eq!(error.status_code(), StatusCode::PAYMENT_REQUIRED);
+    assert_eq!(error.code(), "payment_required");
+}
@@ -0,0 +1,96 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+async fn insert_video(pool: &PgPool, title: &str, s3_key: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ($1, $2) RETURNING id")
+        .bind(title)
+        .bind(s3_key)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn fetch_list(
+    app: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    etag: Option<&str>,
+) -> (http::StatusCode, String) {
+    let mut req = test::TestRequest::get().uri("/api/videos");
+    if let Some(etag) = etag {
+        req = req.insert_header((http::header::IF_NONE_MATCH, etag.to_string()));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    let etag = resp.headers().get(http::header::ETAG).unwrap().to_str().unwrap().to_string();
+    (status, etag)
+}
+
+#[sqlx::test]
+async fn test_video_list_etag(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let video_id = insert_video(&pool, "First", "etag_key_1").await;
+
+    // First request returns the list with an ETag
+    let (status, etag) = fetch_list(&app, None).await;
+    assert_eq!(status, http::StatusCode::OK);
+
+    // Revalidating with the same ETag returns 304
+    let (status, same_etag) = fetch_list(&app, Some(&etag)).await;
+    assert_eq!(status, http::StatusCode::NOT_MODIFIED);
+    assert_eq!(same_etag, etag);
+
+    // Updating a video changes the ETag through the updated_at trigger
+    sqlx::query("UPDATE videos SET title = 'Renamed' WHERE id = $1")
+        .bind(video_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, updated_etag) = fetch_list(&app, Some(&etag)).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_ne!(updated_etag, etag);
+
+    // Adding a video changes it too
+    insert_video(&pool, "Second", "etag_key_2").await;
+    let (status, added_etag) = fetch_list(&app, Some(&updated_etag)).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_ne!(added_etag, updated_etag);
+}
@@ -0,0 +1,14 @@
+use video_streaming_backend::categories::normalize;
+
+#[test]
+fn test_normalize_keeps_order_and_drops_repeats() {
+    assert_eq!(normalize(&[4, 2, 4, 7, 2], 3), Ok(vec![4, 2, 7]));
+    assert_eq!(normalize(&[], 3), Ok(vec![]));
+}
+
+#[test]
+fn test_normalize_refuses_more_than_the_limit() {
+    assert_eq!(normalize(&[1, 2, 3, 4], 3), Err("A video can be in at most 3 categories".to_string()));
+    // Repeats don't count against it
+    assert_eq!(normalize(&[1, 2, 1, 3, 2], 3), Ok(vec![1, 2, 3]));
+}
@@ -0,0 +1,204 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_category(pool: &PgPool, name: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO categories (name, description) VALUES ($1, 'Testing') RETURNING id")
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by, moderation_status) VALUES ($1, $2, $3, 'approved') RETURNING id")
+        .bind("Cross-posted video")
+        .bind("category_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn patch_categories(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, token: &str, category_ids: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "categoryIds": category_ids }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+async fn listed_in(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, category_id: i32) -> Vec<i64> {
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/category/{}", category_id))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let videos: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    videos.iter().map(|v| v["id"].as_i64().unwrap()).collect()
+}
+
+#[sqlx::test]
+async fn test_video_is_listed_under_each_of_its_categories(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "crossposter").await;
+    let video_id = insert_video(&pool, user_id).await;
+    let music = insert_category(&pool, "Music").await;
+    let travel = insert_category(&pool, "Travel").await;
+    let cooking = insert_category(&pool, "Cooking").await;
+
+    let (status, body) = patch_categories(&app, video_id, &token, json!([travel, music, travel])).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(body["category_ids"], json!([travel, music]));
+    assert_eq!(body["category_id"], json!(travel));
+
+    assert_eq!(listed_in(&app, music).await, vec![video_id as i64]);
+    assert_eq!(listed_in(&app, travel).await, vec![video_id as i64]);
+    assert!(listed_in(&app, cooking).await.is_empty());
+
+    // Replacing the set drops the categories left out
+    let (status, body) = patch_categories(&app, video_id, &token, json!([cooking])).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(body["category_ids"], json!([cooking]));
+    assert!(listed_in(&app, travel).await.is_empty());
+    assert_eq!(listed_in(&app, cooking).await, vec![video_id as i64]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let video: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(video["category_ids"], json!([cooking]));
+}
+
+#[sqlx::test]
+async fn test_edit_keeps_categories_when_not_given(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "crossposter").await;
+    let video_id = insert_video(&pool, user_id).await;
+    let music = insert_category(&pool, "Music").await;
+    patch_categories(&app, video_id, &token, json!([music])).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "title": "Renamed" }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["title"], "Renamed");
+    assert_eq!(body["category_ids"], json!([music]));
+}
+
+#[sqlx::test]
+async fn test_too_many_or_unknown_categories_are_refused(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "crossposter").await;
+    let video_id = insert_video(&pool, user_id).await;
+    let mut ids = Vec::new();
+    for name in ["One", "Two", "Three", "Four"] {
+        ids.push(insert_category(&pool, name).await);
+    }
+
+    let (status, body) = patch_categories(&app, video_id, &token, json!(ids)).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("at most 3"), "{}", body);
+
+    let (status, body) = patch_categories(&app, video_id, &token, json!([ids[0], ids[3] + 1000])).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("Unknown category"), "{}", body);
+
+    // Nothing was assigned by the refused edits
+    assert!(listed_in(&app, ids[0]).await.is_empty());
+}
+
+#[sqlx::test]
+async fn test_video_lists_carry_categories(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "crossposter").await;
+    let video_id = insert_video(&pool, user_id).await;
+    let music = insert_category(&pool, "Music").await;
+    patch_categories(&app, video_id, &token, json!([music])).await;
+
+    for uri in ["/api/videos", "/api/users/me/videos"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let videos: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+        let video = videos.iter().find(|v| v["id"] == json!(video_id)).expect(uri);
+        assert_eq!(video["category_ids"], json!([music]), "{}", uri);
+        assert_eq!(video["category_id"], json!(music), "{}", uri);
+    }
+}
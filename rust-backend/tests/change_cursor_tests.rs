@@ -0,0 +1,37 @@
+use video_streaming_backend::changes::{clamp_limit, reported_change, Cursor, DEFAULT_LIMIT, MAX_LIMIT};
+
+#[test]
+fn test_cursor_round_trips() {
+    let cursor = Cursor { txid: 7340, id: 12 };
+    assert_eq!(cursor.to_string(), "7340-12");
+    assert_eq!(Cursor::parse(Some("7340-12")), Ok(cursor));
+}
+
+#[test]
+fn test_missing_cursor_starts_at_the_beginning() {
+    assert_eq!(Cursor::parse(None), Ok(Cursor::default()));
+    assert_eq!(Cursor::parse(Some(" ")), Ok(Cursor::default()));
+}
+
+#[test]
+fn test_malformed_cursors_are_refused() {
+    for value in ["12", "abc-1", "1-", "1-2-3"] {
+        assert_eq!(Cursor::parse(Some(value)), Err(format!("Invalid cursor '{}'", value)));
+    }
+}
+
+#[test]
+fn test_limit_is_clamped() {
+    assert_eq!(clamp_limit(None), DEFAULT_LIMIT);
+    assert_eq!(clamp_limit(Some(0)), 1);
+    assert_eq!(clamp_limit(Some(MAX_LIMIT + 1)), MAX_LIMIT);
+}
+
+#[test]
+fn test_videos_no_longer_public_are_reported_deleted() {
+    assert_eq!(reported_change("created", true), "created");
+    assert_eq!(reported_change("updated", true), "updated");
+    assert_eq!(reported_change("created", false), "deleted");
+    assert_eq!(reported_change("updated", false), "deleted");
+    assert_eq!(reported_change("deleted", true), "deleted");
+}
@@ -0,0 +1,164 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+async fn insert_video(pool: &PgPool, title: &str, moderation_status: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, moderation_status) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(moderation_status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn get_changes(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, since: &str) -> (Vec<serde_json::Value>, String) {
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/changes?since={}", since))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/x-ndjson");
+    let next = resp.headers().get("X-Next-Cursor").unwrap().to_str().unwrap().to_string();
+    let body = test::read_body(resp).await;
+    let lines = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (lines, next)
+}
+
+// Changes are held back while any transaction on the server that started earlier is still
+// running, which other tests' can be, so wait for the expected number to show up
+async fn wait_for_changes(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, since: &str, expected: usize) -> (Vec<serde_json::Value>, String) {
+    for _ in 0..50 {
+        let (lines, next) = get_changes(app, since).await;
+        if lines.len() >= expected {
+            return (lines, next);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    get_changes(app, since).await
+}
+
+#[sqlx::test]
+async fn test_changes_follow_a_video_from_creation_to_deletion(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let video_id = insert_video(&pool, "synced", "approved").await;
+
+    let (lines, cursor) = wait_for_changes(&app, "", 1).await;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["change"], "created");
+    assert_eq!(lines[0]["video_id"], video_id);
+    assert_eq!(lines[0]["video"]["title"], "synced");
+    assert_eq!(lines[0]["cursor"], cursor.as_str());
+
+    // Views alone aren't a change
+    sqlx::query("UPDATE videos SET view_count = 10 WHERE id = $1").bind(video_id).execute(&pool).await.unwrap();
+    sqlx::query("UPDATE videos SET title = 'renamed' WHERE id = $1").bind(video_id).execute(&pool).await.unwrap();
+    let (lines, cursor) = wait_for_changes(&app, &cursor, 1).await;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["change"], "updated");
+    assert_eq!(lines[0]["video"]["title"], "renamed");
+
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1").bind(video_id).execute(&pool).await.unwrap();
+    let (lines, cursor) = wait_for_changes(&app, &cursor, 1).await;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["change"], "deleted");
+    assert!(lines[0]["video"].is_null());
+
+    // Nothing new: the same cursor comes back
+    let (lines, next) = get_changes(&app, &cursor).await;
+    assert!(lines.is_empty());
+    assert_eq!(next, cursor);
+}
+
+#[sqlx::test]
+async fn test_changes_to_videos_that_are_not_public_read_as_deletions(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let pending_id = insert_video(&pool, "pending", "pending").await;
+    let category_id: i32 = sqlx::query_scalar("INSERT INTO categories (name) VALUES ('Changes') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let public_id = insert_video(&pool, "public", "approved").await;
+    sqlx::query("INSERT INTO videos_categories (video_id, category_id) VALUES ($1, $2)")
+        .bind(public_id)
+        .bind(category_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (lines, _) = wait_for_changes(&app, "", 3).await;
+    let changes: Vec<(i64, &str)> = lines.iter().map(|l| (l["video_id"].as_i64().unwrap(), l["change"].as_str().unwrap())).collect();
+    assert_eq!(changes, vec![(pending_id as i64, "deleted"), (public_id as i64, "created"), (public_id as i64, "updated")]);
+    assert_eq!(lines[2]["video"]["category_ids"], json!([category_id]));
+}
+
+#[sqlx::test]
+async fn test_changes_limit_and_invalid_cursor(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    for title in ["first", "second", "third"] {
+        insert_video(&pool, title, "approved").await;
+    }
+    wait_for_changes(&app, "", 3).await;
+
+    let req = test::TestRequest::get().uri("/api/changes?limit=2").to_request();
+    let resp = test::call_service(&app, req).await;
+    let cursor = resp.headers().get("X-Next-Cursor").unwrap().to_str().unwrap().to_string();
+    let body = test::read_body(resp).await;
+    assert_eq!(std::str::from_utf8(&body).unwrap().lines().count(), 2);
+
+    let (lines, _) = get_changes(&app, &cursor).await;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["video"]["title"], "third");
+
+    let req = test::TestRequest::get().uri("/api/changes?since=nope").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use chrono::NaiveDate;
+
+use video_streaming_backend::channels::{arrange, validate_layout, MAX_SECTIONS};
+use video_streaming_backend::models::{ChannelLayout, ChannelLayoutSection, Video};
+
+fn video(id: i32) -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id,
+        title: format!("Video {}", id),
+        description: None,
+        s3_key: format!("videos/{}.mp4", id),
+        thumbnail_url: None,
+        uploaded_by: Some(1),
+        upload_date: Some(uploaded),
+        tags: None,
+        view_count: Some(0),
+        category_ids: None,
+        duration: None,
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: Vec::new(),
+        blocked_countries: Vec::new(),
+        allow_embed: true,
+        allow_download: false,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+fn section(title: &str, video_ids: &[i32]) -> ChannelLayoutSection {
+    ChannelLayoutSection { title: title.to_string(), video_ids: video_ids.to_vec() }
+}
+
+fn ids(videos: &[Video]) -> Vec<i32> {
+    videos.iter().map(|v| v.id).collect()
+}
+
+#[test]
+fn validation_rejects_other_users_videos_and_tidies_the_rest() {
+    let owned: HashSet<i32> = [1, 2, 3].into_iter().collect();
+
+    let layout = ChannelLayout {
+        featured_video_id: Some(2),
+        video_order: vec![3, 1, 3],
+        sections: vec![section("  Tutorials ", &[1, 1, 2])],
+    };
+    let validated = validate_layout(&layout, &owned).unwrap();
+    assert_eq!(validated.video_order, vec![3, 1]);
+    assert_eq!(validated.sections, vec![section("Tutorials", &[1, 2])]);
+
+    let foreign = ChannelLayout { featured_video_id: Some(9), ..ChannelLayout::default() };
+    assert_eq!(validate_layout(&foreign, &owned).unwrap_err(), "Video 9 is not one of your videos");
+    let foreign = ChannelLayout { sections: vec![section("Mine", &[1, 9])], ..ChannelLayout::default() };
+    assert!(validate_layout(&foreign, &owned).is_err());
+
+    let untitled = ChannelLayout { sections: vec![section("  ", &[1])], ..ChannelLayout::default() };
+    assert!(validate_layout(&untitled, &owned).is_err());
+    let too_many = ChannelLayout { sections: vec![section("S", &[1]); MAX_SECTIONS + 1], ..ChannelLayout::default() };
+    assert!(validate_layout(&too_many, &owned).is_err());
+}
+
+#[test]
+fn custom_ordered_videos_come_first() {
+    let layout = ChannelLayout { video_order: vec![2, 4], ..ChannelLayout::default() };
+    let (featured, sections, videos) = arrange(vec![video(5), video(4), video(3), video(2), video(1)], &layout);
+    assert!(featured.is_none());
+    assert!(sections.is_empty());
+    assert_eq!(ids(&videos), vec![2, 4, 5, 3, 1]);
+}
+
+#[test]
+fn missing_videos_are_skipped() {
+    // Video 7 has been deleted or hidden since the layout was saved
+    let layout = ChannelLayout {
+        featured_video_id: Some(7),
+        video_order: vec![7, 1],
+        sections: vec![section("Gone", &[7]), section("Kept", &[7, 2])],
+    };
+    let (featured, sections, videos) = arrange(vec![video(2), video(1)], &layout);
+    assert!(featured.is_none());
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].title, "Kept");
+    assert_eq!(ids(&sections[0].videos), vec![2]);
+    assert_eq!(ids(&videos), vec![1, 2]);
+}
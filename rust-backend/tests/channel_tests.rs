@@ -0,0 +1,177 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(uploaded_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn put_layout(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, layout: serde_json::Value) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::put()
+        .uri("/api/users/me/channel")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(layout)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+fn video_ids(videos: &serde_json::Value) -> Vec<i64> {
+    videos.as_array().unwrap().iter().map(|v| v["id"].as_i64().unwrap()).collect()
+}
+
+#[sqlx::test]
+async fn test_channel_page_uses_the_saved_layout(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "chanowner").await;
+    let first = insert_video(&pool, "chan-first", user_id).await;
+    let second = insert_video(&pool, "chan-second", user_id).await;
+    let third = insert_video(&pool, "chan-third", user_id).await;
+
+    let resp = put_layout(&app, &token, json!({
+        "featuredVideoId": second,
+        "videoOrder": [first],
+        "sections": [{ "title": "Best of", "videoIds": [third, first] }]
+    })).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}/videos", user_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["user"]["username"], "chanowner");
+    assert_eq!(body["featured"]["id"], second);
+    assert_eq!(body["sections"][0]["title"], "Best of");
+    assert_eq!(video_ids(&body["sections"][0]["videos"]), vec![third as i64, first as i64]);
+    // The pinned video leads, the rest follow newest first
+    assert_eq!(video_ids(&body["videos"]), vec![first as i64, third as i64, second as i64]);
+
+    // The owner can read the layout back for editing
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/channel")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["featuredVideoId"], second);
+    assert_eq!(body["videoOrder"], json!([first]));
+
+    // Saving again replaces the whole layout
+    put_layout(&app, &token, json!({ "sections": [] })).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}/videos", user_id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(body["featured"].is_null());
+    assert_eq!(body["sections"], json!([]));
+    assert_eq!(video_ids(&body["videos"]), vec![third as i64, second as i64, first as i64]);
+}
+
+#[sqlx::test]
+async fn test_layout_only_accepts_own_videos(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "chanthief").await;
+    let (other_id, _) = register_test_user(&app, "chanvictim").await;
+    let theirs = insert_video(&pool, "chan-theirs", other_id).await;
+
+    let resp = put_layout(&app, &token, json!({ "featuredVideoId": theirs })).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let resp = put_layout(&app, "", json!({})).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_trashed_featured_video_drops_off_the_page(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "chantrash").await;
+    let featured = insert_video(&pool, "chan-trashed", user_id).await;
+    put_layout(&app, &token, json!({ "featuredVideoId": featured })).await;
+
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1")
+        .bind(featured)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}/videos", user_id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(body["featured"].is_null());
+    assert_eq!(body["videos"], json!([]));
+
+    let req = test::TestRequest::get().uri("/api/users/999999/videos").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
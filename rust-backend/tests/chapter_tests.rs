@@ -0,0 +1,193 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::chapters;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, owner: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ('Chaptered', 'videos/chaptered.mp4', $1) RETURNING id")
+        .bind(owner)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_proposals_hidden_until_accepted(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "chapter_owner").await;
+    let (_, other_token) = register_test_user(&app, "chapter_viewer").await;
+    let video_id = insert_video(&pool, owner_id).await;
+
+    assert_eq!(chapters::store_proposals(&pool, video_id, &[0.0, 60.0, 120.0]).await.unwrap(), 3);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/chapters", video_id))
+        .to_request();
+    let public: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(public.as_array().unwrap().is_empty());
+
+    // Only the owner can see proposals
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/chapters?proposed=true", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/chapters?proposed=true", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let proposed: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let proposed = proposed.as_array().unwrap();
+    assert_eq!(proposed.len(), 3);
+    assert_eq!(proposed[1]["source"], "auto");
+    assert_eq!(proposed[1]["accepted"], false);
+    let second_id = proposed[1]["id"].as_i64().unwrap();
+    let third_id = proposed[2]["id"].as_i64().unwrap();
+
+    // Edit and accept one proposal, reject another
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/chapters/{}", video_id, second_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "title": "The good part", "startTime": 58.5, "accepted": true }))
+        .to_request();
+    let updated: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(updated["title"], "The good part");
+    assert_eq!(updated["start_time"], 58.5);
+    assert_eq!(updated["accepted"], true);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}/chapters/{}", video_id, third_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/chapters", video_id))
+        .to_request();
+    let public: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(public.as_array().unwrap().len(), 1);
+    assert_eq!(public[0]["title"], "The good part");
+
+    // Accept the rest
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/chapters/accept", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let accepted: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let starts: Vec<f64> = accepted.as_array().unwrap().iter().map(|c| c["start_time"].as_f64().unwrap()).collect();
+    assert_eq!(starts, vec![0.0, 58.5]);
+}
+
+#[sqlx::test]
+async fn test_new_proposals_keep_manual_and_accepted_chapters(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "chapter_editor").await;
+    let video_id = insert_video(&pool, owner_id).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/chapters", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "title": "Intro", "startTime": 0 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+    let manual: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(manual["source"], "manual");
+    assert_eq!(manual["accepted"], true);
+
+    chapters::store_proposals(&pool, video_id, &[0.0, 90.0]).await.unwrap();
+    // Running detection again replaces pending proposals; the one at 0 is dropped next to "Intro"
+    chapters::store_proposals(&pool, video_id, &[0.0, 200.0, 400.0]).await.unwrap();
+
+    let all = chapters::list_chapters(&pool, video_id, true).await.unwrap();
+    let starts: Vec<(f64, &str)> = all.iter().map(|c| (c.start_time, c.source.as_str())).collect();
+    assert_eq!(starts, vec![(0.0, "manual"), (200.0, "auto"), (400.0, "auto")]);
+}
+
+#[sqlx::test]
+async fn test_chapter_validation(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "chapter_validator").await;
+    let video_id = insert_video(&pool, owner_id).await;
+
+    for body in [
+        json!({ "title": "", "startTime": 10 }),
+        json!({ "title": "Negative", "startTime": -1 }),
+        json!({ "title": "No time" }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/videos/{}/chapters", video_id))
+            .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/chapters", video_id))
+        .set_json(json!({ "title": "Anonymous", "startTime": 10 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+}
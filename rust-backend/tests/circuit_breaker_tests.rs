@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+use actix_web::ResponseError;
+
+use video_streaming_backend::circuit_breaker::{self, BreakerConfig, BreakerState, CallError, CircuitBreaker};
+use video_streaming_backend::error::ApiError;
+
+const CONFIG: BreakerConfig = BreakerConfig {
+    timeout: Duration::from_millis(50),
+    failure_threshold: 3,
+    open_for: Duration::from_secs(30),
+};
+
+#[test]
+fn opens_after_consecutive_failures_and_probes_once() {
+    let breaker = CircuitBreaker::new("test");
+    let start = Instant::now();
+
+    breaker.record_failure(&CONFIG, start);
+    breaker.record_failure(&CONFIG, start);
+    breaker.record_success();
+    breaker.record_failure(&CONFIG, start);
+    breaker.record_failure(&CONFIG, start);
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    breaker.record_failure(&CONFIG, start);
+    assert_eq!(breaker.state(), BreakerState::Open);
+    assert!(!breaker.allow(&CONFIG, start + Duration::from_secs(29)));
+
+    // After the pause one probe goes through while everyone else still fails fast
+    let later = start + Duration::from_secs(30);
+    assert!(breaker.allow(&CONFIG, later));
+    assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    assert!(!breaker.allow(&CONFIG, later));
+
+    // A failed probe starts another full pause
+    breaker.record_failure(&CONFIG, later);
+    assert_eq!(breaker.state(), BreakerState::Open);
+    assert!(!breaker.allow(&CONFIG, later + Duration::from_secs(29)));
+
+    let probe = later + Duration::from_secs(30);
+    assert!(breaker.allow(&CONFIG, probe));
+    breaker.record_success();
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    assert!(breaker.allow(&CONFIG, probe));
+}
+
+#[test]
+fn an_abandoned_probe_is_replaced_after_the_timeout() {
+    let breaker = CircuitBreaker::new("test");
+    let start = Instant::now();
+    for _ in 0..CONFIG.failure_threshold {
+        breaker.record_failure(&CONFIG, start);
+    }
+    let probe = start + CONFIG.open_for;
+    assert!(breaker.allow(&CONFIG, probe));
+    assert!(!breaker.allow(&CONFIG, probe + Duration::from_millis(10)));
+    assert!(breaker.allow(&CONFIG, probe + CONFIG.timeout));
+}
+
+#[tokio::test]
+async fn slow_calls_time_out_and_fail_fast_once_open() {
+    let breaker = CircuitBreaker::new("Slow");
+    for _ in 0..CONFIG.failure_threshold {
+        let result = breaker
+            .call(&CONFIG, async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok::<_, String>(())
+            }, |_| true)
+            .await;
+        assert!(matches!(result, Err(CallError::TimedOut("Slow"))));
+    }
+
+    let started = Instant::now();
+    let result = breaker.call(&CONFIG, async { Ok::<_, String>(()) }, |_| true).await;
+    assert!(started.elapsed() < CONFIG.timeout);
+    let error = result.unwrap_err().into_api_error(ApiError::Internal);
+    assert_eq!(error.status_code(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn answered_errors_do_not_count_as_outages() {
+    let breaker = CircuitBreaker::new("test");
+    for _ in 0..CONFIG.failure_threshold + 1 {
+        let result = breaker.call(&CONFIG, async { Err::<(), _>("no such key") }, |_| false).await;
+        let error = result.unwrap_err().into_api_error(|e| ApiError::NotFound(e.to_string()));
+        assert_eq!(error.status_code(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+    assert_eq!(breaker.state(), BreakerState::Closed);
+}
+
+#[tokio::test]
+async fn redis_commands_are_timed_out_not_just_the_connect() {
+    // Accepts connections and never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            held.push(socket);
+        }
+    });
+    std::env::set_var("REDIS_TIMEOUT_MS", "200");
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}", port)).unwrap();
+
+    let started = Instant::now();
+    let result: redis::RedisResult<Option<String>> = circuit_breaker::redis(&client, |mut conn| async move {
+        redis::cmd("GET").arg("key").query_async(&mut conn).await
+    })
+    .await;
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::IoError);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
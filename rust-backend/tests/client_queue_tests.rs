@@ -0,0 +1,74 @@
+use futures::StreamExt;
+use video_streaming_backend::client_queue::{client_queue_with, metrics, Backlog, OverflowPolicy};
+
+#[tokio::test]
+async fn test_drop_oldest_keeps_newest_messages() {
+    let (tx, mut rx) = client_queue_with(3, OverflowPolicy::DropOldest);
+    let dropped_before = metrics().dropped_messages;
+
+    for i in 0..5 {
+        assert!(tx.send(format!("msg {}", i)));
+    }
+
+    assert_eq!(rx.recv().await.as_deref(), Some("msg 2"));
+    assert_eq!(rx.recv().await.as_deref(), Some("msg 3"));
+    assert_eq!(rx.recv().await.as_deref(), Some("msg 4"));
+    assert!(!rx.overflowed());
+    assert!(metrics().dropped_messages >= dropped_before + 2);
+}
+
+#[tokio::test]
+async fn test_disconnect_policy_closes_queue() {
+    let (tx, mut rx) = client_queue_with(2, OverflowPolicy::Disconnect);
+    let disconnected_before = metrics().disconnected_clients;
+
+    assert!(tx.send("first".to_string()));
+    assert!(tx.send("second".to_string()));
+    assert!(!tx.send("third".to_string()));
+    assert!(tx.is_closed());
+    assert!(!tx.send("fourth".to_string()));
+
+    assert_eq!(rx.recv().await, None);
+    assert!(rx.overflowed());
+    assert!(metrics().disconnected_clients > disconnected_before);
+}
+
+#[tokio::test]
+async fn test_receiver_wakes_for_new_messages_and_closes_on_drop() {
+    let (tx, mut rx) = client_queue_with(4, OverflowPolicy::DropOldest);
+    let sender = tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        sender.send("late".to_string());
+    });
+    assert_eq!(rx.recv().await.as_deref(), Some("late"));
+    assert!(tx.same_channel(&tx.clone()));
+
+    drop(rx);
+    assert!(tx.is_closed());
+    assert!(!tx.send("nobody listening".to_string()));
+}
+
+#[tokio::test]
+async fn test_backlog_counts_what_the_connection_has_not_taken() {
+    let backlog = Backlog::default();
+    let chunks = (0..3).map(|_| Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"0123456789")));
+    let mut stream = backlog.track(futures::stream::iter(chunks));
+
+    backlog.written(25);
+    assert_eq!(backlog.unsent(), 25);
+    assert!(tokio::time::timeout(std::time::Duration::from_millis(20), backlog.wait_below(20)).await.is_err());
+
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(backlog.unsent(), 15);
+    assert!(backlog.wait_below(20).await);
+    // Frame headers count too, so sent can overtake written
+    stream.next().await.unwrap().unwrap();
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(backlog.unsent(), 0);
+
+    // Waiters give up once the connection is gone
+    backlog.written(100);
+    drop(stream);
+    assert!(!backlog.wait_below(20).await);
+}
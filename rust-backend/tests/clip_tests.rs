@@ -0,0 +1,184 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::clips::{self, ClipFormat};
+use video_streaming_backend::frames;
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::signed_urls;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, uploaded_by: i32, moderation_status: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by, duration, moderation_status) VALUES ('clipped', $1, $2, 60, $3) RETURNING id")
+        .bind(format!("videos/clipped-{}.mp4", moderation_status))
+        .bind(uploaded_by)
+        .bind(moderation_status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn post_clip(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, video_id: i32, body: serde_json::Value) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/gif", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_existing_clips_are_reused(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "clipper").await;
+    let video_id = insert_video(&pool, user_id, "approved").await;
+    let clip = clips::insert_clip(&pool, video_id, user_id, 5000, 2000, ClipFormat::Webp, "clips/x/5000-2000.webp").await.unwrap();
+
+    // Same segment and format: no rendering, the stored clip is returned
+    let resp = post_clip(&app, &token, video_id, json!({ "start": 5, "duration": 2, "format": "webp" })).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], clip.id);
+    assert_eq!(body["start"], 5.0);
+    assert_eq!(body["duration"], 2.0);
+    let url = body["url"].as_str().unwrap();
+    assert!(url.starts_with(&format!("/api/videos/{}/clips/{}?expires=", video_id, clip.id)));
+    assert!(!url.contains("clips/x/"));
+}
+
+#[sqlx::test]
+async fn test_clip_requests_are_validated(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "clip_validation").await;
+    let video_id = insert_video(&pool, user_id, "approved").await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/gif", video_id))
+        .set_json(json!({ "start": 0 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    for body in [
+        json!({ "start": 0, "duration": 30 }),
+        json!({ "start": 90 }),
+        json!({ "start": 0, "format": "mp4" }),
+    ] {
+        let resp = post_clip(&app, &token, video_id, body.clone()).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    // Only the uploader can clip a video that isn't approved
+    let pending_id = insert_video(&pool, user_id, "pending").await;
+    let (_, other_token) = register_test_user(&app, "clip_other").await;
+    let resp = post_clip(&app, &other_token, pending_id, json!({ "start": 0 })).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_clip_urls_must_be_signed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "clip_signed").await;
+    let video_id = insert_video(&pool, user_id, "approved").await;
+    let clip = clips::insert_clip(&pool, video_id, user_id, 0, 3000, ClipFormat::Gif, "clips/y/0-3000.gif").await.unwrap();
+
+    let path = signed_urls::clip_path(video_id, clip.id);
+    let req = test::TestRequest::get().uri(&path).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    // Signed for a different clip
+    let other = signed_urls::signed_url(&signed_urls::clip_path(video_id, clip.id + 1), chrono::Utc::now().timestamp());
+    let query = other.split_once('?').unwrap().1;
+    let req = test::TestRequest::get().uri(&format!("{}?{}", path, query)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_frames_are_checked_and_served_from_storage(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "framer").await;
+    let video_id = insert_video(&pool, user_id, "approved").await;
+    let get_frame = |token: &str, video_id: i32, t: &str| test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/frame?t={}", video_id, t))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let req = test::TestRequest::get().uri(&format!("/api/videos/{}/frame?t=1", video_id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+    assert_eq!(test::call_service(&app, get_frame(&token, video_id, "60")).await.status(), http::StatusCode::BAD_REQUEST);
+    assert_eq!(test::call_service(&app, get_frame(&token, video_id, "-1")).await.status(), http::StatusCode::BAD_REQUEST);
+
+    let pending_id = insert_video(&pool, user_id, "pending").await;
+    let (_, other_token) = register_test_user(&app, "frame_other").await;
+    assert_eq!(test::call_service(&app, get_frame(&other_token, pending_id, "1")).await.status(), http::StatusCode::FORBIDDEN);
+
+    // A frame rendered before is served without touching the video, for any time in its second
+    let bucket = std::env::var("S3_BUCKET").or_else(|_| std::env::var("MINIO_BUCKET")).unwrap_or_else(|_| "videos".to_string());
+    let storage = storage::init_storage().await;
+    storage.put(&bucket, &frames::frame_s3_key(video_id, 12_000), bytes::Bytes::from_static(b"jpeg"), "image/jpeg").await.unwrap();
+    let resp = test::call_service(&app, get_frame(&other_token, video_id, "12.5")).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/jpeg");
+    assert_eq!(test::read_body(resp).await, "jpeg");
+}
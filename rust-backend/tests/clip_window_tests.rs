@@ -0,0 +1,41 @@
+use video_streaming_backend::clips::{clip_filter, clip_s3_key, clip_window, ClipFormat, DEFAULT_CLIP_SECS, MAX_CLIP_SECS};
+
+#[test]
+fn formats_default_to_gif() {
+    assert_eq!(ClipFormat::parse(None), Ok(ClipFormat::Gif));
+    assert_eq!(ClipFormat::parse(Some("WebP")), Ok(ClipFormat::Webp));
+    assert!(ClipFormat::parse(Some("mp4")).is_err());
+    assert_eq!(ClipFormat::Webp.content_type(), "image/webp");
+}
+
+#[test]
+fn windows_are_capped_and_kept_inside_the_video() {
+    assert_eq!(clip_window(1.5, None, None), Ok((1500, (DEFAULT_CLIP_SECS * 1000.0) as i32)));
+    assert_eq!(clip_window(0.0, Some(MAX_CLIP_SECS), Some(60)), Ok((0, 10_000)));
+    // Cut short at the end of the video
+    assert_eq!(clip_window(58.0, Some(5.0), Some(60)), Ok((58_000, 2_000)));
+
+    assert!(clip_window(0.0, Some(MAX_CLIP_SECS + 0.1), None).is_err());
+    assert!(clip_window(0.0, Some(0.1), None).is_err());
+    assert!(clip_window(-1.0, None, None).is_err());
+    assert!(clip_window(f64::NAN, None, None).is_err());
+    assert!(clip_window(60.0, None, Some(60)).is_err());
+    assert!(clip_window(1e12, None, None).is_err());
+}
+
+#[test]
+fn windows_snap_to_half_seconds() {
+    // Nearby requests map to the same clip
+    assert_eq!(clip_window(12.345, Some(2.2), None), Ok((12_000, 2_000)));
+    assert_eq!(clip_window(12.499, Some(1.8), None), Ok((12_000, 2_000)));
+    assert_eq!(clip_window(12.5, Some(0.6), None), Ok((12_500, 500)));
+    assert_eq!(clip_window(59.9, None, Some(60)), Ok((59_500, 500)));
+}
+
+#[test]
+fn gifs_get_their_own_palette() {
+    assert!(clip_filter(ClipFormat::Gif).contains("palettegen"));
+    assert!(!clip_filter(ClipFormat::Webp).contains("palettegen"));
+    assert!(clip_filter(ClipFormat::Webp).starts_with("fps=12,scale='min(480,iw)':-2"));
+    assert_eq!(clip_s3_key(7, 1500, 3000, ClipFormat::Webp), "clips/7/1500-3000.webp");
+}
@@ -0,0 +1,43 @@
+use chrono::NaiveDate;
+
+use video_streaming_backend::comment_export::{format_srt_timestamp, to_srt, to_vtt, CommentExportFormat, ExportedComment};
+
+fn comment(id: i32, username: &str, content: &str, video_time: i32) -> ExportedComment {
+    ExportedComment {
+        id,
+        user_id: 1,
+        username: username.to_string(),
+        content: content.to_string(),
+        video_time,
+        created_at: NaiveDate::from_ymd_opt(2025, 9, 1).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+    }
+}
+
+#[test]
+fn formats_are_parsed_case_insensitively() {
+    assert_eq!(CommentExportFormat::parse(None), Ok(CommentExportFormat::Json));
+    assert_eq!(CommentExportFormat::parse(Some("SRT")), Ok(CommentExportFormat::Srt));
+    assert_eq!(CommentExportFormat::parse(Some("vtt")), Ok(CommentExportFormat::Vtt));
+    assert!(CommentExportFormat::parse(Some("ass")).is_err());
+}
+
+#[test]
+fn srt_numbers_cues_and_uses_comma_timings() {
+    assert_eq!(format_srt_timestamp(3725.5), "01:02:05,500");
+
+    let srt = to_srt(&[comment(7, "ann", "first!", 0), comment(9, "bob", "nice --> part\n\nreally", 65)]);
+    assert_eq!(
+        srt,
+        "1\n00:00:00,000 --> 00:00:05,000\nann: first!\n\n\
+         2\n00:01:05,000 --> 00:01:10,000\nbob: nice -> part\nreally\n\n"
+    );
+}
+
+#[test]
+fn vtt_escapes_markup_in_comments() {
+    let vtt = to_vtt(&[comment(3, "ann", "<b>loud</b> & clear -->", 12)]);
+    assert_eq!(
+        vtt,
+        "WEBVTT\n\n3\n00:00:12.000 --> 00:00:17.000\nann: &lt;b&gt;loud&lt;/b&gt; &amp; clear --&gt;\n"
+    );
+}
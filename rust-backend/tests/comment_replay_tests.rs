@@ -0,0 +1,156 @@
+use actix_web::{web, App};
+use dotenv::dotenv;
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+use video_streaming_backend::comment_replay::{replay_comments, replay_limit, DEFAULT_REPLAY_LIMIT, MAX_REPLAY_LIMIT};
+use video_streaming_backend::handlers;
+use video_streaming_backend::storage;
+use video_streaming_backend::websocket;
+use video_streaming_backend::AppState;
+
+async fn insert_video_with_comments(pool: &PgPool, comments: &[(i32, &str)]) -> i32 {
+    let user_id: i32 = sqlx::query_scalar("INSERT INTO users (username, email, password) VALUES ('replayer', 'replayer@example.com', 'x') RETURNING id")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ('replayed', 'videos/replayed.mp4', $1) RETURNING id")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    // Posted a minute apart, in the order given
+    for (i, (video_time, content)) in comments.iter().enumerate() {
+        sqlx::query("INSERT INTO comments (video_id, user_id, content, video_time, created_at) VALUES ($1, $2, $3, $4, NOW() - make_interval(mins => $5))")
+            .bind(video_id)
+            .bind(user_id)
+            .bind(content)
+            .bind(video_time)
+            .bind((comments.len() - i) as i32)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    video_id
+}
+
+fn contents(comments: &[video_streaming_backend::models::Comment]) -> Vec<&str> {
+    comments.iter().map(|c| c.content.as_str()).collect()
+}
+
+#[test]
+fn replay_limits_are_clamped() {
+    assert_eq!(replay_limit(None), DEFAULT_REPLAY_LIMIT);
+    assert_eq!(replay_limit(Some(0)), 0);
+    assert_eq!(replay_limit(Some(-5)), 0);
+    assert_eq!(replay_limit(Some(10_000)), MAX_REPLAY_LIMIT);
+}
+
+#[sqlx::test]
+async fn test_latest_comments_are_replayed_in_video_order(pool: PgPool) {
+    let video_id = insert_video_with_comments(&pool, &[(50, "oldest"), (40, "older"), (30, "newer"), (10, "newest")]).await;
+
+    let latest = replay_comments(&pool, video_id, None, 3).await.unwrap();
+    assert_eq!(contents(&latest), vec!["newest", "newer", "older"]);
+
+    let none = replay_comments(&pool, video_id + 1, None, 3).await.unwrap();
+    assert!(none.is_empty());
+}
+
+#[sqlx::test]
+async fn test_comments_nearest_the_position_are_replayed(pool: PgPool) {
+    let video_id = insert_video_with_comments(&pool, &[(0, "intro"), (100, "before"), (118, "just before"), (121, "just after"), (150, "after"), (500, "far")]).await;
+
+    let around = replay_comments(&pool, video_id, Some(120), 10).await.unwrap();
+    assert_eq!(contents(&around), vec!["before", "just before", "just after", "after"]);
+
+    // The closest ones win when there are more than the limit
+    let nearest = replay_comments(&pool, video_id, Some(120), 2).await.unwrap();
+    assert_eq!(contents(&nearest), vec!["just before", "just after"]);
+}
+
+// Run the app, including WebSocket routes, on a real port for WebSocket clients
+async fn spawn_test_server(pool: PgPool, test_port: u16) {
+    dotenv().ok();
+    let storage = storage::init_storage().await;
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None,
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+    let (tx, rx) = oneshot::channel::<()>();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build runtime");
+    std::thread::spawn(move || {
+        rt.block_on(async {
+            let server = actix_web::HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(app_state.clone()))
+                    .configure(handlers::configure_routes)
+                    .configure(websocket::configure_ws_routes)
+            })
+            .bind(format!("127.0.0.1:{}", test_port)).expect("Failed to bind to test port")
+            .run();
+            let _ = tx.send(());
+            server.await.expect("Server error");
+        });
+    });
+    let _ = rx.await;
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn next_json<S>(read: &mut S) -> serde_json::Value
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let msg = timeout(Duration::from_secs(5), read.next()).await.expect("No message from the server").unwrap().unwrap();
+        if let Message::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[sqlx::test]
+async fn test_comment_socket_replays_on_connect_and_seek(pool: PgPool) {
+    let video_id = insert_video_with_comments(&pool, &[(5, "start"), (300, "middle"), (600, "end")]).await;
+    let test_port = 8771;
+    spawn_test_server(pool, test_port).await;
+
+    let url = format!("ws://127.0.0.1:{}/api/ws/comments/{}?position=290", test_port, video_id);
+    let (stream, _) = connect_async(url).await.expect("Failed to connect to the comment WebSocket");
+    let (mut write, mut read) = stream.split();
+
+    let replay = next_json(&mut read).await;
+    assert_eq!(replay["type"], "replay");
+    assert_eq!(replay["position"], 290);
+    let replayed: Vec<&str> = replay["comments"].as_array().unwrap().iter().map(|c| c["content"].as_str().unwrap()).collect();
+    assert_eq!(replayed, vec!["middle"]);
+
+    write.send(Message::Text(json!({ "type": "seek", "position": 590 }).to_string())).await.unwrap();
+    let replay = next_json(&mut read).await;
+    assert_eq!(replay["position"], 590);
+    assert_eq!(replay["comments"][0]["content"], "end");
+
+    // Without a position, the latest comments
+    let url = format!("ws://127.0.0.1:{}/api/ws/comments/{}?limit=2", test_port, video_id);
+    let (stream, _) = connect_async(url).await.expect("Failed to connect to the comment WebSocket");
+    let (_, mut read) = stream.split();
+    let replay = next_json(&mut read).await;
+    assert_eq!(replay["position"], serde_json::Value::Null);
+    assert_eq!(replay["comments"].as_array().unwrap().len(), 2);
+
+    let _ = write.send(Message::Close(None)).await;
+}
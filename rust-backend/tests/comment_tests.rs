@@ -10,6 +10,7 @@ use video_streaming_backend::models::{RegisterRequest, CommentRequest};
 use video_streaming_backend::handlers;
 use video_streaming_backend::AppState;
 use video_streaming_backend::services;
+use video_streaming_backend::storage;
 
 async fn setup_test_app() -> impl actix_web::dev::Service<
     actix_http::Request,
@@ -20,12 +21,12 @@ async fn setup_test_app() -> impl actix_web::dev::Service<
     
     // Initialize the database pool and S3 client
     let db_pool = services::init_db_pool().await;
-    let s3_client = services::init_s3_client().await;
+    let storage = storage::init_storage().await;
     
     // Create the app state
     let app_state = Arc::new(Mutex::new(AppState {
         db_pool,
-        s3_client,
+        storage,
         redis_client: None,
         job_queue: None, // No job queue in tests
         video_clients: std::sync::Mutex::new(HashMap::new()),
@@ -247,9 +248,9 @@ async fn test_unauthorized_comment() {
     
     let post_resp = test::call_service(&app, post_req).await;
     
-    // Assert that we get a 403 Forbidden
-    assert_eq!(post_resp.status(), http::StatusCode::FORBIDDEN, 
-        "Expected 403 Forbidden for unauthorized comment, got: {:?}", post_resp.status());
+    // Assert that we get a 401 Unauthorized
+    assert_eq!(post_resp.status(), http::StatusCode::UNAUTHORIZED, 
+        "Expected 401 Unauthorized for unauthorized comment, got: {:?}", post_resp.status());
     
     // Check the error message
     let body = test::read_body(post_resp).await;
@@ -258,6 +259,7 @@ async fn test_unauthorized_comment() {
     assert!(error_json.get("error").is_some(), "Error response missing 'error' field");
     assert!(error_json["error"].as_str().unwrap().contains("Unauthorized"), 
         "Unexpected error message: {}", error_json["error"]);
+    assert_eq!(error_json["code"], "unauthorized");
     
     println!("Successfully tested unauthorized comment rejection");
 }
@@ -299,9 +301,9 @@ async fn test_comment_with_invalid_token() {
     
     let post_resp = test::call_service(&app, post_req).await;
     
-    // Assert that we get a 403 Forbidden
-    assert_eq!(post_resp.status(), http::StatusCode::FORBIDDEN, 
-        "Expected 403 Forbidden for comment with invalid token, got: {:?}", post_resp.status());
+    // Assert that we get a 401 Unauthorized
+    assert_eq!(post_resp.status(), http::StatusCode::UNAUTHORIZED, 
+        "Expected 401 Unauthorized for comment with invalid token, got: {:?}", post_resp.status());
     
     // Check the error message
     let body = test::read_body(post_resp).await;
@@ -310,6 +312,7 @@ async fn test_comment_with_invalid_token() {
     assert!(error_json.get("error").is_some(), "Error response missing 'error' field");
     assert!(error_json["error"].as_str().unwrap().contains("Unauthorized"), 
         "Unexpected error message: {}", error_json["error"]);
+    assert_eq!(error_json["code"], "unauthorized");
     
     println!("Successfully tested comment rejection with invalid token");
 }
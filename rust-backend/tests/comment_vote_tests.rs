@@ -0,0 +1,187 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, duration, moderation_status) VALUES ('Talk', 'videos/talk.mp4', 100, 'approved') RETURNING id")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn insert_comment(pool: &PgPool, video_id: i32, user_id: i32, content: &str, video_time: i32, minutes_ago: i32) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO comments (video_id, user_id, content, video_time, created_at)
+         VALUES ($1, $2, $3, $4, NOW() - make_interval(mins => $5)) RETURNING id"
+    )
+    .bind(video_id)
+    .bind(user_id)
+    .bind(content)
+    .bind(video_time)
+    .bind(minutes_ago)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn vote(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, comment_id: i32, value: i64, token: &str) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/comments/{}/vote", comment_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "value": value }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+async fn contents(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, uri: &str) -> Vec<String> {
+    let resp = test::call_service(app, test::TestRequest::get().uri(uri).to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let comments: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    comments.iter().map(|c| c["content"].as_str().unwrap().to_string()).collect()
+}
+
+#[sqlx::test]
+async fn test_votes_are_counted_once_per_user(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (author_id, author_token) = register_test_user(&app, "voteauthor").await;
+    let (_, voter_token) = register_test_user(&app, "voter").await;
+    let video_id = insert_video(&pool).await;
+    let comment_id = insert_comment(&pool, video_id, author_id, "first", 5, 0).await;
+
+    let (status, comment) = vote(&app, comment_id, 1, &voter_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!((comment["upvotes"].as_i64(), comment["downvotes"].as_i64()), (Some(1), Some(0)));
+
+    // Voting the same way again changes nothing; voting the other way moves the vote
+    let (_, comment) = vote(&app, comment_id, 1, &voter_token).await;
+    assert_eq!((comment["upvotes"].as_i64(), comment["downvotes"].as_i64()), (Some(1), Some(0)));
+    let (_, comment) = vote(&app, comment_id, -1, &voter_token).await;
+    assert_eq!((comment["upvotes"].as_i64(), comment["downvotes"].as_i64()), (Some(0), Some(1)));
+    let (_, comment) = vote(&app, comment_id, 1, &author_token).await;
+    assert_eq!((comment["upvotes"].as_i64(), comment["downvotes"].as_i64()), (Some(1), Some(1)));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/comments/{}/vote", comment_id))
+        .insert_header(("Authorization", format!("Bearer {}", voter_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let comment: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!((comment["upvotes"].as_i64(), comment["downvotes"].as_i64()), (Some(1), Some(0)));
+}
+
+#[sqlx::test]
+async fn test_invalid_votes_are_rejected(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "badvoter").await;
+    let video_id = insert_video(&pool).await;
+    let comment_id = insert_comment(&pool, video_id, user_id, "first", 5, 0).await;
+
+    let (status, _) = vote(&app, comment_id, 2, &token).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    let (status, _) = vote(&app, comment_id + 1000, 1, &token).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/comments/{}/vote", comment_id))
+        .set_json(json!({ "value": 1 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_comments_are_sorted_by_the_requested_order(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "sorter").await;
+    let (_, other_token) = register_test_user(&app, "othersorter").await;
+    let video_id = insert_video(&pool).await;
+    let early = insert_comment(&pool, video_id, user_id, "early", 10, 30).await;
+    let late = insert_comment(&pool, video_id, user_id, "late", 90, 20).await;
+    insert_comment(&pool, video_id, user_id, "middle", 50, 10).await;
+
+    vote(&app, late, 1, &token).await;
+    vote(&app, late, 1, &other_token).await;
+    vote(&app, early, -1, &token).await;
+
+    let uri = format!("/api/comments/{}", video_id);
+    assert_eq!(contents(&app, &uri).await, ["early", "middle", "late"]);
+    assert_eq!(contents(&app, &format!("{}?sort=timestamp", uri)).await, ["early", "middle", "late"]);
+    assert_eq!(contents(&app, &format!("{}?sort=newest", uri)).await, ["middle", "late", "early"]);
+    assert_eq!(contents(&app, &format!("{}?sort=top", uri)).await, ["late", "middle", "early"]);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&format!("{}?sort=best", uri)).to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,225 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::compilations::{self, MAX_ATTEMPTS, STATUS_FAILED, STATUS_PENDING, STATUS_PROCESSING};
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, uploaded_by: i32, name: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by, duration) VALUES ($1, $2, $3, 60) RETURNING id")
+        .bind(name)
+        .bind(format!("videos/{}.mp4", name))
+        .bind(uploaded_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn post_compilation(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, body: serde_json::Value) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::post()
+        .uri("/api/compilations")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_compilation_is_queued_for_owned_videos(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "compiler").await;
+    let first = insert_video(&pool, user_id, "compiled_first").await;
+    let second = insert_video(&pool, user_id, "compiled_second").await;
+
+    let resp = post_compilation(&app, &token, json!({
+        "title": "  Highlights  ",
+        "segments": [
+            { "videoId": first, "start": 1.5, "end": 4 },
+            { "videoId": second, "start": 0, "end": 10 },
+            { "videoId": first, "start": 30, "end": 35 }
+        ]
+    })).await;
+    assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let compilation_id = body["id"].as_i64().unwrap();
+    assert_eq!(body["title"], "Highlights");
+    assert_eq!(body["status"], STATUS_PENDING);
+    assert_eq!(body["progress"], 0);
+    assert!(body["video_id"].is_null());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/compilations/{}", compilation_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["segments"], json!([
+        { "videoId": first, "start": 1.5, "end": 4.0 },
+        { "videoId": second, "start": 0.0, "end": 10.0 },
+        { "videoId": first, "start": 30.0, "end": 35.0 }
+    ]));
+
+    let req = test::TestRequest::get()
+        .uri("/api/compilations")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    // Other users can't see it
+    let (_, other_token) = register_test_user(&app, "compiler_onlooker").await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/compilations/{}", compilation_id))
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_compilation_sources_are_checked(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "compiler_checked").await;
+    let (other_id, _) = register_test_user(&app, "compiler_other").await;
+    let own = insert_video(&pool, user_id, "compiled_own").await;
+    let theirs = insert_video(&pool, other_id, "compiled_theirs").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/compilations")
+        .set_json(json!({ "title": "Anonymous", "segments": [{ "videoId": own, "start": 0, "end": 5 }] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    let resp = post_compilation(&app, &token, json!({
+        "title": "Borrowed",
+        "segments": [{ "videoId": own, "start": 0, "end": 5 }, { "videoId": theirs, "start": 0, "end": 5 }]
+    })).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let resp = post_compilation(&app, &token, json!({ "title": "Missing", "segments": [{ "videoId": 999_999, "start": 0, "end": 5 }] })).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    for body in [
+        json!({ "title": " ", "segments": [{ "videoId": own, "start": 0, "end": 5 }] }),
+        json!({ "title": "Empty", "segments": [] }),
+        json!({ "title": "Too long", "segments": [{ "videoId": own, "start": 50, "end": 70 }] }),
+    ] {
+        let resp = post_compilation(&app, &token, body.clone()).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM compilations").fetch_one(&pool).await.unwrap();
+    assert_eq!(count, 0);
+}
+
+#[sqlx::test]
+async fn test_failed_renders_are_retried_then_given_up(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "compiler_retry").await;
+    let video_id = insert_video(&pool, user_id, "compiled_retry").await;
+    let resp = post_compilation(&app, &token, json!({ "title": "Retry", "segments": [{ "videoId": video_id, "start": 0, "end": 5 }] })).await;
+    assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let compilation = compilations::claim_next(&pool).await.unwrap().unwrap();
+        assert_eq!(compilation.status, STATUS_PROCESSING);
+        assert_eq!(compilation.attempts, attempt);
+        // Claimed compilations aren't handed to another worker
+        assert!(compilations::claim_next(&pool).await.unwrap().is_none());
+        compilations::record_failure(&pool, &compilation, "ffmpeg failed", true).await.unwrap();
+    }
+
+    assert!(compilations::claim_next(&pool).await.unwrap().is_none());
+    let compilation = compilations::get_compilation(&pool, compilations::list_compilations(&pool, user_id).await.unwrap()[0].id, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(compilation.status, STATUS_FAILED);
+    assert_eq!(compilation.last_error.as_deref(), Some("ffmpeg failed"));
+}
+
+#[sqlx::test]
+async fn test_compilation_fails_when_a_source_is_trashed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "compiler_trash").await;
+    let video_id = insert_video(&pool, user_id, "compiled_trashed").await;
+    let resp = post_compilation(&app, &token, json!({ "title": "Trashed", "segments": [{ "videoId": video_id, "start": 0, "end": 5 }] })).await;
+    assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1")
+        .bind(video_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let storage = storage::init_storage().await;
+    assert!(compilations::process_next(&pool, storage.as_ref(), None).await.unwrap());
+    let compilation = compilations::list_compilations(&pool, user_id).await.unwrap().remove(0);
+    assert_eq!(compilation.status, STATUS_FAILED);
+    assert_eq!(compilation.attempts, 1);
+
+    // Nothing left to do
+    assert!(!compilations::process_next(&pool, storage.as_ref(), None).await.unwrap());
+}
@@ -0,0 +1,36 @@
+use video_streaming_backend::compilations::{segment_progress, validate_segments, MAX_SEGMENTS};
+use video_streaming_backend::models::CompilationSegmentRequest;
+
+fn segment(video_id: i32, start: f64, end: f64) -> CompilationSegmentRequest {
+    CompilationSegmentRequest { video_id, start, end }
+}
+
+#[test]
+fn segments_are_stored_in_milliseconds() {
+    assert_eq!(
+        validate_segments(&[segment(3, 1.25, 4.0), segment(7, 0.0, 0.5), segment(3, 10.0, 12.0)]).unwrap(),
+        vec![(3, 1250, 4000), (7, 0, 500), (3, 10000, 12000)]
+    );
+}
+
+#[test]
+fn invalid_segments_are_rejected() {
+    assert!(validate_segments(&[]).is_err());
+    assert!(validate_segments(&vec![segment(1, 0.0, 1.0); MAX_SEGMENTS + 1]).is_err());
+    assert!(validate_segments(&[segment(1, -1.0, 1.0)]).is_err());
+    assert!(validate_segments(&[segment(1, 5.0, 5.2)]).is_err());
+    assert!(validate_segments(&[segment(1, 5.0, 2.0)]).is_err());
+    assert!(validate_segments(&[segment(1, 0.0, f64::NAN)]).is_err());
+
+    // Thirty minutes in total at most
+    assert!(validate_segments(&[segment(1, 0.0, 1200.0), segment(2, 0.0, 600.0)]).is_ok());
+    assert!(validate_segments(&[segment(1, 0.0, 1200.0), segment(2, 0.0, 601.0)]).is_err());
+}
+
+#[test]
+fn cutting_segments_is_most_of_the_progress() {
+    assert_eq!(segment_progress(0, 4), 0);
+    assert_eq!(segment_progress(1, 4), 22);
+    assert_eq!(segment_progress(4, 4), 90);
+    assert_eq!(segment_progress(0, 0), 0);
+}
@@ -0,0 +1,173 @@
+use actix_web::{test, web, App, http, middleware};
+use actix_web::cookie::{Cookie, SameSite};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::auth_cookies;
+use video_streaming_backend::sessions;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+    // Every test in this file runs in cookie auth mode
+    std::env::set_var("AUTH_MODE", "cookie");
+    std::env::set_var("AUTH_COOKIE_SECURE", "false");
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(auth_cookies::check_csrf))
+            .wrap(middleware::from_fn(sessions::check_session))
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Register a user and return (session cookie, CSRF token)
+async fn register_cookie_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (Cookie<'static>, String) {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert!(resp.status().is_success());
+
+    let cookie = resp.response().cookies().find(|c| c.name() == auth_cookies::SESSION_COOKIE).unwrap().into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    (cookie, body["csrfToken"].as_str().unwrap().to_string())
+}
+
+fn save_search_request(cookie: &Cookie<'static>, csrf_token: Option<&str>) -> actix_http::Request {
+    let mut req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .cookie(Cookie::new(auth_cookies::SESSION_COOKIE, cookie.value().to_string()))
+        .set_json(json!({ "name": "cats", "query": "cats" }));
+    if let Some(csrf_token) = csrf_token {
+        req = req.insert_header((auth_cookies::CSRF_HEADER, csrf_token));
+    }
+    req.to_request()
+}
+
+#[sqlx::test]
+async fn test_login_sets_an_http_only_session_cookie(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    register_cookie_user(&app, "cookielogin").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "username": "cookielogin@example.com", "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let cookie = resp.response().cookies().find(|c| c.name() == auth_cookies::SESSION_COOKIE).unwrap().into_owned();
+    assert_eq!(cookie.http_only(), Some(true));
+    assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    assert_eq!(cookie.path(), Some("/"));
+    assert!(cookie.max_age().is_some());
+
+    // The token stays out of reach of scripts
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body.get("token").is_none());
+    assert!(body["csrfToken"].is_string());
+
+    // The cookie alone authenticates reads
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/sessions")
+        .cookie(Cookie::new(auth_cookies::SESSION_COOKIE, cookie.value().to_string()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_state_changes_require_the_csrf_token(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (cookie, csrf_token) = register_cookie_user(&app, "cookiecsrf").await;
+    let (_, other_csrf_token) = register_cookie_user(&app, "cookieother").await;
+
+    let resp = test::call_service(&app, save_search_request(&cookie, None)).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    // Another session's token doesn't work either
+    let resp = test::call_service(&app, save_search_request(&cookie, Some(&other_csrf_token))).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(&app, save_search_request(&cookie, Some(&csrf_token))).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // The token can be fetched again, e.g. after a page reload
+    let req = test::TestRequest::get()
+        .uri("/api/auth/csrf")
+        .cookie(Cookie::new(auth_cookies::SESSION_COOKIE, cookie.value().to_string()))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["csrfToken"], csrf_token.as_str());
+}
+
+#[sqlx::test]
+async fn test_bearer_tokens_skip_the_csrf_check(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (cookie, _) = register_cookie_user(&app, "cookiebearer").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .insert_header(("Authorization", format!("Bearer {}", cookie.value())))
+        .set_json(json!({ "name": "dogs", "query": "dogs" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_logout_clears_the_cookie(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (cookie, csrf_token) = register_cookie_user(&app, "cookielogout").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/logout")
+        .cookie(Cookie::new(auth_cookies::SESSION_COOKIE, cookie.value().to_string()))
+        .insert_header((auth_cookies::CSRF_HEADER, csrf_token.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let removal = resp.response().cookies().find(|c| c.name() == auth_cookies::SESSION_COOKIE).unwrap();
+    assert_eq!(removal.value(), "");
+    assert_eq!(removal.max_age(), Some(actix_web::cookie::time::Duration::ZERO));
+
+    // The old cookie's session is revoked
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/sessions")
+        .cookie(Cookie::new(auth_cookies::SESSION_COOKIE, cookie.value().to_string()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+}
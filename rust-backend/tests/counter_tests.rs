@@ -0,0 +1,81 @@
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use video_streaming_backend::counters::{self, apply_deltas, apply_pending, Counter};
+use video_streaming_backend::models::Video;
+
+async fn insert_video(pool: &PgPool, title: &str, view_count: Option<i32>) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, view_count) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(view_count)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn view_count(pool: &PgPool, video_id: i32) -> Option<i32> {
+    sqlx::query_scalar("SELECT view_count FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn counter_keys_are_per_column() {
+    assert_eq!(Counter::Views.column(), "view_count");
+    assert_eq!(Counter::Views.key(), "counters:view_count");
+    assert_eq!(Counter::Views.flushing_key(), "counters:view_count:flushing");
+    assert_eq!(Counter::Views.flush_lock_key(), "counters:view_count:flush_lock");
+}
+
+#[sqlx::test]
+async fn test_increment_without_redis_writes_to_postgres(pool: PgPool) {
+    let video_id = insert_video(&pool, "counted", Some(3)).await;
+    let never_viewed = insert_video(&pool, "never_viewed", None).await;
+
+    let buffered = counters::increment(&pool, None, Counter::Views, video_id).await.unwrap();
+    assert!(!buffered);
+    counters::increment(&pool, None, Counter::Views, never_viewed).await.unwrap();
+
+    assert_eq!(view_count(&pool, video_id).await, Some(4));
+    assert_eq!(view_count(&pool, never_viewed).await, Some(1));
+}
+
+#[sqlx::test]
+async fn test_apply_deltas_updates_every_video_at_once(pool: PgPool) {
+    let first = insert_video(&pool, "first", Some(10)).await;
+    let second = insert_video(&pool, "second", None).await;
+    let untouched = insert_video(&pool, "untouched", Some(7)).await;
+
+    // A video deleted since it was counted is skipped
+    let updated = apply_deltas(&pool, Counter::Views, &[(first, 5), (second, 2), (999_999, 4)]).await.unwrap();
+    assert_eq!(updated, 2);
+    assert_eq!(view_count(&pool, first).await, Some(15));
+    assert_eq!(view_count(&pool, second).await, Some(2));
+    assert_eq!(view_count(&pool, untouched).await, Some(7));
+
+    assert_eq!(apply_deltas(&pool, Counter::Views, &[]).await.unwrap(), 0);
+}
+
+#[sqlx::test]
+async fn test_pending_increments_are_added_to_responses(pool: PgPool) {
+    let viewed = insert_video(&pool, "viewed", Some(100)).await;
+    let fresh = insert_video(&pool, "fresh", None).await;
+    let quiet = insert_video(&pool, "quiet", Some(8)).await;
+    let mut videos = sqlx::query_as::<_, Video>("SELECT * FROM videos ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+
+    apply_pending(&mut videos, Counter::Views, &HashMap::from([(viewed, 5), (fresh, 2)]));
+    let counts: HashMap<i32, Option<i32>> = videos.iter().map(|v| (v.id, v.view_count)).collect();
+    assert_eq!(counts[&viewed], Some(105));
+    assert_eq!(counts[&fresh], Some(2));
+    assert_eq!(counts[&quiet], Some(8));
+
+    // Without Redis nothing is pending
+    counters::with_pending(None, &mut videos).await;
+    assert_eq!(videos.iter().find(|v| v.id == viewed).unwrap().view_count, Some(105));
+}
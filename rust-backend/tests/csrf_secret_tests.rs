@@ -0,0 +1,17 @@
+use video_streaming_backend::auth_cookies;
+
+// The only test in this binary, so it can clear the environment
+#[test]
+fn csrf_tokens_need_a_configured_secret() {
+    std::env::remove_var("CSRF_SECRET");
+    std::env::remove_var("JWT_SECRET");
+    assert!(auth_cookies::csrf_secret().is_err());
+    assert_eq!(auth_cookies::csrf_token("session-a"), "");
+    assert!(!auth_cookies::verify_csrf_token("session-a", ""));
+
+    std::env::set_var("JWT_SECRET", "jwt_fallback_secret");
+    let token = auth_cookies::csrf_token("session-a");
+    assert!(auth_cookies::verify_csrf_token("session-a", &token));
+    std::env::set_var("CSRF_SECRET", "csrf_secret");
+    assert!(!auth_cookies::verify_csrf_token("session-a", &token));
+}
@@ -0,0 +1,34 @@
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::test::TestRequest;
+
+use video_streaming_backend::auth_cookies;
+
+#[test]
+fn csrf_tokens_are_bound_to_the_session() {
+    std::env::set_var("CSRF_SECRET", "test_csrf_secret");
+    let token = auth_cookies::csrf_token("session-a");
+    assert_eq!(token, auth_cookies::csrf_token("session-a"));
+    assert!(auth_cookies::verify_csrf_token("session-a", &token));
+    assert!(!auth_cookies::verify_csrf_token("session-b", &token));
+    assert!(!auth_cookies::verify_csrf_token("session-a", "not-hex"));
+    assert!(!auth_cookies::verify_csrf_token("session-a", ""));
+}
+
+#[test]
+fn cookie_mode_settings() {
+    std::env::set_var("AUTH_MODE", "cookie");
+    std::env::set_var("AUTH_COOKIE_SAMESITE", "none");
+    std::env::set_var("AUTH_COOKIE_SECURE", "false");
+    assert!(auth_cookies::cookie_mode());
+    assert_eq!(auth_cookies::same_site(), SameSite::None);
+    // SameSite=None cookies have to be Secure regardless
+    assert!(auth_cookies::secure());
+
+    // Only unsafe methods authenticated by the cookie need a CSRF token
+    let cookie = || Cookie::new(auth_cookies::SESSION_COOKIE, "token");
+    assert!(auth_cookies::requires_csrf(&TestRequest::post().uri("/api/videos/1/comments").cookie(cookie()).to_http_request()));
+    assert!(auth_cookies::requires_csrf(&TestRequest::delete().uri("/api/users/me/sessions").cookie(cookie()).to_http_request()));
+    assert!(!auth_cookies::requires_csrf(&TestRequest::get().uri("/api/users/me/sessions").cookie(cookie()).to_http_request()));
+    assert!(!auth_cookies::requires_csrf(&TestRequest::post().uri("/api/auth/login").cookie(cookie()).to_http_request()));
+    assert!(!auth_cookies::requires_csrf(&TestRequest::post().uri("/api/videos/1/comments").to_http_request()));
+}
@@ -0,0 +1,144 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32, allow_download: bool) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, uploaded_by, allow_download) VALUES ($1, $2, $3, $4) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(uploaded_by)
+    .bind(allow_download)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+async fn test_download_requires_uploader_permission(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "dlowner").await;
+    let (_, viewer_token) = register_test_user(&app, "dlviewer").await;
+    let video_id = insert_video(&pool, "download-off", owner_id, false).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download?quality=720p", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_download_unknown_quality(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "dlquality").await;
+    let allowed_id = insert_video(&pool, "download-on", owner_id, true).await;
+    let private_id = insert_video(&pool, "download-private", owner_id, false).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download?quality=4320p", allowed_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // The uploader can always download their own video
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download?quality=4320p", private_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_uploader_toggles_downloads(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "dltoggle").await;
+    let video_id = insert_video(&pool, "download-toggle", owner_id, false).await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/restrictions", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "allowDownload": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(video["allow_download"], true);
+    assert_eq!(video["allow_embed"], true);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download?quality=4320p", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
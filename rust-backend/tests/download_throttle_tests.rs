@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use video_streaming_backend::downloads::{attachment_filename, Throttle};
+
+#[test]
+fn throttle_waits_until_bytes_are_due() {
+    let mut throttle = Throttle::new(1000);
+    // 500 bytes are due at 0.5s; sent after 0.1s, so wait 0.4s
+    assert_eq!(throttle.record(500, Duration::from_millis(100)), Duration::from_millis(400));
+    // 1500 bytes are due at 1.5s
+    assert_eq!(throttle.record(1000, Duration::from_millis(600)), Duration::from_millis(900));
+}
+
+#[test]
+fn throttle_does_not_wait_when_behind_schedule() {
+    let mut throttle = Throttle::new(1000);
+    assert_eq!(throttle.record(500, Duration::from_secs(2)), Duration::ZERO);
+}
+
+#[test]
+fn zero_limit_disables_throttling() {
+    let mut throttle = Throttle::new(0);
+    assert_eq!(throttle.record(10_000_000, Duration::ZERO), Duration::ZERO);
+}
+
+#[test]
+fn attachment_filenames_are_header_safe() {
+    assert_eq!(attachment_filename("My Trip", Some("720p"), "mp4"), "My Trip (720p).mp4");
+    assert_eq!(attachment_filename("a \"quoted\"/title", None, "webm"), "a _quoted__title.webm");
+    assert_eq!(attachment_filename("日本", None, "mp4"), "video.mp4");
+}
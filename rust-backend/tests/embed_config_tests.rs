@@ -0,0 +1,116 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use video_streaming_backend::embed::{self as embed_config, EmbedConfig};
+use video_streaming_backend::feature_flags::{self, FeatureFlag, Flags};
+use video_streaming_backend::models::{Video, VideoChapter};
+
+fn now() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2025, 9, 18).unwrap().and_hms_opt(12, 0, 0).unwrap()
+}
+
+fn video() -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id: 5,
+        title: "Launch day".to_string(),
+        description: None,
+        s3_key: "videos/5-original.mp4".to_string(),
+        thumbnail_url: Some("thumbnails/5.jpg".to_string()),
+        uploaded_by: Some(3),
+        upload_date: Some(uploaded),
+        tags: None,
+        view_count: None,
+        category_ids: None,
+        duration: Some(95),
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: Vec::new(),
+        blocked_countries: Vec::new(),
+        allow_embed: true,
+        allow_download: true,
+        processing_status: "ready".to_string(),
+        loudness_lufs: Some(-14.2),
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+fn chapter(title: &str, start_time: f64, accepted: bool) -> VideoChapter {
+    VideoChapter {
+        id: 1,
+        video_id: 5,
+        title: title.to_string(),
+        start_time,
+        source: if accepted { "manual" } else { "auto" }.to_string(),
+        accepted,
+        created_at: now(),
+        updated_at: now(),
+    }
+}
+
+fn config(video: &Video, chapters: &[VideoChapter], flags: &Flags, now: NaiveDateTime) -> EmbedConfig {
+    embed_config::config(video, chapters, None, flags, "https://videos.example.com", now)
+}
+
+#[test]
+fn plain_videos_stream_with_signed_urls() {
+    let chapters = [chapter("Intro", 0.0, true), chapter("Scene 2", 41.5, false)];
+    let embed = config(&video(), &chapters, &Flags::from_rows(Vec::new()), now());
+
+    assert_eq!(embed.watch_url, "https://videos.example.com/video/5");
+    let stream_url = embed.stream_url.unwrap();
+    assert!(stream_url.starts_with("/api/videos/5/stream?expires="), "{}", stream_url);
+    assert!(stream_url.contains("&signature="));
+    assert!(embed.hls_url.is_none());
+    assert!(embed.poster_url.unwrap().starts_with("/api/videos/5/thumbnail?v="));
+
+    // Proposed auto chapters aren't shown to viewers
+    assert_eq!(embed.chapters.len(), 1);
+    assert_eq!(embed.chapters[0].title, "Intro");
+    assert!(embed.captions.is_empty());
+    assert!(embed.features.download && embed.features.comments && embed.features.watch_party);
+    assert!(!embed.features.requires_sign_in);
+}
+
+#[test]
+fn encrypted_videos_play_through_hls() {
+    let mut encrypted = video();
+    encrypted.hls_encrypted = true;
+    encrypted.allow_download = false;
+    let embed = config(&encrypted, &[], &Flags::from_rows(Vec::new()), now());
+
+    assert!(embed.stream_url.is_none());
+    assert!(embed.hls_url.unwrap().starts_with("/api/videos/5/hls/index.m3u8?expires="));
+    assert!(embed.features.requires_sign_in);
+    assert!(!embed.features.download);
+}
+
+#[test]
+fn upcoming_premieres_are_marked_for_the_uploaders_preview() {
+    let mut premiering = video();
+    premiering.premiere_at = Some(now() + chrono::Duration::hours(2));
+    let embed = config(&premiering, &[], &Flags::from_rows(Vec::new()), now());
+    assert!(embed.stream_url.is_some());
+    assert_eq!(embed.premiere_at, premiering.premiere_at);
+
+    // Once it has started it plays like any other video
+    let embed = config(&premiering, &[], &Flags::from_rows(Vec::new()), now() + chrono::Duration::hours(3));
+    assert!(embed.stream_url.is_some());
+    assert!(embed.premiere_at.is_none());
+}
+
+#[test]
+fn features_follow_the_feature_flags() {
+    let off = |name: &str| FeatureFlag { name: name.to_string(), enabled: false, message: None, updated_by: None, updated_at: Some(now()) };
+    let flags = Flags::from_rows(vec![off(feature_flags::COMMENTS), off(feature_flags::WATCH_PARTIES)]);
+    let embed = config(&video(), &[], &flags, now());
+    assert!(!embed.features.comments);
+    assert!(!embed.features.watch_party);
+}
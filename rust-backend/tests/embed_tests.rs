@@ -0,0 +1,164 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32, allow_embed: bool) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, thumbnail_url, uploaded_by, allow_embed) VALUES ($1, $2, $3, $4, $5) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(format!("thumbnails/{}.jpg", title))
+    .bind(uploaded_by)
+    .bind(allow_embed)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+async fn test_embed_config_has_media_captions_and_chapters(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "embedauthor").await;
+    let video_id = insert_video(&pool, "embed-video", owner_id, true).await;
+
+    sqlx::query("INSERT INTO video_chapters (video_id, title, start_time) VALUES ($1, 'Intro', 0), ($1, 'Demo', 42.5)")
+        .bind(video_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO video_transcripts (video_id, backend, language, segments, vtt, content) VALUES ($1, 'api', 'en', '[]', 'WEBVTT', '')"
+    )
+    .bind(video_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/embed/{}/config", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["title"], "embed-video");
+    assert!(body["stream_url"].as_str().unwrap().starts_with(&format!("/api/videos/{}/stream?", video_id)));
+    assert!(body["hls_url"].is_null());
+    assert!(body["poster_url"].as_str().unwrap().starts_with(&format!("/api/videos/{}/thumbnail?", video_id)));
+    assert_eq!(body["captions"], json!([{
+        "language": "en",
+        "auto_generated": true,
+        "url": format!("/api/videos/{}/transcript?format=vtt", video_id)
+    }]));
+    assert_eq!(body["chapters"], json!([{ "title": "Intro", "start_time": 0.0 }, { "title": "Demo", "start_time": 42.5 }]));
+    assert_eq!(body["audio_tracks"], json!([]));
+    assert!(body["spherical"].is_null());
+    assert_eq!(body["features"]["download"], true);
+}
+
+#[sqlx::test]
+async fn test_embed_config_refuses_unembeddable_videos(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "embedprivate").await;
+    let video_id = insert_video(&pool, "embed-private", owner_id, false).await;
+
+    for (id, expected) in [(video_id, http::StatusCode::FORBIDDEN), (999_999, http::StatusCode::NOT_FOUND)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/embed/{}/config", id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+    }
+}
+
+#[sqlx::test]
+async fn test_embed_config_waits_for_the_premiere(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "embedpremiere").await;
+    let video_id = insert_video(&pool, "embed-premiere", owner_id, true).await;
+    sqlx::query("UPDATE videos SET premiere_at = $1 WHERE id = $2")
+        .bind(chrono::Utc::now().naive_utc() + chrono::Duration::hours(2))
+        .bind(video_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/embed/{}/config", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    // The uploader previews it
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/embed/{}/config", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["stream_url"].is_string());
+    assert!(body["premiere_at"].is_string());
+}
@@ -0,0 +1,71 @@
+use sqlx::PgPool;
+
+use video_streaming_backend::encoding_ladder::{self, classify, frame_difference, motion, select_ladder, Complexity, SourceStats};
+
+fn stats(motion: Option<f64>, bitrate: Option<u64>) -> SourceStats {
+    SourceStats { width: Some(1920), height: Some(1080), fps: Some(30.0), bitrate, motion }
+}
+
+#[test]
+fn motion_is_the_mean_frame_difference() {
+    assert_eq!(frame_difference(&[0, 0, 255, 255], &[0, 0, 255, 255]), Some(0.0));
+    assert_eq!(frame_difference(&[0, 0, 0, 0], &[255, 255, 0, 0]), Some(0.5));
+    // A frame past the end of the video
+    assert_eq!(frame_difference(&[10, 20], &[]), None);
+
+    assert_eq!(motion(&[0.25, 0.75]), Some(0.5));
+    assert_eq!(motion(&[]), None);
+}
+
+#[test]
+fn sources_are_classified_by_motion_then_bitrate() {
+    // 1080p30 is about 62 million pixels a second
+    assert!((stats(None, Some(6_220_800)).bits_per_pixel().unwrap() - 0.1).abs() < 1e-9);
+    assert_eq!(SourceStats { fps: None, ..stats(None, Some(1_000_000)) }.bits_per_pixel(), None);
+
+    assert_eq!(classify(&stats(Some(0.1), Some(500_000))), Complexity::High);
+    assert_eq!(classify(&stats(Some(0.005), Some(20_000_000))), Complexity::Low);
+    assert_eq!(classify(&stats(Some(0.03), Some(1_000_000))), Complexity::Low);
+    assert_eq!(classify(&stats(Some(0.03), Some(8_000_000))), Complexity::Medium);
+    assert_eq!(classify(&SourceStats::default()), Complexity::Medium);
+
+    assert_eq!(Complexity::Medium.crf(), 23);
+    assert!(Complexity::Low.crf() > Complexity::Medium.crf());
+    assert!(Complexity::High.crf() < Complexity::Medium.crf());
+}
+
+#[test]
+fn ladders_never_upscale_and_thin_out_for_simple_content() {
+    let configured = [480, 1080, 360, 720, 720];
+    assert_eq!(select_ladder(&configured, Some(1080), Complexity::Medium), vec![1080, 720, 480, 360]);
+    assert_eq!(select_ladder(&configured, Some(1080), Complexity::High), vec![1080, 720, 480, 360]);
+    assert_eq!(select_ladder(&configured, Some(1080), Complexity::Low), vec![1080, 360]);
+    assert_eq!(select_ladder(&configured, Some(600), Complexity::Low), vec![480, 360]);
+    assert_eq!(select_ladder(&configured, None, Complexity::Medium), vec![1080, 720, 480, 360]);
+    assert_eq!(select_ladder(&configured, Some(240), Complexity::Medium), Vec::<i32>::new());
+}
+
+#[sqlx::test]
+async fn test_profile_is_replaced_on_reanalysis(pool: PgPool) {
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ('laddered', 'videos/laddered.mp4') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(encoding_ladder::profile(&pool, video_id).await.unwrap().is_none());
+
+    let slides = stats(Some(0.002), Some(800_000));
+    encoding_ladder::save_profile(&pool, video_id, &slides, Complexity::Low, &[1080, 360]).await.unwrap();
+    let profile = encoding_ladder::profile(&pool, video_id).await.unwrap().unwrap();
+    assert_eq!(profile.complexity, "low");
+    assert_eq!(profile.heights, vec![1080, 360]);
+    assert_eq!(profile.crf, Complexity::Low.crf());
+    assert_eq!(profile.bitrate_kbps, Some(800));
+
+    let action = stats(Some(0.2), None);
+    encoding_ladder::save_profile(&pool, video_id, &action, Complexity::High, &[1080, 720, 480]).await.unwrap();
+    let profile = encoding_ladder::profile(&pool, video_id).await.unwrap().unwrap();
+    assert_eq!(profile.complexity, "high");
+    assert_eq!(profile.heights, vec![1080, 720, 480]);
+    assert_eq!(profile.bitrate_kbps, None);
+    assert_eq!(profile.motion, Some(0.2));
+}
@@ -0,0 +1,50 @@
+use video_streaming_backend::experiments::{bucket, pick, validate};
+use video_streaming_backend::models::{ExperimentRequest, ExperimentVariant};
+
+fn variants(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+fn request(key: &str, variants: &[(&str, i32)]) -> ExperimentRequest {
+    ExperimentRequest {
+        key: key.to_string(),
+        description: None,
+        variants: variants.iter().map(|(name, weight)| ExperimentVariant { name: name.to_string(), weight: *weight }).collect(),
+    }
+}
+
+#[test]
+fn test_buckets_are_stable_and_differ_between_experiments() {
+    assert_eq!(bucket("search_ranking", 42), bucket("search_ranking", 42));
+    assert!(bucket("search_ranking", 42) < 10_000);
+    let moved = (1..100).filter(|user_id| bucket("search_ranking", *user_id) != bucket("home_feed_ranking", *user_id)).count();
+    assert!(moved > 90);
+}
+
+#[test]
+fn test_variants_get_buckets_by_weight() {
+    let names = variants(&["control", "recent"]);
+    assert_eq!(pick(&names, &[1, 3], 0), Some("control"));
+    assert_eq!(pick(&names, &[1, 3], 2499), Some("control"));
+    assert_eq!(pick(&names, &[1, 3], 2500), Some("recent"));
+    assert_eq!(pick(&names, &[1, 3], 9999), Some("recent"));
+    assert_eq!(pick(&names, &[0, 5], 0), Some("recent"));
+    assert_eq!(pick(&names, &[0, 0], 0), None);
+}
+
+#[test]
+fn test_users_split_roughly_evenly() {
+    let names = variants(&["control", "treatment"]);
+    let treated = (1..=10_000).filter(|user_id| pick(&names, &[1, 1], bucket("even_split", *user_id)) == Some("treatment")).count();
+    assert!((4_700..=5_300).contains(&treated), "{}", treated);
+}
+
+#[test]
+fn test_experiment_definitions_are_validated() {
+    assert!(validate(&request("search_ranking", &[("control", 1), ("most_viewed", 1)])).is_ok());
+    assert!(validate(&request("Search Ranking", &[("control", 1), ("b", 1)])).is_err());
+    assert!(validate(&request("one_variant", &[("control", 1)])).is_err());
+    assert_eq!(validate(&request("twice", &[("a", 1), ("a", 1)])), Err("Variant 'a' is listed twice".to_string()));
+    assert!(validate(&request("negative", &[("a", 1), ("b", -1)])).is_err());
+    assert!(validate(&request("nobody", &[("a", 0), ("b", 0)])).is_err());
+}
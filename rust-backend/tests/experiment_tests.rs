@@ -0,0 +1,189 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn insert_video(pool: &PgPool, title: &str, view_count: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, duration, view_count, moderation_status) VALUES ($1, $2, 100, $3, 'approved') RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(view_count)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn admin_request(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, req: test::TestRequest, token: &str) -> (http::StatusCode, serde_json::Value) {
+    let req = req.insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+async fn home_feed(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: Option<&str>) -> Vec<i64> {
+    let mut req = test::TestRequest::get().uri("/api/feed/home");
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let videos: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    videos.iter().map(|v| v["id"].as_i64().unwrap()).collect()
+}
+
+#[sqlx::test]
+async fn test_home_feed_ranking_follows_the_users_variant(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "expadmin").await;
+    make_admin(&pool, admin_id).await;
+    let (_, viewer_token) = register_test_user(&app, "expviewer").await;
+    let older_popular = insert_video(&pool, "older-popular", 1000).await;
+    sqlx::query("UPDATE videos SET upload_date = NOW() - INTERVAL '1 day' WHERE id = $1")
+        .bind(older_popular)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let newer = insert_video(&pool, "newer", 1).await;
+
+    // Without the experiment everyone gets control
+    assert_eq!(home_feed(&app, Some(&viewer_token)).await, vec![older_popular as i64, newer as i64]);
+
+    // Everybody in the recent variant
+    let (status, report) = admin_request(&app, test::TestRequest::post().uri("/api/admin/experiments").set_json(json!({
+        "key": "home_feed_ranking",
+        "description": "Newest first",
+        "variants": [{ "name": "control", "weight": 0 }, { "name": "recent", "weight": 1 }]
+    })), &admin_token).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    assert_eq!(report["active"], true);
+
+    assert_eq!(home_feed(&app, Some(&viewer_token)).await, vec![newer as i64, older_popular as i64]);
+    home_feed(&app, Some(&viewer_token)).await;
+    // Anonymous visitors aren't part of it
+    assert_eq!(home_feed(&app, None).await, vec![older_popular as i64, newer as i64]);
+
+    let (status, report) = admin_request(&app, test::TestRequest::get().uri("/api/admin/experiments/home_feed_ranking"), &admin_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(report["assignments"], json!([
+        { "variant": "control", "users": 0, "exposures": 0 },
+        { "variant": "recent", "users": 1, "exposures": 2 }
+    ]));
+
+    // Stopped experiments go back to control
+    let (status, report) = admin_request(&app, test::TestRequest::patch().uri("/api/admin/experiments/home_feed_ranking").set_json(json!({ "active": false })), &admin_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(report["active"], false);
+    assert_eq!(home_feed(&app, Some(&viewer_token)).await, vec![older_popular as i64, newer as i64]);
+}
+
+#[sqlx::test]
+async fn test_experiment_admin_endpoints(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "expadmin").await;
+    make_admin(&pool, admin_id).await;
+    let (_, user_token) = register_test_user(&app, "expuser").await;
+    let definition = json!({
+        "key": "search_ranking",
+        "variants": [{ "name": "control", "weight": 1 }, { "name": "most_viewed", "weight": 1 }]
+    });
+
+    let (status, _) = admin_request(&app, test::TestRequest::post().uri("/api/admin/experiments").set_json(&definition), &user_token).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+
+    let (status, _) = admin_request(&app, test::TestRequest::post().uri("/api/admin/experiments").set_json(&definition), &admin_token).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    let (status, _) = admin_request(&app, test::TestRequest::post().uri("/api/admin/experiments").set_json(&definition), &admin_token).await;
+    assert_eq!(status, http::StatusCode::CONFLICT);
+
+    let (status, _) = admin_request(&app, test::TestRequest::post().uri("/api/admin/experiments").set_json(json!({
+        "key": "lonely",
+        "variants": [{ "name": "control", "weight": 1 }]
+    })), &admin_token).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let (status, list) = admin_request(&app, test::TestRequest::get().uri("/api/admin/experiments"), &admin_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["key"], "search_ranking");
+    assert_eq!(list[0]["variants"], json!(["control", "most_viewed"]));
+
+    let (status, _) = admin_request(&app, test::TestRequest::get().uri("/api/admin/experiments/missing"), &admin_token).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+}
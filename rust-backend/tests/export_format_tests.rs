@@ -0,0 +1,55 @@
+use chrono::NaiveDate;
+
+use video_streaming_backend::exports::{build_query, csv_line, dataset, date_range, select_fields, ExportFormat};
+
+#[test]
+fn csv_values_are_quoted_only_when_needed() {
+    let line = csv_line([Some("plain"), Some("a, b"), Some("say \"hi\""), None, Some("two\nlines")]);
+    assert_eq!(line, "plain,\"a, b\",\"say \"\"hi\"\"\",,\"two\nlines\"\r\n");
+}
+
+#[test]
+fn csv_values_that_look_like_formulas_are_defused() {
+    let line = csv_line([Some("=HYPERLINK(\"http://x\")"), Some("+1"), Some("-2.5"), Some("@SUM(A1)"), Some("-x"), Some("a=b")]);
+    assert_eq!(line, "\"'=HYPERLINK(\"\"http://x\"\")\",+1,-2.5,'@SUM(A1),'-x,a=b\r\n");
+}
+
+#[test]
+fn fields_are_validated_and_kept_in_request_order() {
+    let videos = dataset("videos").unwrap();
+    let all = select_fields(videos, None).unwrap();
+    assert_eq!(all.len(), videos.fields.len());
+
+    let picked = select_fields(videos, Some("title, id,title")).unwrap();
+    assert_eq!(picked.iter().map(|f| f.name).collect::<Vec<_>>(), vec!["title", "id"]);
+
+    let err = select_fields(videos, Some("id,password")).unwrap_err();
+    assert!(err.contains("Unknown videos field 'password'"));
+    assert!(select_fields(dataset("users").unwrap(), Some("password")).is_err());
+    assert!(dataset("comments").is_none());
+}
+
+#[test]
+fn date_filters_include_the_whole_to_day() {
+    let (from, to) = date_range(Some("2025-08-01"), Some("2025-08-31")).unwrap();
+    assert_eq!(from, NaiveDate::from_ymd_opt(2025, 8, 1).unwrap().and_hms_opt(0, 0, 0));
+    assert_eq!(to, NaiveDate::from_ymd_opt(2025, 9, 1).unwrap().and_hms_opt(0, 0, 0));
+
+    assert_eq!(date_range(None, None).unwrap(), (None, None));
+    assert!(date_range(Some("08/01/2025"), None).is_err());
+    assert!(date_range(Some("2025-09-01"), Some("2025-08-01")).is_err());
+}
+
+#[test]
+fn formats_select_json_or_text() {
+    assert_eq!(ExportFormat::parse(None).unwrap(), ExportFormat::Csv);
+    assert_eq!(ExportFormat::parse(Some("NDJSON")).unwrap(), ExportFormat::Ndjson);
+    assert!(ExportFormat::parse(Some("xml")).is_err());
+
+    let videos = dataset("videos").unwrap();
+    let fields = select_fields(videos, Some("id,tags")).unwrap();
+    let ndjson = build_query(videos, &fields, ExportFormat::Ndjson);
+    assert!(ndjson.starts_with("SELECT json_build_object('id', v.id, 'tags', v.tags)::text FROM videos v"));
+    let csv = build_query(videos, &fields, ExportFormat::Csv);
+    assert!(csv.starts_with("SELECT (v.id)::text, array_to_string(v.tags, ';') FROM videos v"));
+}
@@ -0,0 +1,168 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn export(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, uri: &str) -> (http::StatusCode, Option<String>, String) {
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let content_type = resp.headers().get("content-type").map(|h| h.to_str().unwrap().to_string());
+    let body = test::read_body(resp).await;
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[sqlx::test]
+async fn test_exports_are_admin_only(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "exportuser").await;
+
+    let (status, _, _) = export(&app, &token, "/api/admin/exports/videos").await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_video_export_as_csv_with_selected_fields(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, token) = register_test_user(&app, "exportadmin").await;
+    make_admin(&pool, admin_id).await;
+    sqlx::query(
+        "INSERT INTO videos (title, s3_key, uploaded_by, tags, upload_date) VALUES
+         ('Old, but gold', 'videos/old.mp4', $1, ARRAY['a', 'b'], '2025-07-15 10:00:00'),
+         ('Recent', 'videos/recent.mp4', $1, NULL, '2025-08-02 10:00:00')"
+    )
+    .bind(admin_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let (status, content_type, body) = export(
+        &app, &token, "/api/admin/exports/videos?fields=title,uploader,tags&from=2025-07-01&to=2025-07-31"
+    ).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("text/csv; charset=utf-8"));
+    assert_eq!(body, "title,uploader,tags\r\n\"Old, but gold\",exportadmin,a;b\r\n");
+
+    let (status, _, body) = export(&app, &token, "/api/admin/exports/videos?fields=id,password").await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert!(body.contains("password"));
+
+    let (status, _, _) = export(&app, &token, "/api/admin/exports/secrets").await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_view_and_user_exports_as_ndjson(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, token) = register_test_user(&app, "viewadmin").await;
+    make_admin(&pool, admin_id).await;
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ('Watched', 'videos/watched.mp4') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    // Fetching a video records a view, attributed to the signed-in viewer
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+    let req = test::TestRequest::get().uri(&format!("/api/videos/{}", video_id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    let (status, content_type, body) = export(
+        &app, &token, "/api/admin/exports/views?format=ndjson&fields=video_id,video_title,username"
+    ).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/x-ndjson"));
+    let rows: Vec<serde_json::Value> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(rows, vec![
+        json!({ "video_id": video_id, "video_title": "Watched", "username": "viewadmin" }),
+        json!({ "video_id": video_id, "video_title": "Watched", "username": null }),
+    ]);
+
+    let (_, _, body) = export(&app, &token, "/api/admin/exports/users?format=ndjson").await;
+    let user: serde_json::Value = body
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .find(|user| user["id"] == admin_id)
+        .unwrap();
+    assert_eq!(user["username"], "viewadmin");
+    assert_eq!(user["is_admin"], true);
+    assert!(user.get("password").is_none());
+}
@@ -0,0 +1,62 @@
+use actix_web::http::Method;
+
+use video_streaming_backend::feature_flags::{blocking_flag, unavailable_message, FeatureFlag, Flags, COMMENTS, MAINTENANCE, UPLOADS, WATCH_PARTIES};
+
+fn flag(name: &str, enabled: bool) -> FeatureFlag {
+    FeatureFlag { name: name.to_string(), enabled, message: None, updated_by: Some(1), updated_at: None }
+}
+
+fn blocked_by(flags: &Flags, method: Method, path: &str) -> Option<String> {
+    blocking_flag(flags, &method, path).map(|flag| flag.name.clone())
+}
+
+#[test]
+fn flags_without_rows_have_their_defaults() {
+    let flags = Flags::from_rows(vec![flag(UPLOADS, false), flag("retired", true)]);
+    assert!(!flags.is_enabled(UPLOADS));
+    assert!(flags.is_enabled(COMMENTS));
+    assert!(!flags.is_enabled(MAINTENANCE));
+    assert!(flags.get("retired").is_none());
+    assert_eq!(flags.flags.len(), 5);
+}
+
+#[test]
+fn everything_passes_by_default() {
+    let flags = Flags::from_rows(Vec::new());
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/uploads"), None);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/comments/1"), None);
+    assert_eq!(blocked_by(&flags, Method::DELETE, "/api/videos/1"), None);
+}
+
+#[test]
+fn subsystems_that_are_off_are_refused() {
+    let flags = Flags::from_rows(vec![flag(UPLOADS, false), flag(WATCH_PARTIES, false), flag(COMMENTS, false)]);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/uploads").as_deref(), Some(UPLOADS));
+    assert_eq!(blocked_by(&flags, Method::HEAD, "/api/uploads/abc").as_deref(), Some(UPLOADS));
+    assert_eq!(blocked_by(&flags, Method::GET, "/api/watchparty/3").as_deref(), Some(WATCH_PARTIES));
+    assert_eq!(blocked_by(&flags, Method::GET, "/api/ws/watchparty/3").as_deref(), Some(WATCH_PARTIES));
+    assert_eq!(blocked_by(&flags, Method::PUT, "/api/comments/9/vote").as_deref(), Some(COMMENTS));
+
+    // Comments can still be read, and similar paths aren't caught
+    assert_eq!(blocked_by(&flags, Method::GET, "/api/comments/3"), None);
+    assert_eq!(blocked_by(&flags, Method::GET, "/api/uploadsfoo"), None);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/videos/search"), None);
+}
+
+#[test]
+fn maintenance_refuses_writes_outside_admin_and_sign_in() {
+    let flags = Flags::from_rows(vec![flag(MAINTENANCE, true)]);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/videos/search").as_deref(), Some(MAINTENANCE));
+    assert_eq!(blocked_by(&flags, Method::PATCH, "/api/videos/1").as_deref(), Some(MAINTENANCE));
+    assert_eq!(blocked_by(&flags, Method::GET, "/api/videos/1"), None);
+    assert_eq!(blocked_by(&flags, Method::PUT, "/api/admin/feature-flags/maintenance"), None);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/auth/login"), None);
+    assert_eq!(blocked_by(&flags, Method::POST, "/api/auth/register").as_deref(), Some(MAINTENANCE));
+}
+
+#[test]
+fn messages_default_per_flag() {
+    assert!(unavailable_message(&flag(MAINTENANCE, true)).contains("maintenance"));
+    let custom = FeatureFlag { message: Some("Back at 6pm UTC".to_string()), ..flag(UPLOADS, false) };
+    assert_eq!(unavailable_message(&custom), "Back at 6pm UTC");
+}
@@ -0,0 +1,157 @@
+use actix_web::{middleware, test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::feature_flags;
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(feature_flags::check_features))
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn set_flag(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, name: &str, body: serde_json::Value, token: &str) -> http::StatusCode {
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/admin/feature-flags/{}", name))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await.status()
+}
+
+async fn create_upload(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::post()
+        .uri("/api/uploads")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "filename": "talk.mp4", "size": 1024, "title": "Talk" }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+// One test, as the flags are cached for the whole test binary
+#[sqlx::test]
+async fn test_admins_toggle_subsystems_and_maintenance(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "flagadmin").await;
+    make_admin(&pool, admin_id).await;
+    let (_, user_token) = register_test_user(&app, "flaguser").await;
+
+    assert_eq!(set_flag(&app, "uploads", json!({ "enabled": false }), &user_token).await, http::StatusCode::FORBIDDEN);
+    assert_eq!(set_flag(&app, "teleport", json!({ "enabled": false }), &admin_token).await, http::StatusCode::NOT_FOUND);
+
+    // Uploads off: refused with the admin's message, and reported to clients
+    assert_eq!(set_flag(&app, "uploads", json!({ "enabled": false, "message": "Storage migration" }), &admin_token).await, http::StatusCode::OK);
+    let (status, body) = create_upload(&app, &user_token).await;
+    assert_eq!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "Storage migration");
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/features").to_request()).await;
+    let features: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(features["uploads"], json!({ "enabled": false, "message": "Storage migration" }));
+    assert_eq!(features["maintenance"]["enabled"], false);
+
+    assert_eq!(set_flag(&app, "uploads", json!({ "enabled": true }), &admin_token).await, http::StatusCode::OK);
+    let (status, _) = create_upload(&app, &user_token).await;
+    assert_ne!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+
+    // Maintenance: reads work, writes don't, except the admin API
+    assert_eq!(set_flag(&app, "maintenance", json!({ "enabled": true }), &admin_token).await, http::StatusCode::OK);
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/videos").to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(json!({ "name": "Cats", "query": "cats" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/feature-flags")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let flags: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    let maintenance = flags.iter().find(|f| f["name"] == "maintenance").unwrap();
+    assert_eq!(maintenance["enabled"], true);
+    assert_eq!(maintenance["updated_by"], admin_id);
+
+    assert_eq!(set_flag(&app, "maintenance", json!({ "enabled": false }), &admin_token).await, http::StatusCode::OK);
+    let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE event = 'feature_flag_changed' AND user_id = $1")
+        .bind(admin_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(audited, 4);
+}
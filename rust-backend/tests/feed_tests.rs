@@ -0,0 +1,183 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32, category_id: Option<i32>, moderation_status: &str, hls_encrypted: bool) -> i32 {
+    let video_id: i32 = sqlx::query_scalar(
+        "INSERT INTO videos (title, description, s3_key, thumbnail_url, uploaded_by, duration, moderation_status, hls_encrypted)
+         VALUES ($1, 'A <b>great</b> video', $2, $3, $4, 3725, $5, $6) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.webm", title))
+    .bind(format!("thumbnails/{}.jpg", title))
+    .bind(uploaded_by)
+    .bind(moderation_status)
+    .bind(hls_encrypted)
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    if let Some(category_id) = category_id {
+        sqlx::query("INSERT INTO videos_categories (video_id, category_id, position) VALUES ($1, $2, 0)")
+            .bind(video_id)
+            .bind(category_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    video_id
+}
+
+async fn get_feed(
+    app: &impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+    uri: &str,
+) -> (http::StatusCode, String) {
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    if status.is_success() {
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/rss+xml; charset=utf-8");
+    }
+    (status, String::from_utf8(test::read_body(resp).await.to_vec()).unwrap())
+}
+
+#[sqlx::test]
+async fn test_user_feed_lists_public_uploads(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "feeduser").await;
+    let public_id = insert_video(&pool, "feed & public", user_id, None, "approved", false).await;
+    insert_video(&pool, "feed-pending", user_id, None, "pending", false).await;
+    insert_video(&pool, "feed-encrypted", user_id, None, "approved", true).await;
+
+    let (status, xml) = get_feed(&app, &format!("/api/users/{}/feed.xml", user_id)).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\""));
+    assert!(xml.contains("<title>feeduser on VideoStreaming</title>"));
+    assert!(xml.contains(&format!("<atom:link href=\"http://localhost/api/users/{}/feed.xml\" rel=\"self\"", user_id)));
+    assert_eq!(xml.matches("<item>").count(), 1);
+    assert!(xml.contains("<title>feed &amp; public</title>"));
+    assert!(xml.contains("<description>A &lt;b&gt;great&lt;/b&gt; video</description>"));
+    assert!(xml.contains(&format!(
+        "<enclosure url=\"http://localhost/api/videos/{}/stream\" length=\"0\" type=\"video/webm\"/>", public_id
+    )));
+    assert!(xml.contains("<media:thumbnail url=\"http://localhost/api/thumbnails/feed &amp; public.jpg\"/>"));
+    assert!(xml.contains("<itunes:duration>01:02:05</itunes:duration>"));
+    assert!(xml.contains(&format!(
+        "<media:content url=\"http://localhost/api/videos/{}/stream\" type=\"video/webm\" medium=\"video\" duration=\"3725\"/>", public_id
+    )));
+    assert!(xml.contains("<itunes:author>feeduser</itunes:author>"));
+    assert!(!xml.contains("feed-pending"));
+    assert!(!xml.contains("feed-encrypted"));
+
+    let (status, _) = get_feed(&app, "/api/users/999999/feed.xml").await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_category_feed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "feedcategory").await;
+    let category_id: i32 = sqlx::query_scalar("INSERT INTO categories (name, description) VALUES ('Feed Testing', 'Feeds & more') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    insert_video(&pool, "feed-in-category", user_id, Some(category_id), "approved", false).await;
+    insert_video(&pool, "feed-uncategorized", user_id, None, "approved", false).await;
+
+    let (status, xml) = get_feed(&app, &format!("/api/categories/{}/feed.xml", category_id)).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(xml.contains("<title>Feed Testing on VideoStreaming</title>"));
+    assert!(xml.contains("<description>Feeds &amp; more</description>"));
+    assert!(xml.contains(&format!("<link>http://localhost/categories/{}</link>", category_id)));
+    assert!(xml.contains("<title>feed-in-category</title>"));
+    assert!(xml.contains("<itunes:author>feedcategory</itunes:author>"));
+    assert!(!xml.contains("feed-uncategorized"));
+
+    let (status, _) = get_feed(&app, "/api/categories/999999/feed.xml").await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_tag_feed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "feedtag").await;
+    let tagged_id = insert_video(&pool, "feed-tagged", user_id, None, "approved", false).await;
+    insert_video(&pool, "feed-untagged", user_id, None, "approved", false).await;
+    sqlx::query("UPDATE videos SET tags = ARRAY['live music'] WHERE id = $1")
+        .bind(tagged_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (status, xml) = get_feed(&app, "/api/tags/live%20music/feed.xml").await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(xml.contains("<title>#live music on VideoStreaming</title>"));
+    assert!(xml.contains("<link>http://localhost/tags/live%20music</link>"));
+    assert!(xml.contains("<title>feed-tagged</title>"));
+    assert!(!xml.contains("feed-untagged"));
+
+    // An unused tag is just an empty feed
+    let (status, xml) = get_feed(&app, "/api/tags/nothing-here/feed.xml").await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(!xml.contains("<item>"));
+}
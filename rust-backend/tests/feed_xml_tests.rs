@@ -0,0 +1,15 @@
+use video_streaming_backend::feeds::{enclosure_type, escape_xml};
+
+#[test]
+fn escapes_markup_and_drops_control_characters() {
+    assert_eq!(escape_xml("Tom & Jerry <3 \"quotes\" 'n'"), "Tom &amp; Jerry &lt;3 &quot;quotes&quot; &apos;n&apos;");
+    assert_eq!(escape_xml("bad\u{0}\u{1b}char\n"), "badchar\n");
+}
+
+#[test]
+fn enclosure_type_follows_extension() {
+    assert_eq!(enclosure_type("videos/abc.webm"), "video/webm");
+    assert_eq!(enclosure_type("videos/abc.MOV"), "video/quicktime");
+    assert_eq!(enclosure_type("videos/abc.mp4"), "video/mp4");
+    assert_eq!(enclosure_type("videos/abc"), "video/mp4");
+}
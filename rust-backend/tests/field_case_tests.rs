@@ -0,0 +1,53 @@
+use serde_json::json;
+
+use video_streaming_backend::field_case::{camel_case, camelize};
+use video_streaming_backend::models::{AdvancedSearchRequest, CommentRequest, PlaybackBeacon, UserSettings};
+
+#[test]
+fn snake_case_keys_become_camel_case() {
+    assert_eq!(camel_case("watched_secs"), "watchedSecs");
+    assert_eq!(camel_case("p95_startup_ms"), "p95StartupMs");
+    assert_eq!(camel_case("id"), "id");
+    assert_eq!(camel_case("videoTime"), "videoTime");
+    assert_eq!(camel_case("_private"), "_private");
+    assert_eq!(camel_case("pt-br"), "pt-br");
+    assert_eq!(camel_case("Big_Tag"), "Big_Tag");
+}
+
+#[test]
+fn documents_are_camelized_except_data_keyed_objects() {
+    let document = json!({
+        "video_ids": [1, 2],
+        "items": [{ "view_count": 3, "upload_date": null }],
+        "facets": { "tags": { "rust_lang": 2 } },
+        "theme": { "name": "mine", "isCustom": true, "bg_primary": "#000" },
+        "isAuthenticated": false
+    });
+    assert_eq!(camelize(document), json!({
+        "videoIds": [1, 2],
+        "items": [{ "viewCount": 3, "uploadDate": null }],
+        "facets": { "tags": { "rust_lang": 2 } },
+        "theme": { "name": "mine", "isCustom": true, "bg_primary": "#000" },
+        "isAuthenticated": false
+    }));
+}
+
+#[test]
+fn requests_take_both_namings() {
+    let camel: CommentRequest = serde_json::from_value(json!({ "text": "hi", "videoTime": 3 })).unwrap();
+    let snake: CommentRequest = serde_json::from_value(json!({ "text": "hi", "video_time": 3 })).unwrap();
+    assert_eq!(camel.video_time, 3);
+    assert_eq!(snake.video_time, 3);
+
+    let search: AdvancedSearchRequest = serde_json::from_value(json!({ "tagsAll": ["a"], "min_duration": 10 })).unwrap();
+    assert_eq!(search.tags_all, Some(vec!["a".to_string()]));
+    assert_eq!(search.min_duration, Some(10));
+
+    let beacon: PlaybackBeacon = serde_json::from_value(json!({ "video_id": 1, "watchedSecs": 2.5 })).unwrap();
+    assert_eq!(beacon.video_id, 1);
+    assert_eq!(beacon.watched_secs, Some(2.5));
+
+    let settings: UserSettings = serde_json::from_value(json!({ "playback_speed": 1.5 })).unwrap();
+    assert_eq!(settings.playback_speed, 1.5);
+    assert_eq!(serde_json::to_value(&settings).unwrap()["playbackSpeed"], 1.5);
+}
@@ -0,0 +1,27 @@
+use video_streaming_backend::frames::{frame_s3_key, frame_time, frames_prefix};
+
+#[test]
+fn times_are_rounded_down_to_whole_seconds() {
+    assert_eq!(frame_time(12.5, None), Ok(12_000));
+    assert_eq!(frame_time(0.0, Some(60)), Ok(0));
+    assert_eq!(frame_time(1.0 / 3.0, None), Ok(0));
+    assert_eq!(frame_time(59.999, Some(60)), Ok(59_000));
+    // Every time within a second shares one rendered frame
+    assert_eq!(frame_time(7.001, None), frame_time(7.999, None));
+}
+
+#[test]
+fn times_outside_the_video_are_rejected() {
+    assert!(frame_time(60.0, Some(60)).is_err());
+    assert!(frame_time(-0.5, None).is_err());
+    assert!(frame_time(f64::NAN, None).is_err());
+    assert!(frame_time(f64::INFINITY, None).is_err());
+    assert!(frame_time(1e12, None).is_err());
+}
+
+#[test]
+fn frames_are_stored_per_video() {
+    assert_eq!(frame_s3_key(7, 12_000), "frames/7/12000.jpg");
+    assert!(frame_s3_key(7, 0).starts_with(&frames_prefix(7)));
+    assert!(!frame_s3_key(70, 0).starts_with(&frames_prefix(7)));
+}
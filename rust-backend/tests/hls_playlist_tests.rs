@@ -0,0 +1,136 @@
+use openssl::symm::{decrypt, Cipher};
+
+use video_streaming_backend::hls;
+use video_streaming_backend::models::{VideoAudioTrack, VideoHlsSegment};
+
+fn segment(segment_index: i32, key_index: i32) -> VideoHlsSegment {
+    VideoHlsSegment {
+        video_id: 7,
+        segment_index,
+        duration: 5.5,
+        s3_key: format!("hls/7/abc/{}.ts", segment_index),
+        key_index,
+    }
+}
+
+#[test]
+fn segment_iv_is_big_endian_media_sequence() {
+    let iv = hls::segment_iv(258);
+    assert_eq!(&iv[..14], &[0u8; 14]);
+    assert_eq!(&iv[14..], &[1, 2]);
+}
+
+#[test]
+fn encrypted_segment_decrypts_with_key_and_sequence_iv() {
+    let key = hls::generate_key().unwrap();
+    let plain: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+    let encrypted = hls::encrypt_segment(&key, 3, &plain).unwrap();
+    assert_ne!(encrypted, plain);
+    assert!(encrypted.len().is_multiple_of(16));
+
+    let decrypted = decrypt(Cipher::aes_128_cbc(), &key, Some(&hls::segment_iv(3)), &encrypted).unwrap();
+    assert_eq!(decrypted, plain);
+}
+
+#[test]
+fn generated_keys_differ() {
+    assert_ne!(hls::generate_key().unwrap(), hls::generate_key().unwrap());
+}
+
+#[test]
+fn parses_ffmpeg_media_playlist() {
+    let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:7\n#EXT-X-MEDIA-SEQUENCE:0\n\
+        #EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:6.006000,\nseg00000.ts\n#EXTINF:3.200000,\nseg00001.ts\n#EXT-X-ENDLIST\n";
+    let segments = hls::parse_media_playlist(playlist);
+    assert_eq!(segments, vec![(6.006, "seg00000.ts".to_string()), (3.2, "seg00001.ts".to_string())]);
+}
+
+#[test]
+fn playlist_emits_a_key_tag_per_rotation() {
+    let segments = vec![segment(0, 0), segment(1, 0), segment(2, 1)];
+    let playlist = hls::build_playlist(7, &segments, None);
+
+    assert!(playlist.starts_with("#EXTM3U\n"));
+    assert!(playlist.contains("#EXT-X-TARGETDURATION:6\n"));
+    assert_eq!(playlist.matches("#EXT-X-KEY:").count(), 2);
+    assert!(playlist.contains("#EXT-X-KEY:METHOD=AES-128,URI=\"/api/videos/7/key?index=0\"\n#EXTINF:5.500,\n/api/videos/7/hls/0.ts\n"));
+    assert!(playlist.contains("#EXT-X-KEY:METHOD=AES-128,URI=\"/api/videos/7/key?index=1\"\n#EXTINF:5.500,\n/api/videos/7/hls/2.ts\n"));
+    assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+}
+
+#[test]
+fn playlist_passes_token_to_key_uris() {
+    let playlist = hls::build_playlist(7, &[segment(0, 0)], Some("a.b+c"));
+    assert!(playlist.contains("URI=\"/api/videos/7/key?index=0&token=a.b%2Bc\""));
+    assert!(playlist.contains("\n/api/videos/7/hls/0.ts\n"));
+}
+
+fn track(track_index: i32, label: &str, language: Option<&str>, is_default: bool) -> VideoAudioTrack {
+    VideoAudioTrack {
+        video_id: 7,
+        track_index,
+        language: language.map(str::to_string),
+        label: label.to_string(),
+        codec: Some("aac".to_string()),
+        channels: Some(2),
+        is_default,
+        audio_description: false,
+        standalone: false,
+        s3_key: None,
+        content_type: None,
+    }
+}
+
+#[test]
+fn master_playlist_lists_an_audio_rendition_per_track() {
+    let tracks = vec![track(0, "English", Some("eng"), true), track(1, "Fran\"cais", Some("fra"), false)];
+    let playlist = hls::build_master_playlist(7, &tracks, Some("a.b+c"), None);
+
+    assert!(playlist.starts_with("#EXTM3U\n"));
+    assert!(playlist.contains(
+        "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"English\",DEFAULT=YES,AUTOSELECT=YES,LANGUAGE=\"eng\",CHANNELS=\"2\",\
+         URI=\"/api/videos/7/hls/audio/0/index.m3u8?token=a.b%2Bc\"\n"
+    ));
+    assert!(playlist.contains("NAME=\"Fran'cais\",DEFAULT=NO,"));
+    assert!(playlist.contains("#EXT-X-STREAM-INF:BANDWIDTH="));
+    assert!(playlist.ends_with(",AUDIO=\"audio\"\n/api/videos/7/hls/video.m3u8?token=a.b%2Bc\n"));
+}
+
+#[test]
+fn master_playlist_keeps_track_names_unique() {
+    let tracks = vec![track(0, "Stereo", None, true), track(1, "Stereo", None, false)];
+    let playlist = hls::build_master_playlist(7, &tracks, None, None);
+    assert!(playlist.contains("NAME=\"Stereo (1)\""));
+    assert!(playlist.contains("NAME=\"Stereo (2)\""));
+    assert!(!playlist.contains("LANGUAGE="));
+    assert!(playlist.contains("URI=\"/api/videos/7/hls/audio/1/index.m3u8\"\n"));
+}
+
+#[test]
+fn master_playlist_passes_the_playback_token_on() {
+    let playlist = hls::build_master_playlist(7, &[track(0, "English", None, true)], Some("a.b"), Some("3.s1.99.f0"));
+    assert!(playlist.contains("URI=\"/api/videos/7/hls/audio/0/index.m3u8?token=a.b&playback_token=3.s1.99.f0\"\n"));
+    assert!(playlist.ends_with("/api/videos/7/hls/video.m3u8?token=a.b&playback_token=3.s1.99.f0\n"));
+
+    let playlist = hls::build_master_playlist(7, &[track(0, "English", None, true)], None, Some("3.s1.99.f0"));
+    assert!(playlist.ends_with("/api/videos/7/hls/video.m3u8?playback_token=3.s1.99.f0\n"));
+}
+
+#[test]
+fn audio_playlist_points_at_the_track_segments() {
+    let playlist = hls::build_audio_playlist(7, 2, &[segment(0, 0)], None);
+    assert!(playlist.contains("URI=\"/api/videos/7/key?index=0\"\n"));
+    assert!(playlist.contains("\n/api/videos/7/hls/audio/2/0.ts\n"));
+}
+
+#[test]
+fn audio_description_is_marked_and_never_default() {
+    let description = VideoAudioTrack { audio_description: true, ..track(1, "Audio description", Some("eng"), true) };
+    let playlist = hls::build_master_playlist(7, &[track(0, "English", Some("eng"), true), description], None, None);
+    assert!(playlist.contains(
+        "NAME=\"Audio description\",DEFAULT=NO,AUTOSELECT=YES,LANGUAGE=\"eng\",CHARACTERISTICS=\"public.accessibility.describes-video\","
+    ));
+    assert_eq!(playlist.matches("CHARACTERISTICS=").count(), 1);
+    assert_eq!(playlist.matches("DEFAULT=YES").count(), 1);
+}
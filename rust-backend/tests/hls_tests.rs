@@ -0,0 +1,379 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::audio_tracks;
+use video_streaming_backend::hls;
+use video_streaming_backend::models::{VideoAudioTrack, VideoHlsSegment};
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32, status: &str) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, uploaded_by, hls_encrypted, moderation_status) VALUES ($1, $2, $3, TRUE, $4) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(uploaded_by)
+    .bind(status)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn package(pool: &PgPool, video_id: i32) -> [u8; hls::KEY_BYTES] {
+    let key = hls::generate_key().unwrap();
+    let segments: Vec<VideoHlsSegment> = (0..3)
+        .map(|i| VideoHlsSegment {
+            video_id,
+            segment_index: i,
+            duration: 6.0,
+            s3_key: format!("hls/{}/test/{}.ts", video_id, i),
+            key_index: i / 2,
+        })
+        .collect();
+    hls::replace_package(pool, video_id, &[(0, key), (1, hls::generate_key().unwrap())], &segments, &[]).await.unwrap();
+    key
+}
+
+#[sqlx::test]
+async fn test_key_requires_authentication(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "hlsowner").await;
+    let (_, viewer_token) = register_test_user(&app, "hlsviewer").await;
+    let video_id = insert_video(&pool, "hls-auth", owner_id, "approved").await;
+    let key = package(&pool, video_id).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=0", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=0", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap().to_str().unwrap(), "no-store, private");
+    let body = test::read_body(resp).await;
+    assert_eq!(&body[..], &key[..]);
+
+    // Players that can't set headers pass the token in the query string
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=1&token={}", video_id, viewer_token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(test::read_body(resp).await.len(), hls::KEY_BYTES);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=5", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_key_of_unapproved_video_is_owner_only(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "hlspendingowner").await;
+    let (_, viewer_token) = register_test_user(&app, "hlspendingviewer").await;
+    let video_id = insert_video(&pool, "hls-pending", owner_id, "pending").await;
+    package(&pool, video_id).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=0", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/key?index=0", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_playlist_references_rotating_keys(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "hlsplaylist").await;
+    let video_id = insert_video(&pool, "hls-playlist", owner_id, "approved").await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/index.m3u8", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    package(&pool, video_id).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/index.m3u8?token=abc", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/vnd.apple.mpegurl");
+
+    let playlist = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(playlist.contains(&format!("URI=\"/api/videos/{}/key?index=0&token=abc\"", video_id)));
+    assert!(playlist.contains(&format!("URI=\"/api/videos/{}/key?index=1&token=abc\"", video_id)));
+    assert_eq!(playlist.matches("#EXTINF:").count(), 3);
+}
+
+#[sqlx::test]
+async fn test_alternate_audio_is_served_through_a_master_playlist(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "hlsaudio").await;
+    let video_id = insert_video(&pool, "hls-audio", owner_id, "approved").await;
+
+    let tracks: Vec<VideoAudioTrack> = ["English", "Deutsch"]
+        .iter()
+        .enumerate()
+        .map(|(i, label)| VideoAudioTrack {
+            video_id,
+            track_index: i as i32,
+            language: Some(if i == 0 { "eng" } else { "deu" }.to_string()),
+            label: label.to_string(),
+            codec: Some("aac".to_string()),
+            channels: Some(2),
+            is_default: i == 0,
+            audio_description: false,
+            standalone: false,
+            s3_key: None,
+            content_type: None,
+        })
+        .collect();
+    audio_tracks::replace(&pool, video_id, &tracks).await.unwrap();
+    let segment = |i: i32, s3_key: String| VideoHlsSegment { video_id, segment_index: i, duration: 6.0, s3_key, key_index: 0 };
+    let segments: Vec<VideoHlsSegment> = (0..2).map(|i| segment(i, format!("hls/{}/test/{}.ts", video_id, i))).collect();
+    let audio_segments: Vec<(i32, VideoHlsSegment)> = (0..2)
+        .flat_map(|track| (0..2).map(move |i| (track, i)))
+        .map(|(track, i)| (track, segment(i, format!("hls/{}/test/audio{}/{}.ts", video_id, track, i))))
+        .collect();
+    hls::replace_package(&pool, video_id, &[(0, hls::generate_key().unwrap())], &segments, &audio_segments).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/index.m3u8?token=abc", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let playlist = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(playlist.matches("#EXT-X-MEDIA:TYPE=AUDIO").count(), 2);
+    assert!(playlist.contains(&format!("/api/videos/{}/hls/video.m3u8?token=abc", video_id)));
+    assert!(!playlist.contains("#EXTINF:"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/video.m3u8", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let playlist = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(playlist.matches("#EXTINF:").count(), 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/audio/1/index.m3u8?token=abc", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let playlist = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(playlist.contains(&format!("/api/videos/{}/hls/audio/1/1.ts", video_id)));
+    assert!(playlist.contains("&token=abc"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/hls/audio/5/index.m3u8", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // The detail API lists the tracks for the player's language menu
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], video_id);
+    assert_eq!(body["audio_tracks"].as_array().unwrap().len(), 2);
+    assert_eq!(body["audio_tracks"][1]["language"], "deu");
+    assert_eq!(body["audio_tracks"][1]["label"], "Deutsch");
+    assert_eq!(body["audio_tracks"][0]["is_default"], true);
+}
+
+#[sqlx::test]
+async fn test_audio_description_track_upload(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "adowner").await;
+    let (_, other_token) = register_test_user(&app, "adother").await;
+    let video_id = insert_video(&pool, "ad-upload", owner_id, "approved").await;
+    let uri = format!("/api/videos/{}/audio-tracks?audioDescription=true&language=eng", video_id);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .insert_header(("Content-Type", "audio/mp4"))
+        .set_payload(vec![0u8; 64])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .insert_header(("Content-Type", "text/plain"))
+        .set_payload("not audio")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .insert_header(("Content-Type", "audio/mp4"))
+        .set_payload(vec![0u8; 64])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+    let track: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(track["track_index"], audio_tracks::STANDALONE_TRACK_BASE);
+    assert_eq!(track["label"], "Audio description");
+    assert_eq!(track["audio_description"], true);
+    assert_eq!(track["standalone"], true);
+    assert!(track.get("s3_key").is_none());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/audio-tracks", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let tracks: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(tracks.as_array().unwrap().len(), 1);
+
+    // Encrypted videos only play it through HLS
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/audio-tracks/{}", video_id, audio_tracks::STANDALONE_TRACK_BASE))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}/audio-tracks/{}", video_id, audio_tracks::STANDALONE_TRACK_BASE))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+    assert!(audio_tracks::list(&pool, video_id).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn test_encrypted_video_is_not_streamed_directly(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "hlsstream").await;
+    let video_id = insert_video(&pool, "hls-stream", owner_id, "approved").await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/stream", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_encryption_toggle(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "hlstoggleowner").await;
+    let (_, other_token) = register_test_user(&app, "hlstoggleother").await;
+    let video_id = insert_video(&pool, "hls-toggle", owner_id, "approved").await;
+    package(&pool, video_id).await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/encryption", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .set_json(json!({ "enabled": false }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    // Packaging needs the job queue, which tests run without
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/encryption", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "enabled": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/encryption", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "enabled": false }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(video["hls_encrypted"], false);
+
+    assert!(hls::list_segments(&pool, video_id).await.unwrap().is_empty());
+    assert!(hls::get_key(&pool, video_id, 0).await.unwrap().is_none());
+}
@@ -0,0 +1,73 @@
+use actix_web::http::{Method, StatusCode};
+use actix_web::{middleware, test, web, App, HttpResponse};
+
+use video_streaming_backend::hotlinks::{check_hotlink, is_media_path, require_signature, source_allowed};
+
+fn origins(origins: &[&str]) -> Vec<String> {
+    origins.iter().map(|o| o.to_string()).collect()
+}
+
+#[test]
+fn media_paths_are_recognized() {
+    assert!(is_media_path(&Method::GET, "/api/videos/12/stream"));
+    assert!(is_media_path(&Method::HEAD, "/api/videos/12/download"));
+    assert!(is_media_path(&Method::GET, "/api/videos/12/hls/index.m3u8"));
+    assert!(is_media_path(&Method::GET, "/api/videos/12/hls/audio/1/00003.ts"));
+    assert!(is_media_path(&Method::GET, "/api/videos/12/clips/4"));
+    assert!(is_media_path(&Method::GET, "/api/videos/12/audio-tracks/100"));
+    assert!(is_media_path(&Method::GET, "/api/thumbnails/abc.jpg"));
+
+    assert!(!is_media_path(&Method::GET, "/api/videos/12"));
+    assert!(!is_media_path(&Method::GET, "/api/videos/12/audio-tracks"));
+    assert!(!is_media_path(&Method::GET, "/api/videos/12/thumbnail-candidates"));
+    assert!(!is_media_path(&Method::GET, "/api/videos/search/stream"));
+    assert!(!is_media_path(&Method::DELETE, "/api/videos/12/audio-tracks/100"));
+}
+
+#[test]
+fn only_listed_sites_may_embed() {
+    let allowed = origins(&["https://videos.example.com", "https://partner.example.org"]);
+    assert!(source_allowed(None, &allowed));
+    assert!(source_allowed(Some("https://videos.example.com"), &allowed));
+    assert!(source_allowed(Some("https://partner.example.org/blog/post?id=1"), &allowed));
+    assert!(!source_allowed(Some("https://elsewhere.example.net/page"), &allowed));
+    assert!(!source_allowed(Some("https://videos.example.com.evil.example/"), &allowed));
+    assert!(!source_allowed(Some("null"), &allowed));
+}
+
+#[actix_web::test]
+async fn protection_refuses_foreign_pages() {
+    // Every test in this file that reads the environment runs with protection on
+    std::env::set_var("HOTLINK_PROTECTION", "on");
+    std::env::set_var("HOTLINK_REQUIRE_SIGNED_URLS", "on");
+    std::env::set_var("CORS_ALLOWED_ORIGINS", "https://videos.example.com");
+    std::env::set_var("HOTLINK_ALLOWED_ORIGINS", "https://partner.example.org");
+
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(check_hotlink))
+            .route("/api/videos/{id}/stream", web::get().to(HttpResponse::Ok))
+            .route("/api/videos/{id}", web::get().to(HttpResponse::Ok))
+    ).await;
+
+    let status = |uri: &str, referer: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(referer) = referer {
+            req = req.insert_header(("Referer", referer));
+        }
+        req.to_request()
+    };
+    let resp = test::call_service(&app, status("/api/videos/1/stream", Some("https://elsewhere.example.net/"))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, status("/api/videos/1/stream", Some("https://partner.example.org/watch"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, status("/api/videos/1/stream", None)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    // Metadata isn't media
+    let resp = test::call_service(&app, status("/api/videos/1", Some("https://elsewhere.example.net/"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    assert!(require_signature(None, None).is_err());
+    assert!(require_signature(Some(1_700_000_000), None).is_err());
+    assert!(require_signature(Some(1_700_000_000), Some("abcd")).is_ok());
+}
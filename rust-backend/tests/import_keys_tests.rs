@@ -0,0 +1,33 @@
+use std::collections::HashSet;
+
+use video_streaming_backend::imports::{is_video_key, objects_to_import, title_from_key, ListedObject};
+
+fn object(key: &str, size: i64) -> ListedObject {
+    ListedObject { key: key.to_string(), size, last_modified: None }
+}
+
+#[test]
+fn titles_come_from_file_names() {
+    assert_eq!(title_from_key("videos/talks/2019_Keynote-final.mp4"), "2019 Keynote final");
+    assert_eq!(title_from_key("videos/Summer%20Trip.MOV"), "Summer Trip");
+    assert_eq!(title_from_key("videos/no-extension"), "no extension");
+    assert_eq!(title_from_key("videos/___.webm"), "Untitled video");
+}
+
+#[test]
+fn only_new_video_files_are_imported() {
+    assert!(is_video_key("videos/a.MP4"));
+    assert!(!is_video_key("videos/cover.jpg"));
+    assert!(!is_video_key("videos.mp4/readme"));
+
+    let objects = vec![
+        object("videos/new.mp4", 10),
+        object("videos/known.webm", 10),
+        object("videos/notes.txt", 10),
+        object("videos/folder/", 0),
+        object("videos/empty.mp4", 0),
+    ];
+    let known: HashSet<String> = ["videos/known.webm".to_string()].into_iter().collect();
+    let keys: Vec<&str> = objects_to_import(&objects, &known).iter().map(|o| o.key.as_str()).collect();
+    assert_eq!(keys, vec!["videos/new.mp4"]);
+}
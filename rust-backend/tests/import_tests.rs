@@ -0,0 +1,138 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use std::env;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::services;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn run_import(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, body: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::post()
+        .uri("/api/admin/imports")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[sqlx::test]
+async fn test_import_is_admin_only(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "importuser").await;
+
+    let (status, _) = run_import(&app, &token, json!({})).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+}
+
+// Needs the S3 service (MinIO) the streaming tests use
+#[sqlx::test]
+async fn test_import_creates_videos_for_new_objects_once(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, token) = register_test_user(&app, "importadmin").await;
+    make_admin(&pool, admin_id).await;
+
+    let storage = storage::init_storage().await;
+    services::ensure_bucket_exists(storage.as_ref()).await;
+    let bucket = env::var("S3_BUCKET").or_else(|_| env::var("MINIO_BUCKET")).unwrap_or_else(|_| "videos".to_string());
+    let prefix = format!("import-test/{}/", uuid::Uuid::new_v4());
+    for name in ["Holiday_Film.mp4", "poster.jpg"] {
+        storage.put(&bucket, &format!("{}{}", prefix, name), b"not really a video".to_vec().into(), "application/octet-stream")
+            .await
+            .expect("Storage must be available for this test");
+    }
+
+    let (status, report) = run_import(&app, &token, json!({ "prefix": prefix, "dryRun": true })).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(report["scanned"], 2);
+    assert_eq!(report["imported"][0]["title"], "Holiday Film");
+    assert!(report["imported"][0]["video_id"].is_null());
+
+    let (_, report) = run_import(&app, &token, json!({ "prefix": prefix })).await;
+    let video_id = report["imported"][0]["video_id"].as_i64().unwrap();
+    let (title, uploaded_by): (String, Option<i32>) = sqlx::query_as("SELECT title, uploaded_by FROM videos WHERE id = $1")
+        .bind(video_id as i32)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(title, "Holiday Film");
+    assert_eq!(uploaded_by, Some(admin_id));
+
+    // Running it again finds nothing new
+    let (_, report) = run_import(&app, &token, json!({ "prefix": prefix })).await;
+    assert_eq!(report["imported"], json!([]));
+    assert_eq!(report["already_imported"], 1);
+}
@@ -0,0 +1,138 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::job_history;
+use video_streaming_backend::job_queue::{MediaJob, MediaJobKind};
+use video_streaming_backend::models::Video;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    let storage = storage::init_storage().await;
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+async fn register_admin(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, pool: &PgPool, username: &str) -> String {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(body["user"]["id"].as_i64().unwrap() as i32)
+        .execute(pool)
+        .await
+        .unwrap();
+    body["token"].as_str().unwrap().to_string()
+}
+
+async fn insert_video(pool: &PgPool, s3_key: &str) -> Video {
+    sqlx::query_as::<_, Video>("INSERT INTO videos (title, s3_key) VALUES ('Job video', $1) RETURNING *")
+        .bind(s3_key)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn test_retried_jobs_count_attempts() {
+    let job: MediaJob = serde_json::from_value(json!({
+        "kind": "thumbnail",
+        "video_id": 1,
+        "s3_key": "videos/a.mp4",
+        "bucket": "videos"
+    }))
+    .unwrap();
+    // Jobs queued before attempts were counted are on their first
+    assert_eq!(job.attempt, 1);
+    assert_eq!(MediaJobKind::parse("detect_scenes"), Some(MediaJobKind::DetectScenes));
+    assert_eq!(MediaJobKind::parse("encode"), None);
+}
+
+#[sqlx::test]
+async fn test_job_status_lists_attempts(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let token = register_admin(&app, &pool, "jobsadmin").await;
+    let video = insert_video(&pool, "job_status_key").await;
+
+    let first = MediaJob::new(MediaJobKind::Thumbnail, &video, "videos");
+    let id = job_history::start_attempt(&pool, &first, "worker-a").await;
+    job_history::finish_attempt(&pool, id, Some("ffmpeg exited with 1"), true).await;
+    let second = MediaJob { attempt: 2, ..first.clone() };
+    let id = job_history::start_attempt(&pool, &second, "worker-b").await;
+    job_history::finish_attempt(&pool, id, None, false).await;
+    job_history::start_attempt(&pool, &MediaJob::new(MediaJobKind::Transcode, &video, "videos"), "worker-a").await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/jobs")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["queues"].is_null());
+    assert_eq!(body["in_flight"].as_array().unwrap().len(), 1);
+    assert_eq!(body["in_flight"][0]["kind"], "transcode");
+    assert_eq!(body["in_flight"][0]["worker"], "worker-a");
+    assert_eq!(body["recent_failures"][0]["error"], "ffmpeg exited with 1");
+    assert_eq!(body["recent_failures"][0]["will_retry"], true);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/admin/videos/{}/jobs?kind=thumbnail", video.id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let attempts: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let outcomes: Vec<(i64, &str)> = attempts.as_array().unwrap().iter()
+        .map(|a| (a["attempt"].as_i64().unwrap(), a["outcome"].as_str().unwrap()))
+        .collect();
+    assert_eq!(outcomes, [(1, "failed"), (2, "succeeded")]);
+
+    // A job claimed from a worker that died closes the attempt it left running
+    job_history::abandon(&pool, video.id, MediaJobKind::Transcode).await;
+    assert!(job_history::in_flight(&pool).await.unwrap().is_empty());
+    let attempts = job_history::attempts(&pool, video.id, Some(MediaJobKind::Transcode)).await.unwrap();
+    assert_eq!(attempts[0].outcome, "failed");
+    assert!(attempts[0].will_retry);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/admin/videos/{}/jobs?kind=encode", video.id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,161 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use serde::{Deserialize, Serialize};
+
+use video_streaming_backend::jwt_keys::{parse_algorithm, KeySet};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Claims {
+    user_id: i32,
+    exp: usize,
+}
+
+// Fixed far in the future, so claims built at different times compare equal
+fn claims(user_id: i32) -> Claims {
+    Claims { user_id, exp: 4_102_444_800 }
+}
+
+// (private PEM, public PEM)
+fn rsa_pems() -> (Vec<u8>, Vec<u8>) {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    (key.private_key_to_pem_pkcs8().unwrap(), key.public_key_to_pem().unwrap())
+}
+
+fn ed25519_pems() -> (Vec<u8>, Vec<u8>) {
+    let key = PKey::generate_ed25519().unwrap();
+    (key.private_key_to_pem_pkcs8().unwrap(), key.public_key_to_pem().unwrap())
+}
+
+fn kid_of(token: &str) -> Option<String> {
+    jsonwebtoken::decode_header(token).unwrap().kid
+}
+
+#[test]
+fn algorithms_are_parsed_case_insensitively() {
+    assert_eq!(parse_algorithm("hs256"), Ok(Algorithm::HS256));
+    assert_eq!(parse_algorithm("RS256"), Ok(Algorithm::RS256));
+    assert_eq!(parse_algorithm("eddsa"), Ok(Algorithm::EdDSA));
+    assert!(parse_algorithm("none").is_err());
+    assert!(parse_algorithm("HS512").is_err());
+}
+
+#[test]
+fn hmac_secrets_rotate_without_invalidating_tokens() {
+    let old = KeySet::hmac("old-secret", &[]).unwrap();
+    let token = old.issue(&claims(1)).unwrap();
+    assert_eq!(kid_of(&token).as_deref(), Some(old.signing_kid()));
+
+    let rotated = KeySet::hmac("new-secret", &["old-secret".to_string()]).unwrap();
+    assert_ne!(rotated.signing_kid(), old.signing_kid());
+    assert_eq!(rotated.decode::<Claims>(&token), Some(claims(1)));
+    assert_eq!(rotated.decode::<Claims>(&rotated.issue(&claims(2)).unwrap()), Some(claims(2)));
+
+    // Once the old secret is retired, its tokens stop working
+    let retired = KeySet::hmac("new-secret", &[]).unwrap();
+    assert_eq!(retired.decode::<Claims>(&token), None);
+
+    assert!(KeySet::hmac("", &[]).is_err());
+    assert_eq!(rotated.jwks()["keys"], serde_json::json!([]));
+}
+
+#[test]
+fn tokens_without_a_kid_are_still_accepted() {
+    let keys = KeySet::hmac("secret", &[]).unwrap();
+    let legacy = jsonwebtoken::encode(&Header::default(), &claims(3), &EncodingKey::from_secret(b"secret")).unwrap();
+    assert_eq!(keys.decode::<Claims>(&legacy), Some(claims(3)));
+
+    let forged = jsonwebtoken::encode(&Header::default(), &claims(3), &EncodingKey::from_secret(b"guess")).unwrap();
+    assert_eq!(keys.decode::<Claims>(&forged), None);
+}
+
+#[test]
+fn rs256_tokens_verify_against_the_published_jwks() {
+    let (private, _) = rsa_pems();
+    let keys = KeySet::asymmetric(Algorithm::RS256, &[("2025-08".to_string(), private)], "2025-08", &[]).unwrap();
+    let token = keys.issue(&claims(4)).unwrap();
+    assert_eq!(jsonwebtoken::decode_header(&token).unwrap().alg, Algorithm::RS256);
+    assert_eq!(kid_of(&token).as_deref(), Some("2025-08"));
+    assert_eq!(keys.decode::<Claims>(&token), Some(claims(4)));
+
+    // Another service only needs the JWKS
+    let jwks: JwkSet = serde_json::from_value(keys.jwks()).unwrap();
+    let jwk = jwks.find("2025-08").unwrap();
+    let decoded = jsonwebtoken::decode::<Claims>(&token, &DecodingKey::from_jwk(jwk).unwrap(), &Validation::new(Algorithm::RS256)).unwrap();
+    assert_eq!(decoded.claims, claims(4));
+}
+
+#[test]
+fn asymmetric_keys_rotate_with_public_keys_kept_for_verification() {
+    let (old_private, old_public) = ed25519_pems();
+    let (new_private, _) = ed25519_pems();
+
+    let before = KeySet::asymmetric(Algorithm::EdDSA, &[("a".to_string(), old_private)], "a", &[]).unwrap();
+    let old_token = before.issue(&claims(5)).unwrap();
+
+    let after = KeySet::asymmetric(
+        Algorithm::EdDSA,
+        &[("a".to_string(), old_public), ("b".to_string(), new_private)],
+        "b",
+        &[],
+    )
+    .unwrap();
+    let new_token = after.issue(&claims(6)).unwrap();
+    assert_eq!(kid_of(&new_token).as_deref(), Some("b"));
+    assert_eq!(after.decode::<Claims>(&old_token), Some(claims(5)));
+    assert_eq!(after.decode::<Claims>(&new_token), Some(claims(6)));
+    assert_eq!(before.decode::<Claims>(&new_token), None);
+
+    let jwks = after.jwks();
+    let kids: Vec<&str> = jwks["keys"].as_array().unwrap().iter().map(|k| k["kid"].as_str().unwrap()).collect();
+    assert_eq!(kids, vec!["a", "b"]);
+    assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+    assert!(jwks["keys"][0].get("d").is_none());
+}
+
+#[test]
+fn switching_algorithms_keeps_hmac_sessions_valid() {
+    let hmac_token = KeySet::hmac("secret", &[]).unwrap().issue(&claims(7)).unwrap();
+    let (private, _) = rsa_pems();
+    let pems = [("k1".to_string(), private)];
+
+    let keys = KeySet::asymmetric(Algorithm::RS256, &pems, "k1", &["secret".to_string()]).unwrap();
+    assert_eq!(keys.decode::<Claims>(&hmac_token), Some(claims(7)));
+    // HMAC secrets are never published
+    assert_eq!(keys.jwks()["keys"].as_array().unwrap().len(), 1);
+
+    let without_legacy = KeySet::asymmetric(Algorithm::RS256, &pems, "k1", &[]).unwrap();
+    assert_eq!(without_legacy.decode::<Claims>(&hmac_token), None);
+}
+
+#[test]
+fn public_keys_cannot_be_used_as_hmac_secrets() {
+    let (private, public) = rsa_pems();
+    let keys = KeySet::asymmetric(Algorithm::RS256, &[("k1".to_string(), private)], "k1", &[]).unwrap();
+
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some("k1".to_string());
+    let forged = jsonwebtoken::encode(&header, &claims(8), &EncodingKey::from_secret(&public)).unwrap();
+    assert_eq!(keys.decode::<Claims>(&forged), None);
+}
+
+#[test]
+fn misconfigured_key_sets_are_rejected() {
+    let (rsa_private, rsa_public) = rsa_pems();
+    let (ed_private, _) = ed25519_pems();
+
+    // The signing key has to exist and be private
+    assert!(KeySet::asymmetric(Algorithm::RS256, &[("k1".to_string(), rsa_private.clone())], "k2", &[]).is_err());
+    assert!(KeySet::asymmetric(Algorithm::RS256, &[("k1".to_string(), rsa_public)], "k1", &[]).is_err());
+    // Every key has to match the algorithm
+    assert!(KeySet::asymmetric(Algorithm::EdDSA, &[("k1".to_string(), rsa_private.clone())], "k1", &[]).is_err());
+    assert!(KeySet::asymmetric(
+        Algorithm::RS256,
+        &[("k1".to_string(), rsa_private), ("k2".to_string(), ed_private)],
+        "k1",
+        &[],
+    )
+    .is_err());
+    assert!(KeySet::asymmetric(Algorithm::RS256, &[("k1".to_string(), b"not a key".to_vec())], "k1", &[]).is_err());
+}
@@ -0,0 +1,146 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32, title: &str, moderation_status: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by, moderation_status) VALUES ($1, $2, $3, $4) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(user_id)
+        .bind(moderation_status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_my_videos_lists_only_own_videos(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "collector").await;
+    let (other_id, _) = register_test_user(&app, "stranger").await;
+    let scraped_id = insert_video(&pool, user_id, "scraped", "pending").await;
+    let trashed_id = insert_video(&pool, user_id, "trashed", "approved").await;
+    insert_video(&pool, other_id, "not_mine", "approved").await;
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1")
+        .bind(trashed_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Includes videos still awaiting moderation, but not the trash
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/videos")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let videos: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let videos = videos.as_array().unwrap();
+    assert_eq!(videos.len(), 1);
+    assert_eq!(videos[0]["id"], scraped_id);
+
+    let req = test::TestRequest::get().uri("/api/users/me/videos").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_uploader_can_edit_metadata(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "editor").await;
+    let video_id = insert_video(&pool, user_id, "original", "approved").await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "title": "  Renamed  ", "tags": ["music", " "] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(video["title"], "Renamed");
+    assert_eq!(video["tags"], json!(["music"]));
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "title": "" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_only_uploader_can_edit_metadata(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "owner").await;
+    let (_, other_token) = register_test_user(&app, "someone_else").await;
+    let video_id = insert_video(&pool, owner_id, "guarded", "approved").await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", other_token)))
+        .set_json(json!({ "title": "Mine now" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
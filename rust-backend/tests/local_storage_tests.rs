@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use bytes::Bytes;
+
+use video_streaming_backend::services::{download_object_parallel, object_sha256, ParallelDownloadConfig};
+use video_streaming_backend::storage::{content_type_for_key, LocalStorage, ObjectStore, StorageError};
+
+// A fresh storage root per test, removed when dropped
+struct Root(PathBuf);
+
+impl Root {
+    fn new() -> Self {
+        Root(std::env::temp_dir().join(format!("local-storage-test-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[tokio::test]
+async fn objects_round_trip_through_files() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+
+    storage.put("videos", "videos/a.mp4", Bytes::from_static(b"0123456789"), "video/mp4").await.unwrap();
+    assert!(root.0.join("videos/videos/a.mp4").is_file());
+
+    let object = storage.get("videos", "videos/a.mp4").await.unwrap();
+    assert_eq!(object.content_type.as_deref(), Some("video/mp4"));
+    assert_eq!(object.content_length, Some(10));
+    assert_eq!(object.bytes().await.unwrap(), Bytes::from_static(b"0123456789"));
+
+    let range = storage.get_range("videos", "videos/a.mp4", 2, 5, None).await.unwrap();
+    assert_eq!(range.bytes().await.unwrap(), Bytes::from_static(b"2345"));
+    assert_eq!(storage.head("videos", "videos/a.mp4").await.unwrap().size, 10);
+
+    storage.delete("videos", "videos/a.mp4").await.unwrap();
+    assert!(matches!(storage.get("videos", "videos/a.mp4").await, Err(StorageError::NotFound(_))));
+    assert!(storage.head("videos", "videos/a.mp4").await.unwrap_err().is_not_found());
+    // Deleting again is fine
+    storage.delete("videos", "videos/a.mp4").await.unwrap();
+}
+
+#[tokio::test]
+async fn keys_cannot_leave_the_bucket() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+
+    for key in ["../secret", "videos/../../secret", "/etc/passwd", "videos\\a.mp4", ""] {
+        assert!(storage.put("videos", key, Bytes::from_static(b"x"), "video/mp4").await.is_err(), "{}", key);
+    }
+    assert!(storage.get("..", "a.mp4").await.is_err());
+}
+
+#[tokio::test]
+async fn listing_walks_the_prefix() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+    for key in ["videos/b.mp4", "videos/nested/c.webm", "videos/a.mp4", "thumbnails/a.jpg"] {
+        storage.put("videos", key, Bytes::from_static(b"data"), "video/mp4").await.unwrap();
+    }
+
+    let page = storage.list("videos", "videos/", None).await.unwrap();
+    let keys: Vec<&str> = page.objects.iter().map(|o| o.key.as_str()).collect();
+    assert_eq!(keys, ["videos/a.mp4", "videos/b.mp4", "videos/nested/c.webm"]);
+    assert_eq!(page.objects[0].size, 4);
+    assert!(page.objects[0].last_modified.is_some());
+    assert!(page.next.is_none());
+
+    assert!(storage.list("other", "", None).await.unwrap().objects.is_empty());
+}
+
+#[tokio::test]
+async fn multipart_uploads_are_assembled_in_order() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+
+    let upload_id = storage.create_multipart("videos", "videos/big.mp4", "video/mp4").await.unwrap();
+    let second = storage.upload_part("videos", "videos/big.mp4", &upload_id, 2, Bytes::from_static(b"world")).await.unwrap();
+    let first = storage.upload_part("videos", "videos/big.mp4", &upload_id, 1, Bytes::from_static(b"hello ")).await.unwrap();
+    storage.complete_multipart("videos", "videos/big.mp4", &upload_id, &[(1, first), (2, second)]).await.unwrap();
+
+    let object = storage.get("videos", "videos/big.mp4").await.unwrap();
+    assert_eq!(object.bytes().await.unwrap(), Bytes::from_static(b"hello world"));
+    assert_eq!(
+        object_sha256(&storage, "videos", "videos/big.mp4").await.unwrap(),
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    );
+    // The parts are gone, so aborting afterwards finds nothing to do
+    storage.abort_multipart("videos", "videos/big.mp4", &upload_id).await.unwrap();
+
+    let aborted = storage.create_multipart("videos", "videos/gone.mp4", "video/mp4").await.unwrap();
+    storage.upload_part("videos", "videos/gone.mp4", &aborted, 1, Bytes::from_static(b"x")).await.unwrap();
+    storage.abort_multipart("videos", "videos/gone.mp4", &aborted).await.unwrap();
+    assert!(storage.upload_part("videos", "videos/gone.mp4", &aborted, 2, Bytes::from_static(b"y")).await.unwrap_err().is_not_found());
+    assert!(storage.head("videos", "videos/gone.mp4").await.unwrap_err().is_not_found());
+}
+
+#[tokio::test]
+async fn parallel_downloads_read_local_files() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    storage.put("videos", "videos/source.mp4", data.clone().into(), "video/mp4").await.unwrap();
+
+    let dest = root.0.join("download.mp4");
+    let config = ParallelDownloadConfig { parallelism: 3, part_size: 1024 };
+    download_object_parallel(&storage, "videos", "videos/source.mp4", &dest, config).await.unwrap();
+    assert_eq!(std::fs::read(&dest).unwrap(), data);
+}
+
+#[tokio::test]
+async fn storage_classes_are_unsupported() {
+    let root = Root::new();
+    let storage = LocalStorage::new(&root.0);
+    storage.put("videos", "videos/a.mp4", Bytes::from_static(b"x"), "video/mp4").await.unwrap();
+
+    assert!(matches!(storage.set_storage_class("videos", "videos/a.mp4", "GLACIER").await, Err(StorageError::Unsupported(_))));
+    assert!(matches!(storage.restore("videos", "videos/a.mp4", 7).await, Err(StorageError::Unsupported(_))));
+}
+
+#[test]
+fn content_types_follow_the_extension() {
+    assert_eq!(content_type_for_key("videos/a.MP4"), Some("video/mp4"));
+    assert_eq!(content_type_for_key("hls/1/0.ts"), Some("video/mp2t"));
+    assert_eq!(content_type_for_key("thumbnails/a.jpg"), Some("image/jpeg"));
+    assert_eq!(content_type_for_key("videos/noextension"), None);
+}
@@ -0,0 +1,22 @@
+use video_streaming_backend::login_throttle::{block_secs, ACCOUNT_POLICY, IP_POLICY};
+
+#[test]
+fn first_failures_are_free() {
+    for failures in 1..=ACCOUNT_POLICY.free_attempts {
+        assert_eq!(block_secs(ACCOUNT_POLICY, failures, 900), 0);
+    }
+}
+
+#[test]
+fn delays_double_until_the_lockout() {
+    let delays: Vec<u64> = (4..=9).map(|failures| block_secs(ACCOUNT_POLICY, failures, 900)).collect();
+    assert_eq!(delays, vec![1, 2, 4, 8, 16, 32]);
+    assert_eq!(block_secs(ACCOUNT_POLICY, ACCOUNT_POLICY.lockout_threshold, 900), 900);
+    assert_eq!(block_secs(ACCOUNT_POLICY, ACCOUNT_POLICY.lockout_threshold + 3, 900), 900);
+}
+
+#[test]
+fn delays_are_capped_below_the_lockout() {
+    assert_eq!(block_secs(IP_POLICY, IP_POLICY.lockout_threshold - 1, 900), 60);
+    assert_eq!(block_secs(IP_POLICY, IP_POLICY.free_attempts + 1, 900), 1);
+}
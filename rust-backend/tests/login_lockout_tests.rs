@@ -0,0 +1,149 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::login_throttle;
+use video_streaming_backend::error::ApiError;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+fn login_request(email: &str, password: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/api/auth/login")
+        .peer_addr("203.0.113.7:50000".parse().unwrap())
+        .set_json(json!({ "username": email, "password": password }))
+        .to_request()
+}
+
+#[sqlx::test]
+async fn test_repeated_failures_delay_further_attempts(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    register_test_user(&app, "lockdelay").await;
+    let email = "lockdelay@example.com";
+
+    for _ in 0..4 {
+        let resp = test::call_service(&app, login_request(email, "wrong")).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+    }
+
+    // The fourth failure earned a one second wait, even for the right password
+    let resp = test::call_service(&app, login_request(email, "password123")).await;
+    assert_eq!(resp.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("Retry-After").unwrap(), "1");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "too_many_requests");
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let resp = test::call_service(&app, login_request(email, "password123")).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // Success cleared the account's failures
+    let resp = test::call_service(&app, login_request(email, "wrong")).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, login_request(email, "password123")).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_account_lockout_is_audit_logged(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "lockaudit").await;
+    let email = "LockAudit@example.com";
+
+    for _ in 0..login_throttle::ACCOUNT_POLICY.lockout_threshold {
+        login_throttle::record_failure(&pool, None, email, None, Some(user_id)).await;
+    }
+
+    match login_throttle::check(None, "lockaudit@example.com", None).await {
+        Err(ApiError::TooManyRequests { retry_after_secs, .. }) => {
+            assert!(retry_after_secs > login_throttle::lockout_duration() - 5)
+        }
+        other => panic!("expected a lockout, got {:?}", other),
+    }
+
+    let (event, audited_user, details) = sqlx::query_as::<_, (String, Option<i32>, serde_json::Value)>(
+        "SELECT event, user_id, details FROM audit_log WHERE details->>'email' = 'lockaudit@example.com'"
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(event, "login_lockout");
+    assert_eq!(audited_user, Some(user_id));
+    assert_eq!(details["scope"], "account");
+}
+
+#[sqlx::test]
+async fn test_ip_lockout_covers_every_account(pool: PgPool) {
+    let ip = "198.51.100.23";
+    for i in 0..login_throttle::IP_POLICY.lockout_threshold {
+        login_throttle::record_failure(&pool, None, &format!("spray{}@example.com", i), Some(ip), None).await;
+    }
+
+    assert!(login_throttle::check(None, "someone-else@example.com", Some(ip)).await.is_err());
+    assert!(login_throttle::check(None, "someone-else@example.com", Some("198.51.100.24")).await.is_ok());
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE ip_address = $1 AND details->>'scope' = 'ip'")
+        .bind(ip)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
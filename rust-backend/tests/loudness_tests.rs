@@ -0,0 +1,42 @@
+use video_streaming_backend::loudness::{parse_loudnorm_output, Loudness};
+
+const LOUDNORM_OUTPUT: &str = r#"Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'source':
+  Duration: 00:03:12.48, start: 0.000000, bitrate: 1820 kb/s
+[Parsed_loudnorm_0 @ 0x55d5c1a2b4c0] 
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-24.02",
+	"output_tp" : "-2.00",
+	"output_lra" : "7.50",
+	"output_thresh" : "-34.91",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}
+"#;
+
+#[test]
+fn measurements_are_read_from_the_json_summary() {
+    assert_eq!(
+        parse_loudnorm_output(LOUDNORM_OUTPUT),
+        Some(Loudness { integrated_lufs: -27.61, range_lu: Some(18.06), true_peak_dbtp: Some(-4.47) })
+    );
+}
+
+#[test]
+fn silence_and_missing_summaries_have_no_loudness() {
+    let silent = LOUDNORM_OUTPUT.replace("\"-27.61\"", "\"-inf\"").replace("\"-4.47\"", "\"-inf\"");
+    assert_eq!(parse_loudnorm_output(&silent), None);
+    assert_eq!(parse_loudnorm_output("Output file is empty, nothing was encoded"), None);
+    assert_eq!(parse_loudnorm_output("{ not json }"), None);
+}
+
+#[test]
+fn unreadable_optional_measurements_are_left_out() {
+    let no_peak = LOUDNORM_OUTPUT.replace("\"-4.47\"", "\"-inf\"");
+    let loudness = parse_loudnorm_output(&no_peak).unwrap();
+    assert_eq!(loudness.integrated_lufs, -27.61);
+    assert_eq!(loudness.true_peak_dbtp, None);
+}
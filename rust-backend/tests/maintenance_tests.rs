@@ -0,0 +1,57 @@
+use sqlx::PgPool;
+
+use video_streaming_backend::maintenance::{create_admin, reindex_search, videos_to_requeue, AdminAccount};
+
+async fn insert_video(pool: &PgPool, title: &str, duration: Option<i32>, thumbnail_url: Option<&str>) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, duration, thumbnail_url) VALUES ($1, $2, $3, $4) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(duration)
+        .bind(thumbnail_url)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_create_admin_creates_or_promotes(pool: PgPool) {
+    let created = create_admin(&pool, "opsadmin", Some("ops@example.com"), Some("hunter22")).await.unwrap();
+    let AdminAccount::Created(id) = created else { panic!("expected a new account, got {:?}", created) };
+    let (is_admin, password): (bool, String) = sqlx::query_as("SELECT is_admin, password FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(is_admin);
+    assert!(bcrypt::verify("hunter22", &password).unwrap());
+
+    // An existing account keeps its password unless a new one is given
+    sqlx::query("UPDATE users SET is_admin = FALSE WHERE id = $1").bind(id).execute(&pool).await.unwrap();
+    assert_eq!(create_admin(&pool, "opsadmin", None, None).await.unwrap(), AdminAccount::Promoted(id));
+    let (is_admin, unchanged): (bool, String) = sqlx::query_as("SELECT is_admin, password FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(is_admin);
+    assert_eq!(unchanged, password);
+
+    assert!(create_admin(&pool, "nobody", None, Some("secret")).await.is_err());
+}
+
+#[sqlx::test]
+async fn test_requeue_picks_unfinished_videos(pool: PgPool) {
+    let done = insert_video(&pool, "requeue-done", Some(12), Some("thumbnails/a.jpg")).await;
+    let no_duration = insert_video(&pool, "requeue-no-duration", None, Some("thumbnails/b.jpg")).await;
+    let no_thumbnail = insert_video(&pool, "requeue-no-thumbnail", Some(3), None).await;
+
+    let ids: Vec<i32> = videos_to_requeue(&pool, None).await.unwrap().iter().map(|v| v.id).collect();
+    assert!(ids.contains(&no_duration));
+    assert!(ids.contains(&no_thumbnail));
+    assert!(!ids.contains(&done));
+
+    let ids: Vec<i32> = videos_to_requeue(&pool, Some(done)).await.unwrap().iter().map(|v| v.id).collect();
+    assert_eq!(ids, vec![done]);
+
+    reindex_search(&pool).await.unwrap();
+}
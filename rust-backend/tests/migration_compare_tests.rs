@@ -0,0 +1,61 @@
+use chrono::Utc;
+
+use video_streaming_backend::migration_status::{blocking_drift, compare, embedded_migrations, up_to_date, AppliedMigration, MigrationState};
+
+fn applied(version: i64, checksum: &[u8], success: bool) -> AppliedMigration {
+    AppliedMigration {
+        version,
+        description: format!("migration {}", version),
+        installed_on: Utc::now(),
+        success,
+        checksum: checksum.to_vec(),
+    }
+}
+
+fn states(embedded: &[(i64, String, Vec<u8>)], applied: &[AppliedMigration]) -> Vec<(i64, MigrationState)> {
+    compare(embedded, applied).iter().map(|s| (s.version, s.state)).collect()
+}
+
+#[test]
+fn every_kind_of_drift_is_reported() {
+    let embedded = vec![
+        (1, "first".to_string(), vec![1]),
+        (2, "second".to_string(), vec![2]),
+        (3, "third".to_string(), vec![3]),
+        (4, "fourth".to_string(), vec![4]),
+    ];
+    let db = vec![applied(1, &[1], true), applied(2, &[9], true), applied(3, &[3], false), applied(7, &[7], true)];
+
+    assert_eq!(states(&embedded, &db), vec![
+        (1, MigrationState::Applied),
+        (2, MigrationState::ChecksumMismatch),
+        (3, MigrationState::Failed),
+        (4, MigrationState::Pending),
+        (7, MigrationState::Unknown),
+    ]);
+
+    let statuses = compare(&embedded, &db);
+    assert!(!up_to_date(&statuses));
+    let blocking: Vec<i64> = blocking_drift(&statuses).iter().map(|s| s.version).collect();
+    assert_eq!(blocking, vec![2, 3, 4]);
+}
+
+#[test]
+fn newer_migrations_alone_do_not_block() {
+    let embedded = vec![(1, "first".to_string(), vec![1])];
+    let statuses = compare(&embedded, &[applied(1, &[1], true), applied(2, &[2], true)]);
+    assert!(blocking_drift(&statuses).is_empty());
+    assert!(!up_to_date(&statuses));
+
+    let statuses = compare(&embedded, &[applied(1, &[1], true)]);
+    assert!(up_to_date(&statuses));
+}
+
+#[test]
+fn embedded_migrations_exclude_down_scripts() {
+    let embedded = embedded_migrations();
+    assert!(!embedded.is_empty());
+    let mut versions: Vec<i64> = embedded.iter().map(|(version, _, _)| *version).collect();
+    versions.dedup();
+    assert_eq!(versions.len(), embedded.len());
+}
@@ -0,0 +1,207 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::moderation::{self, ContentClassifier};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, status: &str) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, tags, moderation_status) VALUES ($1, $2, ARRAY['moderated'], $3) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(status)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn listed_titles(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, uri: &str) -> Vec<String> {
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(app, req).await).await;
+    body.as_array().unwrap().iter().map(|v| v["title"].as_str().unwrap().to_string()).collect()
+}
+
+// Scores frames by file name so tests can say which frames are unsafe
+struct NameClassifier;
+
+impl ContentClassifier for NameClassifier {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn classify<'a>(&'a self, frame: &'a Path) -> BoxFuture<'a, Result<f64, Box<dyn std::error::Error + Send + Sync>>> {
+        let score = if frame.to_string_lossy().contains("unsafe") { 0.95 } else { 0.1 };
+        Box::pin(async move { Ok(score) })
+    }
+}
+
+#[sqlx::test]
+async fn test_unapproved_videos_are_not_listed(pool: PgPool) {
+    insert_video(&pool, "approved_video", "approved").await;
+    insert_video(&pool, "pending_video", "pending").await;
+    insert_video(&pool, "flagged_video", "flagged").await;
+    let app = setup_test_app(pool.clone()).await;
+
+    assert_eq!(listed_titles(&app, "/api/videos").await, vec!["approved_video"]);
+    assert_eq!(listed_titles(&app, "/api/videos/tag/moderated").await, vec!["approved_video"]);
+    assert_eq!(listed_titles(&app, "/api/videos/search/video").await, vec!["approved_video"]);
+
+    let req = test::TestRequest::post()
+        .uri("/api/videos/search")
+        .set_json(json!({ "text": "video" }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn test_admin_moderation_queue_and_override(pool: PgPool) {
+    let pending_id = insert_video(&pool, "queued_video", "pending").await;
+    let flagged_id = insert_video(&pool, "suspect_video", "flagged").await;
+    let app = setup_test_app(pool.clone()).await;
+    let (_, user_token) = register_test_user(&app, "moderation_user").await;
+    let (admin_id, admin_token) = register_test_user(&app, "moderation_admin").await;
+    make_admin(&pool, admin_id).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/moderation")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/moderation")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let queue: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(queue.as_array().unwrap().len(), 2);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/moderation?status=flagged")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let queue: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(queue[0]["id"], flagged_id);
+    assert_eq!(queue.as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/videos/{}/moderation", pending_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({ "status": "approved" }))
+        .to_request();
+    let video: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(video["moderation_status"], "approved");
+    assert_eq!(video["moderated_by"], admin_id);
+
+    assert_eq!(listed_titles(&app, "/api/videos").await, vec!["queued_video"]);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/videos/{}/moderation", pending_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({ "status": "pending" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_classifier_verdict_respects_admin_override(pool: PgPool) {
+    let clean_id = insert_video(&pool, "clean_video", "pending").await;
+    let overridden_id = insert_video(&pool, "overridden_video", "pending").await;
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, _) = register_test_user(&app, "moderation_override").await;
+
+    let clean = vec![(5.0, PathBuf::from("frame-0.jpg")), (50.0, PathBuf::from("frame-1.jpg"))];
+    let verdict = moderation::classify_frames(&NameClassifier, &clean).await.unwrap();
+    assert_eq!(verdict.status, moderation::STATUS_APPROVED);
+    assert!(moderation::apply_verdict(&pool, clean_id, &verdict).await.unwrap());
+
+    let unsafe_frames = vec![(5.0, PathBuf::from("frame-0.jpg")), (50.0, PathBuf::from("unsafe-1.jpg"))];
+    let verdict = moderation::classify_frames(&NameClassifier, &unsafe_frames).await.unwrap();
+    assert_eq!(verdict.status, moderation::STATUS_FLAGGED);
+    assert!(verdict.reason.as_deref().unwrap().contains("50.0s"));
+
+    // An admin approved this one already, so the classifier can't flag it
+    moderation::set_override(&pool, overridden_id, admin_id, moderation::STATUS_APPROVED, None).await.unwrap();
+    assert!(!moderation::apply_verdict(&pool, overridden_id, &verdict).await.unwrap());
+
+    let statuses: Vec<(i32, String)> = sqlx::query_as("SELECT id, moderation_status FROM videos ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(statuses, vec![(clean_id, "approved".to_string()), (overridden_id, "approved".to_string())]);
+}
@@ -0,0 +1,128 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32, allow_embed: bool) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, thumbnail_url, uploaded_by, allow_embed) VALUES ($1, $2, $3, $4, $5) RETURNING id"
+    )
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(format!("thumbnails/{}.jpg", title))
+    .bind(uploaded_by)
+    .bind(allow_embed)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+async fn test_oembed_for_video_url(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "oembedauthor").await;
+    let video_id = insert_video(&pool, "oembed-video", owner_id, true).await;
+
+    let url = format!("http://localhost/video/{}", video_id);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/oembed?url={}&maxwidth=480", urlencoding::encode(&url)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["version"], "1.0");
+    assert_eq!(body["type"], "video");
+    assert_eq!(body["title"], "oembed-video");
+    assert_eq!(body["author_name"], "oembedauthor");
+    assert_eq!(body["thumbnail_url"], "http://localhost/api/thumbnails/oembed-video.jpg");
+    assert_eq!(body["width"], 480);
+    assert_eq!(body["height"], 270);
+    let html = body["html"].as_str().unwrap();
+    assert!(html.starts_with(&format!("<iframe src=\"http://localhost/embed/{}\" width=\"480\" height=\"270\"", video_id)));
+}
+
+#[sqlx::test]
+async fn test_oembed_rejects_unknown_and_unembeddable_videos(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "oembedprivate").await;
+    let video_id = insert_video(&pool, "oembed-private", owner_id, false).await;
+
+    for (url, expected) in [
+        (format!("http://localhost/video/{}", video_id), http::StatusCode::FORBIDDEN),
+        ("http://localhost/video/999999".to_string(), http::StatusCode::NOT_FOUND),
+        ("https://other.example.com/video/1".to_string(), http::StatusCode::NOT_FOUND),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/oembed?url={}", urlencoding::encode(&url)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected, "{}", url);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/oembed?url={}&format=xml", urlencoding::encode("http://localhost/video/1")))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_IMPLEMENTED);
+}
@@ -0,0 +1,28 @@
+use video_streaming_backend::oembed::{embed_size, video_id_from_url};
+
+#[test]
+fn recognizes_video_and_embed_urls() {
+    let base = "https://videos.example.com";
+    assert_eq!(video_id_from_url("https://videos.example.com/video/42", base), Some(42));
+    assert_eq!(video_id_from_url("https://VIDEOS.example.com/video/42/?t=10#c", base), Some(42));
+    assert_eq!(video_id_from_url("https://videos.example.com/embed/7", base), Some(7));
+}
+
+#[test]
+fn rejects_other_sites_and_pages() {
+    let base = "https://videos.example.com";
+    assert_eq!(video_id_from_url("https://evil.example.com/video/42", base), None);
+    assert_eq!(video_id_from_url("https://videos.example.com.evil.com/video/42", base), None);
+    assert_eq!(video_id_from_url("https://videos.example.com/users", base), None);
+    assert_eq!(video_id_from_url("https://videos.example.com/video/abc", base), None);
+    assert_eq!(video_id_from_url("https://videos.example.com/video/-1", base), None);
+}
+
+#[test]
+fn embed_size_fits_consumer_limits() {
+    assert_eq!(embed_size(None, None), (640, 360));
+    assert_eq!(embed_size(Some(1920), Some(1080)), (640, 360));
+    assert_eq!(embed_size(Some(320), None), (320, 180));
+    assert_eq!(embed_size(None, Some(180)), (320, 180));
+    assert_eq!(embed_size(Some(400), Some(180)), (320, 180));
+}
@@ -0,0 +1,170 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(uploaded_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn request_token(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, auth_token: &str, previous: Option<&str>) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/playback-token", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", auth_token)))
+        .set_json(json!({ "token": previous }))
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_playlists_require_a_playback_token(pool: PgPool) {
+    // Every test in this file runs with tokens required
+    std::env::set_var("PLAYBACK_TOKENS_REQUIRED", "on");
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, auth_token) = register_test_user(&app, "tokenviewer").await;
+    let video_id = insert_video(&pool, "token-video", user_id).await;
+    let other_video_id = insert_video(&pool, "token-other", user_id).await;
+
+    let playlist = |video_id: i32, playback_token: Option<&str>| {
+        let uri = match playback_token {
+            Some(t) => format!("/api/videos/{}/hls/index.m3u8?playback_token={}", video_id, urlencoding::encode(t)),
+            None => format!("/api/videos/{}/hls/index.m3u8", video_id),
+        };
+        test::TestRequest::get().uri(&uri).to_request()
+    };
+    let resp = test::call_service(&app, playlist(video_id, None)).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let resp = request_token(&app, video_id, &auth_token, None).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let token = body["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with(&format!("{}.", user_id)));
+    assert!(body["expires_at"].is_string());
+
+    // Past the token check the video simply has no HLS package
+    let resp = test::call_service(&app, playlist(video_id, Some(&token))).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    let resp = test::call_service(&app, playlist(other_video_id, Some(&token))).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+    for uri in ["stream", "download", "audio-tracks/1"] {
+        let resp = test::call_service(&app, test::TestRequest::get()
+            .uri(&format!("/api/videos/{}/{}", video_id, uri))
+            .to_request()).await;
+        assert_eq!(resp.status(), http::StatusCode::FORBIDDEN, "{}", uri);
+    }
+
+    // With the token a download gets as far as the video's own checks; it has no such rendition
+    let resp = test::call_service(&app, test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/download?quality=720p&playback_token={}", video_id, urlencoding::encode(&token)))
+        .insert_header(("Authorization", format!("Bearer {}", auth_token)))
+        .to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_playback_tokens_renew_their_stream(pool: PgPool) {
+    std::env::set_var("PLAYBACK_TOKENS_REQUIRED", "on");
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, auth_token) = register_test_user(&app, "tokenrenewer").await;
+    let (_, other_auth_token) = register_test_user(&app, "tokenborrower").await;
+    let video_id = insert_video(&pool, "token-renewed", user_id).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/playback-token", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+    let resp = request_token(&app, video_id, &auth_token, None).await;
+    let first: serde_json::Value = test::read_body_json(resp).await;
+    let resp = request_token(&app, video_id, &auth_token, first["token"].as_str()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let renewed: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(renewed["stream_id"], first["stream_id"]);
+
+    // Someone else's token doesn't carry over
+    let resp = request_token(&app, video_id, &other_auth_token, first["token"].as_str()).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}/playback-token", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", auth_token)))
+        .set_json(json!({ "token": renewed["token"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+    let resp = request_token(&app, 999_999, &auth_token, None).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,57 @@
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+
+use video_streaming_backend::playback_tokens::{issue, limit_reached, parse, streams_key, verify, PlaybackToken};
+
+const NOW: i64 = 1_760_000_000;
+
+// Signing has no default secret
+fn set_signing_secret() {
+    std::env::set_var("URL_SIGNING_SECRET", "test_url_signing_secret");
+}
+
+#[test]
+fn tokens_name_the_account_and_stream() {
+    set_signing_secret();
+    let token = issue(12, 7, "s1", NOW + 300);
+    assert!(token.starts_with("7.s1.1760000300."));
+    assert_eq!(
+        verify(&token, 12, NOW).unwrap(),
+        PlaybackToken { user_id: 7, stream_id: "s1".to_string(), expires: NOW + 300 }
+    );
+}
+
+#[test]
+fn tokens_only_play_their_video_until_they_expire() {
+    set_signing_secret();
+    let token = issue(12, 7, "s1", NOW + 300);
+    assert!(verify(&token, 13, NOW).is_err());
+    assert!(verify(&token, 12, NOW + 300).is_err());
+    // Expired tokens still identify their stream, for renewing it
+    assert_eq!(parse(&token, 12).unwrap().stream_id, "s1");
+}
+
+#[test]
+fn edited_tokens_are_refused() {
+    set_signing_secret();
+    let token = issue(12, 7, "s1", NOW + 300);
+    assert!(parse(&token.replacen("7.", "8.", 1), 12).is_none());
+    assert!(parse(&token.replace("1760000300", "1760009999"), 12).is_none());
+    assert!(parse(&format!("{}.extra", token), 12).is_none());
+    assert!(parse("", 12).is_none());
+    assert_eq!(verify("7.s1.abc", 12, NOW).unwrap_err().status_code(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn streams_are_tracked_per_account() {
+    assert_eq!(streams_key(7), "playback_streams:7");
+}
+
+#[test]
+fn accounts_at_the_limit_are_refused_with_429() {
+    let error = limit_reached(2, 90);
+    assert_eq!(error.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error.error_response().headers().get("Retry-After").unwrap(), "90");
+    assert!(error.to_string().contains("2 videos"));
+    assert!(limit_reached(1, 90).to_string().contains("somewhere else"));
+}
@@ -0,0 +1,134 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Premiere video")
+        .bind("premiere_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn put_premiere(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, token: Option<&str>, premiere_at: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let mut req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/premiere", video_id))
+        .set_json(json!({ "premiereAt": premiere_at }));
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[sqlx::test]
+async fn test_premiere_locks_playback_and_schedules_watch_party(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (uploader_id, uploader_token) = register_test_user(&app, "premiere_uploader").await;
+    let (_, viewer_token) = register_test_user(&app, "premiere_viewer").await;
+    let video_id = insert_video(&pool, uploader_id).await;
+
+    let premiere_at = chrono::Utc::now() + chrono::Duration::hours(3);
+    let (status, _) = put_premiere(&app, video_id, Some(&viewer_token), json!(premiere_at.to_rfc3339())).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+    let past = chrono::Utc::now() - chrono::Duration::minutes(1);
+    let (status, _) = put_premiere(&app, video_id, Some(&uploader_token), json!(past.to_rfc3339())).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let (status, scheduled) = put_premiere(&app, video_id, Some(&uploader_token), json!(premiere_at.to_rfc3339())).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(scheduled["video"]["premieres_in_secs"].as_i64().unwrap() > 3 * 3600 - 60);
+    assert_eq!(scheduled["watchParty"]["host_user_id"], uploader_id);
+    assert_eq!(scheduled["watchParty"]["scheduled_start"], scheduled["video"]["premiere_at"]);
+
+    // The video is listed with its countdown, but can't be played yet
+    let req = test::TestRequest::get().uri(&format!("/api/videos/{}", video_id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert!(video["premieres_in_secs"].as_i64().unwrap() > 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/stream", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+    let error: serde_json::Value = test::read_body_json(resp).await;
+    assert!(error["error"].as_str().unwrap().starts_with("This video premieres at"));
+
+    // Cancelling opens playback and clears the room's schedule
+    let (status, cancelled) = put_premiere(&app, video_id, Some(&uploader_token), serde_json::Value::Null).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert!(cancelled["video"]["premiere_at"].is_null());
+    assert!(cancelled["video"]["premieres_in_secs"].is_null());
+    assert!(cancelled["watchParty"]["scheduled_start"].is_null());
+}
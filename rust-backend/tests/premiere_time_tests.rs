@@ -0,0 +1,72 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use video_streaming_backend::models::Video;
+use video_streaming_backend::premieres::{check_started, upcoming, validate_premiere_at};
+
+fn video(premiere_at: Option<chrono::NaiveDateTime>) -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id: 5,
+        title: "Premiere".to_string(),
+        description: None,
+        s3_key: "videos/5-original.mp4".to_string(),
+        thumbnail_url: None,
+        uploaded_by: Some(3),
+        upload_date: Some(uploaded),
+        tags: None,
+        view_count: None,
+        category_ids: None,
+        duration: None,
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: Vec::new(),
+        blocked_countries: Vec::new(),
+        allow_embed: true,
+        allow_download: false,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at,
+    }
+}
+
+#[test]
+fn only_the_uploader_plays_before_the_premiere() {
+    let soon = Utc::now().naive_utc() + chrono::Duration::minutes(10);
+    let premiering = video(Some(soon));
+    assert!(upcoming(&premiering, Utc::now().naive_utc()));
+    assert!(check_started(&premiering, None).is_err());
+    assert!(check_started(&premiering, Some(4)).is_err());
+    assert!(check_started(&premiering, Some(3)).is_ok());
+
+    // Videos without an uploader are locked for everyone
+    let mut unowned = video(Some(soon));
+    unowned.uploaded_by = None;
+    assert!(check_started(&unowned, None).is_err());
+}
+
+#[test]
+fn anyone_plays_once_premiered_or_without_a_premiere() {
+    let premiered = video(Some(Utc::now().naive_utc() - chrono::Duration::seconds(1)));
+    assert!(!upcoming(&premiered, Utc::now().naive_utc()));
+    assert!(check_started(&premiered, None).is_ok());
+    assert!(check_started(&video(None), None).is_ok());
+}
+
+#[test]
+fn premiere_time_must_be_ahead_and_within_the_schedule_window() {
+    let now = Utc.with_ymd_and_hms(2025, 9, 1, 12, 0, 0).unwrap();
+    assert!(validate_premiere_at(now, now).is_err());
+    assert!(validate_premiere_at(now - chrono::Duration::hours(1), now).is_err());
+    assert!(validate_premiere_at(now + chrono::Duration::days(91), now).is_err());
+
+    let at = validate_premiere_at(now + chrono::Duration::hours(2), now).unwrap();
+    assert_eq!(at, NaiveDate::from_ymd_opt(2025, 9, 1).unwrap().and_hms_opt(14, 0, 0).unwrap());
+}
@@ -0,0 +1,127 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::job_queue::MediaJobKind;
+use video_streaming_backend::processing::{self, JOB_DONE, JOB_FAILED, JOB_QUEUED, JOB_RUNNING};
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    let storage = storage::init_storage().await;
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+async fn insert_video(pool: &PgPool, s3_key: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, moderation_status) VALUES ('Processing video', $1, 'approved') RETURNING id")
+        .bind(s3_key)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn status(pool: &PgPool, video_id: i32) -> String {
+    sqlx::query_scalar("SELECT processing_status FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_status_follows_jobs(pool: PgPool) {
+    let video_id = insert_video(&pool, "processing_key_1").await;
+    assert_eq!(status(&pool, video_id).await, "uploaded");
+
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Duration, JOB_QUEUED, None).await.unwrap(), "uploaded");
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Thumbnail, JOB_QUEUED, None).await.unwrap(), "uploaded");
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Duration, JOB_RUNNING, None).await.unwrap(), "processing");
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Duration, JOB_DONE, None).await.unwrap(), "processing");
+    // A job being retried keeps the video processing
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Thumbnail, JOB_QUEUED, Some("timeout")).await.unwrap(), "processing");
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Thumbnail, JOB_DONE, None).await.unwrap(), "ready");
+    assert_eq!(status(&pool, video_id).await, "ready");
+
+    let update = processing::current(&pool, video_id).await.unwrap().unwrap();
+    assert_eq!(update.processing_status, "ready");
+    let kinds: Vec<&str> = update.jobs.iter().map(|j| j.kind.as_str()).collect();
+    assert_eq!(kinds, ["duration", "thumbnail"]);
+    assert!(update.jobs.iter().all(|j| j.state == JOB_DONE && j.error.is_none()));
+
+    // Reprocessing starts over, and a job that is given up on fails the video
+    processing::restart(&pool, video_id).await.unwrap();
+    assert!(processing::jobs(&pool, video_id).await.unwrap().is_empty());
+    processing::record(&pool, video_id, MediaJobKind::Transcode, JOB_QUEUED, None).await.unwrap();
+    assert_eq!(status(&pool, video_id).await, "uploaded");
+    assert_eq!(processing::record(&pool, video_id, MediaJobKind::Transcode, JOB_FAILED, Some("missing")).await.unwrap(), "failed");
+
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1").bind(video_id).execute(&pool).await.unwrap();
+    assert!(processing::current(&pool, video_id).await.unwrap().is_none());
+}
+
+#[sqlx::test]
+async fn test_unchanged_status_keeps_list_etag(pool: PgPool) {
+    let video_id = insert_video(&pool, "processing_key_2").await;
+    processing::record(&pool, video_id, MediaJobKind::Duration, JOB_RUNNING, None).await.unwrap();
+    let updated_at = |pool: PgPool| async move {
+        sqlx::query_scalar::<_, chrono::NaiveDateTime>("SELECT updated_at FROM videos WHERE id = $1")
+            .bind(video_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+    };
+    let before = updated_at(pool.clone()).await;
+
+    processing::record(&pool, video_id, MediaJobKind::Thumbnail, JOB_RUNNING, None).await.unwrap();
+    assert_eq!(updated_at(pool.clone()).await, before);
+}
+
+#[sqlx::test]
+async fn test_list_filters_by_processing_status(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let ready = insert_video(&pool, "processing_key_3").await;
+    processing::record(&pool, ready, MediaJobKind::Duration, JOB_DONE, None).await.unwrap();
+    let waiting = insert_video(&pool, "processing_key_4").await;
+
+    let list = |query: &str| test::TestRequest::get().uri(&format!("/api/videos{}", query)).to_request();
+
+    let resp = test::call_service(&app, list("?processing_status=uploaded")).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let etag = resp.headers().get(http::header::ETAG).unwrap().clone();
+    let videos: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let ids: Vec<i64> = videos.iter().map(|v| v["id"].as_i64().unwrap()).collect();
+    assert_eq!(ids, [waiting as i64]);
+    assert_eq!(videos[0]["processing_status"], "uploaded");
+
+    let resp = test::call_service(&app, list("?processing_status=Ready")).await;
+    assert_ne!(resp.headers().get(http::header::ETAG).unwrap(), &etag);
+    let videos: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(videos.iter().any(|v| v["id"] == ready));
+    assert!(videos.iter().all(|v| v["processing_status"] == "ready"));
+
+    let resp = test::call_service(&app, list("?processing_status=encoding")).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,159 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::notifications::create_notification;
+use video_streaming_backend::storage;
+use video_streaming_backend::AppState;
+
+const VAPID_PUBLIC_KEY: &str = "BP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A8";
+const UA_PUBLIC: &str = "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4";
+const ENDPOINT: &str = "https://push.example.net/send/browser-1";
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+    std::env::set_var("VAPID_PUBLIC_KEY", VAPID_PUBLIC_KEY);
+    std::env::set_var("VAPID_PRIVATE_KEY", "yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw");
+    std::env::set_var("VAPID_SUBJECT", "mailto:admin@example.com");
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn subscribe(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, body: serde_json::Value) -> http::StatusCode {
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/push-subscriptions")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await.status()
+}
+
+async fn push_pending(pool: &PgPool, notification_id: i32) -> bool {
+    sqlx::query_scalar("SELECT push_pending FROM notifications WHERE id = $1")
+        .bind(notification_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_subscribed_users_get_notifications_pushed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (ann_id, ann_token) = register_test_user(&app, "push_ann").await;
+    let (bob_id, _) = register_test_user(&app, "push_bob").await;
+
+    let req = test::TestRequest::get().uri("/api/push/public-key").to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["publicKey"], VAPID_PUBLIC_KEY);
+
+    let keys = json!({ "p256dh": UA_PUBLIC, "auth": "BTBZMqHH6r4Tts7J_aSIgg" });
+    assert_eq!(subscribe(&app, &ann_token, json!({ "endpoint": "http://push.example.net/x", "keys": keys })).await, http::StatusCode::BAD_REQUEST);
+    assert_eq!(subscribe(&app, &ann_token, json!({ "endpoint": ENDPOINT, "expirationTime": null, "keys": keys })).await, http::StatusCode::CREATED);
+
+    // The keys stay on the server
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/push-subscriptions")
+        .insert_header(("Authorization", format!("Bearer {}", ann_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["endpoint"], ENDPOINT);
+    assert!(body[0].get("auth").is_none() && body[0].get("p256dh").is_none());
+
+    let for_ann = create_notification(&pool, ann_id, "watch_party_invite", json!({ "videoTitle": "Launch day" })).await.unwrap();
+    let for_bob = create_notification(&pool, bob_id, "watch_party_invite", json!({ "videoTitle": "Launch day" })).await.unwrap();
+    assert!(push_pending(&pool, for_ann.id).await);
+    assert!(!push_pending(&pool, for_bob.id).await);
+
+    let req = test::TestRequest::delete()
+        .uri("/api/users/me/push-subscriptions")
+        .insert_header(("Authorization", format!("Bearer {}", ann_token)))
+        .set_json(json!({ "endpoint": ENDPOINT }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    let later = create_notification(&pool, ann_id, "watch_party_invite", json!({ "videoTitle": "Launch day" })).await.unwrap();
+    assert!(!push_pending(&pool, later.id).await);
+}
+
+#[sqlx::test]
+async fn test_a_browser_registered_again_moves_to_the_new_user(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, ann_token) = register_test_user(&app, "push_ann").await;
+    let (bob_id, bob_token) = register_test_user(&app, "push_bob").await;
+
+    let body = json!({ "endpoint": ENDPOINT, "keys": { "p256dh": UA_PUBLIC, "auth": "BTBZMqHH6r4Tts7J_aSIgg" } });
+    assert_eq!(subscribe(&app, &ann_token, body.clone()).await, http::StatusCode::CREATED);
+    assert_eq!(subscribe(&app, &bob_token, body).await, http::StatusCode::CREATED);
+
+    let owners: Vec<i32> = sqlx::query_scalar("SELECT user_id FROM push_subscriptions WHERE endpoint = $1")
+        .bind(ENDPOINT)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(owners, vec![bob_id]);
+
+    // Ann can't remove Bob's subscription
+    let req = test::TestRequest::delete()
+        .uri("/api/users/me/push-subscriptions")
+        .insert_header(("Authorization", format!("Bearer {}", ann_token)))
+        .set_json(json!({ "endpoint": ENDPOINT }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
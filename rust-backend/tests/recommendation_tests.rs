@@ -0,0 +1,215 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::recommendations::export_interactions;
+use video_streaming_backend::storage::{self, LocalStorage, ObjectStore};
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn insert_video(pool: &PgPool, title: &str, view_count: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, duration, view_count, moderation_status) VALUES ($1, $2, 100, $3, 'approved') RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(view_count)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn import(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, body: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::put()
+        .uri("/api/admin/recommendations")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let body = test::read_body(resp).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+async fn home_feed(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: Option<&str>) -> Vec<i64> {
+    let mut req = test::TestRequest::get().uri("/api/feed/home?limit=3");
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let videos: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    videos.iter().map(|v| v["id"].as_i64().unwrap()).collect()
+}
+
+#[sqlx::test]
+async fn test_home_feed_serves_imported_recommendations_first(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "recadmin").await;
+    make_admin(&pool, admin_id).await;
+    let (viewer_id, viewer_token) = register_test_user(&app, "recviewer").await;
+    let popular = insert_video(&pool, "popular", 1000).await;
+    let niche = insert_video(&pool, "niche", 1).await;
+    let hidden = insert_video(&pool, "hidden", 500).await;
+    let middling = insert_video(&pool, "middling", 100).await;
+
+    let (status, summary) = import(&app, &admin_token, json!({
+        "model": "als-test",
+        "lists": [
+            { "userId": viewer_id, "videoIds": [niche, hidden, 999999, niche] },
+            { "userId": 999999, "videoIds": [popular] }
+        ]
+    })).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(summary, json!({ "users": 1, "recommendations": 2 }));
+
+    // Recommendations that stopped being public are skipped
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1").bind(hidden).execute(&pool).await.unwrap();
+
+    assert_eq!(home_feed(&app, Some(&viewer_token)).await, vec![niche as i64, popular as i64, middling as i64]);
+    assert_eq!(home_feed(&app, Some(&admin_token)).await, vec![popular as i64, middling as i64, niche as i64]);
+    assert_eq!(home_feed(&app, None).await, vec![popular as i64, middling as i64, niche as i64]);
+
+    // An empty list clears the user's recommendations
+    import(&app, &admin_token, json!({ "lists": [{ "userId": viewer_id, "videoIds": [] }] })).await;
+    assert_eq!(home_feed(&app, Some(&viewer_token)).await, vec![popular as i64, middling as i64, niche as i64]);
+}
+
+#[sqlx::test]
+async fn test_recommendation_import_is_admin_only_and_validated(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "recadmin").await;
+    make_admin(&pool, admin_id).await;
+    let (user_id, user_token) = register_test_user(&app, "recuser").await;
+
+    let body = json!({ "lists": [{ "userId": user_id, "videoIds": [] }] });
+    let (status, _) = import(&app, &user_token, body).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+
+    let (status, _) = import(&app, &admin_token, json!({ "lists": [] })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let too_long: Vec<i32> = (1..=201).collect();
+    let (status, error) = import(&app, &admin_token, json!({ "lists": [{ "userId": user_id, "videoIds": too_long }] })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("more than 200 videos"));
+}
+
+#[sqlx::test]
+async fn test_interaction_export_combines_views_watch_time_and_comments(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, _) = register_test_user(&app, "interacting").await;
+    let watched = insert_video(&pool, "watched", 0).await;
+    let commented = insert_video(&pool, "commented", 0).await;
+
+    for _ in 0..2 {
+        sqlx::query("INSERT INTO video_views (video_id, user_id) VALUES ($1, $2)")
+            .bind(watched)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    // Anonymous views aren't part of the matrix
+    sqlx::query("INSERT INTO video_views (video_id) VALUES ($1)").bind(watched).execute(&pool).await.unwrap();
+    for secs in [30.0, 45.5] {
+        sqlx::query("INSERT INTO playback_events (video_id, user_id, watched_secs) VALUES ($1, $2, $3)")
+            .bind(watched)
+            .bind(user_id)
+            .bind(secs)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("INSERT INTO comments (video_id, user_id, content) VALUES ($1, $2, 'Nice')")
+        .bind(commented)
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let root = std::env::temp_dir().join(format!("recommendation-export-test-{}", uuid::Uuid::new_v4()));
+    let storage = LocalStorage::new(&root);
+    let export = export_interactions(&pool, &storage, "videos").await.unwrap();
+    assert_eq!(export.row_count, 2);
+    assert!(export.s3_key.starts_with("exports/interactions/"));
+
+    let csv = storage.get("videos", &export.s3_key).await.unwrap().bytes().await.unwrap();
+    let csv = String::from_utf8(csv.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "user_id,video_id,views,watched_secs,watch_fraction,comments,last_interaction_at");
+    assert!(lines[1].starts_with(&format!("{},{},2,75.5,0.755,0,", user_id, watched)), "{}", lines[1]);
+    assert!(lines[2].starts_with(&format!("{},{},0,0.0,0.000,1,", user_id, commented)), "{}", lines[2]);
+    let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,121 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Reprocess me")
+        .bind("reprocess_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn reprocess(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, token: &str) -> http::StatusCode {
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/reprocess", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    test::call_service(app, req).await.status()
+}
+
+#[sqlx::test]
+async fn test_reprocess_requires_owner_or_admin(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "owner").await;
+    let (_, other_token) = register_test_user(&app, "someone_else").await;
+    let (admin_id, admin_token) = register_test_user(&app, "admin").await;
+    let video_id = insert_video(&pool, owner_id).await;
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(admin_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(reprocess(&app, video_id, &other_token).await, http::StatusCode::FORBIDDEN);
+
+    // Owner and admin get past the permission check; without Redis there is no queue to use
+    assert_eq!(reprocess(&app, video_id, &owner_token).await, http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(reprocess(&app, video_id, &admin_token).await, http::StatusCode::SERVICE_UNAVAILABLE);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/reprocess", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_reprocess_unknown_video(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "owner").await;
+
+    assert_eq!(reprocess(&app, 999_999, &token).await, http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+use actix_web::{middleware, test, web, App, HttpResponse};
+
+use video_streaming_backend::request_metrics::{record_request, snapshot, Histogram, BUCKET_BOUNDS_MS};
+
+#[test]
+fn histogram_buckets_by_upper_bound() {
+    let mut histogram = Histogram::default();
+    histogram.record(Duration::from_millis(3), false);
+    histogram.record(Duration::from_millis(5), false);
+    histogram.record(Duration::from_millis(40), false);
+    histogram.record(Duration::from_secs(60), true);
+
+    assert_eq!(histogram.buckets.len(), BUCKET_BOUNDS_MS.len() + 1);
+    assert_eq!(histogram.buckets[0], 2);
+    assert_eq!(histogram.buckets[3], 1);
+    assert_eq!(*histogram.buckets.last().unwrap(), 1);
+    assert_eq!(histogram.count, 4);
+    assert_eq!(histogram.errors, 1);
+    assert_eq!(histogram.max_ms, 60_000.0);
+}
+
+#[test]
+fn quantiles_come_from_buckets() {
+    let mut histogram = Histogram::default();
+    assert_eq!(histogram.quantile_ms(0.5), None);
+
+    for _ in 0..90 {
+        histogram.record(Duration::from_millis(20), false);
+    }
+    for _ in 0..10 {
+        histogram.record(Duration::from_millis(700), false);
+    }
+    assert_eq!(histogram.quantile_ms(0.5), Some(25.0));
+    assert_eq!(histogram.quantile_ms(0.95), Some(700.0)); // capped at the slowest request
+    assert_eq!(histogram.quantile_ms(0.9), Some(25.0));
+}
+
+#[actix_web::test]
+async fn requests_are_recorded_per_route_pattern() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(record_request))
+            .route("/items/{id}", web::get().to(HttpResponse::Ok))
+            .route("/broken", web::get().to(HttpResponse::InternalServerError))
+    ).await;
+
+    for uri in ["/items/1", "/items/2", "/broken", "/nowhere"] {
+        test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    }
+
+    let stats = snapshot();
+    let route = |pattern: &str| stats.iter().find(|s| s.route == pattern).unwrap();
+    assert_eq!(route("/items/{id}").count, 2);
+    assert_eq!(route("/items/{id}").method, "GET");
+    assert_eq!(route("/broken").errors, 1);
+    assert_eq!(route("(unmatched)").count, 1);
+    assert!(stats.iter().all(|s| !s.route.contains("/items/1")));
+}
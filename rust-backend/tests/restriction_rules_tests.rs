@@ -0,0 +1,51 @@
+use actix_web::test::TestRequest;
+
+use video_streaming_backend::restrictions;
+
+fn codes(codes: &[&str]) -> Vec<String> {
+    codes.iter().map(|c| c.to_string()).collect()
+}
+
+#[test]
+fn normalizes_country_codes() {
+    assert_eq!(restrictions::normalize_countries(&codes(&["us", " De", "US"])).unwrap(), codes(&["DE", "US"]));
+    assert!(restrictions::normalize_countries(&codes(&["USA"])).is_err());
+    assert!(restrictions::normalize_countries(&codes(&["1A"])).is_err());
+}
+
+#[test]
+fn allow_and_block_lists() {
+    let none: Vec<String> = Vec::new();
+    assert!(restrictions::country_allowed(&none, &none, None));
+    assert!(restrictions::country_allowed(&none, &codes(&["FR"]), Some("DE")));
+    assert!(!restrictions::country_allowed(&none, &codes(&["FR"]), Some("FR")));
+    assert!(restrictions::country_allowed(&codes(&["FR", "DE"]), &none, Some("DE")));
+    assert!(!restrictions::country_allowed(&codes(&["FR", "DE"]), &none, Some("US")));
+}
+
+#[test]
+fn unknown_country_only_passes_block_lists() {
+    let none: Vec<String> = Vec::new();
+    assert!(restrictions::country_allowed(&none, &codes(&["FR"]), None));
+    assert!(!restrictions::country_allowed(&codes(&["FR"]), &none, None));
+}
+
+#[test]
+fn embeds_are_detected_from_origin_or_referer() {
+    // CORS_ALLOWED_ORIGINS defaults to the local frontend
+    let direct = TestRequest::default().to_http_request();
+    assert!(!restrictions::is_foreign_embed(&direct));
+
+    let own = TestRequest::default()
+        .insert_header(("Referer", "http://localhost:3000/videos/1"))
+        .to_http_request();
+    assert!(!restrictions::is_foreign_embed(&own));
+
+    let foreign = TestRequest::default()
+        .insert_header(("Referer", "https://blog.example.com/post"))
+        .to_http_request();
+    assert!(restrictions::is_foreign_embed(&foreign));
+
+    let sandboxed = TestRequest::default().insert_header(("Origin", "null")).to_http_request();
+    assert!(restrictions::is_foreign_embed(&sandboxed));
+}
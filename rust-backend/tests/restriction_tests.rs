@@ -0,0 +1,223 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+const PROXY_ADDR: &str = "127.0.0.1:5000";
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+    // Every test in this file resolves countries from this header instead of a GeoIP database,
+    // believing it from the proxy at PROXY_ADDR
+    std::env::set_var("GEOIP_COUNTRY_HEADER", "X-Test-Country");
+    std::env::set_var("TRUSTED_PROXIES", "127.0.0.1");
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(uploaded_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn set_restrictions(
+    app: &impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+    video_id: i32,
+    token: &str,
+    body: serde_json::Value,
+) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/restrictions", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_uploader_sets_restrictions(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "restrictowner").await;
+    let (_, other_token) = register_test_user(&app, "restrictother").await;
+    let video_id = insert_video(&pool, "restrict-set", owner_id).await;
+
+    let resp = set_restrictions(&app, video_id, &other_token, json!({ "allowEmbed": false })).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let resp = set_restrictions(&app, video_id, &owner_token, json!({ "blockedCountries": ["XYZ"] })).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let resp = set_restrictions(&app, video_id, &owner_token, json!({ "allowedCountries": ["fr"], "blockedCountries": ["FR"] })).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let resp = set_restrictions(&app, video_id, &owner_token, json!({ "blockedCountries": ["de", "fr"] })).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(video["blocked_countries"], json!(["DE", "FR"]));
+    assert_eq!(video["allow_embed"], true);
+
+    // Fields left out keep their values
+    let resp = set_restrictions(&app, video_id, &owner_token, json!({ "allowEmbed": false })).await;
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(video["blocked_countries"], json!(["DE", "FR"]));
+    assert_eq!(video["allow_embed"], false);
+}
+
+#[sqlx::test]
+async fn test_blocked_country_cannot_watch(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "geoowner").await;
+    let video_id = insert_video(&pool, "restrict-geo", owner_id).await;
+    set_restrictions(&app, video_id, &owner_token, json!({ "blockedCountries": ["DE"] })).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .peer_addr(PROXY_ADDR.parse().unwrap())
+        .insert_header(("X-Test-Country", "de"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/stream", video_id))
+        .peer_addr(PROXY_ADDR.parse().unwrap())
+        .insert_header(("X-Test-Country", "DE"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .peer_addr(PROXY_ADDR.parse().unwrap())
+        .insert_header(("X-Test-Country", "FR"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let video: serde_json::Value = test::read_body_json(resp).await;
+    // The blocked request didn't count as a view
+    assert_eq!(video["view_count"], 1);
+}
+
+#[sqlx::test]
+async fn test_allow_list_rejects_other_and_unknown_countries(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "allowowner").await;
+    let video_id = insert_video(&pool, "restrict-allow", owner_id).await;
+    set_restrictions(&app, video_id, &owner_token, json!({ "allowedCountries": ["NZ"] })).await;
+
+    for country in [Some("NZ"), Some("AU"), None] {
+        let mut req = test::TestRequest::get().uri(&format!("/api/videos/{}", video_id)).peer_addr(PROXY_ADDR.parse().unwrap());
+        if let Some(country) = country {
+            req = req.insert_header(("X-Test-Country", country));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        let expected = if country == Some("NZ") { http::StatusCode::OK } else { http::StatusCode::FORBIDDEN };
+        assert_eq!(resp.status(), expected, "country {:?}", country);
+    }
+}
+
+#[sqlx::test]
+async fn test_clients_cant_claim_their_own_country(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "spoofowner").await;
+    let video_id = insert_video(&pool, "restrict-spoof", owner_id).await;
+    set_restrictions(&app, video_id, &owner_token, json!({ "allowedCountries": ["NZ"] })).await;
+
+    // Straight from a client rather than through the proxy, the country header isn't believed
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .peer_addr("203.0.113.9:5000".parse().unwrap())
+        .insert_header(("X-Test-Country", "NZ"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_embedding_can_be_disabled(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "embedowner").await;
+    let video_id = insert_video(&pool, "restrict-embed", owner_id).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Referer", "https://elsewhere.example.com/page"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    set_restrictions(&app, video_id, &owner_token, json!({ "allowEmbed": false })).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Referer", "https://elsewhere.example.com/page"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Referer", "http://localhost:3000/videos/1"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+}
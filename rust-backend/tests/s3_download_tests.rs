@@ -0,0 +1,17 @@
+use video_streaming_backend::services::{etag_md5, part_ranges};
+
+#[test]
+fn test_part_ranges_cover_object() {
+    assert_eq!(part_ranges(10, 4), vec![(0, 3), (4, 7), (8, 9)]);
+    assert_eq!(part_ranges(8, 4), vec![(0, 3), (4, 7)]);
+    assert_eq!(part_ranges(3, 16), vec![(0, 2)]);
+    assert!(part_ranges(0, 16).is_empty());
+}
+
+#[test]
+fn test_etag_md5_only_for_single_part_uploads() {
+    let md5 = "9e107d9d372bb6826bd81d3542a419d6";
+    assert_eq!(etag_md5(&format!("\"{}\"", md5), false), Some(md5.to_string()));
+    assert_eq!(etag_md5(&format!("\"{}-3\"", md5), false), None);
+    assert_eq!(etag_md5(&format!("\"{}\"", md5), true), None);
+}
@@ -0,0 +1,154 @@
+use base64::Engine;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::Signer;
+use openssl::x509::{X509Builder, X509NameBuilder};
+use serde_json::json;
+
+use video_streaming_backend::s3_events::{
+    created_objects, decode_key, is_sns_url, parse_notification, sns_signature_valid, sns_string_to_sign, token_matches,
+    SnsMessage,
+};
+
+fn record(event_name: &str, bucket: &str, key: &str, size: i64) -> serde_json::Value {
+    json!({
+        "eventName": event_name,
+        "eventTime": "2025-09-15T08:30:00.000Z",
+        "s3": { "bucket": { "name": bucket }, "object": { "key": key, "size": size } }
+    })
+}
+
+#[test]
+fn keys_are_url_decoded() {
+    assert_eq!(decode_key("videos/Summer+Trip%282024%29.mp4"), "videos/Summer Trip(2024).mp4");
+    assert_eq!(decode_key("videos/plain.mp4"), "videos/plain.mp4");
+}
+
+#[test]
+fn tokens_match_bare_or_as_bearer() {
+    assert!(token_matches(Some("s3cret"), "s3cret"));
+    assert!(token_matches(Some("Bearer s3cret"), "s3cret"));
+    assert!(!token_matches(Some("Bearer other"), "s3cret"));
+    assert!(!token_matches(None, "s3cret"));
+}
+
+#[test]
+fn only_created_video_files_in_the_bucket_and_prefix_are_kept() {
+    let body = json!({ "Records": [
+        record("ObjectCreated:Put", "videos", "videos/talk+one.mp4", 100),
+        record("s3:ObjectCreated:CompleteMultipartUpload", "videos", "videos/two.MOV", 200),
+        record("ObjectRemoved:Delete", "videos", "videos/gone.mp4", 0),
+        record("ObjectCreated:Put", "other", "videos/elsewhere.mp4", 100),
+        record("ObjectCreated:Put", "videos", "thumbnails/a.jpg", 100),
+        record("ObjectCreated:Put", "videos", "renditions/1/720p.mp4", 100),
+        record("ObjectCreated:Put", "videos", "videos/empty.mp4", 0)
+    ]});
+    let notification = parse_notification(body.to_string().as_bytes()).unwrap();
+    let objects = created_objects(&notification, "videos", "videos/");
+    let keys: Vec<&str> = objects.iter().map(|o| o.key.as_str()).collect();
+    assert_eq!(keys, ["videos/talk one.mp4", "videos/two.MOV"]);
+    assert_eq!(objects[0].last_modified.unwrap().to_string(), "2025-09-15 08:30:00");
+}
+
+#[test]
+fn sns_envelopes_and_test_events_are_understood() {
+    let inner = json!({ "Records": [record("ObjectCreated:Put", "videos", "videos/a.mp4", 1)] });
+    let envelope = json!({ "Type": "Notification", "Message": inner.to_string() });
+    let notification = parse_notification(envelope.to_string().as_bytes()).unwrap();
+    assert_eq!(notification.records.len(), 1);
+
+    let test_event = json!({ "Service": "Amazon S3", "Event": "s3:TestEvent", "Bucket": "videos" });
+    assert!(parse_notification(test_event.to_string().as_bytes()).unwrap().records.is_empty());
+    assert!(parse_notification(b"not json").is_err());
+}
+
+fn signing_cert() -> (PKey<Private>, Vec<u8>) {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "sns.amazonaws.com").unwrap();
+    let name = name.build();
+    let mut cert = X509Builder::new().unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+    (key, cert.build().to_pem().unwrap())
+}
+
+fn sns_message(message_type: &str) -> SnsMessage {
+    serde_json::from_value(json!({
+        "Type": message_type,
+        "MessageId": "a1b2",
+        "TopicArn": "arn:aws:sns:us-east-1:123456789012:video-uploads",
+        "Subject": "Amazon S3 Notification",
+        "Message": "{\"Records\":[]}",
+        "Timestamp": "2025-09-15T08:30:00.000Z",
+        "SignatureVersion": "2",
+        "Signature": "",
+        "SigningCertURL": "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem",
+        "SubscribeURL": "https://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription&Token=t0k",
+        "Token": "t0k"
+    }))
+    .unwrap()
+}
+
+fn sign(message: &mut SnsMessage, key: &PKey<Private>) {
+    let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+    signer.update(sns_string_to_sign(message).as_bytes()).unwrap();
+    message.signature = base64::engine::general_purpose::STANDARD.encode(signer.sign_to_vec().unwrap());
+}
+
+#[test]
+fn sns_signs_the_fields_of_each_message_type() {
+    assert_eq!(
+        sns_string_to_sign(&sns_message("Notification")),
+        "Message\n{\"Records\":[]}\nMessageId\na1b2\nSubject\nAmazon S3 Notification\n\
+         Timestamp\n2025-09-15T08:30:00.000Z\nTopicArn\narn:aws:sns:us-east-1:123456789012:video-uploads\n\
+         Type\nNotification\n"
+    );
+    assert_eq!(
+        sns_string_to_sign(&sns_message("SubscriptionConfirmation")),
+        "Message\n{\"Records\":[]}\nMessageId\na1b2\n\
+         SubscribeURL\nhttps://sns.us-east-1.amazonaws.com/?Action=ConfirmSubscription&Token=t0k\n\
+         Timestamp\n2025-09-15T08:30:00.000Z\nToken\nt0k\nTopicArn\narn:aws:sns:us-east-1:123456789012:video-uploads\n\
+         Type\nSubscriptionConfirmation\n"
+    );
+}
+
+#[test]
+fn sns_signatures_are_checked_against_the_certificate() {
+    let (key, cert) = signing_cert();
+    let (other_key, _) = signing_cert();
+
+    let mut message = sns_message("Notification");
+    sign(&mut message, &key);
+    assert!(sns_signature_valid(&message, &cert));
+
+    message.message = "{\"Records\":[{}]}".to_string();
+    assert!(!sns_signature_valid(&message, &cert));
+
+    let mut forged = sns_message("Notification");
+    sign(&mut forged, &other_key);
+    assert!(!sns_signature_valid(&forged, &cert));
+
+    let mut unknown_version = sns_message("Notification");
+    sign(&mut unknown_version, &key);
+    unknown_version.signature_version = "3".to_string();
+    assert!(!sns_signature_valid(&unknown_version, &cert));
+}
+
+#[test]
+fn only_sns_endpoints_are_trusted_for_certificates() {
+    assert!(is_sns_url("https://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem"));
+    assert!(is_sns_url("https://sns.cn-north-1.amazonaws.com.cn/cert.pem"));
+    assert!(!is_sns_url("http://sns.us-east-1.amazonaws.com/cert.pem"));
+    assert!(!is_sns_url("https://sns.us-east-1.amazonaws.com:8443/cert.pem"));
+    assert!(!is_sns_url("https://sns.s3.amazonaws.com/cert.pem"));
+    assert!(!is_sns_url("https://sns.s3-us-west-2.amazonaws.com/cert.pem"));
+    assert!(!is_sns_url("https://sns.us-east-1.amazonaws.com.example.com/cert.pem"));
+    assert!(!is_sns_url("not a url"));
+}
@@ -0,0 +1,106 @@
+use actix_web::{test, web, App, http};
+use bytes::Bytes;
+use dotenv::dotenv;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::s3_events;
+use video_streaming_backend::storage::{LocalStorage, ObjectStore, Storage};
+use video_streaming_backend::AppState;
+
+// A fresh storage root per test, removed when dropped
+struct Root(PathBuf);
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+async fn setup_test_app(pool: PgPool, storage: Storage) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+fn created(key: &str, size: i64) -> serde_json::Value {
+    json!({ "Records": [{
+        "eventName": "ObjectCreated:Put",
+        "eventTime": "2025-09-15T08:30:00.000Z",
+        "s3": { "bucket": { "name": "videos" }, "object": { "key": key, "size": size } }
+    }]})
+}
+
+#[sqlx::test]
+async fn test_created_objects_become_videos_after_the_grace_period(pool: PgPool) {
+    std::env::set_var("S3_EVENTS_TOKEN", "s3-events-test-token");
+    std::env::remove_var("S3_BUCKET");
+    std::env::set_var("MINIO_BUCKET", "videos");
+    std::env::remove_var("IMPORT_S3_PREFIX");
+
+    let root = Root(std::env::temp_dir().join(format!("s3-event-test-{}", uuid::Uuid::new_v4())));
+    let storage: Storage = Arc::new(LocalStorage::new(&root.0));
+    storage.put("videos", "videos/Out Of Band.mp4", Bytes::from_static(b"0123456789"), "video/mp4").await.unwrap();
+    let app = setup_test_app(pool.clone(), storage.clone()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/internal/s3-events")
+        .set_json(created("videos/Out+Of+Band.mp4", 10))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+    // Delivered twice, as S3 may, and once for an object already gone
+    for body in [created("videos/Out+Of+Band.mp4", 10), created("videos/Out+Of+Band.mp4", 10), created("videos/deleted.mp4", 5)] {
+        let req = test::TestRequest::post()
+            .uri("/api/internal/s3-events")
+            .insert_header(("Authorization", "s3-events-test-token"))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["recorded"], 1);
+    }
+
+    // Nothing happens within the grace period
+    let imported = s3_events::ingest_due(&pool, storage.as_ref(), None, "videos", Duration::from_secs(3600)).await.unwrap();
+    assert!(imported.is_empty());
+
+    let imported = s3_events::ingest_due(&pool, storage.as_ref(), None, "videos", Duration::ZERO).await.unwrap();
+    assert_eq!(imported.len(), 1);
+    assert_eq!(imported[0].s3_key, "videos/Out Of Band.mp4");
+    assert_eq!(imported[0].title, "Out Of Band");
+
+    let videos: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM videos WHERE s3_key IN ('videos/Out Of Band.mp4', 'videos/deleted.mp4')")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(videos, 1);
+    assert!(s3_events::ingest_due(&pool, storage.as_ref(), None, "videos", Duration::ZERO).await.unwrap().is_empty());
+}
@@ -0,0 +1,241 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::saved_searches;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": "searcher",
+            "email": "searcher@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+#[sqlx::test]
+async fn test_search_history_is_recorded(pool: PgPool) {
+    let app = setup_test_app(pool).await;
+    let (_, token) = register_test_user(&app).await;
+
+    // Search while authenticated
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/cats")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    // The query shows up in the user's history
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/searches")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let history = body["history"].as_array().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["query"], "cats");
+    assert!(body["saved"].as_array().unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn test_searches_require_authentication(pool: PgPool) {
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/searches")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_saved_search_notifies_on_new_video(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app).await;
+
+    // Save a named search
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "name": "Rust videos", "query": "rust" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let saved: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(saved["name"], "Rust videos");
+    assert_eq!(saved["notify"], true);
+
+    // Upload a matching video after the search was saved
+    sqlx::query(
+        "INSERT INTO videos (title, description, s3_key, uploaded_by, upload_date) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind("Learning Rust")
+    .bind("An intro")
+    .bind("test_key_1")
+    .bind(user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    saved_searches::check_saved_searches(&pool).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/notifications")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let notifications: serde_json::Value = test::read_body_json(resp).await;
+    let notifications = notifications.as_array().unwrap();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["kind"], "saved_search_match");
+    assert_eq!(notifications[0]["payload"]["name"], "Rust videos");
+
+    // A second check with no new uploads does not notify again
+    saved_searches::check_saved_searches(&pool).await.unwrap();
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[sqlx::test]
+async fn test_concurrent_checks_notify_once(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "name": "Rust videos", "query": "rust" }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    sqlx::query(
+        "INSERT INTO videos (title, s3_key, uploaded_by, upload_date) VALUES ($1, $2, $3, $4)"
+    )
+    .bind("Rust in production")
+    .bind("test_key_concurrent")
+    .bind(user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Like two replicas checking at the same moment
+    let (first, second) = tokio::join!(
+        saved_searches::check_saved_searches(&pool),
+        saved_searches::check_saved_searches(&pool)
+    );
+    first.unwrap();
+    second.unwrap();
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[sqlx::test]
+async fn test_video_approved_after_a_check_still_notifies(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/me/searches")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "name": "Rust videos", "query": "rust" }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    // Uploaded and held for moderation before the check runs
+    let video_id: i32 = sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, uploaded_by, upload_date, moderation_status) VALUES ($1, $2, $3, $4, 'pending') RETURNING id"
+    )
+    .bind("Rust under review")
+    .bind("test_key_pending")
+    .bind(user_id)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    saved_searches::check_saved_searches(&pool).await.unwrap();
+
+    sqlx::query("UPDATE videos SET moderation_status = 'approved', moderated_at = $1 WHERE id = $2")
+        .bind(chrono::Utc::now().naive_utc())
+        .bind(video_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    saved_searches::check_saved_searches(&pool).await.unwrap();
+
+    let video_ids: Vec<serde_json::Value> = sqlx::query_scalar("SELECT payload->'videoIds' FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(video_ids, vec![json!([video_id])]);
+}
@@ -0,0 +1,37 @@
+use video_streaming_backend::chapters::{detect_cuts, histogram, histogram_distance, propose_chapter_starts};
+
+fn solid_frame(r: u8, g: u8, b: u8) -> Vec<u8> {
+    std::iter::repeat_n([r, g, b], 64 * 36).flatten().collect()
+}
+
+#[test]
+fn test_histogram_distance_bounds() {
+    let red = histogram(&solid_frame(255, 0, 0));
+    let blue = histogram(&solid_frame(0, 0, 255));
+    let dark_red = histogram(&solid_frame(250, 5, 5));
+
+    assert_eq!(histogram_distance(&red, &red), 0.0);
+    assert!((histogram_distance(&red, &blue) - 2.0 / 3.0).abs() < 1e-6);
+    // Small colour shifts inside the same bins don't register as a change
+    assert_eq!(histogram_distance(&red, &dark_red), 0.0);
+}
+
+#[test]
+fn test_detect_cuts_at_scene_changes() {
+    let red = histogram(&solid_frame(255, 0, 0));
+    let blue = histogram(&solid_frame(0, 0, 255));
+    let frames = vec![red, red, red, blue, blue, red];
+
+    assert_eq!(detect_cuts(&frames, 1.0, 0.4), vec![3.0, 5.0]);
+    assert_eq!(detect_cuts(&frames, 2.0, 0.4), vec![1.5, 2.5]);
+    assert!(detect_cuts(&frames, 1.0, 0.9).is_empty());
+}
+
+#[test]
+fn test_propose_chapter_starts_enforces_minimum_length() {
+    let cuts = [10.0, 45.0, 50.0, 100.0, 590.0];
+
+    assert_eq!(propose_chapter_starts(&cuts, Some(600.0), 30.0), vec![0.0, 45.0, 100.0]);
+    assert_eq!(propose_chapter_starts(&cuts, None, 30.0), vec![0.0, 45.0, 100.0, 590.0]);
+    assert_eq!(propose_chapter_starts(&[], Some(600.0), 30.0), vec![0.0]);
+}
@@ -0,0 +1,91 @@
+use chrono::NaiveDate;
+
+use video_streaming_backend::changes::Change;
+use video_streaming_backend::models::Video;
+use video_streaming_backend::search_index::{meilisearch_filter, order_by_hits, plan_sync, Document, SearchQuery};
+
+fn video(id: i32) -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id,
+        title: format!("Video {}", id),
+        description: Some("About things".to_string()),
+        s3_key: format!("videos/{}.mp4", id),
+        thumbnail_url: None,
+        uploaded_by: Some(3),
+        upload_date: Some(uploaded),
+        tags: Some(vec!["music".to_string()]),
+        view_count: None,
+        category_ids: Some(vec![2, 5]),
+        duration: Some(90),
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: Vec::new(),
+        blocked_countries: Vec::new(),
+        allow_embed: true,
+        allow_download: true,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+fn change(video_id: i32, video: Option<Video>) -> Change {
+    Change {
+        cursor: format!("1-{}", video_id),
+        video_id,
+        change: if video.is_some() { "updated" } else { "deleted" },
+        changed_at: NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        video,
+    }
+}
+
+#[test]
+fn test_document_from_video() {
+    let document = Document::from_video(&video(7));
+    assert_eq!(document.id, 7);
+    assert_eq!(document.tags, vec!["music".to_string()]);
+    assert_eq!(document.category_ids, vec![2, 5]);
+    assert_eq!(document.upload_date, Some(1741064767));
+    assert_eq!(document.duration, Some(90));
+}
+
+#[test]
+fn test_meilisearch_filter() {
+    let mut query = SearchQuery { text: "rust".to_string(), limit: 20, ..Default::default() };
+    assert_eq!(meilisearch_filter(&query), None);
+
+    query.tag = Some("say \"hi\"".to_string());
+    query.category_id = Some(4);
+    assert_eq!(meilisearch_filter(&query).as_deref(), Some("tags = \"say \\\"hi\\\"\" AND category_ids = 4"));
+}
+
+#[test]
+fn test_latest_change_per_video_wins() {
+    let changes = vec![
+        change(1, Some(video(1))),
+        change(2, Some(video(2))),
+        change(1, None),
+        change(3, None),
+        change(3, Some(video(3))),
+    ];
+    let (upserts, deletes) = plan_sync(&changes);
+    let upserted: Vec<i32> = upserts.iter().map(|d| d.id).collect();
+    assert_eq!(upserted, vec![2, 3]);
+    assert_eq!(deletes, vec![1]);
+}
+
+#[test]
+fn test_results_follow_the_engine_ranking() {
+    let videos = vec![video(1), video(2), video(3)];
+    let ids: Vec<i32> = order_by_hits(videos, &[3, 9, 1]).iter().map(|v| v.id).collect();
+    assert_eq!(ids, vec![3, 1]);
+}
@@ -0,0 +1,102 @@
+use std::sync::Mutex;
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+
+use video_streaming_backend::search_index::{sync_once, Document, SearchBackend, SearchHits, SearchQuery};
+
+type IndexResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Records what the indexer sends instead of talking to a search engine
+#[derive(Default)]
+struct RecordingBackend {
+    upserted: Mutex<Vec<Document>>,
+    deleted: Mutex<Vec<i32>>,
+}
+
+impl SearchBackend for RecordingBackend {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn search<'a>(&'a self, _query: &'a SearchQuery) -> BoxFuture<'a, IndexResult<SearchHits>> {
+        Box::pin(async { Ok(SearchHits::default()) })
+    }
+
+    fn upsert<'a>(&'a self, documents: &'a [Document]) -> BoxFuture<'a, IndexResult<()>> {
+        self.upserted.lock().unwrap().extend_from_slice(documents);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, video_ids: &'a [i32]) -> BoxFuture<'a, IndexResult<()>> {
+        self.deleted.lock().unwrap().extend_from_slice(video_ids);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+async fn insert_video(pool: &PgPool, title: &str, moderation_status: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, moderation_status) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(moderation_status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// Changes are held back while older transactions anywhere on the server are running, so
+// retry until the sync picks some up
+async fn sync_until_applied(pool: &PgPool, backend: &RecordingBackend) -> usize {
+    for _ in 0..50 {
+        let applied = sync_once(pool, backend).await.unwrap();
+        if applied > 0 {
+            return applied;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    0
+}
+
+#[sqlx::test]
+async fn test_first_sync_indexes_every_public_video(pool: PgPool) {
+    let first = insert_video(&pool, "first", "approved").await;
+    let second = insert_video(&pool, "second", "approved").await;
+    insert_video(&pool, "pending", "pending").await;
+    let backend = RecordingBackend::default();
+
+    assert_eq!(sync_once(&pool, &backend).await.unwrap(), 2);
+    let indexed: Vec<i32> = backend.upserted.lock().unwrap().iter().map(|d| d.id).collect();
+    assert_eq!(indexed, vec![first, second]);
+
+    // The changes that created them aren't replayed
+    assert_eq!(sync_once(&pool, &backend).await.unwrap(), 0);
+    let cursor: Option<String> = sqlx::query_scalar("SELECT cursor FROM search_index_cursors WHERE backend = 'recording'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(cursor.is_some());
+}
+
+#[sqlx::test]
+async fn test_sync_applies_updates_and_deletions(pool: PgPool) {
+    let kept = insert_video(&pool, "kept", "approved").await;
+    let removed = insert_video(&pool, "removed", "approved").await;
+    let backend = RecordingBackend::default();
+    sync_once(&pool, &backend).await.unwrap();
+    backend.upserted.lock().unwrap().clear();
+
+    sqlx::query("UPDATE videos SET title = 'kept and renamed' WHERE id = $1").bind(kept).execute(&pool).await.unwrap();
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1").bind(removed).execute(&pool).await.unwrap();
+
+    let mut applied = 0;
+    while applied < 2 {
+        let newly = sync_until_applied(&pool, &backend).await;
+        assert!(newly > 0, "changes never became readable");
+        applied += newly;
+    }
+
+    let upserted = backend.upserted.lock().unwrap();
+    assert_eq!(upserted.len(), 1);
+    assert_eq!(upserted[0].id, kept);
+    assert_eq!(upserted[0].title, "kept and renamed");
+    assert_eq!(*backend.deleted.lock().unwrap(), vec![removed]);
+}
@@ -7,7 +7,7 @@ use sqlx::PgPool;
 
 use video_streaming_backend::handlers;
 use video_streaming_backend::AppState;
-use video_streaming_backend::services;
+use video_streaming_backend::storage;
 
 async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
     actix_http::Request,
@@ -17,12 +17,12 @@ async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
     dotenv().ok();
     
     // Initialize S3 client
-    let s3_client = services::init_s3_client().await;
+    let storage = storage::init_storage().await;
     
     // Create the app state using the provided pool
     let app_state = Arc::new(Mutex::new(AppState {
         db_pool: pool,
-        s3_client,
+        storage,
         redis_client: None,
         job_queue: None, // No job queue in tests
         video_clients: std::sync::Mutex::new(HashMap::new()),
@@ -161,7 +161,7 @@ async fn test_search_videos_by_tags(pool: PgPool) {
     .bind("A video with tags")
     .bind("test_key_1")
     .bind(1)
-    .bind(&vec!["rust", "programming"])
+    .bind(vec!["rust", "programming"])
     .execute(&pool)
     .await
     .unwrap();
@@ -173,7 +173,7 @@ async fn test_search_videos_by_tags(pool: PgPool) {
     .bind("Another video")
     .bind("test_key_2")
     .bind(1)
-    .bind(&vec!["cooking", "food"])
+    .bind(vec!["cooking", "food"])
     .execute(&pool)
     .await
     .unwrap();
@@ -253,3 +253,167 @@ async fn test_search_videos_no_results(pool: PgPool) {
     
     assert_eq!(videos.len(), 0);
 }
+
+async fn insert_search_fixtures(pool: &PgPool) {
+    sqlx::query(
+        "INSERT INTO users (username, email, password) VALUES ($1, $2, $3) ON CONFLICT (username) DO NOTHING"
+    )
+    .bind("testuser")
+    .bind("test@example.com")
+    .bind("hashedpassword")
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let fixtures: [(&str, &str, Vec<&str>, i32, i32); 3] = [
+        ("Rust Basics", "test_key_1", vec!["rust", "beginner"], 120, 10),
+        ("Advanced Rust", "test_key_2", vec!["rust", "advanced"], 3600, 50),
+        ("Cooking Pasta", "test_key_3", vec!["cooking"], 600, 30),
+    ];
+
+    for (title, s3_key, tags, duration, view_count) in fixtures {
+        sqlx::query(
+            "INSERT INTO videos (title, description, s3_key, uploaded_by, tags, duration, view_count) VALUES ($1, $2, $3, $4, $5, $6, $7)"
+        )
+        .bind(title)
+        .bind("A test video")
+        .bind(s3_key)
+        .bind(1)
+        .bind(tags)
+        .bind(duration)
+        .bind(view_count)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+}
+
+#[sqlx::test]
+async fn test_advanced_search_tags_and_duration(pool: PgPool) {
+    insert_search_fixtures(&pool).await;
+    let app = setup_test_app(pool).await;
+
+    // All tags must match and duration must be within range
+    let req = test::TestRequest::post()
+        .uri("/api/videos/search")
+        .set_json(serde_json::json!({
+            "tags_all": ["rust"],
+            "max_duration": 600
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let videos = body.as_array().unwrap();
+
+    assert_eq!(videos.len(), 1);
+    assert_eq!(videos[0]["title"], "Rust Basics");
+}
+
+#[sqlx::test]
+async fn test_advanced_search_tags_any_sorted(pool: PgPool) {
+    insert_search_fixtures(&pool).await;
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/videos/search")
+        .set_json(serde_json::json!({
+            "tags_any": ["advanced", "cooking"],
+            "sort": "most_viewed"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let titles: Vec<&str> = body.as_array().unwrap().iter().map(|v| v["title"].as_str().unwrap()).collect();
+
+    assert_eq!(titles, vec!["Advanced Rust", "Cooking Pasta"]);
+}
+
+#[sqlx::test]
+async fn test_advanced_search_rejects_invalid_range(pool: PgPool) {
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/videos/search")
+        .set_json(serde_json::json!({
+            "min_duration": 600,
+            "max_duration": 60
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_search_results_are_compressed(pool: PgPool) {
+    dotenv().ok();
+
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage: storage::init_storage().await,
+        redis_client: None,
+        job_queue: None,
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Same middleware stack as main.rs
+    let app = test::init_service(
+        App::new()
+            .wrap(actix_web::middleware::Compress::default())
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/cats")
+        .insert_header((actix_web::http::header::ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(actix_web::http::header::CONTENT_ENCODING).unwrap(), "gzip");
+
+    // Clients that don't ask for compression get plain JSON
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/cats")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get(actix_web::http::header::CONTENT_ENCODING).is_none());
+}
+
+#[sqlx::test]
+async fn test_search_videos_filtered_by_tag(pool: PgPool) {
+    insert_search_fixtures(&pool).await;
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/rust?tag=advanced")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let titles: Vec<&str> = body.as_array().unwrap().iter().map(|v| v["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["Advanced Rust"]);
+}
+
+#[sqlx::test]
+async fn test_search_facets_need_a_search_backend(pool: PgPool) {
+    std::env::remove_var("SEARCH_BACKEND");
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/rust/facets")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+}
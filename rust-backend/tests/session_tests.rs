@@ -0,0 +1,304 @@
+use actix_web::{test, web, App, http, middleware};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::sessions;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .wrap(middleware::from_fn(sessions::check_session))
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn login(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str, user_agent: &str) -> String {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .insert_header(("User-Agent", user_agent))
+        .set_json(json!({
+            "username": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    body["token"].as_str().unwrap().to_string()
+}
+
+async fn list_sessions(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/sessions")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_sessions_are_listed_with_device(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, register_token) = register_test_user(&app, "sesslist").await;
+    let laptop_token = login(&app, "sesslist", "Laptop Browser").await;
+
+    let resp = list_sessions(&app, &laptop_token).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let user_sessions = body.as_array().unwrap();
+    assert_eq!(user_sessions.len(), 2);
+
+    let current: Vec<_> = user_sessions.iter().filter(|s| s["current"] == true).collect();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0]["user_agent"], "Laptop Browser");
+
+    // Both tokens still work
+    assert_eq!(list_sessions(&app, &register_token).await.status(), http::StatusCode::OK);
+    assert_eq!(list_sessions(&app, "not-a-token").await.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_revoked_session_token_is_rejected(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, phone_token) = register_test_user(&app, "sessrevoke").await;
+    let laptop_token = login(&app, "sessrevoke", "Laptop").await;
+    let (_, other_token) = register_test_user(&app, "sessother").await;
+
+    let body: serde_json::Value = test::read_body_json(list_sessions(&app, &laptop_token).await).await;
+    let phone_session = body.as_array().unwrap().iter().find(|s| s["current"] == false).unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Other users can't revoke it
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/users/me/sessions/{}", phone_session))
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/users/me/sessions/{}", phone_session))
+        .insert_header(("Authorization", format!("Bearer {}", laptop_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    assert_eq!(list_sessions(&app, &phone_token).await.status(), http::StatusCode::UNAUTHORIZED);
+    // Tokens passed in the query string (EventSource, HLS keys) are checked too
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/status?token={}", phone_token))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = test::read_body_json(list_sessions(&app, &laptop_token).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn test_revoke_all_keeps_the_current_session(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, first_token) = register_test_user(&app, "sessall").await;
+    let second_token = login(&app, "sessall", "Tablet").await;
+    let current_token = login(&app, "sessall", "Desktop").await;
+
+    let req = test::TestRequest::delete()
+        .uri("/api/users/me/sessions")
+        .insert_header(("Authorization", format!("Bearer {}", current_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["revoked"], 2);
+
+    assert_eq!(list_sessions(&app, &first_token).await.status(), http::StatusCode::UNAUTHORIZED);
+    assert_eq!(list_sessions(&app, &second_token).await.status(), http::StatusCode::UNAUTHORIZED);
+    assert_eq!(list_sessions(&app, &current_token).await.status(), http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_logout_revokes_the_session(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "sesslogout").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/logout")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    assert_eq!(list_sessions(&app, &token).await.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+async fn auth_status(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: Option<&str>) -> serde_json::Value {
+    let mut req = test::TestRequest::get().uri("/api/auth/status");
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    test::read_body_json(resp).await
+}
+
+#[sqlx::test]
+async fn test_auth_status_describes_the_signed_in_user(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "sessstatus").await;
+
+    let status = auth_status(&app, Some(&token)).await;
+    assert_eq!(status["isAuthenticated"], true);
+    assert_eq!(status["user"], json!({ "id": user_id, "username": "sessstatus", "email": "sessstatus@example.com" }));
+    assert_eq!(status["roles"], json!(["user"]));
+    let expires_at = chrono::DateTime::parse_from_rfc3339(status["expiresAt"].as_str().unwrap()).unwrap();
+    assert!(expires_at > chrono::Utc::now());
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1").bind(user_id).execute(&pool).await.unwrap();
+    assert_eq!(auth_status(&app, Some(&token)).await["roles"], json!(["user", "admin"]));
+
+    assert_eq!(auth_status(&app, None).await, json!({ "isAuthenticated": false }));
+    assert_eq!(auth_status(&app, Some("not-a-token")).await, json!({ "isAuthenticated": false }));
+}
+
+async fn refresh(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, refresh_token: &str) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": refresh_token }))
+        .to_request();
+    test::call_service(app, req).await
+}
+
+async fn register_with_refresh_token(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (String, String) {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(app, req).await).await;
+    (body["token"].as_str().unwrap().to_string(), body["refreshToken"].as_str().unwrap().to_string())
+}
+
+#[sqlx::test]
+async fn test_refresh_tokens_rotate(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (token, refresh_token) = register_with_refresh_token(&app, "sessrefresh").await;
+
+    let resp = refresh(&app, &refresh_token).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let new_token = body["token"].as_str().unwrap();
+    let new_refresh_token = body["refreshToken"].as_str().unwrap();
+    assert_ne!(new_token, token);
+    assert_ne!(new_refresh_token, refresh_token);
+    assert!(body["expiresAt"].is_string());
+
+    // Both access tokens belong to the same session
+    assert_eq!(list_sessions(&app, new_token).await.status(), http::StatusCode::OK);
+    let sessions: Vec<serde_json::Value> = test::read_body_json(list_sessions(&app, &token).await).await;
+    assert_eq!(sessions.len(), 1);
+
+    assert_eq!(refresh(&app, new_refresh_token).await.status(), http::StatusCode::OK);
+    assert_eq!(refresh(&app, "not-a-refresh-token").await.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_reused_refresh_token_revokes_the_session(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, refresh_token) = register_with_refresh_token(&app, "sessreuse").await;
+
+    let body: serde_json::Value = test::read_body_json(refresh(&app, &refresh_token).await).await;
+    let new_token = body["token"].as_str().unwrap();
+    let new_refresh_token = body["refreshToken"].as_str().unwrap();
+
+    // Someone replays the used token: the whole session goes
+    assert_eq!(refresh(&app, &refresh_token).await.status(), http::StatusCode::UNAUTHORIZED);
+    assert_eq!(list_sessions(&app, new_token).await.status(), http::StatusCode::UNAUTHORIZED);
+    assert_eq!(refresh(&app, new_refresh_token).await.status(), http::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn test_logout_revokes_the_refresh_token(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (token, refresh_token) = register_with_refresh_token(&app, "sessrefreshout").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/logout")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    assert_eq!(refresh(&app, &refresh_token).await.status(), http::StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,128 @@
+use chrono::NaiveDate;
+
+use video_streaming_backend::models::Video;
+use video_streaming_backend::signed_urls::{self, expiry, sign, thumbnail_path, thumbnail_version, verify, verify_if_signed};
+
+fn video(id: i32, thumbnail: Option<&str>, hls_encrypted: bool) -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id,
+        title: "Signed".to_string(),
+        description: None,
+        s3_key: format!("videos/{}-original.mp4", id),
+        thumbnail_url: thumbnail.map(str::to_string),
+        uploaded_by: None,
+        upload_date: Some(uploaded),
+        tags: None,
+        view_count: None,
+        category_ids: None,
+        duration: None,
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted,
+        allowed_countries: Vec::new(),
+        blocked_countries: Vec::new(),
+        allow_embed: true,
+        allow_download: false,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+// Signing has no default secret
+fn set_signing_secret() {
+    std::env::set_var("URL_SIGNING_SECRET", "test_url_signing_secret");
+}
+
+// (path, expires, signature) of a URL from signed_url
+fn parts(url: &str) -> (String, i64, String) {
+    let (rest, signature) = url.rsplit_once("&signature=").unwrap();
+    let (path, expires) = rest.rsplit_once("expires=").unwrap();
+    (path[..path.len() - 1].to_string(), expires.parse().unwrap(), signature.to_string())
+}
+
+#[test]
+fn expiries_are_stable_within_a_window() {
+    assert_eq!(expiry(0, 100), 200);
+    assert_eq!(expiry(99, 100), 200);
+    assert_eq!(expiry(100, 100), 300);
+    // Always at least one window away
+    for now in [1_000, 1_050, 1_099] {
+        assert!(expiry(now, 100) - now > 100);
+    }
+}
+
+#[test]
+fn signatures_cover_path_and_expiry() {
+    set_signing_secret();
+    let signature = sign("/api/videos/1/stream", 2_000);
+    assert!(verify("/api/videos/1/stream", 2_000, &signature, 1_000).is_ok());
+    assert!(verify("/api/videos/2/stream", 2_000, &signature, 1_000).is_err());
+    assert!(verify("/api/videos/1/stream", 3_000, &signature, 1_000).is_err());
+    assert!(verify("/api/videos/1/stream", 2_000, "not-hex", 1_000).is_err());
+    // Expired
+    assert!(verify("/api/videos/1/stream", 2_000, &signature, 2_000).is_err());
+}
+
+#[test]
+fn playback_only_checks_urls_that_claim_a_signature() {
+    set_signing_secret();
+    let signature = sign("/api/videos/1/stream", 2_000);
+    assert!(verify_if_signed("/api/videos/1/stream", None, None, 1_000).is_ok());
+    assert!(verify_if_signed("/api/videos/1/stream", Some(2_000), Some(&signature), 1_000).is_ok());
+    assert!(verify_if_signed("/api/videos/1/stream", Some(2_000), None, 1_000).is_err());
+    assert!(verify_if_signed("/api/videos/1/stream", None, Some(&signature), 1_000).is_err());
+    assert!(verify_if_signed("/api/videos/1/stream", Some(2_000), Some(&signature), 3_000).is_err());
+}
+
+#[test]
+fn playback_urls_are_signed_and_keep_keys_private() {
+    set_signing_secret();
+    let now = 1_700_000_000;
+    let url = signed_urls::playback_url(&video(7, None, false), now);
+    let (path, expires, signature) = parts(&url);
+    assert_eq!(path, "/api/videos/7/stream");
+    assert!(verify(&path, expires, &signature, now).is_ok());
+    assert!(!url.contains("original"));
+
+    let encrypted = signed_urls::playback_url(&video(7, None, true), now);
+    assert!(encrypted.starts_with("/api/videos/7/hls/index.m3u8?expires="));
+}
+
+#[test]
+fn thumbnail_urls_name_a_version_not_a_key() {
+    set_signing_secret();
+    let now = 1_700_000_000;
+    let url = signed_urls::thumbnail_url(&video(7, Some("thumbnails/abc.jpg"), false), now).unwrap();
+    let (path, expires, signature) = parts(&url);
+    assert_eq!(path, thumbnail_path(7, &thumbnail_version("thumbnails/abc.jpg")));
+    assert!(verify(&path, expires, &signature, now).is_ok());
+    assert!(!url.contains("abc"));
+
+    // A new thumbnail gets a new URL
+    assert_ne!(thumbnail_version("thumbnails/abc.jpg"), thumbnail_version("thumbnails/def.jpg"));
+
+    assert_eq!(signed_urls::thumbnail_url(&video(7, None, false), now), None);
+    assert_eq!(
+        signed_urls::thumbnail_url(&video(7, Some("https://i.example.com/7.jpg"), false), now).as_deref(),
+        Some("https://i.example.com/7.jpg")
+    );
+}
+
+#[test]
+fn serialized_videos_carry_urls_instead_of_keys() {
+    set_signing_secret();
+    let json = serde_json::to_value(video(7, Some("thumbnails/abc.jpg"), false)).unwrap();
+    assert!(json.get("s3_key").is_none());
+    assert!(json["playback_url"].as_str().unwrap().starts_with("/api/videos/7/stream?expires="));
+    assert!(json["thumbnail_url"].as_str().unwrap().starts_with("/api/videos/7/thumbnail?v="));
+    assert_eq!(json["title"], "Signed");
+}
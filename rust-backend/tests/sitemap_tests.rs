@@ -0,0 +1,78 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::sitemap;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+#[sqlx::test]
+async fn test_sitemap_goes_stale_when_catalogue_changes(pool: PgPool) {
+    // Never generated
+    let watermark = sitemap::stale_watermark(&pool).await.unwrap().expect("missing documents are stale");
+
+    sitemap::record_document(&pool, sitemap::VIDEO_SITEMAP, "sitemaps/sitemap-videos.xml", watermark).await.unwrap();
+    assert!(sitemap::stale_watermark(&pool).await.unwrap().is_some(), "MRSS feed is still missing");
+    sitemap::record_document(&pool, sitemap::MRSS_FEED, "sitemaps/mrss.xml", watermark).await.unwrap();
+    assert!(sitemap::stale_watermark(&pool).await.unwrap().is_none());
+
+    sqlx::query("INSERT INTO videos (title, s3_key) VALUES ('sitemap-new', 'videos/sitemap-new.mp4')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let newer = sitemap::stale_watermark(&pool).await.unwrap().expect("a new video makes the documents stale");
+    assert!(newer > watermark);
+}
+
+#[sqlx::test]
+async fn test_sitemap_revalidation(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+
+    let req = test::TestRequest::get().uri("/sitemap-videos.xml").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    let generated = chrono::NaiveDate::from_ymd_opt(2025, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+    sitemap::record_document(&pool, sitemap::VIDEO_SITEMAP, "sitemaps/sitemap-videos.xml", generated).await.unwrap();
+
+    // Answered from the database, so no S3 needed
+    let req = test::TestRequest::get()
+        .uri("/sitemap-videos.xml")
+        .insert_header(("If-Modified-Since", "Thu, 02 Jan 2025 03:04:05 GMT"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get("Last-Modified").unwrap(), "Thu, 02 Jan 2025 03:04:05 GMT");
+}
@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+
+use video_streaming_backend::models::Video;
+use video_streaming_backend::sitemap::build_video_sitemap;
+
+fn video(id: i32, thumbnail: Option<&str>) -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 3, 4).unwrap().and_hms_opt(5, 6, 7).unwrap();
+    Video {
+        id,
+        title: "Cats & dogs".to_string(),
+        description: None,
+        s3_key: format!("videos/{}.mp4", id),
+        thumbnail_url: thumbnail.map(str::to_string),
+        uploaded_by: None,
+        upload_date: Some(uploaded),
+        tags: Some(vec!["pets".to_string()]),
+        view_count: Some(12),
+        category_ids: None,
+        duration: Some(95),
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: None,
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: Vec::new(),
+        blocked_countries: vec!["DE".to_string(), "FR".to_string()],
+        allow_embed: false,
+        allow_download: false,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+#[test]
+fn sitemap_lists_video_pages_with_video_metadata() {
+    let xml = build_video_sitemap(&[video(3, Some("thumbnails/abc.jpg"))], "https://videos.example.com");
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\""));
+    assert!(xml.contains("<loc>https://videos.example.com/video/3</loc>"));
+    assert!(xml.contains("<lastmod>2025-03-04T05:06:07+00:00</lastmod>"));
+    assert!(xml.contains("<video:thumbnail_loc>https://videos.example.com/api/thumbnails/abc.jpg</video:thumbnail_loc>"));
+    // The title stands in for a missing description
+    assert!(xml.contains("<video:title>Cats &amp; dogs</video:title>"));
+    assert!(xml.contains("<video:description>Cats &amp; dogs</video:description>"));
+    assert!(xml.contains("<video:content_loc>https://videos.example.com/api/videos/3/stream</video:content_loc>"));
+    assert!(!xml.contains("<video:player_loc>"));
+    assert!(xml.contains("<video:duration>95</video:duration>"));
+    assert!(xml.contains("<video:tag>pets</video:tag>"));
+    assert!(xml.contains("<video:restriction relationship=\"deny\">DE FR</video:restriction>"));
+    assert!(xml.ends_with("</urlset>\n"));
+}
+
+#[test]
+fn videos_without_thumbnails_are_listed_without_video_extension() {
+    let xml = build_video_sitemap(&[video(4, None)], "https://videos.example.com");
+    assert!(xml.contains("<loc>https://videos.example.com/video/4</loc>"));
+    assert!(!xml.contains("<video:video>"));
+}
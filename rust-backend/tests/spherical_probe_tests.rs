@@ -0,0 +1,55 @@
+use video_streaming_backend::models::SphericalRequest;
+use video_streaming_backend::spherical::{parse_probe_output, validate, Spherical};
+
+fn spherical(projection: &str, stereo_mode: &str) -> Option<Spherical> {
+    Some(Spherical { projection: projection.to_string(), stereo_mode: stereo_mode.to_string() })
+}
+
+#[test]
+fn reads_projection_and_stereo_side_data() {
+    let json = r#"{
+        "programs": [],
+        "streams": [{
+            "side_data_list": [
+                { "side_data_type": "Stereo 3D", "type": "top and bottom", "inverted": 0 },
+                { "side_data_type": "Spherical Mapping", "projection": "equirectangular", "yaw": 0, "pitch": 0, "roll": 0 }
+            ]
+        }]
+    }"#;
+    assert_eq!(parse_probe_output(json).unwrap(), spherical("equirectangular", "top_bottom"));
+}
+
+#[test]
+fn tiled_projections_and_webm_stereo_tags_are_recognized() {
+    let json = r#"{"streams": [{
+        "side_data_list": [{ "side_data_type": "Spherical Mapping", "projection": "tiled equirectangular" }],
+        "tags": { "stereo_mode": "left_right" }
+    }]}"#;
+    assert_eq!(parse_probe_output(json).unwrap(), spherical("equirectangular", "left_right"));
+
+    let json = r#"{"streams": [{"side_data_list": [{ "side_data_type": "Spherical Mapping", "projection": "cubemap" }]}]}"#;
+    assert_eq!(parse_probe_output(json).unwrap(), spherical("cubemap", "mono"));
+}
+
+#[test]
+fn flat_video_has_no_projection() {
+    assert_eq!(parse_probe_output(r#"{"streams": [{}]}"#).unwrap(), None);
+    assert_eq!(parse_probe_output(r#"{"streams": []}"#).unwrap(), None);
+    // Stereo alone doesn't make a video 360°
+    let json = r#"{"streams": [{"side_data_list": [{ "side_data_type": "Stereo 3D", "type": "side by side" }]}]}"#;
+    assert_eq!(parse_probe_output(json).unwrap(), None);
+    let json = r#"{"streams": [{"side_data_list": [{ "side_data_type": "Spherical Mapping", "projection": "fisheye" }]}]}"#;
+    assert_eq!(parse_probe_output(json).unwrap(), None);
+}
+
+#[test]
+fn overrides_are_validated() {
+    let request = |projection: &str, stereo_mode: Option<&str>| SphericalRequest {
+        projection: projection.to_string(),
+        stereo_mode: stereo_mode.map(str::to_string),
+    };
+    assert_eq!(validate(&request(" Cubemap ", None)).ok(), spherical("cubemap", "mono"));
+    assert_eq!(validate(&request("none", Some("LEFT_RIGHT"))).ok(), spherical("none", "left_right"));
+    assert!(validate(&request("fisheye", None)).is_err());
+    assert!(validate(&request("equirectangular", Some("checkerboard"))).is_err());
+}
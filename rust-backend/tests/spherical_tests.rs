@@ -0,0 +1,152 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::spherical::{self, Spherical};
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, uploaded_by: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(uploaded_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn set_spherical(
+    app: &impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+    video_id: i32,
+    token: &str,
+    body: serde_json::Value,
+) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/spherical", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+async fn video_detail(
+    app: &impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+    video_id: i32,
+) -> serde_json::Value {
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    test::read_body_json(resp).await
+}
+
+#[sqlx::test]
+async fn test_detected_projection_is_in_video_detail(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "vrdetect").await;
+    let video_id = insert_video(&pool, "vr-detect", owner_id).await;
+
+    assert!(video_detail(&app, video_id).await["spherical"].is_null());
+
+    let detected = Spherical { projection: "equirectangular".to_string(), stereo_mode: "top_bottom".to_string() };
+    spherical::store_detected(&pool, video_id, Some(&detected)).await.unwrap();
+    let detail = video_detail(&app, video_id).await;
+    assert_eq!(detail["spherical"]["projection"], "equirectangular");
+    assert_eq!(detail["spherical"]["stereo_mode"], "top_bottom");
+    assert_eq!(detail["spherical"]["source"], "detected");
+
+    spherical::store_detected(&pool, video_id, None).await.unwrap();
+    assert!(video_detail(&app, video_id).await["spherical"].is_null());
+}
+
+#[sqlx::test]
+async fn test_uploader_overrides_detection(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "vrowner").await;
+    let (_, other_token) = register_test_user(&app, "vrother").await;
+    let video_id = insert_video(&pool, "vr-override", owner_id).await;
+
+    let resp = set_spherical(&app, video_id, &other_token, json!({"projection": "equirectangular"})).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+    let resp = set_spherical(&app, video_id, &owner_token, json!({"projection": "fisheye"})).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    let resp = set_spherical(&app, video_id, &owner_token, json!({"projection": "cubemap", "stereoMode": "sideways"})).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let resp = set_spherical(&app, video_id, &owner_token, json!({"projection": "Equirectangular"})).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let saved: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(saved["projection"], "equirectangular");
+    assert_eq!(saved["stereo_mode"], "mono");
+    assert_eq!(saved["source"], "uploader");
+
+    // Processing the upload again doesn't undo the uploader's choice
+    spherical::store_detected(&pool, video_id, None).await.unwrap();
+    let cubemap = Spherical { projection: "cubemap".to_string(), stereo_mode: "mono".to_string() };
+    spherical::store_detected(&pool, video_id, Some(&cubemap)).await.unwrap();
+    assert_eq!(video_detail(&app, video_id).await["spherical"]["projection"], "equirectangular");
+
+    let resp = set_spherical(&app, video_id, &owner_token, json!({"projection": "none"})).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert!(video_detail(&app, video_id).await["spherical"].is_null());
+}
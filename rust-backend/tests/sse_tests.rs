@@ -0,0 +1,174 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::notifications;
+use video_streaming_backend::job_queue::MediaJobKind;
+use video_streaming_backend::processing;
+use actix_web::body::MessageBody;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("SSE video")
+        .bind("sse_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// Read event stream chunks until one contains `needle`, failing after a few seconds
+async fn read_until(body: &mut actix_web::body::BoxBody, needle: &str) -> String {
+    let deadline = std::time::Duration::from_secs(5);
+    let mut received = String::new();
+    tokio::time::timeout(deadline, async {
+        while !received.contains(needle) {
+            let chunk = std::future::poll_fn(|cx| std::pin::Pin::new(&mut *body).poll_next(cx)).await;
+            let chunk = chunk.expect("event stream ended").unwrap();
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("Timed out waiting for {:?}, got {:?}", needle, received));
+    received
+}
+
+#[sqlx::test]
+async fn test_sse_comments_streams_new_comments(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "sse_commenter").await;
+    let video_id = insert_video(&pool, user_id).await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/sse/comments/{}", video_id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get(http::header::CONTENT_TYPE).unwrap(), "text/event-stream");
+    let mut body = resp.into_body();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/comments/{}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "text": "Streamed over SSE", "videoTime": 3 }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let events = read_until(&mut body, "Streamed over SSE").await;
+    assert!(events.starts_with("event: comment\ndata: "));
+}
+
+#[sqlx::test]
+async fn test_sse_notifications_requires_auth_and_resumes_from_last_event_id(pool: PgPool) {
+    std::env::set_var("SSE_NOTIFICATION_POLL_SECS", "1");
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "sse_listener").await;
+
+    let req = test::TestRequest::get().uri("/api/sse/notifications").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    let missed = notifications::create_notification(&pool, user_id, "saved_search_match", json!({ "n": 1 })).await.unwrap();
+
+    // EventSource passes the JWT in the query string and the last seen id on reconnect
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/sse/notifications?token={}", token))
+        .insert_header(("Last-Event-ID", (missed.id - 1).to_string()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let mut body = resp.into_body();
+
+    let events = read_until(&mut body, "event: notification").await;
+    assert!(events.contains(&format!("id: {}\n", missed.id)));
+
+    let next = notifications::create_notification(&pool, user_id, "watch_party_invite", json!({ "n": 2 })).await.unwrap();
+    let events = read_until(&mut body, &format!("id: {}\n", next.id)).await;
+    assert!(events.contains("watch_party_invite"));
+}
+
+#[sqlx::test]
+async fn test_sse_processing_streams_status_changes(pool: PgPool) {
+    std::env::set_var("PROCESSING_STATUS_POLL_SECS", "1");
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "sse_uploader").await;
+    let (_, other_token) = register_test_user(&app, "sse_onlooker").await;
+    let video_id = insert_video(&pool, user_id).await;
+
+    let uri = format!("/api/sse/videos/{}/processing", video_id);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+    let req = test::TestRequest::get().uri(&format!("{}?token={}", uri, other_token)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get().uri(&format!("{}?token={}", uri, token)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let mut body = resp.into_body();
+
+    let events = read_until(&mut body, "\"processing_status\":\"uploaded\"").await;
+    assert!(events.starts_with("event: processing\ndata: "));
+
+    processing::record(&pool, video_id, MediaJobKind::Duration, processing::JOB_RUNNING, None).await.unwrap();
+    read_until(&mut body, "\"processing_status\":\"processing\"").await;
+    processing::record(&pool, video_id, MediaJobKind::Duration, processing::JOB_DONE, None).await.unwrap();
+    let events = read_until(&mut body, "\"processing_status\":\"ready\"").await;
+    assert!(events.contains("\"kind\":\"duration\",\"state\":\"done\""));
+}
@@ -0,0 +1,18 @@
+use video_streaming_backend::storage_tiering::{needs_restore, restore_completed, COLD_CLASSES, STANDARD};
+
+#[test]
+fn only_archive_classes_need_a_restore() {
+    assert!(needs_restore("GLACIER"));
+    assert!(needs_restore("DEEP_ARCHIVE"));
+    assert!(!needs_restore("GLACIER_IR"));
+    assert!(!needs_restore("STANDARD_IA"));
+    assert!(!needs_restore(STANDARD));
+    assert!(!COLD_CLASSES.contains(&STANDARD));
+}
+
+#[test]
+fn restore_header_is_read() {
+    assert!(restore_completed(Some("ongoing-request=\"false\", expiry-date=\"Fri, 21 Dec 2012 00:00:00 GMT\"")));
+    assert!(!restore_completed(Some("ongoing-request=\"true\"")));
+    assert!(!restore_completed(None));
+}
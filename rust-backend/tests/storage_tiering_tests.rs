@@ -0,0 +1,188 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::storage_tiering::{self, RESTORE_RETRY_AFTER_SECS};
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, name: &str, uploaded_days_ago: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, upload_date) VALUES ($1, $2, NOW() - make_interval(days => $3)) RETURNING id")
+        .bind(name)
+        .bind(format!("videos/{}.mp4", name))
+        .bind(uploaded_days_ago)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn record_view(pool: &PgPool, video_id: i32, days_ago: i32) {
+    sqlx::query("INSERT INTO video_views (video_id, viewed_at) VALUES ($1, NOW() - make_interval(days => $2))")
+        .bind(video_id)
+        .bind(days_ago)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn set_storage(pool: &PgPool, video_id: i32, storage_class: &str, restoring: bool) {
+    sqlx::query("INSERT INTO video_storage (video_id, storage_class, restore_requested_at) VALUES ($1, $2, CASE WHEN $3 THEN NOW() END)")
+        .bind(video_id)
+        .bind(storage_class)
+        .bind(restoring)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn test_cold_videos_have_no_recent_views(pool: PgPool) {
+    let forgotten = insert_video(&pool, "forgotten", 400).await;
+    let rewatched = insert_video(&pool, "rewatched", 400).await;
+    let once_popular = insert_video(&pool, "once_popular", 400).await;
+    let fresh = insert_video(&pool, "fresh", 10).await;
+    let archived = insert_video(&pool, "archived", 400).await;
+    let trashed = insert_video(&pool, "trashed", 400).await;
+    record_view(&pool, rewatched, 3).await;
+    record_view(&pool, once_popular, 200).await;
+    set_storage(&pool, archived, "GLACIER", false).await;
+    sqlx::query("UPDATE videos SET deleted_at = NOW() WHERE id = $1")
+        .bind(trashed)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let cold: Vec<i32> = storage_tiering::cold_videos(&pool, 90, 100).await.unwrap().into_iter().map(|v| v.id).collect();
+    assert_eq!(cold, vec![forgotten, once_popular]);
+    assert!(!cold.contains(&fresh));
+
+    let cold: Vec<i32> = storage_tiering::cold_videos(&pool, 90, 1).await.unwrap().into_iter().map(|v| v.id).collect();
+    assert_eq!(cold, vec![forgotten]);
+}
+
+#[sqlx::test]
+async fn test_stream_waits_for_archive_restore(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let video_id = insert_video(&pool, "glacial", 400).await;
+    set_storage(&pool, video_id, "DEEP_ARCHIVE", true).await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/videos/{}/stream", video_id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers().get("retry-after").unwrap().to_str().unwrap(), RESTORE_RETRY_AFTER_SECS.to_string());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "restore_in_progress");
+
+    // Instantly readable classes are served while they wait to be moved back
+    let infrequent = insert_video(&pool, "infrequent", 400).await;
+    set_storage(&pool, infrequent, "STANDARD_IA", true).await;
+    let video = sqlx::query_as("SELECT * FROM videos WHERE id = $1").bind(infrequent).fetch_one(&pool).await.unwrap();
+    let storage = storage::init_storage().await;
+    assert_eq!(
+        storage_tiering::request_restore(&pool, storage.as_ref(), "videos", &video).await.unwrap(),
+        storage_tiering::Availability::Readable
+    );
+}
+
+#[sqlx::test]
+async fn test_admin_storage_endpoints(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "storage_admin").await;
+    let (_, other_token) = register_test_user(&app, "storage_viewer").await;
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let video_id = insert_video(&pool, "frozen", 400).await;
+    set_storage(&pool, video_id, "GLACIER", false).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/storage")
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/storage")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body[0]["video_id"], video_id);
+    assert_eq!(body[0]["storage_class"], "GLACIER");
+    assert!(body[0]["restore_requested_at"].is_null());
+
+    for (storage_class, status) in [("COLD", http::StatusCode::BAD_REQUEST), ("glacier_ir", http::StatusCode::CONFLICT)] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/admin/videos/{}/storage", video_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "storageClass": storage_class }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status, "{}", storage_class);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/admin/videos/999999/storage")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "storageClass": "GLACIER" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
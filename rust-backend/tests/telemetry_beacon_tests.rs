@@ -0,0 +1,35 @@
+use video_streaming_backend::telemetry::{parse_beacons, MAX_BEACONS_PER_REQUEST};
+
+#[test]
+fn accepts_a_single_beacon_or_an_array() {
+    let beacons = parse_beacons(br#"{"videoId": 7, "sessionId": "abc", "rendition": "720p", "startupMs": 850}"#).unwrap();
+    assert_eq!(beacons.len(), 1);
+    assert_eq!(beacons[0].video_id, 7);
+    assert_eq!(beacons[0].rendition.as_deref(), Some("720p"));
+    assert_eq!(beacons[0].startup_ms, Some(850));
+    assert_eq!(beacons[0].rebuffer_count, None);
+
+    let beacons = parse_beacons(br#"[{"videoId": 1, "rebufferCount": 2}, {"videoId": 2, "watchedSecs": 12.5}]"#).unwrap();
+    assert_eq!(beacons.len(), 2);
+    assert_eq!(beacons[1].watched_secs, Some(12.5));
+}
+
+#[test]
+fn rejects_malformed_or_oversized_batches() {
+    assert!(parse_beacons(b"not json").is_err());
+    assert!(parse_beacons(br#"{"rendition": "720p"}"#).is_err());
+    assert!(parse_beacons(b"[]").is_err());
+
+    let batch = format!("[{}]", vec![r#"{"videoId": 1}"#; MAX_BEACONS_PER_REQUEST + 1].join(","));
+    assert!(parse_beacons(batch.as_bytes()).is_err());
+}
+
+#[test]
+fn rejects_values_out_of_range() {
+    assert!(parse_beacons(br#"{"videoId": 1, "rebufferMs": -5}"#).is_err());
+    assert!(parse_beacons(br#"{"videoId": 1, "startupMs": 3600000}"#).is_err());
+    assert!(parse_beacons(br#"{"videoId": 1, "watchedSecs": -1.0}"#).is_err());
+    assert!(parse_beacons(br#"{"videoId": 1, "rendition": "  "}"#).is_err());
+    assert!(parse_beacons(format!(r#"{{"videoId": 1, "rendition": "{}"}}"#, "x".repeat(33)).as_bytes()).is_err());
+    assert!(parse_beacons(br#"[{"videoId": 1}, {"videoId": 2, "droppedFrames": -1}]"#).is_err());
+}
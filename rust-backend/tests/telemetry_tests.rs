@@ -0,0 +1,217 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ($1, $2) RETURNING id")
+    .bind(title)
+    .bind(format!("videos/{}.mp4", title))
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn send_beacons(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, body: serde_json::Value) -> http::StatusCode {
+    // Sent the way navigator.sendBeacon sends a string
+    let req = test::TestRequest::post()
+        .uri("/api/telemetry/playback")
+        .insert_header(("Content-Type", "text/plain;charset=UTF-8"))
+        .set_payload(body.to_string())
+        .to_request();
+    test::call_service(app, req).await.status()
+}
+
+#[sqlx::test]
+async fn test_beacons_are_recorded(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let video_id = insert_video(&pool, "telemetry").await;
+
+    let status = send_beacons(&app, json!([
+        { "videoId": video_id, "sessionId": "s1", "rendition": "720p", "startupMs": 900, "rebufferCount": 1 },
+        { "videoId": video_id + 1000, "sessionId": "s2", "rendition": "720p" }
+    ])).await;
+    assert_eq!(status, http::StatusCode::NO_CONTENT);
+
+    // The beacon for a video that doesn't exist is dropped
+    let rows: Vec<(i32, Option<String>, Option<i32>, Option<i32>)> =
+        sqlx::query_as("SELECT video_id, rendition, startup_ms, user_id FROM playback_events")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(rows, vec![(video_id, Some("720p".to_string()), Some(900), None)]);
+
+    let status = send_beacons(&app, json!({ "videoId": video_id, "startupMs": -1 })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_report_aggregates_per_rendition(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "telemetryadmin").await;
+    let (_, viewer_token) = register_test_user(&app, "telemetryviewer").await;
+    make_admin(&pool, admin_id).await;
+    let video_id = insert_video(&pool, "ladder").await;
+    let other_id = insert_video(&pool, "other").await;
+
+    send_beacons(&app, json!([
+        { "videoId": video_id, "sessionId": "a", "rendition": "1080p", "startupMs": 1000, "rebufferMs": 3000, "watchedSecs": 60.0 },
+        { "videoId": video_id, "sessionId": "b", "rendition": "1080p", "startupMs": 3000, "rebufferMs": 0, "watchedSecs": 60.0 },
+        { "videoId": video_id, "sessionId": "b", "rendition": "480p", "startupMs": 500 },
+        { "videoId": other_id, "sessionId": "c", "rendition": "480p", "startupMs": 700 }
+    ])).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/admin/telemetry/playback?videoId={}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["days"], 7);
+    let renditions = body["renditions"].as_array().unwrap();
+    assert_eq!(renditions.len(), 2);
+    assert_eq!(renditions[0]["rendition"], "1080p");
+    assert_eq!(renditions[0]["beacons"], 2);
+    assert_eq!(renditions[0]["sessions"], 2);
+    assert_eq!(renditions[0]["avg_startup_ms"], 2000.0);
+    assert_eq!(renditions[0]["rebuffer_ratio"], 0.025);
+    assert_eq!(renditions[1]["rendition"], "480p");
+    assert_eq!(renditions[1]["beacons"], 1);
+
+    // Across every video
+    let req = test::TestRequest::get()
+        .uri("/api/admin/telemetry/playback")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let renditions = body["renditions"].as_array().unwrap();
+    assert!(renditions.iter().all(|r| r["beacons"] == 2));
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/telemetry/playback?days=0")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/telemetry/playback")
+        .insert_header(("Authorization", format!("Bearer {}", viewer_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_beacons_for_unplayable_videos_are_dropped(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let held_id = insert_video(&pool, "held").await;
+    sqlx::query("UPDATE videos SET moderation_status = 'pending' WHERE id = $1")
+        .bind(held_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let status = send_beacons(&app, json!({ "videoId": held_id, "sessionId": "s1", "startupMs": 900 })).await;
+    assert_eq!(status, http::StatusCode::NO_CONTENT);
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM playback_events").fetch_one(&pool).await.unwrap();
+    assert_eq!(count, 0);
+}
+
+#[sqlx::test]
+async fn test_beacons_are_rate_limited_per_client(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "telemetryflood").await;
+    let video_id = insert_video(&pool, "flooded").await;
+
+    let batch = json!((0..50).map(|i| json!({ "videoId": video_id, "sessionId": format!("s{}", i) })).collect::<Vec<_>>());
+    let send = || test::TestRequest::post()
+        .uri("/api/telemetry/playback")
+        .insert_header(("Content-Type", "text/plain;charset=UTF-8"))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_payload(batch.to_string())
+        .to_request();
+    assert_eq!(test::call_service(&app, send()).await.status(), http::StatusCode::NO_CONTENT);
+    // Over the default of 120 a minute by the third batch, or the fifth should a minute end
+    // in between
+    let mut refused = None;
+    for _ in 0..4 {
+        let resp = test::call_service(&app, send()).await;
+        if resp.status() == http::StatusCode::TOO_MANY_REQUESTS {
+            refused = Some(resp);
+            break;
+        }
+    }
+    assert!(refused.expect("Beacons were never rate limited").headers().contains_key("retry-after"));
+}
@@ -0,0 +1,222 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::signed_urls;
+use video_streaming_backend::thumbnails;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, owner: i32, title: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .bind(owner)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_owner_selects_thumbnail_candidate(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "thumb_owner").await;
+    let (_, other_token) = register_test_user(&app, "thumb_viewer").await;
+    let video_id = insert_video(&pool, owner_id, "thumbed").await;
+
+    let candidates = vec![
+        ("thumbnails/a.jpg".to_string(), 5.0, 0.3),
+        ("thumbnails/b.jpg".to_string(), 50.0, 0.8),
+        ("thumbnails/c.jpg".to_string(), 95.0, 0.5),
+    ];
+    let old = thumbnails::replace_candidates(&pool, video_id, &candidates, "thumbnails/b.jpg").await.unwrap();
+    assert!(old.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/thumbnail-candidates", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", other_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/thumbnail-candidates", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    let listed: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let listed = listed.as_array().unwrap();
+    assert_eq!(listed.len(), 3);
+    assert_eq!(listed[0]["s3_key"], "thumbnails/b.jpg");
+    assert_eq!(listed[0]["selected"], true);
+    assert_eq!(listed[1]["s3_key"], "thumbnails/c.jpg");
+    assert_eq!(listed[1]["selected"], false);
+    let c_id = listed[1]["id"].as_i64().unwrap();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/thumbnail-candidates", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "candidateId": c_id }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let updated: serde_json::Value = test::read_body_json(resp).await;
+    let selected: Vec<&str> = updated.as_array().unwrap().iter()
+        .filter(|c| c["selected"] == true)
+        .map(|c| c["s3_key"].as_str().unwrap())
+        .collect();
+    assert_eq!(selected, vec!["thumbnails/c.jpg"]);
+
+    let thumbnail_url: Option<String> = sqlx::query_scalar("SELECT thumbnail_url FROM videos WHERE id = $1")
+        .bind(video_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(thumbnail_url.as_deref(), Some("thumbnails/c.jpg"));
+}
+
+#[sqlx::test]
+async fn test_candidates_are_scoped_to_video(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "thumb_scoper").await;
+    let video_id = insert_video(&pool, owner_id, "thumbed").await;
+    let other_video_id = insert_video(&pool, owner_id, "other").await;
+
+    thumbnails::replace_candidates(&pool, other_video_id, &[("thumbnails/x.jpg".to_string(), 1.0, 0.5)], "thumbnails/x.jpg").await.unwrap();
+    let other_id = thumbnails::list_candidates(&pool, other_video_id).await.unwrap()[0].id;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/thumbnail-candidates", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .set_json(json!({ "candidateId": other_id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    // Regenerating returns the replaced keys so their objects can be removed
+    let old = thumbnails::replace_candidates(&pool, other_video_id, &[("thumbnails/y.jpg".to_string(), 2.0, 0.6)], "thumbnails/y.jpg").await.unwrap();
+    assert_eq!(old, vec!["thumbnails/x.jpg".to_string()]);
+}
+
+#[sqlx::test]
+async fn test_thumbnail_endpoint_only_serves_known_keys(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+
+    // Objects outside thumbnails/ are refused before S3 is asked
+    for path in ["..%2Fvideos%2Fsecret.mp4", "..", "a%20b.jpg", ".env"] {
+        let req = test::TestRequest::get().uri(&format!("/api/thumbnails/{}", path)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST, "{}", path);
+    }
+
+    // A well-formed key no video refers to is not looked up either
+    let req = test::TestRequest::get().uri("/api/thumbnails/stray.jpg").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    let (owner_id, _) = register_test_user(&app, "thumb_keys").await;
+    let video_id = insert_video(&pool, owner_id, "keyed").await;
+    thumbnails::replace_candidates(
+        &pool,
+        video_id,
+        &[("thumbnails/keyed-a.jpg".to_string(), 1.0, 0.9), ("thumbnails/keyed-b.jpg".to_string(), 2.0, 0.4)],
+        "thumbnails/keyed-a.jpg",
+    ).await.unwrap();
+
+    assert!(thumbnails::is_known_thumbnail(&pool, "thumbnails/keyed-a.jpg").await.unwrap());
+    assert!(thumbnails::is_known_thumbnail(&pool, "thumbnails/keyed-b.jpg").await.unwrap());
+    assert!(!thumbnails::is_known_thumbnail(&pool, "thumbnails/stray.jpg").await.unwrap());
+    assert!(!thumbnails::is_known_thumbnail(&pool, "videos/keyed.mp4").await.unwrap());
+}
+
+#[sqlx::test]
+async fn test_signed_thumbnail_urls_are_checked(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "thumb_signed").await;
+    let video_id = insert_video(&pool, owner_id, "signed").await;
+    let now = chrono::Utc::now().timestamp();
+    let path = signed_urls::thumbnail_path(video_id, &signed_urls::thumbnail_version("thumbnails/signed.jpg"));
+
+    // Unsigned, tampered with and expired URLs are refused
+    let expires = now + 60;
+    let signature = signed_urls::sign(&path, expires);
+    for uri in [
+        path.clone(),
+        format!("{}&expires={}&signature={}", path, expires + 1, signature),
+        format!("{}&expires={}&signature={}", signed_urls::thumbnail_path(video_id + 1, "0000000000000000"), expires, signature),
+        format!("{}&expires={}&signature={}", path, now - 1, signed_urls::sign(&path, now - 1)),
+    ] {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN, "{}", uri);
+    }
+
+    // A correctly signed URL for a video without a thumbnail in the bucket
+    let req = test::TestRequest::get().uri(&signed_urls::signed_url(&path, now)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    // The stream only checks signatures that are there
+    let stream = signed_urls::stream_path(video_id);
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?expires={}&signature={}", stream, now - 1, signed_urls::sign(&stream, now - 1)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    // API responses link to the video's media without naming its keys
+    let req = test::TestRequest::get().uri(&format!("/api/videos/{}", video_id)).to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(body.get("s3_key").is_none());
+    assert!(body["playback_url"].as_str().unwrap().starts_with(&format!("{}?expires=", stream)));
+}
@@ -0,0 +1,34 @@
+use video_streaming_backend::thumbnails::{thumbnail_s3_key, MAX_THUMBNAIL_NAME_LEN};
+
+#[test]
+fn names_map_into_the_thumbnails_prefix() {
+    assert_eq!(thumbnail_s3_key("0b5e6a1c-7d2f-4c3a-9e8b-1f2a3b4c5d6e.jpg").as_deref(), Some("thumbnails/0b5e6a1c-7d2f-4c3a-9e8b-1f2a3b4c5d6e.jpg"));
+    assert_eq!(thumbnail_s3_key("thumbnails/a_b.jpg").as_deref(), Some("thumbnails/a_b.jpg"));
+}
+
+#[test]
+fn keys_cannot_leave_the_prefix() {
+    assert_eq!(thumbnail_s3_key("../videos/secret.mp4"), None);
+    assert_eq!(thumbnail_s3_key("thumbnails/../videos/secret.mp4"), None);
+    assert_eq!(thumbnail_s3_key(".."), None);
+    assert_eq!(thumbnail_s3_key("a..jpg"), None);
+    assert_eq!(thumbnail_s3_key("videos/secret.mp4"), None);
+    assert_eq!(thumbnail_s3_key("thumbnails/nested/a.jpg"), None);
+    assert_eq!(thumbnail_s3_key("/etc/passwd"), None);
+    assert_eq!(thumbnail_s3_key("a\\b.jpg"), None);
+}
+
+#[test]
+fn unexpected_characters_and_lengths_are_rejected() {
+    assert_eq!(thumbnail_s3_key(""), None);
+    assert_eq!(thumbnail_s3_key("thumbnails/"), None);
+    assert_eq!(thumbnail_s3_key(".hidden.jpg"), None);
+    assert_eq!(thumbnail_s3_key("a b.jpg"), None);
+    assert_eq!(thumbnail_s3_key("a%2Fb.jpg"), None);
+    assert_eq!(thumbnail_s3_key("caf\u{e9}.jpg"), None);
+    assert_eq!(thumbnail_s3_key("a.jpg\0"), None);
+
+    let longest = "a".repeat(MAX_THUMBNAIL_NAME_LEN);
+    assert!(thumbnail_s3_key(&longest).is_some());
+    assert_eq!(thumbnail_s3_key(&format!("{}a", longest)), None);
+}
@@ -0,0 +1,58 @@
+use video_streaming_backend::thumbnails::{candidate_offsets, score_frame};
+
+const W: usize = 160;
+const H: usize = 90;
+
+fn flat(level: u8) -> Vec<u8> {
+    vec![level; W * H]
+}
+
+// Mid-grey background with a sharp-edged checkerboard, like a detailed, in-focus scene
+fn detailed() -> Vec<u8> {
+    (0..W * H)
+        .map(|i| if ((i % W) / 8 + (i / W) / 8).is_multiple_of(2) { 60 } else { 190 })
+        .collect()
+}
+
+// The same pattern with soft gradients instead of edges, like an out of focus scene
+fn blurry() -> Vec<u8> {
+    (0..W * H)
+        .map(|i| (125.0 + 40.0 * ((i % W) as f64 / 20.0).sin()) as u8)
+        .collect()
+}
+
+#[test]
+fn test_dark_and_blank_frames_score_low() {
+    let black = score_frame(&flat(0), W, H);
+    let white = score_frame(&flat(255), W, H);
+    let grey = score_frame(&flat(128), W, H);
+
+    assert!(black.score < 0.05);
+    assert!(white.score < 0.05);
+    assert_eq!(grey.sharpness, 0.0);
+    assert_eq!(grey.edge_density, 0.0);
+}
+
+#[test]
+fn test_sharp_detailed_frame_beats_blurry_one() {
+    let sharp = score_frame(&detailed(), W, H);
+    let soft = score_frame(&blurry(), W, H);
+
+    assert!(sharp.sharpness > soft.sharpness);
+    assert!(sharp.edge_density > soft.edge_density);
+    assert!(sharp.score > soft.score);
+    assert!(sharp.score > score_frame(&flat(128), W, H).score);
+}
+
+#[test]
+fn test_empty_frame_scores_zero() {
+    assert_eq!(score_frame(&[], W, H).score, 0.0);
+}
+
+#[test]
+fn test_candidate_offsets_skip_intro_and_credits() {
+    assert_eq!(candidate_offsets(Some(100.0), 3), vec![5.0, 50.0, 95.0]);
+    assert_eq!(candidate_offsets(Some(100.0), 1), vec![50.0]);
+    assert_eq!(candidate_offsets(Some(1.0), 6), vec![0.0]);
+    assert_eq!(candidate_offsets(None, 6), vec![0.0]);
+}
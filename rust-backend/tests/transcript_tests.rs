@@ -0,0 +1,160 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::models::TranscriptSegment;
+use video_streaming_backend::transcription;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+
+async fn insert_video(pool: &PgPool, title: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ($1, $2) RETURNING id")
+        .bind(title)
+        .bind(format!("videos/{}.mp4", title))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+fn sample_segments() -> Vec<TranscriptSegment> {
+    vec![
+        TranscriptSegment { start: 0.0, end: 2.5, text: "Welcome to the kitchen".to_string() },
+        TranscriptSegment { start: 2.5, end: 3661.25, text: "Today we bake sourdough bread".to_string() },
+    ]
+}
+
+#[sqlx::test]
+async fn test_get_transcript_json_and_vtt(pool: PgPool) {
+    let video_id = insert_video(&pool, "baking").await;
+    transcription::store_transcript(&pool, video_id, "whisper_cpp", Some("en"), &sample_segments()).await.unwrap();
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/transcript", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["language"], "en");
+    assert_eq!(body["backend"], "whisper_cpp");
+    assert_eq!(body["content"], "Welcome to the kitchen Today we bake sourdough bread");
+    assert_eq!(body["segments"].as_array().unwrap().len(), 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/transcript?q=SOURDOUGH", video_id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let segments = body["segments"].as_array().unwrap();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0]["start"], 2.5);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/transcript?format=vtt", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get("content-type").unwrap().to_str().unwrap().starts_with("text/vtt"));
+    let vtt = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(
+        vtt,
+        "WEBVTT\n\n00:00:00.000 --> 00:00:02.500\nWelcome to the kitchen\n\n00:00:02.500 --> 01:01:01.250\nToday we bake sourdough bread\n"
+    );
+}
+
+#[sqlx::test]
+async fn test_transcript_not_found(pool: PgPool) {
+    let video_id = insert_video(&pool, "silent").await;
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/transcript", video_id))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos/999999/transcript")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_transcript_of_unwatchable_video_is_refused(pool: PgPool) {
+    let held = insert_video(&pool, "held").await;
+    let premiere = insert_video(&pool, "premiere").await;
+    for video_id in [held, premiere] {
+        transcription::store_transcript(&pool, video_id, "api", None, &sample_segments()).await.unwrap();
+    }
+    sqlx::query("UPDATE videos SET moderation_status = 'pending' WHERE id = $1").bind(held).execute(&pool).await.unwrap();
+    sqlx::query("UPDATE videos SET premiere_at = NOW() AT TIME ZONE 'UTC' + INTERVAL '1 day' WHERE id = $1")
+        .bind(premiere)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = setup_test_app(pool).await;
+
+    for video_id in [held, premiere] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/videos/{}/transcript", video_id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+    }
+}
+
+#[test]
+fn test_long_audio_is_sent_in_chunks() {
+    assert_eq!(transcription::chunk_starts(0.0, 600.0), vec![0.0]);
+    assert_eq!(transcription::chunk_starts(600.0, 600.0), vec![0.0]);
+    assert_eq!(transcription::chunk_starts(1500.0, 600.0), vec![0.0, 600.0, 1200.0]);
+}
+
+#[sqlx::test]
+async fn test_advanced_search_matches_transcript(pool: PgPool) {
+    let video_id = insert_video(&pool, "baking").await;
+    insert_video(&pool, "gardening").await;
+    transcription::store_transcript(&pool, video_id, "api", None, &sample_segments()).await.unwrap();
+    let app = setup_test_app(pool).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/videos/search")
+        .set_json(serde_json::json!({ "text": "sourdough" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let videos = body.as_array().unwrap();
+    assert_eq!(videos.len(), 1);
+    assert_eq!(videos[0]["id"], video_id);
+}
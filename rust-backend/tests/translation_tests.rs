@@ -0,0 +1,224 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, title: &str, description: &str, uploaded_by: i32) -> i32 {
+    sqlx::query_scalar(
+        "INSERT INTO videos (title, description, s3_key, uploaded_by) VALUES ($1, $2, $3, $4) RETURNING id"
+    )
+    .bind(title)
+    .bind(description)
+    .bind(format!("videos/{}.mp4", title))
+    .bind(uploaded_by)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+async fn put_translation(
+    app: &impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+    token: &str,
+    video_id: i32,
+    language: &str,
+    title: &str,
+    description: &str,
+) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/videos/{}/translations/{}", video_id, language))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "title": title, "description": description }))
+        .to_request();
+    test::call_service(app, req).await
+}
+
+#[sqlx::test]
+async fn test_only_the_uploader_can_translate(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "trowner").await;
+    let (_, other_token) = register_test_user(&app, "trother").await;
+    let video_id = insert_video(&pool, "tr-owner", "Original", owner_id).await;
+
+    let resp = put_translation(&app, &other_token, video_id, "fr", "Titre", "Description").await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let resp = put_translation(&app, &owner_token, video_id, "en_US", "Titre", "").await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    let resp = put_translation(&app, &owner_token, video_id, "FR", "Titre", "Description").await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["language"], "fr");
+
+    // Putting the same language again replaces it
+    let resp = put_translation(&app, &owner_token, video_id, "fr", "Nouveau titre", "").await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}/translations", video_id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let translations = body.as_array().unwrap();
+    assert_eq!(translations.len(), 1);
+    assert_eq!(translations[0]["title"], "Nouveau titre");
+    assert!(translations[0]["description"].is_null());
+}
+
+#[sqlx::test]
+async fn test_video_detail_follows_accept_language(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "trdetail").await;
+    let video_id = insert_video(&pool, "tr-detail", "Original description", owner_id).await;
+    put_translation(&app, &owner_token, video_id, "pt-br", "Título", "Descrição").await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Accept-Language", "de;q=0.9, pt;q=0.8, en;q=0.5"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("Content-Language").unwrap(), "pt-br");
+    assert_eq!(resp.headers().get("Vary").unwrap(), "Accept-Language");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["title"], "Título");
+    assert_eq!(body["description"], "Descrição");
+
+    // Without a matching language the original is served
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header(("Accept-Language", "ja"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get("Content-Language").is_none());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["title"], "tr-detail");
+}
+
+#[sqlx::test]
+async fn test_video_list_is_localized_and_etag_varies_by_language(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "trlist").await;
+    let video_id = insert_video(&pool, "tr-list", "Original", owner_id).await;
+    put_translation(&app, &owner_token, video_id, "es", "Lista", "Descripción").await;
+
+    let req = test::TestRequest::get().uri("/api/videos").to_request();
+    let resp = test::call_service(&app, req).await;
+    let plain_etag = resp.headers().get("ETag").unwrap().clone();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body[0]["title"], "tr-list");
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos")
+        .insert_header(("Accept-Language", "es-MX,es;q=0.9"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_ne!(resp.headers().get("ETag").unwrap(), &plain_etag);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body[0]["title"], "Lista");
+
+    // A cached untranslated list doesn't revalidate for a Spanish client
+    let req = test::TestRequest::get()
+        .uri("/api/videos")
+        .insert_header(("Accept-Language", "es"))
+        .insert_header(("If-None-Match", plain_etag.to_str().unwrap()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    // Search matches translated text and returns it localized
+    let req = test::TestRequest::get()
+        .uri("/api/videos/search/descripci")
+        .insert_header(("Accept-Language", "es"))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["title"], "Lista");
+}
+
+#[sqlx::test]
+async fn test_deleting_a_translation_changes_the_list_etag(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, owner_token) = register_test_user(&app, "trdelete").await;
+    let video_id = insert_video(&pool, "tr-delete", "Original", owner_id).await;
+    put_translation(&app, &owner_token, video_id, "it", "Titolo", "").await;
+
+    let req = test::TestRequest::get().uri("/api/videos").to_request();
+    let etag = test::call_service(&app, req).await.headers().get("ETag").unwrap().clone();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}/translations/it", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}/translations/it", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", owner_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/videos")
+        .insert_header(("If-None-Match", etag.to_str().unwrap()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::OK);
+}
@@ -0,0 +1,145 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Trash me")
+        .bind("trash_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn test_delete_moves_video_to_trash_and_restore(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "uploader").await;
+    let video_id = insert_video(&pool, user_id).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    // Hidden from the list and from direct lookups
+    let req = test::TestRequest::get().uri("/api/videos").to_request();
+    let videos: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(videos.as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // Shown in the uploader's trash
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/trash")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let trash: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let trash = trash.as_array().unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0]["id"], video_id);
+    assert!(!trash[0]["deleted_at"].is_null());
+
+    // Restoring brings it back
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/videos/{}/restore", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
+
+#[sqlx::test]
+async fn test_only_uploader_can_delete(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (owner_id, _) = register_test_user(&app, "owner").await;
+    let (_, other_token) = register_test_user(&app, "someone_else").await;
+    let video_id = insert_video(&pool, owner_id).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}", video_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", other_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/videos/{}", video_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,64 @@
+use std::net::IpAddr;
+use actix_web::test::TestRequest;
+
+use video_streaming_backend::sessions;
+use video_streaming_backend::trusted_proxies::{resolve_client_ip, IpRange};
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+fn hops(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+#[test]
+fn ranges_match_by_prefix() {
+    let vpc = IpRange::parse("10.0.0.0/16").unwrap();
+    assert!(vpc.contains(ip("10.0.5.1")));
+    assert!(!vpc.contains(ip("10.1.0.1")));
+    // IPv4 peers of a dual-stack listener show up as mapped IPv6 addresses
+    assert!(vpc.contains(ip("::ffff:10.0.0.9")));
+    assert!(IpRange::parse("127.0.0.1").unwrap().contains(ip("127.0.0.1")));
+    assert!(!IpRange::parse("127.0.0.1").unwrap().contains(ip("127.0.0.2")));
+    assert!(IpRange::parse("fd00::/8").unwrap().contains(ip("fd12::1")));
+    assert!(!IpRange::parse("fd00::/8").unwrap().contains(ip("10.0.0.1")));
+    assert!(IpRange::parse("10.0.0.0/33").is_none());
+    assert!(IpRange::parse("proxy.internal").is_none());
+}
+
+#[test]
+fn forwarded_hops_are_only_believed_from_trusted_proxies() {
+    let trusted = vec![IpRange::parse("127.0.0.1").unwrap(), IpRange::parse("10.0.0.0/16").unwrap()];
+    assert_eq!(resolve_client_ip(ip("203.0.113.9"), &hops(&["1.2.3.4"]), &trusted), ip("203.0.113.9"));
+    // Whatever the client put in front of what the proxies appended is ignored
+    assert_eq!(resolve_client_ip(ip("127.0.0.1"), &hops(&["1.2.3.4", "198.51.100.7", "10.0.1.2"]), &trusted), ip("198.51.100.7"));
+    assert_eq!(resolve_client_ip(ip("127.0.0.1"), &hops(&["unknown"]), &trusted), ip("127.0.0.1"));
+    assert_eq!(resolve_client_ip(ip("127.0.0.1"), &hops(&["198.51.100.7"]), &[]), ip("127.0.0.1"));
+    assert_eq!(resolve_client_ip(ip("127.0.0.1"), &hops(&["\"[2001:db8::1]:443\""]), &trusted), ip("2001:db8::1"));
+}
+
+#[test]
+fn client_ip_uses_the_peer_unless_it_is_a_trusted_proxy() {
+    // Every test in this file that reads the environment trusts the same proxy
+    std::env::set_var("TRUSTED_PROXIES", "127.0.0.1, not-an-address");
+
+    let req = TestRequest::default()
+        .peer_addr("127.0.0.1:5000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", "6.6.6.6, 198.51.100.7"))
+        .to_http_request();
+    assert_eq!(sessions::client_ip(&req).as_deref(), Some("198.51.100.7"));
+
+    let req = TestRequest::default()
+        .peer_addr("203.0.113.9:5000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", "6.6.6.6"))
+        .to_http_request();
+    assert_eq!(sessions::client_ip(&req).as_deref(), Some("203.0.113.9"));
+
+    let req = TestRequest::default()
+        .peer_addr("127.0.0.1:5000".parse().unwrap())
+        .insert_header(("Forwarded", "for=192.0.2.60;proto=http, for=\"[2001:db8:cafe::17]:4711\""))
+        .insert_header(("X-Forwarded-For", "6.6.6.6"))
+        .to_http_request();
+    assert_eq!(sessions::client_ip(&req).as_deref(), Some("2001:db8:cafe::17"));
+}
@@ -0,0 +1,171 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+// Insert a session directly, standing in for one created against S3
+async fn insert_session(pool: &PgPool, user_id: i32, offset: i64, expires_in_hours: i64) -> String {
+    let id = format!("session-{}-{}", user_id, offset);
+    sqlx::query(
+        "INSERT INTO upload_sessions (id, user_id, s3_key, s3_upload_id, upload_length, upload_offset, title, expires_at)
+         VALUES ($1, $2, $3, 'upload-1', 1000, $4, 'Resumable', $5)"
+    )
+    .bind(&id)
+    .bind(user_id)
+    .bind(format!("videos/{}.mp4", id))
+    .bind(offset)
+    .bind(chrono::Utc::now().naive_utc() + chrono::Duration::hours(expires_in_hours))
+    .execute(pool)
+    .await
+    .unwrap();
+    id
+}
+
+#[sqlx::test]
+async fn test_create_upload_validation(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "uploader").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/uploads")
+        .set_json(json!({"filename": "clip.mp4", "size": 1000, "title": "Clip"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+    for body in [
+        json!({"filename": "notes.txt", "size": 1000, "title": "Clip"}),
+        json!({"filename": "clip.mp4", "size": 0, "title": "Clip"}),
+        json!({"filename": "clip.mp4", "size": 1000, "title": "  "}),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/api/uploads")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
+
+#[sqlx::test]
+async fn test_upload_offset_query_and_mismatch(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "uploader").await;
+    let (_, other_token) = register_test_user(&app, "someone_else").await;
+    let session_id = insert_session(&pool, user_id, 400, 24).await;
+
+    let req = test::TestRequest::default()
+        .method(http::Method::HEAD)
+        .uri(&format!("/api/uploads/{}", session_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    assert_eq!(resp.headers().get("Upload-Offset").unwrap(), "400");
+    assert_eq!(resp.headers().get("Upload-Length").unwrap(), "1000");
+    assert!(resp.headers().get("Upload-Expires").is_some());
+
+    // Sessions are private to their owner
+    let req = test::TestRequest::default()
+        .method(http::Method::HEAD)
+        .uri(&format!("/api/uploads/{}", session_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", other_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    // Resuming from the wrong offset is rejected without touching the session
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/uploads/{}", session_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header((http::header::CONTENT_TYPE, "application/offset+octet-stream"))
+        .set_payload(vec![0u8; 100])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/uploads/{}", session_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_payload(vec![0u8; 100])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_expired_upload_is_gone(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (user_id, token) = register_test_user(&app, "uploader").await;
+    let session_id = insert_session(&pool, user_id, 0, -1).await;
+
+    let req = test::TestRequest::default()
+        .method(http::Method::HEAD)
+        .uri(&format!("/api/uploads/{}", session_id))
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,166 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::models::UserSettingsRequest;
+use video_streaming_backend::user_settings::{self, validate_update};
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn post_settings(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, token: &str, patch: serde_json::Value) -> actix_web::dev::ServiceResponse {
+    let req = test::TestRequest::post()
+        .uri("/api/user/settings")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(patch)
+        .to_request();
+    test::call_service(app, req).await
+}
+
+fn custom_theme() -> serde_json::Value {
+    json!({
+        "name": "Mine", "isCustom": true, "isDark": true,
+        "primary": "#ff0000", "secondary": "#cc0000", "accent": "#f44", "background": "#0f0f0f",
+        "surface": "#212121", "text": "#ffffff", "textSecondary": "#aaaaaa", "plyrColor": "#ff0000"
+    })
+}
+
+fn request(value: serde_json::Value) -> UserSettingsRequest {
+    serde_json::from_value(value).unwrap()
+}
+
+#[sqlx::test]
+async fn test_settings_are_returned_with_defaults(pool: PgPool) {
+    let app = setup_test_app(pool).await;
+    let (_, token) = register_test_user(&app, "settings_user").await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/user/settings")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["settings"], json!({
+        "theme": { "name": "system" },
+        "playbackSpeed": 1.0,
+        "autoplay": false,
+        "preferredQuality": "auto",
+        "captionsLanguage": null,
+        "volume": 1.0,
+        "watermark": { "enabled": false, "position": "bottomRight", "opacity": 0.8 }
+    }));
+
+    let resp = post_settings(&app, &token, json!({ "theme": custom_theme(), "captionsLanguage": "en" })).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    // Other settings are left alone, and a new theme replaces the old one whole
+    let resp = post_settings(&app, &token, json!({ "theme": { "name": "youtube" }, "autoplay": true })).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["settings"]["theme"], json!({ "name": "youtube" }));
+    assert_eq!(body["settings"]["captionsLanguage"], "en");
+    assert_eq!(body["settings"]["autoplay"], true);
+
+    let resp = post_settings(&app, &token, json!({ "captionsLanguage": "" })).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["settings"]["captionsLanguage"], serde_json::Value::Null);
+
+    let resp = post_settings(&app, &token, json!({ "volume": 3 })).await;
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_concurrent_settings_updates_keep_both(pool: PgPool) {
+    let user_id: i32 = sqlx::query_scalar("INSERT INTO users (username, email, password) VALUES ('racer', 'racer@example.com', 'x') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let update = |patch: serde_json::Value| {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let patch = validate_update(&request(patch)).unwrap();
+            user_settings::update(&pool, user_id, patch).await.unwrap().unwrap();
+        })
+    };
+    let updates = [
+        update(json!({ "autoplay": true })),
+        update(json!({ "volume": 0.5 })),
+        update(json!({ "playbackSpeed": 2.0 })),
+        update(json!({ "preferredQuality": "480p" })),
+    ];
+    for handle in updates {
+        handle.await.unwrap();
+    }
+
+    let stored: serde_json::Value = sqlx::query_scalar("SELECT settings FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let settings = user_settings::from_stored(Some(stored));
+    assert!(settings.autoplay);
+    assert_eq!(settings.volume, 0.5);
+    assert_eq!(settings.playback_speed, 2.0);
+    assert_eq!(settings.preferred_quality, "480p");
+}
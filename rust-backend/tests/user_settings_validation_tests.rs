@@ -0,0 +1,72 @@
+use serde_json::json;
+
+use video_streaming_backend::models::{ThemeSetting, UserSettings, UserSettingsRequest};
+use video_streaming_backend::user_settings::{self, validate_update};
+
+fn custom_theme() -> serde_json::Value {
+    json!({
+        "name": "Mine", "isCustom": true, "isDark": true,
+        "primary": "#ff0000", "secondary": "#cc0000", "accent": "#f44", "background": "#0f0f0f",
+        "surface": "#212121", "text": "#ffffff", "textSecondary": "#aaaaaa", "plyrColor": "#ff0000"
+    })
+}
+
+fn request(value: serde_json::Value) -> UserSettingsRequest {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn missing_settings_read_as_defaults() {
+    let settings = user_settings::from_stored(Some(json!({ "autoplay": true })));
+    assert!(settings.autoplay);
+    assert_eq!(settings.theme, ThemeSetting::default());
+    assert_eq!(settings.playback_speed, 1.0);
+    assert_eq!(settings.preferred_quality, "auto");
+    assert_eq!(settings.captions_language, None);
+    assert_eq!(settings.volume, 1.0);
+
+    assert_eq!(user_settings::from_stored(None), UserSettings::default());
+    assert_eq!(user_settings::from_stored(Some(json!({ "volume": "loud" }))), UserSettings::default());
+}
+
+#[test]
+fn updates_are_validated() {
+    let patch = validate_update(&request(json!({
+        "theme": { "name": " youtube " },
+        "playbackSpeed": 1.5,
+        "preferredQuality": "720P",
+        "captionsLanguage": "pt-BR",
+        "volume": 0.25
+    }))).unwrap();
+    assert_eq!(serde_json::Value::Object(patch), json!({
+        "theme": { "name": "youtube" },
+        "playbackSpeed": 1.5,
+        "preferredQuality": "720p",
+        "captionsLanguage": "pt-br",
+        "volume": 0.25
+    }));
+
+    // Turning captions off clears the language
+    let patch = validate_update(&request(json!({ "captionsLanguage": "" }))).unwrap();
+    assert_eq!(patch["captionsLanguage"], serde_json::Value::Null);
+
+    assert!(validate_update(&request(json!({ "theme": custom_theme() }))).is_ok());
+
+    for invalid in [
+        json!({ "playbackSpeed": 10.0 }),
+        json!({ "playbackSpeed": 0.0 }),
+        json!({ "volume": 1.5 }),
+        json!({ "preferredQuality": "best" }),
+        json!({ "preferredQuality": "99999p" }),
+        json!({ "captionsLanguage": "not a language" }),
+        json!({ "theme": { "name": "" } }),
+        json!({ "theme": { "name": "youtube", "primary": "#ff0000" } }),
+        json!({ "theme": { "name": "Mine", "isCustom": true, "primary": "#ff0000" } }),
+        json!({ "watermark": { "enabled": true, "opacity": 0 } }),
+    ] {
+        assert!(validate_update(&request(invalid.clone())).is_err(), "{} should be rejected", invalid);
+    }
+    let mut bad_color = custom_theme();
+    bad_color["accent"] = json!("red");
+    assert!(validate_update(&request(json!({ "theme": bad_color }))).is_err());
+}
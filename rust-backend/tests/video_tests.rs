@@ -10,6 +10,7 @@ use video_streaming_backend::models::{RegisterRequest, CommentRequest};
 use video_streaming_backend::handlers;
 use video_streaming_backend::AppState;
 use video_streaming_backend::services;
+use video_streaming_backend::storage;
 
 async fn setup_test_app() -> impl actix_web::dev::Service<
     actix_http::Request,
@@ -20,12 +21,12 @@ async fn setup_test_app() -> impl actix_web::dev::Service<
     
     // Initialize the database pool and S3 client
     let db_pool = services::init_db_pool().await;
-    let s3_client = services::init_s3_client().await;
+    let storage = storage::init_storage().await;
     
     // Create the app state
     let app_state = Arc::new(Mutex::new(AppState {
         db_pool,
-        s3_client,
+        storage,
         redis_client: None, // No Redis client in tests
         job_queue: None, // No job queue in tests
         video_clients: std::sync::Mutex::new(HashMap::new()),
@@ -147,7 +148,8 @@ async fn test_video_listing() {
     for video in &videos {
         assert!(video.get("id").is_some(), "Video is missing 'id' field");
         assert!(video.get("title").is_some(), "Video is missing 'title' field");
-        assert!(video.get("s3_key").is_some(), "Video is missing 's3_key' field");
+        assert!(video.get("playback_url").is_some(), "Video is missing 'playback_url' field");
+        assert!(video.get("s3_key").is_none(), "Video exposes its 's3_key'");
     }
     
     println!("Successfully listed {} videos", videos.len());
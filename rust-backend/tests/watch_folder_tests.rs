@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sqlx::PgPool;
+
+use video_streaming_backend::storage::{LocalStorage, ObjectStore, Storage};
+use video_streaming_backend::webhooks;
+use video_streaming_backend::watch_folder::{
+    ingest_file, is_candidate, parse_claimed_name, recover_claims, FileState, PendingFiles, WatchFolderConfig, FAILED_DIR,
+    INGESTING_DIR,
+};
+
+// A fresh directory per test, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn config(folder: &Path) -> WatchFolderConfig {
+    for dir in [INGESTING_DIR, FAILED_DIR] {
+        std::fs::create_dir_all(folder.join(dir)).unwrap();
+    }
+    WatchFolderConfig {
+        folder: folder.to_path_buf(),
+        settle: Duration::from_secs(30),
+        poll: false,
+        uploaded_by: None,
+        bucket: "videos".to_string(),
+    }
+}
+
+#[test]
+fn only_visible_video_files_are_candidates() {
+    assert!(is_candidate(Path::new("/nas/drop/Keynote 2019.MP4")));
+    assert!(is_candidate(Path::new("/nas/drop/talk.mkv")));
+    assert!(!is_candidate(Path::new("/nas/drop/notes.txt")));
+    assert!(!is_candidate(Path::new("/nas/drop/.talk.mp4.part")));
+    assert!(!is_candidate(Path::new("/nas/drop/.ingesting")));
+}
+
+#[test]
+fn files_settle_once_they_stop_changing() {
+    let path = Path::new("/nas/drop/talk.mp4");
+    let start = Instant::now();
+    let settle = Duration::from_secs(30);
+    let mut pending = PendingFiles::default();
+
+    pending.observe(path, FileState { size: 100, modified: None }, start);
+    assert!(pending.settled(settle, start + Duration::from_secs(20)).is_empty());
+
+    // Still growing: the wait starts over
+    pending.observe(path, FileState { size: 200, modified: None }, start + Duration::from_secs(20));
+    assert!(pending.settled(settle, start + Duration::from_secs(40)).is_empty());
+
+    pending.observe(path, FileState { size: 200, modified: None }, start + Duration::from_secs(45));
+    assert_eq!(pending.settled(settle, start + Duration::from_secs(50)), vec![path.to_path_buf()]);
+
+    pending.remove(path);
+    assert!(pending.settled(settle, start + Duration::from_secs(60)).is_empty());
+}
+
+#[test]
+fn empty_files_never_settle() {
+    let path = Path::new("/nas/drop/talk.mp4");
+    let start = Instant::now();
+    let mut pending = PendingFiles::default();
+    pending.observe(path, FileState { size: 0, modified: None }, start);
+    assert!(pending.settled(Duration::from_secs(30), start + Duration::from_secs(600)).is_empty());
+}
+
+#[sqlx::test]
+async fn test_dropped_file_becomes_a_video(pool: PgPool) {
+    let folder = TempDir::new("watch-folder-test");
+    let root = TempDir::new("watch-folder-storage");
+    let storage: Storage = Arc::new(LocalStorage::new(&root.0));
+    let config = config(&folder.0);
+
+    let dropped = folder.0.join("Summer_Trip-2024.mp4");
+    std::fs::write(&dropped, b"not really a video").unwrap();
+
+    let video = ingest_file(&pool, storage.as_ref(), None, &config, &dropped).await.unwrap().unwrap();
+    assert_eq!(video.title, "Summer Trip 2024");
+    assert!(video.s3_key.starts_with("videos/") && video.s3_key.ends_with(".mp4"), "{}", video.s3_key);
+    assert_eq!(storage.head("videos", &video.s3_key).await.unwrap().size, 18);
+
+    // The file was moved, not copied, and can't be ingested twice
+    assert!(!dropped.exists());
+    assert_eq!(std::fs::read_dir(folder.0.join(INGESTING_DIR)).unwrap().count(), 0);
+    assert!(ingest_file(&pool, storage.as_ref(), None, &config, &dropped).await.unwrap().is_none());
+
+    let videos: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM videos WHERE s3_key = $1")
+        .bind(&video.s3_key)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(videos, 1);
+}
+
+#[sqlx::test]
+async fn test_dropped_file_raises_video_created(pool: PgPool) {
+    let folder = TempDir::new("watch-folder-test");
+    let root = TempDir::new("watch-folder-storage");
+    let storage: Storage = Arc::new(LocalStorage::new(&root.0));
+    let config = config(&folder.0);
+    let user_id: i32 = sqlx::query_scalar("INSERT INTO users (username, email, password) VALUES ('watcher', 'watcher@example.com', 'x') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let webhook = webhooks::create_webhook(&pool, user_id, "https://example.com/hook", "s", &["video.created".to_string()], true).await.unwrap();
+
+    let dropped = folder.0.join("talk.mp4");
+    std::fs::write(&dropped, b"not really a video").unwrap();
+    let video = ingest_file(&pool, storage.as_ref(), None, &config, &dropped).await.unwrap().unwrap();
+
+    let deliveries = webhooks::list_deliveries(&pool, webhook.id, 10).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, "video.created");
+    assert_eq!(deliveries[0].payload["id"], video.id);
+}
+
+#[test]
+fn claimed_names_keep_the_original_name() {
+    let (id, name) = parse_claimed_name("0f8fad5b-d9cb-469f-a165-70867728950e-Summer Trip.mp4").unwrap();
+    assert_eq!(id.to_string(), "0f8fad5b-d9cb-469f-a165-70867728950e");
+    assert_eq!(name, "Summer Trip.mp4");
+
+    // Claimed before names were kept
+    let (_, name) = parse_claimed_name("0f8fad5b-d9cb-469f-a165-70867728950e.mov").unwrap();
+    assert_eq!(name, "0f8fad5b-d9cb-469f-a165-70867728950e.mov");
+
+    assert!(parse_claimed_name("Summer Trip.mp4").is_none());
+    assert!(parse_claimed_name("0f8fad5b-d9cb-469f-a165-70867728950e-").is_none());
+}
+
+#[sqlx::test]
+async fn test_stale_claims_are_put_back_unless_already_ingested(pool: PgPool) {
+    let folder = TempDir::new("watch-folder-test");
+    let config = config(&folder.0);
+    let ingesting = folder.0.join(INGESTING_DIR);
+
+    let abandoned = uuid::Uuid::new_v4();
+    std::fs::write(ingesting.join(format!("{}-Summer Trip.mp4", abandoned)), b"half done").unwrap();
+    let finished = uuid::Uuid::new_v4();
+    std::fs::write(ingesting.join(format!("{}-talk.MOV", finished)), b"done").unwrap();
+    sqlx::query("INSERT INTO videos (title, s3_key) VALUES ('talk', $1)")
+        .bind(format!("videos/{}.mov", finished))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Claims still being worked on are left alone
+    assert_eq!(recover_claims(&pool, &config.folder, Duration::from_secs(3600)).await.unwrap(), 0);
+    assert_eq!(std::fs::read_dir(&ingesting).unwrap().count(), 2);
+
+    assert_eq!(recover_claims(&pool, &config.folder, Duration::ZERO).await.unwrap(), 1);
+    assert_eq!(std::fs::read(folder.0.join("Summer Trip.mp4")).unwrap(), b"half done");
+    assert_eq!(std::fs::read_dir(&ingesting).unwrap().count(), 0);
+}
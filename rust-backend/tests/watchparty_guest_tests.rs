@@ -0,0 +1,219 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::watch_parties::{self, Participant};
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Guest video")
+        .bind("watch_party_guest_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn post_json(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, uri: &str, token: Option<&str>, body: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let mut req = test::TestRequest::post().uri(uri).set_json(body);
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+async fn get_json(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, uri: &str, token: &str) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[sqlx::test]
+async fn test_guest_joins_party_under_display_name(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "guest_party_host").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (status, _) = post_json(&app, "/api/watchparty/guest", None, json!({ "displayName": "   " })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let (status, created) = post_json(&app, "/api/watchparty/guest", None, json!({ "displayName": " Popcorn " })).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    assert_eq!(created["guest"]["displayName"], "Popcorn");
+    let guest_id = created["guest"]["id"].as_i64().unwrap() as i32;
+    assert_eq!(created["guest"]["participantId"], -guest_id);
+
+    // A guest token is no login token
+    let guest_token = created["guestToken"].as_str().unwrap();
+    let (status, _) = get_json(&app, "/api/users/me/watchparties", guest_token).await;
+    assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+
+    watch_parties::record_join(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    let session = watch_parties::record_join(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+    assert_eq!(session.peak_participants, 2);
+
+    let (status, guests) = get_json(&app, &format!("/api/watchparty/{}/guests", video_id), &host_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(guests, json!([{ "participantId": -guest_id, "displayName": "Popcorn" }]));
+
+    // The session runs until the guest leaves too
+    watch_parties::record_leave(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    let (_, history) = get_json(&app, "/api/users/me/watchparties", &host_token).await;
+    assert!(history[0]["ended_at"].is_null());
+    assert_eq!(history[0]["participant_count"], 2);
+
+    watch_parties::record_leave(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+    let (_, history) = get_json(&app, "/api/users/me/watchparties", &host_token).await;
+    assert!(!history[0]["ended_at"].is_null());
+    let (_, guests) = get_json(&app, &format!("/api/watchparty/{}/guests", video_id), &host_token).await;
+    assert_eq!(guests, json!([]));
+}
+
+#[sqlx::test]
+async fn test_claiming_guest_merges_history(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, _) = register_test_user(&app, "claim_host").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (_, created) = post_json(&app, "/api/watchparty/guest", None, json!({ "displayName": "Lurker" })).await;
+    let guest_id = created["guest"]["id"].as_i64().unwrap() as i32;
+    let guest_token = created["guestToken"].as_str().unwrap().to_string();
+
+    // One party as a guest that has ended, and one still running
+    watch_parties::record_join(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+    watch_parties::record_leave(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+    let running = watch_parties::record_join(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+
+    let (user_id, user_token) = register_test_user(&app, "claim_user").await;
+    let (status, _) = post_json(&app, "/api/watchparty/guest/claim", None, json!({ "guestToken": guest_token })).await;
+    assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+    let (status, _) = post_json(&app, "/api/watchparty/guest/claim", Some(&user_token), json!({ "guestToken": "not-a-token" })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let (status, claimed) = post_json(&app, "/api/watchparty/guest/claim", Some(&user_token), json!({ "guestToken": guest_token })).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(claimed["mergedSessions"], 2);
+
+    let (_, history) = get_json(&app, "/api/users/me/watchparties", &user_token).await;
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    assert_eq!(history[0]["session_id"], running.id);
+    assert!(history[0]["ended_at"].is_null());
+    assert!(watch_parties::active_guest(&pool, guest_id).await.unwrap().is_none());
+
+    // The guest's open connection now counts as the account's, and closing it ends the party
+    watch_parties::record_leave(&pool, video_id, Participant::Guest(guest_id)).await.unwrap();
+    let (_, history) = get_json(&app, "/api/users/me/watchparties", &user_token).await;
+    assert!(!history[0]["ended_at"].is_null());
+
+    let (status, _) = post_json(&app, "/api/watchparty/guest/claim", Some(&user_token), json!({ "guestToken": guest_token })).await;
+    assert_eq!(status, http::StatusCode::CONFLICT);
+    let participations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM watch_party_participants WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(participations, 2);
+}
+
+#[sqlx::test]
+async fn test_guest_identities_are_throttled_per_ip(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let request_guest = |ip: &'static str| {
+        test::TestRequest::post()
+            .uri("/api/watchparty/guest")
+            .peer_addr(format!("{}:5000", ip).parse().unwrap())
+            .set_json(json!({ "displayName": "Crowd" }))
+            .to_request()
+    };
+
+    // Loop past the limit rather than stopping at it, in case the hour turns over mid-test
+    let mut refused = None;
+    for _ in 0..=2 * watch_parties::guests_per_hour() {
+        let resp = test::call_service(&app, request_guest("198.51.100.23")).await;
+        if resp.status() == http::StatusCode::TOO_MANY_REQUESTS {
+            refused = Some(resp);
+            break;
+        }
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+    }
+    let refused = refused.expect("guest identities were never throttled");
+    assert!(refused.headers().contains_key("retry-after"));
+
+    // Other IPs aren't affected
+    let resp = test::call_service(&app, request_guest("198.51.100.24")).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+}
@@ -0,0 +1,254 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Party video")
+        .bind("watch_party_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn invite(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, token: &str, body: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/watchparty/{}/invite", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+async fn join(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, video_id: i32, token: &str, invite: Option<&str>) -> http::StatusCode {
+    let uri = match invite {
+        Some(invite) => format!("/api/watchparty/{}/join?invite={}", video_id, invite),
+        None => format!("/api/watchparty/{}/join", video_id),
+    };
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    test::call_service(app, req).await.status()
+}
+
+#[sqlx::test]
+async fn test_invited_user_is_notified_and_can_join(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "party_host").await;
+    let (guest_id, guest_token) = register_test_user(&app, "party_guest").await;
+    let (_, outsider_token) = register_test_user(&app, "party_outsider").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (status, body) = invite(&app, video_id, &host_token, json!({ "userIds": [guest_id], "inviteOnly": true })).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    assert_eq!(body["inviteOnly"], true);
+    assert_eq!(body["invites"][0]["userId"], guest_id);
+
+    let kind: String = sqlx::query_scalar("SELECT kind FROM notifications WHERE user_id = $1")
+        .bind(guest_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(kind, "watch_party_invite");
+
+    assert_eq!(join(&app, video_id, &host_token, None).await, http::StatusCode::OK);
+    assert_eq!(join(&app, video_id, &guest_token, None).await, http::StatusCode::OK);
+    assert_eq!(join(&app, video_id, &outsider_token, None).await, http::StatusCode::FORBIDDEN);
+
+    // Only the host can invite more people
+    let (status, _) = invite(&app, video_id, &guest_token, json!({})).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_invite_link_grants_access_until_it_expires(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "link_host").await;
+    let (_, guest_token) = register_test_user(&app, "link_guest").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (status, body) = invite(&app, video_id, &host_token, json!({ "inviteOnly": true, "expiresInHours": 2 })).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    let link = body["token"].as_str().unwrap().to_string();
+    assert_eq!(body["inviteUrl"], format!("/video/{}?invite={}", video_id, link));
+
+    assert_eq!(join(&app, video_id, &guest_token, Some(&link)).await, http::StatusCode::OK);
+    assert_eq!(join(&app, video_id, &guest_token, Some("not-a-real-invite")).await, http::StatusCode::FORBIDDEN);
+
+    sqlx::query("UPDATE watch_party_invites SET expires_at = NOW() - INTERVAL '1 hour' WHERE token = $1")
+        .bind(&link)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(join(&app, video_id, &guest_token, Some(&link)).await, http::StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn test_invite_validates_expiry_and_video(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "expiry_host").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (status, _) = invite(&app, video_id, &host_token, json!({ "expiresInHours": 0 })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+
+    let (status, _) = invite(&app, video_id + 1000, &host_token, json!({})).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+
+    // Rooms stay open to everyone unless the host makes them invite-only
+    let (_, other_token) = register_test_user(&app, "expiry_other").await;
+    let (status, _) = invite(&app, video_id, &host_token, json!({})).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    assert_eq!(join(&app, video_id, &other_token, None).await, http::StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn test_rtc_config_requires_login_and_lists_ice_servers(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, token) = register_test_user(&app, "rtc_user").await;
+
+    let req = test::TestRequest::get().uri("/api/watchparty/rtc-config").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri("/api/watchparty/rtc-config")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(!body["iceServers"][0]["urls"].as_array().unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn test_only_the_uploader_hosts_a_new_room(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (uploader_id, uploader_token) = register_test_user(&app, "room_uploader").await;
+    let (viewer_id, viewer_token) = register_test_user(&app, "room_viewer").await;
+    let video_id = insert_video(&pool, uploader_id).await;
+
+    // Getting there first doesn't make a viewer the host
+    let (status, _) = invite(&app, video_id, &viewer_token, json!({ "inviteOnly": true })).await;
+    assert_eq!(status, http::StatusCode::FORBIDDEN);
+    let host: i32 = sqlx::query_scalar("SELECT host_user_id FROM watch_parties WHERE video_id = $1")
+        .bind(video_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(host, uploader_id);
+
+    // Repeated ids get one invite, and there's a cap on how many go out at once
+    let (status, body) = invite(&app, video_id, &uploader_token, json!({ "userIds": [viewer_id, viewer_id] })).await;
+    assert_eq!(status, http::StatusCode::CREATED);
+    assert_eq!(body["invites"].as_array().unwrap().len(), 1);
+    let too_many: Vec<i32> = (1..=51).collect();
+    let (status, _) = invite(&app, video_id, &uploader_token, json!({ "userIds": too_many })).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn test_invite_only_rooms_are_hidden_from_outsiders(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "hidden_host").await;
+    let (_, outsider_token) = register_test_user(&app, "hidden_outsider").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (_, body) = invite(&app, video_id, &host_token, json!({ "inviteOnly": true })).await;
+    let link = body["token"].as_str().unwrap().to_string();
+
+    let get = |uri: String, token: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(&uri);
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        req.to_request()
+    };
+    let room = format!("/api/watchparty/{}", video_id);
+    assert_eq!(test::call_service(&app, get(room.clone(), Some(&host_token))).await.status(), http::StatusCode::OK);
+    assert_eq!(test::call_service(&app, get(room.clone(), None)).await.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(test::call_service(&app, get(room.clone(), Some(&outsider_token))).await.status(), http::StatusCode::NOT_FOUND);
+    let calendar = format!("/api/watchparty/{}/calendar.ics", video_id);
+    assert_eq!(test::call_service(&app, get(calendar, None)).await.status(), http::StatusCode::NOT_FOUND);
+    let with_link = format!("/api/watchparty/{}?invite={}", video_id, link);
+    assert_eq!(test::call_service(&app, get(with_link, Some(&outsider_token))).await.status(), http::StatusCode::OK);
+
+    // Open rooms are hidden too while their video is held for moderation
+    sqlx::query("UPDATE watch_parties SET invite_only = FALSE WHERE video_id = $1").bind(video_id).execute(&pool).await.unwrap();
+    assert_eq!(test::call_service(&app, get(room.clone(), None)).await.status(), http::StatusCode::OK);
+    sqlx::query("UPDATE videos SET moderation_status = 'pending' WHERE id = $1").bind(video_id).execute(&pool).await.unwrap();
+    assert_eq!(test::call_service(&app, get(room.clone(), None)).await.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(test::call_service(&app, get(room, Some(&host_token))).await.status(), http::StatusCode::OK);
+}
@@ -0,0 +1,154 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::watch_parties::{self, Participant};
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn insert_video(pool: &PgPool, user_id: i32) -> i32 {
+    sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind("Session video")
+        .bind("watch_party_session_key_1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn get_json(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, uri: &str, token: &str) -> (http::StatusCode, serde_json::Value) {
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[sqlx::test]
+async fn test_session_tracks_participants_and_peak(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "session_host").await;
+    let (guest_id, guest_token) = register_test_user(&app, "session_guest").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let session = watch_parties::record_join(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    assert_eq!(session.host_user_id, Some(host_id));
+    // A second tab from the same user doesn't count as another participant
+    watch_parties::record_join(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    let session = watch_parties::record_join(&pool, video_id, Participant::User(guest_id)).await.unwrap();
+    assert_eq!(session.peak_participants, 2);
+
+    watch_parties::record_leave(&pool, video_id, Participant::User(guest_id)).await.unwrap();
+    watch_parties::record_leave(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    let (_, history) = get_json(&app, "/api/users/me/watchparties", &host_token).await;
+    assert!(history[0]["ended_at"].is_null(), "host still has a tab open");
+
+    watch_parties::record_leave(&pool, video_id, Participant::User(host_id)).await.unwrap();
+    let (status, history) = get_json(&app, "/api/users/me/watchparties", &guest_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(history.as_array().unwrap().len(), 1);
+    assert_eq!(history[0]["session_id"], session.id);
+    assert_eq!(history[0]["video_title"], "Session video");
+    assert_eq!(history[0]["participant_count"], 2);
+    assert_eq!(history[0]["peak_participants"], 2);
+    assert!(!history[0]["ended_at"].is_null());
+
+    // The next join starts a new session
+    let next = watch_parties::record_join(&pool, video_id, Participant::User(guest_id)).await.unwrap();
+    assert_ne!(next.id, session.id);
+    assert_eq!(next.host_user_id, Some(guest_id));
+}
+
+#[sqlx::test]
+async fn test_video_watch_party_stats(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (host_id, host_token) = register_test_user(&app, "stats_host").await;
+    let (guest_id, _) = register_test_user(&app, "stats_guest").await;
+    let video_id = insert_video(&pool, host_id).await;
+
+    let (status, stats) = get_json(&app, &format!("/api/videos/{}/watchparty-stats", video_id), &host_token).await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(stats["party_count"], 0);
+    assert!(stats["last_party_at"].is_null());
+
+    for _ in 0..2 {
+        watch_parties::record_join(&pool, video_id, Participant::User(host_id)).await.unwrap();
+        watch_parties::record_join(&pool, video_id, Participant::User(guest_id)).await.unwrap();
+        watch_parties::record_leave(&pool, video_id, Participant::User(host_id)).await.unwrap();
+        watch_parties::record_leave(&pool, video_id, Participant::User(guest_id)).await.unwrap();
+    }
+
+    let (_, stats) = get_json(&app, &format!("/api/videos/{}/watchparty-stats", video_id), &host_token).await;
+    assert_eq!(stats["video_id"], video_id);
+    assert_eq!(stats["party_count"], 2);
+    assert_eq!(stats["participant_count"], 2);
+    assert_eq!(stats["peak_participants"], 2);
+    assert!(!stats["last_party_at"].is_null());
+
+    let (status, _) = get_json(&app, &format!("/api/videos/{}/watchparty-stats", video_id + 1000), &host_token).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+}
@@ -16,6 +16,7 @@ use std::time::Duration as StdDuration;
 use video_streaming_backend::handlers;
 use video_streaming_backend::AppState;
 use video_streaming_backend::services;
+use video_streaming_backend::storage;
 use video_streaming_backend::models::{RegisterRequest, Claims};
 use video_streaming_backend::websocket;
 
@@ -33,12 +34,12 @@ async fn setup_test_app() -> (
     
     // Initialize the database pool and S3 client
     let db_pool = services::init_db_pool().await;
-    let s3_client = services::init_s3_client().await;
+    let storage = storage::init_storage().await;
     
     // Create the app state
     let app_state = Arc::new(Mutex::new(AppState {
         db_pool,
-        s3_client,
+        storage,
         redis_client: None, // No Redis client in tests
         job_queue: None, // No job queue in tests
         video_clients: std::sync::Mutex::new(HashMap::new()),
@@ -100,6 +101,8 @@ fn create_jwt_token(user_id: i32) -> String {
     let claims = Claims {
         user_id,
         exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
+        sid: None,
+        jti: None,
     };
     encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret.as_ref())).unwrap()
 }
@@ -568,3 +571,389 @@ async fn test_watchparty_websocket_communication() {
     // Test passed if we got this far
     println!("WebSocket communication test completed");
 }
+
+// Run the app, including WebSocket routes, on a real port for WebSocket clients
+async fn spawn_test_server(app_state: Arc<Mutex<AppState>>, test_port: u16) {
+    let (tx, rx) = oneshot::channel::<()>();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build runtime");
+    std::thread::spawn(move || {
+        rt.block_on(async {
+            let server = actix_web::HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(app_state.clone()))
+                    .configure(handlers::configure_routes)
+                    .configure(websocket::configure_ws_routes)
+            })
+            .bind(format!("127.0.0.1:{}", test_port)).expect("Failed to bind to test port")
+            .run();
+            let _ = tx.send(());
+            server.await.expect("Server error");
+        });
+    });
+    let _ = rx.await;
+    sleep(Duration::from_secs(1)).await;
+}
+
+// Read text messages from a WebSocket until nothing arrives for a short while
+async fn collect_text_messages<S>(read: &mut S) -> Vec<serde_json::Value>
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let mut messages = Vec::new();
+    while let Ok(Some(Ok(msg))) = timeout(StdDuration::from_secs(1), read.next()).await {
+        if let Message::Text(text) = msg {
+            messages.push(serde_json::from_str(&text).unwrap());
+        }
+    }
+    messages
+}
+
+#[actix_web::test]
+async fn test_watchparty_reactions_are_broadcast_and_rate_limited() {
+    let (app, app_state) = setup_test_app().await;
+    let (user_id1, _) = register_test_user(&app).await;
+    let (user_id2, _) = register_test_user(&app).await;
+    let token1 = create_jwt_token(user_id1);
+    let token2 = create_jwt_token(user_id2);
+    let video_id = 12346;
+    let test_port = 8766;
+
+    spawn_test_server(app_state, test_port).await;
+
+    // Both clients authenticate through the query string
+    let ws_url = |token: &str| format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, token);
+    let (client1_ws_stream, _) = connect_async(ws_url(&token1)).await.expect("Failed to connect client 1 to WebSocket");
+    let (mut client1_write, mut client1_read) = client1_ws_stream.split();
+    let (client2_ws_stream, _) = connect_async(ws_url(&token2)).await.expect("Failed to connect client 2 to WebSocket");
+    let (mut client2_write, mut client2_read) = client2_ws_stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    // The first five reactions in the window go through, the rest are rejected
+    for _ in 0..7 {
+        let reaction = json!({ "type": "reaction", "emoji": "🎉", "time": 12.5 }).to_string();
+        client1_write.send(Message::Text(reaction)).await.unwrap();
+    }
+    let invalid = json!({ "type": "reaction", "emoji": "not an emoji" }).to_string();
+    client1_write.send(Message::Text(invalid)).await.unwrap();
+
+    let received = collect_text_messages(&mut client2_read).await;
+    let reactions: Vec<_> = received.iter().filter(|m| m["type_field"] == "watchPartyReaction").collect();
+    assert_eq!(reactions.len(), 5);
+    assert_eq!(reactions[0]["emoji"], "🎉");
+    assert_eq!(reactions[0]["user_id"], user_id1);
+    assert_eq!(reactions[0]["time"], 12.5);
+
+    let sender_messages = collect_text_messages(&mut client1_read).await;
+    let errors: Vec<_> = sender_messages.iter().filter(|m| m["type_field"] == "watchPartyError").collect();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0]["error"], "Too many reactions, slow down");
+    assert_eq!(errors[2]["error"], "Invalid reaction");
+
+    let _ = client1_write.send(Message::Close(None)).await;
+    let _ = client2_write.send(Message::Close(None)).await;
+}
+
+#[actix_web::test]
+async fn test_watchparty_signals_reach_only_their_target() {
+    let (app, app_state) = setup_test_app().await;
+    let mut tokens = Vec::new();
+    let mut user_ids = Vec::new();
+    for _ in 0..3 {
+        let (user_id, _) = register_test_user(&app).await;
+        user_ids.push(user_id);
+        tokens.push(create_jwt_token(user_id));
+    }
+    let video_id = 12347;
+    let test_port = 8767;
+    spawn_test_server(app_state, test_port).await;
+
+    let mut writers = Vec::new();
+    let mut readers = Vec::new();
+    for token in &tokens {
+        let url = format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, token);
+        let (stream, _) = connect_async(url).await.expect("Failed to connect to WebSocket");
+        let (write, read) = stream.split();
+        writers.push(write);
+        readers.push(read);
+    }
+    sleep(Duration::from_millis(500)).await;
+
+    // A join announcement goes to everyone, an offer only to the peer it names
+    writers[0].send(Message::Text(json!({ "type": "signal", "signal": "join" }).to_string())).await.unwrap();
+    let offer = json!({ "type": "signal", "signal": "offer", "to": user_ids[1], "payload": { "type": "offer", "sdp": "v=0" } });
+    writers[0].send(Message::Text(offer.to_string())).await.unwrap();
+    writers[0].send(Message::Text(json!({ "type": "signal", "signal": "answer" }).to_string())).await.unwrap();
+
+    let signals = |messages: &[serde_json::Value]| -> Vec<String> {
+        messages.iter()
+            .filter(|m| m["type_field"] == "watchPartySignal")
+            .map(|m| m["action"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let peer = collect_text_messages(&mut readers[1]).await;
+    assert_eq!(signals(&peer), vec!["join", "offer"]);
+    let offer = peer.iter().find(|m| m["action"] == "offer").unwrap();
+    assert_eq!(offer["user_id"], user_ids[0]);
+    assert_eq!(offer["payload"]["sdp"], "v=0");
+
+    let bystander = collect_text_messages(&mut readers[2]).await;
+    assert_eq!(signals(&bystander), vec!["join"]);
+
+    let sender = collect_text_messages(&mut readers[0]).await;
+    assert!(sender.iter().any(|m| m["error"] == "Signal is missing a target peer"));
+
+    for mut write in writers {
+        let _ = write.send(Message::Close(None)).await;
+    }
+}
+
+#[actix_web::test]
+async fn test_watchparty_msgpack_clients_exchange_binary_frames() {
+    let (app, app_state) = setup_test_app().await;
+    let (user_id1, _) = register_test_user(&app).await;
+    let (user_id2, _) = register_test_user(&app).await;
+    let video_id = 12348;
+    let test_port = 8768;
+    spawn_test_server(app_state, test_port).await;
+
+    let ws_url = |user_id: i32| format!(
+        "ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}&format=msgpack",
+        test_port, video_id, create_jwt_token(user_id)
+    );
+    let (client1_ws_stream, _) = connect_async(ws_url(user_id1)).await.expect("Failed to connect client 1 to WebSocket");
+    let (mut client1_write, mut client1_read) = client1_ws_stream.split();
+    let (client2_ws_stream, _) = connect_async(ws_url(user_id2)).await.expect("Failed to connect client 2 to WebSocket");
+    let (mut client2_write, mut client2_read) = client2_ws_stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    let control = rmp_serde::to_vec_named(&json!({ "action": "seek", "time": 42.0 })).unwrap();
+    client1_write.send(Message::Binary(control)).await.unwrap();
+
+    let decode = |msg: Message| -> serde_json::Value {
+        match msg {
+            Message::Binary(bytes) => rmp_serde::from_slice(&bytes).unwrap(),
+            other => panic!("Expected a binary frame, got {:?}", other),
+        }
+    };
+
+    // The sender gets its echo and the other participant the broadcast, both MessagePack-encoded
+    let echo = timeout(StdDuration::from_secs(2), client1_read.next()).await.unwrap().unwrap().unwrap();
+    let echo = decode(echo);
+    assert_eq!(echo["type_field"], "watchPartyControl");
+    assert_eq!(echo["user_id"], user_id1);
+
+    let broadcast = timeout(StdDuration::from_secs(2), client2_read.next()).await.unwrap().unwrap().unwrap();
+    let broadcast = decode(broadcast);
+    assert_eq!(broadcast["action"], "seek");
+    assert_eq!(broadcast["time"], 42.0);
+
+    let _ = client1_write.send(Message::Close(None)).await;
+    let _ = client2_write.send(Message::Close(None)).await;
+}
+
+#[actix_web::test]
+async fn test_comments_are_broadcast_to_the_watch_party() {
+    let (app, app_state) = setup_test_app().await;
+    let (user_id, token) = register_test_user(&app).await;
+    let db_pool = app_state.lock().await.db_pool.clone();
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key) VALUES ('Party video', $1) RETURNING id")
+        .bind(format!("videos/{}.mp4", uuid::Uuid::new_v4()))
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    let test_port = 8769;
+    spawn_test_server(app_state, test_port).await;
+
+    let url = format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, create_jwt_token(user_id));
+    let (stream, _) = connect_async(url).await.expect("Failed to connect to WebSocket");
+    let (mut write, mut read) = stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/comments/{}", video_id))
+        .insert_header((actix_web::http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "text": "Look at this part", "videoTime": 95 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let messages = collect_text_messages(&mut read).await;
+    let comment = messages.iter().find(|m| m["type_field"] == "comment").expect("No comment message received");
+    assert_eq!(comment["video_id"], video_id);
+    assert_eq!(comment["user_id"], user_id);
+    assert_eq!(comment["time"], 95.0);
+    assert_eq!(comment["payload"]["content"], "Look at this part");
+
+    let _ = write.send(Message::Close(None)).await;
+}
+
+#[actix_web::test]
+async fn test_watchparty_typing_indicators_are_relayed_and_throttled() {
+    let (app, app_state) = setup_test_app().await;
+    let (user_id1, _) = register_test_user(&app).await;
+    let (user_id2, _) = register_test_user(&app).await;
+    let video_id = 12349;
+    let test_port = 8770;
+    spawn_test_server(app_state, test_port).await;
+
+    let ws_url = |user_id: i32| format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, create_jwt_token(user_id));
+    let (client1_ws_stream, _) = connect_async(ws_url(user_id1)).await.expect("Failed to connect client 1 to WebSocket");
+    let (mut client1_write, _client1_read) = client1_ws_stream.split();
+    let (client2_ws_stream, _) = connect_async(ws_url(user_id2)).await.expect("Failed to connect client 2 to WebSocket");
+    let (mut client2_write, mut client2_read) = client2_ws_stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    // Repeats while typing are held back, stopping goes straight through
+    for typing in [true, true, true, false] {
+        let message = json!({ "type": "typing", "typing": typing }).to_string();
+        client1_write.send(Message::Text(message)).await.unwrap();
+    }
+
+    let received = collect_text_messages(&mut client2_read).await;
+    let typing: Vec<_> = received.iter().filter(|m| m["type_field"] == "watchPartyTyping").collect();
+    assert_eq!(typing.len(), 2);
+    assert_eq!(typing[0]["action"], "start");
+    assert_eq!(typing[0]["user_id"], user_id1);
+    assert_eq!(typing[1]["action"], "stop");
+
+    let _ = client1_write.send(Message::Close(None)).await;
+    let _ = client2_write.send(Message::Close(None)).await;
+}
+
+#[actix_web::test]
+async fn test_scheduled_watch_party_locks_playback_until_it_starts() {
+    let (app, app_state) = setup_test_app().await;
+    let (host_id, host_token) = register_test_user(&app).await;
+    let db_pool = app_state.lock().await.db_pool.clone();
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key, duration, uploaded_by) VALUES ('Premiere night', $1, 5400, $2) RETURNING id")
+        .bind(format!("videos/{}.mp4", uuid::Uuid::new_v4()))
+        .bind(host_id)
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+
+    let schedule = |start: serde_json::Value| test::TestRequest::post()
+        .uri(&format!("/api/watchparty/{}/schedule", video_id))
+        .insert_header((actix_web::http::header::AUTHORIZATION, format!("Bearer {}", host_token)))
+        .set_json(json!({ "scheduledStart": start }))
+        .to_request();
+
+    let past = (chrono::Utc::now() - chrono::Duration::minutes(5)).to_rfc3339();
+    let resp = test::call_service(&app, schedule(json!(past))).await;
+    assert_eq!(resp.status(), 400);
+
+    let start = chrono::Utc::now() + chrono::Duration::hours(2);
+    let resp = test::call_service(&app, schedule(json!(start.to_rfc3339()))).await;
+    assert!(resp.status().is_success());
+    let room: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(room["hostUserId"], host_id);
+    assert_eq!(room["locked"], true);
+    assert_eq!(room["calendar"]["title"], "Watch party: Premiere night");
+    assert!(room["calendar"]["url"].as_str().unwrap().ends_with(&format!("/video/{}", video_id)));
+
+    let req = test::TestRequest::get().uri(&format!("/api/watchparty/{}/calendar.ics", video_id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let ics = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.contains(&format!("DTSTART:{}\r\n", start.format("%Y%m%dT%H%M%SZ"))));
+
+    let test_port = 8772;
+    spawn_test_server(app_state.clone(), test_port).await;
+    let url = format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, create_jwt_token(host_id));
+    let (stream, _) = connect_async(url).await.expect("Failed to connect to WebSocket");
+    let (mut write, mut read) = stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    // Joining shows the countdown, and even the host can't start playback early
+    write.send(Message::Text(json!({ "action": "play", "time": 0.0 }).to_string())).await.unwrap();
+    let messages = collect_text_messages(&mut read).await;
+    let countdown = messages.iter().find(|m| m["type_field"] == "watchPartyCountdown").expect("No countdown received");
+    assert!(countdown["payload"]["secondsRemaining"].as_i64().unwrap() > 7000);
+    assert!(messages.iter().any(|m| m["type_field"] == "watchPartyError" && m["error"] == "This watch party hasn't started yet"));
+    assert!(!messages.iter().any(|m| m["type_field"] == "watchPartyControl"));
+
+    // Once the start comes, the scheduler plays the video for the room exactly once
+    sqlx::query("UPDATE watch_parties SET scheduled_start = NOW() AT TIME ZONE 'UTC' - INTERVAL '1 second' WHERE video_id = $1")
+        .bind(video_id)
+        .execute(&db_pool)
+        .await
+        .unwrap();
+    websocket::tick_scheduled_starts(&app_state).await;
+    websocket::tick_scheduled_starts(&app_state).await;
+    let messages = collect_text_messages(&mut read).await;
+    let plays: Vec<_> = messages.iter().filter(|m| m["type_field"] == "watchPartyControl").collect();
+    assert_eq!(plays.len(), 1);
+    assert_eq!(plays[0]["action"], "play");
+    assert_eq!(plays[0]["time"], 0.0);
+    assert_eq!(plays[0]["user_id"], host_id);
+
+    let _ = write.send(Message::Close(None)).await;
+}
+
+#[actix_web::test]
+async fn test_watchparty_polls_are_tallied_and_saved_to_history() {
+    let (app, app_state) = setup_test_app().await;
+    let (host_id, host_token) = register_test_user(&app).await;
+    let (guest_id, _) = register_test_user(&app).await;
+    let db_pool = app_state.lock().await.db_pool.clone();
+    let video_id: i32 = sqlx::query_scalar("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ('Poll night', $1, $2) RETURNING id")
+        .bind(format!("videos/{}.mp4", uuid::Uuid::new_v4()))
+        .bind(host_id)
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    let test_port = 8773;
+    spawn_test_server(app_state, test_port).await;
+
+    let ws_url = |user_id: i32| format!("ws://127.0.0.1:{}/api/ws/watchparty/{}?token={}", test_port, video_id, create_jwt_token(user_id));
+    let (host_stream, _) = connect_async(ws_url(host_id)).await.expect("Failed to connect host to WebSocket");
+    let (mut host_write, mut host_read) = host_stream.split();
+    let (guest_stream, _) = connect_async(ws_url(guest_id)).await.expect("Failed to connect guest to WebSocket");
+    let (mut guest_write, mut guest_read) = guest_stream.split();
+    sleep(Duration::from_millis(500)).await;
+
+    let create = json!({ "type": "poll", "action": "create", "question": "Sequel?", "options": ["Yes", "No"], "persist": true });
+    host_write.send(Message::Text(create.to_string())).await.unwrap();
+    let messages = collect_text_messages(&mut guest_read).await;
+    let created = messages.iter().find(|m| m["type_field"] == "watchPartyPoll" && m["action"] == "create").expect("No poll received");
+    let poll_id = created["payload"]["id"].as_str().unwrap().to_string();
+    assert_eq!(created["payload"]["options"], json!(["Yes", "No"]));
+
+    // Only the host may start polls, and votes can be changed
+    let guest_poll = json!({ "type": "poll", "action": "create", "question": "Mine?", "options": ["A", "B"] });
+    guest_write.send(Message::Text(guest_poll.to_string())).await.unwrap();
+    for option in [1, 0] {
+        let vote = json!({ "type": "poll", "action": "vote", "pollId": poll_id, "option": option });
+        guest_write.send(Message::Text(vote.to_string())).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+    }
+    host_write.send(Message::Text(json!({ "type": "poll", "action": "vote", "pollId": poll_id, "option": 0 }).to_string())).await.unwrap();
+    let messages = collect_text_messages(&mut guest_read).await;
+    assert!(messages.iter().any(|m| m["type_field"] == "watchPartyError" && m["error"] == "Only the host can do that"));
+    let results: Vec<_> = messages.iter().filter(|m| m["action"] == "results").collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[2]["payload"]["counts"], json!([2, 0]));
+
+    host_write.send(Message::Text(json!({ "type": "poll", "action": "close", "pollId": poll_id }).to_string())).await.unwrap();
+    let messages = collect_text_messages(&mut host_read).await;
+    let closed = messages.iter().find(|m| m["action"] == "closed").expect("No final results received");
+    assert_eq!(closed["payload"]["closed"], true);
+    assert_eq!(closed["payload"]["total_votes"], 2);
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/me/watchparties")
+        .insert_header((actix_web::http::header::AUTHORIZATION, format!("Bearer {}", host_token)))
+        .to_request();
+    let history: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let session = history.as_array().unwrap().iter().find(|s| s["video_id"] == video_id).expect("No session in history");
+    assert_eq!(session["polls"][0]["question"], "Sequel?");
+    assert_eq!(session["polls"][0]["counts"], json!([2, 0]));
+
+    let _ = host_write.send(Message::Close(None)).await;
+    let _ = guest_write.send(Message::Close(None)).await;
+}
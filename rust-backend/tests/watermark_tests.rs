@@ -0,0 +1,90 @@
+use serde_json::json;
+use sqlx::PgPool;
+
+use video_streaming_backend::models::{Video, WatermarkPosition, WatermarkSettings};
+use video_streaming_backend::watermarks::{self, overlay_filter, png_dimensions, validate_image, validate_settings, MAX_WATERMARK_BYTES};
+
+// PNG signature and an IHDR chunk header, which is as far as validation reads
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0, 0, 13];
+    bytes.extend_from_slice(b"IHDR");
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+    bytes
+}
+
+fn settings(position: WatermarkPosition, opacity: f64) -> WatermarkSettings {
+    WatermarkSettings { enabled: true, position, opacity }
+}
+
+#[test]
+fn only_small_png_images_are_accepted() {
+    assert_eq!(png_dimensions(&png(200, 80)), Some((200, 80)));
+    assert!(validate_image(&png(200, 80)).is_ok());
+
+    assert!(validate_image(b"GIF89a not a png at all").is_err());
+    assert!(validate_image(&png(0, 80)).is_err());
+    assert!(validate_image(&png(4096, 80)).is_err());
+
+    let mut too_big = png(200, 80);
+    too_big.resize(MAX_WATERMARK_BYTES + 1, 0);
+    assert!(validate_image(&too_big).is_err());
+}
+
+#[test]
+fn opacity_must_be_visible() {
+    assert!(validate_settings(&WatermarkSettings::default()).is_ok());
+    assert!(validate_settings(&settings(WatermarkPosition::TopLeft, 0.0)).is_err());
+    assert!(validate_settings(&settings(WatermarkPosition::TopLeft, 1.5)).is_err());
+}
+
+#[test]
+fn overlay_scales_with_the_rendition() {
+    assert_eq!(
+        overlay_filter(720, &settings(WatermarkPosition::BottomRight, 0.8)),
+        "[0:v]scale=-2:720[video];[1:v]scale=-1:72,format=rgba,colorchannelmixer=aa=0.80[watermark];[video][watermark]overlay=W-w-22:H-h-22[out]"
+    );
+    assert!(overlay_filter(480, &settings(WatermarkPosition::TopLeft, 0.5)).ends_with("scale=-1:48,format=rgba,colorchannelmixer=aa=0.50[watermark];[video][watermark]overlay=14:14[out]"));
+    assert!(overlay_filter(480, &settings(WatermarkPosition::TopRight, 1.0)).ends_with("overlay=W-w-14:14[out]"));
+    assert!(overlay_filter(480, &settings(WatermarkPosition::BottomLeft, 1.0)).ends_with("overlay=14:H-h-14[out]"));
+}
+
+#[sqlx::test]
+async fn test_watermark_applies_only_when_enabled(pool: PgPool) {
+    let user_id: i32 = sqlx::query_scalar("INSERT INTO users (username, email, password) VALUES ('brand', 'brand@example.com', 'x') RETURNING id")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let video = sqlx::query_as::<_, Video>("INSERT INTO videos (title, s3_key, uploaded_by) VALUES ('branded', 'videos/branded.mp4', $1) RETURNING *")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    // Enabled without an image: nothing to overlay
+    sqlx::query("UPDATE users SET settings = $1 WHERE id = $2")
+        .bind(json!({ "watermark": { "enabled": true, "position": "topLeft", "opacity": 0.5 } }))
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(watermarks::for_video(&pool, &video).await.unwrap(), None);
+
+    assert_eq!(watermarks::set_watermark(&pool, user_id, "watermarks/1/a.png").await.unwrap(), None);
+    assert_eq!(watermarks::set_watermark(&pool, user_id, "watermarks/1/b.png").await.unwrap(), Some("watermarks/1/a.png".to_string()));
+    assert_eq!(
+        watermarks::for_video(&pool, &video).await.unwrap(),
+        Some(("watermarks/1/b.png".to_string(), settings(WatermarkPosition::TopLeft, 0.5)))
+    );
+
+    sqlx::query("UPDATE users SET settings = '{}' WHERE id = $1")
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(watermarks::for_video(&pool, &video).await.unwrap(), None);
+
+    assert_eq!(watermarks::remove_watermark(&pool, user_id).await.unwrap(), Some("watermarks/1/b.png".to_string()));
+    assert_eq!(watermarks::watermark_key(&pool, user_id).await.unwrap(), None);
+}
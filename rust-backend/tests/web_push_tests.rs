@@ -0,0 +1,139 @@
+use base64::Engine;
+use chrono::NaiveDate;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+use openssl::nid::Nid;
+use serde_json::json;
+
+use video_streaming_backend::models::{Notification, PushSubscriptionKeys, PushSubscriptionRequest};
+use video_streaming_backend::web_push::{audience, encrypt_with, is_public_ip, message_for, resolve_endpoint, validate_subscription, Vapid};
+
+// The example keys of RFC 8291, appendix A
+const SENDER_PUBLIC: &str = "BP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A8";
+const SENDER_PRIVATE: &str = "yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw";
+const UA_PUBLIC: &str = "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4";
+const UA_PRIVATE: &str = "q1dXpw3UpT5VOmu_cf_v6ih07Aems3njxI-JWgLcM94";
+const AUTH_SECRET: &str = "BTBZMqHH6r4Tts7J_aSIgg";
+
+fn decode(value: &str) -> Vec<u8> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value).unwrap()
+}
+
+fn subscription(endpoint: &str, p256dh: &str, auth: &str) -> PushSubscriptionRequest {
+    PushSubscriptionRequest {
+        endpoint: endpoint.to_string(),
+        keys: PushSubscriptionKeys { p256dh: p256dh.to_string(), auth: auth.to_string() },
+    }
+}
+
+fn notification(kind: &str, payload: serde_json::Value) -> Notification {
+    Notification {
+        id: 12,
+        user_id: 3,
+        kind: kind.to_string(),
+        payload,
+        read_at: None,
+        created_at: NaiveDate::from_ymd_opt(2025, 9, 17).unwrap().and_hms_opt(8, 0, 0).unwrap(),
+    }
+}
+
+#[test]
+fn payloads_are_encrypted_as_in_rfc_8291() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let public = EcPoint::from_bytes(&group, &decode(SENDER_PUBLIC), &mut ctx).unwrap();
+    let private = BigNum::from_slice(&decode(SENDER_PRIVATE)).unwrap();
+    let sender = EcKey::from_private_components(&group, &private, &public).unwrap();
+
+    let body = encrypt_with(
+        b"When I grow up, I want to be a watermelon",
+        &decode(UA_PUBLIC),
+        &decode(AUTH_SECRET),
+        &sender,
+        &decode("DGv6ra1nlYgDCS1FRnbzlw"),
+    )
+    .unwrap();
+    assert_eq!(
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(body),
+        "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+    );
+}
+
+#[test]
+fn vapid_keys_must_be_a_pair() {
+    assert!(Vapid::new(SENDER_PUBLIC, SENDER_PRIVATE, "mailto:admin@example.com").is_ok());
+    assert!(Vapid::new(SENDER_PUBLIC, UA_PRIVATE, "mailto:admin@example.com").is_err());
+    assert!(Vapid::new(SENDER_PUBLIC, SENDER_PRIVATE, "admin@example.com").is_err());
+}
+
+#[test]
+fn vapid_tokens_are_issued_for_the_endpoint_origin() {
+    let vapid = Vapid::new(SENDER_PUBLIC, SENDER_PRIVATE, "mailto:admin@example.com").unwrap();
+    let header = vapid.authorization("https://push.example.net:8443/send/abc?x=1", 1_000).unwrap();
+    let (token, key) = header.strip_prefix("vapid t=").unwrap().split_once(", k=").unwrap();
+    assert_eq!(key, SENDER_PUBLIC);
+
+    assert_eq!(jsonwebtoken::decode_header(token).unwrap().alg, jsonwebtoken::Algorithm::ES256);
+    let claims: serde_json::Value = serde_json::from_slice(&decode(token.split('.').nth(1).unwrap())).unwrap();
+    assert_eq!(claims, json!({ "aud": "https://push.example.net:8443", "exp": 44_200, "sub": "mailto:admin@example.com" }));
+}
+
+#[test]
+fn subscriptions_need_an_https_endpoint_and_browser_keys() {
+    assert_eq!(audience("https://fcm.googleapis.com/fcm/send/abc"), Some("https://fcm.googleapis.com".to_string()));
+    assert!(validate_subscription(&subscription("https://fcm.googleapis.com/fcm/send/abc", UA_PUBLIC, AUTH_SECRET)).is_ok());
+    // Padded keys are accepted too
+    assert!(validate_subscription(&subscription("https://fcm.googleapis.com/fcm/send/abc", UA_PUBLIC, "BTBZMqHH6r4Tts7J_aSIgg==")).is_ok());
+
+    assert!(validate_subscription(&subscription("http://fcm.googleapis.com/fcm/send/abc", UA_PUBLIC, AUTH_SECRET)).is_err());
+    assert!(validate_subscription(&subscription("not a url", UA_PUBLIC, AUTH_SECRET)).is_err());
+    assert!(validate_subscription(&subscription("https://fcm.googleapis.com/fcm/send/abc", SENDER_PRIVATE, AUTH_SECRET)).is_err());
+    assert!(validate_subscription(&subscription("https://fcm.googleapis.com/fcm/send/abc", UA_PUBLIC, "c2hvcnQ")).is_err());
+}
+
+#[test]
+fn endpoints_on_internal_addresses_are_refused() {
+    for ip in ["10.0.0.5", "127.0.0.1", "169.254.169.254", "192.168.1.1", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:10.0.0.5"] {
+        assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+    }
+    for ip in ["8.8.8.8", "142.250.74.10", "2607:f8b0:4005:80c::200a"] {
+        assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+    }
+
+    for endpoint in ["https://10.0.0.5/push", "https://169.254.169.254/latest/meta-data", "https://[::1]:8443/push", "https://localhost/push", "https://api.localhost./push"] {
+        assert!(validate_subscription(&subscription(endpoint, UA_PUBLIC, AUTH_SECRET)).is_err(), "{}", endpoint);
+    }
+    assert!(validate_subscription(&subscription("https://8.8.8.8/push", UA_PUBLIC, AUTH_SECRET)).is_ok());
+}
+
+#[tokio::test]
+async fn pushes_are_only_sent_to_public_addresses() {
+    assert!(resolve_endpoint("https://127.0.0.1/push").await.is_err());
+    assert!(resolve_endpoint("https://[fe80::1]/push").await.is_err());
+    assert!(resolve_endpoint("https://localhost/push").await.is_err());
+    assert!(resolve_endpoint("https://8.8.8.8/push").await.is_ok());
+}
+
+#[test]
+fn messages_describe_the_notification() {
+    let invite = message_for(&notification(
+        "watch_party_invite",
+        json!({ "videoId": 5, "videoTitle": "Launch day", "invitedBy": 2, "inviteUrl": "/video/5?invite=abc" }),
+    ));
+    assert_eq!(invite.notification_id, 12);
+    assert_eq!(invite.title, "Watch party invite");
+    assert_eq!(invite.body, "You're invited to watch Launch day");
+    assert_eq!(invite.url.as_deref(), Some("/video/5?invite=abc"));
+
+    let one = message_for(&notification("saved_search_match", json!({ "name": "rust", "videoIds": [41] })));
+    assert_eq!(one.title, "New videos for \"rust\"");
+    assert_eq!(one.body, "A new video matches your saved search");
+    assert_eq!(one.url.as_deref(), Some("/video/41"));
+
+    let many = message_for(&notification("saved_search_match", json!({ "name": "rust", "videoIds": [41, 42, 43] })));
+    assert_eq!(many.body, "3 new videos match your saved search");
+    assert_eq!(many.url.as_deref(), Some("/home"));
+
+    let other = message_for(&notification("export_ready", json!({})));
+    assert_eq!((other.title.as_str(), other.body.as_str(), other.url), ("New notification", "export ready", None));
+}
@@ -0,0 +1,299 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+use video_streaming_backend::webhooks;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+async fn setup_test_app(pool: PgPool) -> impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+> {
+    dotenv().ok();
+
+    // Initialize S3 client
+    let storage = storage::init_storage().await;
+
+    // Create the app state using the provided pool
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    // Create the test app
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state))
+            .configure(handlers::configure_routes)
+    ).await
+}
+
+// Helper function to register a test user and get a JWT token
+async fn register_test_user(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let register_req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+
+    let register_resp = test::call_service(app, register_req).await;
+    assert!(register_resp.status().is_success());
+
+    let register_json: serde_json::Value = test::read_body_json(register_resp).await;
+    let user_id = register_json["user"]["id"].as_i64().unwrap() as i32;
+    let token = register_json["token"].as_str().unwrap().to_string();
+
+    (user_id, token)
+}
+
+async fn make_admin(pool: &PgPool, user_id: i32) {
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+// Accept one HTTP request, answer it with `status` and hand back the raw request text
+async fn spawn_receiver(status: u16) -> (String, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head.lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length: ").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        let response = format!("HTTP/1.1 {} Test\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok", status);
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    });
+    (url, handle)
+}
+
+fn header_value<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request.lines()
+        .take_while(|l| !l.is_empty())
+        .find_map(|l| l.split_once(": ").filter(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v))
+}
+
+#[sqlx::test]
+async fn test_webhook_crud_requires_admin(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (_, user_token) = register_test_user(&app, "webhook_user").await;
+    let (admin_id, admin_token) = register_test_user(&app, "webhook_admin").await;
+    make_admin(&pool, admin_id).await;
+
+    let body = json!({ "url": "https://example.com/hook", "events": ["video.created", "comment.created"] });
+
+    let req = test::TestRequest::post()
+        .uri("/api/webhooks")
+        .set_json(&body)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/api/webhooks")
+        .insert_header(("Authorization", format!("Bearer {}", user_token)))
+        .set_json(&body)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/webhooks")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(&body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::CREATED);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    let webhook_id = created["id"].as_i64().unwrap();
+    assert!(created["secret"].as_str().unwrap().starts_with("whsec_"));
+
+    // The secret is not returned after creation
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/webhooks/{}", webhook_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let fetched: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(fetched.get("secret").is_none());
+    assert_eq!(fetched["events"], json!(["video.created", "comment.created"]));
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/webhooks/{}", webhook_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({ "url": "https://example.com/other", "events": ["job.failed"], "active": false }))
+        .to_request();
+    let updated: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(updated["url"], "https://example.com/other");
+    assert_eq!(updated["active"], false);
+
+    let req = test::TestRequest::get()
+        .uri("/api/webhooks")
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let list: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(list.as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/webhooks/{}", webhook_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/webhooks/{}", webhook_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_webhook_validation(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "webhook_validator").await;
+    make_admin(&pool, admin_id).await;
+
+    for body in [
+        json!({ "url": "ftp://example.com/hook", "events": ["video.created"] }),
+        json!({ "url": "https://example.com/hook", "events": [] }),
+        json!({ "url": "https://example.com/hook", "events": ["video.deleted"] }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/api/webhooks")
+            .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+            .set_json(&body)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::BAD_REQUEST, "{}", body);
+    }
+}
+
+#[sqlx::test]
+async fn test_comment_dispatches_to_subscribed_webhooks(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "webhook_commenter").await;
+    make_admin(&pool, admin_id).await;
+
+    let video_id: i32 = sqlx::query_scalar(
+        "INSERT INTO videos (title, s3_key, uploaded_by) VALUES ('Hooked', 'videos/hooked.mp4', $1) RETURNING id"
+    )
+    .bind(admin_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let subscribed = webhooks::create_webhook(&pool, admin_id, "https://example.com/a", "s", &["comment.created".to_string()], true).await.unwrap();
+    let other_event = webhooks::create_webhook(&pool, admin_id, "https://example.com/b", "s", &["video.created".to_string()], true).await.unwrap();
+    let inactive = webhooks::create_webhook(&pool, admin_id, "https://example.com/c", "s", &["comment.created".to_string()], false).await.unwrap();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/comments/{}", video_id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .set_json(json!({ "text": "Nice", "videoTime": 3 }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let deliveries = webhooks::list_deliveries(&pool, subscribed.id, 10).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, "comment.created");
+    assert_eq!(deliveries[0].status, "pending");
+    assert_eq!(deliveries[0].payload["content"], "Nice");
+    assert!(webhooks::list_deliveries(&pool, other_event.id, 10).await.unwrap().is_empty());
+    assert!(webhooks::list_deliveries(&pool, inactive.id, 10).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn test_delivery_is_signed(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, admin_token) = register_test_user(&app, "webhook_signer").await;
+    make_admin(&pool, admin_id).await;
+
+    let (url, received) = spawn_receiver(200).await;
+    let webhook = webhooks::create_webhook(&pool, admin_id, &url, "topsecret", &["job.failed".to_string()], true).await.unwrap();
+    webhooks::dispatch(&pool, "job.failed", json!({ "videoId": 1, "job": "thumbnail" })).await.unwrap();
+
+    let client = reqwest::Client::new();
+    assert_eq!(webhooks::deliver_due(&pool, &client).await.unwrap(), 1);
+
+    let request = received.await.unwrap();
+    let body = request.split_once("\r\n\r\n").unwrap().1;
+    let timestamp: i64 = header_value(&request, "X-Webhook-Timestamp").unwrap().parse().unwrap();
+    assert_eq!(header_value(&request, "X-Webhook-Event"), Some("job.failed"));
+    assert_eq!(
+        header_value(&request, "X-Webhook-Signature").unwrap(),
+        format!("sha256={}", webhooks::sign_payload("topsecret", timestamp, body))
+    );
+    let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["event"], "job.failed");
+    assert_eq!(payload["data"]["job"], "thumbnail");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/webhooks/{}/deliveries", webhook.id))
+        .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+        .to_request();
+    let deliveries: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(deliveries[0]["status"], "succeeded");
+    assert_eq!(deliveries[0]["attempts"], 1);
+    assert_eq!(deliveries[0]["response_status"], 200);
+}
+
+#[sqlx::test]
+async fn test_failed_delivery_is_retried_later(pool: PgPool) {
+    let app = setup_test_app(pool.clone()).await;
+    let (admin_id, _) = register_test_user(&app, "webhook_retrier").await;
+
+    let (url, received) = spawn_receiver(500).await;
+    let webhook = webhooks::create_webhook(&pool, admin_id, &url, "s", &["video.processed".to_string()], true).await.unwrap();
+    webhooks::dispatch(&pool, "video.processed", json!({ "videoId": 1 })).await.unwrap();
+
+    let client = reqwest::Client::new();
+    assert_eq!(webhooks::deliver_due(&pool, &client).await.unwrap(), 1);
+    received.await.unwrap();
+
+    let delivery = &webhooks::list_deliveries(&pool, webhook.id, 10).await.unwrap()[0];
+    assert_eq!(delivery.status, "pending");
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.response_status, Some(500));
+    assert!(delivery.last_error.as_deref().unwrap().starts_with("HTTP 500"));
+    assert!(delivery.next_attempt_at > chrono::Utc::now().naive_utc());
+
+    // Not due again until the backoff has passed
+    assert_eq!(webhooks::deliver_due(&pool, &client).await.unwrap(), 0);
+    assert_eq!(webhooks::retry_delay_secs(1), 30);
+    assert_eq!(webhooks::retry_delay_secs(5), 480);
+}
+
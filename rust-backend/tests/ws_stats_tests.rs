@@ -0,0 +1,113 @@
+use actix_web::{test, web, App, http};
+use dotenv::dotenv;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::collections::HashMap;
+use sqlx::PgPool;
+use serde_json::json;
+
+use video_streaming_backend::client_queue::{client_queue, ClientReceiver};
+use video_streaming_backend::handlers;
+use video_streaming_backend::AppState;
+use video_streaming_backend::storage;
+
+async fn setup_test_app(pool: PgPool) -> (Arc<Mutex<AppState>>, impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>) {
+    dotenv().ok();
+
+    let storage = storage::init_storage().await;
+    let app_state = Arc::new(Mutex::new(AppState {
+        db_pool: pool,
+        storage,
+        redis_client: None,
+        job_queue: None, // No job queue in tests
+        video_clients: std::sync::Mutex::new(HashMap::new()),
+        watchparty_clients: std::sync::Mutex::new(HashMap::new()),
+    }));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app_state.clone()))
+            .configure(handlers::configure_routes)
+    ).await;
+    (app_state, app)
+}
+
+async fn register(app: &impl actix_web::dev::Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error,
+>, username: &str) -> (i32, String) {
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({
+            "username": username,
+            "email": format!("{}@example.com", username),
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    (body["user"]["id"].as_i64().unwrap() as i32, body["token"].as_str().unwrap().to_string())
+}
+
+// Register `count` clients in a room, returning their receivers to keep them connected
+fn connect(clients: &std::sync::Mutex<HashMap<i32, Vec<video_streaming_backend::client_queue::ClientSender>>>, video_id: i32, count: usize) -> Vec<ClientReceiver> {
+    let mut receivers = Vec::new();
+    let mut clients = clients.lock().unwrap();
+    for _ in 0..count {
+        let (tx, rx) = client_queue();
+        clients.entry(video_id).or_default().push(tx);
+        receivers.push(rx);
+    }
+    receivers
+}
+
+#[sqlx::test]
+async fn test_ws_stats_counts_clients_per_room(pool: PgPool) {
+    let (state, app) = setup_test_app(pool.clone()).await;
+    let (admin_id, token) = register(&app, "ws_admin").await;
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+        .bind(admin_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (_comments, _party, gone) = {
+        let state = state.lock().await;
+        let comments = connect(&state.video_clients, 1, 2);
+        let party = connect(&state.watchparty_clients, 2, 3);
+        let gone = connect(&state.watchparty_clients, 3, 1);
+        (comments, party, gone)
+    };
+    // Disconnected clients that haven't been removed yet aren't counted
+    drop(gone);
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/ws/stats")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stats["total_clients"], 5);
+    assert_eq!(stats["videos"], json!([{ "video_id": 1, "clients": 2, "redis_subscribers": null }]));
+    assert_eq!(stats["watch_parties"], json!([{ "video_id": 2, "clients": 3, "redis_subscribers": null }]));
+    assert!(stats["messages"]["sent_total"].is_u64());
+    assert_eq!(stats["messages"]["window_secs"], 60);
+}
+
+#[sqlx::test]
+async fn test_ws_stats_requires_admin(pool: PgPool) {
+    let (_, app) = setup_test_app(pool).await;
+    let (_, token) = register(&app, "ws_viewer").await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin/ws/stats")
+        .insert_header((http::header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+}
@@ -50,18 +50,21 @@ if [[ $response == *"job_ids"* ]]; then
             for job_id in $job_ids; do
                 status_response=$(curl -s "http://localhost:5060/api/jobs/$job_id")
                 
-                # Extract status
-                if [[ $status_response == *"Queued"* ]]; then
+                # Extract status: "Queued", "Processing", {"Completed":{...}} or
+                # {"Failed":{"error":{"kind":...,"message" or "stderr":...},"retryable":...}}
+                if [[ $status_response == '"Queued"' ]]; then
                     status="Queued"
-                elif [[ $status_response == *"Processing"* ]]; then
+                elif [[ $status_response == '"Processing"' ]]; then
                     status="Processing"
-                elif [[ $status_response == *"Completed"* ]]; then
+                elif [[ $status_response == '{"Completed":'* ]]; then
                     video_id=$(echo $status_response | grep -o '"video_id":[0-9]*' | cut -d':' -f2)
                     title=$(echo $status_response | grep -o '"title":"[^"]*"' | cut -d'"' -f4)
                     status="Completed - Video ID: $video_id, Title: $title"
-                elif [[ $status_response == *"Failed"* ]]; then
-                    error=$(echo $status_response | grep -o '"Failed":"[^"]*"' | cut -d'"' -f4)
-                    status="Failed - $error"
+                elif [[ $status_response == '{"Failed":'* ]]; then
+                    kind=$(echo $status_response | grep -o '"kind":"[^"]*"' | cut -d'"' -f4)
+                    error=$(echo $status_response | grep -o '"\(message\|stderr\)":"[^"]*"' | head -n 1 | cut -d'"' -f4)
+                    retryable=$(echo $status_response | grep -o '"retryable":[a-z]*' | cut -d':' -f2)
+                    status="Failed ($kind${retryable:+, retryable: $retryable})${error:+ - $error}"
                 else
                     status="Unknown"
                 fi
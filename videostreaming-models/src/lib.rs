@@ -0,0 +1,12 @@
+// Types the backend and the scraper both use: rows of the tables they share, the payloads of
+// their job queues and the scraper's API requests, and the webhook events both raise. Depending
+// on this crate keeps the two binaries' view of a table from drifting apart.
+
+pub mod models;
+pub mod media_urls;
+pub mod media_jobs;
+pub mod moderation;
+pub mod scrape;
+pub mod webhook_events;
+
+pub use models::{Comment, User, Video};
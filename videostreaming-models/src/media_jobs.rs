@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::Video;
+
+// Media processing jobs the backend queues on its Redis stream, one per kind of work on a video
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaJobKind {
+    #[default]
+    Duration,
+    Thumbnail,
+    Transcode,
+    Transcribe,
+    DetectScenes,
+    Moderate,
+    PackageHls,
+    AnalyzeLoudness,
+}
+
+impl MediaJobKind {
+    pub const ALL: [MediaJobKind; 8] = [
+        MediaJobKind::Duration,
+        MediaJobKind::Thumbnail,
+        MediaJobKind::Transcode,
+        MediaJobKind::Transcribe,
+        MediaJobKind::DetectScenes,
+        MediaJobKind::Moderate,
+        MediaJobKind::PackageHls,
+        MediaJobKind::AnalyzeLoudness,
+    ];
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+
+    // As serialized, e.g. "package_hls"
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MediaJobKind::Duration => "duration",
+            MediaJobKind::Thumbnail => "thumbnail",
+            MediaJobKind::Transcode => "transcode",
+            MediaJobKind::Transcribe => "transcribe",
+            MediaJobKind::DetectScenes => "detect_scenes",
+            MediaJobKind::Moderate => "moderate",
+            MediaJobKind::PackageHls => "package_hls",
+            MediaJobKind::AnalyzeLoudness => "analyze_loudness",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MediaJob {
+    #[serde(default)]
+    pub kind: MediaJobKind,
+    pub video_id: i32,
+    pub s3_key: String,
+    pub bucket: String,
+    // Redo the work even if the video already has a result, e.g. when reprocessing
+    #[serde(default)]
+    pub force: bool,
+    // Which try this is, counting up each time the job is queued again after failing
+    #[serde(default = "first_attempt")]
+    pub attempt: u32,
+}
+
+fn first_attempt() -> u32 {
+    1
+}
+
+impl MediaJob {
+    pub fn new(kind: MediaJobKind, video: &Video, bucket: &str) -> Self {
+        Self {
+            kind,
+            video_id: video.id,
+            s3_key: video.s3_key.clone(),
+            bucket: bucket.to_string(),
+            force: false,
+            attempt: first_attempt(),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::env;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::models::Video;
+
+// API responses link to media through these URLs instead of exposing S3 keys. They name the
+// video by id, so objects can be moved or renamed without breaking clients, and carry an
+// HMAC-signed expiry so they can't be edited to point elsewhere or kept forever.
+
+// How long a signed URL stays valid when SIGNED_URL_TTL_SECS is not set
+pub const DEFAULT_TTL_SECS: i64 = 6 * 60 * 60;
+
+pub fn ttl_secs() -> i64 {
+    env::var("SIGNED_URL_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_TTL_SECS)
+}
+
+// Expiries are rounded up to the end of the next TTL window, so the same URL is handed out for
+// a whole window and browsers can cache what it points at. Every URL lives at least one TTL.
+pub fn expiry(now: i64, ttl: i64) -> i64 {
+    (now.div_euclid(ttl) + 2) * ttl
+}
+
+// URL_SIGNING_SECRET, else JWT_SECRET. There is no default, since anyone knowing it could sign
+// URLs; the backend refuses to start without one.
+pub fn signing_secret() -> Result<String, String> {
+    env::var("URL_SIGNING_SECRET")
+        .or_else(|_| env::var("JWT_SECRET"))
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| "URL_SIGNING_SECRET (or JWT_SECRET) has to be set to sign media URLs".to_string())
+}
+
+// None without a secret, so nothing is signed and no signature matches
+fn mac(path: &str, expires: i64) -> Option<Hmac<Sha256>> {
+    let secret = signing_secret().ok()?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("url.{}.{}", path, expires).as_bytes());
+    Some(mac)
+}
+
+// Hex HMAC over the path (including any query it already has) and the expiry, empty without a
+// secret
+pub fn sign(path: &str, expires: i64) -> String {
+    mac(path, expires).map(|mac| hex::encode(mac.finalize().into_bytes())).unwrap_or_default()
+}
+
+// `path` with the expiry and signature appended to its query
+pub fn signed_url(path: &str, now: i64) -> String {
+    let expires = expiry(now, ttl_secs());
+    let separator = if path.contains('?') { '&' } else { '?' };
+    format!("{}{}expires={}&signature={}", path, separator, expires, sign(path, expires))
+}
+
+// Whether `signature` is the one sign() gives for the path and expiry, whatever the expiry
+pub fn signature_matches(path: &str, expires: i64, signature: &str) -> bool {
+    let Some(mac) = mac(path, expires) else {
+        return false;
+    };
+    hex::decode(signature).is_ok_and(|bytes| mac.verify_slice(&bytes).is_ok())
+}
+
+// Short digest of the thumbnail's key. It changes when the thumbnail does, so a URL can be cached
+// as immutable, without revealing the key.
+pub fn thumbnail_version(s3_key: &str) -> String {
+    hex::encode(&Sha256::digest(s3_key.as_bytes())[..8])
+}
+
+pub fn stream_path(video_id: i32) -> String {
+    format!("/api/videos/{}/stream", video_id)
+}
+
+pub fn hls_playlist_path(video_id: i32) -> String {
+    format!("/api/videos/{}/hls/index.m3u8", video_id)
+}
+
+pub fn thumbnail_path(video_id: i32, version: &str) -> String {
+    format!("/api/videos/{}/thumbnail?v={}", video_id, version)
+}
+
+pub fn clip_path(video_id: i32, clip_id: i32) -> String {
+    format!("/api/videos/{}/clips/{}", video_id, clip_id)
+}
+
+// Encrypted videos can only be played through their HLS playlist
+pub fn playback_url(video: &Video, now: i64) -> String {
+    let path = if video.hls_encrypted { hls_playlist_path(video.id) } else { stream_path(video.id) };
+    signed_url(&path, now)
+}
+
+// Thumbnails from elsewhere (e.g. imported with an external URL) are passed through as they are
+pub fn thumbnail_url(video: &Video, now: i64) -> Option<String> {
+    let key = video.thumbnail_url.as_deref()?;
+    if key.starts_with("http://") || key.starts_with("https://") {
+        return Some(key.to_string());
+    }
+    Some(signed_url(&thumbnail_path(video.id, &thumbnail_version(key)), now))
+}
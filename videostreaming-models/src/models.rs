@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub settings: Option<serde_json::Value>,
+    pub is_admin: bool,
+}
+
+// Serialized by hand below: clients get signed media URLs rather than S3 keys
+#[derive(Debug, Clone, Deserialize, FromRow)]
+pub struct Video {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub s3_key: String,
+    pub thumbnail_url: Option<String>,
+    pub uploaded_by: Option<i32>,
+    pub upload_date: Option<NaiveDateTime>,
+    pub tags: Option<Vec<String>>,
+    pub view_count: Option<i32>,
+    // The video's categories from videos_categories, primary first. Not a column of videos, so
+    // None until the backend loads them; then serialized with category_id as the primary one.
+    #[sqlx(default)]
+    #[serde(default)]
+    pub category_ids: Option<Vec<i32>>,
+    pub duration: Option<i32>, // Duration in seconds
+    pub updated_at: NaiveDateTime,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub sha256: Option<String>, // Hex digest of the stored file, for integrity checks and dedup
+    pub moderation_status: String, // pending, approved or flagged; only approved videos are listed
+    pub moderation_reason: Option<String>,
+    pub moderated_at: Option<NaiveDateTime>,
+    pub moderated_by: Option<i32>, // admin who overrode the classifier, if any
+    pub hls_encrypted: bool, // only served as AES-128 encrypted HLS
+    pub allowed_countries: Vec<String>, // ISO country codes; empty means anywhere not blocked
+    pub blocked_countries: Vec<String>,
+    pub allow_embed: bool, // whether sites outside CORS_ALLOWED_ORIGINS may play the video
+    pub allow_download: bool,
+    pub processing_status: String, // uploaded, processing, ready or failed
+    // EBU R128 measurements of the audio, for players normalizing volume; None until analyzed
+    // or when the video has no audio
+    pub loudness_lufs: Option<f64>, // integrated loudness
+    pub loudness_range_lu: Option<f64>,
+    pub true_peak_dbtp: Option<f64>,
+    pub premiere_at: Option<NaiveDateTime>, // playback opens to everyone at this time
+}
+
+impl Serialize for Video {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let now = chrono::Utc::now().timestamp();
+        let mut video = serializer.serialize_struct("Video", 30)?;
+        video.serialize_field("id", &self.id)?;
+        video.serialize_field("title", &self.title)?;
+        video.serialize_field("description", &self.description)?;
+        video.serialize_field("playback_url", &crate::media_urls::playback_url(self, now))?;
+        video.serialize_field("thumbnail_url", &crate::media_urls::thumbnail_url(self, now))?;
+        video.serialize_field("uploaded_by", &self.uploaded_by)?;
+        video.serialize_field("upload_date", &self.upload_date)?;
+        video.serialize_field("tags", &self.tags)?;
+        video.serialize_field("view_count", &self.view_count)?;
+        match &self.category_ids {
+            Some(category_ids) => {
+                video.serialize_field("category_id", &category_ids.first())?;
+                video.serialize_field("category_ids", category_ids)?;
+            }
+            None => {
+                video.skip_field("category_id")?;
+                video.skip_field("category_ids")?;
+            }
+        }
+        video.serialize_field("duration", &self.duration)?;
+        video.serialize_field("updated_at", &self.updated_at)?;
+        video.serialize_field("deleted_at", &self.deleted_at)?;
+        video.serialize_field("sha256", &self.sha256)?;
+        video.serialize_field("moderation_status", &self.moderation_status)?;
+        video.serialize_field("moderation_reason", &self.moderation_reason)?;
+        video.serialize_field("moderated_at", &self.moderated_at)?;
+        video.serialize_field("moderated_by", &self.moderated_by)?;
+        video.serialize_field("hls_encrypted", &self.hls_encrypted)?;
+        video.serialize_field("allowed_countries", &self.allowed_countries)?;
+        video.serialize_field("blocked_countries", &self.blocked_countries)?;
+        video.serialize_field("allow_embed", &self.allow_embed)?;
+        video.serialize_field("allow_download", &self.allow_download)?;
+        video.serialize_field("processing_status", &self.processing_status)?;
+        video.serialize_field("loudness_lufs", &self.loudness_lufs)?;
+        video.serialize_field("loudness_range_lu", &self.loudness_range_lu)?;
+        video.serialize_field("true_peak_dbtp", &self.true_peak_dbtp)?;
+        video.serialize_field("premiere_at", &self.premiere_at)?;
+        // Countdown for listings, until the premiere starts
+        let premieres_in_secs = self.premiere_at.map(|at| at.and_utc().timestamp() - now).filter(|secs| *secs > 0);
+        video.serialize_field("premieres_in_secs", &premieres_in_secs)?;
+        video.end()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Comment {
+    pub id: i32,
+    pub video_id: i32,
+    pub user_id: i32,
+    pub content: String,
+    pub video_time: i32,
+    pub created_at: NaiveDateTime,
+    #[serde(default)]
+    pub upvotes: i32,
+    #[serde(default)]
+    pub downvotes: i32,
+}
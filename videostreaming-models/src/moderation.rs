@@ -0,0 +1,19 @@
+use std::env;
+
+// Moderation statuses of a video. Only approved videos are public; with a classifier configured
+// (MODERATION_CLASSIFIER) new videos wait as pending until the backend's moderation job rules on
+// them, whichever binary added them.
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_APPROVED: &str = "approved";
+pub const STATUS_FLAGGED: &str = "flagged";
+
+// Whether MODERATION_CLASSIFIER selects a classifier the backend can run
+pub fn classifier_configured() -> bool {
+    env::var("MODERATION_CLASSIFIER").as_deref() == Ok("http") && env::var("MODERATION_API_URL").is_ok()
+}
+
+// Status new videos start with
+pub fn initial_status() -> &'static str {
+    if classifier_configured() { STATUS_PENDING } else { STATUS_APPROVED }
+}
@@ -0,0 +1,66 @@
+// The scraper's API: what clients submit and what a finished job returns. A ScrapeRequest is
+// also the payload stored with each job in the jobs table.
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScrapeRequest {
+    pub youtube_url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    pub max_results: Option<i32>,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchResponse {
+    pub job_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchScrapeRequest {
+    pub urls: Vec<String>,
+    pub tags: Option<Vec<String>>,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    Accepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchItemResult {
+    pub url: String,
+    pub status: BatchItemStatus,
+    pub job_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchScrapeResponse {
+    pub batch_id: Option<String>,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScrapeResponse {
+    pub video_id: i32,
+    pub title: String,
+    pub s3_key: String,
+    pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // True when the download matched an existing video, which is returned instead of a new one
+    #[serde(default)]
+    pub duplicate: bool,
+}
@@ -0,0 +1,31 @@
+use sqlx::PgExecutor;
+
+use crate::models::Video;
+
+// Webhook events either binary can raise. Raising one only queues a row in webhook_deliveries
+// per subscribed webhook; the backend's dispatcher sends them, so the scraper needs nothing but
+// the database to notify subscribers.
+
+pub const EVENT_VIDEO_CREATED: &str = "video.created";
+
+// Queue `event` for every active webhook subscribed to it, returning how many were queued
+pub async fn queue<'c>(executor: impl PgExecutor<'c>, event: &str, data: &serde_json::Value) -> Result<u64, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let result = sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at, created_at) \
+         SELECT id, $1, $2, $3, $3 FROM webhooks WHERE active AND $1 = ANY(events)"
+    )
+    .bind(event)
+    .bind(data)
+    .bind(now)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Every path that creates a video raises video.created through this once the video is
+// committed, whatever created it: uploads, imports, the watch folder, S3 events, compilations
+// or the scraper. The payload is the video as the API returns it.
+pub async fn video_created<'c>(executor: impl PgExecutor<'c>, video: &Video) -> Result<u64, sqlx::Error> {
+    queue(executor, EVENT_VIDEO_CREATED, &serde_json::json!(video)).await
+}
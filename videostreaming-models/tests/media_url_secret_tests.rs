@@ -0,0 +1,20 @@
+use videostreaming_models::media_urls::{sign, signature_matches, signing_secret};
+
+// The only test in this binary, so it can clear the environment
+#[test]
+fn nothing_is_signed_without_a_secret() {
+    std::env::remove_var("URL_SIGNING_SECRET");
+    std::env::remove_var("JWT_SECRET");
+    assert!(signing_secret().is_err());
+    assert_eq!(sign("/api/videos/1/stream", 2_000), "");
+    assert!(!signature_matches("/api/videos/1/stream", 2_000, ""));
+
+    std::env::set_var("JWT_SECRET", "");
+    assert!(signing_secret().is_err());
+
+    std::env::set_var("JWT_SECRET", "jwt_fallback_secret");
+    let signature = sign("/api/videos/1/stream", 2_000);
+    assert!(signature_matches("/api/videos/1/stream", 2_000, &signature));
+    std::env::set_var("URL_SIGNING_SECRET", "url_signing_secret");
+    assert!(!signature_matches("/api/videos/1/stream", 2_000, &signature));
+}
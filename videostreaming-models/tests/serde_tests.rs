@@ -0,0 +1,266 @@
+use chrono::NaiveDate;
+use serde_json::json;
+
+use videostreaming_models::media_jobs::{MediaJob, MediaJobKind};
+use videostreaming_models::scrape::{BatchItemResult, BatchItemStatus, BatchScrapeResponse, ScrapeRequest, ScrapeResponse};
+use videostreaming_models::{Comment, User, Video};
+
+fn video() -> Video {
+    let uploaded = NaiveDate::from_ymd_opt(2025, 8, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    Video {
+        id: 7,
+        title: "Round trip".to_string(),
+        description: Some("A video".to_string()),
+        s3_key: "videos/round-trip.mp4".to_string(),
+        thumbnail_url: Some("thumbnails/round-trip.jpg".to_string()),
+        uploaded_by: Some(3),
+        upload_date: Some(uploaded),
+        tags: Some(vec!["demo".to_string()]),
+        view_count: Some(12),
+        category_ids: None,
+        duration: Some(90),
+        updated_at: uploaded,
+        deleted_at: None,
+        sha256: Some("ab".repeat(32)),
+        moderation_status: "approved".to_string(),
+        moderation_reason: None,
+        moderated_at: None,
+        moderated_by: None,
+        hls_encrypted: false,
+        allowed_countries: vec![],
+        blocked_countries: vec!["US".to_string()],
+        allow_embed: true,
+        allow_download: false,
+        processing_status: "ready".to_string(),
+        loudness_lufs: None,
+        loudness_range_lu: None,
+        true_peak_dbtp: None,
+        premiere_at: None,
+    }
+}
+
+#[test]
+fn test_scrape_request_round_trip() {
+    let request = ScrapeRequest {
+        youtube_url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+        title: Some("Custom title".to_string()),
+        description: None,
+        tags: Some(vec!["music".to_string()]),
+        user_id: Some(1),
+    };
+
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["youtube_url"], "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+    let parsed: ScrapeRequest = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.youtube_url, request.youtube_url);
+    assert_eq!(parsed.title, request.title);
+    assert_eq!(parsed.tags, request.tags);
+    assert_eq!(parsed.user_id, Some(1));
+}
+
+#[test]
+fn test_scrape_response_defaults_for_older_jobs() {
+    // Responses stored before checksums and dedup have neither field
+    let parsed: ScrapeResponse = serde_json::from_value(json!({
+        "video_id": 4,
+        "title": "Old job",
+        "s3_key": "videos/old.mp4",
+        "thumbnail_url": null
+    }))
+    .unwrap();
+    assert_eq!(parsed.sha256, None);
+    assert!(!parsed.duplicate);
+
+    let again: ScrapeResponse = serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+    assert_eq!(again.video_id, 4);
+    assert_eq!(again.s3_key, "videos/old.mp4");
+}
+
+#[test]
+fn test_batch_response_round_trip() {
+    let response = BatchScrapeResponse {
+        batch_id: Some("batch-1".to_string()),
+        accepted: 1,
+        rejected: 1,
+        results: vec![
+            BatchItemResult {
+                url: "https://youtu.be/dQw4w9WgXcQ".to_string(),
+                status: BatchItemStatus::Accepted,
+                job_id: Some("job-1".to_string()),
+                reason: None,
+            },
+            BatchItemResult {
+                url: "not a url".to_string(),
+                status: BatchItemStatus::Rejected,
+                job_id: None,
+                reason: Some("Invalid YouTube URL".to_string()),
+            },
+        ],
+    };
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["results"][0]["status"], "accepted");
+    assert_eq!(json["results"][1]["status"], "rejected");
+    let parsed: BatchScrapeResponse = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.results.len(), 2);
+    assert!(matches!(parsed.results[1].status, BatchItemStatus::Rejected));
+    assert_eq!(parsed.results[1].reason.as_deref(), Some("Invalid YouTube URL"));
+}
+
+#[test]
+fn test_media_job_round_trip() {
+    let mut job = MediaJob::new(MediaJobKind::PackageHls, &video(), "videos");
+    job.force = true;
+    job.attempt = 3;
+
+    let json = serde_json::to_value(&job).unwrap();
+    assert_eq!(json["kind"], "package_hls");
+    let parsed: MediaJob = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.kind, MediaJobKind::PackageHls);
+    assert_eq!(parsed.video_id, 7);
+    assert_eq!(parsed.s3_key, "videos/round-trip.mp4");
+    assert!(parsed.force);
+    assert_eq!(parsed.attempt, 3);
+}
+
+#[test]
+fn test_media_job_defaults_for_queued_payloads() {
+    // Jobs queued before kinds, forcing and attempts were added
+    let parsed: MediaJob = serde_json::from_value(json!({
+        "video_id": 7,
+        "s3_key": "videos/round-trip.mp4",
+        "bucket": "videos"
+    }))
+    .unwrap();
+    assert_eq!(parsed.kind, MediaJobKind::Duration);
+    assert!(!parsed.force);
+    assert_eq!(parsed.attempt, 1);
+}
+
+#[test]
+fn test_media_job_kind_names_match_serde() {
+    for kind in MediaJobKind::ALL {
+        assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        assert_eq!(serde_json::from_value::<MediaJobKind>(json!(kind.as_str())).unwrap(), kind);
+        assert_eq!(MediaJobKind::parse(kind.as_str()), Some(kind));
+    }
+}
+
+#[test]
+fn test_video_serializes_signed_urls_instead_of_keys() {
+    let json = serde_json::to_value(video()).unwrap();
+    assert!(json.get("s3_key").is_none());
+    let playback_url = json["playback_url"].as_str().unwrap();
+    assert!(playback_url.starts_with("/api/videos/7/stream?expires="));
+    assert!(json["thumbnail_url"].as_str().unwrap().starts_with("/api/videos/7/thumbnail?v="));
+    assert_eq!(json["blocked_countries"], json!(["US"]));
+    assert_eq!(json["processing_status"], "ready");
+    assert!(json["loudness_lufs"].is_null());
+}
+
+#[test]
+fn test_video_serializes_loaded_categories() {
+    let json = serde_json::to_value(video()).unwrap();
+    assert!(json.get("category_id").is_none());
+    assert!(json.get("category_ids").is_none());
+
+    let mut categorized = video();
+    categorized.category_ids = Some(vec![4, 2]);
+    let json = serde_json::to_value(&categorized).unwrap();
+    assert_eq!(json["category_id"], 4);
+    assert_eq!(json["category_ids"], json!([4, 2]));
+
+    categorized.category_ids = Some(vec![]);
+    let json = serde_json::to_value(&categorized).unwrap();
+    assert!(json["category_id"].is_null());
+    assert_eq!(json["category_ids"], json!([]));
+}
+
+#[test]
+fn test_video_serializes_premiere_countdown() {
+    let mut upcoming = video();
+    upcoming.premiere_at = Some(chrono::Utc::now().naive_utc() + chrono::Duration::hours(1));
+    let json = serde_json::to_value(&upcoming).unwrap();
+    let secs = json["premieres_in_secs"].as_i64().unwrap();
+    assert!((3590..=3600).contains(&secs), "{}", secs);
+
+    // Once it has premiered there's nothing to count down
+    let mut premiered = video();
+    premiered.premiere_at = Some(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(5));
+    let json = serde_json::to_value(&premiered).unwrap();
+    assert!(!json["premiere_at"].is_null());
+    assert!(json["premieres_in_secs"].is_null());
+    assert!(serde_json::to_value(video()).unwrap()["premieres_in_secs"].is_null());
+}
+
+#[test]
+fn test_video_deserializes_stored_columns() {
+    let original = video();
+    let parsed: Video = serde_json::from_value(json!({
+        "id": original.id,
+        "title": original.title,
+        "description": original.description,
+        "s3_key": original.s3_key,
+        "thumbnail_url": original.thumbnail_url,
+        "uploaded_by": original.uploaded_by,
+        "upload_date": original.upload_date,
+        "tags": original.tags,
+        "view_count": original.view_count,
+        "duration": original.duration,
+        "updated_at": original.updated_at,
+        "deleted_at": original.deleted_at,
+        "sha256": original.sha256,
+        "moderation_status": original.moderation_status,
+        "moderation_reason": original.moderation_reason,
+        "moderated_at": original.moderated_at,
+        "moderated_by": original.moderated_by,
+        "hls_encrypted": original.hls_encrypted,
+        "allowed_countries": original.allowed_countries,
+        "blocked_countries": original.blocked_countries,
+        "allow_embed": original.allow_embed,
+        "allow_download": original.allow_download,
+        "processing_status": original.processing_status
+    }))
+    .unwrap();
+    assert_eq!(parsed.s3_key, "videos/round-trip.mp4");
+    assert_eq!(parsed.upload_date, original.upload_date);
+    assert_eq!(parsed.sha256, original.sha256);
+}
+
+#[test]
+fn test_comment_round_trip() {
+    let comment = Comment {
+        id: 1,
+        video_id: 7,
+        user_id: 3,
+        content: "Nice".to_string(),
+        video_time: 42,
+        created_at: NaiveDate::from_ymd_opt(2025, 8, 2).unwrap().and_hms_opt(9, 30, 0).unwrap(),
+        upvotes: 4,
+        downvotes: 1,
+    };
+
+    let parsed: Comment = serde_json::from_value(serde_json::to_value(&comment).unwrap()).unwrap();
+    assert_eq!(parsed.content, "Nice");
+    assert_eq!(parsed.video_time, 42);
+    assert_eq!(parsed.created_at, comment.created_at);
+    assert_eq!((parsed.upvotes, parsed.downvotes), (4, 1));
+}
+
+#[test]
+fn test_user_round_trip() {
+    let user = User {
+        id: 3,
+        username: "uploader".to_string(),
+        email: "uploader@example.com".to_string(),
+        password: "$2b$12$hash".to_string(),
+        created_at: None,
+        settings: Some(json!({ "theme": "dark" })),
+        is_admin: false,
+    };
+
+    let parsed: User = serde_json::from_value(serde_json::to_value(&user).unwrap()).unwrap();
+    assert_eq!(parsed.username, "uploader");
+    assert_eq!(parsed.settings, user.settings);
+    assert!(!parsed.is_admin);
+}
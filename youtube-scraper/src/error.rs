@@ -0,0 +1,66 @@
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+// yt-dlp messages that mean retrying the same URL will never succeed
+const PERMANENT_DOWNLOAD_ERRORS: &[&str] = &[
+    "Video unavailable",
+    "Private video",
+    "This video has been removed",
+    "copyright",
+    "not available in your country",
+    "Unsupported URL",
+    "Sign in to confirm your age",
+];
+
+#[derive(Debug, Clone, Error, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScrapeError {
+    #[error("{message}")]
+    InvalidUrl { message: String },
+
+    #[error("Failed to download video (exit code {exit_code:?}): {stderr}")]
+    DownloadFailed { exit_code: Option<i32>, stderr: String },
+
+    #[error("Failed to upload video to S3: {message}")]
+    UploadFailed { message: String },
+
+    #[error("Failed to insert video into database: {message}")]
+    DbFailed { message: String },
+
+    #[error("Failed to upload thumbnail: {message}")]
+    ThumbnailFailed { message: String },
+
+    #[error("Scraped videos must belong to a user, but no user_id was given")]
+    MissingUser,
+
+    // Failures outside the scrape pipeline itself, e.g. a job record that can't be read back
+    #[error("{message}")]
+    Internal { message: String },
+}
+
+impl ScrapeError {
+    pub fn invalid_url(message: impl Into<String>) -> Self {
+        ScrapeError::InvalidUrl { message: message.into() }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ScrapeError::Internal { message: message.into() }
+    }
+
+    // Whether resubmitting the same request could succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ScrapeError::InvalidUrl { .. } | ScrapeError::MissingUser | ScrapeError::Internal { .. } => false,
+            ScrapeError::DownloadFailed { stderr, .. } => {
+                !PERMANENT_DOWNLOAD_ERRORS.iter().any(|marker| stderr.contains(marker))
+            }
+            ScrapeError::UploadFailed { .. } | ScrapeError::DbFailed { .. } | ScrapeError::ThumbnailFailed { .. } => true,
+        }
+    }
+}
+
+impl From<sqlx::Error> for ScrapeError {
+    fn from(e: sqlx::Error) -> Self {
+        ScrapeError::DbFailed { message: e.to_string() }
+    }
+}
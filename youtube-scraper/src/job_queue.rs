@@ -1,17 +1,36 @@
+use std::env;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
-use log::{info, error};
+use log::{info, error, warn};
+use thiserror::Error;
+use tokio::sync::Semaphore;
 use sqlx::{PgPool, FromRow};
-use chrono::{Utc, DateTime};
+use sqlx::postgres::PgListener;
+use chrono::Utc;
 use crate::scraper::{ScrapeRequest, ScrapeResponse, YoutubeScraper};
+use crate::error::ScrapeError;
+use crate::limits;
+use crate::workers;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum JobStatus {
     Queued,
     Processing,
     Completed(ScrapeResponse),
-    Failed(String),
+    Failed {
+        error: ScrapeError,
+        retryable: bool,
+    },
+}
+
+impl JobStatus {
+    pub fn failed(error: ScrapeError) -> Self {
+        let retryable = error.is_retryable();
+        JobStatus::Failed { error, retryable }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +40,16 @@ pub struct Job {
     pub status: JobStatus,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProgress {
+    pub batch_id: String,
+    pub total: i64,
+    pub queued: i64,
+    pub processing: i64,
+    pub completed: i64,
+    pub failed: i64,
+}
+
 #[derive(Debug, FromRow)]
 struct JobRecord {
     job_id: String,
@@ -28,48 +57,165 @@ struct JobRecord {
     status: String,
     response: Option<serde_json::Value>,
     error: Option<String>,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    error_details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Error)]
+pub enum EnqueueError {
+    #[error("user_id is required: scraped videos are added to the library of the user who requested them")]
+    MissingUser,
+
+    #[error("User {user_id} already has {active} jobs queued or running, and may have at most {limit}")]
+    UserLimitReached { user_id: i32, active: i64, limit: i64 },
+
+    #[error("{active} jobs are already queued or running, and at most {limit} may be")]
+    QueueFull { active: i64, limit: i64 },
+
+    #[error("Failed to queue job: {0}")]
+    Db(#[from] sqlx::Error),
 }
 
 #[derive(Debug)]
 pub struct JobQueue {
     db_pool: PgPool,
+    // This replica's worker, recorded on the jobs it takes
+    worker_id: String,
+    // Jobs one user may have queued or running at once, see limits
+    max_jobs_per_user: Option<i64>,
+    // Jobs all users together may have queued or running
+    max_queued_jobs: Option<i64>,
 }
 
 impl JobQueue {
     pub fn new(db_pool: PgPool) -> Self {
         Self {
             db_pool,
+            worker_id: workers::new_worker_id(),
+            max_jobs_per_user: limits::max_jobs_per_user(),
+            max_queued_jobs: limits::max_queued_jobs(),
         }
     }
 
-    pub async fn add_job(&self, request: ScrapeRequest) -> String {
-        let job_id = Uuid::new_v4().to_string();
-        
-        // Insert the job into the database
-        let request_json = match serde_json::to_value(&request) {
-            Ok(json) => json,
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+
+    pub async fn add_job(&self, request: ScrapeRequest) -> Result<String, EnqueueError> {
+        let job_ids = self.insert_jobs(vec![request], None).await?;
+        Ok(job_ids.into_iter().next().unwrap_or_default())
+    }
+
+    // Insert all jobs of a batch in a single transaction so a batch is either fully queued or not at all
+    pub async fn add_batch(&self, requests: Vec<ScrapeRequest>) -> Result<(String, Vec<String>), EnqueueError> {
+        let batch_id = Uuid::new_v4().to_string();
+        let job_ids = self.insert_jobs(requests, Some(&batch_id)).await?;
+        info!("Queued batch {} with {} jobs", batch_id, job_ids.len());
+        Ok((batch_id, job_ids))
+    }
+
+    // Queue jobs in one transaction, unless that would take a user or the whole queue past its limit
+    async fn insert_jobs(&self, requests: Vec<ScrapeRequest>, batch_id: Option<&str>) -> Result<Vec<String>, EnqueueError> {
+        if requests.iter().any(|r| r.user_id.is_none()) {
+            return Err(EnqueueError::MissingUser);
+        }
+
+        let mut job_ids = Vec::with_capacity(requests.len());
+        let mut tx = self.db_pool.begin().await?;
+
+        if let Some(limit) = self.max_queued_jobs {
+            // Taken before any user's lock, so submissions always lock in the same order
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtext('scrape_jobs_total'))")
+                .execute(&mut tx)
+                .await?;
+            let active: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jobs WHERE status IN ('queued', 'processing')")
+                .fetch_one(&mut tx)
+                .await?;
+            if active + requests.len() as i64 > limit {
+                return Err(EnqueueError::QueueFull { active, limit });
+            }
+        }
+
+        if let Some(limit) = self.max_jobs_per_user {
+            let mut per_user: BTreeMap<i32, i64> = BTreeMap::new();
+            for user_id in requests.iter().filter_map(|r| r.user_id) {
+                *per_user.entry(user_id).or_default() += 1;
+            }
+            for (user_id, adding) in per_user {
+                // Concurrent submissions of the same user take turns, so they can't both pass the check
+                sqlx::query("SELECT pg_advisory_xact_lock(hashtext('scrape_jobs'), $1)")
+                    .bind(user_id)
+                    .execute(&mut tx)
+                    .await?;
+                let active: i64 = sqlx::query_scalar(
+                    "SELECT COUNT(*) FROM jobs WHERE status IN ('queued', 'processing') AND (request->>'user_id')::INT = $1"
+                )
+                .bind(user_id)
+                .fetch_one(&mut tx)
+                .await?;
+                if active + adding > limit {
+                    return Err(EnqueueError::UserLimitReached { user_id, active, limit });
+                }
+            }
+        }
+
+        for request in requests {
+            let job_id = Uuid::new_v4().to_string();
+            let request_json = serde_json::to_value(&request)
+                .map_err(|e| sqlx::Error::Protocol(format!("Failed to serialize request: {}", e)))?;
+
+            sqlx::query("INSERT INTO jobs (job_id, request, status, batch_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)")
+                .bind(&job_id)
+                .bind(&request_json)
+                .bind("queued")
+                .bind(batch_id)
+                .bind(Utc::now())
+                .bind(Utc::now())
+                .execute(&mut tx)
+                .await?;
+
+            job_ids.push(job_id);
+        }
+
+        tx.commit().await?;
+        Ok(job_ids)
+    }
+
+    pub async fn get_batch_progress(&self, batch_id: &str) -> Option<BatchProgress> {
+        let result = sqlx::query_as::<_, (String, i64)>(
+            "SELECT status, COUNT(*) FROM jobs WHERE batch_id = $1 GROUP BY status"
+        )
+        .bind(batch_id)
+        .fetch_all(&self.db_pool)
+        .await;
+
+        match result {
+            Ok(rows) if rows.is_empty() => None,
+            Ok(rows) => {
+                let mut progress = BatchProgress {
+                    batch_id: batch_id.to_string(),
+                    total: 0,
+                    queued: 0,
+                    processing: 0,
+                    completed: 0,
+                    failed: 0,
+                };
+                for (status, count) in rows {
+                    progress.total += count;
+                    match status.as_str() {
+                        "queued" => progress.queued += count,
+                        "processing" => progress.processing += count,
+                        "completed" => progress.completed += count,
+                        "failed" => progress.failed += count,
+                        _ => {}
+                    }
+                }
+                Some(progress)
+            },
             Err(e) => {
-                error!("Failed to serialize request: {}", e);
-                return job_id;
+                error!("Failed to get batch progress from database: {}", e);
+                None
             }
-        };
-        
-        let result = sqlx::query("INSERT INTO jobs (job_id, request, status, created_at, updated_at) VALUES ($1, $2, $3, $4, $5)")
-            .bind(&job_id)
-            .bind(&request_json)
-            .bind("queued")
-            .bind(Utc::now())
-            .bind(Utc::now())
-            .execute(&self.db_pool)
-            .await;
-        
-        if let Err(e) = result {
-            error!("Failed to insert job into database: {}", e);
         }
-        
-        job_id
     }
 
     pub async fn get_job_status(&self, job_id: &str) -> Option<JobStatus> {
@@ -89,14 +235,20 @@ impl JobQueue {
                                 Ok(response) => Some(JobStatus::Completed(response)),
                                 Err(e) => {
                                     error!("Failed to deserialize response: {}", e);
-                                    Some(JobStatus::Failed("Failed to deserialize response".to_string()))
+                                    Some(JobStatus::failed(ScrapeError::internal("Failed to deserialize response")))
                                 }
                             }
                         } else {
-                            Some(JobStatus::Failed("Response data missing".to_string()))
+                            Some(JobStatus::failed(ScrapeError::internal("Response data missing")))
                         }
                     },
-                    "failed" => Some(JobStatus::Failed(record.error.unwrap_or_else(|| "Unknown error".to_string()))),
+                    "failed" => {
+                        // Jobs that failed before error details were recorded only have the message
+                        let error = record.error_details
+                            .and_then(|details| serde_json::from_value::<ScrapeError>(details).ok())
+                            .unwrap_or_else(|| ScrapeError::internal(record.error.unwrap_or_else(|| "Unknown error".to_string())));
+                        Some(JobStatus::failed(error))
+                    },
                     _ => None,
                 }
             },
@@ -108,10 +260,13 @@ impl JobQueue {
         }
     }
 
-    pub async fn update_job_status(&self, job_id: &str, status: JobStatus) {
-        let (status_str, response_json, error_str) = match &status {
-            JobStatus::Queued => ("queued", None, None),
-            JobStatus::Processing => ("processing", None, None),
+    // Record how a job this worker took ended. Returns false when the job is no longer this
+    // worker's, e.g. it was requeued after missed heartbeats and another worker has it, in which
+    // case its row is left alone.
+    pub async fn update_job_status(&self, job_id: &str, status: JobStatus) -> bool {
+        let (status_str, response_json, error_str, error_details) = match &status {
+            JobStatus::Queued => ("queued", None, None, None),
+            JobStatus::Processing => ("processing", None, None, None),
             JobStatus::Completed(response) => {
                 let response_json = match serde_json::to_value(response) {
                     Ok(json) => Some(json),
@@ -120,22 +275,41 @@ impl JobQueue {
                         None
                     }
                 };
-                ("completed", response_json, None)
+                ("completed", response_json, None, None)
+            },
+            JobStatus::Failed { error, .. } => {
+                let error_details = match serde_json::to_value(error) {
+                    Ok(json) => Some(json),
+                    Err(e) => {
+                        error!("Failed to serialize error: {}", e);
+                        None
+                    }
+                };
+                ("failed", None, Some(error.to_string()), error_details)
             },
-            JobStatus::Failed(error) => ("failed", None, Some(error.clone())),
         };
         
-        let result = sqlx::query("UPDATE jobs SET status = $1, response = $2, error = $3, updated_at = $4 WHERE job_id = $5")
-            .bind(status_str)
-            .bind(response_json)
-            .bind(error_str)
-            .bind(Utc::now())
-            .bind(job_id)
-            .execute(&self.db_pool)
-            .await;
+        let result = sqlx::query(
+            "UPDATE jobs SET status = $1, response = $2, error = $3, error_details = $4, updated_at = $5
+             WHERE job_id = $6 AND worker_id = $7 AND status = 'processing'"
+        )
+        .bind(status_str)
+        .bind(response_json)
+        .bind(error_str)
+        .bind(error_details)
+        .bind(Utc::now())
+        .bind(job_id)
+        .bind(&self.worker_id)
+        .execute(&self.db_pool)
+        .await;
         
-        if let Err(e) = result {
-            error!("Failed to update job status in database: {}", e);
+        match result {
+            Ok(result) => result.rows_affected() > 0,
+            Err(e) => {
+                error!("Failed to update job status in database: {}", e);
+                // Still ours as far as we know; the heartbeat timeout requeues it otherwise
+                true
+            }
         }
     }
 
@@ -164,10 +338,11 @@ impl JobQueue {
         };
         
         if let Some(record) = job_record {
-            // Update the job status to processing
-            let result = sqlx::query("UPDATE jobs SET status = 'processing', updated_at = $1 WHERE job_id = $2")
+            // Update the job status to processing, taken by this worker
+            let result = sqlx::query("UPDATE jobs SET status = 'processing', worker_id = $3, heartbeat_at = $1, updated_at = $1 WHERE job_id = $2")
                 .bind(Utc::now())
                 .bind(&record.job_id)
+                .bind(&self.worker_id)
                 .execute(&mut tx)
                 .await;
             
@@ -203,32 +378,129 @@ impl JobQueue {
     }
 }
 
-pub async fn start_worker(job_queue: Arc<JobQueue>, scraper: YoutubeScraper) {
-    info!("Starting worker thread");
+// Channel the jobs table's trigger notifies whenever a job is queued
+const JOBS_CHANNEL: &str = "scrape_jobs";
+
+// How often the worker checks for jobs without being notified, from SCRAPER_POLL_INTERVAL_SECS.
+// Catches notifications missed while the listener was reconnecting.
+fn poll_interval() -> Duration {
+    let secs = env::var("SCRAPER_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(15);
+    Duration::from_secs(secs)
+}
+
+async fn listen_for_jobs(db_pool: &PgPool) -> Option<PgListener> {
+    let mut listener = match PgListener::connect_with(db_pool).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to connect job listener, polling every {:?} instead: {}", poll_interval(), e);
+            return None;
+        }
+    };
+    if let Err(e) = listener.listen(JOBS_CHANNEL).await {
+        warn!("Failed to listen on {}, polling every {:?} instead: {}", JOBS_CHANNEL, poll_interval(), e);
+        return None;
+    }
+    Some(listener)
+}
+
+// Wait until a job is queued or the poll interval passes. Without a listener, just wait.
+async fn wait_for_job(listener: &mut Option<PgListener>, db_pool: &PgPool) {
+    let Some(active) = listener.as_mut() else {
+        tokio::time::sleep(poll_interval()).await;
+        // Try listening again for the next wait
+        *listener = listen_for_jobs(db_pool).await;
+        return;
+    };
+    match tokio::time::timeout(poll_interval(), active.recv()).await {
+        Ok(Ok(_)) | Err(_) => {}
+        Ok(Err(e)) => {
+            error!("Job listener failed, polling until it reconnects: {}", e);
+            *listener = None;
+        }
+    }
+}
+
+// Whether admins paused scraping from the backend, with its scraping feature flag or by putting
+// the site in maintenance. Queued jobs wait meanwhile. Without the backend's feature_flags
+// table, scraping goes ahead.
+async fn scraping_paused(db_pool: &PgPool) -> bool {
+    let paused = sqlx::query_scalar::<_, bool>(
+        "SELECT COALESCE(bool_or((name = 'scraping' AND NOT enabled) OR (name = 'maintenance' AND enabled)), FALSE)
+         FROM feature_flags WHERE name IN ('scraping', 'maintenance')"
+    )
+    .fetch_one(db_pool)
+    .await;
+    match paused {
+        Ok(paused) => paused,
+        Err(e) => {
+            warn!("Failed to check whether scraping is paused, going ahead: {}", e);
+            false
+        }
+    }
+}
+
+async fn run_job(job_queue: &JobQueue, scraper: &YoutubeScraper, job: Job) {
+    info!("Processing job {}", job.id);
+    
+    // Process the job
+    let job_id = job.id.clone();
+    let result = scraper.scrape_video(job.request).await;
+    
+    // Update the job status
+    let status = match result {
+        Ok(response) => {
+            info!("Job {} completed successfully", job_id);
+            JobStatus::Completed(response)
+        }
+        Err(e) => {
+            error!("Job {} failed: {}", job_id, e);
+            JobStatus::failed(e)
+        }
+    };
+    if !job_queue.update_job_status(&job_id, status).await {
+        warn!("Lost the lease on job {} while it ran, leaving its status to the worker that has it now", job_id);
+    }
+}
+
+// Runs up to SCRAPER_MAX_CONCURRENT_DOWNLOADS jobs at once
+pub async fn start_worker(job_queue: Arc<JobQueue>, scraper: Arc<YoutubeScraper>) {
+    let max_downloads = limits::max_concurrent_downloads();
+    info!("Starting worker thread as {} ({} concurrent downloads)", job_queue.worker_id, max_downloads);
+    if let Err(e) = workers::register(&job_queue.db_pool, &job_queue.worker_id).await {
+        error!("Failed to register worker {}: {}", job_queue.worker_id, e);
+    }
+    tokio::spawn(workers::run_heartbeat(job_queue.db_pool.clone(), job_queue.worker_id.clone()));
+    let mut listener = listen_for_jobs(&job_queue.db_pool).await;
+    let downloads = Arc::new(Semaphore::new(max_downloads));
     
     loop {
+        // Only take a job once there is a free download slot, leaving it to other replicas until then
+        let slot = downloads.clone().acquire_owned().await.expect("download slots are never closed");
+
+        if scraping_paused(&job_queue.db_pool).await {
+            drop(slot);
+            tokio::time::sleep(poll_interval()).await;
+            continue;
+        }
+
         // Get the next job from the queue
         if let Some(job) = job_queue.get_next_queued_job().await {
-            info!("Processing job {}", job.id);
-            
-            // Process the job
-            let job_id = job.id.clone();
-            let result = scraper.scrape_video(job.request).await;
-            
-            // Update the job status
-            match result {
-                Ok(response) => {
-                    info!("Job {} completed successfully", job_id);
-                    job_queue.update_job_status(&job_id, JobStatus::Completed(response)).await;
-                }
-                Err(e) => {
-                    error!("Job {} failed: {}", job_id, e);
-                    job_queue.update_job_status(&job_id, JobStatus::Failed(e)).await;
-                }
-            }
+            let job_queue = job_queue.clone();
+            let scraper = scraper.clone();
+            tokio::spawn(async move {
+                run_job(&job_queue, &scraper, job).await;
+                drop(slot);
+            });
+            // More jobs may be waiting; their notifications may have arrived while this one was taken
+            continue;
         }
+        drop(slot);
         
-        // Sleep for 15 seconds before checking for new jobs to avoid hammering the database
-        tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
+        // Nothing queued: sleep until a job is queued rather than hammering the database
+        wait_for_job(&mut listener, &job_queue.db_pool).await;
     }
 }
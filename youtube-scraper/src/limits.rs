@@ -0,0 +1,57 @@
+use std::env;
+use log::warn;
+
+// Limits that keep downloads from saturating a replica. SCRAPER_LIMIT_RATE caps each yt-dlp
+// download's bandwidth, SCRAPER_MAX_CONCURRENT_DOWNLOADS how many jobs a replica runs at once,
+// SCRAPER_MAX_JOBS_PER_USER how many jobs one user may have queued or running, and
+// SCRAPER_MAX_QUEUED_JOBS how many all users together may.
+
+const DEFAULT_MAX_JOBS_PER_USER: i64 = 20;
+const DEFAULT_MAX_QUEUED_JOBS: i64 = 1000;
+
+// A yt-dlp --limit-rate value: bytes per second, optionally with a K, M or G suffix, e.g. "4.2M"
+fn is_valid_rate(rate: &str) -> bool {
+    let number = rate.strip_suffix(['K', 'M', 'G', 'k', 'm', 'g']).unwrap_or(rate);
+    !number.is_empty() && number.parse::<f64>().map_or(false, |n| n.is_finite() && n > 0.0)
+}
+
+// Per-download bandwidth cap to pass to yt-dlp, if one is configured
+pub fn limit_rate() -> Option<String> {
+    let rate = env::var("SCRAPER_LIMIT_RATE").ok()?;
+    let rate = rate.trim();
+    if rate.is_empty() {
+        return None;
+    }
+    if !is_valid_rate(rate) {
+        warn!("Ignoring SCRAPER_LIMIT_RATE={}: expected bytes per second, e.g. 500K or 5M", rate);
+        return None;
+    }
+    Some(rate.to_string())
+}
+
+pub fn max_concurrent_downloads() -> usize {
+    env::var("SCRAPER_MAX_CONCURRENT_DOWNLOADS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(1)
+}
+
+// None when unlimited, i.e. SCRAPER_MAX_JOBS_PER_USER=0
+pub fn max_jobs_per_user() -> Option<i64> {
+    let limit = env::var("SCRAPER_MAX_JOBS_PER_USER")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_MAX_JOBS_PER_USER);
+    (limit > 0).then_some(limit)
+}
+
+// The user_id of a submission isn't authenticated, so the per-user cap alone can be sidestepped by
+// spreading jobs over user IDs. This caps the whole queue. None when unlimited, i.e. 0.
+pub fn max_queued_jobs() -> Option<i64> {
+    let limit = env::var("SCRAPER_MAX_QUEUED_JOBS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_MAX_QUEUED_JOBS);
+    (limit > 0).then_some(limit)
+}
@@ -1,7 +1,7 @@
-use actix_web::{web, App, HttpServer, HttpResponse, Responder, post, get, middleware};
+use actix_web::{web, App, HttpServer, HttpRequest, HttpResponse, Responder, post, get, put, delete, middleware, http};
 use actix_cors::Cors;
 use dotenv::dotenv;
-use log::{info, error};
+use log::{info, warn, error};
 use sqlx::{PgPool};
 use std::env;
 use std::sync::Arc;
@@ -11,32 +11,198 @@ use aws_types::region::Region;
 use clap::Parser;
 use serde::{Serialize, Deserialize};
 use futures::future::join_all;
+use std::collections::HashSet;
 
 mod models;
 mod scraper;
 mod job_queue;
+mod schedules;
+mod scratch;
+mod error;
+mod workers;
+mod limits;
 
-use job_queue::JobQueue;
+use job_queue::{EnqueueError, JobQueue};
+use schedules::{Scheduler, ScheduleRequest};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct JobResponse {
     job_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct JobStatusRequest {
-    job_id: String,
-}
-
 #[post("/api/scrape")]
 async fn scrape_video(
     req: web::Json<scraper::ScrapeRequest>,
     job_queue: web::Data<Arc<JobQueue>>,
 ) -> impl Responder {
     // Add the job to the queue
-    let job_id = job_queue.add_job(req.into_inner()).await;
-    
-    HttpResponse::Accepted().json(JobResponse { job_id })
+    match job_queue.add_job(req.into_inner()).await {
+        Ok(job_id) => HttpResponse::Accepted().json(JobResponse { job_id }),
+        Err(e) => enqueue_error_response(e),
+    }
+}
+
+fn enqueue_error_response(e: EnqueueError) -> HttpResponse {
+    match e {
+        EnqueueError::MissingUser => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string()
+        })),
+        EnqueueError::UserLimitReached { .. } | EnqueueError::QueueFull { .. } => HttpResponse::TooManyRequests().json(serde_json::json!({
+            "error": e.to_string()
+        })),
+        EnqueueError::Db(e) => {
+            error!("Failed to queue job: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to queue job"
+            }))
+        }
+    }
+}
+
+// Maximum number of URLs accepted in a single batch submission
+const MAX_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct BatchQuery {
+    user_id: Option<i32>,
+}
+
+#[post("/api/scrape/batch")]
+async fn scrape_batch(
+    http_req: HttpRequest,
+    body: web::Bytes,
+    query: web::Query<BatchQuery>,
+    job_queue: web::Data<Arc<JobQueue>>,
+) -> impl Responder {
+    // Accept either a JSON body or a newline-separated list of URLs
+    let is_text = http_req.headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .map(|ct| ct.starts_with("text/plain"))
+        .unwrap_or(false);
+
+    let batch_request = if is_text {
+        let text = String::from_utf8_lossy(&body);
+        scraper::BatchScrapeRequest {
+            urls: text.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            tags: None,
+            user_id: query.user_id,
+        }
+    } else {
+        match serde_json::from_slice::<scraper::BatchScrapeRequest>(&body) {
+            Ok(mut request) => {
+                request.user_id = request.user_id.or(query.user_id);
+                request
+            },
+            Err(e) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Invalid batch request: {}", e)
+                }));
+            }
+        }
+    };
+
+    if batch_request.urls.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "No URLs provided"
+        }));
+    }
+
+    if batch_request.urls.len() > MAX_BATCH_SIZE {
+        return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+            "error": format!("A batch may contain at most {} URLs", MAX_BATCH_SIZE)
+        }));
+    }
+
+    // Validate and dedupe by YouTube video ID, remembering each URL's slot in the response
+    let mut seen_ids = HashSet::new();
+    let mut results = Vec::with_capacity(batch_request.urls.len());
+    let mut accepted_requests = Vec::new();
+    let mut accepted_slots = Vec::new();
+
+    for url in &batch_request.urls {
+        match scraper::validate_youtube_url(url) {
+            Ok(video_id) if !seen_ids.insert(video_id.clone()) => {
+                results.push(scraper::BatchItemResult {
+                    url: url.clone(),
+                    status: scraper::BatchItemStatus::Rejected,
+                    job_id: None,
+                    reason: Some(format!("Duplicate of another URL in this batch (video {})", video_id)),
+                });
+            },
+            Ok(_) => {
+                accepted_slots.push(results.len());
+                accepted_requests.push(scraper::ScrapeRequest {
+                    youtube_url: url.trim().to_string(),
+                    title: None,
+                    description: None,
+                    tags: batch_request.tags.clone(),
+                    user_id: batch_request.user_id,
+                });
+                results.push(scraper::BatchItemResult {
+                    url: url.clone(),
+                    status: scraper::BatchItemStatus::Accepted,
+                    job_id: None,
+                    reason: None,
+                });
+            },
+            Err(reason) => {
+                results.push(scraper::BatchItemResult {
+                    url: url.clone(),
+                    status: scraper::BatchItemStatus::Rejected,
+                    job_id: None,
+                    reason: Some(reason.to_string()),
+                });
+            }
+        }
+    }
+
+    let accepted = accepted_requests.len();
+    let rejected = results.len() - accepted;
+
+    if accepted_requests.is_empty() {
+        return HttpResponse::BadRequest().json(scraper::BatchScrapeResponse {
+            batch_id: None,
+            accepted,
+            rejected,
+            results,
+        });
+    }
+
+    match job_queue.add_batch(accepted_requests).await {
+        Ok((batch_id, job_ids)) => {
+            for (slot, job_id) in accepted_slots.into_iter().zip(job_ids) {
+                results[slot].job_id = Some(job_id);
+            }
+
+            HttpResponse::Accepted().json(scraper::BatchScrapeResponse {
+                batch_id: Some(batch_id),
+                accepted,
+                rejected,
+                results,
+            })
+        },
+        Err(e) => enqueue_error_response(e),
+    }
+}
+
+#[get("/api/batches/{batch_id}")]
+async fn get_batch_status(
+    path: web::Path<String>,
+    job_queue: web::Data<Arc<JobQueue>>,
+) -> impl Responder {
+    let batch_id = path.into_inner();
+
+    match job_queue.get_batch_progress(&batch_id).await {
+        Some(progress) => HttpResponse::Ok().json(progress),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Batch not found"
+        }))
+    }
 }
 
 #[post("/api/search")]
@@ -71,10 +237,25 @@ async fn search_videos(
                 futures.push(job_queue.add_job(scrape_request));
             }
             
-            // Wait for all jobs to be added
-            let job_ids = join_all(futures).await;
-            
-            HttpResponse::Accepted().json(scraper::SearchResponse { job_ids })
+            // Wait for all jobs to be added. Past the user's job limit the rest are left out.
+            let mut job_ids = Vec::new();
+            let mut first_error = None;
+            for result in join_all(futures).await {
+                match result {
+                    Ok(job_id) => job_ids.push(job_id),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            match first_error {
+                Some(e) if job_ids.is_empty() => enqueue_error_response(e),
+                Some(e) => {
+                    info!("Queued {} of the videos found for {}: {}", job_ids.len(), query, e);
+                    HttpResponse::Accepted().json(scraper::SearchResponse { job_ids })
+                }
+                None => HttpResponse::Accepted().json(scraper::SearchResponse { job_ids }),
+            }
         },
         Err(e) => {
             error!("Failed to search YouTube: {}", e);
@@ -100,6 +281,129 @@ async fn get_job_status(
     }
 }
 
+// Scraper replicas sharing the jobs table, with the job each is running
+#[get("/api/workers")]
+async fn list_workers(
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    match workers::list_workers(&db_pool, workers::heartbeat_timeout()).await {
+        Ok(workers) => HttpResponse::Ok().json(workers),
+        Err(e) => {
+            error!("Failed to list workers: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list workers"
+            }))
+        }
+    }
+}
+
+#[get("/api/schedules")]
+async fn list_schedules(
+    scheduler: web::Data<Arc<Scheduler>>,
+) -> impl Responder {
+    match scheduler.list_schedules().await {
+        Ok(schedules) => HttpResponse::Ok().json(schedules),
+        Err(e) => {
+            error!("Failed to list schedules: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to list schedules"
+            }))
+        }
+    }
+}
+
+#[post("/api/schedules")]
+async fn create_schedule(
+    req: web::Json<ScheduleRequest>,
+    scheduler: web::Data<Arc<Scheduler>>,
+) -> impl Responder {
+    let source_url = match req.validate() {
+        Ok(url) => url,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e
+            }));
+        }
+    };
+
+    match scheduler.create_schedule(&req, &source_url).await {
+        Ok(schedule) => HttpResponse::Created().json(schedule),
+        Err(e) => {
+            error!("Failed to create schedule: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to create schedule"
+            }))
+        }
+    }
+}
+
+#[get("/api/schedules/{id}")]
+async fn get_schedule(
+    path: web::Path<i32>,
+    scheduler: web::Data<Arc<Scheduler>>,
+) -> impl Responder {
+    match scheduler.get_schedule(path.into_inner()).await {
+        Ok(Some(schedule)) => HttpResponse::Ok().json(schedule),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Schedule not found"
+        })),
+        Err(e) => {
+            error!("Failed to get schedule: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to get schedule"
+            }))
+        }
+    }
+}
+
+#[put("/api/schedules/{id}")]
+async fn update_schedule(
+    path: web::Path<i32>,
+    req: web::Json<ScheduleRequest>,
+    scheduler: web::Data<Arc<Scheduler>>,
+) -> impl Responder {
+    let source_url = match req.validate() {
+        Ok(url) => url,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e
+            }));
+        }
+    };
+
+    match scheduler.update_schedule(path.into_inner(), &req, &source_url).await {
+        Ok(Some(schedule)) => HttpResponse::Ok().json(schedule),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Schedule not found"
+        })),
+        Err(e) => {
+            error!("Failed to update schedule: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to update schedule"
+            }))
+        }
+    }
+}
+
+#[delete("/api/schedules/{id}")]
+async fn delete_schedule(
+    path: web::Path<i32>,
+    scheduler: web::Data<Arc<Scheduler>>,
+) -> impl Responder {
+    match scheduler.delete_schedule(path.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Schedule not found"
+        })),
+        Err(e) => {
+            error!("Failed to delete schedule: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to delete schedule"
+            }))
+        }
+    }
+}
+
 #[post("/api/status")]
 async fn scrape_status() -> impl Responder {
     HttpResponse::Ok().json(serde_json::json!({
@@ -118,7 +422,7 @@ struct Args {
     #[arg(short, long)]
     url: Option<String>,
 
-    /// User ID to associate with the video
+    /// User whose library the video is added to (required with --url)
     #[arg(short = 'i', long)]
     user_id: Option<i32>,
 
@@ -135,10 +439,18 @@ async fn main() -> std::io::Result<()> {
     // Parse command line arguments
     let args = Args::parse();
 
+    // Set up the download scratch directory, clearing files left by earlier crashed runs
+    scratch::ScratchDir::from_env().prepare()?;
+
     // Initialize database and S3 client
     let db_pool = init_db_pool().await;
     let s3_client = init_s3_client().await;
 
+    // The video.created webhooks this queues carry signed media URLs
+    if let Err(e) = videostreaming_models::media_urls::signing_secret() {
+        warn!("{}; scraped videos won't be announced to webhooks with working media URLs", e);
+    }
+
     if args.server {
         // Create job queue
         let job_queue = Arc::new(JobQueue::new(db_pool.clone()));
@@ -148,9 +460,18 @@ async fn main() -> std::io::Result<()> {
         let worker_s3_client = s3_client.clone();
         let worker_job_queue = job_queue.clone();
         tokio::spawn(async move {
-            let scraper = scraper::YoutubeScraper::new(worker_db_pool, worker_s3_client);
+            let scraper = Arc::new(scraper::YoutubeScraper::new(worker_db_pool, worker_s3_client));
             job_queue::start_worker(worker_job_queue, scraper).await;
         });
+
+        // Start the recurring scrape scheduler
+        let scheduler = Arc::new(Scheduler::new(db_pool.clone()));
+        let scheduler_clone = scheduler.clone();
+        let scheduler_job_queue = job_queue.clone();
+        let scheduler_scraper = scraper::YoutubeScraper::new(db_pool.clone(), s3_client.clone());
+        tokio::spawn(async move {
+            schedules::start_scheduler(scheduler_clone, scheduler_job_queue, scheduler_scraper).await;
+        });
         
         // Run as API server
         info!("Starting YouTube scraper API server on 0.0.0.0:5060");
@@ -166,10 +487,19 @@ async fn main() -> std::io::Result<()> {
                 .app_data(web::Data::new(db_pool.clone()))
                 .app_data(web::Data::new(s3_client.clone()))
                 .app_data(web::Data::new(job_queue.clone()))
+                .app_data(web::Data::new(scheduler.clone()))
                 .app_data(web::Data::new(Arc::new(scraper::YoutubeScraper::new(db_pool.clone(), s3_client.clone()))))
                 .service(scrape_video)
+                .service(scrape_batch)
+                .service(get_batch_status)
                 .service(search_videos)
                 .service(get_job_status)
+                .service(list_workers)
+                .service(list_schedules)
+                .service(create_schedule)
+                .service(get_schedule)
+                .service(update_schedule)
+                .service(delete_schedule)
                 .service(scrape_status)
         })
         .bind(("0.0.0.0", 5060))?
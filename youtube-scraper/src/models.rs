@@ -24,19 +24,6 @@ pub struct RegisterRequest {
     pub password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct Video {
-    pub id: i32,
-    pub title: String,
-    pub description: Option<String>,
-    pub s3_key: String,
-    pub thumbnail_url: Option<String>,
-    pub uploaded_by: Option<i32>,
-    pub upload_date: Option<NaiveDateTime>,
-    pub tags: Option<Vec<String>>,
-    pub view_count: Option<i32>,
-}
-
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Comment {
     pub id: i32,
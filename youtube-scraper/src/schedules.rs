@@ -0,0 +1,250 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use log::{info, error};
+use sqlx::{PgPool, FromRow};
+use chrono::{Utc, DateTime, Duration};
+use crate::job_queue::{EnqueueError, JobQueue};
+use crate::scraper::{ScrapeRequest, YoutubeScraper, validate_channel_or_playlist_url};
+use crate::error::ScrapeError;
+
+// Shortest allowed interval between runs of a schedule
+pub const MIN_INTERVAL_MINUTES: i32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScrapeSchedule {
+    pub id: i32,
+    pub name: String,
+    pub source_url: String,
+    pub interval_minutes: i32,
+    pub tags: Option<Vec<String>>,
+    pub user_id: Option<i32>,
+    pub enabled: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub next_run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRequest {
+    pub name: String,
+    pub source_url: String,
+    pub interval_minutes: i32,
+    pub tags: Option<Vec<String>>,
+    pub user_id: Option<i32>,
+    pub enabled: Option<bool>,
+}
+
+impl ScheduleRequest {
+    // Validate the request, returning the normalized source URL
+    pub fn validate(&self) -> Result<String, String> {
+        if self.name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+        if self.interval_minutes < MIN_INTERVAL_MINUTES {
+            return Err(format!("interval_minutes must be at least {}", MIN_INTERVAL_MINUTES));
+        }
+        if self.user_id.is_none() {
+            return Err("user_id is required: scraped videos are added to that user's library".to_string());
+        }
+        validate_channel_or_playlist_url(&self.source_url).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    db_pool: PgPool,
+}
+
+impl Scheduler {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+        }
+    }
+
+    pub async fn list_schedules(&self) -> Result<Vec<ScrapeSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ScrapeSchedule>("SELECT * FROM scrape_schedules ORDER BY id ASC")
+            .fetch_all(&self.db_pool)
+            .await
+    }
+
+    pub async fn get_schedule(&self, id: i32) -> Result<Option<ScrapeSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ScrapeSchedule>("SELECT * FROM scrape_schedules WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db_pool)
+            .await
+    }
+
+    pub async fn create_schedule(&self, request: &ScheduleRequest, source_url: &str) -> Result<ScrapeSchedule, sqlx::Error> {
+        sqlx::query_as::<_, ScrapeSchedule>(
+            "INSERT INTO scrape_schedules (name, source_url, interval_minutes, tags, user_id, enabled, next_run_at, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $7)
+             RETURNING *"
+        )
+        .bind(request.name.trim())
+        .bind(source_url)
+        .bind(request.interval_minutes)
+        .bind(request.tags.clone().unwrap_or_default())
+        .bind(request.user_id)
+        .bind(request.enabled.unwrap_or(true))
+        .bind(Utc::now())
+        .fetch_one(&self.db_pool)
+        .await
+    }
+
+    pub async fn update_schedule(&self, id: i32, request: &ScheduleRequest, source_url: &str) -> Result<Option<ScrapeSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ScrapeSchedule>(
+            "UPDATE scrape_schedules
+             SET name = $1, source_url = $2, interval_minutes = $3, tags = $4, user_id = $5, enabled = $6, updated_at = $7
+             WHERE id = $8
+             RETURNING *"
+        )
+        .bind(request.name.trim())
+        .bind(source_url)
+        .bind(request.interval_minutes)
+        .bind(request.tags.clone().unwrap_or_default())
+        .bind(request.user_id)
+        .bind(request.enabled.unwrap_or(true))
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.db_pool)
+        .await
+    }
+
+    pub async fn delete_schedule(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM scrape_schedules WHERE id = $1")
+            .bind(id)
+            .execute(&self.db_pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Claim the next due schedule and push its next run forward so other scraper replicas skip it
+    async fn claim_due_schedule(&self) -> Result<Option<ScrapeSchedule>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let schedule = sqlx::query_as::<_, ScrapeSchedule>(
+            "SELECT * FROM scrape_schedules WHERE enabled AND next_run_at <= NOW() ORDER BY next_run_at ASC LIMIT 1 FOR UPDATE SKIP LOCKED"
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if let Some(schedule) = &schedule {
+            let now = Utc::now();
+            sqlx::query("UPDATE scrape_schedules SET last_run_at = $1, next_run_at = $2 WHERE id = $3")
+                .bind(now)
+                .bind(now + Duration::minutes(schedule.interval_minutes as i64))
+                .bind(schedule.id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(schedule)
+    }
+
+    // Record a video ID for a schedule, returning false if it was already seen
+    async fn mark_seen(&self, schedule_id: i32, youtube_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO scrape_schedule_videos (schedule_id, youtube_id, first_seen_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
+        )
+        .bind(schedule_id)
+        .bind(youtube_id)
+        .bind(Utc::now())
+        .execute(&self.db_pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Forget a video ID that couldn't be queued, so the next run tries it again
+    async fn unmark_seen(&self, schedule_id: i32, youtube_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM scrape_schedule_videos WHERE schedule_id = $1 AND youtube_id = $2")
+            .bind(schedule_id)
+            .bind(youtube_id)
+            .execute(&self.db_pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_job(&self, schedule_id: i32, youtube_id: &str, job_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE scrape_schedule_videos SET job_id = $1 WHERE schedule_id = $2 AND youtube_id = $3")
+            .bind(job_id)
+            .bind(schedule_id)
+            .bind(youtube_id)
+            .execute(&self.db_pool)
+            .await?;
+        Ok(())
+    }
+
+    // Run a schedule once: list the source and enqueue videos that haven't been seen before
+    pub async fn run_schedule(&self, schedule: &ScrapeSchedule, job_queue: &JobQueue, scraper: &YoutubeScraper) -> Result<usize, ScrapeError> {
+        // Schedules created before user_id was required have nobody to own their videos
+        if schedule.user_id.is_none() {
+            return Err(ScrapeError::MissingUser);
+        }
+        let video_ids = scraper.list_playlist_video_ids(&schedule.source_url).await?;
+        let mut enqueued = 0;
+
+        for youtube_id in video_ids {
+            let is_new = self.mark_seen(schedule.id, &youtube_id).await?;
+            if !is_new {
+                continue;
+            }
+
+            let queued = job_queue.add_job(ScrapeRequest {
+                youtube_url: format!("https://www.youtube.com/watch?v={}", youtube_id),
+                title: None,
+                description: None,
+                tags: schedule.tags.clone().filter(|tags| !tags.is_empty()),
+                user_id: schedule.user_id,
+            }).await;
+            let job_id = match queued {
+                Ok(job_id) => job_id,
+                // Leave the rest for the next run, once some of the queued jobs have finished
+                Err(e @ (EnqueueError::UserLimitReached { .. } | EnqueueError::QueueFull { .. })) => {
+                    info!("Schedule {} stopped after {} videos: {}", schedule.id, enqueued, e);
+                    self.unmark_seen(schedule.id, &youtube_id).await?;
+                    break;
+                }
+                Err(EnqueueError::MissingUser) => {
+                    self.unmark_seen(schedule.id, &youtube_id).await?;
+                    return Err(ScrapeError::MissingUser);
+                }
+                Err(EnqueueError::Db(e)) => {
+                    self.unmark_seen(schedule.id, &youtube_id).await?;
+                    return Err(e.into());
+                }
+            };
+
+            if let Err(e) = self.record_job(schedule.id, &youtube_id, &job_id).await {
+                error!("Failed to record job {} for schedule {}: {}", job_id, schedule.id, e);
+            }
+            enqueued += 1;
+        }
+
+        Ok(enqueued)
+    }
+}
+
+pub async fn start_scheduler(scheduler: Arc<Scheduler>, job_queue: Arc<JobQueue>, scraper: YoutubeScraper) {
+    info!("Starting scrape scheduler");
+
+    loop {
+        match scheduler.claim_due_schedule().await {
+            Ok(Some(schedule)) => {
+                info!("Running scrape schedule {} ({})", schedule.id, schedule.source_url);
+                match scheduler.run_schedule(&schedule, &job_queue, &scraper).await {
+                    Ok(enqueued) => info!("Schedule {} enqueued {} new videos", schedule.id, enqueued),
+                    Err(e) => error!("Schedule {} failed: {}", schedule.id, e),
+                }
+                // Check for other due schedules straight away
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to claim due schedule: {}", e),
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    }
+}
@@ -3,47 +3,41 @@ use std::process::Command;
 use log::{info, error};
 use url::Url;
 use uuid::Uuid;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_s3::primitives::ByteStream;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
-use crate::models::Video as DbVideo;
-use reqwest;
+use videostreaming_models::Video as DbVideo;
+use videostreaming_models::{moderation, webhook_events};
+use crate::scratch::ScratchDir;
+use crate::error::ScrapeError;
+use crate::limits;
+
+pub use videostreaming_models::scrape::{
+    BatchItemResult, BatchItemStatus, BatchScrapeRequest, BatchScrapeResponse, ScrapeRequest, ScrapeResponse,
+    SearchRequest, SearchResponse,
+};
 
 pub struct YoutubeScraper {
     db_pool: PgPool,
     s3_client: S3Client,
     cookies_file: Option<String>,
+    scratch: ScratchDir,
+    // yt-dlp --limit-rate for downloads, from SCRAPER_LIMIT_RATE
+    limit_rate: Option<String>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ScrapeRequest {
-    pub youtube_url: String,
-    pub title: Option<String>,
-    pub description: Option<String>,
-    pub tags: Option<Vec<String>>,
-    pub user_id: Option<i32>,
-}
-
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct SearchRequest {
-    pub query: String,
-    pub max_results: Option<i32>,
-    pub user_id: Option<i32>,
-}
-
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct SearchResponse {
-    pub job_ids: Vec<String>,
-}
-
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ScrapeResponse {
-    pub video_id: i32,
-    pub title: String,
-    pub s3_key: String,
-    pub thumbnail_url: Option<String>,
+// Columns for a newly scraped video row
+struct NewVideo<'a> {
+    title: &'a str,
+    description: Option<&'a str>,
+    s3_key: &'a str,
+    thumbnail_url: Option<&'a str>,
+    uploaded_by: i32,
+    tags: &'a [String],
+    sha256: &'a str,
 }
 
 impl YoutubeScraper {
@@ -52,6 +46,8 @@ impl YoutubeScraper {
             db_pool,
             s3_client,
             cookies_file: None,
+            scratch: ScratchDir::from_env(),
+            limit_rate: limits::limit_rate(),
         }
     }
 
@@ -63,9 +59,7 @@ impl YoutubeScraper {
         info!("Searching YouTube for: {}", query);
         
         // Encode the query for URL
-        let encoded_query = match urlencoding::encode(query).to_string() {
-            s => s,
-        };
+        let encoded_query = urlencoding::encode(query).to_string();
         
         info!("Encoded query: {}", encoded_query);
         
@@ -146,41 +140,44 @@ impl YoutubeScraper {
         Ok(video_urls)
     }
 
-    pub async fn scrape_video(&self, request: ScrapeRequest) -> Result<ScrapeResponse, String> {
-        // Parse and validate YouTube URL
-        let youtube_url = match Url::parse(&request.youtube_url) {
-            Ok(url) => url,
-            Err(_) => return Err("Invalid YouTube URL".to_string()),
-        };
-
-        // Extract video ID from URL
-        let video_id = match self.extract_youtube_id(&youtube_url) {
-            Some(id) => id,
-            None => return Err("Could not extract YouTube video ID".to_string()),
-        };
+    pub async fn scrape_video(&self, request: ScrapeRequest) -> Result<ScrapeResponse, ScrapeError> {
+        // Parse and validate YouTube URL, extracting the video ID
+        let video_id = validate_youtube_url(&request.youtube_url)?;
+        // Every scraped video goes into its requester's library
+        let user_id = request.user_id.ok_or(ScrapeError::MissingUser)?;
 
         info!("Downloading YouTube video with ID: {}", video_id);
 
         // Download video using yt-dlp
-        let video = match self.download_video(&video_id).await {
-            Ok(v) => v,
-            Err(e) => return Err(format!("Failed to download video: {}", e)),
-        };
+        let video = self.download_video(&video_id).await?;
+
+        // A user scraping a video they already have gets that video back instead of a second
+        // copy. Other users' copies are never matched, so their ids and keys stay private.
+        let sha256 = hex::encode(Sha256::digest(&video.0));
+        if let Some(existing) = self.find_by_checksum(&sha256, user_id).await? {
+            info!("Downloaded video matches user {}'s video {}, skipping upload", user_id, existing.id);
+            return Ok(ScrapeResponse {
+                video_id: existing.id,
+                title: existing.title,
+                s3_key: existing.s3_key,
+                thumbnail_url: existing.thumbnail_url,
+                sha256: existing.sha256,
+                duplicate: true,
+            });
+        }
 
         // Generate a unique S3 key for the video
         let s3_key = format!("videos/{}.mp4", Uuid::new_v4());
         
         // Upload video to MinIO
-        match self.upload_to_minio(&video.0, &s3_key).await {
-            Ok(_) => info!("Video uploaded to MinIO successfully"),
-            Err(e) => return Err(format!("Failed to upload video to MinIO: {}", e)),
-        }
+        self.upload_to_minio(&video.0, &s3_key).await?;
+        info!("Video uploaded to MinIO successfully");
 
         // Upload thumbnail to MinIO if available
         let thumbnail_url = match self.upload_thumbnail(&video_id).await {
             Ok(url) => Some(url),
             Err(e) => {
-                info!("Failed to upload thumbnail: {}", e);
+                info!("{}", e);
                 None
             }
         };
@@ -189,68 +186,105 @@ impl YoutubeScraper {
         let title = request.title.unwrap_or_else(|| video.1.clone());
         let description = request.description.or(Some(format!("Scraped from YouTube: {}", request.youtube_url)));
         let tags = request.tags.unwrap_or_else(|| vec!["youtube".to_string()]);
-        let user_id = request.user_id;
 
         // Insert video metadata into database
-        let db_video = match self.insert_into_database(&title, description.as_deref(), &s3_key, thumbnail_url.as_deref(), user_id, &tags).await {
-            Ok(v) => v,
-            Err(e) => return Err(format!("Failed to insert video into database: {}", e)),
-        };
+        let db_video = self.insert_into_database(NewVideo {
+            title: &title,
+            description: description.as_deref(),
+            s3_key: &s3_key,
+            thumbnail_url: thumbnail_url.as_deref(),
+            uploaded_by: user_id,
+            tags: &tags,
+            sha256: &sha256,
+        }).await?;
+
+        // Subscribers hear about scraped videos like any other; the backend sends the delivery
+        if let Err(e) = webhook_events::video_created(&self.db_pool, &db_video).await {
+            error!("Failed to queue video.created for video {}: {}", db_video.id, e);
+        }
 
         Ok(ScrapeResponse {
             video_id: db_video.id,
             title: db_video.title,
             s3_key: db_video.s3_key,
             thumbnail_url: db_video.thumbnail_url,
+            sha256: db_video.sha256,
+            duplicate: false,
         })
     }
 
-    fn extract_youtube_id(&self, url: &Url) -> Option<String> {
-        // Extract video ID from various YouTube URL formats
-        if url.host_str() == Some("youtu.be") {
-            // Short URL format: https://youtu.be/VIDEO_ID
-            return url.path_segments()?.next().map(|s| s.to_string());
-        } else if url.host_str() == Some("youtube.com") || url.host_str() == Some("www.youtube.com") {
-            // Standard URL format: https://www.youtube.com/watch?v=VIDEO_ID
-            return url.query_pairs()
-                .find(|(key, _)| key == "v")
-                .map(|(_, value)| value.to_string());
+    // List the video IDs of a channel or playlist without downloading anything
+    pub async fn list_playlist_video_ids(&self, source_url: &str) -> Result<Vec<String>, ScrapeError> {
+        let mut cmd = Command::new("/opt/venv/bin/yt-dlp");
+        cmd.args(["--flat-playlist", "--print", "id"]);
+
+        if let Some(cookies_file) = &self.cookies_file {
+            cmd.args(["--cookies", cookies_file]);
+        }
+
+        cmd.arg(source_url);
+
+        let output = cmd.output()
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: format!("Failed to execute yt-dlp: {}", e) })?;
+
+        if !output.status.success() {
+            return Err(ScrapeError::DownloadFailed {
+                exit_code: output.status.code(),
+                stderr: stderr_tail(&output.stderr),
+            });
         }
-        None
+
+        let video_ids = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect::<Vec<_>>();
+
+        info!("Found {} videos in {}", video_ids.len(), source_url);
+        Ok(video_ids)
     }
 
-    async fn download_video(&self, video_id: &str) -> Result<(Vec<u8>, String), String> {
-        // Create a temporary file path
-        let output_path = format!("/tmp/videos/{}.mp4", Uuid::new_v4());
+    async fn download_video(&self, video_id: &str) -> Result<(Vec<u8>, String), ScrapeError> {
+        // Reserve scratch space; the guard removes the download (and any partial files) on every exit path
+        let scratch = self.scratch.reserve()
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: e })?;
+        let output_path = scratch.file("video.mp4");
+        let output_path = output_path.to_string_lossy();
         
         // Build yt-dlp command with optional cookies
         let mut cmd = Command::new("/opt/venv/bin/yt-dlp");
-        cmd.args(&[
+        cmd.args([
             "-f", "best", // Get the best quality
             "-o", &output_path,
+            "--max-filesize", &scratch.max_file_bytes().to_string(),
         ]);
+        if let Some(limit_rate) = &self.limit_rate {
+            cmd.args(["--limit-rate", limit_rate]);
+        }
         
         // Add cookies file if provided (copy to writable location first)
         if let Some(cookies_file) = &self.cookies_file {
             info!("Using cookies file: {}", cookies_file);
             
             // Copy cookies to a writable location to avoid read-only filesystem issues
-            let writable_cookies = "/tmp/writable_cookies.txt";
-            if let Err(e) = std::fs::copy(cookies_file, writable_cookies) {
+            let writable_cookies = scratch.file("cookies.txt");
+            if let Err(e) = std::fs::copy(cookies_file, &writable_cookies) {
                 info!("Failed to copy cookies file, proceeding without cookies: {}", e);
             } else {
-                cmd.args(&["--cookies", writable_cookies]);
+                cmd.arg("--cookies").arg(&writable_cookies);
             }
         }
         
-        cmd.arg(&format!("https://www.youtube.com/watch?v={}", video_id));
+        cmd.arg(format!("https://www.youtube.com/watch?v={}", video_id));
         
-        // Run yt-dlp to download the video
-        let status = cmd.status()
-            .map_err(|e| format!("Failed to execute yt-dlp: {}", e))?;
+        // Run yt-dlp to download the video, keeping stderr so failures can be classified
+        let output = cmd.output()
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: format!("Failed to execute yt-dlp: {}", e) })?;
         
-        if !status.success() {
-            return Err(format!("yt-dlp failed with exit code: {:?}", status.code()));
+        if !output.status.success() {
+            let stderr = stderr_tail(&output.stderr);
+            error!("yt-dlp failed for {}: {}", video_id, stderr);
+            return Err(ScrapeError::DownloadFailed { exit_code: output.status.code(), stderr });
         }
         
         // Get the video title with cookies if available
@@ -259,33 +293,39 @@ impl YoutubeScraper {
         
         // Add cookies file for title retrieval too
         if let Some(cookies_file) = &self.cookies_file {
-            title_cmd.args(&["--cookies", cookies_file]);
+            title_cmd.args(["--cookies", cookies_file]);
         }
         
-        title_cmd.arg(&format!("https://www.youtube.com/watch?v={}", video_id));
+        title_cmd.arg(format!("https://www.youtube.com/watch?v={}", video_id));
         
         let output = title_cmd.output()
-            .map_err(|e| format!("Failed to get video title: {}", e))?;
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: format!("Failed to get video title: {}", e) })?;
         
         let title = String::from_utf8_lossy(&output.stdout).trim().to_string();
         
+        // yt-dlp exits successfully but skips the download when --max-filesize is exceeded
+        if !std::path::Path::new(output_path.as_ref()).exists() {
+            return Err(ScrapeError::DownloadFailed {
+                exit_code: None,
+                stderr: format!(
+                    "yt-dlp did not produce a file; the video may be larger than the {} MB scratch limit",
+                    scratch.max_file_bytes() / (1024 * 1024)
+                ),
+            });
+        }
+        
         // Read the video file into memory
-        let mut file = File::open(&output_path).await
-            .map_err(|e| format!("Failed to open downloaded video file: {}", e))?;
+        let mut file = File::open(output_path.as_ref()).await
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: format!("Failed to open downloaded video file: {}", e) })?;
         
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer).await
-            .map_err(|e| format!("Failed to read video file: {}", e))?;
-        
-        // Clean up the downloaded file
-        if let Err(e) = tokio::fs::remove_file(&output_path).await {
-            info!("Failed to remove temporary file {}: {}", output_path, e);
-        }
+            .map_err(|e| ScrapeError::DownloadFailed { exit_code: None, stderr: format!("Failed to read video file: {}", e) })?;
         
         Ok((buffer, title))
     }
 
-    async fn upload_to_minio(&self, video_data: &[u8], s3_key: &str) -> Result<(), String> {
+    async fn upload_to_minio(&self, video_data: &[u8], s3_key: &str) -> Result<(), ScrapeError> {
         let bucket_name = env::var("S3_BUCKET")
             .or_else(|_| env::var("MINIO_BUCKET"))
             .unwrap_or_else(|_| "videos".to_string());
@@ -309,27 +349,27 @@ impl YoutubeScraper {
             .await
         {
             Ok(_) => Ok(()),
-            Err(e) => Err(format!("Failed to upload to S3: {}", e)),
+            Err(e) => Err(ScrapeError::UploadFailed { message: e.to_string() }),
         }
     }
 
-    async fn upload_thumbnail(&self, video_id: &str) -> Result<String, String> {
+    async fn upload_thumbnail(&self, video_id: &str) -> Result<String, ScrapeError> {
         // Construct the YouTube thumbnail URL
         let thumbnail_url = format!("https://img.youtube.com/vi/{}/maxresdefault.jpg", video_id);
         
         // Download the thumbnail
         let response = match reqwest::get(&thumbnail_url).await {
             Ok(resp) => resp,
-            Err(e) => return Err(format!("Failed to download thumbnail: {}", e)),
+            Err(e) => return Err(ScrapeError::ThumbnailFailed { message: format!("Failed to download thumbnail: {}", e) }),
         };
         
         if !response.status().is_success() {
-            return Err(format!("Failed to download thumbnail: HTTP status {}", response.status()));
+            return Err(ScrapeError::ThumbnailFailed { message: format!("Failed to download thumbnail: HTTP status {}", response.status()) });
         }
         
         let thumbnail_data = match response.bytes().await {
             Ok(bytes) => bytes,
-            Err(e) => return Err(format!("Failed to read thumbnail data: {}", e)),
+            Err(e) => return Err(ScrapeError::ThumbnailFailed { message: format!("Failed to read thumbnail data: {}", e) }),
         };
         
         // Generate a unique S3 key for the thumbnail
@@ -354,35 +394,105 @@ impl YoutubeScraper {
             .await
         {
             Ok(_) => Ok(s3_key),
-            Err(e) => Err(format!("Failed to upload thumbnail to S3: {}", e)),
+            Err(e) => Err(ScrapeError::ThumbnailFailed { message: format!("Failed to upload thumbnail to S3: {}", e) }),
         }
     }
 
-    async fn insert_into_database(
-        &self,
-        title: &str,
-        description: Option<&str>,
-        s3_key: &str,
-        thumbnail_url: Option<&str>,
-        uploaded_by: Option<i32>,
-        tags: &[String],
-    ) -> Result<DbVideo, sqlx::Error> {
-        // Insert the video metadata into the database
+    async fn insert_into_database(&self, video: NewVideo<'_>) -> Result<DbVideo, sqlx::Error> {
+        // Insert the video metadata into the database. With a classifier configured it stays
+        // pending until the backend's moderation sweep has it checked.
         sqlx::query_as::<_, DbVideo>(
             r#"
-            INSERT INTO videos (title, description, s3_key, thumbnail_url, uploaded_by, upload_date, tags)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            RETURNING id, title, description, s3_key, thumbnail_url, uploaded_by, upload_date, tags, view_count
+            INSERT INTO videos (title, description, s3_key, thumbnail_url, uploaded_by, upload_date, tags, sha256, moderation_status)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
             "#
         )
-        .bind(title)
-        .bind(description)
-        .bind(s3_key)
-        .bind(thumbnail_url)
-        .bind(uploaded_by)
+        .bind(video.title)
+        .bind(video.description)
+        .bind(video.s3_key)
+        .bind(video.thumbnail_url)
+        .bind(video.uploaded_by)
         .bind(chrono::Utc::now().naive_utc())
-        .bind(tags)
+        .bind(video.tags)
+        .bind(video.sha256)
+        .bind(moderation::initial_status())
         .fetch_one(&self.db_pool)
         .await
     }
+
+    async fn find_by_checksum(&self, sha256: &str, user_id: i32) -> Result<Option<DbVideo>, sqlx::Error> {
+        sqlx::query_as::<_, DbVideo>(
+            r#"
+            SELECT *
+            FROM videos
+            WHERE sha256 = $1 AND uploaded_by = $2 AND deleted_at IS NULL
+            LIMIT 1
+            "#
+        )
+        .bind(sha256)
+        .bind(user_id)
+        .fetch_optional(&self.db_pool)
+        .await
+    }
+}
+
+pub fn extract_youtube_id(url: &Url) -> Option<String> {
+    // Extract video ID from various YouTube URL formats
+    if url.host_str() == Some("youtu.be") {
+        // Short URL format: https://youtu.be/VIDEO_ID
+        return url.path_segments()?.next().map(|s| s.to_string());
+    } else if url.host_str() == Some("youtube.com") || url.host_str() == Some("www.youtube.com") {
+        // Standard URL format: https://www.youtube.com/watch?v=VIDEO_ID
+        return url.query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, value)| value.to_string());
+    }
+    None
+}
+
+// Validate a submitted URL and return the YouTube video ID it points to
+pub fn validate_youtube_url(raw_url: &str) -> Result<String, ScrapeError> {
+    let url = Url::parse(raw_url.trim()).map_err(|_| ScrapeError::invalid_url("Invalid YouTube URL"))?;
+
+    match extract_youtube_id(&url) {
+        Some(id) if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => Ok(id),
+        _ => Err(ScrapeError::invalid_url("Could not extract YouTube video ID")),
+    }
+}
+
+// Validate that a URL points at a YouTube channel or playlist that yt-dlp can enumerate
+pub fn validate_channel_or_playlist_url(raw_url: &str) -> Result<String, ScrapeError> {
+    let url = Url::parse(raw_url.trim()).map_err(|_| ScrapeError::invalid_url("Invalid YouTube URL"))?;
+
+    if url.host_str() != Some("youtube.com") && url.host_str() != Some("www.youtube.com") {
+        return Err(ScrapeError::invalid_url("Only youtube.com channel and playlist URLs can be scheduled"));
+    }
+
+    let path = url.path();
+    let is_playlist = path == "/playlist" && url.query_pairs().any(|(key, _)| key == "list");
+    let is_channel = path.starts_with("/@")
+        || path.starts_with("/channel/")
+        || path.starts_with("/c/")
+        || path.starts_with("/user/");
+
+    if is_playlist || is_channel {
+        Ok(url.to_string())
+    } else {
+        Err(ScrapeError::invalid_url("URL must be a YouTube channel or playlist"))
+    }
+}
+
+// Keep the end of yt-dlp's stderr, which is where the actual error is reported
+fn stderr_tail(stderr: &[u8]) -> String {
+    const MAX_STDERR_CHARS: usize = 2000;
+
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim();
+    let char_count = stderr.chars().count();
+    if char_count <= MAX_STDERR_CHARS {
+        stderr.to_string()
+    } else {
+        stderr.chars().skip(char_count - MAX_STDERR_CHARS).collect()
+    }
 }
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+use log::{info, error};
+use uuid::Uuid;
+
+use crate::limits;
+
+// Leftover job directories older than this are removed on startup
+const STALE_AFTER: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Job directories are named with this prefix, so cleanup leaves anything else in the root alone
+const JOB_DIR_PREFIX: &str = "scraper-job-";
+
+// Bytes promised to each download in progress, by job directory. Shared by every ScratchDir in the
+// process so concurrent downloads can't be promised the same space.
+static RESERVATIONS: LazyLock<Mutex<HashMap<PathBuf, u64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+// Scratch space for downloads. Configured via SCRAPER_SCRATCH_DIR, SCRAPER_SCRATCH_MAX_MB
+// (total size the directory may grow to) and SCRAPER_MIN_FREE_MB (free disk space to keep).
+#[derive(Debug, Clone)]
+pub struct ScratchDir {
+    root: PathBuf,
+    max_bytes: u64,
+    min_free_bytes: u64,
+}
+
+impl ScratchDir {
+    pub fn from_env() -> Self {
+        let root = env::var("SCRAPER_SCRATCH_DIR").unwrap_or_else(|_| "/tmp/videos".to_string());
+        let max_mb = env::var("SCRAPER_SCRATCH_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10 * 1024);
+        let min_free_mb = env::var("SCRAPER_MIN_FREE_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1024);
+
+        Self {
+            root: PathBuf::from(root),
+            max_bytes: max_mb * BYTES_PER_MB,
+            min_free_bytes: min_free_mb * BYTES_PER_MB,
+        }
+    }
+
+    // Create the scratch directory and remove job directories left behind by a crashed run
+    pub fn prepare(&self) -> io::Result<()> {
+        std::fs::create_dir_all(&self.root)?;
+
+        let cutoff = SystemTime::now() - STALE_AFTER;
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_name().to_string_lossy().starts_with(JOB_DIR_PREFIX) || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified());
+            if matches!(modified, Ok(modified) if modified > cutoff) {
+                continue;
+            }
+
+            let path = entry.path();
+            match std::fs::remove_dir_all(&path) {
+                Ok(_) => info!("Removed stale scratch directory {}", path.display()),
+                Err(e) => error!("Failed to remove stale scratch directory {}: {}", path.display(), e),
+            }
+        }
+
+        info!("Scratch directory ready at {}", self.root.display());
+        Ok(())
+    }
+
+    // Check the size limit and free disk space, then create a per-download directory. The space left
+    // is shared between the download slots still free, and what one download is promised isn't
+    // offered to another until its guard is dropped, which removes the directory and everything in it.
+    pub fn reserve(&self) -> Result<ScratchGuard, String> {
+        std::fs::create_dir_all(&self.root)
+            .map_err(|e| format!("Failed to create scratch directory: {}", e))?;
+
+        let mut reservations = RESERVATIONS.lock().unwrap();
+        // Files of downloads in progress count against their reservation, not on top of it
+        let (reserved, written) = reservations.iter().fold((0u64, 0u64), |(reserved, written), (path, bytes)| {
+            (reserved + bytes, written + dir_size(path).unwrap_or(0).min(*bytes))
+        });
+
+        let on_disk = dir_size(&self.root)
+            .map_err(|e| format!("Failed to measure scratch directory: {}", e))?;
+        let used = on_disk.saturating_sub(written) + reserved;
+        if used >= self.max_bytes {
+            return Err(format!(
+                "Scratch directory is full ({} MB used or reserved of {} MB)",
+                used / BYTES_PER_MB,
+                self.max_bytes / BYTES_PER_MB
+            ));
+        }
+
+        let available = available_space(&self.root)
+            .map_err(|e| format!("Failed to check free disk space: {}", e))?
+            .saturating_sub(reserved - written);
+        if available <= self.min_free_bytes {
+            return Err(format!(
+                "Not enough free disk space ({} MB available, {} MB required)",
+                available / BYTES_PER_MB,
+                self.min_free_bytes / BYTES_PER_MB
+            ));
+        }
+
+        let path = self.root.join(format!("{}{}", JOB_DIR_PREFIX, Uuid::new_v4()));
+        std::fs::create_dir(&path)
+            .map_err(|e| format!("Failed to create scratch directory: {}", e))?;
+
+        let free_slots = limits::max_concurrent_downloads().saturating_sub(reservations.len()).max(1) as u64;
+        let max_file_bytes = (self.max_bytes - used).min(available - self.min_free_bytes) / free_slots;
+        reservations.insert(path.clone(), max_file_bytes);
+
+        Ok(ScratchGuard { path, max_file_bytes })
+    }
+}
+
+#[derive(Debug)]
+pub struct ScratchGuard {
+    path: PathBuf,
+    max_file_bytes: u64,
+}
+
+impl ScratchGuard {
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    // Largest file that can be written without exceeding the size limit or the free-space reserve
+    pub fn max_file_bytes(&self) -> u64 {
+        self.max_file_bytes
+    }
+}
+
+impl Drop for ScratchGuard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                error!("Failed to clean up scratch directory {}: {}", self.path.display(), e);
+            }
+        }
+        RESERVATIONS.lock().unwrap().remove(&self.path);
+    }
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
@@ -0,0 +1,134 @@
+use std::env;
+use std::time::Duration;
+use serde::Serialize;
+use log::{info, warn, error};
+use sqlx::{PgPool, FromRow};
+use chrono::{Utc, DateTime};
+use uuid::Uuid;
+
+// Each scraper replica runs one worker. Workers heartbeat every SCRAPER_HEARTBEAT_SECS, and a job
+// whose worker hasn't for SCRAPER_HEARTBEAT_TIMEOUT_SECS is queued again for another replica.
+
+// Workers that stopped heartbeating this long ago are dropped from GET /api/workers
+const FORGET_AFTER_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WorkerStatus {
+    pub worker_id: String,
+    pub hostname: String,
+    pub started_at: DateTime<Utc>,
+    pub heartbeat_at: DateTime<Utc>,
+    pub alive: bool,
+    pub current_job_ids: Vec<String>,
+}
+
+fn secs_from_env(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(default)
+}
+
+pub fn heartbeat_interval() -> Duration {
+    Duration::from_secs(secs_from_env("SCRAPER_HEARTBEAT_SECS", 15))
+}
+
+// Several missed heartbeats, so a slow database doesn't requeue jobs that are still running
+pub fn heartbeat_timeout() -> Duration {
+    Duration::from_secs(secs_from_env("SCRAPER_HEARTBEAT_TIMEOUT_SECS", 120))
+}
+
+pub fn hostname() -> String {
+    env::var("HOSTNAME").unwrap_or_else(|_| "scraper".to_string())
+}
+
+// e.g. "scraper-5d8f7-3fa2b1c0": the host plus a suffix, as a restarted container keeps its hostname
+pub fn new_worker_id() -> String {
+    format!("{}-{}", hostname(), &Uuid::new_v4().simple().to_string()[..8])
+}
+
+pub async fn register(db_pool: &PgPool, worker_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO scraper_workers (worker_id, hostname, started_at, heartbeat_at) VALUES ($1, $2, $3, $3)
+         ON CONFLICT (worker_id) DO UPDATE SET heartbeat_at = EXCLUDED.heartbeat_at"
+    )
+    .bind(worker_id)
+    .bind(hostname())
+    .bind(Utc::now())
+    .execute(db_pool)
+    .await?;
+    Ok(())
+}
+
+// Show the worker and the job it is running are alive
+pub async fn heartbeat(db_pool: &PgPool, worker_id: &str) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query("UPDATE scraper_workers SET heartbeat_at = $2 WHERE worker_id = $1")
+        .bind(worker_id)
+        .bind(now)
+        .execute(db_pool)
+        .await?;
+    sqlx::query("UPDATE jobs SET heartbeat_at = $2 WHERE worker_id = $1 AND status = 'processing'")
+        .bind(worker_id)
+        .bind(now)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+// Queue jobs again whose worker stopped heartbeating, returning their ids. Jobs started before
+// heartbeats existed go by when they were last updated.
+pub async fn requeue_expired_jobs(db_pool: &PgPool, timeout: Duration) -> Result<Vec<String>, sqlx::Error> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(timeout).unwrap_or_else(|_| chrono::Duration::minutes(2));
+    sqlx::query_scalar(
+        "UPDATE jobs SET status = 'queued', worker_id = NULL, heartbeat_at = NULL, updated_at = NOW()
+         WHERE status = 'processing' AND COALESCE(heartbeat_at, updated_at) < $1
+         RETURNING job_id"
+    )
+    .bind(cutoff)
+    .fetch_all(db_pool)
+    .await
+}
+
+pub async fn list_workers(db_pool: &PgPool, timeout: Duration) -> Result<Vec<WorkerStatus>, sqlx::Error> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(timeout).unwrap_or_else(|_| chrono::Duration::minutes(2));
+    sqlx::query_as::<_, WorkerStatus>(
+        "SELECT w.worker_id, w.hostname, w.started_at, w.heartbeat_at, w.heartbeat_at >= $1 AS alive,
+                ARRAY(SELECT j.job_id FROM jobs j WHERE j.worker_id = w.worker_id AND j.status = 'processing' ORDER BY j.updated_at) AS current_job_ids
+         FROM scraper_workers w
+         ORDER BY w.started_at ASC"
+    )
+    .bind(cutoff)
+    .fetch_all(db_pool)
+    .await
+}
+
+async fn forget_stopped_workers(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM scraper_workers WHERE heartbeat_at < $1")
+        .bind(Utc::now() - chrono::Duration::hours(FORGET_AFTER_HOURS))
+        .execute(db_pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// Heartbeat for the worker until the process exits, requeueing the jobs of workers that stopped
+pub async fn run_heartbeat(db_pool: PgPool, worker_id: String) {
+    let interval = heartbeat_interval();
+    info!("Starting heartbeat for worker {} (interval: {:?})", worker_id, interval);
+
+    loop {
+        if let Err(e) = heartbeat(&db_pool, &worker_id).await {
+            error!("Failed to record heartbeat for worker {}: {}", worker_id, e);
+        }
+        match requeue_expired_jobs(&db_pool, heartbeat_timeout()).await {
+            Ok(job_ids) if !job_ids.is_empty() => warn!("Requeued jobs of stopped workers: {:?}", job_ids),
+            Ok(_) => {}
+            Err(e) => error!("Failed to requeue jobs of stopped workers: {}", e),
+        }
+        if let Err(e) = forget_stopped_workers(&db_pool).await {
+            error!("Failed to remove stopped workers: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}